@@ -0,0 +1,73 @@
+//! Encode and decode the batch verification of multiple full wrapped proofs
+//! through the `processQueryBatch` function of the Solidity query verifier
+//! contract.
+
+use crate::gas::{proof_data_token, query_token_from_proof};
+use anyhow::{bail, ensure, Result};
+use ethers::{
+    abi::{Contract, Token},
+    types::U256,
+};
+
+/// The name of the batch verification function in the Solidity verifier
+const PROCESS_QUERY_BATCH_FUN: &str = "processQueryBatch";
+
+/// The query result of each proof in a batch, it's `None` if the proof is
+/// rejected by the verifier contract.
+pub type BatchQueryResults = Vec<Option<Vec<U256>>>;
+
+/// Build the ABI encoded calldata of `processQueryBatch` for the full proofs.
+/// Each proof is the combined bytes returned by `Groth16Prover::prove`, and
+/// its expected query argument is decoded from its plonky2 public inputs.
+/// All the proofs must be generated from the same wrapped circuit, since the
+/// verifier contract checks them against one public input commitment scheme.
+pub fn encode_process_query_batch(contract: &Contract, proofs: &[&[u8]]) -> Result<Vec<u8>> {
+    ensure!(!proofs.is_empty(), "No proofs to verify in the batch");
+    ensure!(
+        proofs.iter().all(|proof| proof.len() == proofs[0].len()),
+        "All the proofs of the batch must have the same length",
+    );
+
+    let data = proofs.iter().map(|proof| proof_data_token(proof)).collect();
+    let queries = proofs
+        .iter()
+        .map(|proof| query_token_from_proof(proof))
+        .collect::<Result<_>>()?;
+
+    let fun = &contract.functions[PROCESS_QUERY_BATCH_FUN][0];
+    let calldata = fun.encode_input(&[Token::Array(data), Token::Array(queries)])?;
+
+    Ok(calldata)
+}
+
+/// Decode the per-proof results from the output of `processQueryBatch`.
+pub fn decode_process_query_batch(contract: &Contract, output: &[u8]) -> Result<BatchQueryResults> {
+    let fun = &contract.functions[PROCESS_QUERY_BATCH_FUN][0];
+    let tokens = fun.decode_output(output)?;
+
+    let (verified, results) = match tokens.as_slice() {
+        [Token::Array(verified), Token::Array(results)] => (verified, results),
+        _ => bail!("Invalid output of processQueryBatch: {tokens:?}"),
+    };
+    ensure!(
+        verified.len() == results.len(),
+        "The numbers of verified flags and results must be equal",
+    );
+
+    verified
+        .iter()
+        .zip(results)
+        .map(|(verified, result)| match (verified, result) {
+            (Token::Bool(true), Token::Array(result)) => result
+                .iter()
+                .map(|u| match u {
+                    Token::Uint(u) => Ok(*u),
+                    _ => bail!("Invalid query result item: {u:?}"),
+                })
+                .collect::<Result<_>>()
+                .map(Some),
+            (Token::Bool(false), _) => Ok(None),
+            _ => bail!("Invalid batch result: {verified:?}, {result:?}"),
+        })
+        .collect()
+}
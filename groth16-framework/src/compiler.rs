@@ -2,11 +2,13 @@
 
 use crate::{
     utils::{
-        serialize_circuit_data, write_file, CIRCUIT_DATA_FILENAME, SOLIDITY_VERIFIER_FILENAME,
+        deserialize_circuit_data, read_file, serialize_circuit_data, write_file,
+        CIRCUIT_DATA_FILENAME, CIRCUIT_FINGERPRINT_FILENAME, SOLIDITY_VERIFIER_FILENAME,
     },
     C, D, F,
 };
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, ensure, Context, Result};
+use mr_plonky2_circuits::api::deserialize_proof;
 use plonky2::plonk::{circuit_data::CircuitData, config::GenericHashOut};
 use plonky2x::backend::{
     circuit::{DefaultParameters, Groth16WrapperParameters},
@@ -31,6 +33,9 @@ pub fn compile_and_generate_assets(
     // reused in proving.
     save_circuit_data(&circuit_data, dst_asset_dir)?;
 
+    // Save the fingerprint of the circuit, to detect stale assets later.
+    save_circuit_fingerprint(&circuit_data, dst_asset_dir)?;
+
     // Create the wrapped circuit.
     let wrapper = WrapCircuit::build_from_raw_circuit(circuit_data);
 
@@ -63,6 +68,55 @@ fn save_circuit_data(circuit_data: &CircuitData<F, C, D>, dst_asset_dir: &str) -
     write_file(file_path, &data)
 }
 
+/// Check if the asset files in the asset dir were generated from the given
+/// circuit, by comparing the fingerprint stored in `circuit_fingerprint.txt`
+/// with the one of the circuit data. It returns an error if the fingerprint
+/// file can't be read, e.g. for assets generated before it was introduced.
+pub fn assets_match(asset_dir: &str, circuit_data: &CircuitData<F, C, D>) -> Result<bool> {
+    let file_path = Path::new(asset_dir).join(CIRCUIT_FINGERPRINT_FILENAME);
+    let stored = read_file(&file_path)
+        .with_context(|| format!("Failed to read the circuit fingerprint {file_path:?}"))?;
+    let stored = String::from_utf8(stored)?;
+
+    Ok(stored.trim() == circuit_fingerprint(circuit_data))
+}
+
+/// Check that the wrapped proof was generated by the circuit the asset files
+/// were generated from, i.e. the circuit expected by the deployed verifier. The
+/// digest of the circuit data stored in `circuit.bin` is first checked against
+/// the fingerprint stored in `circuit_fingerprint.txt`, then the proof is
+/// verified with this circuit data. It's much cheaper than failing in the EVM.
+pub fn verify_circuit_binding(asset_dir: &str, proof: &[u8]) -> Result<()> {
+    let file_path = Path::new(asset_dir).join(CIRCUIT_DATA_FILENAME);
+    let circuit_data = read_file(&file_path)
+        .with_context(|| format!("Failed to read the circuit data {file_path:?}"))?;
+    let circuit_data = deserialize_circuit_data(&circuit_data)?;
+    ensure!(
+        assets_match(asset_dir, &circuit_data)?,
+        "The circuit data doesn't match the circuit fingerprint in {asset_dir}"
+    );
+
+    let proof = deserialize_proof(proof)?;
+    circuit_data
+        .verify(proof)
+        .context("The proof wasn't generated by the circuit of the asset files")
+}
+
+/// Save the fingerprint of the circuit to file `circuit_fingerprint.txt` in
+/// the asset dir.
+fn save_circuit_fingerprint(
+    circuit_data: &CircuitData<F, C, D>,
+    dst_asset_dir: &str,
+) -> Result<()> {
+    let file_path = Path::new(dst_asset_dir).join(CIRCUIT_FINGERPRINT_FILENAME);
+    write_file(file_path, circuit_fingerprint(circuit_data).as_bytes())
+}
+
+/// Get the fingerprint of the circuit, which is the hex encoding of its digest.
+fn circuit_fingerprint(circuit_data: &CircuitData<F, C, D>) -> String {
+    hex::encode(circuit_data.verifier_only.circuit_digest.to_bytes())
+}
+
 /// Get the wrapped circuit digest.
 /// <https://github.com/succinctlabs/succinctx/blob/9df6a9db651507d60ffa2d75eda3fe526d13f90a/plonky2x/core/src/backend/function/mod.rs#L97>
 fn wrapped_circuit_digest(wrapper: &WrapCircuit) -> String {
@@ -122,3 +176,87 @@ fn add_circuit_digest_to_verifier_contract(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mr_plonky2_circuits::api::serialize_proof;
+    use plonky2::{
+        field::types::Field,
+        iop::{
+            target::Target,
+            witness::{PartialWitness, WitnessWrite},
+        },
+        plonk::{circuit_builder::CircuitBuilder, circuit_data::CircuitConfig},
+    };
+    use rand::{thread_rng, Rng};
+    use std::env;
+
+    /// Build a circuit adding 2 values, or multiplying them if `mul` is set.
+    fn build_circuit(mul: bool) -> CircuitData<F, C, D> {
+        build_circuit_with_inputs(mul).0
+    }
+
+    /// Build the circuit of `build_circuit`, also returning its input targets.
+    fn build_circuit_with_inputs(mul: bool) -> (CircuitData<F, C, D>, [Target; 2]) {
+        let mut cb = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let [a, b] = [0; 2].map(|_| cb.add_virtual_target());
+        let c = if mul { cb.mul(a, b) } else { cb.add(a, b) };
+        cb.register_public_input(c);
+
+        (cb.build::<C>(), [a, b])
+    }
+
+    /// Build the circuit of `build_circuit` and generate a serialized proof.
+    fn build_and_prove(mul: bool) -> (CircuitData<F, C, D>, Vec<u8>) {
+        let (circuit_data, targets) = build_circuit_with_inputs(mul);
+        let mut pw = PartialWitness::new();
+        let inputs = thread_rng().gen::<[u32; 2]>().map(F::from_canonical_u32);
+        pw.set_target_arr(&targets, &inputs);
+        let proof = serialize_proof(&circuit_data.prove(pw).unwrap()).unwrap();
+
+        (circuit_data, proof)
+    }
+
+    #[test]
+    fn test_assets_match() {
+        let asset_dir = env::temp_dir().join("groth16_assets_match");
+        let asset_dir = asset_dir.to_str().unwrap();
+        let circuit_data = build_circuit(false);
+
+        // No fingerprint has been saved yet.
+        let _ = std::fs::remove_dir_all(asset_dir);
+        assert!(assets_match(asset_dir, &circuit_data).is_err());
+
+        // The fingerprint saved with the assets matches the same circuit, even
+        // when it's rebuilt.
+        save_circuit_fingerprint(&circuit_data, asset_dir).unwrap();
+        assert!(assets_match(asset_dir, &circuit_data).unwrap());
+        assert!(assets_match(asset_dir, &build_circuit(false)).unwrap());
+
+        // A stale fingerprint is detected when the circuit changes.
+        assert!(!assets_match(asset_dir, &build_circuit(true)).unwrap());
+
+        std::fs::remove_dir_all(asset_dir).unwrap();
+    }
+
+    #[test]
+    fn test_verify_circuit_binding() {
+        let asset_dir = env::temp_dir().join("groth16_circuit_binding");
+        let asset_dir = asset_dir.to_str().unwrap();
+        let _ = std::fs::remove_dir_all(asset_dir);
+
+        let (circuit_data, proof) = build_and_prove(false);
+        save_circuit_data(&circuit_data, asset_dir).unwrap();
+        save_circuit_fingerprint(&circuit_data, asset_dir).unwrap();
+
+        // A proof of the circuit of the asset files is accepted.
+        verify_circuit_binding(asset_dir, &proof).unwrap();
+
+        // A proof of a different wrap circuit is rejected.
+        let (_, other_proof) = build_and_prove(true);
+        assert!(verify_circuit_binding(asset_dir, &other_proof).is_err());
+
+        std::fs::remove_dir_all(asset_dir).unwrap();
+    }
+}
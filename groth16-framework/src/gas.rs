@@ -0,0 +1,426 @@
+//! Estimate the gas used by the Solidity verification of a full wrapped proof
+
+use crate::{
+    utils::{read_file, QUERY_VERIFIER_ABI_FILENAME, QUERY_VERIFIER_CONTRACT_FILENAME},
+    EVMVerifier,
+};
+use anyhow::{ensure, Result};
+use ethers::{
+    abi::{Contract, Token},
+    types::{Address, U256},
+};
+use mrp2_utils::types::CURVE_TARGET_LEN;
+use plonky2::hash::hash_types::NUM_HASH_OUT_ELTS;
+use std::path::Path;
+
+/// The number of bytes of the Groth16 proofs and inputs at the beginning of
+/// the full proof: 8 * U256 (proofs) + 3 * U256 (inputs)
+const GROTH16_BYTES_LEN: usize = 11 * 32;
+
+/// The number of plonky2 public inputs of the wrapped proof apart from the
+/// `L` NFT IDs. It must be consistent with `PI_TOTAL_LEN` in the Solidity
+/// verifier contract.
+const NUM_PLONKY2_PIS_WITHOUT_IDS: usize = 99;
+
+/// Offsets of the query fields in the plonky2 public inputs, they must be
+/// consistent with the offsets defined in the Solidity verifier contract.
+const PI_MIN_BLOCK_NUM_OFFSET: usize = 2;
+const PI_MAX_BLOCK_NUM_OFFSET: usize = 3;
+const PI_CONTRACT_ADDR_OFFSET: usize = 4;
+const PI_USER_ADDR_OFFSET: usize = 9;
+const PI_NFT_IDS_OFFSET: usize = 16;
+
+/// Offsets of the plonky2 public inputs following the `L` NFT IDs, they must
+/// be consistent with the offsets defined in the Solidity verifier contract.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct PiOffsets {
+    pub(crate) block_hash: usize,
+    pub(crate) rewards_rate: usize,
+    pub(crate) erc20_result: usize,
+    pub(crate) query_commitment: usize,
+    pub(crate) decimals: usize,
+    pub(crate) scaled_result: usize,
+    pub(crate) scaled_remainder: usize,
+    pub(crate) contract_digest: usize,
+    pub(crate) slots_commitment: usize,
+    pub(crate) aggregation_mode: usize,
+    pub(crate) max_rewards_rate: usize,
+    pub(crate) threshold: usize,
+    pub(crate) leaf_mode: usize,
+    pub(crate) query_identifier: usize,
+}
+
+impl PiOffsets {
+    /// Compute the offsets for `num_ids` NFT IDs.
+    pub(crate) fn new(num_ids: usize) -> Self {
+        let block_hash = PI_NFT_IDS_OFFSET + num_ids;
+        let rewards_rate = block_hash + PACKED_HASH_LEN;
+        let erc20_result = rewards_rate + PACKED_U256_LEN;
+        let query_commitment = erc20_result + PACKED_U256_LEN;
+        let decimals = query_commitment + PACKED_HASH_LEN;
+        let scaled_result = decimals + 1;
+        let scaled_remainder = scaled_result + PACKED_U256_LEN;
+        let contract_digest = scaled_remainder + PACKED_U256_LEN;
+        let slots_commitment = contract_digest + CURVE_TARGET_LEN;
+        let aggregation_mode = slots_commitment + NUM_HASH_OUT_ELTS;
+        let max_rewards_rate = aggregation_mode + 1;
+        let threshold = max_rewards_rate + PACKED_U256_LEN;
+        let leaf_mode = threshold + PACKED_U256_LEN;
+        let query_identifier = leaf_mode + 1;
+
+        Self {
+            block_hash,
+            rewards_rate,
+            erc20_result,
+            query_commitment,
+            decimals,
+            scaled_result,
+            scaled_remainder,
+            contract_digest,
+            slots_commitment,
+            aggregation_mode,
+            max_rewards_rate,
+            threshold,
+            leaf_mode,
+            query_identifier,
+        }
+    }
+}
+
+/// Number of packed u32 elements of an address and a hash
+const PACKED_ADDRESS_LEN: usize = 5;
+const PACKED_HASH_LEN: usize = 8;
+const PACKED_U256_LEN: usize = 8;
+
+/// Compute the gas used to verify the full wrapped proof through the
+/// `processQuery` function of the Solidity query verifier contract.
+/// The asset dir must include the query verifier contract
+/// `TestGroth16Verifier.sol` and its ABI `TestGroth16Verifier.abi`. The proof
+/// is the combined bytes returned by `Groth16Prover::prove`; the expected
+/// query argument is decoded from its plonky2 public inputs.
+pub fn estimate_verification_gas(asset_dir: &str, proof: &[u8]) -> Result<u64> {
+    let solidity_file_path = Path::new(asset_dir)
+        .join(QUERY_VERIFIER_CONTRACT_FILENAME)
+        .to_string_lossy()
+        .to_string();
+
+    let contract = Contract::load(
+        read_file(Path::new(asset_dir).join(QUERY_VERIFIER_ABI_FILENAME))?.as_slice(),
+    )?;
+
+    let data = proof_data_token(proof);
+    let query = query_token_from_proof(proof)?;
+
+    // Build the ABI encoded data.
+    let fun = &contract.functions["processQuery"][0];
+    let calldata = fun.encode_input(&[data, query])?;
+
+    let verifier = EVMVerifier::new(&solidity_file_path)?;
+    let (gas_used, _) = verifier.verify(calldata)?;
+
+    Ok(gas_used)
+}
+
+/// Encode the full proof to the bytes32 array expected by `processQuery`.
+pub(crate) fn proof_data_token(proof: &[u8]) -> Token {
+    Token::Array(
+        proof
+            .chunks(32)
+            .map(|b| Token::FixedBytes(b.to_vec()))
+            .collect(),
+    )
+}
+
+/// Decode the query struct expected by `processQuery` from the plonky2
+/// public inputs of the full proof.
+pub(crate) fn query_token_from_proof(proof: &[u8]) -> Result<Token> {
+    ensure!(
+        proof.len() > GROTH16_BYTES_LEN && (proof.len() - GROTH16_BYTES_LEN) % 8 == 0,
+        "Invalid length of the full proof: {}",
+        proof.len(),
+    );
+
+    // Each plonky2 public input is serialized as a little-endian u64.
+    let pis: Vec<_> = proof[GROTH16_BYTES_LEN..]
+        .chunks(8)
+        .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+        .collect();
+    ensure!(
+        pis.len() > NUM_PLONKY2_PIS_WITHOUT_IDS,
+        "Invalid number of the plonky2 public inputs: {}",
+        pis.len(),
+    );
+    let num_ids = pis.len() - NUM_PLONKY2_PIS_WITHOUT_IDS;
+
+    // The packed elements are the little-endian u32s of the original bytes.
+    let packed_bytes = |offset: usize, len: usize| -> Vec<u8> {
+        pis[offset..offset + len]
+            .iter()
+            .flat_map(|u| (*u as u32).to_le_bytes())
+            .collect()
+    };
+
+    let offsets = PiOffsets::new(num_ids);
+    let (block_hash_offset, rewards_rate_offset, identifier_offset) = (
+        offsets.block_hash,
+        offsets.rewards_rate,
+        offsets.query_identifier,
+    );
+
+    let u256_at = |offset: usize| {
+        pis[offset..offset + PACKED_U256_LEN]
+            .iter()
+            .rev()
+            .fold(U256::zero(), |acc, u| (acc << 32) + U256::from(*u as u32))
+    };
+
+    Ok(Token::Tuple(vec![
+        Token::Address(Address::from_slice(&packed_bytes(
+            PI_CONTRACT_ADDR_OFFSET,
+            PACKED_ADDRESS_LEN,
+        ))),
+        Token::Uint(pis[PI_MIN_BLOCK_NUM_OFFSET].into()),
+        Token::Address(Address::from_slice(&packed_bytes(
+            PI_USER_ADDR_OFFSET,
+            PACKED_ADDRESS_LEN,
+        ))),
+        Token::Uint(pis[PI_MAX_BLOCK_NUM_OFFSET].into()),
+        // The client address isn't checked in the verifier contract.
+        Token::Address(Address::zero()),
+        Token::Uint(u256_at(rewards_rate_offset)),
+        Token::Uint((pis[identifier_offset] as u8).into()),
+        Token::FixedBytes(packed_bytes(block_hash_offset, PACKED_HASH_LEN)),
+        Token::Uint((pis[offsets.leaf_mode] as u8).into()),
+        Token::Uint(u256_at(offsets.threshold)),
+        Token::Uint(u256_at(offsets.max_rewards_rate)),
+    ]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TEST_DATA_DIR;
+    use mr_plonky2_circuits::{
+        query2,
+        query_erc20::{
+            block::aggregation::AggregationKind,
+            revelation::{
+                self, contract_digest, identifier_for, slots_commitment,
+                RevelationPublicInputsBuilder,
+            },
+        },
+    };
+    use mrp2_utils::utils::convert_u8_slice_to_u32_fields;
+    use plonky2::field::{
+        goldilocks_field::GoldilocksField,
+        types::{Field, PrimeField64},
+    };
+    use rand::{thread_rng, Rng};
+    use std::{collections::HashMap, iter::once};
+
+    const L: usize = 5;
+
+    /// Evaluate the `uint8` and `uint32` constants of a Solidity file, whose
+    /// values are expressions of integers, other constants, `+`, `*` and
+    /// parentheses.
+    fn solidity_constants(path: &str) -> HashMap<String, usize> {
+        fn eval(tokens: &[String], pos: &mut usize, constants: &HashMap<String, usize>) -> usize {
+            let mut sum = 0;
+            loop {
+                let mut product = 1;
+                loop {
+                    let token = &tokens[*pos];
+                    *pos += 1;
+                    product *= if token == "(" {
+                        let value = eval(tokens, pos, constants);
+                        assert_eq!(tokens[*pos], ")");
+                        *pos += 1;
+                        value
+                    } else {
+                        token.parse().unwrap_or_else(|_| constants[token.as_str()])
+                    };
+                    if tokens.get(*pos).map(String::as_str) != Some("*") {
+                        break;
+                    }
+                    *pos += 1;
+                }
+                sum += product;
+                if tokens.get(*pos).map(String::as_str) != Some("+") {
+                    return sum;
+                }
+                *pos += 1;
+            }
+        }
+
+        let content = String::from_utf8(read_file(path).unwrap()).unwrap();
+        let mut constants = HashMap::new();
+        for line in content.lines().map(str::trim) {
+            let Some(declaration) = line
+                .strip_prefix("uint32 constant ")
+                .or(line.strip_prefix("uint8 constant "))
+            else {
+                continue;
+            };
+            let (name, expr) = declaration.trim_end_matches(';').split_once('=').unwrap();
+            let mut tokens = vec![];
+            for c in expr.chars().filter(|c| !c.is_whitespace()) {
+                match c {
+                    '(' | ')' | '+' | '*' => tokens.push(c.to_string()),
+                    _ if tokens
+                        .last()
+                        .is_some_and(|t: &String| !["(", ")", "+", "*"].contains(&t.as_str())) =>
+                    {
+                        tokens.last_mut().unwrap().push(c)
+                    }
+                    _ => tokens.push(c.to_string()),
+                }
+            }
+            let value = eval(&tokens, &mut 0, &constants);
+            constants.insert(name.trim().to_string(), value);
+        }
+
+        constants
+    }
+
+    fn u256_at(pis: &[u64], offset: usize) -> U256 {
+        pis[offset..offset + PACKED_U256_LEN]
+            .iter()
+            .rev()
+            .fold(U256::zero(), |acc, u| (acc << 32) + U256::from(*u as u32))
+    }
+
+    #[test]
+    fn test_pi_layout_matches_solidity_verifier() {
+        let rng = &mut thread_rng();
+        let contract_address = Address::random();
+        let block_hash = rng.gen::<[u8; 32]>();
+        let rewards_rate = U256::from(rng.gen::<u64>());
+        let threshold = U256::from(rng.gen::<u64>());
+        let results = U256::from(rng.gen::<u128>());
+        let decimals = 6;
+        let inputs = RevelationPublicInputsBuilder::<L>::new()
+            .block_number(GoldilocksField::from_canonical_u32(100))
+            .range(GoldilocksField::from_canonical_u32(10))
+            .min_block_number(GoldilocksField::from_canonical_u32(92))
+            .max_block_number(GoldilocksField::from_canonical_u32(98))
+            .smart_contract_address(contract_address)
+            .user_address(Address::random())
+            .mapping_slot(GoldilocksField::from_canonical_u8(3))
+            .mapping_slot_length(GoldilocksField::from_canonical_u8(4))
+            .block_header(block_hash)
+            .rewards_rate(rewards_rate)
+            .query_results(results)
+            .query_commitment(rng.gen())
+            .decimals(decimals)
+            .aggregation_mode(AggregationKind::Max)
+            .max_rewards_rate(rewards_rate + 1)
+            .threshold(threshold)
+            .leaf_mode(1)
+            .build()
+            .unwrap();
+        // the wrapped proof exposes the revelation public inputs followed by
+        // the query identifier
+        let identifier = identifier_for("QueryERC20");
+        let pis = inputs
+            .iter()
+            .map(|f| f.to_canonical_u64())
+            .chain(once(identifier as u64))
+            .collect::<Vec<_>>();
+        assert_eq!(pis.len(), revelation::num_io::<L>());
+        assert_eq!(pis.len(), query2::revelation::num_io::<L>());
+        assert_eq!(pis.len(), L + NUM_PLONKY2_PIS_WITHOUT_IDS);
+
+        // the offsets point to the fields of the revelation public inputs
+        let offsets = PiOffsets::new(L);
+        assert_eq!(
+            pis[offsets.block_hash..offsets.block_hash + PACKED_HASH_LEN],
+            convert_u8_slice_to_u32_fields::<GoldilocksField>(&block_hash)
+                .iter()
+                .map(|f| f.to_canonical_u64())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(u256_at(&pis, offsets.rewards_rate), rewards_rate);
+        assert_eq!(u256_at(&pis, offsets.erc20_result), results);
+        assert_eq!(pis[offsets.decimals], decimals as u64);
+        let scale = U256::exp10(decimals as usize);
+        assert_eq!(u256_at(&pis, offsets.scaled_result), results / scale);
+        assert_eq!(u256_at(&pis, offsets.scaled_remainder), results % scale);
+        let digest = contract_digest(&contract_address);
+        assert_eq!(
+            pis[offsets.contract_digest..offsets.contract_digest + CURVE_TARGET_LEN],
+            digest
+                .x
+                .0
+                .iter()
+                .chain(&digest.y.0)
+                .map(|f| f.to_canonical_u64())
+                .chain([digest.is_inf as u64])
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            pis[offsets.slots_commitment..offsets.slots_commitment + NUM_HASH_OUT_ELTS],
+            slots_commitment(&[3, 4])
+                .elements
+                .map(|f| f.to_canonical_u64())
+        );
+        assert_eq!(pis[offsets.aggregation_mode], AggregationKind::Max as u64);
+        assert_eq!(u256_at(&pis, offsets.max_rewards_rate), rewards_rate + 1);
+        assert_eq!(u256_at(&pis, offsets.threshold), threshold);
+        assert_eq!(pis[offsets.leaf_mode], 1);
+        assert_eq!(offsets.query_identifier, pis.len() - 1);
+
+        let proof = [0; GROTH16_BYTES_LEN]
+            .into_iter()
+            .chain(pis.iter().flat_map(|u| u.to_le_bytes()))
+            .collect::<Vec<_>>();
+        let Token::Tuple(query) = query_token_from_proof(&proof).unwrap() else {
+            panic!("the query must be decoded as a tuple");
+        };
+        assert_eq!(query[0], Token::Address(contract_address));
+        assert_eq!(query[5], Token::Uint(rewards_rate));
+        assert_eq!(query[6], Token::Uint(identifier.into()));
+        assert_eq!(query[7], Token::FixedBytes(block_hash.to_vec()));
+        assert_eq!(query[8], Token::Uint(1.into()));
+        assert_eq!(query[9], Token::Uint(threshold));
+        assert_eq!(query[10], Token::Uint(rewards_rate + 1));
+
+        // the Solidity verifier employs the same layout
+        for file in [
+            QUERY_VERIFIER_CONTRACT_FILENAME,
+            "Groth16VerifierExtensions.sol",
+        ] {
+            let constants = solidity_constants(&format!("{TEST_DATA_DIR}/{file}"));
+            assert_eq!(constants["L"], L, "{file}");
+            assert_eq!(constants["CURVE_POINT_LEN"], CURVE_TARGET_LEN, "{file}");
+            assert_eq!(constants["HASH_OUT_LEN"], NUM_HASH_OUT_ELTS, "{file}");
+            assert_eq!(constants["PI_TOTAL_LEN"], pis.len() * 8, "{file}");
+            assert_eq!(
+                constants["QUERY_IDENTIFIER_ERC20"], identifier as usize,
+                "{file}"
+            );
+            for (name, offset) in [
+                ("PI_MIN_BLOCK_NUM_OFFSET", PI_MIN_BLOCK_NUM_OFFSET),
+                ("PI_MAX_BLOCK_NUM_OFFSET", PI_MAX_BLOCK_NUM_OFFSET),
+                ("PI_CONTRACT_ADDR_OFFSET", PI_CONTRACT_ADDR_OFFSET),
+                ("PI_USER_ADDR_OFFSET", PI_USER_ADDR_OFFSET),
+                ("PI_NFT_IDS_OFFSET", PI_NFT_IDS_OFFSET),
+                ("PI_BLOCK_HASH_OFFSET", offsets.block_hash),
+                ("PI_REWARDS_RATE_OFFSET", offsets.rewards_rate),
+                ("PI_ERC20_RESULT_OFFSET", offsets.erc20_result),
+                ("PI_QUERY_COMMITMENT_OFFSET", offsets.query_commitment),
+                ("PI_DECIMALS_OFFSET", offsets.decimals),
+                ("PI_SCALED_RESULT_OFFSET", offsets.scaled_result),
+                ("PI_SCALED_REMAINDER_OFFSET", offsets.scaled_remainder),
+                ("PI_CONTRACT_DIGEST_OFFSET", offsets.contract_digest),
+                ("PI_SLOTS_COMMITMENT_OFFSET", offsets.slots_commitment),
+                ("PI_AGGREGATION_MODE_OFFSET", offsets.aggregation_mode),
+                ("PI_MAX_REWARDS_RATE_OFFSET", offsets.max_rewards_rate),
+                ("PI_THRESHOLD_OFFSET", offsets.threshold),
+                ("PI_LEAF_MODE_OFFSET", offsets.leaf_mode),
+                ("PI_QUERY_IDENTIFIER_OFFSET", offsets.query_identifier),
+            ] {
+                assert_eq!(constants[name], offset * 8, "{name} in {file}");
+            }
+        }
+    }
+}
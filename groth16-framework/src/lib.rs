@@ -67,8 +67,10 @@
 
 use plonky2::{field::goldilocks_field::GoldilocksField, plonk::config::PoseidonGoldilocksConfig};
 
+mod batch;
 mod compiler;
 mod evm;
+mod gas;
 mod proof;
 pub mod prover;
 pub mod test_utils;
@@ -84,6 +86,26 @@ pub type C = PoseidonGoldilocksConfig;
 // called for re-generating these asset files when the circuit code changes.
 pub use compiler::compile_and_generate_assets;
 
+// Check if the asset files were generated from the given circuit, to detect
+// stale asset files after the circuit code changes.
+pub use compiler::assets_match;
+
+// Check if a wrapped proof was generated by the circuit the asset files were
+// generated from, before submitting it to the deployed verifier.
+pub use compiler::verify_circuit_binding;
+
+// Compute the gas used to verify the full wrapped proof through the
+// `processQuery` function of the Solidity query verifier contract.
+pub use gas::estimate_verification_gas;
+
+// Encode and decode the batch verification of multiple full wrapped proofs
+// through the `processQueryBatch` function of the Solidity verifier contract.
+pub use batch::{decode_process_query_batch, encode_process_query_batch, BatchQueryResults};
+
+// Compute off-chain the commitment to the public inputs of the final wrapped
+// proof checked by the Solidity verifier.
+pub use utils::public_input_commitment;
+
 // The exported Groth16 proof struct
 pub use proof::Groth16Proof;
 
@@ -2,14 +2,22 @@
 
 use crate::{
     prover::groth16::combine_proofs,
-    utils::{hex_to_u256, read_file, write_file},
+    utils::{
+        hex_to_u256, read_file, write_file, QUERY_VERIFIER_CONTRACT_FILENAME,
+        SOLIDITY_VERIFIER_FILENAME,
+    },
     EVMVerifier, Groth16Proof, Groth16Prover, Groth16Verifier, C, D, F,
 };
+use anyhow::{anyhow, Result};
 use ethers::abi::{Contract, Token};
 use mr_plonky2_circuits::api::deserialize_proof;
 use plonky2::{field::types::PrimeField64, plonk::proof::ProofWithPublicInputs};
 use std::path::Path;
 
+/// Directory of the checked-in test data, resolved from the crate root so it
+/// doesn't depend on the working directory of the test run
+pub const TEST_DATA_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/test_data");
+
 /// Convert the plonky2 proof public inputs to bytes and save to a file
 /// `plonky2_proof_pis.bin` in the specified dir.
 pub fn save_plonky2_proof_pis(dir: &str, proof: &ProofWithPublicInputs<F, C, D>) {
@@ -24,6 +32,39 @@ pub fn save_plonky2_proof_pis(dir: &str, proof: &ProofWithPublicInputs<F, C, D>)
     write_file(file_path, &bytes).unwrap();
 }
 
+/// Generate the Solidity query verifier contract `TestGroth16Verifier.sol` in
+/// the destination dir, by appending the contracts of the extensions file to the
+/// `verifier.sol` generated in the asset dir. The extensions file imports
+/// `verifier.sol`, so its header (license, pragma and imports) is dropped. The
+/// generated verifier embeds the verification key and the digest of the wrapped
+/// circuit, so the query verifier must be regenerated whenever the wrapped
+/// circuit, e.g. the layout of its public inputs, changes.
+/// This function returns the full file path of the query verifier contract.
+pub fn generate_query_verifier_contract(
+    asset_dir: &str,
+    extensions_file_path: &str,
+    dst_dir: &str,
+) -> Result<String> {
+    let verifier = read_file(Path::new(asset_dir).join(SOLIDITY_VERIFIER_FILENAME))?;
+    let verifier = String::from_utf8(verifier)?;
+    let extensions = String::from_utf8(read_file(extensions_file_path)?)?;
+
+    // Skip the header of the extensions file up to the first contract.
+    let offset = extensions
+        .find("\ncontract ")
+        .ok_or(anyhow!("No contract found in {extensions_file_path}"))?;
+
+    let contract = format!(
+        "{}\n\n{}",
+        verifier.trim(),
+        extensions[offset..].trim_start()
+    );
+    let file_path = Path::new(dst_dir).join(QUERY_VERIFIER_CONTRACT_FILENAME);
+    write_file(&file_path, contract.as_bytes())?;
+
+    Ok(file_path.to_string_lossy().to_string())
+}
+
 /// Test Groth16 proving, verification and Solidity verification.
 pub fn test_groth16_proving_and_verification(asset_dir: &str, plonky2_proof: &[u8]) {
     // Generate the Groth16 proof.
@@ -84,7 +125,7 @@ fn evm_verify(asset_dir: &str, proof: &Groth16Proof) {
         .to_string();
 
     let contract = Contract::load(
-        read_file(Path::new("test_data").join("TestGroth16Verifier.abi"))
+        read_file(Path::new(TEST_DATA_DIR).join("TestGroth16Verifier.abi"))
             .unwrap()
             .as_slice(),
     )
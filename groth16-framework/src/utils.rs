@@ -4,7 +4,8 @@ use crate::{C, D, F};
 use anyhow::{anyhow, Result};
 use ethers::types::U256;
 use mrp2_utils::serialization::{FromBytes, SerializationError, ToBytes};
-use plonky2::plonk::circuit_data::CircuitData;
+use plonky2::{field::types::PrimeField64, plonk::circuit_data::CircuitData};
+use sha2::{Digest, Sha256};
 use std::{
     fs::{create_dir_all, File},
     io::{Read, Write},
@@ -16,9 +17,20 @@ use std::{
 /// The actual mapreduce-plonky2 proof being verified has already been hardcoded in the wrapped proof.
 pub const CIRCUIT_DATA_FILENAME: &str = "circuit.bin";
 
+/// The filename of the fingerprint of the circuit the asset files were generated
+/// from, which is used to detect stale asset files.
+pub const CIRCUIT_FINGERPRINT_FILENAME: &str = "circuit_fingerprint.txt";
+
 /// The filename of the exported Solidity verifier contract.
 pub const SOLIDITY_VERIFIER_FILENAME: &str = "verifier.sol";
 
+/// The filename of the Solidity query verifier contract, which combines the
+/// generated verifier with the `processQuery` extension functions.
+pub const QUERY_VERIFIER_CONTRACT_FILENAME: &str = "TestGroth16Verifier.sol";
+
+/// The filename of the ABI of the Solidity query verifier contract.
+pub const QUERY_VERIFIER_ABI_FILENAME: &str = "TestGroth16Verifier.abi";
+
 /// Convert a string with `0x` prefix to an U256.
 pub fn hex_to_u256(s: &str) -> Result<U256> {
     let s = s
@@ -52,6 +64,22 @@ pub fn write_file<P: AsRef<Path>>(file_path: P, data: &[u8]) -> Result<()> {
     Ok(())
 }
 
+/// Compute the commitment to the plonky2 public inputs of the final wrapped proof,
+/// as reconstructed by the `processQuery` function of the Solidity verifier: the
+/// sha256 hash of the public inputs, each serialized as a little-endian u64, with
+/// the top 3 bits set to 0 to fit in the scalar field. The commitment is returned
+/// as the big-endian bytes of the uint256 checked against the last Groth16 input.
+pub fn public_input_commitment(pis: &[F]) -> [u8; 32] {
+    let bytes: Vec<_> = pis
+        .iter()
+        .flat_map(|f| f.to_canonical_u64().to_le_bytes())
+        .collect();
+    let mut commitment: [u8; 32] = Sha256::digest(bytes).into();
+    commitment[0] &= 0x1f;
+
+    commitment
+}
+
 /// Serialize the circuit data to bytes.
 pub fn serialize_circuit_data(circuit_data: &CircuitData<F, C, D>) -> Result<Vec<u8>> {
     // Assume that the circuit data could always be serialized by the custom
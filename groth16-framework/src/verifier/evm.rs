@@ -1,10 +1,12 @@
 //! The verifier used to test the Solidity verification.
 
 use crate::{
+    batch::{decode_process_query_batch, encode_process_query_batch, BatchQueryResults},
     evm::{executor::deploy_and_call, utils::compile_solidity},
     utils::read_file,
 };
 use anyhow::Result;
+use ethers::abi::Contract;
 
 /// EVM verifier
 #[derive(Debug)]
@@ -42,4 +44,20 @@ impl EVMVerifier {
             }
         }
     }
+
+    /// Verify multiple full proofs in one call of the `processQueryBatch`
+    /// function, the contract ABI is used to encode the calldata and decode
+    /// the output. Return the gas_used and the query result of each proof,
+    /// which is `None` if the proof is rejected.
+    pub fn verify_batch(
+        &self,
+        contract: &Contract,
+        proofs: &[&[u8]],
+    ) -> Result<(u64, BatchQueryResults)> {
+        let calldata = encode_process_query_batch(contract, proofs)?;
+        let (gas_used, output) = self.verify(calldata)?;
+        let results = decode_process_query_batch(contract, &output)?;
+
+        Ok((gas_used, results))
+    }
 }
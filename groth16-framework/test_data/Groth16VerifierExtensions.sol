@@ -14,6 +14,12 @@ contract Query2 is Verifier {
     // byteLen(uint256) / 4
     uint32 constant PACKED_U256_LEN = 8;
 
+    // The number of field elements of a curve point digest (x, y coordinates and is_inf flag).
+    uint32 constant CURVE_POINT_LEN = 11;
+
+    // The number of field elements of a Poseidon hash output.
+    uint32 constant HASH_OUT_LEN = 4;
+
     // Top 3 bits mask.
     uint256 constant TOP_THREE_BIT_MASK = ~(uint256(7) << 253);
 
@@ -27,7 +33,7 @@ contract Query2 is Verifier {
     // The total length of the plonky2 public inputs. Each input value is
     // serialized as an uint64. It's related with both the full proof
     // serialization and the wrapped circuit code.
-    uint32 constant PI_TOTAL_LEN = (L + 41) * 8;
+    uint32 constant PI_TOTAL_LEN = (L + 99) * 8;
 
     // The min block number offset in the plonky2 public inputs.
     uint32 constant PI_MIN_BLOCK_NUM_OFFSET = 2 * 8;
@@ -53,8 +59,38 @@ contract Query2 is Verifier {
     // The ERC20 result offset in the plonky2 public inputs.
     uint32 constant PI_ERC20_RESULT_OFFSET = PI_REWARDS_RATE_OFFSET + PACKED_U256_LEN * 8;
 
+    // The query commitment offset in the plonky2 public inputs.
+    uint32 constant PI_QUERY_COMMITMENT_OFFSET = PI_ERC20_RESULT_OFFSET + PACKED_U256_LEN * 8;
+
+    // The token decimals offset in the plonky2 public inputs.
+    uint32 constant PI_DECIMALS_OFFSET = PI_QUERY_COMMITMENT_OFFSET + PACKED_HASH_LEN * 8;
+
+    // The offset of the ERC20 result scaled by the token decimals in the plonky2 public inputs.
+    uint32 constant PI_SCALED_RESULT_OFFSET = PI_DECIMALS_OFFSET + 8;
+
+    // The offset of the remainder of the scaled ERC20 result in the plonky2 public inputs.
+    uint32 constant PI_SCALED_REMAINDER_OFFSET = PI_SCALED_RESULT_OFFSET + PACKED_U256_LEN * 8;
+
+    // The contract digest offset in the plonky2 public inputs.
+    uint32 constant PI_CONTRACT_DIGEST_OFFSET = PI_SCALED_REMAINDER_OFFSET + PACKED_U256_LEN * 8;
+
+    // The mapping slots commitment offset in the plonky2 public inputs.
+    uint32 constant PI_SLOTS_COMMITMENT_OFFSET = PI_CONTRACT_DIGEST_OFFSET + CURVE_POINT_LEN * 8;
+
+    // The aggregation mode offset in the plonky2 public inputs.
+    uint32 constant PI_AGGREGATION_MODE_OFFSET = PI_SLOTS_COMMITMENT_OFFSET + HASH_OUT_LEN * 8;
+
+    // The max rewards rate offset in the plonky2 public inputs.
+    uint32 constant PI_MAX_REWARDS_RATE_OFFSET = PI_AGGREGATION_MODE_OFFSET + 8;
+
+    // The threshold offset in the plonky2 public inputs.
+    uint32 constant PI_THRESHOLD_OFFSET = PI_MAX_REWARDS_RATE_OFFSET + PACKED_U256_LEN * 8;
+
+    // The leaf mode offset in the plonky2 public inputs.
+    uint32 constant PI_LEAF_MODE_OFFSET = PI_THRESHOLD_OFFSET + PACKED_U256_LEN * 8;
+
     // The query identifier offset in the plonky2 public inputs.
-    uint32 constant PI_QUERY_IDENTIFIER_OFFSET = PI_ERC20_RESULT_OFFSET + PACKED_U256_LEN * 8;
+    uint32 constant PI_QUERY_IDENTIFIER_OFFSET = PI_LEAF_MODE_OFFSET + 8;
 
     // Supported query identifiers
     uint8 constant QUERY_IDENTIFIER_NFT = 67;
@@ -70,6 +106,15 @@ contract Query2 is Verifier {
         uint88 rewardsRate;
         uint8 identifier;
         bytes32 blockHash;
+        // Value computed over the balances of ERC20 queries (0: proportional
+        // rewards, 1: flat rewards per holder, 2: threshold).
+        uint8 leafMode;
+        // Threshold compared to the balances, or minimum balance of the flat
+        // rewards. It's zero for proportional rewards.
+        uint256 threshold;
+        // Upper bound on the rewards rate of the proportional rewards, the
+        // maximum uint256 if unbounded.
+        uint256 maxRewardsRate;
     }
 
     // This processQuery function does the followings:
@@ -98,6 +143,32 @@ contract Query2 is Verifier {
         return parseQueryResult(pis, query.identifier);
     }
 
+    // This processQueryBatch function verifies multiple full proofs in one
+    // call. Each proof is processed as `processQuery` with its own expected
+    // query, and a rejected proof doesn't revert the whole batch. It returns
+    // whether each proof is verified and its query result (empty if rejected).
+    function processQueryBatch(bytes32[][] calldata data, Query[] memory queries)
+        public
+        view
+        returns (bool[] memory, uint256[][] memory)
+    {
+        require(data.length == queries.length, "The numbers of proofs and queries must be equal");
+
+        bool[] memory verified = new bool[](data.length);
+        uint256[][] memory results = new uint256[][](data.length);
+        for (uint256 i = 0; i < data.length; ++i) {
+            try this.processQuery(data[i], queries[i]) returns (uint256[] memory result) {
+                verified[i] = true;
+                results[i] = result;
+            } catch {
+                verified[i] = false;
+                results[i] = new uint256[](0);
+            }
+        }
+
+        return (verified, results);
+    }
+
     // Parse the Groth16 proofs and inputs, and do verification. It returns the Groth16 inputs.
     function verifyGroth16Proof(bytes32[] calldata data) internal view returns (uint256[3] memory) {
         uint256[8] memory proofs;
@@ -187,6 +258,38 @@ contract Query2 is Verifier {
             require(
                 rewardsRate == query.rewardsRate, "The parsed rewards rate must be equal to the expected one in query."
             );
+
+            require(
+                uint8(pis[PI_LEAF_MODE_OFFSET]) == query.leafMode,
+                "The parsed leaf mode must be equal to the expected one in query."
+            );
+
+            uint256 threshold = convertByteSliceToU256(pis, PI_THRESHOLD_OFFSET);
+            require(threshold == query.threshold, "The parsed threshold must be equal to the expected one in query.");
+
+            uint256 maxRewardsRate = convertByteSliceToU256(pis, PI_MAX_REWARDS_RATE_OFFSET);
+            require(
+                maxRewardsRate == query.maxRewardsRate,
+                "The parsed max rewards rate must be equal to the expected one in query."
+            );
+
+            // Recompute the commitment to the query parameters the proof has been generated for.
+            bytes32 queryCommitment = keccak256(
+                abi.encodePacked(
+                    query.contractAddress,
+                    query.userAddress,
+                    uint32(query.minBlockNumber),
+                    uint32(query.maxBlockNumber),
+                    query.leafMode,
+                    uint256(query.rewardsRate),
+                    query.threshold,
+                    query.maxRewardsRate
+                )
+            );
+            require(
+                convertToHash(pis, PI_QUERY_COMMITMENT_OFFSET) == queryCommitment,
+                "The parsed query commitment must be equal to the one computed from the query."
+            );
         }
 
         require(
@@ -216,10 +319,12 @@ contract Query2 is Verifier {
         return nft_ids;
     }
 
-    // Parse the ERC20 result from the plonky2 public inputs.
+    // Parse the ERC20 result from the plonky2 public inputs, followed by the mode
+    // employed to aggregate it over the blocks (0: sum, 1: min, 2: max).
     function parseErc20Result(bytes memory pis) internal pure returns (uint256[] memory) {
-        uint256[] memory result = new uint256[](1);
+        uint256[] memory result = new uint256[](2);
         result[0] = convertByteSliceToU256(pis, PI_ERC20_RESULT_OFFSET);
+        result[1] = uint256(uint8(pis[PI_AGGREGATION_MODE_OFFSET]));
 
         return result;
     }
@@ -52,36 +52,36 @@ contract Verifier {
     uint256 constant EXP_SQRT_FP = 0xC19139CB84C680A6E14116DA060561765E05AA45A1C72A34F082305B61F3F52; // (P + 1) / 4;
 
     // Groth16 alpha point in G1
-    uint256 constant ALPHA_X = 21240516409640074159992156247830471303318613709451580478046683288261228863107;
-    uint256 constant ALPHA_Y = 2863273447500662726540589889538139326922297979347073835004930101072635702167;
+    uint256 constant ALPHA_X = 14203975404520260752093969438138389323271181511080380152435798100099745969379;
+    uint256 constant ALPHA_Y = 18287204318794171332400073719746908786851491729756415454223442198102849074855;
 
     // Groth16 beta point in G2 in powers of i
-    uint256 constant BETA_NEG_X_0 = 9622943318700839915620502627492638317225878995261086935391208320726699696091;
-    uint256 constant BETA_NEG_X_1 = 1915355384824813121540430733246104328063604536133804493912662899585912286885;
-    uint256 constant BETA_NEG_Y_0 = 3486640936748288141201090521922077045652997587123991678093958762361053217452;
-    uint256 constant BETA_NEG_Y_1 = 4261055074226999291012216260069736274638560654934039929903546784360386024495;
+    uint256 constant BETA_NEG_X_0 = 14182746057118932531119419775248655215999552851587880769894092919815318854079;
+    uint256 constant BETA_NEG_X_1 = 12683390308506128326666583226496302601857318988227740948513564592565150412253;
+    uint256 constant BETA_NEG_Y_0 = 17702544229660055425992089692161462261736050259607705457555294197497668133802;
+    uint256 constant BETA_NEG_Y_1 = 21127033415818163472118538170889890527553241346229649624605221274417900855452;
 
     // Groth16 gamma point in G2 in powers of i
-    uint256 constant GAMMA_NEG_X_0 = 20446168008593010703506739414396747911537528802810400810987576378139948230883;
-    uint256 constant GAMMA_NEG_X_1 = 9938344830880059644640837524255824539803536618795158361598623007532675287270;
-    uint256 constant GAMMA_NEG_Y_0 = 13241561899532022044356916790112186784480090603133498253236447851344817614755;
-    uint256 constant GAMMA_NEG_Y_1 = 15033979315717992650539636793642983809038285873892513756311692973714921200820;
+    uint256 constant GAMMA_NEG_X_0 = 499394079463924875962540958234756143708429577458159326946111281188934993028;
+    uint256 constant GAMMA_NEG_X_1 = 21077090675402266397722954859415016654416356733606653058855662580372634814937;
+    uint256 constant GAMMA_NEG_Y_0 = 21079311319027403275557387062295699330658827889470830916149494859737623436894;
+    uint256 constant GAMMA_NEG_Y_1 = 17922207505503121146806922346995760187901822834219765373403832291996633569360;
 
     // Groth16 delta point in G2 in powers of i
-    uint256 constant DELTA_NEG_X_0 = 5057233726553684200414545040369972586840254254249332799679602273145725120188;
-    uint256 constant DELTA_NEG_X_1 = 21375177356820717456125639885952559565504618269990203805706128410408836720813;
-    uint256 constant DELTA_NEG_Y_0 = 2565437463233336066732079359136884404434383328608424217723037536374408961503;
-    uint256 constant DELTA_NEG_Y_1 = 1629531920701405296769355033111374027860865665297386448100611738795830922588;
+    uint256 constant DELTA_NEG_X_0 = 20287536507502928815755415033200397197241347462498607141501277090035751617946;
+    uint256 constant DELTA_NEG_X_1 = 20271679153223182686550786505203159428061009856639272167146423924973293551751;
+    uint256 constant DELTA_NEG_Y_0 = 4646539917572385376126580834872916886805974994211311635641582655361809245150;
+    uint256 constant DELTA_NEG_Y_1 = 8385798782071614272745500550950623661677313521612535590842438779503301227533;
 
     // Constant and public input points
-    uint256 constant CONSTANT_X = 15872046867362446613037110867505735869353943781323041265037366348995732996229;
-    uint256 constant CONSTANT_Y = 18204743816605744266917295311598586630045827491127366037081199919910134153346;
-    uint256 constant PUB_0_X = 1525128681295354430452259933683986301192768679565049058446925383742101820316;
-    uint256 constant PUB_0_Y = 18569965592898315534774698044305249715166051666906970536384326941971730868645;
-    uint256 constant PUB_1_X = 13655321137774104281658230437921903580259464705088947146801086009545440362501;
-    uint256 constant PUB_1_Y = 2605156225401539698855190068336862170045688673403865187991787913073163054778;
-    uint256 constant PUB_2_X = 11780442367952134545983279023737732821613270225534356887452597683195440415168;
-    uint256 constant PUB_2_Y = 1440868210672729738328406784295586301132697827683223028987611298375453327290;
+    uint256 constant CONSTANT_X = 12972316725243678057073554578436389425980338930515051194131416297164538120316;
+    uint256 constant CONSTANT_Y = 2467383993954176961469420008365922174939128553633466223895737162957587484853;
+    uint256 constant PUB_0_X = 13485612424243073354768837647639370850516262424802561696539968552274236968416;
+    uint256 constant PUB_0_Y = 16938032263268700169078617345222276997395080086877142413446124891951166401812;
+    uint256 constant PUB_1_X = 9577871516163645227022497024355368800947618780467796129245411052446525718871;
+    uint256 constant PUB_1_Y = 2255554114010427737405622218768298085159488821115248649813601084257761833871;
+    uint256 constant PUB_2_X = 2729903753053334408029284154588476418397822073389430137379814638748981364113;
+    uint256 constant PUB_2_Y = 8588614792037255042687646565980841920112454621626098637175634238186999562225;
 
     /// Negation in Fp.
     /// @notice Returns a number x such that a + x = 0 in Fp.
@@ -151,7 +151,7 @@ contract Verifier {
     }
 
     /// Square test in Fp.
-    /// @notice Returns whether a number x exists such that x * x = a in Fp.
+    /// @notice Returns wheter a number x exists such that x * x = a in Fp.
     /// @notice Will revert with InvalidProof() if the input is not a square
     /// or not reduced.
     /// @param a the square
@@ -543,8 +543,10 @@ contract Verifier {
         }
     }
 
-    bytes32 constant CIRCUIT_DIGEST = 0x2816759d0f7a8cd499354b2f471d63c78d15c2ddb925f8ca46c3ed129ae82807;
+    bytes32 constant CIRCUIT_DIGEST = 0x299431a9262b45ab4993c36cd23c4cf37af4a66eb6207c49202a7c4176c60fbf;
+}
 
+contract Query2 is Verifier {
     // byteLen(uint160) / 4
     uint32 constant PACKED_ADDRESS_LEN = 5;
 
@@ -554,6 +556,12 @@ contract Verifier {
     // byteLen(uint256) / 4
     uint32 constant PACKED_U256_LEN = 8;
 
+    // The number of field elements of a curve point digest (x, y coordinates and is_inf flag).
+    uint32 constant CURVE_POINT_LEN = 11;
+
+    // The number of field elements of a Poseidon hash output.
+    uint32 constant HASH_OUT_LEN = 4;
+
     // Top 3 bits mask.
     uint256 constant TOP_THREE_BIT_MASK = ~(uint256(7) << 253);
 
@@ -567,7 +575,7 @@ contract Verifier {
     // The total length of the plonky2 public inputs. Each input value is
     // serialized as an uint64. It's related with both the full proof
     // serialization and the wrapped circuit code.
-    uint32 constant PI_TOTAL_LEN = (L + 41) * 8;
+    uint32 constant PI_TOTAL_LEN = (L + 99) * 8;
 
     // The min block number offset in the plonky2 public inputs.
     uint32 constant PI_MIN_BLOCK_NUM_OFFSET = 2 * 8;
@@ -593,8 +601,38 @@ contract Verifier {
     // The ERC20 result offset in the plonky2 public inputs.
     uint32 constant PI_ERC20_RESULT_OFFSET = PI_REWARDS_RATE_OFFSET + PACKED_U256_LEN * 8;
 
+    // The query commitment offset in the plonky2 public inputs.
+    uint32 constant PI_QUERY_COMMITMENT_OFFSET = PI_ERC20_RESULT_OFFSET + PACKED_U256_LEN * 8;
+
+    // The token decimals offset in the plonky2 public inputs.
+    uint32 constant PI_DECIMALS_OFFSET = PI_QUERY_COMMITMENT_OFFSET + PACKED_HASH_LEN * 8;
+
+    // The offset of the ERC20 result scaled by the token decimals in the plonky2 public inputs.
+    uint32 constant PI_SCALED_RESULT_OFFSET = PI_DECIMALS_OFFSET + 8;
+
+    // The offset of the remainder of the scaled ERC20 result in the plonky2 public inputs.
+    uint32 constant PI_SCALED_REMAINDER_OFFSET = PI_SCALED_RESULT_OFFSET + PACKED_U256_LEN * 8;
+
+    // The contract digest offset in the plonky2 public inputs.
+    uint32 constant PI_CONTRACT_DIGEST_OFFSET = PI_SCALED_REMAINDER_OFFSET + PACKED_U256_LEN * 8;
+
+    // The mapping slots commitment offset in the plonky2 public inputs.
+    uint32 constant PI_SLOTS_COMMITMENT_OFFSET = PI_CONTRACT_DIGEST_OFFSET + CURVE_POINT_LEN * 8;
+
+    // The aggregation mode offset in the plonky2 public inputs.
+    uint32 constant PI_AGGREGATION_MODE_OFFSET = PI_SLOTS_COMMITMENT_OFFSET + HASH_OUT_LEN * 8;
+
+    // The max rewards rate offset in the plonky2 public inputs.
+    uint32 constant PI_MAX_REWARDS_RATE_OFFSET = PI_AGGREGATION_MODE_OFFSET + 8;
+
+    // The threshold offset in the plonky2 public inputs.
+    uint32 constant PI_THRESHOLD_OFFSET = PI_MAX_REWARDS_RATE_OFFSET + PACKED_U256_LEN * 8;
+
+    // The leaf mode offset in the plonky2 public inputs.
+    uint32 constant PI_LEAF_MODE_OFFSET = PI_THRESHOLD_OFFSET + PACKED_U256_LEN * 8;
+
     // The query identifier offset in the plonky2 public inputs.
-    uint32 constant PI_QUERY_IDENTIFIER_OFFSET = PI_ERC20_RESULT_OFFSET + PACKED_U256_LEN * 8;
+    uint32 constant PI_QUERY_IDENTIFIER_OFFSET = PI_LEAF_MODE_OFFSET + 8;
 
     // Supported query identifiers
     uint8 constant QUERY_IDENTIFIER_NFT = 67;
@@ -610,6 +648,15 @@ contract Verifier {
         uint88 rewardsRate;
         uint8 identifier;
         bytes32 blockHash;
+        // Value computed over the balances of ERC20 queries (0: proportional
+        // rewards, 1: flat rewards per holder, 2: threshold).
+        uint8 leafMode;
+        // Threshold compared to the balances, or minimum balance of the flat
+        // rewards. It's zero for proportional rewards.
+        uint256 threshold;
+        // Upper bound on the rewards rate of the proportional rewards, the
+        // maximum uint256 if unbounded.
+        uint256 maxRewardsRate;
     }
 
     // This processQuery function does the followings:
@@ -638,6 +685,32 @@ contract Verifier {
         return parseQueryResult(pis, query.identifier);
     }
 
+    // This processQueryBatch function verifies multiple full proofs in one
+    // call. Each proof is processed as `processQuery` with its own expected
+    // query, and a rejected proof doesn't revert the whole batch. It returns
+    // whether each proof is verified and its query result (empty if rejected).
+    function processQueryBatch(bytes32[][] calldata data, Query[] memory queries)
+        public
+        view
+        returns (bool[] memory, uint256[][] memory)
+    {
+        require(data.length == queries.length, "The numbers of proofs and queries must be equal");
+
+        bool[] memory verified = new bool[](data.length);
+        uint256[][] memory results = new uint256[][](data.length);
+        for (uint256 i = 0; i < data.length; ++i) {
+            try this.processQuery(data[i], queries[i]) returns (uint256[] memory result) {
+                verified[i] = true;
+                results[i] = result;
+            } catch {
+                verified[i] = false;
+                results[i] = new uint256[](0);
+            }
+        }
+
+        return (verified, results);
+    }
+
     // Parse the Groth16 proofs and inputs, and do verification. It returns the Groth16 inputs.
     function verifyGroth16Proof(bytes32[] calldata data) internal view returns (uint256[3] memory) {
         uint256[8] memory proofs;
@@ -690,7 +763,10 @@ contract Verifier {
         pis_hash = pis_hash & TOP_THREE_BIT_MASK;
 
         // Require the sha256 hash equals to the last Groth16 input.
-        require(pis_hash == groth16_inputs[2], "The plonky2 public inputs hash must be equal to the last of the Groth16 inputs");
+        require(
+            pis_hash == groth16_inputs[2],
+            "The plonky2 public inputs hash must be equal to the last of the Groth16 inputs"
+        );
     }
 
     // Verify the plonky2 inputs with the expected Query instance.
@@ -714,22 +790,47 @@ contract Verifier {
         );
 
         address userAddress = convertToAddress(pis, PI_USER_ADDR_OFFSET);
-        require(
-            userAddress == query.userAddress,
-            "The parsed user address must be equal to the expected one in query."
-        );
+        require(userAddress == query.userAddress, "The parsed user address must be equal to the expected one in query.");
 
         bytes32 blockHash = bytes32(convertToHash(pis, PI_BLOCK_HASH_OFFSET));
-        require(
-            blockHash == query.blockHash,
-            "The parsed block hash must be equal to the expected one in query."
-        );
+        require(blockHash == query.blockHash, "The parsed block hash must be equal to the expected one in query.");
 
         if (query.identifier == QUERY_IDENTIFIER_ERC20) {
             uint256 rewardsRate = convertByteSliceToU256(pis, PI_REWARDS_RATE_OFFSET);
             require(
-                rewardsRate == query.rewardsRate,
-                "The parsed rewards rate must be equal to the expected one in query."
+                rewardsRate == query.rewardsRate, "The parsed rewards rate must be equal to the expected one in query."
+            );
+
+            require(
+                uint8(pis[PI_LEAF_MODE_OFFSET]) == query.leafMode,
+                "The parsed leaf mode must be equal to the expected one in query."
+            );
+
+            uint256 threshold = convertByteSliceToU256(pis, PI_THRESHOLD_OFFSET);
+            require(threshold == query.threshold, "The parsed threshold must be equal to the expected one in query.");
+
+            uint256 maxRewardsRate = convertByteSliceToU256(pis, PI_MAX_REWARDS_RATE_OFFSET);
+            require(
+                maxRewardsRate == query.maxRewardsRate,
+                "The parsed max rewards rate must be equal to the expected one in query."
+            );
+
+            // Recompute the commitment to the query parameters the proof has been generated for.
+            bytes32 queryCommitment = keccak256(
+                abi.encodePacked(
+                    query.contractAddress,
+                    query.userAddress,
+                    uint32(query.minBlockNumber),
+                    uint32(query.maxBlockNumber),
+                    query.leafMode,
+                    uint256(query.rewardsRate),
+                    query.threshold,
+                    query.maxRewardsRate
+                )
+            );
+            require(
+                convertToHash(pis, PI_QUERY_COMMITMENT_OFFSET) == queryCommitment,
+                "The parsed query commitment must be equal to the one computed from the query."
             );
         }
 
@@ -760,10 +861,12 @@ contract Verifier {
         return nft_ids;
     }
 
-    // Parse the ERC20 result from the plonky2 public inputs.
+    // Parse the ERC20 result from the plonky2 public inputs, followed by the mode
+    // employed to aggregate it over the blocks (0: sum, 1: min, 2: max).
     function parseErc20Result(bytes memory pis) internal pure returns (uint256[] memory) {
-        uint256[] memory result = new uint256[](1);
+        uint256[] memory result = new uint256[](2);
         result[0] = convertByteSliceToU256(pis, PI_ERC20_RESULT_OFFSET);
+        result[1] = uint256(uint8(pis[PI_AGGREGATION_MODE_OFFSET]));
 
         return result;
     }
@@ -801,7 +904,7 @@ contract Verifier {
     // Convert the specified byte slice to an uint256.
     function convertByteSliceToU256(bytes memory pis, uint32 offset) internal pure returns (uint256) {
         uint256 result;
-        for (uint32 i = 0; i < 8 ; ++i) {
+        for (uint32 i = 0; i < 8; ++i) {
             result |= uint256(convertToU32(pis, offset + i * 8)) << (32 * i);
         }
 
@@ -3,7 +3,7 @@
 use super::{TestContext, TestQuery};
 use groth16_framework::{C, D, F};
 use itertools::Itertools;
-use mr_plonky2_circuits::block::{empty_merkle_root, PublicInputs, NUM_IVC_PUBLIC_INPUTS};
+use mr_plonky2_circuits::block::{empty_merkle_root, BlockDbState, NUM_IVC_PUBLIC_INPUTS};
 use plonky2::{
     field::{
         goldilocks_field::GoldilocksField,
@@ -22,12 +22,6 @@ impl<const BLOCK_DB_DEPTH: usize> TestContext<BLOCK_DB_DEPTH> {
         &self,
         query: &TestQuery,
     ) -> ProofWithPublicInputs<F, C, D> {
-        let init_root = empty_merkle_root::<F, D, BLOCK_DB_DEPTH>();
-        let last_root = HashOut {
-            elements: F::rand_vec(NUM_HASH_OUT_ELTS).try_into().unwrap(),
-        };
-        let init_block_number = F::ONE;
-        let last_block_number = F::from_canonical_u32(query.max_block_number + 1);
         let last_block_hash = query
             .block_hash
             .0
@@ -36,18 +30,22 @@ impl<const BLOCK_DB_DEPTH: usize> TestContext<BLOCK_DB_DEPTH> {
             .collect::<Vec<_>>()
             .try_into()
             .unwrap();
-        let block_db_inputs: [F; NUM_IVC_PUBLIC_INPUTS] = PublicInputs::from_parts(
-            &init_root.elements,
-            &last_root.elements,
-            init_block_number,
-            last_block_number,
-            &last_block_hash,
-        )
-        .into_iter()
-        .chain(once(F::ONE))
-        .collect_vec()
-        .try_into()
-        .unwrap();
+        let block_db_state = BlockDbState {
+            init_root: empty_merkle_root::<F, D, BLOCK_DB_DEPTH>(),
+            last_root: HashOut {
+                elements: F::rand_vec(NUM_HASH_OUT_ELTS).try_into().unwrap(),
+            },
+            first_block: F::ONE,
+            last_block: F::from_canonical_u32(query.max_block_number + 1),
+            header: last_block_hash,
+        };
+        let block_db_inputs: [F; NUM_IVC_PUBLIC_INPUTS] = block_db_state
+            .to_public_inputs()
+            .into_iter()
+            .chain(once(F::ONE))
+            .collect_vec()
+            .try_into()
+            .unwrap();
         self.block_db_circuits
             .generate_input_proofs::<1>([block_db_inputs.clone()])
             .unwrap()[0]
@@ -16,7 +16,6 @@ use recursion_framework::{
 /// Test context
 pub(crate) struct TestContext<const BLOCK_DB_DEPTH: usize> {
     pub(crate) block_db_circuits: TestingRecursiveCircuits<F, C, D, BLOCK_DB_NUM_IO>,
-    pub(crate) erc_circuits: TestingRecursiveCircuits<F, C, D, ERC_NUM_IO>,
     pub(crate) nft_circuits: TestingRecursiveCircuits<F, C, D, NFT_NUM_IO>,
     pub(crate) erc_params: ErcParameters<BLOCK_DB_DEPTH, L>,
     pub(crate) nft_params: NftParameters<BLOCK_DB_DEPTH, L>,
@@ -32,7 +31,8 @@ impl<const BLOCK_DB_DEPTH: usize> TestContext<BLOCK_DB_DEPTH> {
         let block_db_circuit_set = block_db_circuits.get_recursive_circuit_set();
         let block_db_vk = block_db_circuits.verifier_data_for_input_proofs::<1>()[0];
 
-        // Generate a fake ERC20 circuit set.
+        // Generate a fake ERC20 circuit set, the same one of the fake proofs
+        // generated by `fake_query_block_proof`.
         let erc_circuits = TestingRecursiveCircuits::<F, C, D, ERC_NUM_IO>::default();
         let erc_circuit_set = erc_circuits.get_recursive_circuit_set();
 
@@ -62,7 +62,6 @@ impl<const BLOCK_DB_DEPTH: usize> TestContext<BLOCK_DB_DEPTH> {
 
         Self {
             block_db_circuits,
-            erc_circuits,
             nft_circuits,
             erc_params,
             nft_params,
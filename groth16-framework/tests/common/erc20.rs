@@ -6,16 +6,11 @@ use groth16_framework::{test_utils::save_plonky2_proof_pis, C, D, F};
 use mr_plonky2_circuits::{
     api::{deserialize_proof, serialize_proof, ProofWithVK},
     block::PublicInputs as BlockDbPublicInputs,
-    query_erc20::{
-        block::BlockPublicInputs, revelation::RevelationRecursiveInput, RevelationErcInput,
-    },
-    utils::{Packer, ToFields},
-};
-use plonky2::{
-    field::types::{Field, PrimeField64, Sample},
-    hash::hash_types::HashOut,
-    plonk::proof::ProofWithPublicInputs,
+    query_erc20::{revelation::RevelationRecursiveInput, RevelationErcInput},
+    test_support::{fake_query_block_proof, FakeQueryBlockParams},
 };
+use plonky2::{hash::hash_types::HashOut, plonk::proof::ProofWithPublicInputs};
+use rand::{thread_rng, Rng};
 
 impl<const BLOCK_DB_DEPTH: usize> TestContext<BLOCK_DB_DEPTH> {
     /// Generate a fake ERC20 query proof.
@@ -28,57 +23,36 @@ impl<const BLOCK_DB_DEPTH: usize> TestContext<BLOCK_DB_DEPTH> {
     ) -> Vec<u8> {
         // Generate a fake ERC20 query proof.
         let block_db_pi = BlockDbPublicInputs::<F>::from(&block_db_proof.public_inputs);
-        let query_max_number = F::from_canonical_u32(query.max_block_number);
-        let query_min_number = F::from_canonical_u32(query.min_block_number);
-        let query_range =
-            F::from_canonical_u32(query.max_block_number - query.min_block_number + 1);
-        let query_root = HashOut {
-            elements: block_db_pi.root_data().try_into().unwrap(),
+        let params = FakeQueryBlockParams {
+            max_block_number: query.max_block_number,
+            range: query.max_block_number - query.min_block_number + 1,
+            root: HashOut {
+                elements: block_db_pi.root_data().try_into().unwrap(),
+            },
+            contract_address: query.contract_address,
+            user_address: query.user_address,
+            mapping_slot: thread_rng().gen(),
+            length_slot: thread_rng().gen(),
+            query_results: query_result,
+            rewards_rate: U256::from(query.rewards_rate),
+            max_rewards_rate: query.max_rewards_rate,
+            threshold: query.threshold,
+            leaf_mode: query.leaf_mode,
+            state_depth: 3,
         };
-        let contract_address = query.contract_address;
-        let user_address = query.user_address;
-        let mapping_slot = F::rand();
-        let length_slot = F::rand();
-        let pi = BlockPublicInputs::from_parts(
-            query_max_number,
-            query_range,
-            query_root,
-            &contract_address
-                .as_fixed_bytes()
-                .pack()
-                .to_fields()
-                .try_into()
-                .unwrap(),
-            &user_address
-                .as_fixed_bytes()
-                .pack()
-                .to_fields()
-                .try_into()
-                .unwrap(),
-            mapping_slot,
-            length_slot,
-            &query_result.to_fields().try_into().unwrap(),
-            &U256::from(query.rewards_rate)
-                .to_fields()
-                .try_into()
-                .unwrap(),
-        );
-        let query_proof = self.erc_circuits.generate_input_proofs([pi]).unwrap();
-        let query_vk = self.erc_circuits.verifier_data_for_input_proofs::<1>();
-        let query_proof = ProofWithVK::from((query_proof[0].clone(), query_vk[0].clone()))
-            .serialize()
-            .unwrap();
+        let (query_proof, query_circuit_set) = fake_query_block_proof(&params).unwrap();
 
         // Generate the revelation proof.
         let input = RevelationRecursiveInput::<L>::new(
             RevelationErcInput::new(
-                query_min_number.to_canonical_u64() as usize,
-                query_max_number.to_canonical_u64() as usize,
-                query_proof,
+                query.min_block_number as usize,
+                query.max_block_number as usize,
+                18,
+                query_proof.serialize().unwrap(),
                 serialize_proof(&block_db_proof).unwrap(),
             )
             .unwrap(),
-            self.erc_circuits.get_recursive_circuit_set().clone(),
+            query_circuit_set,
         )
         .unwrap();
         let proof = self
@@ -14,7 +14,7 @@ use mr_plonky2_circuits::{
 use mrp2_utils::{
     eth::{left_pad, left_pad32},
     group_hashing,
-    types::MAPPING_KEY_LEN,
+    types::{address_to_packed_fields, MAPPING_KEY_LEN},
 };
 use plonky2::{
     field::types::{Field, PrimeField64, Sample},
@@ -59,12 +59,7 @@ impl<const BLOCK_DB_DEPTH: usize> TestContext<BLOCK_DB_DEPTH> {
             query_max_number,
             query_range,
             query_root,
-            &contract_address
-                .as_fixed_bytes()
-                .pack()
-                .to_fields()
-                .try_into()
-                .unwrap(),
+            &address_to_packed_fields(&contract_address),
             &left_pad32(user_address.as_fixed_bytes())
                 .pack()
                 .to_fields()
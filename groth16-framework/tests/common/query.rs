@@ -5,7 +5,7 @@ use ethers::types::{Address, U256};
 use std::str::FromStr;
 
 /// The query struct used to check with the plonky2 public inputs in Solidity.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub(crate) struct TestQuery {
     pub(crate) contract_address: Address,
     pub(crate) user_address: Address,
@@ -16,6 +16,9 @@ pub(crate) struct TestQuery {
     // Only for testing, since we use uint88 type in the verifier contract.
     pub(crate) rewards_rate: u64,
     pub(crate) identifier: u8,
+    pub(crate) leaf_mode: u8,
+    pub(crate) threshold: U256,
+    pub(crate) max_rewards_rate: U256,
 }
 
 impl TestQuery {
@@ -35,6 +38,11 @@ impl TestQuery {
             ]),
             rewards_rate: 2000,
             identifier: QUERY_IDENTIFIER_NFT,
+            // Proportional rewards.
+            leaf_mode: 0,
+            threshold: U256::zero(),
+            // Unbounded rewards rate.
+            max_rewards_rate: U256::MAX,
         }
     }
 }
@@ -51,7 +59,9 @@ impl TestQueryResult {
     pub(crate) fn enforce_equal(&self, expected_result: &[U256]) {
         let self_result = match self {
             Self::NftIds(ids) => ids.iter().cloned().map(Into::into).collect(),
-            Self::Erc20(u) => vec![*u],
+            // The test ERC20 result is summed over the blocks, i.e. with the
+            // aggregation mode 0.
+            Self::Erc20(u) => vec![*u, U256::zero()],
         };
 
         assert_eq!(self_result, expected_result);
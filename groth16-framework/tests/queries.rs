@@ -3,12 +3,18 @@
 use crate::common::{QUERY_IDENTIFIER_ERC20, QUERY_IDENTIFIER_NFT};
 use common::{TestContext, TestQuery, TestQueryResult, L};
 use ethers::{
-    abi::{Contract, Token},
-    types::U256,
+    abi::{Contract, Function, Token},
+    types::{Address, U256},
 };
 use groth16_framework::{
-    test_utils::test_groth16_proving_and_verification, utils::read_file, EVMVerifier,
+    estimate_verification_gas, public_input_commitment,
+    test_utils::{
+        generate_query_verifier_contract, test_groth16_proving_and_verification, TEST_DATA_DIR,
+    },
+    utils::{read_file, write_file, SOLIDITY_VERIFIER_FILENAME},
+    EVMVerifier, F,
 };
+use plonky2::field::types::Field;
 use serial_test::serial;
 use std::path::Path;
 
@@ -23,6 +29,10 @@ const TEST_NFT_IDS: [u32; L] = [1, 2, 3, 4, 5];
 /// Test ERC20 query result
 const TEST_ERC20_RESULT: U256 = U256([1, 2, 3, 4]);
 
+/// Gas threshold of the full wrapped proof verification in Solidity, used to
+/// detect cost regressions of the checked-in verifier
+const MAX_VERIFICATION_GAS: u64 = 1_000_000;
+
 /// Test proving for the query circuit.
 #[ignore] // Ignore for long running time in CI.
 #[serial]
@@ -39,6 +49,10 @@ fn test_groth16_proving_for_queries() {
     // Generate the Groth16 asset files.
     ctx.generate_assets(ASSET_DIR);
 
+    // Regenerate the checked-in Solidity verifiers, which embed the
+    // verification key of the wrapped circuit generated above.
+    regenerate_test_data_verifiers(ASSET_DIR);
+
     // Generate a fake block db proof.
     let block_db_proof = ctx.generate_block_db_proof(&query);
 
@@ -47,6 +61,8 @@ fn test_groth16_proving_for_queries() {
     let proof = ctx.generate_nft_query_proof(ASSET_DIR, &query, &block_db_proof, &TEST_NFT_IDS);
     test_groth16_proving_and_verification(ASSET_DIR, &proof);
     verify_query2_solidity_fun(ASSET_DIR, &query, TestQueryResult::NftIds(TEST_NFT_IDS));
+    verify_public_input_commitment(ASSET_DIR);
+    let nft_proof = read_file(Path::new(ASSET_DIR).join("full_proof.bin")).unwrap();
 
     // Generate the ERC20 query proof and do verification.
     query.identifier = QUERY_IDENTIFIER_ERC20;
@@ -54,17 +70,78 @@ fn test_groth16_proving_for_queries() {
         ctx.generate_erc20_query_proof(ASSET_DIR, &query, &block_db_proof, TEST_ERC20_RESULT);
     test_groth16_proving_and_verification(ASSET_DIR, &proof);
     verify_query2_solidity_fun(ASSET_DIR, &query, TestQueryResult::Erc20(TEST_ERC20_RESULT));
+    verify_public_input_commitment(ASSET_DIR);
+    verify_altered_query_rejected(ASSET_DIR, &query);
+    verify_gas_under_threshold(ASSET_DIR);
+    let erc20_proof = read_file(Path::new(ASSET_DIR).join("full_proof.bin")).unwrap();
+
+    // Verify both the NFT and ERC20 query proofs in one Solidity call.
+    verify_batch_solidity_fun(&nft_proof, &erc20_proof);
 }
 
-/// Verify the Query2 Solidity function.
-fn verify_query2_solidity_fun(asset_dir: &str, query: &TestQuery, query_result: TestQueryResult) {
-    let solidity_file_path = Path::new("test_data")
+/// Copy the generated `verifier.sol` to the test data dir, and regenerate the
+/// query verifier `TestGroth16Verifier.sol` from it and the extension contracts.
+fn regenerate_test_data_verifiers(asset_dir: &str) {
+    let verifier = read_file(Path::new(asset_dir).join(SOLIDITY_VERIFIER_FILENAME)).unwrap();
+    write_file(
+        Path::new(TEST_DATA_DIR).join(SOLIDITY_VERIFIER_FILENAME),
+        &verifier,
+    )
+    .unwrap();
+
+    generate_query_verifier_contract(
+        asset_dir,
+        &format!("{TEST_DATA_DIR}/Groth16VerifierExtensions.sol"),
+        TEST_DATA_DIR,
+    )
+    .expect("Failed to generate the query verifier contract");
+}
+
+/// Verify the public input commitment computed off-chain is the value the
+/// Solidity verifier derives from the plonky2 public inputs of the full proof,
+/// i.e. the last Groth16 input.
+fn verify_public_input_commitment(asset_dir: &str) {
+    // The full proof is made of 8 Groth16 proof items and 3 Groth16 inputs, each
+    // one a little-endian uint256, followed by the plonky2 public inputs.
+    let proof_bytes = read_file(Path::new(asset_dir).join("full_proof.bin")).unwrap();
+    let (groth16_bytes, pi_bytes) = proof_bytes.split_at(11 * 32);
+    let last_groth16_input = U256::from_little_endian(&groth16_bytes[10 * 32..]);
+    let pis: Vec<_> = pi_bytes
+        .chunks(8)
+        .map(|b| F::from_canonical_u64(u64::from_le_bytes(b.try_into().unwrap())))
+        .collect();
+
+    let commitment = public_input_commitment(&pis);
+    assert_eq!(U256::from_big_endian(&commitment), last_groth16_input);
+}
+
+/// Verify the gas used by the full proof verification of the checked-in
+/// Solidity verifier stays under the threshold.
+fn verify_gas_under_threshold(asset_dir: &str) {
+    let proof_bytes = read_file(Path::new(asset_dir).join("full_proof.bin")).unwrap();
+
+    let gas_used = estimate_verification_gas(TEST_DATA_DIR, &proof_bytes)
+        .expect("Failed to estimate the verification gas");
+
+    assert!(
+        gas_used < MAX_VERIFICATION_GAS,
+        "Verification gas {gas_used} exceeds the threshold {MAX_VERIFICATION_GAS}"
+    );
+}
+
+/// Build the Solidity verifier and the calldata of `processQuery` for the
+/// full proof saved in the asset dir and the expected query.
+fn build_process_query_call(
+    asset_dir: &str,
+    query: &TestQuery,
+) -> (EVMVerifier, Function, Vec<u8>) {
+    let solidity_file_path = Path::new(TEST_DATA_DIR)
         .join("TestGroth16Verifier.sol")
         .to_string_lossy()
         .to_string();
 
     let contract = Contract::load(
-        read_file(Path::new("test_data").join("TestGroth16Verifier.abi"))
+        read_file(Path::new(TEST_DATA_DIR).join("TestGroth16Verifier.abi"))
             .unwrap()
             .as_slice(),
     )
@@ -97,11 +174,14 @@ fn verify_query2_solidity_fun(asset_dir: &str, query: &TestQuery, query_result:
         Token::Uint(query.rewards_rate.into()),
         Token::Uint(query.identifier.into()),
         Token::FixedBytes(block_hash_bytes),
+        Token::Uint(query.leaf_mode.into()),
+        Token::Uint(query.threshold),
+        Token::Uint(query.max_rewards_rate),
     ]);
 
     // Build the ABI encoded data.
     let args = vec![data, query];
-    let fun = &contract.functions["processQuery"][0];
+    let fun = contract.functions["processQuery"][0].clone();
     let calldata = fun
         .encode_input(&args)
         .expect("Failed to encode the inputs of Solidity respond function");
@@ -109,6 +189,13 @@ fn verify_query2_solidity_fun(asset_dir: &str, query: &TestQuery, query_result:
     let verifier =
         EVMVerifier::new(&solidity_file_path).expect("Failed to initialize the EVM verifier");
 
+    (verifier, fun, calldata)
+}
+
+/// Verify the Query2 Solidity function.
+fn verify_query2_solidity_fun(asset_dir: &str, query: &TestQuery, query_result: TestQueryResult) {
+    let (verifier, fun, calldata) = build_process_query_call(asset_dir, query);
+
     // Verify in Solidity.
     let output = verifier
         .verify(calldata)
@@ -133,3 +220,86 @@ fn verify_query2_solidity_fun(asset_dir: &str, query: &TestQuery, query_result:
     // Check the returned query result.
     query_result.enforce_equal(&real_result);
 }
+
+/// Verify the NFT and ERC20 query proofs together in one call of the
+/// `processQueryBatch` Solidity function, and check a tampered proof of the
+/// batch is rejected without failing the others.
+fn verify_batch_solidity_fun(nft_proof: &[u8], erc20_proof: &[u8]) {
+    let solidity_file_path = Path::new(TEST_DATA_DIR)
+        .join("TestGroth16Verifier.sol")
+        .to_string_lossy()
+        .to_string();
+    let contract = Contract::load(
+        read_file(Path::new(TEST_DATA_DIR).join("TestGroth16Verifier.abi"))
+            .unwrap()
+            .as_slice(),
+    )
+    .expect("Failed to load the Solidity verifier contract from ABI");
+    let verifier =
+        EVMVerifier::new(&solidity_file_path).expect("Failed to initialize the EVM verifier");
+
+    let (_, results) = verifier
+        .verify_batch(&contract, &[nft_proof, erc20_proof])
+        .expect("Failed to verify the batch in Solidity");
+    assert_eq!(results.len(), 2);
+    TestQueryResult::NftIds(TEST_NFT_IDS).enforce_equal(results[0].as_ref().unwrap());
+    TestQueryResult::Erc20(TEST_ERC20_RESULT).enforce_equal(results[1].as_ref().unwrap());
+
+    // Alter the last plonky2 public input, then it mismatches the commitment
+    // in the Groth16 inputs.
+    let mut tampered_proof = nft_proof.to_vec();
+    *tampered_proof.last_mut().unwrap() ^= 1;
+    let (_, results) = verifier
+        .verify_batch(&contract, &[&tampered_proof, erc20_proof])
+        .expect("Failed to verify the batch in Solidity");
+    assert!(results[0].is_none());
+    TestQueryResult::Erc20(TEST_ERC20_RESULT).enforce_equal(results[1].as_ref().unwrap());
+}
+
+/// Verify the Solidity function rejects the proof if any field of the query
+/// it has been generated for is altered.
+fn verify_altered_query_rejected(asset_dir: &str, query: &TestQuery) {
+    let other_address = Address::random();
+    let altered_queries = [
+        TestQuery {
+            contract_address: other_address,
+            ..query.clone()
+        },
+        TestQuery {
+            user_address: other_address,
+            ..query.clone()
+        },
+        TestQuery {
+            min_block_number: query.min_block_number - 1,
+            ..query.clone()
+        },
+        TestQuery {
+            max_block_number: query.max_block_number + 1,
+            ..query.clone()
+        },
+        TestQuery {
+            rewards_rate: query.rewards_rate + 1,
+            ..query.clone()
+        },
+        TestQuery {
+            leaf_mode: query.leaf_mode + 1,
+            ..query.clone()
+        },
+        TestQuery {
+            threshold: query.threshold + 1,
+            ..query.clone()
+        },
+        TestQuery {
+            max_rewards_rate: query.max_rewards_rate - 1,
+            ..query.clone()
+        },
+    ];
+
+    altered_queries.iter().for_each(|altered_query| {
+        let (verifier, _, calldata) = build_process_query_call(asset_dir, altered_query);
+        assert!(
+            verifier.verify(calldata).is_err(),
+            "The Solidity verification must fail for the altered query {altered_query:?}"
+        );
+    });
+}
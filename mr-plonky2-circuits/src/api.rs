@@ -1,14 +1,24 @@
-use anyhow::Result;
+use anyhow::{anyhow, ensure, Result};
+use ethers::types::{Address, U256};
 use mrp2_utils::serialization::{
     circuit_data_serialization::SerializableRichField, deserialize, serialize,
 };
 use plonky2::{
-    hash::poseidon::PoseidonHash,
+    field::types::PrimeField64,
+    hash::{
+        hash_types::{HashOut, NUM_HASH_OUT_ELTS},
+        poseidon::PoseidonHash,
+    },
     iop::witness::PartialWitness,
     plonk::{
         circuit_builder::CircuitBuilder,
-        circuit_data::{CircuitConfig, CircuitData, VerifierCircuitData, VerifierOnlyCircuitData},
-        config::{AlgebraicHasher, GenericConfig, Hasher, PoseidonGoldilocksConfig},
+        circuit_data::{
+            CircuitConfig, CircuitData, CommonCircuitData, VerifierCircuitData,
+            VerifierOnlyCircuitData,
+        },
+        config::{
+            AlgebraicHasher, GenericConfig, GenericHashOut, Hasher, PoseidonGoldilocksConfig,
+        },
         proof::{ProofWithPublicInputs, ProofWithPublicInputsTarget},
     },
 };
@@ -16,6 +26,7 @@ use recursion_framework::framework::{
     RecursiveCircuits, RecursiveCircuitsVerifierGagdet, RecursiveCircuitsVerifierTarget,
 };
 use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
 
 pub use crate::storage::{
     self,
@@ -30,9 +41,16 @@ pub use crate::state::{
 
 use crate::{
     block::Inputs,
-    query2::{self, revelation::num_io},
-    query_erc20,
+    query2::{
+        self,
+        revelation::{identifier_for, num_io},
+    },
+    query_erc20::{
+        self, BlockCircuitInput, LeafMode, RevelationErcInput, RewardFormula, StateCircuitInput,
+        StorageCircuitInput,
+    },
     state::{block_linking, lpn::api::ProofInputs},
+    types::HashOutput,
 };
 
 use self::storage::{digest_equal, length_match};
@@ -116,6 +134,17 @@ impl<const MAX_DEPTH: usize> BlockDBCircuitInfo<MAX_DEPTH> {
     }
 }
 
+/// Estimate the number of bytes allocated to generate a proof for the circuit
+/// described by `common`, i.e. the low-degree extensions of the wires, of the
+/// `Z` and partial products polynomials and of the quotient polynomials, which
+/// dominate the memory footprint of the prover.
+pub(crate) fn estimate_proving_memory(common: &CommonCircuitData<F, D>) -> usize {
+    let num_polys = common.config.num_wires
+        + common.num_zs_partial_products_polys()
+        + common.num_quotient_polys();
+    num_polys * common.lde_size() * std::mem::size_of::<F>()
+}
+
 /// Retrieve a common `CircuitConfig` to be employed to generate the parameters for the circuits
 /// employed for the pre-processing statge of LPN
 pub(crate) fn default_config() -> CircuitConfig {
@@ -124,7 +153,7 @@ pub(crate) fn default_config() -> CircuitConfig {
 /// Instantiate the circuits employed for the pre-processing stage of LPN, returning their
 /// corresponding parameters
 pub fn build_circuits_params<const MAX_DEPTH: usize>() -> PublicParameters<MAX_DEPTH> {
-    let mapping = mapping::build_circuits_params();
+    let mapping: mapping::PublicParameters = mapping::build_circuits_params();
     let length_extract = length_extract::PublicParameters::build();
     let length_match = length_match::Parameters::build(
         mapping.get_mapping_circuit_set(),
@@ -271,6 +300,42 @@ where
     pub fn circuit_data(&self) -> &CircuitData<F, C, D> {
         &self.circuit_data
     }
+
+    /// Check that `wrapped`, a proof generated by this wrap circuit, is valid and that it
+    /// wraps the revelation proof `inner`, i.e., it exposes the same revelation public
+    /// inputs as `inner`. The query identifier, exposed as the last public input, is not
+    /// compared, as it only depends on the circuit that generated `inner`.
+    pub fn verify_wraps(&self, inner: &ProofWithVK, wrapped: &[u8]) -> Result<()> {
+        let wrapped = deserialize_proof::<F, C, D>(wrapped)?;
+        let wrapped_pis = wrapped.public_inputs.clone();
+        self.circuit_data.verify(wrapped)?;
+        let inner_pis = &inner.proof().public_inputs;
+        ensure!(
+            wrapped_pis.len() == num_io::<L>() && inner_pis.len() >= num_io::<L>(),
+            "unexpected number of public inputs: {} in the wrapped proof, {} in the inner one",
+            wrapped_pis.len(),
+            inner_pis.len()
+        );
+        // revelation public inputs come before the identifier
+        let num_revelation_pis = num_io::<L>() - 1;
+        ensure!(
+            wrapped_pis[..num_revelation_pis] == inner_pis[..num_revelation_pis],
+            "wrapped proof doesn't expose the public inputs of the inner revelation proof"
+        );
+
+        Ok(())
+    }
+
+    /// Compute the digest of the set of query circuits whose proofs can be wrapped by a
+    /// wrap circuit built over the circuits with digests `members`. The digest depends
+    /// on the order of `members`, which must be the same order employed to build the
+    /// `RecursiveCircuits` set given to `build`.
+    pub fn expected_set_digest(members: &[HashOut<F>]) -> HashOut<F> {
+        let set_digest = RecursiveCircuits::<F, C, D>::new_from_circuit_digests(members.to_vec())
+            .get_circuit_set_digest()
+            .flatten();
+        HashOut::from_vec(set_digest)
+    }
 }
 
 /// Parameters for circuits proving queries
@@ -353,6 +418,398 @@ pub enum QueryInput<const L: usize> {
     QueryErc(query_erc20::CircuitInput<L>),
 }
 
+/// Type of a query, identified in the revelation proofs by the public input
+/// registered by the revelation circuit of each query
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum QueryType {
+    /// NFT query, revealing the NFT IDs owned by the user over the block range
+    Nft,
+    /// ERC-20 query, computing the rewards of the user over the block range
+    Erc20,
+}
+
+impl QueryType {
+    /// Identifier of the query type registered as the last public input of the
+    /// revelation proofs, i.e. `keccak256(name)[0]`
+    pub fn identifier(&self) -> u8 {
+        let name = match self {
+            QueryType::Nft => "QueryNFT",
+            QueryType::Erc20 => "QueryERC20",
+        };
+        identifier_for(name)
+    }
+}
+
+/// Get the type of the query proven by `wrapped_proof`, a proof returned by
+/// `QueryParameters::generate_proof` for revelation inputs, by matching the query
+/// identifier exposed as the last public input of the proof
+pub fn query_type_of(wrapped_proof: &[u8]) -> Result<QueryType> {
+    let proof = deserialize_proof::<F, C, D>(wrapped_proof)?;
+    let identifier = proof
+        .public_inputs
+        .last()
+        .ok_or(anyhow!("wrapped proof has no public inputs"))?
+        .to_canonical_u64();
+    [QueryType::Nft, QueryType::Erc20]
+        .into_iter()
+        .find(|query_type| query_type.identifier() as u64 == identifier)
+        .ok_or(anyhow!("unknown query identifier: {identifier}"))
+}
+
+/// Self-describing answer to a query, bundling the final wrapped proof with the
+/// query it answers, the root of the block DB it was proven against and the
+/// identifier of the query type, so that a single artifact can be shipped to clients
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProofEnvelope {
+    /// Final wrapped proof, as returned by `QueryParameters::generate_proof` for
+    /// revelation inputs
+    pub proof: Vec<u8>,
+    /// Query proven by `proof`
+    pub query: QuerySpec,
+    /// Root of the block DB the query was proven against
+    pub block_db_root: HashOutput,
+    /// Identifier of the query type, exposed as the last public input of `proof`
+    pub identifier: u8,
+}
+
+impl ProofEnvelope {
+    /// Bundle `proof` with the `query` it answers and the `block_db_root` it was
+    /// proven against; it fails if `proof` doesn't prove a query of the same type
+    pub fn new(proof: Vec<u8>, query: QuerySpec, block_db_root: HashOutput) -> Result<Self> {
+        let envelope = Self {
+            identifier: query.query_type.identifier(),
+            proof,
+            query,
+            block_db_root,
+        };
+        envelope.check_consistency()?;
+        Ok(envelope)
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        Ok(bincode::serialize(self)?)
+    }
+
+    /// Decode an envelope encoded with `to_bytes`, checking that the identifier
+    /// matches both the type of the query and the one exposed by the proof
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let envelope: Self = bincode::deserialize(bytes)?;
+        envelope.check_consistency()?;
+        Ok(envelope)
+    }
+
+    fn check_consistency(&self) -> Result<()> {
+        ensure!(
+            self.identifier == self.query.query_type.identifier(),
+            "identifier {} doesn't match the query type {:?}",
+            self.identifier,
+            self.query.query_type,
+        );
+        let proven_type = query_type_of(&self.proof)?;
+        ensure!(
+            proven_type == self.query.query_type,
+            "proof of a {proven_type:?} query provided for a {:?} query",
+            self.query.query_type,
+        );
+        Ok(())
+    }
+}
+
+/// Description of a query proven by `prove_query`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuerySpec {
+    /// Type of the query
+    pub query_type: QueryType,
+    /// Rewards rate of the query, only employed by ERC-20 queries: the rate of the
+    /// proportional rewards, or the reward granted to each holder with flat rewards.
+    /// It must be zero in `LeafMode::Threshold`.
+    pub rewards_rate: U256,
+    /// Value computed over the balance of the user, only employed by ERC-20 queries
+    pub leaf_mode: LeafMode,
+    /// Formula computing the rewards in `LeafMode::Rewards`, only employed by ERC-20
+    /// queries
+    pub reward_formula: RewardFormula,
+    /// Threshold the balance is compared to in `LeafMode::Threshold`, or minimum
+    /// balance of `RewardFormula::FlatPerHolder`; it must be zero for proportional
+    /// rewards. Only employed by ERC-20 queries.
+    pub threshold: U256,
+    /// Decimals of the token the result is scaled by, only employed by ERC-20 queries
+    pub decimals: u8,
+    /// Address of the queried contract
+    pub contract_address: Address,
+    /// Address of the user the query is about
+    pub user_address: Address,
+    /// Slot of the mapping in the contract storage
+    pub mapping_slot: u32,
+    /// Slot of the length variable in the contract storage
+    pub length_slot: u32,
+    /// First block of the queried range
+    pub min_block: usize,
+    /// Last block of the queried range
+    pub max_block: usize,
+    /// Migrations of the mapping to a new slot, as `(block, slot)` pairs: the
+    /// mapping is stored at `slot` starting from `block`, and at `mapping_slot`
+    /// before the first migration. The migrations are committed in the block
+    /// proofs, so that blocks on either side of a migration can be aggregated.
+    pub slot_remaps: Vec<(u32, u32)>,
+}
+
+impl QuerySpec {
+    /// Slot of the mapping in the contract storage at block `block_number`
+    pub fn mapping_slot_at(&self, block_number: u32) -> u32 {
+        query_erc20::slot_at_block(block_number, &self.slot_remaps).unwrap_or(self.mapping_slot)
+    }
+
+    /// Input of the storage leaf of the user in `block`, proven with the circuit
+    /// of the leaf mode and reward formula of the query
+    fn storage_leaf_input(&self, block: &QueryBlockData) -> StorageCircuitInput {
+        let user = self.user_address;
+        match (self.leaf_mode, self.reward_formula) {
+            (LeafMode::Rewards, RewardFormula::Proportional) => StorageCircuitInput::new_leaf(
+                user,
+                user,
+                block.balance,
+                block.total_supply,
+                self.rewards_rate,
+            ),
+            (LeafMode::Rewards, RewardFormula::FlatPerHolder) => {
+                StorageCircuitInput::new_flat_per_holder_leaf(
+                    user,
+                    user,
+                    block.balance,
+                    self.rewards_rate,
+                    self.threshold,
+                )
+            }
+            (LeafMode::Threshold, _) => {
+                StorageCircuitInput::new_threshold_leaf(user, user, block.balance, self.threshold)
+            }
+        }
+    }
+}
+
+/// Data of a block of the queried range, needed to prove the storage and state
+/// trees of the block
+#[derive(Clone, Debug)]
+pub struct QueryBlockData {
+    /// Number of the block
+    pub block_number: u32,
+    /// Hash of the block
+    pub block_hash: HashOutput,
+    /// Balance of the user in the block
+    pub balance: U256,
+    /// Total supply of the token in the block
+    pub total_supply: U256,
+    /// Opening of the storage tree, from the leaf of the user up to the root:
+    /// each entry is the hash of the sibling, together with a flag specifying
+    /// whether the node on the path is the right child
+    pub storage_path: Vec<(HashOutput, bool)>,
+    /// Siblings of the opening of the state tree for the contract
+    pub state_siblings: Vec<HashOutput>,
+    /// Positions of the opening of the state tree for the contract
+    pub state_positions: Vec<bool>,
+}
+
+/// Shape of the tree of blocks aggregated by a query, mirroring the block DB
+/// tree over the queried range
+#[derive(Clone, Debug)]
+pub enum QueryBlockTree {
+    /// A block of the queried range
+    Leaf(QueryBlockData),
+    /// A node whose children are both in the queried range
+    Full(Box<QueryBlockTree>, Box<QueryBlockTree>),
+    /// A node with only one child in the queried range; the other child is
+    /// only provided by its hash
+    Partial {
+        child: Box<QueryBlockTree>,
+        sibling_hash: HashOutput,
+        sibling_is_left: bool,
+    },
+}
+
+/// Data of the chain needed to prove a query with `prove_query`
+#[derive(Clone, Debug)]
+pub struct ChainInputs {
+    /// Tree of blocks aggregated by the query
+    pub block_tree: QueryBlockTree,
+    /// Proof of the block DB, whose root must be the one of `block_tree`
+    pub block_db_proof: Vec<u8>,
+}
+
+/// Prove `query` over the data of the chain provided in `chain_inputs`, running
+/// the whole pipeline: for each block, the storage and state circuits are proven,
+/// then the block proofs are aggregated following the shape of the block tree,
+/// and finally the revelation proof is generated and wrapped. The method returns
+/// the final wrapped proof, as the one returned by `QueryParameters::generate_proof`
+/// for revelation inputs.
+pub fn prove_query<const MAX_DEPTH: usize, const L: usize>(
+    params: &QueryParameters<MAX_DEPTH, L>,
+    query: QuerySpec,
+    chain_inputs: ChainInputs,
+) -> Result<Vec<u8>>
+where
+    [(); query2::revelation::num_io::<L>()]:,
+    [(); query_erc20::revelation::num_io::<L>()]:,
+    [(); <PoseidonHash as Hasher<F>>::HASH_SIZE]:,
+{
+    prove_query_timed(params, query, chain_inputs).map(|(proof, _)| proof)
+}
+
+/// Wall-clock time spent in each logical phase of the query pipeline, as measured
+/// by `prove_query_timed`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Timings {
+    /// Time spent proving the storage trees of all the blocks
+    pub storage: Duration,
+    /// Time spent proving the state trees of all the blocks
+    pub state: Duration,
+    /// Time spent aggregating the block proofs
+    pub block: Duration,
+    /// Time spent generating the revelation proof
+    pub revelation: Duration,
+    /// Time spent wrapping the revelation proof
+    pub wrap: Duration,
+}
+
+impl Timings {
+    /// Overall time spent over all the phases
+    pub fn total(&self) -> Duration {
+        self.storage + self.state + self.block + self.revelation + self.wrap
+    }
+}
+
+/// Run `f`, adding the time it takes to `elapsed`
+fn timed<T>(elapsed: &mut Duration, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let res = f();
+    *elapsed += start.elapsed();
+    res
+}
+
+/// Same as `prove_query`, but it also returns the time spent in each phase of
+/// the pipeline, to track performance regressions
+pub fn prove_query_timed<const MAX_DEPTH: usize, const L: usize>(
+    params: &QueryParameters<MAX_DEPTH, L>,
+    query: QuerySpec,
+    chain_inputs: ChainInputs,
+) -> Result<(Vec<u8>, Timings)>
+where
+    [(); query2::revelation::num_io::<L>()]:,
+    [(); query_erc20::revelation::num_io::<L>()]:,
+    [(); <PoseidonHash as Hasher<F>>::HASH_SIZE]:,
+{
+    ensure!(
+        query.query_type == QueryType::Erc20,
+        "only ERC-20 queries can be proven with prove_query"
+    );
+    let mut timings = Timings::default();
+    let block_proof =
+        params.prove_erc20_block_tree_timed(&query, chain_inputs.block_tree, &mut timings)?;
+    let input = query_erc20::CircuitInput::Revelation(RevelationErcInput::new(
+        query.min_block,
+        query.max_block,
+        query.decimals,
+        block_proof,
+        chain_inputs.block_db_proof,
+    )?);
+    let (revelation_proof, _) = timed(&mut timings.revelation, || {
+        params
+            .query_erc_params
+            .generate_proof(input, &params.query_circuit_set)
+    })?;
+    let proof = timed(&mut timings.wrap, || {
+        params.wrap_circuit.generate_proof(
+            &params.query_circuit_set,
+            &ProofWithVK::deserialize(&revelation_proof)?,
+        )
+    })?;
+
+    Ok((proof, timings))
+}
+
+impl<const MAX_DEPTH: usize, const L: usize> QueryParameters<MAX_DEPTH, L>
+where
+    [(); query2::revelation::num_io::<L>()]:,
+    [(); query_erc20::revelation::num_io::<L>()]:,
+    [(); <PoseidonHash as Hasher<F>>::HASH_SIZE]:,
+{
+    /// Generate the proof of the root of the ERC-20 query block tree `tree`
+    pub(crate) fn prove_erc20_block_tree(
+        &self,
+        query: &QuerySpec,
+        tree: QueryBlockTree,
+    ) -> Result<Vec<u8>> {
+        self.prove_erc20_block_tree_timed(query, tree, &mut Timings::default())
+    }
+
+    /// Same as `prove_erc20_block_tree`, but it also adds to `timings` the time spent
+    /// in the storage, state and block phases
+    fn prove_erc20_block_tree_timed(
+        &self,
+        query: &QuerySpec,
+        tree: QueryBlockTree,
+        timings: &mut Timings,
+    ) -> Result<Vec<u8>> {
+        let prove = |input| self.generate_proof(QueryInput::QueryErc(input));
+        match tree {
+            QueryBlockTree::Leaf(block) => {
+                let storage_proof = timed(&mut timings.storage, || {
+                    let mut storage_proof = prove(query_erc20::CircuitInput::Storage(
+                        query.storage_leaf_input(&block),
+                    ))?;
+                    for (sibling_hash, proved_is_right) in &block.storage_path {
+                        let (left, right) = if *proved_is_right {
+                            (sibling_hash.as_slice(), storage_proof.as_slice())
+                        } else {
+                            (storage_proof.as_slice(), sibling_hash.as_slice())
+                        };
+                        let input =
+                            StorageCircuitInput::new_inner_node(left, right, *proved_is_right);
+                        storage_proof = prove(query_erc20::CircuitInput::Storage(input))?;
+                    }
+                    Ok::<_, anyhow::Error>(storage_proof)
+                })?;
+                let input = StateCircuitInput::from_storage_proof(
+                    storage_proof,
+                    block.block_number,
+                    (query.mapping_slot, query.length_slot),
+                    query.contract_address,
+                    block.block_hash,
+                    &block.state_siblings,
+                    &block.state_positions,
+                )?
+                .with_slot_remaps(&query.slot_remaps)?;
+                timed(&mut timings.state, || {
+                    prove(query_erc20::CircuitInput::State(input))
+                })
+            }
+            QueryBlockTree::Full(left, right) => {
+                let left_proof = self.prove_erc20_block_tree_timed(query, *left, timings)?;
+                let right_proof = self.prove_erc20_block_tree_timed(query, *right, timings)?;
+                let input = BlockCircuitInput::new_full_node(left_proof, right_proof)?;
+                timed(&mut timings.block, || {
+                    prove(query_erc20::CircuitInput::Block(input))
+                })
+            }
+            QueryBlockTree::Partial {
+                child,
+                sibling_hash,
+                sibling_is_left,
+            } => {
+                let child_proof = self.prove_erc20_block_tree_timed(query, *child, timings)?;
+                let input = BlockCircuitInput::new_partial_node(
+                    child_proof,
+                    sibling_hash,
+                    sibling_is_left,
+                )?;
+                timed(&mut timings.block, || {
+                    prove(query_erc20::CircuitInput::Block(input))
+                })
+            }
+        }
+    }
+}
+
 /// ProofWithVK is a generic struct holding a child proof and its associated verification key.
 #[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
 pub struct ProofWithVK {
@@ -361,17 +818,77 @@ pub struct ProofWithVK {
     pub(crate) vk: VerifierOnlyCircuitData<C, D>,
 }
 
+/// Magic bytes prefixing the serialization of a `ProofWithVK`
+pub const PROOF_WITH_VK_MAGIC: [u8; 4] = *b"MRPV";
+/// Version of the encoding of `ProofWithVK` employed by its `serialize` and
+/// `deserialize` methods. It must be bumped whenever the circuits change in a
+/// way that makes proofs generated with previous parameters incompatible, so
+/// that such proofs are rejected when deserialized rather than failing later
+/// at verification.
+pub const PROOF_WITH_VK_ENCODING_VERSION: u8 = 1;
+/// Length of the tag `magic || version || circuit digest` prefixing a
+/// serialized `ProofWithVK`
+const PROOF_WITH_VK_TAG_LEN: usize = PROOF_WITH_VK_MAGIC.len() + 1 + NUM_HASH_OUT_ELTS * 8;
+
 impl ProofWithVK {
-    /// serialize a `ProofWithVK`
+    /// serialize a `ProofWithVK`, prefixed by a tag made of magic bytes, the
+    /// encoding version and the digest of the circuit that generated the proof
     pub fn serialize(&self) -> Result<Vec<u8>> {
-        let buff = bincode::serialize(&self)?;
+        let mut buff = PROOF_WITH_VK_MAGIC.to_vec();
+        buff.push(PROOF_WITH_VK_ENCODING_VERSION);
+        buff.extend(self.vk.circuit_digest.to_bytes());
+        buff.extend(bincode::serialize(&self)?);
         Ok(buff)
     }
-    /// deserialize a `ProofWithVK`
+    /// deserialize a `ProofWithVK` serialized with `serialize`, failing if the
+    /// tag prefixing the proof is not the expected one
     pub fn deserialize(buff: &[u8]) -> Result<Self> {
-        let s = bincode::deserialize(buff)?;
+        let (circuit_digest, payload) = Self::decode_tag(buff)?;
+        let s: Self = bincode::deserialize(payload)?;
+        ensure!(
+            s.vk.circuit_digest == circuit_digest,
+            "serialized proof tagged with circuit digest {:?}, but generated by circuit with digest {:?}",
+            circuit_digest,
+            s.vk.circuit_digest,
+        );
         Ok(s)
     }
+    /// deserialize a `ProofWithVK` serialized with `serialize`, checking that
+    /// the proof has been generated by the circuit with verifier data `expected_vk`
+    pub fn deserialize_for_circuit(
+        buff: &[u8],
+        expected_vk: &VerifierOnlyCircuitData<C, D>,
+    ) -> Result<Self> {
+        let (circuit_digest, _) = Self::decode_tag(buff)?;
+        ensure!(
+            circuit_digest == expected_vk.circuit_digest,
+            "serialized proof generated by circuit with digest {:?}, expected {:?}",
+            circuit_digest,
+            expected_vk.circuit_digest,
+        );
+        Self::deserialize(buff)
+    }
+
+    /// Decode the tag prefixing a serialized `ProofWithVK`, returning the
+    /// circuit digest found in the tag and the serialized proof
+    fn decode_tag(buff: &[u8]) -> Result<(HashOut<F>, &[u8])> {
+        ensure!(
+            buff.len() >= PROOF_WITH_VK_TAG_LEN,
+            "serialized proof is too short"
+        );
+        let (magic, rest) = buff.split_at(PROOF_WITH_VK_MAGIC.len());
+        ensure!(
+            magic == PROOF_WITH_VK_MAGIC,
+            "serialized proof doesn't start with the expected magic bytes"
+        );
+        ensure!(
+            rest[0] == PROOF_WITH_VK_ENCODING_VERSION,
+            "unsupported encoding version of serialized proof: expected {PROOF_WITH_VK_ENCODING_VERSION}, found {}",
+            rest[0]
+        );
+        let (digest, payload) = rest[1..].split_at(NUM_HASH_OUT_ELTS * 8);
+        Ok((HashOut::from_bytes(digest), payload))
+    }
 
     pub(crate) fn proof(&self) -> &ProofWithPublicInputs<F, C, D> {
         &self.proof
@@ -380,6 +897,59 @@ impl ProofWithVK {
     pub(crate) fn verifier_data(&self) -> &VerifierOnlyCircuitData<C, D> {
         &self.vk
     }
+
+    /// Number of public inputs of the proof, which depends on the circuit that
+    /// generated it; it allows to check the public inputs before slicing them
+    pub fn num_public_inputs(&self) -> usize {
+        self.proof.public_inputs.len()
+    }
+
+    /// Public inputs of the proof, including the ones possibly added by the
+    /// recursion framework after the public inputs of the circuit
+    pub fn public_inputs(&self) -> &[F] {
+        &self.proof.public_inputs
+    }
+
+    /// Return the first `num_io` public inputs of the proof, failing if the
+    /// proof exposes less public inputs than the ones expected for `circuit`
+    fn circuit_public_inputs(&self, num_io: usize, circuit: &str) -> Result<&[F]> {
+        ensure!(
+            self.num_public_inputs() >= num_io,
+            "{circuit} proof expected to have at least {num_io} public inputs, found {}",
+            self.num_public_inputs()
+        );
+        Ok(&self.proof.public_inputs[..num_io])
+    }
+
+    /// Public inputs of a proof generated by the block circuits of the ERC20 query
+    pub fn block_public_inputs(&self) -> Result<query_erc20::block::BlockPublicInputs<F>> {
+        self.circuit_public_inputs(query_erc20::block::NUM_IO, "block")
+            .map(query_erc20::block::BlockPublicInputs::from)
+    }
+
+    /// Public inputs of a proof generated by the revelation circuit of the ERC20
+    /// query for `L` revealed items
+    pub fn revelation_public_inputs<const L: usize>(
+        &self,
+    ) -> Result<query_erc20::revelation::RevelationPublicInputs<F, L>> {
+        self.circuit_public_inputs(
+            query_erc20::revelation::RevelationPublicInputs::<F, L>::total_len(),
+            "revelation",
+        )
+        .map(query_erc20::revelation::RevelationPublicInputs::from)
+    }
+
+    /// Check that `self` and `other` are proofs generated by the same circuit,
+    /// i.e., that they have the same verifier data
+    pub fn assert_same_circuit(&self, other: &ProofWithVK) -> Result<()> {
+        ensure!(
+            self.vk == other.vk,
+            "proofs generated by different circuits: circuit digests {:?} and {:?}",
+            self.vk.circuit_digest,
+            other.vk.circuit_digest,
+        );
+        Ok(())
+    }
 }
 
 impl
@@ -397,6 +967,33 @@ impl
         ProofWithVK { proof, vk }
     }
 }
+/// Version of the encoding of circuit inputs employed by the `to_bytes` and
+/// `from_bytes` methods of the query `CircuitInput` enums. It must be bumped
+/// whenever the encoding of any variant changes, so that inputs queued with a
+/// previous encoding are rejected instead of being wrongly decoded.
+pub const CIRCUIT_INPUT_ENCODING_VERSION: u8 = 4;
+
+/// Encode a circuit input as `version || tag || payload`, where `tag` explicitly
+/// identifies the variant of the input and `payload` is the bincode serialization
+/// of the data of the variant
+pub(crate) fn encode_circuit_input<T: Serialize>(tag: u8, payload: &T) -> Result<Vec<u8>> {
+    let mut bytes = vec![CIRCUIT_INPUT_ENCODING_VERSION, tag];
+    bytes.extend(bincode::serialize(payload)?);
+    Ok(bytes)
+}
+
+/// Decode the version and the tag of a circuit input encoded with
+/// `encode_circuit_input`, returning the tag and the payload of the input
+pub(crate) fn decode_circuit_input(bytes: &[u8]) -> Result<(u8, &[u8])> {
+    ensure!(bytes.len() >= 2, "encoded circuit input is too short");
+    ensure!(
+        bytes[0] == CIRCUIT_INPUT_ENCODING_VERSION,
+        "unsupported encoding version of circuit input: expected {CIRCUIT_INPUT_ENCODING_VERSION}, found {}",
+        bytes[0]
+    );
+    Ok((bytes[1], &bytes[2..]))
+}
+
 /// Serialize a proof
 pub fn serialize_proof<F: SerializableRichField<D>, C: GenericConfig<D, F = F>, const D: usize>(
     proof: &ProofWithPublicInputs<F, C, D>,
@@ -462,7 +1059,15 @@ pub(crate) mod tests {
     };
 
     use super::*;
+    use crate::{
+        block::{empty_merkle_root, public_inputs::BlockDbState, NUM_IVC_PUBLIC_INPUTS},
+        keccak::PACKED_HASH_LEN,
+        types::{PACKED_ADDRESS_LEN, PACKED_U256_LEN},
+        utils::keccak256,
+    };
     use anyhow::Result;
+    use itertools::Itertools;
+    use plonky2::{field::types::Sample, plonk::config::GenericHashOut};
     use plonky2::{
         iop::witness::PartialWitness,
         plonk::{
@@ -471,9 +1076,13 @@ pub(crate) mod tests {
             proof::ProofWithPublicInputs,
         },
     };
+    use rand::{thread_rng, Rng};
     use recursion_framework::{
-        circuit_builder::CircuitLogicWires, framework_testing::DummyCircuitWires,
+        circuit_builder::CircuitLogicWires,
+        framework_testing::{DummyCircuitWires, TestingRecursiveCircuits},
     };
+    use serial_test::serial;
+    use std::iter;
 
     /// Circuit that does nothing but can be passed as a children proof to some circuit when testing the aggregation
     /// logic. See state/block_linking/mod.rs tests for example.
@@ -509,6 +1118,15 @@ pub(crate) mod tests {
         }
     }
 
+    /// Generate a `ProofWithVK` for a `TestDummyCircuit` exposing `public_inputs`
+    pub(crate) fn dummy_proof_with_vk<const NUM_PUBLIC_INPUTS: usize>(
+        public_inputs: [F; NUM_PUBLIC_INPUTS],
+    ) -> ProofWithVK {
+        let circuit = TestDummyCircuit::<NUM_PUBLIC_INPUTS>::build();
+        let proof = circuit.generate_proof(public_inputs).unwrap();
+        (proof, circuit.circuit_data().verifier_only.clone()).into()
+    }
+
     // check that the closure $f actually panics, printing $msg as error message if the function
     // did not panic; this macro is employed in tests in place of #[should_panic] to ensure that a
     // panic occurred in the expected function rather than in other parts of the test
@@ -521,6 +1139,332 @@ pub(crate) mod tests {
 
     pub(crate) use check_panic;
 
+    #[test]
+    fn test_circuit_input_encoding() {
+        let payload = (42u32, vec![1u8, 2, 3]);
+        let bytes = encode_circuit_input(7, &payload).unwrap();
+        assert_eq!(bytes[0], CIRCUIT_INPUT_ENCODING_VERSION);
+        assert_eq!(bytes[1], 7);
+
+        let (tag, decoded) = decode_circuit_input(&bytes).unwrap();
+        assert_eq!(tag, 7);
+        assert_eq!(
+            bincode::deserialize::<(u32, Vec<u8>)>(decoded).unwrap(),
+            payload
+        );
+
+        // an input encoded with another version is rejected
+        let mut other_version = bytes.clone();
+        other_version[0] = CIRCUIT_INPUT_ENCODING_VERSION + 1;
+        assert!(decode_circuit_input(&other_version).is_err());
+        // an input without tag is rejected
+        assert!(decode_circuit_input(&bytes[..1]).is_err());
+    }
+
+    #[test]
+    fn test_query_type_of() {
+        // wrapped proofs expose the query identifier as the last public input
+        let wrapped_proof = |identifier: u8| {
+            let proof =
+                dummy_proof_with_vk([F::rand(), F::rand(), F::from_canonical_u8(identifier)]);
+            serialize_proof(&proof.proof).unwrap()
+        };
+
+        for query_type in [QueryType::Nft, QueryType::Erc20] {
+            let proof = wrapped_proof(query_type.identifier());
+            assert_eq!(query_type_of(&proof).unwrap(), query_type);
+        }
+        assert_eq!(
+            QueryType::Nft.identifier(),
+            keccak256("QueryNFT".as_bytes())[0]
+        );
+        assert_eq!(
+            QueryType::Erc20.identifier(),
+            keccak256("QueryERC20".as_bytes())[0]
+        );
+
+        // an unknown identifier is rejected
+        let unknown = (0..=u8::MAX)
+            .find(|id| {
+                ![QueryType::Nft, QueryType::Erc20]
+                    .iter()
+                    .any(|q| q.identifier() == *id)
+            })
+            .unwrap();
+        assert!(query_type_of(&wrapped_proof(unknown)).is_err());
+        // so are malformed proofs
+        assert!(query_type_of(&[1, 2, 3]).is_err());
+    }
+
+    #[test]
+    fn test_proof_envelope() {
+        let rng = &mut thread_rng();
+        let query = QuerySpec {
+            query_type: QueryType::Erc20,
+            rewards_rate: U256::from(rng.gen::<u64>()),
+            leaf_mode: LeafMode::Rewards,
+            reward_formula: RewardFormula::Proportional,
+            threshold: U256::zero(),
+            decimals: 18,
+            contract_address: Address::random(),
+            user_address: Address::random(),
+            mapping_slot: rng.gen(),
+            length_slot: rng.gen(),
+            min_block: 10,
+            max_block: 20,
+            slot_remaps: vec![],
+        };
+        let wrapped_proof = |identifier: u8| {
+            let proof = dummy_proof_with_vk([F::rand(), F::from_canonical_u8(identifier)]);
+            serialize_proof(&proof.proof).unwrap()
+        };
+        let block_db_root = rng.gen::<HashOutput>();
+
+        let envelope = ProofEnvelope::new(
+            wrapped_proof(QueryType::Erc20.identifier()),
+            query.clone(),
+            block_db_root,
+        )
+        .unwrap();
+        assert_eq!(envelope.identifier, QueryType::Erc20.identifier());
+        let bytes = envelope.to_bytes().unwrap();
+        assert_eq!(ProofEnvelope::from_bytes(&bytes).unwrap(), envelope);
+
+        // a tampered identifier is rejected
+        let tampered = ProofEnvelope {
+            identifier: QueryType::Nft.identifier(),
+            ..envelope.clone()
+        };
+        assert!(ProofEnvelope::from_bytes(&tampered.to_bytes().unwrap()).is_err());
+        // so is a proof for another query type
+        let other_proof = ProofEnvelope {
+            proof: wrapped_proof(QueryType::Nft.identifier()),
+            ..envelope.clone()
+        };
+        assert!(ProofEnvelope::from_bytes(&other_proof.to_bytes().unwrap()).is_err());
+        assert!(ProofEnvelope::new(
+            wrapped_proof(QueryType::Nft.identifier()),
+            query,
+            block_db_root
+        )
+        .is_err());
+        // and truncated envelopes
+        assert!(ProofEnvelope::from_bytes(&bytes[..bytes.len() / 2]).is_err());
+    }
+
+    #[test]
+    fn test_wrap_circuit_expected_set_digest() {
+        const L: usize = 2;
+        let members = [
+            TestDummyCircuit::<1>::build()
+                .circuit_data()
+                .verifier_only
+                .circuit_digest,
+            TestDummyCircuit::<2>::build()
+                .circuit_data()
+                .verifier_only
+                .circuit_digest,
+            TestDummyCircuit::<3>::build()
+                .circuit_data()
+                .verifier_only
+                .circuit_digest,
+        ];
+
+        let digest = WrapCircuitParams::<L>::expected_set_digest(&members);
+        // the digest is deterministic
+        assert_eq!(
+            digest,
+            WrapCircuitParams::<L>::expected_set_digest(&members)
+        );
+        // the digest is the same as the one of the circuit set employed to build the wrap circuit
+        let circuit_set = RecursiveCircuits::<F, C, D>::new_from_circuit_digests(members.to_vec());
+        assert_eq!(
+            digest.elements.to_vec(),
+            circuit_set.get_circuit_set_digest().flatten()
+        );
+
+        // reordering the members changes the digest, consistently with the circuit set
+        let reordered = [members[1], members[0], members[2]];
+        let reordered_digest = WrapCircuitParams::<L>::expected_set_digest(&reordered);
+        assert_ne!(digest, reordered_digest);
+        let reordered_set =
+            RecursiveCircuits::<F, C, D>::new_from_circuit_digests(reordered.to_vec());
+        assert_eq!(
+            reordered_digest.elements.to_vec(),
+            reordered_set.get_circuit_set_digest().flatten()
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn test_wrap_circuit_verify_wraps() {
+        const L: usize = 5;
+        const NUM_IO: usize = num_io::<L>();
+
+        let query_circuits = TestingRecursiveCircuits::<F, C, D, NUM_IO>::default();
+        let query_circuit_set = query_circuits.get_recursive_circuit_set();
+        let wrap_circuit = WrapCircuitParams::<L>::build(query_circuit_set);
+        let revelation_proof = |pis: Vec<F>| -> ProofWithVK {
+            let proof = query_circuits
+                .generate_input_proofs([pis.try_into().unwrap()])
+                .unwrap()[0]
+                .clone();
+            let vk = query_circuits.verifier_data_for_input_proofs::<1>()[0].clone();
+            (proof, vk).into()
+        };
+
+        // wrap an ERC20 revelation proof
+        let rng = &mut thread_rng();
+        let mut erc20_pis = |query_results: U256| {
+            query_erc20::revelation::RevelationPublicInputsBuilder::<L>::new()
+                .block_number(F::from_canonical_u32(100))
+                .range(F::from_canonical_u32(10))
+                .min_block_number(F::from_canonical_u32(91))
+                .max_block_number(F::from_canonical_u32(100))
+                .smart_contract_address(Address::random())
+                .user_address(Address::random())
+                .mapping_slot(F::from_canonical_u8(3))
+                .mapping_slot_length(F::from_canonical_u8(4))
+                .block_header(rng.gen())
+                .rewards_rate(U256::from(rng.gen::<u64>()))
+                .query_results(query_results)
+                .query_commitment(rng.gen())
+                .decimals(18)
+                .build()
+                .unwrap()
+                .into_iter()
+                .chain(iter::once(F::from_canonical_u8(
+                    QueryType::Erc20.identifier(),
+                )))
+                .collect_vec()
+        };
+        let inner = revelation_proof(erc20_pis(U256::from(42)));
+        let wrapped = wrap_circuit
+            .generate_proof(query_circuit_set, &inner)
+            .unwrap();
+        wrap_circuit.verify_wraps(&inner, &wrapped).unwrap();
+
+        // the wrapped proof is not bound to another revelation proof
+        let other_inner = revelation_proof(erc20_pis(U256::from(43)));
+        assert!(wrap_circuit.verify_wraps(&other_inner, &wrapped).is_err());
+        // a corrupted wrapped proof is rejected
+        assert!(wrap_circuit
+            .verify_wraps(&inner, &wrapped[..wrapped.len() / 2])
+            .is_err());
+    }
+
+    #[test]
+    fn test_assert_same_circuit() {
+        const NUM_PUBLIC_INPUTS: usize = 4;
+        let circuit = TestDummyCircuit::<NUM_PUBLIC_INPUTS>::build();
+        let other_circuit = TestDummyCircuit::<{ NUM_PUBLIC_INPUTS + 1 }>::build();
+        let vk = &circuit.circuit_data().verifier_only;
+        let other_vk = &other_circuit.circuit_data().verifier_only;
+
+        let first_proof = ProofWithVK::from((
+            circuit
+                .generate_proof([F::from_canonical_u8(1); NUM_PUBLIC_INPUTS])
+                .unwrap(),
+            vk.clone(),
+        ));
+        let second_proof = ProofWithVK::from((
+            circuit
+                .generate_proof([F::from_canonical_u8(2); NUM_PUBLIC_INPUTS])
+                .unwrap(),
+            vk.clone(),
+        ));
+        let other_proof = ProofWithVK::from((
+            other_circuit
+                .generate_proof([F::from_canonical_u8(1); NUM_PUBLIC_INPUTS + 1])
+                .unwrap(),
+            other_vk.clone(),
+        ));
+
+        // proofs of the same circuit should pass the check
+        first_proof.assert_same_circuit(&second_proof).unwrap();
+        second_proof.assert_same_circuit(&first_proof).unwrap();
+        // proofs of different circuits should fail the check
+        assert!(first_proof.assert_same_circuit(&other_proof).is_err());
+        assert!(other_proof.assert_same_circuit(&second_proof).is_err());
+    }
+
+    #[test]
+    fn test_proof_with_vk_serialization() {
+        const NUM_PUBLIC_INPUTS: usize = 4;
+        let proof = dummy_proof_with_vk::<NUM_PUBLIC_INPUTS>(F::rand_array());
+        let other_proof = dummy_proof_with_vk::<{ NUM_PUBLIC_INPUTS + 1 }>(F::rand_array());
+        let serialized = proof.serialize().unwrap();
+        assert_eq!(ProofWithVK::deserialize(&serialized).unwrap(), proof);
+        assert_eq!(
+            ProofWithVK::deserialize_for_circuit(&serialized, proof.verifier_data()).unwrap(),
+            proof
+        );
+        // the proof has not been generated by the expected circuit
+        assert!(
+            ProofWithVK::deserialize_for_circuit(&serialized, other_proof.verifier_data()).is_err()
+        );
+
+        // a proof serialized with another encoding version is rejected
+        let mut wrong_version = serialized.clone();
+        wrong_version[PROOF_WITH_VK_MAGIC.len()] = PROOF_WITH_VK_ENCODING_VERSION + 1;
+        let err = ProofWithVK::deserialize(&wrong_version).unwrap_err();
+        assert!(err.to_string().contains(&format!(
+            "expected {}, found {}",
+            PROOF_WITH_VK_ENCODING_VERSION,
+            PROOF_WITH_VK_ENCODING_VERSION + 1
+        )));
+        // a proof without the magic bytes is rejected
+        let mut wrong_magic = serialized.clone();
+        wrong_magic[0] ^= 1;
+        assert!(ProofWithVK::deserialize(&wrong_magic).is_err());
+        // a proof tagged with the digest of another circuit is rejected
+        let mut wrong_digest = serialized.clone();
+        wrong_digest[PROOF_WITH_VK_MAGIC.len() + 1..PROOF_WITH_VK_TAG_LEN]
+            .copy_from_slice(&other_proof.verifier_data().circuit_digest.to_bytes());
+        assert!(ProofWithVK::deserialize(&wrong_digest).is_err());
+        // an untagged proof is rejected
+        assert!(ProofWithVK::deserialize(&bincode::serialize(&proof).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_typed_public_inputs() {
+        const NUM_IO: usize = query_erc20::block::NUM_IO;
+        let block_number = F::from_canonical_u32(42);
+        let rand_address = || F::rand_vec(PACKED_ADDRESS_LEN).try_into().unwrap();
+        let rand_u256 = || F::rand_vec(PACKED_U256_LEN).try_into().unwrap();
+        let pis = query_erc20::block::BlockPublicInputs::from_parts(
+            block_number,
+            F::ONE,
+            F::ONE,
+            HashOut::rand(),
+            &rand_address(),
+            &rand_address(),
+            F::from_canonical_u8(3),
+            F::from_canonical_u8(4),
+            &rand_u256(),
+            &rand_u256(),
+            &rand_u256(),
+            F::from_canonical_u8(2),
+            query_erc20::block::aggregation::AggregationKind::Sum,
+            HashOut::ZERO,
+            F::ZERO,
+            HashOut::ZERO,
+            &[F::ZERO; PACKED_U256_LEN],
+            F::ZERO,
+        );
+        let proof =
+            ProofWithVK::deserialize(&dummy_proof_with_vk::<NUM_IO>(pis).serialize().unwrap())
+                .unwrap();
+        assert_eq!(proof.public_inputs(), pis.as_slice());
+        let block_pi = proof.block_public_inputs().unwrap();
+        assert_eq!(block_pi.block_number(), block_number);
+
+        // a proof exposing less public inputs than expected is rejected
+        let short_proof = dummy_proof_with_vk::<{ NUM_IO - 1 }>(pis[1..].try_into().unwrap());
+        assert!(short_proof.block_public_inputs().is_err());
+        assert!(proof.revelation_public_inputs::<5>().is_err());
+    }
+
     #[test]
     fn test_verify_proof_with_fixed_circuit() {
         const NUM_IO: usize = 4;
@@ -614,4 +1558,227 @@ pub(crate) mod tests {
             "successful recursive verification of proof for second circuit with first verifier"
         );
     }
+
+    /// Random data of the block `block_number` aggregated by an ERC-20 query
+    fn random_query_block(block_number: u32) -> QueryBlockTree {
+        let rng = &mut thread_rng();
+        let random_hash = || -> HashOutput { HashOut::<F>::rand().to_bytes().try_into().unwrap() };
+        QueryBlockTree::Leaf(QueryBlockData {
+            block_number,
+            block_hash: random_hash(),
+            balance: U256::from(rng.gen::<u64>()),
+            total_supply: U256::from(u64::MAX),
+            storage_path: vec![(random_hash(), false)],
+            state_siblings: vec![random_hash()],
+            state_positions: vec![true],
+        })
+    }
+
+    /// Build the query parameters over a fake block DB circuit set, together with an
+    /// ERC-20 query over the blocks `block_number` and `block_number + 1`, where the
+    /// mapping migrated according to `slot_remaps`, and the data of the chain needed
+    /// to prove it
+    fn erc20_query_setup<const BLOCK_DB_DEPTH: usize, const L: usize>(
+        block_number: u32,
+        slot_remaps: Vec<(u32, u32)>,
+    ) -> (QueryParameters<BLOCK_DB_DEPTH, L>, QuerySpec, ChainInputs)
+    where
+        [(); query2::revelation::num_io::<L>()]:,
+        [(); query_erc20::revelation::num_io::<L>()]:,
+        [(); <PoseidonHash as Hasher<F>>::HASH_SIZE]:,
+    {
+        // Generate a fake block DB circuit set
+        let block_db_circuits =
+            TestingRecursiveCircuits::<F, C, D, NUM_IVC_PUBLIC_INPUTS>::default();
+        let block_db_info = BlockDBCircuitInfo::<BLOCK_DB_DEPTH>::new(
+            block_db_circuits.get_recursive_circuit_set().clone(),
+            block_db_circuits.verifier_data_for_input_proofs::<1>()[0].clone(),
+        );
+        let params =
+            QueryParameters::<BLOCK_DB_DEPTH, L>::build(&block_db_info.serialize().unwrap())
+                .unwrap();
+
+        let rng = &mut thread_rng();
+        let query = QuerySpec {
+            query_type: QueryType::Erc20,
+            rewards_rate: U256::from(rng.gen::<u32>()),
+            leaf_mode: LeafMode::Rewards,
+            reward_formula: RewardFormula::Proportional,
+            threshold: U256::zero(),
+            decimals: 18,
+            contract_address: Address::random(),
+            user_address: Address::random(),
+            mapping_slot: rng.gen::<u8>() as u32,
+            length_slot: rng.gen::<u8>() as u32,
+            min_block: block_number as usize,
+            max_block: block_number as usize + 1,
+            slot_remaps,
+        };
+        let block_tree = QueryBlockTree::Full(
+            Box::new(random_query_block(block_number)),
+            Box::new(random_query_block(block_number + 1)),
+        );
+
+        // Generate a fake block DB proof over the root of the block tree
+        let block_proof = params
+            .prove_erc20_block_tree(&query, block_tree.clone())
+            .unwrap();
+        let block_proof = ProofWithVK::deserialize(&block_proof).unwrap();
+        let block_pi = block_proof.block_public_inputs().unwrap();
+        let block_db_state = BlockDbState {
+            init_root: empty_merkle_root::<F, D, BLOCK_DB_DEPTH>(),
+            last_root: block_pi.root(),
+            first_block: F::from_canonical_u32(block_number - 10),
+            last_block: F::from_canonical_u32(block_number + 2),
+            header: F::rand_vec(PACKED_HASH_LEN).try_into().unwrap(),
+        };
+        let block_db_pi = block_db_state
+            .to_public_inputs()
+            .into_iter()
+            .chain(iter::once(F::ONE))
+            .collect_vec();
+        let block_db_proof = &block_db_circuits
+            .generate_input_proofs([block_db_pi.try_into().unwrap()])
+            .unwrap()[0];
+
+        let chain_inputs = ChainInputs {
+            block_tree,
+            block_db_proof: serialize_proof(block_db_proof).unwrap(),
+        };
+        (params, query, chain_inputs)
+    }
+
+    #[test]
+    #[serial]
+    fn test_prove_query_erc20() {
+        const BLOCK_DB_DEPTH: usize = 2;
+        const L: usize = 5;
+        const BLOCK_NUMBER: u32 = 1000;
+
+        let (params, query, chain_inputs) =
+            erc20_query_setup::<BLOCK_DB_DEPTH, L>(BLOCK_NUMBER, vec![]);
+
+        let proof = prove_query(&params, query, chain_inputs).unwrap();
+        params
+            .final_proof_circuit_data()
+            .verify(deserialize_proof(&proof).unwrap())
+            .unwrap();
+    }
+
+    #[test]
+    #[serial]
+    fn test_prove_query_erc20_across_slot_remap() {
+        const BLOCK_NUMBER: u32 = 1000;
+        const BLOCK_DB_DEPTH: usize = 2;
+        const L: usize = 5;
+
+        // the mapping migrates at the second block of the queried range
+        let new_slot = u8::MAX as u32 + 1;
+        let slot_remaps = vec![(BLOCK_NUMBER + 1, new_slot)];
+        let (params, query, chain_inputs) =
+            erc20_query_setup::<BLOCK_DB_DEPTH, L>(BLOCK_NUMBER, slot_remaps.clone());
+
+        // the state proof of each block is generated for the slot in use at
+        // that block, while exposing the original slot and the commitment to
+        // the migrations, which are shared by the blocks on both sides
+        let remaps_commitment = query_erc20::slot_remaps_commitment(&slot_remaps).unwrap();
+        for (block_number, expected_slot) in [
+            (BLOCK_NUMBER, query.mapping_slot),
+            (BLOCK_NUMBER + 1, new_slot),
+        ] {
+            assert_eq!(query.mapping_slot_at(block_number), expected_slot);
+            let leaf_proof = params
+                .prove_erc20_block_tree(&query, random_query_block(block_number))
+                .unwrap();
+            let leaf_proof = ProofWithVK::deserialize(&leaf_proof).unwrap();
+            let leaf_pi = leaf_proof.block_public_inputs().unwrap();
+            assert_eq!(
+                leaf_pi.mapping_slot(),
+                F::from_canonical_u32(query.mapping_slot)
+            );
+            assert_eq!(leaf_pi.slot_remaps_commitment(), remaps_commitment);
+        }
+
+        // the range spanning the migration is aggregated and revealed
+        let proof = prove_query(&params, query, chain_inputs).unwrap();
+        params
+            .final_proof_circuit_data()
+            .verify(deserialize_proof(&proof).unwrap())
+            .unwrap();
+    }
+
+    #[test]
+    #[serial]
+    fn test_prove_erc20_block_tree_leaf_modes() {
+        const BLOCK_NUMBER: u32 = 1000;
+        const BLOCK_DB_DEPTH: usize = 2;
+        const L: usize = 5;
+
+        let (params, query, _) = erc20_query_setup::<BLOCK_DB_DEPTH, L>(BLOCK_NUMBER, vec![]);
+        let threshold = U256::from(u32::MAX);
+        // the leaves are proven with the circuit of the mode of the query, which
+        // is exposed by the block proof together with the threshold
+        for (leaf_mode, reward_formula, rewards_rate) in [
+            (
+                LeafMode::Threshold,
+                RewardFormula::Proportional,
+                U256::zero(),
+            ),
+            (
+                LeafMode::Rewards,
+                RewardFormula::FlatPerHolder,
+                query.rewards_rate,
+            ),
+        ] {
+            let query = QuerySpec {
+                leaf_mode,
+                reward_formula,
+                rewards_rate,
+                threshold,
+                ..query.clone()
+            };
+            let proof = params
+                .prove_erc20_block_tree(&query, random_query_block(BLOCK_NUMBER))
+                .unwrap();
+            let proof = ProofWithVK::deserialize(&proof).unwrap();
+            let pi = proof.block_public_inputs().unwrap();
+            assert_eq!(
+                pi.leaf_mode(),
+                query_erc20::leaf_mode_id(leaf_mode, reward_formula)
+            );
+            assert_eq!(pi.threshold(), threshold);
+            assert_eq!(pi.rewards_rate(), rewards_rate);
+        }
+    }
+
+    #[test]
+    #[serial]
+    #[ignore = "proving the whole query pipeline is too slow for CI"]
+    fn test_prove_query_timed() {
+        const BLOCK_DB_DEPTH: usize = 2;
+        const L: usize = 5;
+
+        let (params, query, chain_inputs) = erc20_query_setup::<BLOCK_DB_DEPTH, L>(1000, vec![]);
+
+        let start = Instant::now();
+        let (proof, timings) = prove_query_timed(&params, query, chain_inputs).unwrap();
+        let elapsed = start.elapsed();
+        params
+            .final_proof_circuit_data()
+            .verify(deserialize_proof(&proof).unwrap())
+            .unwrap();
+
+        for (phase, duration) in [
+            ("storage", timings.storage),
+            ("state", timings.state),
+            ("block", timings.block),
+            ("revelation", timings.revelation),
+            ("wrap", timings.wrap),
+        ] {
+            assert!(!duration.is_zero(), "no time reported for {phase} phase");
+        }
+        // the phases cover the whole pipeline, up to the bookkeeping between them
+        assert!(timings.total() <= elapsed);
+        assert!(timings.total() >= elapsed.mul_f64(0.8));
+    }
 }
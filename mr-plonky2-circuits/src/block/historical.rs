@@ -0,0 +1,283 @@
+//! This circuit proves that a block belongs to the block tree committed by a
+//! block DB proof. Since the block DB proofs only expose the root of the tree
+//! after the last block inserted, it allows to prove values at any block
+//! inserted in the DB (e.g., the balance at block N < last block), by opening
+//! the tree from the last root down to the leaf of the historical block.
+
+use mrp2_utils::serialization::{deserialize, deserialize_array, serialize, serialize_array};
+use plonky2::{
+    field::extension::Extendable,
+    hash::{
+        hash_types::{HashOut, HashOutTarget, RichField},
+        merkle_proofs::{MerkleProof, MerkleProofTarget},
+        poseidon::PoseidonHash,
+    },
+    iop::{
+        target::{BoolTarget, Target},
+        witness::{PartialWitness, WitnessWrite},
+    },
+    plonk::{circuit_builder::CircuitBuilder, config::GenericHashOut},
+};
+use serde::{Deserialize, Serialize};
+use std::array;
+
+use super::PublicInputs;
+use crate::types::HashOutput;
+
+#[derive(Serialize, Deserialize)]
+/// Wires to assign for the opening of the block tree
+pub struct HistoricalBlockWires<const MAX_DEPTH: usize> {
+    #[serde(
+        serialize_with = "serialize_array",
+        deserialize_with = "deserialize_array"
+    )]
+    /// The index of the historical leaf, given by its little-endian bits
+    leaf_index_bits: [BoolTarget; MAX_DEPTH],
+    #[serde(serialize_with = "serialize", deserialize_with = "deserialize")]
+    /// The path from the historical leaf to the last root of the block tree
+    path: MerkleProofTarget,
+}
+
+/// Circuit proving that the leaf of a historical block is found in the block
+/// tree with the last root exposed by a block DB proof
+#[derive(Clone, Debug)]
+pub struct HistoricalBlockCircuit<F, const MAX_DEPTH: usize>
+where
+    F: RichField,
+{
+    /// The index of the historical leaf, equal to
+    /// `block_number - first_block_number`
+    leaf_index: usize,
+    /// The path starts from the sibling of the historical leaf, and the
+    /// parent's siblings at each level. The root is not included.
+    path: MerkleProof<F, PoseidonHash>,
+}
+
+impl<F, const MAX_DEPTH: usize> HistoricalBlockCircuit<F, MAX_DEPTH>
+where
+    F: RichField,
+{
+    pub fn new(leaf_index: usize, siblings: Vec<HashOutput>) -> Self {
+        let siblings = siblings
+            .into_iter()
+            .map(|s| HashOut::from_bytes(&s))
+            .collect();
+        Self::new_from(leaf_index, MerkleProof { siblings })
+    }
+
+    pub fn new_from(leaf_index: usize, path: MerkleProof<F, PoseidonHash>) -> Self {
+        assert_eq!(
+            path.siblings.len(),
+            MAX_DEPTH,
+            "the path must contain {MAX_DEPTH} siblings"
+        );
+        Self { leaf_index, path }
+    }
+
+    /// Build for the circuit. The arguments are:
+    /// - db_pi: Public inputs of the block DB proof, exposing the last root.
+    /// - leaf_hash: Hash of the leaf of the historical block, i.e.,
+    ///   `H(block_number || block_header || state_root)`, as computed by
+    ///   [block_leaf_hash](super::block_leaf_hash).
+    /// - block_number: Number of the historical block.
+    pub fn build<const D: usize>(
+        cb: &mut CircuitBuilder<F, D>,
+        db_pi: &PublicInputs<Target>,
+        leaf_hash: &HashOutTarget,
+        block_number: Target,
+    ) -> HistoricalBlockWires<MAX_DEPTH>
+    where
+        F: Extendable<D>,
+    {
+        let leaf_index_bits = array::from_fn(|_| cb.add_virtual_bool_target_safe());
+        let path = MerkleProofTarget {
+            siblings: cb.add_virtual_hashes(MAX_DEPTH),
+        };
+
+        // Check `first_block_number + leaf_index = block_number`, so that the
+        // leaf opened is the one of the historical block.
+        let leaf_index = cb.le_sum(leaf_index_bits.iter());
+        let exp_block_num = cb.add(db_pi.first_block_number().0, leaf_index);
+        cb.connect(exp_block_num, block_number);
+
+        // Verify the leaf is present at the given index in the block tree with
+        // the last root. The leaf hash is not hashed again by the Merkle gadget,
+        // since data of `NUM_HASH_OUT_ELTS` elements is used as it is.
+        cb.verify_merkle_proof::<PoseidonHash>(
+            leaf_hash.elements.to_vec(),
+            &leaf_index_bits,
+            db_pi.root(),
+            &path,
+        );
+
+        HistoricalBlockWires {
+            leaf_index_bits,
+            path,
+        }
+    }
+
+    /// Assign the wires.
+    pub fn assign(&self, pw: &mut PartialWitness<F>, wires: &HistoricalBlockWires<MAX_DEPTH>) {
+        let mut index = self.leaf_index;
+        for i in 0..MAX_DEPTH {
+            let bit = index & 1;
+            index >>= 1;
+            pw.set_bool_target(wires.leaf_index_bits[i], bit == 1);
+        }
+
+        wires
+            .path
+            .siblings
+            .iter()
+            .zip(&self.path.siblings)
+            .for_each(|(t, v)| pw.set_hash_target(*t, *v));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        block::{empty_merkle_root, BlockDbState},
+        query_erc20::{
+            block::BlockPublicInputs, state::tests::run_state_circuit_with_slot_and_addresses,
+        },
+    };
+    use ethers::types::Address;
+    use mrp2_test_utils::circuit::{run_circuit, UserCircuit};
+    use mrp2_utils::{u256::CircuitBuilderU256, utils::convert_u32_fields_to_u256};
+    use plonky2::{
+        field::types::{Field, Sample},
+        hash::{hash_types::NUM_HASH_OUT_ELTS, merkle_tree::MerkleTree},
+        plonk::config::{GenericConfig, PoseidonGoldilocksConfig},
+    };
+    use rand::{thread_rng, Rng};
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    const MAX_DEPTH: usize = 2;
+
+    /// Test circuit exposing the block number and the query results of an
+    /// ERC20 state proof for a historical block of the block DB
+    #[derive(Clone, Debug)]
+    struct TestHistoricalCircuit {
+        db_pi: Vec<F>,
+        state_pi: Vec<F>,
+        c: HistoricalBlockCircuit<F, MAX_DEPTH>,
+    }
+
+    impl UserCircuit<F, D> for TestHistoricalCircuit {
+        type Wires = (Vec<Target>, Vec<Target>, HistoricalBlockWires<MAX_DEPTH>);
+
+        fn build(cb: &mut CircuitBuilder<F, D>) -> Self::Wires {
+            let db_pi = cb.add_virtual_targets(PublicInputs::<Target>::TOTAL_LEN);
+            let state_pi = cb.add_virtual_targets(BlockPublicInputs::<Target>::total_len());
+            let state = BlockPublicInputs::from(state_pi.as_slice());
+
+            let wires = HistoricalBlockCircuit::build(
+                cb,
+                &PublicInputs::from(&db_pi),
+                &state.root(),
+                state.block_number(),
+            );
+
+            cb.register_public_input(state.block_number());
+            cb.register_public_input_u256(&state.query_results());
+
+            (db_pi, state_pi, wires)
+        }
+
+        fn prove(&self, pw: &mut PartialWitness<F>, wires: &Self::Wires) {
+            pw.set_target_arr(&wires.0, &self.db_pi);
+            pw.set_target_arr(&wires.1, &self.state_pi);
+            self.c.assign(pw, &wires.2);
+        }
+    }
+
+    /// Build a block DB of 4 blocks, where the block at `historical_index` is
+    /// the block of the ERC20 state proof, returning the public inputs of the
+    /// block DB proof, of the state proof and the path to the historical leaf.
+    fn historical_inputs(
+        first_block_num: u32,
+        historical_index: usize,
+    ) -> (Vec<F>, Vec<F>, MerkleProof<F, PoseidonHash>) {
+        let state_pi = run_state_circuit_with_slot_and_addresses(
+            first_block_num + historical_index as u32,
+            thread_rng().gen(),
+            thread_rng().gen(),
+            Address::random(),
+            Address::random(),
+        );
+        let historical_leaf = BlockPublicInputs::from(state_pi.as_slice()).root();
+
+        // The leaves are given by their hashes, which aren't hashed again by the
+        // Merkle tree, the same as in the circuit.
+        let leaves = (0..1 << MAX_DEPTH)
+            .map(|i| {
+                if i == historical_index {
+                    historical_leaf.elements.to_vec()
+                } else {
+                    F::rand_vec(NUM_HASH_OUT_ELTS)
+                }
+            })
+            .collect();
+        let tree = MerkleTree::<F, PoseidonHash>::new(leaves, 0);
+        let db_pi = BlockDbState {
+            init_root: empty_merkle_root::<F, D, MAX_DEPTH>(),
+            last_root: tree.cap.0[0],
+            first_block: F::from_canonical_u32(first_block_num),
+            last_block: F::from_canonical_u32(first_block_num + (1 << MAX_DEPTH) - 1),
+            header: array::from_fn(|_| F::rand()),
+        }
+        .to_public_inputs();
+
+        (db_pi.to_vec(), state_pi, tree.prove(historical_index))
+    }
+
+    #[test]
+    fn test_historical_block_circuit() {
+        let first_block_num = thread_rng().gen_range(1..10_000);
+        // intermediate block of the DB
+        let historical_index = 2;
+        let (db_pi, state_pi, path) = historical_inputs(first_block_num, historical_index);
+
+        let proof = run_circuit::<F, D, C, _>(TestHistoricalCircuit {
+            db_pi: db_pi.clone(),
+            state_pi: state_pi.clone(),
+            c: HistoricalBlockCircuit::new_from(historical_index, path.clone()),
+        });
+        let state = BlockPublicInputs::from(state_pi.as_slice());
+        assert_eq!(
+            proof.public_inputs[0],
+            F::from_canonical_u32(first_block_num + historical_index as u32)
+        );
+        assert_eq!(proof.public_inputs[0], state.block_number());
+        assert_eq!(
+            convert_u32_fields_to_u256(&proof.public_inputs[1..]),
+            state.query_results()
+        );
+
+        // the opening fails for a leaf index not matching the block number
+        let res = std::panic::catch_unwind(|| {
+            run_circuit::<F, D, C, _>(TestHistoricalCircuit {
+                db_pi: db_pi.clone(),
+                state_pi: state_pi.clone(),
+                c: HistoricalBlockCircuit::new_from(historical_index + 1, path.clone()),
+            })
+        });
+        assert!(res.is_err(), "wrong leaf index not detected");
+
+        // the opening fails for a block DB with a different last root
+        let (other_db_pi, _, _) = historical_inputs(first_block_num, historical_index);
+        let res = std::panic::catch_unwind(|| {
+            run_circuit::<F, D, C, _>(TestHistoricalCircuit {
+                db_pi: other_db_pi,
+                state_pi,
+                c: HistoricalBlockCircuit::new_from(historical_index, path),
+            })
+        });
+        assert!(res.is_err(), "wrong block DB root not detected");
+    }
+}
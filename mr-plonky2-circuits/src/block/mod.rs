@@ -5,8 +5,9 @@
 //! - Prove the append-only property, that we keep appending blocks without
 //!   deletion and modification.
 
+pub mod historical;
 pub mod public_inputs;
-pub use public_inputs::PublicInputs;
+pub use public_inputs::{BlockDbState, PublicInputs};
 
 use crate::{
     api::{default_config, ProofWithVK},
@@ -15,7 +16,7 @@ use crate::{
     types::HashOutput,
     utils::{convert_u8_to_u32_slice, hash_two_to_one},
 };
-use anyhow::Result;
+use anyhow::{ensure, Result};
 use mrp2_utils::serialization::{
     circuit_data_serialization::SerializableRichField, deserialize, deserialize_array, serialize,
     serialize_array,
@@ -50,7 +51,12 @@ use recursion_framework::{
 };
 
 use serde::{Deserialize, Serialize};
-use std::array;
+use std::{
+    any::{Any, TypeId},
+    array,
+    collections::HashMap,
+    sync::{Mutex, OnceLock},
+};
 
 /// Returns the hash in bytes of the leaf of the block tree. It takes as parameters
 /// the block number, the block header in bytes and the state root in bytes.
@@ -321,6 +327,34 @@ const NUM_STATE_PUBLIC_INPUTS: usize = StateInputs::<Target>::TOTAL_LEN;
 // additional public input value is set to 1, while IVC proofs will have
 // this public input value set to 0.
 pub const NUM_IVC_PUBLIC_INPUTS: usize = NUM_IO + 1;
+
+/// Check that `proof` is a block DB proof generated by the IVC circuit, whose verifier
+/// data is `expected_vk`, rather than by the dummy circuit, as enforced in circuit by
+/// the revelation circuits. It allows to validate the block DB proof before proving
+/// the revelation step; the proof itself is not verified.
+pub fn assert_is_ivc_proof(
+    proof: &ProofWithVK,
+    expected_vk: &VerifierOnlyCircuitData<C, D>,
+) -> Result<()> {
+    ensure!(
+        proof.verifier_data() == expected_vk,
+        "block DB proof not generated by the IVC circuit: circuit digest {:?}, expected {:?}",
+        proof.verifier_data().circuit_digest,
+        expected_vk.circuit_digest,
+    );
+    ensure!(
+        proof.num_public_inputs() >= NUM_IVC_PUBLIC_INPUTS,
+        "block DB proof expected to have at least {NUM_IVC_PUBLIC_INPUTS} public inputs (currently {})",
+        proof.num_public_inputs()
+    );
+    // proofs of the dummy circuit are the only ones exposing 1 as additional public input
+    ensure!(
+        proof.proof().public_inputs[NUM_IO] == F::ZERO,
+        "block DB proof exposes the flag of the dummy circuit"
+    );
+
+    Ok(())
+}
 /// This data strcuture contains the input values related to the additional
 /// logic enforced in the block tree IVC circuit besides recursive verification
 /// of  previously generated IVC proof
@@ -660,8 +694,41 @@ impl<const MAX_DEPTH: usize> CircuitInput<MAX_DEPTH> {
     }
 }
 
+/// Roots of the empty merkle trees computed so far, keyed by field and depth of the tree
+type EmptyMerkleRoots = HashMap<(TypeId, usize), Box<dyn Any + Send + Sync>>;
+static EMPTY_MERKLE_ROOTS: OnceLock<Mutex<EmptyMerkleRoots>> = OnceLock::new();
+
+/// Returns the root of the empty merkle tree of depth `MAX_DEPTH`. Roots are
+/// cached, so that building several circuits with the same depth computes the
+/// root only once.
 pub fn empty_merkle_root<F: SerializableRichField<D>, const D: usize, const MAX_DEPTH: usize>(
 ) -> HashOut<F> {
+    cached_empty_merkle_root::<F, D, MAX_DEPTH>().0
+}
+
+/// Returns the root of the empty merkle tree of depth `MAX_DEPTH`, together with
+/// a flag specifying whether the root was found in the cache
+fn cached_empty_merkle_root<F: SerializableRichField<D>, const D: usize, const MAX_DEPTH: usize>(
+) -> (HashOut<F>, bool) {
+    let mut roots = EMPTY_MERKLE_ROOTS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap();
+    let key = (TypeId::of::<F>(), MAX_DEPTH);
+    if let Some(root) = roots.get(&key) {
+        return (*root.downcast_ref::<HashOut<F>>().unwrap(), true);
+    }
+    let root = compute_empty_merkle_root::<F, D, MAX_DEPTH>();
+    roots.insert(key, Box::new(root));
+
+    (root, false)
+}
+
+fn compute_empty_merkle_root<
+    F: SerializableRichField<D>,
+    const D: usize,
+    const MAX_DEPTH: usize,
+>() -> HashOut<F> {
     (0..MAX_DEPTH).fold(HashOut::<F>::from_partial(&[]), |hash, _| {
         PoseidonHash::two_to_one(hash, hash)
     })
@@ -779,6 +846,27 @@ mod tests {
         let proof = params.generate_proof(inputs).unwrap();
 
         params.verify_proof(&proof).unwrap();
+        let ivc_vk = params.get_block_db_vk();
+        assert_is_ivc_proof(&ProofWithVK::deserialize(&proof).unwrap(), ivc_vk).unwrap();
+
+        // a proof of the dummy circuit is rejected, even if paired with the IVC verifier data
+        let dummy_proof = params
+            .set
+            .generate_proof(
+                &params.dummy,
+                [],
+                [],
+                DummyCircuitInputs {
+                    first_block_number: F::from_canonical_usize(first_block_num),
+                    parent_hash: array::from_fn(|_| F::rand()),
+                },
+            )
+            .unwrap();
+        let dummy_vk = &params.dummy.circuit_data().verifier_only;
+        assert!(
+            assert_is_ivc_proof(&(dummy_proof.clone(), dummy_vk.clone()).into(), ivc_vk).is_err()
+        );
+        assert!(assert_is_ivc_proof(&(dummy_proof, ivc_vk.clone()).into(), ivc_vk).is_err());
 
         let leaf_index = leaf_index + 1;
         leaves[leaf_index] = rand_leaf_data(first_block_num + 1);
@@ -1011,6 +1099,28 @@ mod tests {
         data
     }
 
+    #[test]
+    fn test_empty_merkle_root_cache() {
+        // depth not employed elsewhere, so that the root isn't cached yet
+        const DEPTH: usize = 57;
+        let expected = compute_empty_merkle_root::<F, D, DEPTH>();
+
+        let (root, is_cached) = cached_empty_merkle_root::<F, D, DEPTH>();
+        assert_eq!(root, expected);
+        assert!(!is_cached);
+
+        let (root, is_cached) = cached_empty_merkle_root::<F, D, DEPTH>();
+        assert_eq!(root, expected);
+        assert!(is_cached);
+        assert_eq!(empty_merkle_root::<F, D, DEPTH>(), expected);
+
+        // roots of different depths are cached separately
+        assert_ne!(
+            empty_merkle_root::<F, D, { DEPTH + 1 }>(),
+            empty_merkle_root::<F, D, DEPTH>()
+        );
+    }
+
     #[test]
     fn test_hash_leaf() {
         let block_number = thread_rng().gen_range(1..10_000);
@@ -1,7 +1,7 @@
 use crate::{keccak::OutputHash, keccak::PACKED_HASH_LEN};
 use plonky2::{
     field::{extension::Extendable, goldilocks_field::GoldilocksField},
-    hash::hash_types::{HashOutTarget, RichField, NUM_HASH_OUT_ELTS},
+    hash::hash_types::{HashOut, HashOutTarget, RichField, NUM_HASH_OUT_ELTS},
     iop::target::Target,
     plonk::circuit_builder::CircuitBuilder,
 };
@@ -118,3 +118,89 @@ impl PublicInputs<'_, GoldilocksField> {
         arr
     }
 }
+
+/// Structured representation of the public inputs of the block DB proofs,
+/// avoiding to assemble the raw public inputs by hand.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlockDbState {
+    /// Initial empty root
+    pub init_root: HashOut<GoldilocksField>,
+    /// New root
+    pub last_root: HashOut<GoldilocksField>,
+    /// First block number inserted
+    pub first_block: GoldilocksField,
+    /// New block number inserted
+    pub last_block: GoldilocksField,
+    /// Header hash of the new block inserted
+    pub header: [GoldilocksField; PACKED_HASH_LEN],
+}
+
+impl BlockDbState {
+    /// Convert to the public inputs of the block DB proofs.
+    pub fn to_public_inputs(
+        &self,
+    ) -> [GoldilocksField; PublicInputs::<GoldilocksField>::TOTAL_LEN] {
+        PublicInputs::from_parts(
+            &self.init_root.elements,
+            &self.last_root.elements,
+            self.first_block,
+            self.last_block,
+            &self.header,
+        )
+    }
+
+    /// Build from the public inputs of the block DB proofs. The input slice
+    /// could be longer than the block DB public inputs (e.g. the public inputs
+    /// of the IVC proofs), the trailing elements are ignored.
+    pub fn from_public_inputs(pi: &[GoldilocksField]) -> Self {
+        assert!(
+            pi.len() >= PublicInputs::<GoldilocksField>::TOTAL_LEN,
+            "Invalid length of the block DB public inputs"
+        );
+        let pi = PublicInputs::from(&pi[..PublicInputs::<GoldilocksField>::TOTAL_LEN]);
+
+        Self {
+            init_root: HashOut::from_vec(pi.init_root_data().to_vec()),
+            last_root: HashOut::from_vec(pi.root_data().to_vec()),
+            first_block: pi.first_block_number_data(),
+            last_block: pi.block_number_data(),
+            header: pi.block_header_data().try_into().unwrap(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::{empty_merkle_root, NUM_IVC_PUBLIC_INPUTS};
+    use plonky2::field::types::{Field, Sample};
+
+    #[test]
+    fn test_block_db_state_round_trip() {
+        let state = BlockDbState {
+            init_root: empty_merkle_root::<GoldilocksField, 2, 3>(),
+            last_root: HashOut::rand(),
+            first_block: GoldilocksField::from_canonical_u32(42),
+            last_block: GoldilocksField::from_canonical_u32(4242),
+            header: GoldilocksField::rand_vec(PACKED_HASH_LEN)
+                .try_into()
+                .unwrap(),
+        };
+
+        let pi = state.to_public_inputs();
+        let expected_pi = PublicInputs::from_parts(
+            &state.init_root.elements,
+            &state.last_root.elements,
+            state.first_block,
+            state.last_block,
+            &state.header,
+        );
+        assert_eq!(pi, expected_pi);
+        assert_eq!(BlockDbState::from_public_inputs(&pi), state);
+
+        // The additional public input of the IVC proofs is ignored.
+        let ivc_pi: Vec<_> = pi.into_iter().chain([GoldilocksField::ONE]).collect();
+        assert_eq!(ivc_pi.len(), NUM_IVC_PUBLIC_INPUTS);
+        assert_eq!(BlockDbState::from_public_inputs(&ivc_pi), state);
+    }
+}
@@ -16,3 +16,4 @@ pub mod query2;
 pub mod query_erc20;
 pub mod state;
 pub mod storage;
+pub mod test_support;
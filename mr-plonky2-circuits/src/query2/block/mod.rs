@@ -23,7 +23,7 @@ use recursion_framework::{
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    api::{default_config, ProofWithVK, C, D, F},
+    api::{decode_circuit_input, default_config, encode_circuit_input, ProofWithVK, C, D, F},
     types::{
         HashOutput, PackedAddressTarget, PackedValueTarget, CURVE_TARGET_LEN, PACKED_ADDRESS_LEN,
         PACKED_VALUE_LEN,
@@ -36,7 +36,7 @@ use self::{
     partial_node::{PartialNodeCircuitInputs, PartialNodeWires},
 };
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 
 pub mod full_node;
 pub mod partial_node;
@@ -67,6 +67,30 @@ impl CircuitInput {
             sibling_is_left,
         )))
     }
+
+    const FULL_NODE_TAG: u8 = 0;
+    const PARTIAL_NODE_TAG: u8 = 1;
+
+    /// Encode the input in a stable format, where each variant is identified by
+    /// an explicit tag, so that inputs can be durably queued before proving
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        match self {
+            CircuitInput::FullNode(proofs) => encode_circuit_input(Self::FULL_NODE_TAG, proofs),
+            CircuitInput::PartialNode(partial_node) => {
+                encode_circuit_input(Self::PARTIAL_NODE_TAG, partial_node)
+            }
+        }
+    }
+
+    /// Decode an input encoded with `to_bytes`
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let (tag, payload) = decode_circuit_input(bytes)?;
+        Ok(match tag {
+            Self::FULL_NODE_TAG => CircuitInput::FullNode(bincode::deserialize(payload)?),
+            Self::PARTIAL_NODE_TAG => CircuitInput::PartialNode(bincode::deserialize(payload)?),
+            _ => bail!("unknown tag {tag} for query2 block circuit input"),
+        })
+    }
 }
 
 pub const NUM_IO: usize = BlockPublicInputs::<Target>::total_len();
@@ -315,13 +339,8 @@ impl<'a> BlockPublicInputs<'a, Target> {
     }
 
     pub(crate) fn smart_contract_address(&self) -> PackedAddressTarget {
-        PackedAddressTarget::try_from(
-            self.smart_contract_address_raw()
-                .iter()
-                .map(|&t| U32Target(t))
-                .collect_vec(),
-        )
-        .unwrap()
+        PackedAddressTarget::from_targets_checked(self.smart_contract_address_raw())
+            .expect("the smart contract address is exposed as a packed address")
     }
 
     pub(crate) fn user_address(&self) -> PackedValueTarget {
@@ -443,15 +462,18 @@ impl<'a> BlockPublicInputs<'a, GoldilocksField> {
 mod tests {
     use ethers::types::Address;
     use itertools::Itertools;
-    use plonky2::field::types::Field;
+    use plonky2::field::types::{Field, Sample};
     use plonky2::plonk::config::GenericHashOut;
     use plonky2::{
-        hash::{hashing::hash_n_to_hash_no_pad, poseidon::PoseidonPermutation},
+        hash::{
+            hash_types::HashOut, hashing::hash_n_to_hash_no_pad, poseidon::PoseidonPermutation,
+        },
         iop::target::Target,
     };
     use recursion_framework::framework_testing::TestingRecursiveCircuits;
     use serial_test::serial;
 
+    use crate::api::tests::dummy_proof_with_vk;
     use crate::query2::{
         state::{tests::generate_inputs_for_state_circuit, Parameters as StateParams},
         storage::public_inputs::PublicInputs as StorageInputs,
@@ -461,6 +483,35 @@ mod tests {
     type C = crate::api::C;
     const D: usize = crate::api::D;
 
+    #[test]
+    fn test_query2_block_circuit_input_encoding() {
+        let full_node = super::CircuitInput::FullNode((
+            dummy_proof_with_vk([F::rand(); 4]),
+            dummy_proof_with_vk([F::rand(); 4]),
+        ));
+        let partial_node = super::CircuitInput::new_partial_node(
+            dummy_proof_with_vk([F::rand(); 4]).serialize().unwrap(),
+            HashOut::<F>::rand().to_bytes().try_into().unwrap(),
+            true,
+        )
+        .unwrap();
+
+        for (input, is_full_node) in [(full_node, true), (partial_node, false)] {
+            let bytes = input.to_bytes().unwrap();
+            let decoded = super::CircuitInput::from_bytes(&bytes).unwrap();
+            assert_eq!(decoded.to_bytes().unwrap(), bytes);
+            assert_eq!(
+                matches!(decoded, super::CircuitInput::FullNode(_)),
+                is_full_node
+            );
+
+            // an unknown tag is rejected
+            let mut unknown_tag = bytes;
+            unknown_tag[1] = u8::MAX;
+            assert!(super::CircuitInput::from_bytes(&unknown_tag).is_err());
+        }
+    }
+
     #[test]
     #[serial]
     fn test_block_circuit_api() {
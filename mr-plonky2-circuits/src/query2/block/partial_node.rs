@@ -22,7 +22,7 @@ pub struct PartialNodeWires {
     proved_is_right: BoolTarget,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PartialNodeCircuit {
     sibling_hash: HashOut<F>,
     sibling_is_left: bool,
@@ -100,6 +100,7 @@ impl CircuitLogicWires<F, D, 1> for PartialNodeWires {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct PartialNodeCircuitInputs {
     inputs: PartialNodeCircuit,
     child_proof: ProofWithVK,
@@ -1,4 +1,4 @@
-use super::{num_io, RevelationInput, RevelationPublicInputs};
+use super::{identifier_for, num_io, RevelationInput, RevelationPublicInputs};
 use crate::{
     api::{default_config, C, D, F},
     block::{
@@ -14,10 +14,7 @@ use crate::{
     utils::{greater_than_or_equal_to, less_than, less_than_or_equal_to},
 };
 use itertools::Itertools;
-use mrp2_utils::{
-    serialization::{deserialize, serialize},
-    utils::keccak256,
-};
+use mrp2_utils::serialization::{deserialize, serialize};
 use plonky2::{
     field::{goldilocks_field::GoldilocksField, types::Field},
     hash::{hash_types::HashOutTarget, poseidon::PoseidonHash},
@@ -292,8 +289,7 @@ where
             RevelationCircuit::<L>::build::<BLOCK_DB_DEPTH>(builder, block_db_pi, query_block_pi);
 
         // register additional public input to identify the query circuits
-        let identifier =
-            builder.constant(F::from_canonical_u8(keccak256("QueryNFT".as_bytes())[0]));
+        let identifier = builder.constant(F::from_canonical_u8(identifier_for("QueryNFT")));
         builder.register_public_input(identifier);
 
         RevelationRecursiveWires {
@@ -0,0 +1,97 @@
+//! Gadget enforcing that a sorted set of NFT IDs is a contiguous range
+use crate::utils::less_than;
+use plonky2::{
+    field::extension::Extendable, hash::hash_types::RichField, iop::target::Target,
+    plonk::circuit_builder::CircuitBuilder,
+};
+
+/// Trait adding to the circuit builder the check that a set of NFT IDs has no gaps
+pub trait CircuitBuilderContiguousIds {
+    /// Enforce `ids[i + 1] == ids[i] + 1` for all the first `count` IDs, while the
+    /// remaining ones are considered padding and left unconstrained. It's an opt-in
+    /// stricter check than the strictly increasing order enforced by the revelation
+    /// circuit, for collections requiring contiguous ID ranges.
+    /// `count` is expected to fit in 32 bits.
+    fn assert_contiguous_ids(&mut self, ids: &[Target], count: Target);
+}
+
+impl<F: RichField + Extendable<D>, const D: usize> CircuitBuilderContiguousIds
+    for CircuitBuilder<F, D>
+{
+    fn assert_contiguous_ids(&mut self, ids: &[Target], count: Target) {
+        let zero = self.zero();
+        for (i, pair) in ids.windows(2).enumerate() {
+            // the pair is checked only if both IDs are within the first `count` ones
+            let next = self.constant(F::from_canonical_usize(i + 1));
+            let should_check = less_than(self, next, count, 32);
+            let expected = self.add_const(pair[0], F::ONE);
+            let diff = self.sub(pair[1], expected);
+            let diff = self.mul(should_check.target, diff);
+            self.connect(diff, zero);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{C, D, F};
+    use mrp2_test_utils::circuit::{run_circuit, UserCircuit};
+    use plonky2::{
+        field::types::Field,
+        iop::witness::{PartialWitness, WitnessWrite},
+    };
+
+    const L: usize = 5;
+
+    #[derive(Clone, Debug)]
+    struct TestContiguousIdsCircuit {
+        ids: [u32; L],
+        count: usize,
+    }
+
+    impl UserCircuit<F, D> for TestContiguousIdsCircuit {
+        type Wires = ([Target; L], Target);
+
+        fn build(b: &mut CircuitBuilder<F, D>) -> Self::Wires {
+            let ids = b.add_virtual_target_arr::<L>();
+            let count = b.add_virtual_target();
+            b.assert_contiguous_ids(&ids, count);
+
+            (ids, count)
+        }
+
+        fn prove(&self, pw: &mut PartialWitness<F>, wires: &Self::Wires) {
+            for (target, id) in wires.0.iter().zip(self.ids) {
+                pw.set_target(*target, F::from_canonical_u32(id));
+            }
+            pw.set_target(wires.1, F::from_canonical_usize(self.count));
+        }
+    }
+
+    #[test]
+    fn test_assert_contiguous_ids() {
+        // contiguous IDs pass, regardless of the padding after `count`
+        run_circuit::<F, D, C, _>(TestContiguousIdsCircuit {
+            ids: [7, 8, 9, 10, 11],
+            count: L,
+        });
+        run_circuit::<F, D, C, _>(TestContiguousIdsCircuit {
+            ids: [7, 8, 9, 0, 0],
+            count: 3,
+        });
+        run_circuit::<F, D, C, _>(TestContiguousIdsCircuit {
+            ids: [0; L],
+            count: 0,
+        });
+
+        // a gap within the first `count` IDs is detected
+        let res = std::panic::catch_unwind(|| {
+            run_circuit::<F, D, C, _>(TestContiguousIdsCircuit {
+                ids: [7, 8, 10, 11, 0],
+                count: 4,
+            })
+        });
+        assert!(res.is_err(), "gap in contiguous IDs not detected");
+    }
+}
@@ -0,0 +1,662 @@
+//! Circuit proving the merge of the NFT IDs revealed by two queries over disjoint
+//! subranges of blocks, e.g. computed by different workers. Each worker returns a
+//! sorted set of IDs, and the merged set is their sorted union, without duplicates.
+//! The revelation proofs of the workers are verified recursively, and the IDs to
+//! be merged are taken from their public inputs, whose hashes are exposed to
+//! identify the merged proofs.
+
+use super::{identifier_for, nft_ids_at_runtime, num_io};
+use crate::{
+    api::{default_config, serialize_proof, ProofWithVK, C, D, F},
+    group_hashing::CircuitBuilderGroupHashing,
+    types::{CURVE_TARGET_LEN, PACKED_MAPPING_KEY_LEN},
+    utils::less_than,
+};
+use anyhow::{ensure, Result};
+use itertools::Itertools;
+use mrp2_utils::{
+    serialization::{deserialize, serialize},
+    utils::convert_slice_to_curve_point,
+};
+use plonky2::{
+    field::{
+        extension::{quintic::QuinticExtension, FieldExtension},
+        goldilocks_field::GoldilocksField,
+        types::{Field, PrimeField64},
+    },
+    hash::{
+        hash_types::{HashOut, NUM_HASH_OUT_ELTS},
+        hashing::hash_n_to_hash_no_pad,
+        poseidon::{PoseidonHash, PoseidonPermutation},
+    },
+    iop::{
+        target::{BoolTarget, Target},
+        witness::{PartialWitness, WitnessWrite},
+    },
+    plonk::{circuit_builder::CircuitBuilder, circuit_data::CircuitData, config::Hasher},
+};
+use plonky2_ecgfp5::{curve::curve::WeierstrassPoint, gadgets::curve::CircuitBuilderEcGFp5};
+use recursion_framework::framework::{
+    RecursiveCircuits, RecursiveCircuitsVerifierGagdet, RecursiveCircuitsVerifierTarget,
+};
+use serde::{Deserialize, Serialize};
+use std::{array::from_fn as create_array, cmp::Ordering};
+
+/// Merge natively two sorted sets of NFT IDs into their sorted union, removing
+/// the IDs found in both sets.
+pub fn merge_nft_results(left: &[u32], right: &[u32]) -> Vec<u32> {
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    let (mut i, mut j) = (0, 0);
+    while i < left.len() && j < right.len() {
+        match left[i].cmp(&right[j]) {
+            Ordering::Less => {
+                merged.push(left[i]);
+                i += 1;
+            }
+            Ordering::Greater => {
+                merged.push(right[j]);
+                j += 1;
+            }
+            Ordering::Equal => {
+                merged.push(left[i]);
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    merged.extend_from_slice(&left[i..]);
+    merged.extend_from_slice(&right[j..]);
+    merged
+}
+
+/// Set of at most `L` NFT IDs, sorted in the first `num_entries` slots
+#[derive(Serialize, Deserialize)]
+struct NftIdsTarget<const L: usize> {
+    #[serde(with = "serde_arrays")]
+    ids: [Target; L],
+    num_entries: Target,
+}
+
+impl<const L: usize> NftIdsTarget<L> {
+    /// Build a set of IDs, enforcing that IDs fit in 32 bits and that the
+    /// first `num_entries` IDs are strictly increasing. It returns as well the
+    /// flags specifying whether each slot is a real entry of the set.
+    fn new(b: &mut CircuitBuilder<GoldilocksField, 2>) -> (Self, [BoolTarget; L]) {
+        let t = b._true();
+        let ids: [Target; L] = create_array(|_| b.add_virtual_target());
+        let num_entries = b.add_virtual_target();
+        // num_entries <= L
+        let max_entries = b.constant(GoldilocksField::from_canonical_usize(L + 1));
+        let valid_num_entries = less_than(b, num_entries, max_entries, 8);
+        b.connect(valid_num_entries.target, t.target);
+
+        let is_included = create_array(|i| {
+            b.range_check(ids[i], 32);
+            let it = b.constant(GoldilocksField::from_canonical_usize(i));
+            less_than(b, it, num_entries, 8)
+        });
+        // values are unique since they are in strictly increasing order
+        for i in 1..L {
+            let ordered = less_than(b, ids[i - 1], ids[i], 32);
+            let should_be_ordered = b.select(is_included[i], ordered.target, t.target);
+            b.connect(should_be_ordered, t.target);
+        }
+
+        (Self { ids, num_entries }, is_included)
+    }
+
+    /// Enforce that the IDs of the set are the first `num_entries` NFT IDs
+    /// revealed by the revelation proof with public inputs `revelation_inputs`
+    fn connect_revelation(
+        &self,
+        b: &mut CircuitBuilder<GoldilocksField, 2>,
+        is_included: &[BoolTarget; L],
+        revelation_inputs: &[Target],
+    ) {
+        let revealed_ids = nft_ids_at_runtime(revelation_inputs, L);
+        for ((id, revealed), included) in self.ids.iter().zip(revealed_ids).zip(is_included) {
+            let expected = b.select(*included, *revealed, *id);
+            b.connect(expected, *id);
+        }
+    }
+
+    fn assign(&self, pw: &mut PartialWitness<GoldilocksField>, ids: &[u32]) {
+        assert!(ids.len() <= L, "too many IDs for the set");
+        self.ids.iter().enumerate().for_each(|(i, wire)| {
+            pw.set_target(
+                *wire,
+                GoldilocksField::from_canonical_u32(ids.get(i).copied().unwrap_or_default()),
+            )
+        });
+        pw.set_target(
+            self.num_entries,
+            GoldilocksField::from_canonical_usize(ids.len()),
+        );
+    }
+}
+
+/// Returns whether `id` is one of the entries of the set
+fn is_in_set<const L: usize>(
+    b: &mut CircuitBuilder<GoldilocksField, 2>,
+    id: Target,
+    set: &NftIdsTarget<L>,
+    is_included: &[BoolTarget; L],
+) -> BoolTarget {
+    let mut found = b._false();
+    for (entry, included) in set.ids.iter().zip(is_included) {
+        let is_equal = b.is_equal(id, *entry);
+        let is_entry = b.and(is_equal, *included);
+        found = b.or(found, is_entry);
+    }
+    found
+}
+
+/// Hash of the public inputs of a revelation proof, exposed by the merge circuit
+/// to identify the revelation proofs whose IDs are merged
+pub fn revelation_inputs_hash(inputs: &[GoldilocksField]) -> HashOut<GoldilocksField> {
+    hash_n_to_hash_no_pad::<GoldilocksField, PoseidonPermutation<_>>(inputs)
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MergeNftResultsWires<const L: usize> {
+    left: NftIdsTarget<L>,
+    right: NftIdsTarget<L>,
+    merged: Vec<Target>,
+    num_merged: Target,
+}
+
+/// Circuit proving that `merged` is the sorted union without duplicates of the
+/// `left` and `right` sets of at most `L` NFT IDs each, which are the IDs
+/// revealed by two query2 revelation proofs, whose public inputs are provided to
+/// `build`. The merged set, of at most `2 * L` IDs, its digest and the identities
+/// of the revelation proofs are exposed as public inputs:
+/// - merged IDs (`2 * L` F), padded with 0s
+/// - number of merged IDs (F)
+/// - digest of the merged IDs (curve point), accumulated as in the query2 circuits
+/// - hash of the public inputs of the left revelation proof (hash)
+/// - hash of the public inputs of the right revelation proof (hash)
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MergeNftResultsCircuit<const L: usize> {
+    pub(crate) left: Vec<u32>,
+    pub(crate) right: Vec<u32>,
+    pub(crate) merged: Vec<u32>,
+}
+
+impl<const L: usize> MergeNftResultsCircuit<L> {
+    /// Inputs merging the sorted sets of IDs `left` and `right`, revealed by the
+    /// revelation proofs with public inputs `left_revelation` and
+    /// `right_revelation`, respectively
+    pub fn new(
+        left: Vec<u32>,
+        left_revelation: &[GoldilocksField],
+        right: Vec<u32>,
+        right_revelation: &[GoldilocksField],
+    ) -> Result<Self> {
+        for (ids, revelation) in [(&left, left_revelation), (&right, right_revelation)] {
+            ensure!(
+                ids.len() <= L,
+                "number of IDs {} exceeds the maximum {L} of the merge circuit",
+                ids.len()
+            );
+            ensure!(
+                ids.iter().tuple_windows().all(|(prev, curr)| prev < curr),
+                "IDs to be merged must be sorted without duplicates"
+            );
+            ensure!(
+                revelation.len() == num_io::<L>(),
+                "invalid number of revelation public inputs: {}",
+                revelation.len()
+            );
+            ensure!(
+                ids.iter()
+                    .zip(nft_ids_at_runtime(revelation, L))
+                    .all(|(id, revealed)| GoldilocksField::from_canonical_u32(*id) == *revealed),
+                "IDs to be merged must be the ones revealed by the revelation proof"
+            );
+        }
+        let merged = merge_nft_results(&left, &right);
+
+        Ok(Self {
+            left,
+            right,
+            merged,
+        })
+    }
+
+    /// Build the circuit merging the IDs revealed by the revelation proofs with
+    /// public inputs `left_revelation` and `right_revelation`
+    pub fn build(
+        b: &mut CircuitBuilder<GoldilocksField, 2>,
+        left_revelation: &[Target],
+        right_revelation: &[Target],
+    ) -> MergeNftResultsWires<L> {
+        let t = b._true();
+        let (left, left_included) = NftIdsTarget::<L>::new(b);
+        let (right, right_included) = NftIdsTarget::<L>::new(b);
+        // the sets are the IDs revealed by the revelation proofs
+        left.connect_revelation(b, &left_included, left_revelation);
+        right.connect_revelation(b, &right_included, right_revelation);
+
+        let merged = b.add_virtual_targets(2 * L);
+        let num_merged = b.add_virtual_target();
+        let max_merged = b.constant(GoldilocksField::from_canonical_usize(2 * L + 1));
+        let valid_num_merged = less_than(b, num_merged, max_merged, 8);
+        b.connect(valid_num_merged.target, t.target);
+
+        let zero = b.zero();
+        let p0 = b.curve_zero();
+        let mut digests = Vec::with_capacity(2 * L);
+        let mut merged_included = Vec::with_capacity(2 * L);
+        for i in 0..2 * L {
+            b.range_check(merged[i], 32);
+            let it = b.constant(GoldilocksField::from_canonical_usize(i));
+            let should_be_included = less_than(b, it, num_merged, 8);
+            // the merged IDs are sorted and unique
+            if i > 0 {
+                let ordered = less_than(b, merged[i - 1], merged[i], 32);
+                let should_be_ordered = b.select(should_be_included, ordered.target, t.target);
+                b.connect(should_be_ordered, t.target);
+            }
+            // each merged ID comes from one of the sets
+            let in_left = is_in_set(b, merged[i], &left, &left_included);
+            let in_right = is_in_set(b, merged[i], &right, &right_included);
+            let in_sets = b.or(in_left, in_right);
+            let should_be_in_sets = b.select(should_be_included, in_sets.target, t.target);
+            b.connect(should_be_in_sets, t.target);
+            // unused slots are padded with 0s
+            let padded = b.select(should_be_included, zero, merged[i]);
+            b.connect(padded, zero);
+            // accumulate the ID as a left padded mapping key, as done in query2 circuits
+            let mut packed_key = [zero; PACKED_MAPPING_KEY_LEN];
+            packed_key[PACKED_MAPPING_KEY_LEN - 1] = merged[i];
+            let p = b.map_to_curve_point(&packed_key);
+            digests.push(b.curve_select(should_be_included, p, p0));
+            merged_included.push(should_be_included);
+        }
+
+        // each ID of the sets is in the merged set
+        for (set, is_included) in [(&left, &left_included), (&right, &right_included)] {
+            for (id, included) in set.ids.iter().zip(is_included) {
+                let mut found = b._false();
+                for (entry, entry_included) in merged.iter().zip(&merged_included) {
+                    let is_equal = b.is_equal(*id, *entry);
+                    let is_entry = b.and(is_equal, *entry_included);
+                    found = b.or(found, is_entry);
+                }
+                let should_be_found = b.select(*included, found.target, t.target);
+                b.connect(should_be_found, t.target);
+            }
+        }
+        let digest = b.add_curve_point(&digests);
+
+        b.register_public_inputs(&merged);
+        b.register_public_input(num_merged);
+        b.register_curve_public_input(digest);
+        for revelation in [left_revelation, right_revelation] {
+            let hash = b.hash_n_to_hash_no_pad::<PoseidonHash>(revelation.to_vec());
+            b.register_public_inputs(&hash.elements);
+        }
+
+        MergeNftResultsWires {
+            left,
+            right,
+            merged,
+            num_merged,
+        }
+    }
+
+    pub fn assign(
+        &self,
+        pw: &mut PartialWitness<GoldilocksField>,
+        wires: &MergeNftResultsWires<L>,
+    ) {
+        wires.left.assign(pw, &self.left);
+        wires.right.assign(pw, &self.right);
+        assert!(self.merged.len() <= 2 * L, "too many merged IDs");
+        wires.merged.iter().enumerate().for_each(|(i, wire)| {
+            pw.set_target(
+                *wire,
+                GoldilocksField::from_canonical_u32(
+                    self.merged.get(i).copied().unwrap_or_default(),
+                ),
+            )
+        });
+        pw.set_target(
+            wires.num_merged,
+            GoldilocksField::from_canonical_usize(self.merged.len()),
+        );
+    }
+}
+
+/// Inputs of the merge circuit: the sets of IDs to be merged together with the
+/// revelation proofs revealing them
+pub struct MergeNftResultsInput<const L: usize> {
+    circuit: MergeNftResultsCircuit<L>,
+    left_proof: ProofWithVK,
+    right_proof: ProofWithVK,
+}
+
+impl<const L: usize> MergeNftResultsInput<L> {
+    /// Inputs merging the sorted sets of IDs `left` and `right`, revealed by the
+    /// revelation proofs `left_proof` and `right_proof`, respectively
+    pub fn new(
+        left: Vec<u32>,
+        left_proof: Vec<u8>,
+        right: Vec<u32>,
+        right_proof: Vec<u8>,
+    ) -> Result<Self> {
+        let left_proof = ProofWithVK::deserialize(&left_proof)?;
+        let right_proof = ProofWithVK::deserialize(&right_proof)?;
+        let circuit = MergeNftResultsCircuit::new(
+            left,
+            &left_proof.proof().public_inputs,
+            right,
+            &right_proof.proof().public_inputs,
+        )?;
+
+        Ok(Self {
+            circuit,
+            left_proof,
+            right_proof,
+        })
+    }
+}
+
+/// Parameters of the merge circuit, which verifies the revelation proofs in the
+/// set of query circuits and enforces that both are query2 revelation proofs
+/// before merging the IDs they reveal
+#[derive(Serialize, Deserialize)]
+pub struct MergeNftResultsParams<const L: usize> {
+    merge_wires: MergeNftResultsWires<L>,
+    left_verifier_wires: RecursiveCircuitsVerifierTarget<D>,
+    right_verifier_wires: RecursiveCircuitsVerifierTarget<D>,
+    #[serde(serialize_with = "serialize", deserialize_with = "deserialize")]
+    circuit_data: CircuitData<F, C, D>,
+}
+
+impl<const L: usize> MergeNftResultsParams<L>
+where
+    [(); num_io::<L>()]:,
+    [(); <PoseidonHash as Hasher<F>>::HASH_SIZE]:,
+{
+    pub fn build(query_circuit_set: &RecursiveCircuits<F, C, D>) -> Self {
+        let mut b = CircuitBuilder::new(default_config());
+        let verifier_gadget = RecursiveCircuitsVerifierGagdet::<F, C, D, { num_io::<L>() }>::new(
+            default_config(),
+            query_circuit_set,
+        );
+        let left_verifier_wires = verifier_gadget.verify_proof_in_circuit_set(&mut b);
+        let right_verifier_wires = verifier_gadget.verify_proof_in_circuit_set(&mut b);
+        let left_revelation =
+            left_verifier_wires.get_public_input_targets::<F, { num_io::<L>() }>();
+        let right_revelation =
+            right_verifier_wires.get_public_input_targets::<F, { num_io::<L>() }>();
+        // the query circuit set includes the revelation circuits of other queries:
+        // the identifier, registered as the last public input, must be the query2 one
+        let identifier = b.constant(F::from_canonical_u8(identifier_for("QueryNFT")));
+        for revelation in [left_revelation, right_revelation] {
+            b.connect(revelation[num_io::<L>() - 1], identifier);
+        }
+        let merge_wires = MergeNftResultsCircuit::build(&mut b, left_revelation, right_revelation);
+        let circuit_data = b.build::<C>();
+
+        Self {
+            merge_wires,
+            left_verifier_wires,
+            right_verifier_wires,
+            circuit_data,
+        }
+    }
+
+    pub fn generate_proof(
+        &self,
+        query_circuit_set: &RecursiveCircuits<F, C, D>,
+        input: MergeNftResultsInput<L>,
+    ) -> Result<Vec<u8>> {
+        let mut pw = PartialWitness::new();
+        for (wires, proof) in [
+            (&self.left_verifier_wires, &input.left_proof),
+            (&self.right_verifier_wires, &input.right_proof),
+        ] {
+            let (proof, vd) = proof.into();
+            wires.set_target(&mut pw, query_circuit_set, proof, vd)?;
+        }
+        input.circuit.assign(&mut pw, &self.merge_wires);
+        let proof = self.circuit_data.prove(pw)?;
+        serialize_proof(&proof)
+    }
+
+    pub fn circuit_data(&self) -> &CircuitData<F, C, D> {
+        &self.circuit_data
+    }
+}
+
+/// Public inputs of the merge circuit, see `MergeNftResultsCircuit`
+pub struct MergeNftResultsPublicInputs<'a, const L: usize> {
+    pub inputs: &'a [GoldilocksField],
+}
+
+impl<'a, const L: usize> MergeNftResultsPublicInputs<'a, L> {
+    pub const TOTAL_LEN: usize = 2 * L + 1 + CURVE_TARGET_LEN + 2 * NUM_HASH_OUT_ELTS;
+
+    pub fn from_slice(inputs: &'a [GoldilocksField]) -> Self {
+        assert!(inputs.len() >= Self::TOTAL_LEN);
+        Self { inputs }
+    }
+
+    /// Merged IDs, without the padding
+    pub fn merged_ids(&self) -> Vec<u32> {
+        let num_merged = self.inputs[2 * L].to_canonical_u64() as usize;
+        self.inputs[..num_merged]
+            .iter()
+            .map(|id| id.to_canonical_u64() as u32)
+            .collect()
+    }
+
+    pub fn digest(&self) -> WeierstrassPoint {
+        let (x, y, is_inf) =
+            convert_slice_to_curve_point(&self.inputs[2 * L + 1..2 * L + 1 + CURVE_TARGET_LEN]);
+        WeierstrassPoint {
+            x: QuinticExtension::<GoldilocksField>::from_basefield_array(x),
+            y: QuinticExtension::<GoldilocksField>::from_basefield_array(y),
+            is_inf: is_inf.is_nonzero(),
+        }
+    }
+
+    /// Hash of the public inputs of the left revelation proof
+    pub fn left_revelation_hash(&self) -> HashOut<GoldilocksField> {
+        let offset = 2 * L + 1 + CURVE_TARGET_LEN;
+        HashOut::from_partial(&self.inputs[offset..offset + NUM_HASH_OUT_ELTS])
+    }
+
+    /// Hash of the public inputs of the right revelation proof
+    pub fn right_revelation_hash(&self) -> HashOut<GoldilocksField> {
+        let offset = 2 * L + 1 + CURVE_TARGET_LEN + NUM_HASH_OUT_ELTS;
+        HashOut::from_partial(&self.inputs[offset..offset + NUM_HASH_OUT_ELTS])
+    }
+
+    /// Check that the merged IDs come from the revelation proofs with public
+    /// inputs `left` and `right`
+    pub fn merges(&self, left: &[GoldilocksField], right: &[GoldilocksField]) -> bool {
+        self.left_revelation_hash() == revelation_inputs_hash(left)
+            && self.right_revelation_hash() == revelation_inputs_hash(right)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query2::revelation::verify_ids_against_digest;
+    use mrp2_test_utils::circuit::{run_circuit, UserCircuit};
+    use plonky2::field::types::Sample;
+    use recursion_framework::framework_testing::TestingRecursiveCircuits;
+
+    const L: usize = 3;
+
+    /// Public inputs of a revelation proof revealing `ids`
+    fn revelation_inputs(ids: &[u32]) -> Vec<F> {
+        let mut inputs = F::rand_vec(num_io::<L>());
+        // locate the revealed IDs through the positions of the public inputs
+        let positions = (0..inputs.len()).collect::<Vec<_>>();
+        for (position, id) in nft_ids_at_runtime(&positions, L).iter().zip(ids) {
+            inputs[*position] = F::from_canonical_u32(*id);
+        }
+        inputs
+    }
+
+    /// Public inputs of a revelation proof of the query identified by `name`
+    fn identified_revelation_inputs(ids: &[u32], name: &str) -> [F; num_io::<L>()] {
+        let mut inputs = revelation_inputs(ids);
+        *inputs.last_mut().unwrap() = F::from_canonical_u8(identifier_for(name));
+        inputs.try_into().unwrap()
+    }
+
+    #[derive(Clone, Debug)]
+    struct TestMergeCircuit {
+        c: MergeNftResultsCircuit<L>,
+        left_revelation: Vec<F>,
+        right_revelation: Vec<F>,
+    }
+
+    impl UserCircuit<F, D> for TestMergeCircuit {
+        type Wires = (MergeNftResultsWires<L>, Vec<Target>, Vec<Target>);
+
+        fn build(b: &mut CircuitBuilder<F, D>) -> Self::Wires {
+            let left_revelation = b.add_virtual_targets(num_io::<L>());
+            let right_revelation = b.add_virtual_targets(num_io::<L>());
+            let wires = MergeNftResultsCircuit::build(b, &left_revelation, &right_revelation);
+            (wires, left_revelation, right_revelation)
+        }
+
+        fn prove(&self, pw: &mut PartialWitness<F>, wires: &Self::Wires) {
+            pw.set_target_arr(&wires.1, &self.left_revelation);
+            pw.set_target_arr(&wires.2, &self.right_revelation);
+            self.c.assign(pw, &wires.0)
+        }
+    }
+
+    #[test]
+    fn test_merge_nft_results() {
+        assert_eq!(
+            merge_nft_results(&[1, 3, 5], &[2, 3, 6]),
+            vec![1, 2, 3, 5, 6]
+        );
+        assert_eq!(merge_nft_results(&[], &[2, 3]), vec![2, 3]);
+
+        let left_revelation = revelation_inputs(&[1, 3, 5]);
+        let right_revelation = revelation_inputs(&[2, 3, 6]);
+        let circuit = MergeNftResultsCircuit::<L>::new(
+            vec![1, 3, 5],
+            &left_revelation,
+            vec![2, 3, 6],
+            &right_revelation,
+        )
+        .unwrap();
+        assert_eq!(circuit.merged, vec![1, 2, 3, 5, 6]);
+        let test_circuit = TestMergeCircuit {
+            c: circuit.clone(),
+            left_revelation: left_revelation.clone(),
+            right_revelation: right_revelation.clone(),
+        };
+        let proof = run_circuit::<F, D, C, _>(test_circuit.clone());
+        let pi = MergeNftResultsPublicInputs::<L>::from_slice(&proof.public_inputs);
+        assert_eq!(pi.merged_ids(), vec![1, 2, 3, 5, 6]);
+        assert!(verify_ids_against_digest(&[1, 2, 3, 5, 6], pi.digest()));
+        assert!(pi.merges(&left_revelation, &right_revelation));
+        assert!(!pi.merges(&right_revelation, &left_revelation));
+
+        // unsorted sets and IDs not revealed by the revelation proofs are
+        // rejected natively
+        assert!(MergeNftResultsCircuit::<L>::new(
+            vec![3, 1],
+            &revelation_inputs(&[3, 1]),
+            vec![2],
+            &revelation_inputs(&[2]),
+        )
+        .is_err());
+        assert!(MergeNftResultsCircuit::<L>::new(
+            vec![1, 3, 4],
+            &left_revelation,
+            vec![2, 3, 6],
+            &right_revelation,
+        )
+        .is_err());
+
+        // a set differing from the IDs revealed by its revelation proof is
+        // rejected by the circuit
+        let res = std::panic::catch_unwind(|| {
+            run_circuit::<F, D, C, _>(TestMergeCircuit {
+                left_revelation: revelation_inputs(&[1, 3, 4]),
+                ..test_circuit.clone()
+            })
+        });
+        assert!(res.is_err(), "set not revealed by the revelation proof");
+
+        // incorrect merges are rejected by the circuit: missing ID, duplicated ID,
+        // ID not found in any set, unsorted IDs
+        for merged in [
+            vec![1, 2, 5, 6],
+            vec![1, 2, 3, 3, 5, 6],
+            vec![1, 2, 3, 4, 5, 6],
+            vec![2, 1, 3, 5, 6],
+        ] {
+            let res = std::panic::catch_unwind(|| {
+                run_circuit::<F, D, C, _>(TestMergeCircuit {
+                    c: MergeNftResultsCircuit::<L> {
+                        merged,
+                        ..circuit.clone()
+                    },
+                    ..test_circuit.clone()
+                })
+            });
+            assert!(res.is_err(), "incorrect merge not detected");
+        }
+    }
+
+    #[test]
+    fn test_merge_nft_results_recursive() {
+        let query_circuits = TestingRecursiveCircuits::<F, C, D, { num_io::<L>() }>::default();
+        let query_circuit_set = query_circuits.get_recursive_circuit_set();
+        let params = MergeNftResultsParams::<L>::build(query_circuit_set);
+        // generate revelation proofs in the set of query circuits
+        let revelation_proof = |inputs: [F; num_io::<L>()]| {
+            let [proof] = query_circuits.generate_input_proofs([inputs]).unwrap();
+            let [vd] = query_circuits.verifier_data_for_input_proofs::<1>();
+            ProofWithVK::from((proof, vd.clone())).serialize().unwrap()
+        };
+
+        let left_inputs = identified_revelation_inputs(&[1, 3, 5], "QueryNFT");
+        let right_inputs = identified_revelation_inputs(&[2, 3, 6], "QueryNFT");
+        let input = MergeNftResultsInput::<L>::new(
+            vec![1, 3, 5],
+            revelation_proof(left_inputs),
+            vec![2, 3, 6],
+            revelation_proof(right_inputs),
+        )
+        .unwrap();
+        let proof = params.generate_proof(query_circuit_set, input).unwrap();
+        let proof = crate::api::deserialize_proof::<F, C, D>(&proof).unwrap();
+        params.circuit_data().verify(proof.clone()).unwrap();
+        // the IDs are merged from the public inputs of the verified proofs
+        let pi = MergeNftResultsPublicInputs::<L>::from_slice(&proof.public_inputs);
+        assert_eq!(pi.merged_ids(), vec![1, 2, 3, 5, 6]);
+        assert!(pi.merges(&left_inputs, &right_inputs));
+
+        // a proof of another query is rejected
+        let erc20_inputs = identified_revelation_inputs(&[2, 3, 6], "QueryERC20");
+        let input = MergeNftResultsInput::<L>::new(
+            vec![1, 3, 5],
+            revelation_proof(left_inputs),
+            vec![2, 3, 6],
+            revelation_proof(erc20_inputs),
+        )
+        .unwrap();
+        let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            params.generate_proof(query_circuit_set, input)
+        }));
+        assert!(
+            !matches!(res, Ok(Ok(_))),
+            "revelation proof of another query accepted"
+        );
+    }
+}
@@ -2,15 +2,17 @@ use crate::{
     api::{default_config, deserialize_proof, ProofWithVK, C, D, F, QUERY_CIRCUIT_SET_SIZE},
     block::NUM_IVC_PUBLIC_INPUTS,
     eth::left_pad32,
+    group_hashing::map_to_curve_point,
     query2::block,
     types::PACKED_MAPPING_KEY_LEN,
-    utils::Packer,
+    utils::{keccak256, Packer},
 };
-use anyhow::Result;
+use anyhow::{bail, ensure, Result};
 use circuit::{
     revelation_num_io, BuilderParams, RevelationRecursiveInput, RevelationRecursiveWires,
 };
 use plonky2::{
+    field::{goldilocks_field::GoldilocksField, types::Field},
     hash::poseidon::PoseidonHash,
     iop::target::Target,
     plonk::{
@@ -19,6 +21,7 @@ use plonky2::{
         proof::ProofWithPublicInputs,
     },
 };
+use plonky2_ecgfp5::curve::curve::{Point, WeierstrassPoint};
 use recursion_framework::{
     circuit_builder::{CircuitWithUniversalVerifier, CircuitWithUniversalVerifierBuilder},
     framework::RecursiveCircuits,
@@ -29,7 +32,15 @@ use std::{array::from_fn as create_array, collections::BTreeMap};
 pub use self::circuit::RevelationCircuit;
 
 pub mod circuit;
+mod contiguous;
+mod merge;
 mod public_inputs;
+pub use self::contiguous::CircuitBuilderContiguousIds;
+pub use self::merge::{
+    merge_nft_results, revelation_inputs_hash, MergeNftResultsCircuit, MergeNftResultsInput,
+    MergeNftResultsParams, MergeNftResultsPublicInputs, MergeNftResultsWires,
+};
+pub(crate) use self::public_inputs::nft_ids_at_runtime;
 pub use self::public_inputs::RevelationPublicInputs;
 /// Wires containing the main logic wires of the RevelationCircuit,
 /// the verifier wires to check a crate::block proof (block db) and
@@ -109,6 +120,20 @@ pub const fn num_io<const L: usize>() -> usize {
     revelation_num_io::<L>() + 1
 }
 
+/// Full keccak hash of the name of a query type, from which the identifier
+/// registered by its revelation circuit is derived
+pub fn identifier_hash(name: &str) -> [u8; 32] {
+    keccak256(name.as_bytes())
+        .try_into()
+        .expect("keccak hash is 32 bytes long")
+}
+
+/// Identifier of the query type `name` registered as the last public input of
+/// its revelation proofs, i.e. `keccak256(name)[0]`
+pub fn identifier_for(name: &str) -> u8 {
+    identifier_hash(name)[0]
+}
+
 impl<const BLOCK_DB_DEPTH: usize, const L: usize> Parameters<BLOCK_DB_DEPTH, L>
 where
     [(); <PoseidonHash as Hasher<F>>::HASH_SIZE]:,
@@ -166,6 +191,116 @@ where
     }
 }
 
+/// Number of NFT IDs to reveal supported by `build_revelation_params`
+pub const SUPPORTED_NUM_IDS: [usize; 3] = [5, 10, 20];
+
+/// Inputs for the revelation step when the number of NFT IDs to reveal is only
+/// known at runtime; see `RevelationInput::new` for the meaning of each field.
+pub struct DynRevelationInput {
+    pub mapping_keys: Vec<Vec<u8>>,
+    pub query_min_block: usize,
+    pub query_max_block: usize,
+    pub query2_block_proof: Vec<u8>,
+    pub block_db_proof: Vec<u8>,
+    /// Set of circuits for query2/block proofs
+    pub query_block_circuit_set: RecursiveCircuits<F, C, D>,
+}
+
+/// Uniform interface over the revelation parameters built for any number of
+/// NFT IDs to reveal, which allows to choose it at runtime
+pub trait RevelationParams {
+    /// Number of NFT IDs revealed by the proofs, i.e. `L`
+    fn num_ids(&self) -> usize;
+    fn generate_proof(
+        &self,
+        query_circuits: &RecursiveCircuits<F, C, D>,
+        input: DynRevelationInput,
+    ) -> Result<Vec<u8>>;
+    fn verify_proof(&self, proof: Vec<u8>) -> Result<()>;
+}
+
+impl<const BLOCK_DB_DEPTH: usize, const L: usize> RevelationParams for Parameters<BLOCK_DB_DEPTH, L>
+where
+    [(); <PoseidonHash as Hasher<F>>::HASH_SIZE]:,
+    [(); num_io::<L>()]:,
+{
+    fn num_ids(&self) -> usize {
+        L
+    }
+
+    fn generate_proof(
+        &self,
+        query_circuits: &RecursiveCircuits<F, C, D>,
+        input: DynRevelationInput,
+    ) -> Result<Vec<u8>> {
+        ensure!(
+            input.mapping_keys.len() <= L,
+            "number of entries {} exceeds the number of IDs {L} of the revelation circuit",
+            input.mapping_keys.len()
+        );
+        let inputs = RevelationRecursiveInput::new(
+            RevelationInput::<L>::new(
+                input.mapping_keys,
+                input.query_min_block,
+                input.query_max_block,
+                input.query2_block_proof,
+                input.block_db_proof,
+            )?,
+            input.query_block_circuit_set,
+        )?;
+        Parameters::generate_proof(self, query_circuits, inputs)
+    }
+
+    fn verify_proof(&self, proof: Vec<u8>) -> Result<()> {
+        Parameters::verify_proof(self, proof)
+    }
+}
+
+/// Build the revelation parameters for `num_ids` NFT IDs to reveal, which must be
+/// one of `SUPPORTED_NUM_IDS`. Arguments are the same as `Parameters::build`.
+pub fn build_revelation_params<const BLOCK_DB_DEPTH: usize>(
+    num_ids: usize,
+    query2_block_set: &RecursiveCircuits<F, C, D>,
+    block_db_circuit_set: &RecursiveCircuits<F, C, D>,
+    block_db_verifier_data: &VerifierOnlyCircuitData<C, D>,
+) -> Result<Box<dyn RevelationParams>> {
+    Ok(match num_ids {
+        5 => Box::new(Parameters::<BLOCK_DB_DEPTH, 5>::build(
+            query2_block_set,
+            block_db_circuit_set,
+            block_db_verifier_data,
+        )),
+        10 => Box::new(Parameters::<BLOCK_DB_DEPTH, 10>::build(
+            query2_block_set,
+            block_db_circuit_set,
+            block_db_verifier_data,
+        )),
+        20 => Box::new(Parameters::<BLOCK_DB_DEPTH, 20>::build(
+            query2_block_set,
+            block_db_circuit_set,
+            block_db_verifier_data,
+        )),
+        _ => bail!(
+            "unsupported number of IDs to reveal {num_ids}, expected one of {:?}",
+            SUPPORTED_NUM_IDS
+        ),
+    })
+}
+
+/// Check natively that the NFT IDs revealed by a revelation proof are exactly
+/// the ones accumulated in `digest`, mirroring the check done in circuit: each
+/// ID is the last limb of the packed, left padded, mapping key mapped to a curve
+/// point, and the points of all the IDs are summed.
+pub fn verify_ids_against_digest(ids: &[u32], digest: WeierstrassPoint) -> bool {
+    let computed = ids.iter().fold(Point::NEUTRAL, |acc, id| {
+        let mut packed_key = [GoldilocksField::ZERO; PACKED_MAPPING_KEY_LEN];
+        packed_key[PACKED_MAPPING_KEY_LEN - 1] = GoldilocksField::from_canonical_u32(*id);
+        acc + map_to_curve_point(&packed_key)
+    });
+
+    computed.to_weierstrass() == digest
+}
+
 #[cfg(test)]
 mod test {
     use std::iter::once;
@@ -189,7 +324,7 @@ mod test {
         },
         hash::hash_types::{HashOut, NUM_HASH_OUT_ELTS},
     };
-    use rand::{thread_rng, Rng};
+    use rand::{seq::index::sample, thread_rng, Rng};
     use recursion_framework::framework_testing::TestingRecursiveCircuits;
     use serial_test::serial;
 
@@ -202,34 +337,22 @@ mod test {
         query2::block::BlockPublicInputs,
     };
 
-    #[test]
-    #[serial]
-    fn test_revelation_api() -> Result<()> {
-        // Generate a fake query2/block circuit set
-        let query2_testing_framework =
-            TestingRecursiveCircuits::<F, C, D, QUERY2_BLOCK_NUM_IO>::default();
-        let query2_block_circuit_set = query2_testing_framework.get_recursive_circuit_set();
-
-        // Generate a fake block/ verification key
-        let block_db_testing_framework =
-            TestingRecursiveCircuits::<F, C, D, BLOCK_DB_NUM_IO>::default();
-        let block_db_circuit_set = block_db_testing_framework.get_recursive_circuit_set();
-
-        // Generate a fake query circuits verification key
-        let queries_testing_framework =
-            TestingRecursiveCircuits::<F, C, D, { num_io::<L>() }>::default();
-        let queries_circuit_set = queries_testing_framework.get_recursive_circuit_set();
-
-        let block_db_vk = block_db_testing_framework.verifier_data_for_input_proofs::<1>()[0];
-        // Build the params
-        const L: usize = 2;
-        const BLOCK_DB_DEPTH: usize = 2;
-        let params = super::Parameters::<BLOCK_DB_DEPTH, L>::build(
-            query2_block_circuit_set,
-            block_db_circuit_set,
-            block_db_vk,
-        );
+    /// Inputs of a revelation proof built over fake query2/block and block db proofs
+    struct TestRevelationInputs {
+        mapping_keys: Vec<Vec<u8>>,
+        query_min_block: usize,
+        query_max_block: usize,
+        query2_block_proof: Vec<u8>,
+        block_db_proof: Vec<u8>,
+    }
 
+    /// Generate a fake block db proof and a fake query2/block proof for `num_keys`
+    /// distinct mapping keys, consistent with each other
+    fn generate_test_inputs<const BLOCK_DB_DEPTH: usize>(
+        num_keys: usize,
+        query2_testing_framework: &TestingRecursiveCircuits<F, C, D, QUERY2_BLOCK_NUM_IO>,
+        block_db_testing_framework: &TestingRecursiveCircuits<F, C, D, BLOCK_DB_NUM_IO>,
+    ) -> Result<TestRevelationInputs> {
         // Generate a fake block db proof
         let init_root = empty_merkle_root::<GoldilocksField, 2, BLOCK_DB_DEPTH>();
         let last_root = HashOut {
@@ -268,8 +391,11 @@ mod test {
         let user_address = Address::random();
         let mapping_slot = F::rand();
         let length_slot = F::rand();
-        let mapping_keys = (0..L)
-            .map(|_| left_pad::<MAPPING_KEY_LEN>(&[thread_rng().gen::<u8>()]))
+        // mapping keys must be distinct and non-zero, otherwise they are deduplicated
+        // or mistaken for padding when building the revelation inputs
+        let mapping_keys = sample(&mut thread_rng(), u8::MAX as usize, num_keys)
+            .into_iter()
+            .map(|key| left_pad::<MAPPING_KEY_LEN>(&[key as u8 + 1]))
             .collect::<Vec<_>>();
         let packed_field_mks = mapping_keys
             .iter()
@@ -304,19 +430,59 @@ mod test {
             .unwrap();
         let query2_block_vd = query2_testing_framework.verifier_data_for_input_proofs::<1>();
 
-        let q2_proof_buff = ProofWithVK {
-            proof: query2_block_proof[0].clone(),
-            vk: query2_block_vd[0].clone(),
-        }
-        .serialize()?;
-        let block_db_buff = serialize_proof(block_db_proof)?;
+        Ok(TestRevelationInputs {
+            mapping_keys: mapping_keys.into_iter().map(|x| x.to_vec()).collect(),
+            query_min_block: query_min_number.to_canonical_u64() as usize,
+            query_max_block: query_max_number.to_canonical_u64() as usize,
+            query2_block_proof: ProofWithVK {
+                proof: query2_block_proof[0].clone(),
+                vk: query2_block_vd[0].clone(),
+            }
+            .serialize()?,
+            block_db_proof: serialize_proof(block_db_proof)?,
+        })
+    }
+
+    #[test]
+    #[serial]
+    fn test_revelation_api() -> Result<()> {
+        // Generate a fake query2/block circuit set
+        let query2_testing_framework =
+            TestingRecursiveCircuits::<F, C, D, QUERY2_BLOCK_NUM_IO>::default();
+        let query2_block_circuit_set = query2_testing_framework.get_recursive_circuit_set();
+
+        // Generate a fake block/ verification key
+        let block_db_testing_framework =
+            TestingRecursiveCircuits::<F, C, D, BLOCK_DB_NUM_IO>::default();
+        let block_db_circuit_set = block_db_testing_framework.get_recursive_circuit_set();
+
+        // Generate a fake query circuits verification key
+        let queries_testing_framework =
+            TestingRecursiveCircuits::<F, C, D, { num_io::<L>() }>::default();
+        let queries_circuit_set = queries_testing_framework.get_recursive_circuit_set();
+
+        let block_db_vk = block_db_testing_framework.verifier_data_for_input_proofs::<1>()[0];
+        // Build the params
+        const L: usize = 2;
+        const BLOCK_DB_DEPTH: usize = 2;
+        let params = super::Parameters::<BLOCK_DB_DEPTH, L>::build(
+            query2_block_circuit_set,
+            block_db_circuit_set,
+            block_db_vk,
+        );
+
+        let inputs = generate_test_inputs::<BLOCK_DB_DEPTH>(
+            L,
+            &query2_testing_framework,
+            &block_db_testing_framework,
+        )?;
         let revelation_inputs = RevelationRecursiveInput::new(
             RevelationInput::new(
-                mapping_keys.into_iter().map(|x| x.to_vec()).collect(),
-                query_min_number.to_canonical_u64() as usize,
-                query_max_number.to_canonical_u64() as usize,
-                q2_proof_buff,
-                block_db_buff,
+                inputs.mapping_keys,
+                inputs.query_min_block,
+                inputs.query_max_block,
+                inputs.query2_block_proof,
+                inputs.block_db_proof,
             )?,
             query2_block_circuit_set.clone(),
         )?;
@@ -325,4 +491,103 @@ mod test {
         params.verify_proof(proof)?;
         Ok(())
     }
+
+    /// Build the revelation parameters for `L` chosen at runtime, and prove and
+    /// verify a revelation proof through the uniform interface
+    fn check_revelation_params_for<const L: usize>() -> Result<()>
+    where
+        [(); num_io::<L>()]:,
+    {
+        const BLOCK_DB_DEPTH: usize = 2;
+        let query2_testing_framework =
+            TestingRecursiveCircuits::<F, C, D, QUERY2_BLOCK_NUM_IO>::default();
+        let block_db_testing_framework =
+            TestingRecursiveCircuits::<F, C, D, BLOCK_DB_NUM_IO>::default();
+        let queries_testing_framework =
+            TestingRecursiveCircuits::<F, C, D, { num_io::<L>() }>::default();
+
+        let params = build_revelation_params::<BLOCK_DB_DEPTH>(
+            L,
+            query2_testing_framework.get_recursive_circuit_set(),
+            block_db_testing_framework.get_recursive_circuit_set(),
+            block_db_testing_framework.verifier_data_for_input_proofs::<1>()[0],
+        )?;
+        assert_eq!(params.num_ids(), L);
+
+        // reveal less IDs than the available slots
+        let inputs = generate_test_inputs::<BLOCK_DB_DEPTH>(
+            L - 1,
+            &query2_testing_framework,
+            &block_db_testing_framework,
+        )?;
+        let input = DynRevelationInput {
+            mapping_keys: inputs.mapping_keys,
+            query_min_block: inputs.query_min_block,
+            query_max_block: inputs.query_max_block,
+            query2_block_proof: inputs.query2_block_proof,
+            block_db_proof: inputs.block_db_proof,
+            query_block_circuit_set: query2_testing_framework.get_recursive_circuit_set().clone(),
+        };
+        let proof =
+            params.generate_proof(queries_testing_framework.get_recursive_circuit_set(), input)?;
+        params.verify_proof(proof)
+    }
+
+    #[test]
+    #[serial]
+    fn test_build_revelation_params() -> Result<()> {
+        check_revelation_params_for::<5>()?;
+        check_revelation_params_for::<10>()?;
+
+        // unsupported number of IDs
+        let query2_testing_framework =
+            TestingRecursiveCircuits::<F, C, D, QUERY2_BLOCK_NUM_IO>::default();
+        let block_db_testing_framework =
+            TestingRecursiveCircuits::<F, C, D, BLOCK_DB_NUM_IO>::default();
+        assert!(build_revelation_params::<2>(
+            7,
+            query2_testing_framework.get_recursive_circuit_set(),
+            block_db_testing_framework.get_recursive_circuit_set(),
+            block_db_testing_framework.verifier_data_for_input_proofs::<1>()[0],
+        )
+        .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_ids_against_digest() {
+        let mut rng = thread_rng();
+        // NFT IDs are stored in mapping keys of 4 bytes, left padded
+        let mapping_keys = (0..5)
+            .map(|_| left_pad32(&rng.gen::<[u8; 4]>()).pack())
+            .collect_vec();
+        // the revealed IDs are the last limbs of the packed mapping keys
+        let ids = mapping_keys
+            .iter()
+            .map(|key| *key.last().unwrap())
+            .collect_vec();
+        // digest computed as in the query2 circuits, from the packed mapping keys
+        let digest = group_hashing::add_curve_point(
+            &mapping_keys
+                .iter()
+                .map(|key| group_hashing::map_to_curve_point(&key.to_fields()))
+                .collect_vec(),
+        )
+        .to_weierstrass();
+
+        assert!(verify_ids_against_digest(&ids, digest));
+        // the order of the IDs doesn't matter
+        let reversed_ids = ids.iter().rev().cloned().collect_vec();
+        assert!(verify_ids_against_digest(&reversed_ids, digest));
+
+        // tampered IDs
+        let mut tampered_ids = ids.clone();
+        tampered_ids[2] = tampered_ids[2].wrapping_add(1);
+        assert!(!verify_ids_against_digest(&tampered_ids, digest));
+        assert!(!verify_ids_against_digest(&ids[1..], digest));
+        let mut extra_ids = ids.clone();
+        extra_ids.push(rng.gen::<u32>());
+        assert!(!verify_ids_against_digest(&extra_ids, digest));
+    }
 }
@@ -2,12 +2,15 @@ use std::array::from_fn as create_array;
 
 use mrp2_utils::{types::PACKED_U256_LEN, u256};
 use plonky2::{
-    field::goldilocks_field::GoldilocksField, iop::target::Target,
-    plonk::circuit_builder::CircuitBuilder,
+    field::goldilocks_field::GoldilocksField, hash::hash_types::NUM_HASH_OUT_ELTS,
+    iop::target::Target, plonk::circuit_builder::CircuitBuilder,
 };
 use plonky2_crypto::u32::arithmetic_u32::U32Target;
 
-use crate::{keccak::OutputHash, types::PackedAddressTarget};
+use crate::{
+    keccak::OutputHash,
+    types::{PackedAddressTarget, CURVE_TARGET_LEN},
+};
 
 #[derive(Clone, Copy, Debug)]
 #[repr(u8)]
@@ -24,9 +27,33 @@ enum Inputs<const L: usize> {
     BlockHeader,
     // Padded (2 * uint256) to make it uniform with the query-erc20 revelation public inputs
     Padded512,
+    // Padded hash to make it uniform with the query commitment of the query-erc20
+    // revelation public inputs
+    PaddedQueryCommitment,
+    // Padded (1 + 2 * uint256) to make it uniform with the scaled query result of
+    // the query-erc20 revelation public inputs
+    PaddedScaledResult,
+    // Padded curve point to make it uniform with the contract digest of the
+    // query-erc20 revelation public inputs
+    PaddedContractDigest,
+    // Padded hash to make it uniform with the slots commitment of the query-erc20
+    // revelation public inputs
+    PaddedSlotsCommitment,
+    // Padded item to make it uniform with the aggregation mode of the query-erc20
+    // revelation public inputs
+    PaddedAggregationMode,
+    // Padded uint256 to make it uniform with the max rewards rate of the
+    // query-erc20 revelation public inputs
+    PaddedMaxRewardsRate,
+    // Padded uint256 to make it uniform with the threshold of the query-erc20
+    // revelation public inputs
+    PaddedThreshold,
+    // Padded item to make it uniform with the leaf mode of the query-erc20
+    // revelation public inputs
+    PaddedLeafMode,
 }
 impl<const L: usize> Inputs<L> {
-    const SIZES: [usize; 11] = [
+    const SIZES: [usize; 19] = [
         // Block number
         1,
         // Range
@@ -49,6 +76,22 @@ impl<const L: usize> Inputs<L> {
         OutputHash::LEN,
         // Padded uint512
         2 * u256::NUM_LIMBS,
+        // Padded query commitment
+        OutputHash::LEN,
+        // Padded decimals and scaled uint256 result
+        1 + 2 * u256::NUM_LIMBS,
+        // Padded contract digest
+        CURVE_TARGET_LEN,
+        // Padded slots commitment
+        NUM_HASH_OUT_ELTS,
+        // Padded aggregation mode
+        1,
+        // Padded max rewards rate
+        PACKED_U256_LEN,
+        // Padded threshold
+        PACKED_U256_LEN,
+        // Padded leaf mode
+        1,
     ];
 
     const fn total_len() -> usize {
@@ -63,6 +106,14 @@ impl<const L: usize> Inputs<L> {
             + Self::SIZES[8]
             + Self::SIZES[9]
             + Self::SIZES[10]
+            + Self::SIZES[11]
+            + Self::SIZES[12]
+            + Self::SIZES[13]
+            + Self::SIZES[14]
+            + Self::SIZES[15]
+            + Self::SIZES[16]
+            + Self::SIZES[17]
+            + Self::SIZES[18]
     }
 
     fn range(&self) -> std::ops::Range<usize> {
@@ -155,6 +206,22 @@ impl<'a, const L: usize> RevelationPublicInputs<'a, Target, L> {
         // Register the 16 padded items (2 * uint256).
         let zero = b.zero();
         b.register_public_inputs(&[zero; 2 * u256::NUM_LIMBS]);
+        // Register the padded query commitment.
+        b.register_public_inputs(&[zero; OutputHash::LEN]);
+        // Register the padded decimals and scaled result.
+        b.register_public_inputs(&[zero; 1 + 2 * u256::NUM_LIMBS]);
+        // Register the padded contract digest.
+        b.register_public_inputs(&[zero; CURVE_TARGET_LEN]);
+        // Register the padded slots commitment.
+        b.register_public_inputs(&[zero; NUM_HASH_OUT_ELTS]);
+        // Register the padded aggregation mode.
+        b.register_public_input(zero);
+        // Register the padded max rewards rate.
+        b.register_public_inputs(&[zero; PACKED_U256_LEN]);
+        // Register the padded threshold.
+        b.register_public_inputs(&[zero; PACKED_U256_LEN]);
+        // Register the padded leaf mode.
+        b.register_public_input(zero);
     }
 
     fn block_number(&self) -> Target {
@@ -252,3 +319,10 @@ impl<'a, const L: usize> RevelationPublicInputs<'a, GoldilocksField, L> {
         self.block_header_raw()
     }
 }
+
+/// Get the NFT IDs from the public inputs of a revelation proof for `l` NFT IDs,
+/// where `l` is known only at runtime
+pub(crate) fn nft_ids_at_runtime<T>(inputs: &[T], l: usize) -> &[T] {
+    let offset = Inputs::<0>::NftIds.range().start;
+    &inputs[offset..offset + l]
+}
@@ -22,7 +22,7 @@ pub struct LeafWires {
 }
 
 /// This circuit prove the new root hash of a leaf containing the requested data
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct LeafCircuit {
     pub mapping_key: [u32; PACKED_MAPPING_KEY_LEN],
     pub mapping_value: [u32; PACKED_VALUE_LEN],
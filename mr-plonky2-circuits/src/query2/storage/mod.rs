@@ -1,7 +1,7 @@
 //! The module implementing the required mechanisms for ‶Query 2″
 //! https://www.notion.so/lagrangelabs/Cryptographic-Documentation-85adb821f18647b2a3dc65efbe144981?pvs=4#fa3f5d23a7724d0699a04f72bbec2a16
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 use plonky2::{
     field::goldilocks_field::GoldilocksField, hash::hash_types::HashOut,
     plonk::config::GenericHashOut,
@@ -13,7 +13,7 @@ use recursion_framework::{
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    api::{default_config, ProofWithVK, C, D, F},
+    api::{decode_circuit_input, default_config, encode_circuit_input, ProofWithVK, C, D, F},
     eth::left_pad32,
     utils::convert_u8_to_u32_slice,
 };
@@ -46,6 +46,36 @@ impl CircuitInput {
         })
     }
 
+    const LEAF_TAG: u8 = 0;
+    const PARTIAL_INNER_TAG: u8 = 1;
+    const FULL_INNER_TAG: u8 = 2;
+
+    /// Encode the input in a stable format, where each variant is identified by
+    /// an explicit tag, so that inputs can be durably queued before proving
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        match self {
+            CircuitInput::Leaf(leaf) => encode_circuit_input(Self::LEAF_TAG, leaf),
+            CircuitInput::PartialInner(partial_inner, proof) => {
+                encode_circuit_input(Self::PARTIAL_INNER_TAG, &(partial_inner, proof))
+            }
+            CircuitInput::FullInner(proofs) => encode_circuit_input(Self::FULL_INNER_TAG, proofs),
+        }
+    }
+
+    /// Decode an input encoded with `to_bytes`
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let (tag, payload) = decode_circuit_input(bytes)?;
+        Ok(match tag {
+            Self::LEAF_TAG => CircuitInput::Leaf(bincode::deserialize(payload)?),
+            Self::PARTIAL_INNER_TAG => {
+                let (partial_inner, proof) = bincode::deserialize(payload)?;
+                CircuitInput::PartialInner(partial_inner, proof)
+            }
+            Self::FULL_INNER_TAG => CircuitInput::FullInner(bincode::deserialize(payload)?),
+            _ => bail!("unknown tag {tag} for query2 storage circuit input"),
+        })
+    }
+
     pub fn new_partial_node(left: &[u8], right: &[u8], proved_is_right: bool) -> Self {
         let proof = ProofWithVK::deserialize(if proved_is_right { right } else { left })
             .expect("unable to deserialize proof");
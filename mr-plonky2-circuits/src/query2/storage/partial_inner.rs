@@ -28,7 +28,7 @@ pub struct PartialInnerNodeWires {
 /// This circuit prove the root of the subtree made of:
 ///   - a child whose hash has not changes on the side defined by unproved_is_left
 ///   - another child whose hash has been updated.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PartialInnerNodeCircuit {
     pub proved_is_right: bool,
     pub unproved_hash: HashOut<GoldilocksField>,
@@ -27,7 +27,7 @@ use plonky2::{
 use rand::{rngs::StdRng, RngCore, SeedableRng};
 
 use crate::{
-    api::ProofWithVK,
+    api::{tests::dummy_proof_with_vk, ProofWithVK},
     eth::left_pad32,
     group_hashing::map_to_curve_point,
     storage::lpn::{intermediate_node_hash, leaf_hash_for_mapping},
@@ -415,3 +415,49 @@ fn test_api() {
         .verify(ProofWithVK::deserialize(&full_inner).unwrap().proof)
         .unwrap();
 }
+
+#[test]
+fn test_query2_storage_circuit_input_encoding() {
+    let rng = &mut StdRng::seed_from_u64(42);
+    let leaf = CircuitInput::new_leaf(&rng.next_u32().to_be_bytes(), Address::random().as_bytes());
+    let partial_inner = CircuitInput::PartialInner(
+        PartialInnerNodeCircuit {
+            proved_is_right: rng.next_u32() & 1 == 1,
+            unproved_hash: HashOut::rand(),
+        },
+        dummy_proof_with_vk([F::rand(); 4]),
+    );
+    let full_inner = CircuitInput::FullInner((
+        dummy_proof_with_vk([F::rand(); 4]),
+        dummy_proof_with_vk([F::rand(); 4]),
+    ));
+
+    for input in [leaf, partial_inner, full_inner] {
+        let bytes = input.to_bytes().unwrap();
+        let decoded = CircuitInput::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.to_bytes().unwrap(), bytes);
+        match (input, decoded) {
+            (CircuitInput::Leaf(expected), CircuitInput::Leaf(found)) => {
+                assert_eq!(found.mapping_key, expected.mapping_key);
+                assert_eq!(found.mapping_value, expected.mapping_value);
+            }
+            (
+                CircuitInput::PartialInner(expected, expected_proof),
+                CircuitInput::PartialInner(found, proof),
+            ) => {
+                assert_eq!(found.proved_is_right, expected.proved_is_right);
+                assert_eq!(found.unproved_hash, expected.unproved_hash);
+                assert_eq!(proof, expected_proof);
+            }
+            (CircuitInput::FullInner(expected), CircuitInput::FullInner(found)) => {
+                assert_eq!(found, expected);
+            }
+            _ => panic!("wrong variant decoded"),
+        }
+
+        // an unknown tag is rejected
+        let mut unknown_tag = bytes;
+        unknown_tag[1] = u8::MAX;
+        assert!(CircuitInput::from_bytes(&unknown_tag).is_err());
+    }
+}
@@ -5,12 +5,13 @@ use super::{
     storage,
 };
 
+pub use super::block::multi_contract::CircuitInput as MultiContractCircuitInput;
 pub use super::block::CircuitInput as BlockCircuitInput;
 pub use super::revelation::RevelationErcInput;
 pub use super::state::CircuitInput as StateCircuitInput;
 pub use super::storage::CircuitInput as StorageCircuitInput;
 
-use crate::api::{BlockDBCircuitInfo, C, D, F};
+use crate::api::{estimate_proving_memory, BlockDBCircuitInfo, C, D, F};
 use plonky2::{
     hash::poseidon::PoseidonHash,
     plonk::{circuit_data::CircuitData, config::Hasher},
@@ -18,7 +19,7 @@ use plonky2::{
 use recursion_framework::framework::RecursiveCircuits;
 use serde::{Deserialize, Serialize};
 
-use crate::query_erc20::revelation::RevelationRecursiveInput;
+use crate::query_erc20::{revelation::RevelationRecursiveInput, transcript::ProofTranscript};
 use anyhow::Result;
 
 /// L is the number of elements we allow to expose in the result
@@ -29,16 +30,33 @@ pub enum CircuitInput<const L: usize> {
     State(state::CircuitInput),
     /// Input to be provided to generate a proof for the block DB circuit of query-erc20
     Block(block::CircuitInput),
+    /// Input to be provided to generate a proof aggregating the results of 2 contracts
+    /// for the same user, from the root proofs of the block DB circuit of each contract
+    MultiContract(block::multi_contract::CircuitInput),
     /// Input to be provided to generate a proof for the revelation circuit of query-erc20
     Revelation(revelation::RevelationErcInput<L>),
 }
 
+/// Shape of the proofs to be generated to aggregate the query results over a
+/// block tree, employed to estimate the resources needed before proving
+#[derive(Clone, Debug)]
+pub enum AggregationPlan {
+    /// A block, proven by a storage leaf proof, `storage_depth` storage inner
+    /// node proofs and a state proof
+    Leaf { storage_depth: usize },
+    /// A full node over 2 aggregated subtrees
+    Full(Box<AggregationPlan>, Box<AggregationPlan>),
+    /// A partial node over 1 aggregated subtree
+    Partial(Box<AggregationPlan>),
+}
+
 #[derive(Serialize, Deserialize)]
 /// Parameters representing the circuits employed to prove query2
 pub struct PublicParameters<const BLOCK_DB_DEPTH: usize, const L: usize> {
     storage: storage::Parameters,
     state: state::Parameters,
     block: block::Parameters,
+    multi_contract: block::multi_contract::Parameters,
     revelation: revelation::Parameters<BLOCK_DB_DEPTH, L>,
 }
 
@@ -52,6 +70,8 @@ where
         let storage = storage::Parameters::build();
         let state = state::Parameters::build(storage.get_storage_circuit_set());
         let block = block::Parameters::build(&state);
+        let multi_contract =
+            block::multi_contract::Parameters::build(block.get_block_circuit_set());
         let block_db_info =
             BlockDBCircuitInfo::<BLOCK_DB_DEPTH>::deserialize(block_db_circuit_info)?;
         let revelation = revelation::Parameters::build(
@@ -63,6 +83,7 @@ where
             storage,
             state,
             block,
+            multi_contract,
             revelation,
         })
     }
@@ -89,6 +110,11 @@ where
                 false,
             )),
             CircuitInput::Block(input) => Ok((self.block.generate_proof(input)?, false)),
+            CircuitInput::MultiContract(input) => Ok((
+                self.multi_contract
+                    .generate_proof(self.block.get_block_circuit_set(), input)?,
+                false,
+            )),
             CircuitInput::Revelation(inputs) => Ok((
                 self.revelation.generate_proof(
                     query_circuit_set,
@@ -101,8 +127,57 @@ where
             )),
         }
     }
+    /// Same as `generate_proof`, but also records the proof generation step in
+    /// `transcript`
+    pub(crate) fn generate_proof_with_transcript(
+        &self,
+        input: CircuitInput<L>,
+        query_circuit_set: &RecursiveCircuits<F, C, D>,
+        transcript: &mut ProofTranscript,
+    ) -> Result<(Vec<u8>, bool)> {
+        let (circuit, inputs) = ProofTranscript::step_inputs(&input)?;
+        let (proof, is_revelation) = self.generate_proof(input, query_circuit_set)?;
+        transcript.record(circuit, inputs, &proof);
+        Ok((proof, is_revelation))
+    }
     /// Circuit data for the final revelation circuit
     pub fn final_proof_circuit_data(&self) -> &CircuitData<F, C, D> {
         self.revelation.circuit_data()
     }
+    /// Set of circuits generating the proofs aggregating the results of several
+    /// contracts, employed to recursively verify them
+    pub fn multi_contract_circuit_set(&self) -> &RecursiveCircuits<F, C, D> {
+        self.multi_contract.get_circuit_set()
+    }
+}
+
+impl<const BLOCK_DB_DEPTH: usize, const L: usize> PublicParameters<BLOCK_DB_DEPTH, L> {
+    /// Maximum number of blocks that can be queried with these parameters, i.e. the
+    /// number of leaves of the block DB Merkle tree of depth `BLOCK_DB_DEPTH`
+    pub const fn max_block_range() -> usize {
+        1 << BLOCK_DB_DEPTH
+    }
+
+    /// Estimate the number of bytes needed to generate all the proofs in `plan`,
+    /// summing the memory footprint of the prover for each proof. This is an upper
+    /// bound useful for a scheduler to avoid running out of memory when the
+    /// proofs of the plan are generated concurrently.
+    pub fn estimate_peak_memory(&self, plan: &AggregationPlan) -> usize {
+        let estimate = |data: &CircuitData<F, C, D>| estimate_proving_memory(&data.common);
+        match plan {
+            AggregationPlan::Leaf { storage_depth } => {
+                estimate(self.storage.leaf_circuit_data())
+                    + storage_depth * estimate(self.storage.inner_node_circuit_data())
+                    + estimate(self.state.circuit_data())
+            }
+            AggregationPlan::Full(left, right) => {
+                estimate(self.block.full_node_circuit_data())
+                    + self.estimate_peak_memory(left)
+                    + self.estimate_peak_memory(right)
+            }
+            AggregationPlan::Partial(child) => {
+                estimate(self.block.partial_node_circuit_data()) + self.estimate_peak_memory(child)
+            }
+        }
+    }
 }
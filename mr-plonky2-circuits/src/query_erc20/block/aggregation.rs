@@ -0,0 +1,228 @@
+//! Aggregation of the query results of two children in the block tree, with
+//! the neutral element of each aggregation mode employed for padded children.
+
+use anyhow::{bail, Result};
+use ethers::prelude::U256;
+use mrp2_utils::u256::{CircuitBuilderU256, UInt256Target};
+use plonky2::{
+    field::{
+        goldilocks_field::GoldilocksField,
+        types::{Field, PrimeField64},
+    },
+    iop::target::{BoolTarget, Target},
+    plonk::circuit_builder::CircuitBuilder,
+};
+use serde::{Deserialize, Serialize};
+
+/// Operation employed to aggregate the query results of the children
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AggregationKind {
+    #[default]
+    Sum = 0,
+    Min = 1,
+    Max = 2,
+}
+
+impl AggregationKind {
+    const ALL: [Self; 3] = [Self::Sum, Self::Min, Self::Max];
+
+    /// Encoding of the aggregation kind as the aggregation mode exposed in
+    /// the public inputs of the block circuits
+    pub fn to_field(self) -> GoldilocksField {
+        GoldilocksField::from_canonical_u8(self as u8)
+    }
+
+    /// Decode the aggregation mode exposed in the public inputs of the block
+    /// circuits
+    pub fn from_field(mode: GoldilocksField) -> Result<Self> {
+        match Self::ALL.into_iter().find(|kind| kind.to_field() == mode) {
+            Some(kind) => Ok(kind),
+            None => bail!("invalid aggregation mode {}", mode.to_canonical_u64()),
+        }
+    }
+
+    /// Aggregate natively the query results of 2 children, as done in the
+    /// block circuits. It returns `None` if the sum overflows.
+    pub fn aggregate(self, left: U256, right: U256) -> Option<U256> {
+        match self {
+            AggregationKind::Sum => left.checked_add(right),
+            AggregationKind::Min => Some(left.min(right)),
+            AggregationKind::Max => Some(left.max(right)),
+        }
+    }
+
+    /// Neutral element of the aggregation, i.e., the value `e` such that
+    /// `aggregate(x, e) == x` for any `x`:
+    /// - `Sum`: 0
+    /// - `Min`: the maximum U256 value
+    /// - `Max`: 0
+    pub fn identity(self) -> U256 {
+        match self {
+            AggregationKind::Sum | AggregationKind::Max => U256::zero(),
+            AggregationKind::Min => U256::MAX,
+        }
+    }
+}
+
+/// Return the neutral element of the aggregation `kind`, i.e., the value `e`
+/// such that `aggregate(x, e) == x` for any `x`, see [AggregationKind::identity]
+pub fn aggregation_identity(
+    b: &mut CircuitBuilder<GoldilocksField, 2>,
+    kind: AggregationKind,
+) -> UInt256Target {
+    b.constant_u256(kind.identity())
+}
+
+/// Replace `value` with the neutral element of the aggregation mode `mode` if
+/// `is_padded` is true, so that a padded or absent child doesn't affect the
+/// aggregated result. The validity of `mode` is enforced by
+/// [aggregate_query_results].
+pub fn pad_query_result(
+    b: &mut CircuitBuilder<GoldilocksField, 2>,
+    mode: Target,
+    value: &UInt256Target,
+    is_padded: BoolTarget,
+) -> UInt256Target {
+    // only `Min` has a non-zero identity
+    let min_mode = b.constant(AggregationKind::Min.to_field());
+    let is_min = b.is_equal(mode, min_mode);
+    let [zero_identity, min_identity] =
+        [AggregationKind::Sum, AggregationKind::Min].map(|kind| aggregation_identity(b, kind));
+    let identity = b.select_u256(is_min, &min_identity, &zero_identity);
+    b.select_u256(is_padded, &identity, value)
+}
+
+/// Enforce that `mode` encodes a valid [AggregationKind], returning a flag for
+/// each kind, in the order `[Sum, Min, Max]`, exactly one of them being true.
+pub fn aggregation_mode_flags(
+    b: &mut CircuitBuilder<GoldilocksField, 2>,
+    mode: Target,
+) -> [BoolTarget; 3] {
+    let flags = AggregationKind::ALL.map(|kind| {
+        let kind = b.constant(kind.to_field());
+        b.is_equal(mode, kind)
+    });
+    let num_flags = b.add_many(flags.iter().map(|flag| flag.target));
+    let one = b.one();
+    b.connect(num_flags, one);
+    flags
+}
+
+/// Aggregate the query results of 2 children according to `mode`, a target
+/// encoding the [AggregationKind] chosen at proving time rather than when
+/// building the circuit. The circuit enforces that `mode` is a valid kind and,
+/// for `Sum`, that the addition doesn't overflow.
+pub fn aggregate_query_results(
+    b: &mut CircuitBuilder<GoldilocksField, 2>,
+    mode: Target,
+    left: &UInt256Target,
+    right: &UInt256Target,
+) -> UInt256Target {
+    let [is_sum, _, is_max] = aggregation_mode_flags(b, mode);
+    let (sum, overflow) = b.add_u256(left, right);
+    // an overflow is only forbidden if the result is actually the sum
+    let sum_overflow = b.mul(is_sum.target, overflow.0);
+    let zero = b.zero();
+    b.connect(sum_overflow, zero);
+    let min = b.min_u256(left, right);
+    let max = b.max_u256(left, right);
+    let min_or_max = b.select_u256(is_max, &max, &min);
+    b.select_u256(is_sum, &sum, &min_or_max)
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::prelude::U256;
+    use mrp2_test_utils::circuit::{run_circuit, UserCircuit};
+    use mrp2_utils::u256::WitnessWriteU256;
+    use plonky2::{
+        iop::witness::{PartialWitness, WitnessWrite},
+        plonk::config::PoseidonGoldilocksConfig,
+    };
+    use rand::{thread_rng, Rng};
+
+    use crate::utils::ToFields;
+
+    use super::*;
+
+    type F = GoldilocksField;
+    type C = PoseidonGoldilocksConfig;
+    const D: usize = 2;
+
+    #[derive(Clone, Debug)]
+    struct TestAggregationCircuit {
+        kind: AggregationKind,
+        left: U256,
+        right: U256,
+        right_is_padded: bool,
+    }
+
+    impl UserCircuit<F, D> for TestAggregationCircuit {
+        type Wires = (Target, UInt256Target, UInt256Target, BoolTarget);
+
+        fn build(c: &mut CircuitBuilder<F, D>) -> Self::Wires {
+            let mode = c.add_virtual_target();
+            let left = c.add_virtual_u256();
+            let right = c.add_virtual_u256();
+            let right_is_padded = c.add_virtual_bool_target_safe();
+            let padded_right = pad_query_result(c, mode, &right, right_is_padded);
+            let result = aggregate_query_results(c, mode, &left, &padded_right);
+            c.register_public_input_u256(&result);
+            (mode, left, right, right_is_padded)
+        }
+
+        fn prove(&self, pw: &mut PartialWitness<F>, wires: &Self::Wires) {
+            pw.set_target(wires.0, self.kind.to_field());
+            pw.set_u256_target(&wires.1, self.left);
+            pw.set_u256_target(&wires.2, self.right);
+            pw.set_bool_target(wires.3, self.right_is_padded);
+        }
+    }
+
+    fn check_aggregation(left: U256, right: U256, right_is_padded: bool) {
+        AggregationKind::ALL.into_iter().for_each(|kind| {
+            let circuit = TestAggregationCircuit {
+                kind,
+                left,
+                right,
+                right_is_padded,
+            };
+            let proof = run_circuit::<F, D, C, _>(circuit);
+
+            let expected = if right_is_padded {
+                left
+            } else {
+                kind.aggregate(left, right).unwrap()
+            };
+            assert_eq!(
+                proof.public_inputs,
+                expected.to_fields(),
+                "wrong aggregation result for {kind:?}"
+            );
+        });
+    }
+
+    #[test]
+    fn test_aggregation_with_padding() {
+        let rng = &mut thread_rng();
+        // small values to avoid overflows in the sum
+        let left = U256::from(rng.gen::<u128>());
+        let right = U256::from(rng.gen::<u128>());
+
+        check_aggregation(left, right, false);
+        check_aggregation(right, left, false);
+        // padding a child leaves the aggregated result unchanged
+        check_aggregation(left, right, true);
+        check_aggregation(U256::zero(), right, true);
+        check_aggregation(U256::MAX, right, true);
+    }
+
+    #[test]
+    fn test_aggregation_identity() {
+        let value = U256::from(thread_rng().gen::<u128>());
+        AggregationKind::ALL.into_iter().for_each(|kind| {
+            assert_eq!(kind.aggregate(value, kind.identity()), Some(value));
+            assert_eq!(kind.aggregate(kind.identity(), value), Some(value));
+        });
+    }
+}
@@ -3,22 +3,47 @@ use mrp2_utils::u256::CircuitBuilderU256;
 use plonky2::{
     field::goldilocks_field::GoldilocksField,
     hash::{hash_types::NUM_HASH_OUT_ELTS, poseidon::PoseidonHash},
-    iop::{target::Target, witness::PartialWitness},
+    iop::{
+        target::Target,
+        witness::{PartialWitness, WitnessWrite},
+    },
     plonk::circuit_builder::CircuitBuilder,
 };
 use recursion_framework::circuit_builder::CircuitLogicWires;
 use serde::{Deserialize, Serialize};
 
-use crate::array::Array;
+use crate::{array::Array, utils::greater_than};
 
-use super::BlockPublicInputs;
+use super::{
+    aggregation::{aggregate_query_results, pad_query_result, AggregationKind},
+    BlockPublicInputs,
+};
+
+/// Number of bits of the state depths compared to compute the maximum one;
+/// state depths are bounded by the maximum depth of the state tree
+pub(super) const STATE_DEPTH_BITS: usize = 8;
+
+/// Number of bits of the range of blocks covered by a node; block numbers are
+/// 32-bit values, so the range covered by any node must fit in 32 bits as well
+pub(crate) const RANGE_BITS: usize = 32;
 
 #[derive(Serialize, Deserialize)]
-pub struct FullNodeWires {}
+pub struct FullNodeWires {
+    /// Aggregation mode, which must be the one of both children
+    aggregation_mode: Target,
+}
 
-#[derive(Clone, Debug)]
-pub struct FullNodeCircuit {}
+#[derive(Clone, Debug, Default)]
+pub struct FullNodeCircuit {
+    aggregation: AggregationKind,
+}
 impl FullNodeCircuit {
+    /// Creates the full node aggregating the query results of its children
+    /// with `aggregation`, which must be the aggregation mode of the children
+    pub fn new(aggregation: AggregationKind) -> Self {
+        Self { aggregation }
+    }
+
     pub fn build(
         b: &mut CircuitBuilder<GoldilocksField, 2>,
         inputs: [BlockPublicInputs<Target>; 2],
@@ -38,7 +63,9 @@ impl FullNodeCircuit {
         inputs[0]
             .user_address()
             .enforce_equal(b, &inputs[1].user_address());
-        // M[0] == M[1]
+        // M[0] == M[1]: M is the original slot of the mapping, while each block
+        // is proven for the slot in use at that block, so the children may span
+        // a migration of the mapping
         b.connect(inputs[0].mapping_slot(), inputs[1].mapping_slot());
         // A[0] == A[1]
         inputs[0]
@@ -49,26 +76,66 @@ impl FullNodeCircuit {
             inputs[0].mapping_slot_length(),
             inputs[1].mapping_slot_length(),
         );
+        // O[0] == O[1] == O: the aggregation mode can't change along the tree
+        let aggregation_mode = b.add_virtual_target();
+        b.connect(inputs[0].aggregation_mode(), aggregation_mode);
+        b.connect(inputs[1].aggregation_mode(), aggregation_mode);
+        // K[0] == K[1], K_B[0] == K_B[1]: the rates of all the blocks are bound
+        // to the same schedule
+        b.connect_hashes(inputs[0].rates_commitment(), inputs[1].rates_commitment());
+        b.connect(inputs[0].rates_first_block(), inputs[1].rates_first_block());
+        // K_M[0] == K_M[1]: the slots of all the blocks are resolved from the
+        // same migrations of the mapping
+        b.connect_hashes(
+            inputs[0].slot_remaps_commitment(),
+            inputs[1].slot_remaps_commitment(),
+        );
+        // T[0] == T[1], L[0] == L[1]: all the blocks are computed by the leaves of
+        // the same mode
+        b.connect(inputs[0].leaf_mode(), inputs[1].leaf_mode());
+        b.enforce_equal_u256(&inputs[0].threshold(), &inputs[1].threshold());
 
         // block_number[0] == block_number[1] - range
-        let right_min = b.sub(inputs[1].block_number(), inputs[1].range());
-        let left_max = inputs[0].block_number();
+        let [(left_max, left_range), (right_max, right_range)] =
+            inputs.each_ref().map(|pi| pi.block_number_and_range(b));
+        let right_min = b.sub(right_max, right_range);
         b.connect(left_max, right_min);
 
         let root = b.hash_n_to_hash_no_pad::<PoseidonHash>(Vec::from(to_hash.arr));
-        let new_upper_block = inputs[1].block_number();
-        let new_range_length = b.add(inputs[0].range(), inputs[1].range());
-        let (new_result, overflow) =
-            b.add_u256(&inputs[0].query_results(), &inputs[1].query_results());
-        // ensure the prover is not trying to obtain invalid results by overflowing the mul
-        let _false = b._false();
-        b.connect(overflow.0, _false.target);
+        let new_upper_block = right_max;
+        let new_range_length = b.add(left_range, right_range);
+        // ensure the sum of the ranges doesn't wrap around the field
+        b.range_check(new_range_length, RANGE_BITS);
+        let new_aggregated_count =
+            b.add(inputs[0].aggregated_count(), inputs[1].aggregated_count());
+        // a child aggregating no blocks is a padding child, whose result is
+        // replaced by the identity of the aggregation mode
+        let zero = b.zero();
+        let [left_result, right_result] = inputs.each_ref().map(|pi| {
+            let is_padded = b.is_equal(pi.aggregated_count(), zero);
+            pad_query_result(b, aggregation_mode, &pi.query_results(), is_padded)
+        });
+        let new_result = aggregate_query_results(b, aggregation_mode, &left_result, &right_result);
         b.enforce_equal_u256(&inputs[0].rewards_rate(), &inputs[1].rewards_rate());
+        b.enforce_equal_u256(&inputs[0].max_rewards_rate(), &inputs[1].max_rewards_rate());
+        // D = max(D[0], D[1])
+        let left_is_deeper = greater_than(
+            b,
+            inputs[0].state_depth(),
+            inputs[1].state_depth(),
+            STATE_DEPTH_BITS,
+        );
+        let new_state_depth = b.select(
+            left_is_deeper,
+            inputs[0].state_depth(),
+            inputs[1].state_depth(),
+        );
 
         BlockPublicInputs::<Target>::register(
             b,
             new_upper_block,
             new_range_length,
+            new_aggregated_count,
             &root,
             &inputs[0].smart_contract_address(),
             &inputs[0].user_address(),
@@ -76,12 +143,22 @@ impl FullNodeCircuit {
             inputs[0].mapping_slot_length(),
             new_result,
             inputs[0].rewards_rate(),
+            inputs[0].max_rewards_rate(),
+            new_state_depth,
+            aggregation_mode,
+            &inputs[0].rates_commitment(),
+            inputs[0].rates_first_block(),
+            &inputs[0].slot_remaps_commitment(),
+            inputs[0].threshold(),
+            inputs[0].leaf_mode(),
         );
 
-        FullNodeWires {}
+        FullNodeWires { aggregation_mode }
     }
 
-    pub fn assign(&self, _pw: &mut PartialWitness<GoldilocksField>, _wires: &FullNodeWires) {}
+    pub fn assign(&self, pw: &mut PartialWitness<GoldilocksField>, wires: &FullNodeWires) {
+        pw.set_target(wires.aggregation_mode, self.aggregation.to_field());
+    }
 }
 
 type F = crate::api::F;
@@ -1,27 +1,41 @@
 use self::{
+    aggregation::AggregationKind,
     full_node::{FullNodeCircuit, FullNodeWires},
+    packing::{pack_block_range, packed_block_range, unpack_block_range, unpacked_block_range},
     partial_node::{PartialNodeCircuitInputs, PartialNodeWires},
 };
 use crate::{
-    api::{default_config, ProofWithVK, C, D, F},
+    api::{decode_circuit_input, default_config, encode_circuit_input, ProofWithVK, C, D, F},
+    query2::block::BlockPublicInputs as NftBlockPublicInputs,
     types::{HashOutput, PackedAddressTarget, PACKED_ADDRESS_LEN, PACKED_VALUE_LEN},
-    utils::convert_u32_fields_to_u8_vec,
+    utils::{convert_u32_fields_to_u8_vec, ToFields},
 };
-use anyhow::Result;
+use anyhow::{anyhow, bail, ensure, Result};
 use ethers::prelude::U256;
 use itertools::Itertools;
 use mrp2_utils::{
+    serialization::{deserialize, serialize},
     types::PACKED_U256_LEN,
     u256::{CircuitBuilderU256, UInt256Target},
     utils::convert_u32_fields_to_u256,
 };
 use plonky2::{
-    field::{goldilocks_field::GoldilocksField, types::Field},
-    hash::hash_types::{HashOut, HashOutTarget, NUM_HASH_OUT_ELTS},
+    field::{
+        goldilocks_field::GoldilocksField,
+        types::{Field, PrimeField64},
+    },
+    hash::{
+        hash_types::{HashOut, HashOutTarget, NUM_HASH_OUT_ELTS},
+        hashing::hash_n_to_hash_no_pad,
+        poseidon::PoseidonPermutation,
+    },
     iop::target::Target,
-    plonk::{circuit_builder::CircuitBuilder, config::GenericHashOut},
+    plonk::{
+        circuit_builder::CircuitBuilder,
+        circuit_data::{CircuitData, VerifierCircuitData},
+        config::GenericHashOut,
+    },
 };
-use plonky2_crypto::u32::arithmetic_u32::U32Target;
 use plonky2_ecgfp5::curve::curve::WeierstrassPoint;
 use recursion_framework::{
     circuit_builder::{CircuitWithUniversalVerifier, CircuitWithUniversalVerifierBuilder},
@@ -31,9 +45,13 @@ use serde::{Deserialize, Serialize};
 use std::{
     array::from_fn as create_array,
     fmt::{self, Debug},
+    iter,
 };
 
+pub mod aggregation;
 pub mod full_node;
+pub mod multi_contract;
+pub mod packing;
 pub mod partial_node;
 
 pub(crate) const BLOCK_CIRCUIT_SET_SIZE: usize = 3;
@@ -41,6 +59,9 @@ pub enum CircuitInput {
     /// left and right children proof
     FullNode((ProofWithVK, ProofWithVK)),
     PartialNode(PartialNodeCircuitInputs),
+    /// state proof of the only block matching the query, employed as the root
+    /// of the aggregation
+    SingleLeaf(ProofWithVK),
 }
 
 impl CircuitInput {
@@ -62,6 +83,39 @@ impl CircuitInput {
             sibling_is_left,
         )))
     }
+
+    /// Employ the state proof of a single block as the root of the aggregation,
+    /// for queries where only one block matches, without requiring a sibling
+    pub fn new_single_leaf(state_proof: Vec<u8>) -> Result<Self> {
+        Ok(Self::SingleLeaf(ProofWithVK::deserialize(&state_proof)?))
+    }
+
+    const FULL_NODE_TAG: u8 = 0;
+    const PARTIAL_NODE_TAG: u8 = 1;
+    const SINGLE_LEAF_TAG: u8 = 2;
+
+    /// Encode the input in a stable format, where each variant is identified by
+    /// an explicit tag, so that inputs can be durably queued before proving
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        match self {
+            CircuitInput::FullNode(proofs) => encode_circuit_input(Self::FULL_NODE_TAG, proofs),
+            CircuitInput::PartialNode(partial_node) => {
+                encode_circuit_input(Self::PARTIAL_NODE_TAG, partial_node)
+            }
+            CircuitInput::SingleLeaf(proof) => encode_circuit_input(Self::SINGLE_LEAF_TAG, proof),
+        }
+    }
+
+    /// Decode an input encoded with `to_bytes`
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let (tag, payload) = decode_circuit_input(bytes)?;
+        Ok(match tag {
+            Self::FULL_NODE_TAG => CircuitInput::FullNode(bincode::deserialize(payload)?),
+            Self::PARTIAL_NODE_TAG => CircuitInput::PartialNode(bincode::deserialize(payload)?),
+            Self::SINGLE_LEAF_TAG => CircuitInput::SingleLeaf(bincode::deserialize(payload)?),
+            _ => bail!("unknown tag {tag} for query ERC20 block circuit input"),
+        })
+    }
 }
 
 pub const NUM_IO: usize = BlockPublicInputs::<Target>::total_len();
@@ -71,6 +125,10 @@ pub struct Parameters {
     full_node_circuit: CircuitWithUniversalVerifier<F, C, D, 2, FullNodeWires>,
     partial_node_circuit: CircuitWithUniversalVerifier<F, C, D, 1, PartialNodeWires>,
     circuit_set: RecursiveCircuits<F, C, D>,
+    /// verifier data of the state circuit, whose proofs can be employed as single
+    /// leaf roots
+    #[serde(serialize_with = "serialize", deserialize_with = "deserialize")]
+    state_verifier_data: VerifierCircuitData<F, C, D>,
 }
 
 impl Parameters {
@@ -82,11 +140,9 @@ impl Parameters {
         let full_node_circuit = circuit_builder.build_circuit(());
         let partial_node_circuit = circuit_builder.build_circuit(());
 
+        let state_verifier_data = state_circuit_params.circuit_data().verifier_data();
         let circuit_digests = vec![
-            state_circuit_params
-                .circuit_data()
-                .verifier_only
-                .circuit_digest,
+            state_verifier_data.verifier_only.circuit_digest,
             full_node_circuit
                 .circuit_data()
                 .verifier_only
@@ -103,24 +159,14 @@ impl Parameters {
             full_node_circuit,
             partial_node_circuit,
             circuit_set,
+            state_verifier_data,
         }
     }
 
     pub fn generate_proof(&self, input: CircuitInput) -> Result<Vec<u8>> {
         match input {
             CircuitInput::FullNode((left_proof, right_proof)) => {
-                let (left_proof, left_vd) = left_proof.into();
-                let (right_proof, right_vd) = right_proof.into();
-                let proof = self.circuit_set.generate_proof(
-                    &self.full_node_circuit,
-                    [left_proof, right_proof],
-                    [&left_vd, &right_vd],
-                    FullNodeCircuit {},
-                )?;
-                ProofWithVK::from((
-                    proof,
-                    self.full_node_circuit.circuit_data().verifier_only.clone(),
-                ))
+                self.prove_full_node(left_proof, right_proof)?
             }
             CircuitInput::PartialNode(input) => {
                 let (inputs, child_proof) = input.into();
@@ -139,46 +185,186 @@ impl Parameters {
                         .clone(),
                 ))
             }
+            CircuitInput::SingleLeaf(state_proof) => {
+                // the state circuit belongs to the block circuit set, so its proof
+                // is already a valid root for the revelation circuit
+                ensure!(
+                    state_proof.verifier_data().circuit_digest
+                        == self.state_verifier_data.verifier_only.circuit_digest,
+                    "single leaf root must be a proof of the state circuit"
+                );
+                let pi = BlockPublicInputs::from(&state_proof.proof().public_inputs[..NUM_IO]);
+                ensure!(
+                    pi.range() == F::ONE,
+                    "single leaf root must span a single block"
+                );
+                state_proof
+            }
         }
         .serialize()
     }
 
+    fn prove_full_node(
+        &self,
+        left_proof: ProofWithVK,
+        right_proof: ProofWithVK,
+    ) -> Result<ProofWithVK> {
+        let [left_pi, right_pi] = [&left_proof, &right_proof]
+            .map(|proof| BlockPublicInputs::from(&proof.proof().public_inputs[..NUM_IO]));
+        let range_length = [&left_pi, &right_pi]
+            .iter()
+            .map(|pi| pi.range().to_canonical_u64())
+            .sum::<u64>();
+        ensure!(
+            range_length < 1 << full_node::RANGE_BITS,
+            "range of blocks covered by the full node {range_length} exceeds {} bits",
+            full_node::RANGE_BITS
+        );
+        // the aggregation mode is chosen by the leaves and propagated up to the root
+        let aggregation = left_pi.aggregation_mode()?;
+        ensure!(
+            right_pi.aggregation_mode()? == aggregation,
+            "children of the full node employ different aggregation modes"
+        );
+        let (left_proof, left_vd) = left_proof.into();
+        let (right_proof, right_vd) = right_proof.into();
+        let proof = self.circuit_set.generate_proof(
+            &self.full_node_circuit,
+            [left_proof, right_proof],
+            [&left_vd, &right_vd],
+            FullNodeCircuit::new(aggregation),
+        )?;
+        Ok(ProofWithVK::from((
+            proof,
+            self.full_node_circuit.circuit_data().verifier_only.clone(),
+        )))
+    }
+
+    /// Aggregate the block proofs `leaves`, sorted by block number, with full
+    /// nodes as soon as they arrive, returning the proof of the root. Pending
+    /// subtrees are merged as soon as they have the same height, so only
+    /// O(log n) proofs are kept in memory. If the number of leaves is a power
+    /// of 2, the aggregation tree is the complete binary tree over the leaves;
+    /// otherwise, the remaining subtrees are merged from the rightmost one.
+    pub fn fold_aggregation(&self, leaves: impl Iterator<Item = ProofWithVK>) -> Result<Vec<u8>> {
+        // stack of the roots of the pending subtrees, with their heights,
+        // which are strictly decreasing from the bottom of the stack
+        let mut subtrees: Vec<(usize, ProofWithVK)> = vec![];
+        for leaf in leaves {
+            let (mut height, mut root) = (0, leaf);
+            while subtrees
+                .last()
+                .is_some_and(|(last_height, _)| *last_height == height)
+            {
+                let (_, left) = subtrees.pop().unwrap();
+                root = self.prove_full_node(left, root)?;
+                height += 1;
+            }
+            subtrees.push((height, root));
+        }
+        let (_, mut root) = subtrees
+            .pop()
+            .ok_or(anyhow!("no block proofs to aggregate"))?;
+        while let Some((_, left)) = subtrees.pop() {
+            root = self.prove_full_node(left, root)?;
+        }
+
+        root.serialize()
+    }
+
+    /// Verify a proof of any root of the aggregation: a full or partial node, or
+    /// a state proof employed as single leaf root
     pub(crate) fn verify_proof(&self, proof: &[u8]) -> Result<()> {
         let proof = ProofWithVK::deserialize(proof)?;
         let (proof, vd) = proof.into();
-        let circuit_data = match () {
+        match () {
             () if vd == self.full_node_circuit.circuit_data().verifier_only => {
-                Ok(self.full_node_circuit.circuit_data())
+                self.full_node_circuit.circuit_data().verify(proof)
             }
             () if vd == self.partial_node_circuit.circuit_data().verifier_only => {
-                Ok(self.partial_node_circuit.circuit_data())
+                self.partial_node_circuit.circuit_data().verify(proof)
+            }
+            () if vd == self.state_verifier_data.verifier_only => {
+                self.state_verifier_data.verify(proof)
             }
             () => Err(anyhow::Error::msg(
                 "No circuit found for provided verifier data",
             )),
-        }?;
-        circuit_data.verify(proof)
+        }
+    }
+
+    /// Verify the provided proof and return its decoded public inputs, only if
+    /// the verification succeeds.
+    pub fn verify_and_decode(&self, proof: &[u8]) -> Result<OwnedBlockPublicInputs> {
+        self.verify_proof(proof)?;
+        let proof = ProofWithVK::deserialize(proof)?;
+        Ok(OwnedBlockPublicInputs(
+            proof.proof.public_inputs[..NUM_IO].to_vec(),
+        ))
     }
 
     pub(crate) fn get_block_circuit_set(&self) -> &RecursiveCircuits<F, C, D> {
         &self.circuit_set
     }
+
+    pub(crate) fn full_node_circuit_data(&self) -> &CircuitData<F, C, D> {
+        self.full_node_circuit.circuit_data()
+    }
+
+    pub(crate) fn partial_node_circuit_data(&self) -> &CircuitData<F, C, D> {
+        self.partial_node_circuit.circuit_data()
+    }
+}
+
+/// Compute natively the root exposed by the aggregation of block proofs, folding
+/// `leaf_roots` in order with Poseidon as done by `FullNodeCircuit` and
+/// `PartialNodeCircuit`. The first root is the one of the first subtree, and each
+/// subsequent root is hashed with the root accumulated so far; `positions[i]` is
+/// true if `leaf_roots[i + 1]` is the left child, as `sibling_is_left` in
+/// `PartialNodeCircuit`. A full node over 2 children corresponds to `false`.
+pub fn expected_root(leaf_roots: &[HashOut<F>], positions: &[bool]) -> HashOut<F> {
+    assert!(!leaf_roots.is_empty(), "no root to aggregate");
+    assert_eq!(
+        positions.len(),
+        leaf_roots.len() - 1,
+        "a position is needed for each aggregated root"
+    );
+    leaf_roots[1..].iter().zip(positions).fold(
+        leaf_roots[0],
+        |root, (sibling, &sibling_is_left)| {
+            let (left, right) = if sibling_is_left {
+                (sibling, &root)
+            } else {
+                (&root, sibling)
+            };
+            let inputs = left
+                .elements
+                .iter()
+                .chain(right.elements.iter())
+                .copied()
+                .collect_vec();
+            hash_n_to_hash_no_pad::<F, PoseidonPermutation<_>>(&inputs)
+        },
+    )
 }
 
 #[derive(Clone, Copy, Debug)]
 #[repr(u8)]
 pub enum Inputs {
-    /// B - block number of the latest block aggregated
-    BlockNumber,
-    /// R - aggregated range
-    Range,
+    /// B‖R - block number of the latest block aggregated and aggregated range,
+    /// packed in a single element as `(B << 32) | R`, see `packing`
+    BlockRange,
+    /// N - number of blocks actually aggregated, which is smaller than the
+    /// range if some blocks of the range are missing in the tree
+    AggregatedCount,
     /// C - Merkle hash of the subtree, or poseidon hash of the leaf
     Root,
     /// A - SMC address in compact packed u32
     SmartContractAddress,
     /// X - onwer's address - treated as generic 32byte value, packed in u32
     UserAddress,
-    /// M - mapping slot
+    /// M - mapping slot, which is the original one if the mapping migrated
+    /// to other slots
     MappingSlot,
     /// S - storage slot length
     StorageSlotLength,
@@ -186,8 +372,45 @@ pub enum Inputs {
     QueryResult,
     /// R - Rewards rate of the query
     RewardsRate,
+    /// R_MAX - upper bound on the rewards rate enforced by the storage leaves,
+    /// the maximum U256 if unbounded
+    MaxRewardsRate,
+    /// D - maximum depth of the state tree openings of the aggregated blocks
+    StateDepth,
+    /// O - aggregation mode employed to aggregate the query results, encoding
+    /// an `AggregationKind`
+    AggregationMode,
+    /// K - commitment to the schedule of rewards rates the rate of each
+    /// aggregated block is bound to, or the zero hash if there is no schedule
+    RatesCommitment,
+    /// K_B - first block number of the schedule of rewards rates
+    RatesFirstBlock,
+    /// K_M - commitment to the migrations of the mapping to other slots the
+    /// slot of each aggregated block is resolved from, or the zero hash if the
+    /// mapping never migrated
+    SlotRemapsCommitment,
+    /// T - threshold the balances are compared to, or minimum balance of the
+    /// flat rewards, zero for proportional rewards
+    Threshold,
+    /// L - mode of the storage leaves, encoding the identifier returned by
+    /// `leaf_mode_id`
+    LeafMode,
 }
-const NUM_ELEMENTS: usize = 9;
+const NUM_ELEMENTS: usize = 17;
+
+// Adding a variant to `Inputs` without updating `NUM_ELEMENTS` and `SIZES`
+// would silently corrupt the layout of the public inputs: there must be one
+// size per variant, and the last variant must end where the sum of the sizes
+// does.
+const _: () = assert!(
+    NUM_ELEMENTS == Inputs::LeafMode as usize + 1,
+    "one size must be provided for each variant of the block public inputs"
+);
+const _: () = assert!(
+    Inputs::LeafMode.offset() + Inputs::LeafMode.len() == Inputs::total_len(),
+    "inconsistent layout of the block public inputs"
+);
+
 impl Inputs {
     const SIZES: [usize; NUM_ELEMENTS] = [
         1,
@@ -199,33 +422,42 @@ impl Inputs {
         1,
         PACKED_U256_LEN, // result
         PACKED_U256_LEN, // reward rate
+        PACKED_U256_LEN, // max reward rate
+        1,
+        1,
+        NUM_HASH_OUT_ELTS, // rates commitment
+        1,
+        NUM_HASH_OUT_ELTS, // slot remaps commitment
+        PACKED_U256_LEN,   // threshold
+        1,
     ];
 
+    /// Sum of the sizes of the first `n` variants
+    const fn sum_sizes(n: usize) -> usize {
+        let mut sum = 0;
+        let mut i = 0;
+        while i < n {
+            sum += Self::SIZES[i];
+            i += 1;
+        }
+        sum
+    }
+
     const fn total_len() -> usize {
-        Self::SIZES[0]
-            + Self::SIZES[1]
-            + Self::SIZES[2]
-            + Self::SIZES[3]
-            + Self::SIZES[4]
-            + Self::SIZES[5]
-            + Self::SIZES[6]
-            + Self::SIZES[7]
-            + Self::SIZES[8]
+        Self::sum_sizes(NUM_ELEMENTS)
     }
 
     pub const fn len(&self) -> usize {
-        let me = *self as u8;
-        Self::SIZES[me as usize]
+        Self::SIZES[*self as usize]
     }
 
-    fn range(&self) -> std::ops::Range<usize> {
-        let mut offset = 0;
-        let me = *self as u8;
-        for i in 0..me {
-            offset += Self::SIZES[i as usize];
-        }
+    const fn offset(&self) -> usize {
+        Self::sum_sizes(*self as usize)
+    }
 
-        offset..offset + Self::SIZES[me as usize]
+    fn range(&self) -> std::ops::Range<usize> {
+        let offset = self.offset();
+        offset..offset + self.len()
     }
 }
 
@@ -237,8 +469,8 @@ pub struct BlockPublicInputs<'input, T: Clone> {
 
 impl<'a, T: Clone + Copy + Debug> Debug for BlockPublicInputs<'a, T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        writeln!(f, "BlockNumber: {:?}", self.block_number_raw())?;
-        writeln!(f, "Range: {:?}", self.range_raw())?;
+        writeln!(f, "BlockNumber‖Range: {:?}", self.block_range_raw())?;
+        writeln!(f, "Aggregated count: {:?}", self.aggregated_count_raw())?;
         writeln!(f, "Root: {:?}", self.root_raw())?;
         writeln!(f, "SC Address: {:?}", self.smart_contract_address_raw())?;
         writeln!(f, "Owner Address: {:?}", self.user_address_raw())?;
@@ -248,7 +480,20 @@ impl<'a, T: Clone + Copy + Debug> Debug for BlockPublicInputs<'a, T> {
             "Storage slot length: {:?}",
             self.storage_slot_length_raw()
         )?;
-        writeln!(f, "Query Results: {:?}", self.query_results_raw())
+        writeln!(f, "Query Results: {:?}", self.query_results_raw())?;
+        writeln!(f, "Rewards rate: {:?}", self.rewards_rate_raw())?;
+        writeln!(f, "Max rewards rate: {:?}", self.max_rewards_rate_raw())?;
+        writeln!(f, "State depth: {:?}", self.state_depth_raw())?;
+        writeln!(f, "Aggregation mode: {:?}", self.aggregation_mode_raw())?;
+        writeln!(f, "Rates commitment: {:?}", self.rates_commitment_raw())?;
+        writeln!(f, "Rates first block: {:?}", self.rates_first_block_raw())?;
+        writeln!(
+            f,
+            "Slot remaps commitment: {:?}",
+            self.slot_remaps_commitment_raw()
+        )?;
+        writeln!(f, "Threshold: {:?}", self.threshold_raw())?;
+        writeln!(f, "Leaf mode: {:?}", self.leaf_mode_raw())
     }
 }
 
@@ -259,12 +504,29 @@ impl<'a, T: Clone + Copy> From<&'a [T]> for BlockPublicInputs<'a, T> {
     }
 }
 
+/// Public inputs of a block proof owned by the caller, as returned by
+/// `Parameters::verify_and_decode`, which are accessed through the
+/// `BlockPublicInputs` view borrowing them
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OwnedBlockPublicInputs(Vec<GoldilocksField>);
+
+impl OwnedBlockPublicInputs {
+    /// Returns the `BlockPublicInputs` view over the public inputs
+    pub fn view(&self) -> BlockPublicInputs<'_, GoldilocksField> {
+        BlockPublicInputs::from(self.0.as_slice())
+    }
+
+    pub fn as_slice(&self) -> &[GoldilocksField] {
+        &self.0
+    }
+}
+
 impl<'a, T: Clone + Copy> BlockPublicInputs<'a, T> {
-    fn block_number_raw(&self) -> &[T] {
-        &self.inputs[Inputs::BlockNumber.range()]
+    fn block_range_raw(&self) -> &[T] {
+        &self.inputs[Inputs::BlockRange.range()]
     }
-    fn range_raw(&self) -> &[T] {
-        &self.inputs[Inputs::Range.range()]
+    fn aggregated_count_raw(&self) -> &[T] {
+        &self.inputs[Inputs::AggregatedCount.range()]
     }
     fn root_raw(&self) -> &[T] {
         &self.inputs[Inputs::Root.range()]
@@ -291,18 +553,61 @@ impl<'a, T: Clone + Copy> BlockPublicInputs<'a, T> {
         self.inputs[Inputs::RewardsRate.range()].try_into().unwrap()
     }
 
+    fn max_rewards_rate_raw(&self) -> [T; PACKED_U256_LEN] {
+        self.inputs[Inputs::MaxRewardsRate.range()]
+            .try_into()
+            .unwrap()
+    }
+
+    fn state_depth_raw(&self) -> &[T] {
+        &self.inputs[Inputs::StateDepth.range()]
+    }
+
+    fn aggregation_mode_raw(&self) -> &[T] {
+        &self.inputs[Inputs::AggregationMode.range()]
+    }
+
+    fn rates_commitment_raw(&self) -> &[T] {
+        &self.inputs[Inputs::RatesCommitment.range()]
+    }
+
+    fn rates_first_block_raw(&self) -> &[T] {
+        &self.inputs[Inputs::RatesFirstBlock.range()]
+    }
+
+    fn slot_remaps_commitment_raw(&self) -> &[T] {
+        &self.inputs[Inputs::SlotRemapsCommitment.range()]
+    }
+
+    fn threshold_raw(&self) -> [T; PACKED_U256_LEN] {
+        self.inputs[Inputs::Threshold.range()].try_into().unwrap()
+    }
+
+    fn leaf_mode_raw(&self) -> &[T] {
+        &self.inputs[Inputs::LeafMode.range()]
+    }
+
     pub(crate) const fn total_len() -> usize {
         Inputs::total_len()
     }
 }
 
 impl<'a> BlockPublicInputs<'a, Target> {
-    pub(crate) fn block_number(&self) -> Target {
-        self.block_number_raw()[0]
+    /// Returns the packed block number and range, which can be compared as is
+    pub(crate) fn packed_block_range(&self) -> Target {
+        self.block_range_raw()[0]
     }
 
-    pub(crate) fn range(&self) -> Target {
-        self.range_raw()[0]
+    /// Unpack the block number and the range, returned in this order
+    pub(crate) fn block_number_and_range(
+        &self,
+        b: &mut CircuitBuilder<GoldilocksField, 2>,
+    ) -> (Target, Target) {
+        unpack_block_range(b, self.packed_block_range())
+    }
+
+    pub(crate) fn aggregated_count(&self) -> Target {
+        self.aggregated_count_raw()[0]
     }
 
     pub(crate) fn root(&self) -> HashOutTarget {
@@ -312,23 +617,13 @@ impl<'a> BlockPublicInputs<'a, Target> {
     }
 
     pub(crate) fn smart_contract_address(&self) -> PackedAddressTarget {
-        PackedAddressTarget::try_from(
-            self.smart_contract_address_raw()
-                .iter()
-                .map(|&t| U32Target(t))
-                .collect_vec(),
-        )
-        .unwrap()
+        PackedAddressTarget::from_targets_checked(self.smart_contract_address_raw())
+            .expect("the smart contract address is exposed as a packed address")
     }
 
     pub(crate) fn user_address(&self) -> PackedAddressTarget {
-        PackedAddressTarget::try_from(
-            self.user_address_raw()
-                .iter()
-                .map(|&t| U32Target(t))
-                .collect_vec(),
-        )
-        .unwrap()
+        PackedAddressTarget::from_targets_checked(self.user_address_raw())
+            .expect("the user address is exposed as a packed address")
     }
 
     pub(crate) fn mapping_slot(&self) -> Target {
@@ -349,10 +644,49 @@ impl<'a> BlockPublicInputs<'a, Target> {
         UInt256Target::new_from_target_limbs(&raw).expect("invalid length of slice inputs")
     }
 
+    pub(crate) fn max_rewards_rate(&self) -> UInt256Target {
+        let raw = self.max_rewards_rate_raw();
+        UInt256Target::new_from_target_limbs(&raw).expect("invalid length of slice inputs")
+    }
+
+    pub(crate) fn state_depth(&self) -> Target {
+        self.state_depth_raw()[0]
+    }
+
+    pub(crate) fn aggregation_mode(&self) -> Target {
+        self.aggregation_mode_raw()[0]
+    }
+
+    pub(crate) fn rates_commitment(&self) -> HashOutTarget {
+        HashOutTarget {
+            elements: self.rates_commitment_raw().try_into().unwrap(),
+        }
+    }
+
+    pub(crate) fn rates_first_block(&self) -> Target {
+        self.rates_first_block_raw()[0]
+    }
+
+    pub(crate) fn slot_remaps_commitment(&self) -> HashOutTarget {
+        HashOutTarget {
+            elements: self.slot_remaps_commitment_raw().try_into().unwrap(),
+        }
+    }
+
+    pub(crate) fn threshold(&self) -> UInt256Target {
+        let raw = self.threshold_raw();
+        UInt256Target::new_from_target_limbs(&raw).expect("invalid length of slice inputs")
+    }
+
+    pub(crate) fn leaf_mode(&self) -> Target {
+        self.leaf_mode_raw()[0]
+    }
+
     pub fn register(
         b: &mut CircuitBuilder<GoldilocksField, 2>,
         block_number: Target,
         range: Target,
+        aggregated_count: Target,
         root: &HashOutTarget,
         smc_address: &PackedAddressTarget,
         user_address: &PackedAddressTarget,
@@ -360,9 +694,18 @@ impl<'a> BlockPublicInputs<'a, Target> {
         mapping_slot_length: Target,
         results: UInt256Target,
         rewards_rate: UInt256Target,
+        max_rewards_rate: UInt256Target,
+        state_depth: Target,
+        aggregation_mode: Target,
+        rates_commitment: &HashOutTarget,
+        rates_first_block: Target,
+        slot_remaps_commitment: &HashOutTarget,
+        threshold: UInt256Target,
+        leaf_mode: Target,
     ) {
-        b.register_public_input(block_number);
-        b.register_public_input(range);
+        let block_range = pack_block_range(b, block_number, range);
+        b.register_public_input(block_range);
+        b.register_public_input(aggregated_count);
         b.register_public_inputs(&root.elements);
         smc_address.register_as_public_input(b);
         user_address.register_as_public_input(b);
@@ -370,14 +713,24 @@ impl<'a> BlockPublicInputs<'a, Target> {
         b.register_public_input(mapping_slot_length);
         b.register_public_input_u256(&results);
         b.register_public_input_u256(&rewards_rate);
+        b.register_public_input_u256(&max_rewards_rate);
+        b.register_public_input(state_depth);
+        b.register_public_input(aggregation_mode);
+        b.register_public_inputs(&rates_commitment.elements);
+        b.register_public_input(rates_first_block);
+        b.register_public_inputs(&slot_remaps_commitment.elements);
+        b.register_public_input_u256(&threshold);
+        b.register_public_input(leaf_mode);
     }
 }
 
 impl<'a> BlockPublicInputs<'a, GoldilocksField> {
-    // Only used for testing.
+    // Only used for testing. It panics if the block number or the range can't be
+    // packed in a single element.
     pub fn from_parts(
         block_number: GoldilocksField,
         range: GoldilocksField,
+        aggregated_count: GoldilocksField,
         root: HashOut<GoldilocksField>,
         smart_contract_address: &[GoldilocksField; PACKED_ADDRESS_LEN],
         user_address: &[GoldilocksField; PACKED_ADDRESS_LEN],
@@ -385,10 +738,21 @@ impl<'a> BlockPublicInputs<'a, GoldilocksField> {
         storage_slot_length: GoldilocksField,
         query_results: &[GoldilocksField; PACKED_U256_LEN],
         rewards_rate: &[GoldilocksField; PACKED_U256_LEN],
+        max_rewards_rate: &[GoldilocksField; PACKED_U256_LEN],
+        state_depth: GoldilocksField,
+        aggregation: AggregationKind,
+        rates_commitment: HashOut<GoldilocksField>,
+        rates_first_block: GoldilocksField,
+        slot_remaps_commitment: HashOut<GoldilocksField>,
+        threshold: &[GoldilocksField; PACKED_U256_LEN],
+        leaf_mode: GoldilocksField,
     ) -> [GoldilocksField; Self::total_len()] {
         let mut inputs = vec![];
-        inputs.push(block_number);
-        inputs.push(range);
+        inputs.push(
+            packed_block_range(block_number.to_canonical_u64(), range.to_canonical_u64())
+                .expect("block number and range must fit in 32 bits"),
+        );
+        inputs.push(aggregated_count);
         inputs.extend_from_slice(&root.elements);
         inputs.extend_from_slice(smart_contract_address.as_slice());
         inputs.extend_from_slice(user_address.as_slice());
@@ -396,6 +760,14 @@ impl<'a> BlockPublicInputs<'a, GoldilocksField> {
         inputs.push(storage_slot_length);
         inputs.extend_from_slice(query_results);
         inputs.extend_from_slice(rewards_rate);
+        inputs.extend_from_slice(max_rewards_rate);
+        inputs.push(state_depth);
+        inputs.push(aggregation.to_field());
+        inputs.extend_from_slice(&rates_commitment.elements);
+        inputs.push(rates_first_block);
+        inputs.extend_from_slice(&slot_remaps_commitment.elements);
+        inputs.extend_from_slice(threshold);
+        inputs.push(leaf_mode);
         println!(
             "inputs size {} vs total_len {}",
             inputs.len(),
@@ -404,11 +776,15 @@ impl<'a> BlockPublicInputs<'a, GoldilocksField> {
         inputs.try_into().unwrap()
     }
     pub fn block_number(&self) -> GoldilocksField {
-        self.block_number_raw()[0]
+        unpacked_block_range(self.block_range_raw()[0]).0
     }
 
     pub fn range(&self) -> GoldilocksField {
-        self.range_raw()[0]
+        unpacked_block_range(self.block_range_raw()[0]).1
+    }
+
+    pub fn aggregated_count(&self) -> GoldilocksField {
+        self.aggregated_count_raw()[0]
     }
 
     pub fn root(&self) -> HashOut<GoldilocksField> {
@@ -435,35 +811,665 @@ impl<'a> BlockPublicInputs<'a, GoldilocksField> {
         convert_u32_fields_to_u256(&self.rewards_rate_raw())
     }
 
+    /// Returns the upper bound on the rewards rate enforced by the storage
+    /// leaves, which is the maximum U256 if the rate is unbounded
+    pub fn max_rewards_rate(&self) -> U256 {
+        convert_u32_fields_to_u256(&self.max_rewards_rate_raw())
+    }
+
     pub(crate) fn query_results(&self) -> U256 {
         convert_u32_fields_to_u256(&self.query_results_raw())
     }
+
+    pub fn state_depth(&self) -> GoldilocksField {
+        self.state_depth_raw()[0]
+    }
+
+    /// Returns the aggregation mode employed to aggregate the query results,
+    /// failing if it doesn't encode a valid `AggregationKind`
+    pub fn aggregation_mode(&self) -> Result<AggregationKind> {
+        AggregationKind::from_field(self.aggregation_mode_raw()[0])
+    }
+
+    /// Returns the commitment to the schedule of rewards rates, which is the
+    /// zero hash if the rewards rate isn't bound to any schedule
+    pub fn rates_commitment(&self) -> HashOut<GoldilocksField> {
+        HashOut::from_vec(self.rates_commitment_raw().to_owned())
+    }
+
+    pub fn rates_first_block(&self) -> GoldilocksField {
+        self.rates_first_block_raw()[0]
+    }
+
+    /// Returns the commitment to the migrations of the mapping slot, which is
+    /// the zero hash if the mapping never migrated
+    pub fn slot_remaps_commitment(&self) -> HashOut<GoldilocksField> {
+        HashOut::from_vec(self.slot_remaps_commitment_raw().to_owned())
+    }
+
+    /// Returns the threshold the balances are compared to, or the minimum
+    /// balance of the flat rewards, which is zero for proportional rewards
+    pub fn threshold(&self) -> U256 {
+        convert_u32_fields_to_u256(&self.threshold_raw())
+    }
+
+    /// Returns the identifier of the mode of the storage leaves, see `leaf_mode_id`
+    pub fn leaf_mode(&self) -> u8 {
+        self.leaf_mode_raw()[0].to_canonical_u64() as u8
+    }
+
+    /// Check natively that `parent` is a valid aggregation of `children`, as
+    /// enforced by `FullNodeCircuit` (2 children) and `PartialNodeCircuit`
+    /// (1 child): the query parameters must be shared by all the proofs, the
+    /// children must cover consecutive block ranges ending at the parent block
+    /// number, and the parent range and aggregated count must be the sum of
+    /// the ones of the children, while the parent state depth must be the
+    /// maximum of the ones of the children. The parent result must be the
+    /// aggregation of the children results according to the aggregation mode,
+    /// which must be shared by all the proofs; children aggregating no blocks
+    /// are padding children, contributing the identity of the aggregation.
+    /// The root isn't checked, since it depends on the siblings of the children.
+    pub fn verify_aggregation(
+        children: &[BlockPublicInputs<GoldilocksField>],
+        parent: &BlockPublicInputs<GoldilocksField>,
+    ) -> Result<()> {
+        ensure!(
+            matches!(children.len(), 1 | 2),
+            "an aggregation has 1 or 2 children, found {}",
+            children.len()
+        );
+        for child in children {
+            ensure!(
+                child.smart_contract_address() == parent.smart_contract_address(),
+                "smart contract address differs between children and parent"
+            );
+            ensure!(
+                child.user_address() == parent.user_address(),
+                "user address differs between children and parent"
+            );
+            ensure!(
+                child.mapping_slot() == parent.mapping_slot()
+                    && child.slot_remaps_commitment() == parent.slot_remaps_commitment(),
+                "mapping slot differs between children and parent"
+            );
+            ensure!(
+                child.mapping_slot_length() == parent.mapping_slot_length(),
+                "length slot differs between children and parent"
+            );
+            ensure!(
+                child.rewards_rate() == parent.rewards_rate(),
+                "rewards rate differs between children and parent"
+            );
+            ensure!(
+                child.max_rewards_rate() == parent.max_rewards_rate(),
+                "rewards rate bound differs between children and parent"
+            );
+            ensure!(
+                child.aggregation_mode()? == parent.aggregation_mode()?,
+                "aggregation mode differs between children and parent"
+            );
+            ensure!(
+                child.rates_commitment() == parent.rates_commitment()
+                    && child.rates_first_block() == parent.rates_first_block(),
+                "rates schedule differs between children and parent"
+            );
+            ensure!(
+                child.leaf_mode() == parent.leaf_mode() && child.threshold() == parent.threshold(),
+                "leaf mode differs between children and parent"
+            );
+        }
+
+        // block_number[i] == block_number[i + 1] - range[i + 1]
+        for (left, right) in children.iter().tuple_windows() {
+            ensure!(
+                left.block_number() == right.block_number() - right.range(),
+                "children don't cover consecutive block ranges"
+            );
+        }
+        let last = children.last().unwrap();
+        ensure!(
+            parent.block_number() == last.block_number(),
+            "parent block number is not the one of the last child"
+        );
+        let range = children
+            .iter()
+            .fold(GoldilocksField::ZERO, |acc, child| acc + child.range());
+        ensure!(
+            parent.range() == range,
+            "parent range is not the sum of the children ranges"
+        );
+        let aggregated_count = children.iter().fold(GoldilocksField::ZERO, |acc, child| {
+            acc + child.aggregated_count()
+        });
+        ensure!(
+            parent.aggregated_count() == aggregated_count,
+            "parent aggregated count is not the sum of the children counts"
+        );
+
+        let aggregation = parent.aggregation_mode()?;
+        let results = children
+            .iter()
+            .map(|child| {
+                Some(if child.aggregated_count() == GoldilocksField::ZERO {
+                    aggregation.identity()
+                } else {
+                    child.query_results()
+                })
+            })
+            .reduce(|acc, result| aggregation.aggregate(acc?, result?))
+            .unwrap();
+        ensure!(
+            results == Some(parent.query_results()),
+            "parent query results are not the {aggregation:?} of the children results"
+        );
+        let state_depth = children
+            .iter()
+            .map(|child| child.state_depth())
+            .max_by_key(|depth| depth.to_canonical_u64())
+            .unwrap();
+        ensure!(
+            parent.state_depth() == state_depth,
+            "parent state depth is not the maximum of the children depths"
+        );
+
+        Ok(())
+    }
+
+    /// Converts the public inputs of a block proof of the NFT query
+    /// (`query2::block`) to the ERC20 layout. All the fields are shared,
+    /// except for:
+    /// - the user address, which is a left-padded 32-byte value in the NFT
+    ///   layout: the conversion fails if it isn't a valid 20-byte address
+    /// - the query results, which are accumulated in a digest of the NFT IDs
+    ///   in the NFT layout, and so they can't be recovered from `nft_pi`
+    /// - the rewards rate, which is missing in the NFT layout
+    /// - the rewards rate bound, which is missing in the NFT layout: it's set
+    ///   to the maximum U256, i.e. the rate is unbounded
+    /// - the aggregated count, which is missing in the NFT layout: it's set to
+    ///   the range, since the NFT block tree has no missing blocks
+    /// - the state depth, which is missing in the NFT layout
+    /// - the aggregation mode, which is missing in the NFT layout: it's set to
+    ///   `Sum`, the only aggregation of the NFT query
+    /// - the rates schedule, which is missing in the NFT layout: it's set to
+    ///   the zero hash, since the rewards rate isn't bound to any schedule
+    /// - the migrations of the mapping slot, which are missing in the NFT
+    ///   layout: they're set to the zero hash, since the mapping never migrated
+    /// - the threshold and the leaf mode, which are missing in the NFT layout:
+    ///   they're set to zero, i.e. proportional rewards
+    ///
+    /// The query results, the rewards rate and the state depth must then be
+    /// explicitly provided by the caller.
+    pub fn from_nft(
+        nft_pi: &NftBlockPublicInputs<GoldilocksField>,
+        query_results: U256,
+        rewards_rate: U256,
+        state_depth: GoldilocksField,
+    ) -> Result<[GoldilocksField; Self::total_len()]> {
+        let (padding, user_address) = nft_pi
+            .user_address()
+            .split_at(PACKED_VALUE_LEN - PACKED_ADDRESS_LEN);
+        ensure!(
+            padding.iter().all(|f| *f == GoldilocksField::ZERO),
+            "user address of the NFT public inputs is not a 20-byte address"
+        );
+
+        Ok(Self::from_parts(
+            nft_pi.block_number(),
+            nft_pi.range(),
+            nft_pi.range(),
+            nft_pi.root(),
+            &nft_pi.smart_contract_address().try_into()?,
+            &user_address.try_into()?,
+            nft_pi.mapping_slot(),
+            nft_pi.mapping_slot_length(),
+            &query_results.to_fields().try_into().unwrap(),
+            &rewards_rate.to_fields().try_into().unwrap(),
+            &U256::MAX.to_fields().try_into().unwrap(),
+            state_depth,
+            AggregationKind::Sum,
+            HashOut::ZERO,
+            GoldilocksField::ZERO,
+            HashOut::ZERO,
+            &[GoldilocksField::ZERO; PACKED_U256_LEN],
+            GoldilocksField::ZERO,
+        ))
+    }
+
+    /// Converts these public inputs to the layout of a block proof of the NFT
+    /// query (`query2::block`). The user address is left-padded to 32 bytes,
+    /// while the aggregated count, the query results and the rewards rate don't
+    /// translate to the NFT layout and are dropped, as well as the rewards rate
+    /// bound, the state depth, the aggregation mode, the rates schedule, the
+    /// migrations of the mapping slot, the threshold and the leaf mode: the
+    /// `digest` of the NFT IDs must then be
+    /// explicitly provided by the caller.
+    pub fn to_nft(
+        &self,
+        digest: WeierstrassPoint,
+    ) -> [GoldilocksField; NftBlockPublicInputs::<GoldilocksField>::total_len()] {
+        let user_address = iter::repeat(GoldilocksField::ZERO)
+            .take(PACKED_VALUE_LEN - PACKED_ADDRESS_LEN)
+            .chain(self.user_address().iter().cloned())
+            .collect_vec();
+
+        NftBlockPublicInputs::from_parts(
+            self.block_number(),
+            self.range(),
+            self.root(),
+            self.smart_contract_address().try_into().unwrap(),
+            user_address.as_slice().try_into().unwrap(),
+            self.mapping_slot(),
+            self.mapping_slot_length(),
+            digest,
+        )
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use ethers::types::Address;
+    use ethers::types::{Address, U256};
     use itertools::Itertools;
-    use plonky2::field::types::Field;
+    use mrp2_utils::eth::left_pad32;
+    use plonky2::field::types::{Field, Sample};
     use plonky2::plonk::config::GenericHashOut;
     use plonky2::{
-        hash::{hashing::hash_n_to_hash_no_pad, poseidon::PoseidonPermutation},
+        hash::{
+            hash_types::HashOut, hashing::hash_n_to_hash_no_pad, poseidon::PoseidonPermutation,
+        },
         iop::target::Target,
     };
+    use rand::{thread_rng, Rng};
     use recursion_framework::framework_testing::TestingRecursiveCircuits;
     use serial_test::serial;
 
-    use crate::api::ProofWithVK;
+    use crate::api::{tests::dummy_proof_with_vk, ProofWithVK};
+    use crate::group_hashing::map_to_curve_point;
     use crate::query_erc20::{
-        block::{BlockPublicInputs, NUM_IO},
+        block::{aggregation::AggregationKind, BlockPublicInputs, NftBlockPublicInputs, NUM_IO},
         state::{tests::generate_inputs_for_state_circuit, Parameters as StateParams},
         storage::public_inputs::PublicInputs as StorageInputs,
     };
+    use crate::types::{PACKED_ADDRESS_LEN, PACKED_VALUE_LEN};
+    use crate::utils::{Packer, ToFields};
 
     type F = crate::api::F;
     type C = crate::api::C;
     const D: usize = crate::api::D;
 
+    #[test]
+    fn test_inputs_layout_consistency() {
+        use super::Inputs::*;
+
+        assert_eq!(BlockPublicInputs::<F>::total_len(), NUM_IO);
+        // the variants are laid out contiguously, in declaration order, and
+        // cover all the public inputs
+        let variants = [
+            BlockNumber,
+            Range,
+            AggregatedCount,
+            Root,
+            SmartContractAddress,
+            UserAddress,
+            MappingSlot,
+            StorageSlotLength,
+            QueryResult,
+            RewardsRate,
+            MaxRewardsRate,
+            StateDepth,
+            AggregationMode,
+            RatesCommitment,
+            RatesFirstBlock,
+            SlotRemapsCommitment,
+            Threshold,
+            LeafMode,
+        ];
+        assert_eq!(variants.len(), super::NUM_ELEMENTS);
+        let mut offset = 0;
+        for variant in variants {
+            assert_eq!(
+                variant.range(),
+                offset..offset + variant.len(),
+                "{variant:?}"
+            );
+            offset += variant.len();
+        }
+        assert_eq!(offset, super::Inputs::total_len());
+    }
+
+    #[test]
+    fn test_query_erc20_block_circuit_input_encoding() {
+        let full_node = super::CircuitInput::FullNode((
+            dummy_proof_with_vk([F::rand(); 4]),
+            dummy_proof_with_vk([F::rand(); 4]),
+        ));
+        let partial_node = super::CircuitInput::new_partial_node(
+            dummy_proof_with_vk([F::rand(); 4]).serialize().unwrap(),
+            HashOut::<F>::rand().to_bytes().try_into().unwrap(),
+            true,
+        )
+        .unwrap();
+        let single_leaf = super::CircuitInput::new_single_leaf(
+            dummy_proof_with_vk([F::rand(); 4]).serialize().unwrap(),
+        )
+        .unwrap();
+
+        // every variant is round-tripped, with its own tag
+        for (input, tag) in [
+            (full_node, super::CircuitInput::FULL_NODE_TAG),
+            (partial_node, super::CircuitInput::PARTIAL_NODE_TAG),
+            (single_leaf, super::CircuitInput::SINGLE_LEAF_TAG),
+        ] {
+            let bytes = input.to_bytes().unwrap();
+            assert_eq!(bytes[1], tag);
+            let decoded = super::CircuitInput::from_bytes(&bytes).unwrap();
+            assert_eq!(decoded.to_bytes().unwrap(), bytes);
+            let decoded_tag = match decoded {
+                super::CircuitInput::FullNode(_) => super::CircuitInput::FULL_NODE_TAG,
+                super::CircuitInput::PartialNode(_) => super::CircuitInput::PARTIAL_NODE_TAG,
+                super::CircuitInput::SingleLeaf(_) => super::CircuitInput::SINGLE_LEAF_TAG,
+            };
+            assert_eq!(decoded_tag, tag);
+
+            // an unknown tag is rejected
+            let mut unknown_tag = bytes;
+            unknown_tag[1] = u8::MAX;
+            assert!(super::CircuitInput::from_bytes(&unknown_tag).is_err());
+        }
+    }
+
+    #[test]
+    fn test_block_public_inputs_verify_aggregation() {
+        let rng = &mut thread_rng();
+        let smart_contract_address: [F; PACKED_ADDRESS_LEN] = Address::random()
+            .as_fixed_bytes()
+            .pack()
+            .to_fields()
+            .try_into()
+            .unwrap();
+        let user_address: [F; PACKED_ADDRESS_LEN] = Address::random()
+            .as_fixed_bytes()
+            .pack()
+            .to_fields()
+            .try_into()
+            .unwrap();
+        let mapping_slot = F::from_canonical_u8(rng.gen());
+        let length_slot = F::from_canonical_u8(rng.gen());
+        let rewards_rate = U256::from(rng.gen::<u64>());
+        let max_rewards_rate = rewards_rate + rng.gen::<u32>();
+        let rates_commitment = HashOut::rand();
+        let rates_first_block = F::from_canonical_u32(rng.gen_range(0..100));
+        let slot_remaps_commitment = HashOut::rand();
+        let block_pi_with_mode = |block_number: u32,
+                                  range: u32,
+                                  count: u32,
+                                  query_results: U256,
+                                  depth: u8,
+                                  aggregation: AggregationKind| {
+            BlockPublicInputs::from_parts(
+                F::from_canonical_u32(block_number),
+                F::from_canonical_u32(range),
+                F::from_canonical_u32(count),
+                HashOut::rand(),
+                &smart_contract_address,
+                &user_address,
+                mapping_slot,
+                length_slot,
+                &query_results.to_fields().try_into().unwrap(),
+                &rewards_rate.to_fields().try_into().unwrap(),
+                &max_rewards_rate.to_fields().try_into().unwrap(),
+                F::from_canonical_u8(depth),
+                aggregation,
+                rates_commitment,
+                rates_first_block,
+                slot_remaps_commitment,
+                &[F::ZERO; PACKED_U256_LEN],
+                F::ZERO,
+            )
+        };
+        let block_pi_with_depth =
+            |block_number: u32, range: u32, count: u32, query_results: U256, depth: u8| {
+                block_pi_with_mode(
+                    block_number,
+                    range,
+                    count,
+                    query_results,
+                    depth,
+                    AggregationKind::Sum,
+                )
+            };
+        let block_pi_with_count = |block_number: u32, range: u32, count: u32, query_results| {
+            block_pi_with_depth(block_number, range, count, query_results, 3)
+        };
+        let block_pi = |block_number: u32, range: u32, query_results: U256| {
+            block_pi_with_count(block_number, range, range, query_results)
+        };
+
+        let block_number = rng.gen_range(100..10_000);
+        let [left_results, right_results] = [0; 2].map(|_| U256::from(rng.gen::<u128>()));
+        let left = block_pi(block_number, 3, left_results);
+        let right = block_pi(block_number + 2, 2, right_results);
+        let parent = block_pi(block_number + 2, 5, left_results + right_results);
+        let [left_pi, right_pi, parent_pi] =
+            [&left, &right, &parent].map(|inputs| BlockPublicInputs::from(inputs.as_slice()));
+
+        // valid aggregations of a full node and of a partial node
+        BlockPublicInputs::verify_aggregation(&[left_pi.clone(), right_pi.clone()], &parent_pi)
+            .unwrap();
+        let partial_parent = block_pi(block_number + 2, 5, left_results + right_results);
+        BlockPublicInputs::verify_aggregation(
+            &[parent_pi.clone()],
+            &BlockPublicInputs::from(partial_parent.as_slice()),
+        )
+        .unwrap();
+
+        // a parent with a tampered result is rejected
+        let tampered = block_pi(block_number + 2, 5, left_results + right_results + 1);
+        assert!(BlockPublicInputs::verify_aggregation(
+            &[left_pi.clone(), right_pi.clone()],
+            &BlockPublicInputs::from(tampered.as_slice())
+        )
+        .is_err());
+
+        // children with rates bound to different schedules are rejected
+        // as well as children with different bounds on the rewards rate, or
+        // with slots resolved from different migrations of the mapping, or with
+        // leaves of different modes
+        for position in [
+            super::Inputs::MaxRewardsRate.range().start,
+            super::Inputs::RatesCommitment.range().start,
+            super::Inputs::RatesFirstBlock.range().start,
+            super::Inputs::SlotRemapsCommitment.range().start,
+            super::Inputs::Threshold.range().start,
+            super::Inputs::LeafMode.range().start,
+        ] {
+            let mut other_schedule = right;
+            other_schedule[position] += F::ONE;
+            assert!(BlockPublicInputs::verify_aggregation(
+                &[
+                    left_pi.clone(),
+                    BlockPublicInputs::from(other_schedule.as_slice())
+                ],
+                &parent_pi
+            )
+            .is_err());
+        }
+
+        // children not covering consecutive block ranges are rejected
+        let gap = block_pi(block_number + 3, 2, right_results);
+        let gap_parent = block_pi(block_number + 3, 5, left_results + right_results);
+        assert!(BlockPublicInputs::verify_aggregation(
+            &[left_pi, BlockPublicInputs::from(gap.as_slice())],
+            &BlockPublicInputs::from(gap_parent.as_slice())
+        )
+        .is_err());
+
+        // in a gapped tree the span differs from the count of aggregated blocks,
+        // which are summed separately
+        let gapped_left = block_pi_with_count(block_number, 3, 1, left_results);
+        let gapped_parent =
+            block_pi_with_count(block_number + 2, 5, 3, left_results + right_results);
+        let gapped_parent_pi = BlockPublicInputs::from(gapped_parent.as_slice());
+        BlockPublicInputs::verify_aggregation(
+            &[
+                BlockPublicInputs::from(gapped_left.as_slice()),
+                right_pi.clone(),
+            ],
+            &gapped_parent_pi,
+        )
+        .unwrap();
+        assert_ne!(
+            gapped_parent_pi.range(),
+            gapped_parent_pi.aggregated_count()
+        );
+        assert!(BlockPublicInputs::verify_aggregation(
+            &[
+                BlockPublicInputs::from(gapped_left.as_slice()),
+                right_pi.clone()
+            ],
+            &parent_pi
+        )
+        .is_err());
+
+        // the parent state depth is the maximum of the children ones
+        let deeper_left = block_pi_with_depth(block_number, 3, 3, left_results, 4);
+        let deeper_left_pi = BlockPublicInputs::from(deeper_left.as_slice());
+        let deeper_parent =
+            block_pi_with_depth(block_number + 2, 5, 5, left_results + right_results, 4);
+        BlockPublicInputs::verify_aggregation(
+            &[deeper_left_pi.clone(), right_pi.clone()],
+            &BlockPublicInputs::from(deeper_parent.as_slice()),
+        )
+        .unwrap();
+        assert!(BlockPublicInputs::verify_aggregation(
+            &[deeper_left_pi, right_pi.clone()],
+            &parent_pi
+        )
+        .is_err());
+
+        // with min and max aggregations, the parent result is the min or the
+        // max of the children ones, and all the proofs share the same mode
+        for (aggregation, results) in [
+            (AggregationKind::Min, left_results.min(right_results)),
+            (AggregationKind::Max, left_results.max(right_results)),
+        ] {
+            let pi = |block_number, range, query_results| {
+                block_pi_with_mode(block_number, range, range, query_results, 3, aggregation)
+            };
+            let [left, right, parent, summed] = [
+                pi(block_number, 3, left_results),
+                pi(block_number + 2, 2, right_results),
+                pi(block_number + 2, 5, results),
+                pi(block_number + 2, 5, left_results + right_results),
+            ];
+            let summed_right = block_pi(block_number + 2, 2, right_results);
+            let [left_pi, right_pi, parent_pi, summed_pi, summed_right_pi] =
+                [&left, &right, &parent, &summed, &summed_right]
+                    .map(|inputs| BlockPublicInputs::from(inputs.as_slice()));
+            BlockPublicInputs::verify_aggregation(&[left_pi.clone(), right_pi.clone()], &parent_pi)
+                .unwrap();
+            assert!(BlockPublicInputs::verify_aggregation(
+                &[left_pi.clone(), right_pi.clone()],
+                &summed_pi
+            )
+            .is_err());
+            // children aggregated with different modes are rejected
+            assert!(BlockPublicInputs::verify_aggregation(
+                &[left_pi.clone(), summed_right_pi],
+                &parent_pi
+            )
+            .is_err());
+            // a padding child, aggregating no blocks, contributes the identity
+            // of the aggregation whatever its result
+            let padding = block_pi_with_mode(block_number + 2, 2, 0, right_results, 3, aggregation);
+            let padded_parent =
+                block_pi_with_mode(block_number + 2, 5, 3, left_results, 3, aggregation);
+            BlockPublicInputs::verify_aggregation(
+                &[left_pi, BlockPublicInputs::from(padding.as_slice())],
+                &BlockPublicInputs::from(padded_parent.as_slice()),
+            )
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_block_public_inputs_nft_conversion() {
+        let rng = &mut thread_rng();
+        let smart_contract_address: [F; PACKED_ADDRESS_LEN] = Address::random()
+            .as_fixed_bytes()
+            .pack()
+            .to_fields()
+            .try_into()
+            .unwrap();
+        let user_address: [F; PACKED_VALUE_LEN] = left_pad32(Address::random().as_fixed_bytes())
+            .pack()
+            .to_fields()
+            .try_into()
+            .unwrap();
+        let digest = map_to_curve_point(&F::rand_vec(4)).to_weierstrass();
+        let nft_inputs = NftBlockPublicInputs::from_parts(
+            F::from_canonical_u32(rng.gen()),
+            F::from_canonical_u32(rng.gen()),
+            HashOut::rand(),
+            &smart_contract_address,
+            &user_address,
+            F::from_canonical_u8(rng.gen()),
+            F::from_canonical_u8(rng.gen()),
+            digest,
+        );
+        let nft_pi = NftBlockPublicInputs::from(nft_inputs.as_slice());
+
+        // the shared fields are preserved, while the result fields are the
+        // ones explicitly provided
+        let query_results = U256::from(rng.gen::<u128>());
+        let rewards_rate = U256::from(rng.gen::<u64>());
+        let state_depth = F::from_canonical_u8(rng.gen_range(1..=5));
+        let inputs =
+            BlockPublicInputs::from_nft(&nft_pi, query_results, rewards_rate, state_depth).unwrap();
+        let pi = BlockPublicInputs::from(inputs.as_slice());
+        assert_eq!(pi.block_number(), nft_pi.block_number());
+        assert_eq!(pi.range(), nft_pi.range());
+        assert_eq!(pi.aggregated_count(), nft_pi.range());
+        assert_eq!(pi.root(), nft_pi.root());
+        assert_eq!(pi.smart_contract_address(), nft_pi.smart_contract_address());
+        assert_eq!(
+            pi.user_address(),
+            &nft_pi.user_address()[PACKED_VALUE_LEN - PACKED_ADDRESS_LEN..]
+        );
+        assert_eq!(pi.mapping_slot(), nft_pi.mapping_slot());
+        assert_eq!(pi.mapping_slot_length(), nft_pi.mapping_slot_length());
+        assert_eq!(pi.query_results(), query_results);
+        assert_eq!(pi.rewards_rate(), rewards_rate);
+        assert_eq!(pi.state_depth(), state_depth);
+        assert_eq!(pi.aggregation_mode().unwrap(), AggregationKind::Sum);
+        assert_eq!(pi.max_rewards_rate(), U256::MAX);
+        assert_eq!(pi.rates_commitment(), HashOut::ZERO);
+        assert_eq!(pi.slot_remaps_commitment(), HashOut::ZERO);
+
+        // the round trip recovers the NFT public inputs, given the digest
+        assert_eq!(pi.to_nft(digest), nft_inputs);
+
+        // a user address which is not a 20-byte address can't be converted
+        let mut invalid_user_address = user_address;
+        invalid_user_address[0] = F::ONE;
+        let invalid_inputs = NftBlockPublicInputs::from_parts(
+            nft_pi.block_number(),
+            nft_pi.range(),
+            nft_pi.root(),
+            &smart_contract_address,
+            &invalid_user_address,
+            nft_pi.mapping_slot(),
+            nft_pi.mapping_slot_length(),
+            digest,
+        );
+        assert!(BlockPublicInputs::from_nft(
+            &NftBlockPublicInputs::from(invalid_inputs.as_slice()),
+            query_results,
+            rewards_rate,
+            state_depth
+        )
+        .is_err());
+    }
+
     #[test]
     #[serial]
     fn test_query_erc20_block_circuit_api() {
@@ -516,28 +1522,59 @@ mod tests {
 
         println!("leaf proofs built");
 
+        // A single matching block is employed as the root without any sibling.
+        let single_leaf_root = block_circuit_params
+            .generate_proof(super::CircuitInput::new_single_leaf(left_leaf_proof.clone()).unwrap())
+            .unwrap();
+        // the single leaf root is verified and decoded like any other root
+        let single_leaf_pi = block_circuit_params
+            .verify_and_decode(&single_leaf_root)
+            .unwrap();
+        let single_leaf_pi = single_leaf_pi.view();
+        assert_eq!(single_leaf_pi.range(), F::ONE);
+        assert_eq!(single_leaf_pi.query_results(), left_leaf_pi.query_results());
+        assert_eq!(single_leaf_pi.root(), left_leaf_pi.root());
+
         let full_node_proof = block_circuit_params
             .generate_proof(
                 super::CircuitInput::new_full_node(left_leaf_proof, right_leaf_proof).unwrap(),
             )
             .unwrap();
 
-        block_circuit_params.verify_proof(&full_node_proof).unwrap();
+        let full_node_pi = block_circuit_params
+            .verify_and_decode(&full_node_proof)
+            .unwrap();
+        let full_node_pi = full_node_pi.view();
+        assert_eq!(
+            full_node_pi.block_number(),
+            F::from_canonical_u32(BLOCK_NUMBER + 1)
+        );
 
-        let full_node_pi = ProofWithVK::deserialize(&full_node_proof)
-            .unwrap()
-            .proof
-            .public_inputs;
-        let full_node_pi = BlockPublicInputs::from(&full_node_pi[..NUM_IO]);
+        // A proof with tampered public inputs should fail before being decoded.
+        let mut invalid_proof = ProofWithVK::deserialize(&full_node_proof).unwrap();
+        invalid_proof.proof.public_inputs[0] += F::ONE;
+        assert!(block_circuit_params
+            .verify_and_decode(&invalid_proof.serialize().unwrap())
+            .is_err());
 
         // Check if full_node_reward == left_leaf_reward + right_leaf_reward.
         assert_eq!(
             full_node_pi.query_results(),
             left_leaf_pi.query_results() + right_leaf_pi.query_results()
         );
+        BlockPublicInputs::verify_aggregation(
+            &[left_leaf_pi.clone(), right_leaf_pi.clone()],
+            &full_node_pi,
+        )
+        .unwrap();
 
         println!("full node proof built");
 
+        // Only state proofs can be employed as single leaf roots.
+        assert!(block_circuit_params
+            .generate_proof(super::CircuitInput::new_single_leaf(full_node_proof.clone()).unwrap())
+            .is_err());
+
         let sibling_hash = hash_n_to_hash_no_pad::<F, PoseidonPermutation<_>>(
             &b"ernesto"
                 .iter()
@@ -572,5 +1609,84 @@ mod tests {
             partial_node_pi.query_results(),
             full_node_pi.query_results()
         );
+        BlockPublicInputs::verify_aggregation(&[full_node_pi.clone()], &partial_node_pi).unwrap();
+
+        // Check the root of the 3 aggregated leaves can be computed natively.
+        assert_eq!(
+            partial_node_pi.root(),
+            super::expected_root(
+                &[left_leaf_pi.root(), right_leaf_pi.root(), sibling_hash],
+                &[false, true],
+            )
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn test_query_erc20_block_fold_aggregation() {
+        const NUM_STORAGE_INPUTS: usize = StorageInputs::<Target>::TOTAL_LEN;
+        const NUM_LEAVES: u32 = 8;
+        const BLOCK_NUMBER: u32 = 123456;
+        let smart_contract_address = Address::random();
+        let user_address = Address::random();
+        let testing_framework = TestingRecursiveCircuits::<F, C, D, NUM_STORAGE_INPUTS>::default();
+        let state_circuit_params =
+            StateParams::build(testing_framework.get_recursive_circuit_set());
+        let block_circuit_params = super::Parameters::build(&state_circuit_params);
+
+        let leaves = (0..NUM_LEAVES)
+            .map(|i| {
+                let inputs = generate_inputs_for_state_circuit(
+                    &testing_framework,
+                    Some(BLOCK_NUMBER + i),
+                    Some(42),
+                    Some(24),
+                    Some(smart_contract_address),
+                    Some(user_address),
+                );
+                let proof = state_circuit_params
+                    .generate_proof(block_circuit_params.get_block_circuit_set(), inputs)
+                    .unwrap();
+                ProofWithVK::deserialize(&proof).unwrap()
+            })
+            .collect_vec();
+
+        // build the tree level by level, holding all the proofs of each level
+        let mut level = leaves.clone();
+        while level.len() > 1 {
+            level = level
+                .into_iter()
+                .tuples()
+                .map(|(left, right)| {
+                    let proof = block_circuit_params
+                        .generate_proof(super::CircuitInput::FullNode((left, right)))
+                        .unwrap();
+                    ProofWithVK::deserialize(&proof).unwrap()
+                })
+                .collect();
+        }
+        let batch_root = level.pop().unwrap();
+
+        let streaming_root = block_circuit_params
+            .fold_aggregation(leaves.into_iter())
+            .unwrap();
+        let streaming_pi = block_circuit_params
+            .verify_and_decode(&streaming_root)
+            .unwrap();
+        assert_eq!(
+            streaming_pi.as_slice(),
+            &batch_root.proof().public_inputs[..NUM_IO]
+        );
+        let streaming_pi = streaming_pi.view();
+        assert_eq!(
+            streaming_pi.block_number(),
+            F::from_canonical_u32(BLOCK_NUMBER + NUM_LEAVES - 1)
+        );
+        assert_eq!(streaming_pi.range(), F::from_canonical_u32(NUM_LEAVES));
+
+        // there must be at least one leaf to aggregate
+        assert!(block_circuit_params
+            .fold_aggregation(std::iter::empty())
+            .is_err());
     }
 }
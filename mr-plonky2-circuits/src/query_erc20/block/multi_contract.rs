@@ -0,0 +1,657 @@
+//! This circuit aggregates the query results of the same user over the same
+//! block range for 2 different contracts, e.g. to compute the total balance of
+//! a user across contracts A and B. Differently from `FullNodeCircuit`, the
+//! smart contract addresses of the children aren't required to be equal: the
+//! contracts contributing to the result are accumulated in a digest instead,
+//! and a commitment to this digest is exposed in place of the smart contract
+//! address. The proofs thus share the public inputs layout of the block
+//! proofs, but this node can't be part of the set of block circuits: it's
+//! wrapped in a circuit recursively verifying 2 proofs of the block circuit
+//! set, which is the only member of its own circuit set.
+
+use anyhow::Result;
+use itertools::Itertools;
+use mrp2_utils::u256::CircuitBuilderU256;
+use plonky2::{
+    field::{
+        goldilocks_field::GoldilocksField,
+        types::{Field, PrimeField64},
+    },
+    hash::{hash_types::HashOutTarget, poseidon::PoseidonHash},
+    iop::{target::Target, witness::PartialWitness},
+    plonk::{
+        circuit_builder::CircuitBuilder, circuit_data::CircuitData, config::Hasher,
+        proof::ProofWithPublicInputsTarget,
+    },
+};
+use plonky2_crypto::u32::arithmetic_u32::U32Target;
+use plonky2_ecgfp5::{
+    curve::curve::{Point, WeierstrassPoint},
+    gadgets::curve::CurveTarget,
+};
+use recursion_framework::{
+    circuit_builder::{
+        CircuitLogicWires, CircuitWithUniversalVerifier, CircuitWithUniversalVerifierBuilder,
+    },
+    framework::{
+        RecursiveCircuits, RecursiveCircuitsVerifierGagdet, RecursiveCircuitsVerifierTarget,
+    },
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::{default_config, ProofWithVK, C, D, F},
+    array::Array,
+    group_hashing::{map_to_curve_point, CircuitBuilderGroupHashing},
+    types::{PackedAddressTarget, PACKED_ADDRESS_LEN},
+    utils::greater_than,
+};
+
+use super::{
+    aggregation::{aggregate_query_results, AggregationKind},
+    full_node::STATE_DEPTH_BITS,
+    BlockPublicInputs, NUM_IO,
+};
+
+/// Compute natively the digest of the set of contracts contributing to the
+/// results of the ERC20 query block proofs `children`, as accumulated by
+/// `MultiContractNodeCircuit`: each contract is identified by its address
+/// together with the mapping and length slots, and the commitment to the
+/// migrations of the mapping.
+pub fn contracts_digest(children: &[BlockPublicInputs<GoldilocksField>]) -> WeierstrassPoint {
+    children
+        .iter()
+        .map(|pi| {
+            let contract = pi
+                .smart_contract_address()
+                .iter()
+                .copied()
+                .chain([pi.mapping_slot(), pi.mapping_slot_length()])
+                .chain(pi.slot_remaps_commitment().elements)
+                .collect_vec();
+            map_to_curve_point(&contract)
+        })
+        .fold(Point::NEUTRAL, |acc, point| acc + point)
+        .to_weierstrass()
+}
+
+/// Compute natively the commitment to the set of contracts contributing to the
+/// results of `children`, which is exposed by `MultiContractNodeCircuit` in
+/// place of the smart contract address: the first `PACKED_ADDRESS_LEN` u32
+/// limbs of the hash of the digest of the contracts.
+pub fn contracts_commitment(
+    children: &[BlockPublicInputs<GoldilocksField>],
+) -> [GoldilocksField; PACKED_ADDRESS_LEN] {
+    let digest = contracts_digest(children);
+    let digest = digest
+        .x
+        .0
+        .into_iter()
+        .chain(digest.y.0)
+        .chain([GoldilocksField::from_bool(digest.is_inf)])
+        .collect_vec();
+    let hash = PoseidonHash::hash_no_pad(&digest);
+    let limbs = hash
+        .elements
+        .iter()
+        .flat_map(|e| {
+            let e = e.to_canonical_u64();
+            [e & u32::MAX as u64, e >> 32]
+        })
+        .map(GoldilocksField::from_canonical_u64)
+        .collect_vec();
+
+    limbs[..PACKED_ADDRESS_LEN].try_into().unwrap()
+}
+
+/// Commitment to the contracts accumulated in `digest`, packed as an address
+fn contracts_commitment_target(
+    b: &mut CircuitBuilder<GoldilocksField, 2>,
+    digest: CurveTarget,
+) -> PackedAddressTarget {
+    let ([x, y], is_inf) = digest.0;
+    let inputs = x.0.into_iter().chain(y.0).chain([is_inf.target]).collect();
+    let hash: HashOutTarget = b.hash_n_to_hash_no_pad::<PoseidonHash>(inputs);
+    let limbs = hash
+        .elements
+        .iter()
+        .flat_map(|e| {
+            let (low, high) = b.split_low_high(*e, 32, 64);
+            [low, high]
+        })
+        .map(U32Target)
+        .collect_vec();
+
+    Array {
+        arr: limbs[..PACKED_ADDRESS_LEN].try_into().unwrap(),
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MultiContractNodeWires {}
+
+#[derive(Clone, Debug)]
+pub struct MultiContractNodeCircuit {}
+
+impl MultiContractNodeCircuit {
+    pub fn build(
+        b: &mut CircuitBuilder<GoldilocksField, 2>,
+        inputs: [BlockPublicInputs<Target>; 2],
+    ) -> MultiContractNodeWires {
+        let f = b._false();
+
+        // Both children aggregate the same blocks of the same block tree
+        b.connect(
+            inputs[0].packed_block_range(),
+            inputs[1].packed_block_range(),
+        );
+        b.connect(inputs[0].aggregated_count(), inputs[1].aggregated_count());
+        b.connect_hashes(inputs[0].root(), inputs[1].root());
+        // X[0] == X[1]
+        inputs[0]
+            .user_address()
+            .enforce_equal(b, &inputs[1].user_address());
+        b.enforce_equal_u256(&inputs[0].rewards_rate(), &inputs[1].rewards_rate());
+        b.enforce_equal_u256(&inputs[0].max_rewards_rate(), &inputs[1].max_rewards_rate());
+        // The rewards rate is bound to the same schedule for both contracts
+        b.connect_hashes(inputs[0].rates_commitment(), inputs[1].rates_commitment());
+        b.connect(inputs[0].rates_first_block(), inputs[1].rates_first_block());
+        // The results of both contracts are computed by leaves of the same mode
+        b.connect(inputs[0].leaf_mode(), inputs[1].leaf_mode());
+        b.enforce_equal_u256(&inputs[0].threshold(), &inputs[1].threshold());
+        // The results of the contracts are summed, so they must have been
+        // summed over the blocks as well
+        let sum_mode = b.constant(AggregationKind::Sum.to_field());
+        inputs
+            .iter()
+            .for_each(|pi| b.connect(pi.aggregation_mode(), sum_mode));
+
+        // Each contract is identified by (A, M, S)
+        let contracts = inputs.each_ref().map(|pi| {
+            pi.smart_contract_address()
+                .to_targets()
+                .arr
+                .into_iter()
+                .chain([pi.mapping_slot(), pi.mapping_slot_length()])
+                .collect_vec()
+        });
+        // The contracts must be different, otherwise the same results would be
+        // counted twice
+        let same_contract =
+            contracts[0]
+                .iter()
+                .zip(&contracts[1])
+                .fold(b._true(), |acc, (l, r)| {
+                    let is_equal = b.is_equal(*l, *r);
+                    b.and(acc, is_equal)
+                });
+        b.connect(same_contract.target, f.target);
+        // The digest also binds the migrations of the mapping the slots of the
+        // blocks are resolved from
+        let points = [0, 1].map(|i| {
+            let contract = contracts[i]
+                .iter()
+                .copied()
+                .chain(inputs[i].slot_remaps_commitment().elements)
+                .collect_vec();
+            b.map_to_curve_point(&contract)
+        });
+        let contracts_digest = b.add_curve_point(&points);
+        // The set of contracts is exposed in place of the contract address,
+        // while the slots and their migrations are already bound by the digest
+        let contracts_commitment = contracts_commitment_target(b, contracts_digest);
+        let zero = b.zero();
+        let zero_hash = HashOutTarget::from([zero; 4]);
+        let (block_number, range) = inputs[0].block_number_and_range(b);
+
+        let new_result = aggregate_query_results(
+            b,
+            sum_mode,
+            &inputs[0].query_results(),
+            &inputs[1].query_results(),
+        );
+        // D = max(D[0], D[1])
+        let left_is_deeper = greater_than(
+            b,
+            inputs[0].state_depth(),
+            inputs[1].state_depth(),
+            STATE_DEPTH_BITS,
+        );
+        let new_state_depth = b.select(
+            left_is_deeper,
+            inputs[0].state_depth(),
+            inputs[1].state_depth(),
+        );
+
+        BlockPublicInputs::<Target>::register(
+            b,
+            block_number,
+            range,
+            inputs[0].aggregated_count(),
+            &inputs[0].root(),
+            &contracts_commitment,
+            &inputs[0].user_address(),
+            zero,
+            zero,
+            new_result,
+            inputs[0].rewards_rate(),
+            inputs[0].max_rewards_rate(),
+            new_state_depth,
+            sum_mode,
+            &inputs[0].rates_commitment(),
+            inputs[0].rates_first_block(),
+            &zero_hash,
+            inputs[0].threshold(),
+            inputs[0].leaf_mode(),
+        );
+
+        MultiContractNodeWires {}
+    }
+
+    pub fn assign(
+        &self,
+        _pw: &mut PartialWitness<GoldilocksField>,
+        _wires: &MultiContractNodeWires,
+    ) {
+    }
+}
+
+/// The multi-contract circuit is the only member of its circuit set
+const MULTI_CONTRACT_CIRCUIT_SET_SIZE: usize = 1;
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct MultiContractRecursiveWires {
+    node_wires: MultiContractNodeWires,
+    block_verifiers: [RecursiveCircuitsVerifierTarget<D>; 2],
+}
+
+/// Inputs to be provided to the publicly exposed query API in order to generate
+/// a proof aggregating the results of 2 contracts, i.e. the root proofs of the
+/// block circuit set for each contract
+pub struct CircuitInput {
+    block_proofs: [ProofWithVK; 2],
+}
+
+impl CircuitInput {
+    pub fn new(left_proof: Vec<u8>, right_proof: Vec<u8>) -> Result<Self> {
+        Ok(Self {
+            block_proofs: [
+                ProofWithVK::deserialize(&left_proof)?,
+                ProofWithVK::deserialize(&right_proof)?,
+            ],
+        })
+    }
+
+    /// Proofs of the block circuit set aggregated by the multi-contract circuit
+    pub(crate) fn block_proofs(&self) -> &[ProofWithVK; 2] {
+        &self.block_proofs
+    }
+}
+
+/// Set of inputs necessary to generate a proof for the multi-contract circuit
+pub(crate) struct CircuitInputsInternal {
+    api_inputs: CircuitInput,
+    block_circuit_set: RecursiveCircuits<F, C, D>,
+}
+
+impl CircuitLogicWires<F, D, 0> for MultiContractRecursiveWires {
+    type CircuitBuilderParams = RecursiveCircuitsVerifierGagdet<F, C, D, NUM_IO>;
+
+    type Inputs = CircuitInputsInternal;
+
+    const NUM_PUBLIC_INPUTS: usize = NUM_IO;
+
+    fn circuit_logic(
+        builder: &mut CircuitBuilder<F, D>,
+        _verified_proofs: [&ProofWithPublicInputsTarget<D>; 0],
+        builder_parameters: Self::CircuitBuilderParams,
+    ) -> Self {
+        let block_verifiers =
+            [0; 2].map(|_| builder_parameters.verify_proof_in_circuit_set(builder));
+        let children_pi = block_verifiers.each_ref().map(|verifier| {
+            BlockPublicInputs::from(verifier.get_public_input_targets::<F, NUM_IO>())
+        });
+        let node_wires = MultiContractNodeCircuit::build(builder, children_pi);
+
+        Self {
+            node_wires,
+            block_verifiers,
+        }
+    }
+
+    fn assign_input(&self, inputs: Self::Inputs, pw: &mut PartialWitness<F>) -> Result<()> {
+        MultiContractNodeCircuit {}.assign(pw, &self.node_wires);
+        for (verifier, proof) in self
+            .block_verifiers
+            .iter()
+            .zip(&inputs.api_inputs.block_proofs)
+        {
+            let (proof, vd) = proof.into();
+            verifier.set_target(pw, &inputs.block_circuit_set, proof, vd)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Parameters {
+    circuit: CircuitWithUniversalVerifier<F, C, D, 0, MultiContractRecursiveWires>,
+    circuit_set: RecursiveCircuits<F, C, D>,
+}
+
+impl Parameters {
+    pub fn build(block_circuit_set: &RecursiveCircuits<F, C, D>) -> Self {
+        let verifier_gadget =
+            RecursiveCircuitsVerifierGagdet::new(default_config(), block_circuit_set);
+        let circuit_builder = CircuitWithUniversalVerifierBuilder::<F, D, NUM_IO>::new::<C>(
+            default_config(),
+            MULTI_CONTRACT_CIRCUIT_SET_SIZE,
+        );
+        let circuit = circuit_builder.build_circuit(verifier_gadget);
+        let circuit_set = RecursiveCircuits::new_from_circuit_digests(vec![
+            circuit.circuit_data().verifier_only.circuit_digest,
+        ]);
+
+        Self {
+            circuit,
+            circuit_set,
+        }
+    }
+
+    /// Generate a proof aggregating the results of the 2 proofs of
+    /// `block_circuit_set` in `input`
+    pub fn generate_proof(
+        &self,
+        block_circuit_set: &RecursiveCircuits<F, C, D>,
+        input: CircuitInput,
+    ) -> Result<Vec<u8>> {
+        let proof = self.circuit_set.generate_proof(
+            &self.circuit,
+            [],
+            [],
+            CircuitInputsInternal {
+                api_inputs: input,
+                block_circuit_set: block_circuit_set.clone(),
+            },
+        )?;
+        ProofWithVK::serialize(&(proof, self.circuit.circuit_data().verifier_only.clone()).into())
+    }
+
+    pub fn verify_proof(&self, proof: &[u8]) -> Result<()> {
+        let proof = ProofWithVK::deserialize(proof)?;
+        self.circuit.circuit_data().verify(proof.proof)
+    }
+
+    /// Set of circuits generating multi-contract proofs, employed to recursively
+    /// verify them
+    pub fn get_circuit_set(&self) -> &RecursiveCircuits<F, C, D> {
+        &self.circuit_set
+    }
+
+    pub(crate) fn circuit_data(&self) -> &CircuitData<F, C, D> {
+        self.circuit.circuit_data()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        api::{C, D, F},
+        types::address_to_packed_fields,
+        utils::ToFields,
+    };
+    use ethers::types::{Address, U256};
+    use mrp2_test_utils::circuit::{run_circuit, UserCircuit};
+    use mrp2_utils::types::PACKED_U256_LEN;
+    use plonky2::{field::types::Sample, hash::hash_types::HashOut, iop::witness::WitnessWrite};
+    use rand::{thread_rng, Rng};
+    use recursion_framework::framework_testing::TestingRecursiveCircuits;
+    use serial_test::serial;
+
+    #[derive(Clone, Debug)]
+    struct TestMultiContractNodeCircuit<'a> {
+        children: [&'a [F]; 2],
+    }
+
+    impl UserCircuit<F, D> for TestMultiContractNodeCircuit<'_> {
+        type Wires = (MultiContractNodeWires, [Vec<Target>; 2]);
+
+        fn build(b: &mut CircuitBuilder<F, D>) -> Self::Wires {
+            let child_inputs =
+                [0; 2].map(|_| b.add_virtual_targets(BlockPublicInputs::<Target>::total_len()));
+            let children_pi = std::array::from_fn(|i| {
+                BlockPublicInputs::<Target>::from(child_inputs[i].as_slice())
+            });
+            let wires = MultiContractNodeCircuit::build(b, children_pi);
+
+            (wires, child_inputs)
+        }
+
+        fn prove(&self, pw: &mut PartialWitness<F>, wires: &Self::Wires) {
+            pw.set_target_arr(&wires.1[0], self.children[0]);
+            pw.set_target_arr(&wires.1[1], self.children[1]);
+            MultiContractNodeCircuit {}.assign(pw, &wires.0);
+        }
+    }
+
+    #[test]
+    fn test_multi_contract_node_circuit() {
+        let rng = &mut thread_rng();
+        let block_number = F::from_canonical_u32(rng.gen_range(1000..10_000));
+        let range = F::from_canonical_u32(rng.gen_range(1..1000));
+        let root = HashOut::rand();
+        let user_address = address_to_packed_fields(&Address::random());
+        let rewards_rate = U256::from(rng.gen::<u64>());
+        let rates_commitment = HashOut::rand();
+        let rates_first_block = F::from_canonical_u32(rng.gen_range(0..1000));
+        let contract_pi = |contract_address: &Address, query_results: U256| {
+            BlockPublicInputs::from_parts(
+                block_number,
+                range,
+                range,
+                root,
+                &address_to_packed_fields(contract_address),
+                &user_address,
+                F::from_canonical_u8(24),
+                F::from_canonical_u8(42),
+                &query_results.to_fields().try_into().unwrap(),
+                &rewards_rate.to_fields().try_into().unwrap(),
+                &U256::MAX.to_fields().try_into().unwrap(),
+                F::from_canonical_u8(3),
+                AggregationKind::Sum,
+                rates_commitment,
+                rates_first_block,
+                HashOut::ZERO,
+                &[F::ZERO; PACKED_U256_LEN],
+                F::ZERO,
+            )
+        };
+        let [contract_a, contract_b] = [0; 2].map(|_| Address::random());
+        let [results_a, results_b] = [0; 2].map(|_| U256::from(rng.gen::<u128>()));
+        let pi_a = contract_pi(&contract_a, results_a);
+        let pi_b = contract_pi(&contract_b, results_b);
+
+        let proof = run_circuit::<F, D, C, _>(TestMultiContractNodeCircuit {
+            children: [&pi_a, &pi_b],
+        });
+
+        let pi = BlockPublicInputs::<F>::from(proof.public_inputs.as_slice());
+        assert_eq!(pi.block_number(), block_number);
+        assert_eq!(pi.range(), range);
+        assert_eq!(pi.root(), root);
+        assert_eq!(pi.user_address(), user_address.as_slice());
+        assert_eq!(pi.query_results(), results_a + results_b);
+        assert_eq!(pi.rewards_rate(), rewards_rate);
+        assert_eq!(pi.max_rewards_rate(), U256::MAX);
+        assert_eq!(pi.state_depth(), F::from_canonical_u8(3));
+        assert_eq!(pi.aggregation_mode().unwrap(), AggregationKind::Sum);
+        assert_eq!(pi.rates_commitment(), rates_commitment);
+        assert_eq!(pi.rates_first_block(), rates_first_block);
+        // the set of contracts is committed in place of the contract address
+        let children = [
+            BlockPublicInputs::from(pi_a.as_slice()),
+            BlockPublicInputs::from(pi_b.as_slice()),
+        ];
+        assert_eq!(
+            pi.smart_contract_address(),
+            contracts_commitment(&children).as_slice()
+        );
+        assert_eq!(pi.mapping_slot(), F::ZERO);
+        assert_eq!(pi.mapping_slot_length(), F::ZERO);
+        assert_eq!(pi.slot_remaps_commitment(), HashOut::ZERO);
+        // the commitment doesn't depend on the order of the contracts
+        assert_eq!(
+            contracts_commitment(&children),
+            contracts_commitment(&[children[1].clone(), children[0].clone()])
+        );
+
+        // aggregating the same contract twice fails
+        let res = std::panic::catch_unwind(|| {
+            run_circuit::<F, D, C, _>(TestMultiContractNodeCircuit {
+                children: [&pi_a, &pi_a],
+            })
+        });
+        assert!(res.is_err(), "same contract aggregated twice");
+
+        // aggregating the results of different users fails
+        let other_user = BlockPublicInputs::from_parts(
+            block_number,
+            range,
+            range,
+            root,
+            &address_to_packed_fields(&contract_b),
+            &address_to_packed_fields(&Address::random()),
+            F::from_canonical_u8(24),
+            F::from_canonical_u8(42),
+            &results_b.to_fields().try_into().unwrap(),
+            &rewards_rate.to_fields().try_into().unwrap(),
+            &U256::MAX.to_fields().try_into().unwrap(),
+            F::from_canonical_u8(3),
+            AggregationKind::Sum,
+            rates_commitment,
+            rates_first_block,
+            HashOut::ZERO,
+            &[F::ZERO; PACKED_U256_LEN],
+            F::ZERO,
+        );
+        let res = std::panic::catch_unwind(|| {
+            run_circuit::<F, D, C, _>(TestMultiContractNodeCircuit {
+                children: [&pi_a, &other_user],
+            })
+        });
+        assert!(res.is_err(), "results of different users aggregated");
+
+        // aggregating results which aren't summed over the blocks fails
+        let max_pi = BlockPublicInputs::from_parts(
+            block_number,
+            range,
+            range,
+            root,
+            &address_to_packed_fields(&contract_b),
+            &user_address,
+            F::from_canonical_u8(24),
+            F::from_canonical_u8(42),
+            &results_b.to_fields().try_into().unwrap(),
+            &rewards_rate.to_fields().try_into().unwrap(),
+            &U256::MAX.to_fields().try_into().unwrap(),
+            F::from_canonical_u8(3),
+            AggregationKind::Max,
+            rates_commitment,
+            rates_first_block,
+            HashOut::ZERO,
+            &[F::ZERO; PACKED_U256_LEN],
+            F::ZERO,
+        );
+        let res = std::panic::catch_unwind(|| {
+            run_circuit::<F, D, C, _>(TestMultiContractNodeCircuit {
+                children: [&pi_a, &max_pi],
+            })
+        });
+        assert!(res.is_err(), "results aggregated with max summed");
+
+        // aggregating rates bound to different schedules fails
+        let mut other_schedule = pi_b;
+        other_schedule[crate::query_erc20::block::Inputs::RatesCommitment
+            .range()
+            .start] += F::ONE;
+        let res = std::panic::catch_unwind(|| {
+            run_circuit::<F, D, C, _>(TestMultiContractNodeCircuit {
+                children: [&pi_a, &other_schedule],
+            })
+        });
+        assert!(res.is_err(), "rates of different schedules aggregated");
+    }
+    #[test]
+    #[serial]
+    fn test_multi_contract_parameters() -> Result<()> {
+        // Generate a fake block circuit set
+        let testing_framework = TestingRecursiveCircuits::<F, C, D, NUM_IO>::default();
+        let block_circuit_set = testing_framework.get_recursive_circuit_set();
+        let params = Parameters::build(block_circuit_set);
+
+        let rng = &mut thread_rng();
+        let block_number = F::from_canonical_u32(rng.gen_range(1000..10_000));
+        let range = F::from_canonical_u32(rng.gen_range(1..1000));
+        let root = HashOut::rand();
+        let user_address = address_to_packed_fields(&Address::random());
+        let rewards_rate = U256::from(rng.gen::<u64>());
+        let results = [0; 2].map(|_| U256::from(rng.gen::<u128>()));
+        let children = results.map(|query_results| {
+            BlockPublicInputs::from_parts(
+                block_number,
+                range,
+                range,
+                root,
+                &address_to_packed_fields(&Address::random()),
+                &user_address,
+                F::from_canonical_u8(24),
+                F::from_canonical_u8(42),
+                &query_results.to_fields().try_into().unwrap(),
+                &rewards_rate.to_fields().try_into().unwrap(),
+                &U256::MAX.to_fields().try_into().unwrap(),
+                F::from_canonical_u8(3),
+                AggregationKind::Sum,
+                HashOut::ZERO,
+                F::ZERO,
+                HashOut::ZERO,
+                &[F::ZERO; PACKED_U256_LEN],
+                F::ZERO,
+            )
+        });
+        let vd = testing_framework.verifier_data_for_input_proofs::<1>()[0];
+        let [left_proof, right_proof] =
+            testing_framework
+                .generate_input_proofs(children)?
+                .map(|proof| {
+                    ProofWithVK {
+                        proof,
+                        vk: vd.clone(),
+                    }
+                    .serialize()
+                    .unwrap()
+                });
+
+        let proof = params.generate_proof(
+            block_circuit_set,
+            CircuitInput::new(left_proof, right_proof)?,
+        )?;
+        params.verify_proof(&proof)?;
+        let proof = ProofWithVK::deserialize(&proof)?;
+        let pi = BlockPublicInputs::<F>::from(&proof.proof.public_inputs[..NUM_IO]);
+        assert_eq!(pi.block_number(), block_number);
+        assert_eq!(pi.user_address(), user_address.as_slice());
+        assert_eq!(pi.query_results(), results[0] + results[1]);
+        assert_eq!(
+            pi.smart_contract_address(),
+            contracts_commitment(
+                &children
+                    .each_ref()
+                    .map(|pi| BlockPublicInputs::from(pi.as_slice()))
+            )
+            .as_slice()
+        );
+
+        Ok(())
+    }
+}
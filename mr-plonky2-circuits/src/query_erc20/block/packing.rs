@@ -0,0 +1,184 @@
+//! Compact encoding of the block number and the range of a block proof in a
+//! single field element, as `(block_number << 32) | range`, which halves the
+//! number of public inputs employed for these values. This is the encoding of
+//! the block number and the range exposed by `BlockPublicInputs`.
+//!
+//! Both values must fit in 32 bits. Since the Goldilocks modulus is
+//! `2^64 - 2^32 + 1`, the encoding of `block_number = 2^32 - 1` with a non-zero
+//! range would wrap around the modulus, so this block number is not allowed.
+
+use anyhow::{ensure, Result};
+use plonky2::{
+    field::{
+        goldilocks_field::GoldilocksField,
+        types::{Field, PrimeField64},
+    },
+    iop::target::Target,
+    plonk::circuit_builder::CircuitBuilder,
+};
+
+const SHIFT: usize = 32;
+
+/// Compute the packed encoding of `block_number` and `range`
+pub fn packed_block_range(block_number: u64, range: u64) -> Result<GoldilocksField> {
+    ensure!(
+        block_number < u32::MAX as u64,
+        "block number {block_number} can't be packed in 32 bits"
+    );
+    ensure!(
+        range <= u32::MAX as u64,
+        "range {range} can't be packed in 32 bits"
+    );
+    Ok(GoldilocksField::from_canonical_u64(
+        (block_number << SHIFT) | range,
+    ))
+}
+
+/// Unpack a value computed with `packed_block_range`, returning the block
+/// number and the range, in this order
+pub fn unpacked_block_range(packed: GoldilocksField) -> (GoldilocksField, GoldilocksField) {
+    let packed = packed.to_canonical_u64();
+    (
+        GoldilocksField::from_canonical_u64(packed >> SHIFT),
+        GoldilocksField::from_canonical_u64(packed & ((1 << SHIFT) - 1)),
+    )
+}
+
+/// Pack `block_number` and `range` in a single target, enforcing that both
+/// values fit in 32 bits
+pub fn pack_block_range(
+    b: &mut CircuitBuilder<GoldilocksField, 2>,
+    block_number: Target,
+    range: Target,
+) -> Target {
+    b.range_check(block_number, SHIFT);
+    b.range_check(range, SHIFT);
+    forbid_wrapping_block_number(b, block_number);
+    let shift = GoldilocksField::from_canonical_u64(1 << SHIFT);
+    b.mul_const_add(shift, block_number, range)
+}
+
+/// Unpack a target computed with `pack_block_range`, returning the block number
+/// and the range, in this order
+pub fn unpack_block_range(
+    b: &mut CircuitBuilder<GoldilocksField, 2>,
+    packed: Target,
+) -> (Target, Target) {
+    let (range, block_number) = b.split_low_high(packed, SHIFT, 2 * SHIFT);
+    // without this check, a packed value could be decomposed in 2 different ways
+    forbid_wrapping_block_number(b, block_number);
+    (block_number, range)
+}
+
+fn forbid_wrapping_block_number(b: &mut CircuitBuilder<GoldilocksField, 2>, block_number: Target) {
+    let max = b.constant(GoldilocksField::from_canonical_u32(u32::MAX));
+    let is_max = b.is_equal(block_number, max);
+    let _false = b._false();
+    b.connect(is_max.target, _false.target);
+}
+
+#[cfg(test)]
+mod tests {
+    use mrp2_test_utils::circuit::{run_circuit, UserCircuit};
+    use plonky2::{
+        iop::witness::{PartialWitness, WitnessWrite},
+        plonk::config::PoseidonGoldilocksConfig,
+    };
+    use rand::{thread_rng, Rng};
+
+    use super::*;
+
+    type F = GoldilocksField;
+    type C = PoseidonGoldilocksConfig;
+    const D: usize = 2;
+
+    #[derive(Clone, Debug)]
+    struct TestPackingCircuit {
+        block_number: F,
+        range: F,
+        packed: F,
+    }
+
+    impl UserCircuit<F, D> for TestPackingCircuit {
+        type Wires = (Target, Target, Target);
+
+        fn build(c: &mut CircuitBuilder<F, D>) -> Self::Wires {
+            let block_number = c.add_virtual_target();
+            let range = c.add_virtual_target();
+            let packed = c.add_virtual_target();
+            let computed_packed = pack_block_range(c, block_number, range);
+            let (unpacked_block_number, unpacked_range) = unpack_block_range(c, packed);
+            c.register_public_input(computed_packed);
+            c.register_public_input(unpacked_block_number);
+            c.register_public_input(unpacked_range);
+            (block_number, range, packed)
+        }
+
+        fn prove(&self, pw: &mut PartialWitness<F>, wires: &Self::Wires) {
+            pw.set_target(wires.0, self.block_number);
+            pw.set_target(wires.1, self.range);
+            pw.set_target(wires.2, self.packed);
+        }
+    }
+
+    #[test]
+    fn test_block_range_packing() {
+        let mut rng = thread_rng();
+        let block_number = rng.gen::<u32>() >> 1;
+        let range = rng.gen::<u32>();
+        let packed = packed_block_range(block_number as u64, range as u64).unwrap();
+        assert_eq!(
+            unpacked_block_range(packed),
+            (
+                F::from_canonical_u32(block_number),
+                F::from_canonical_u32(range)
+            )
+        );
+
+        // packed and unpacked forms agree
+        let proof = run_circuit::<F, D, C, _>(TestPackingCircuit {
+            block_number: F::from_canonical_u32(block_number),
+            range: F::from_canonical_u32(range),
+            packed,
+        });
+        assert_eq!(
+            proof.public_inputs,
+            vec![
+                packed,
+                F::from_canonical_u32(block_number),
+                F::from_canonical_u32(range)
+            ]
+        );
+
+        // values exceeding 32 bits are rejected
+        let too_big = F::from_canonical_u64(1 << 32);
+        assert!(packed_block_range(1 << 32, 0).is_err());
+        assert!(packed_block_range(0, 1 << 32).is_err());
+        assert!(packed_block_range(u32::MAX as u64, 0).is_err());
+        let res = std::panic::catch_unwind(|| {
+            run_circuit::<F, D, C, _>(TestPackingCircuit {
+                block_number: F::ZERO,
+                range: too_big,
+                packed,
+            })
+        });
+        assert!(res.is_err(), "range exceeding 32 bits accepted");
+        let res = std::panic::catch_unwind(|| {
+            run_circuit::<F, D, C, _>(TestPackingCircuit {
+                block_number: too_big,
+                range: F::ZERO,
+                packed,
+            })
+        });
+        assert!(res.is_err(), "block number exceeding 32 bits accepted");
+        // the block number whose encoding would wrap around the modulus is rejected
+        let res = std::panic::catch_unwind(|| {
+            run_circuit::<F, D, C, _>(TestPackingCircuit {
+                block_number: F::from_canonical_u32(u32::MAX),
+                range: F::ZERO,
+                packed,
+            })
+        });
+        assert!(res.is_err(), "wrapping block number accepted");
+    }
+}
@@ -22,7 +22,7 @@ pub struct PartialNodeWires {
     proved_is_right: BoolTarget,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PartialNodeCircuit {
     sibling_hash: HashOut<F>,
     sibling_is_left: bool,
@@ -44,6 +44,7 @@ impl PartialNodeCircuit {
     ) -> PartialNodeWires {
         let unproved = b.add_virtual_hash();
         let proved_is_right = b.add_virtual_bool_target_safe();
+        let (block_number, range) = proved.block_number_and_range(b);
         let root = hash_maybe_swap(
             b,
             &[proved.root().elements, unproved.elements],
@@ -52,8 +53,9 @@ impl PartialNodeCircuit {
 
         BlockPublicInputs::<Target>::register(
             b,
-            proved.block_number(),
-            proved.range(),
+            block_number,
+            range,
+            proved.aggregated_count(),
             &root,
             &proved.smart_contract_address(),
             &proved.user_address(),
@@ -61,6 +63,14 @@ impl PartialNodeCircuit {
             proved.mapping_slot_length(),
             proved.query_results(),
             proved.rewards_rate(),
+            proved.max_rewards_rate(),
+            proved.state_depth(),
+            proved.aggregation_mode(),
+            &proved.rates_commitment(),
+            proved.rates_first_block(),
+            &proved.slot_remaps_commitment(),
+            proved.threshold(),
+            proved.leaf_mode(),
         );
 
         PartialNodeWires {
@@ -101,6 +111,7 @@ impl CircuitLogicWires<F, D, 1> for PartialNodeWires {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct PartialNodeCircuitInputs {
     inputs: PartialNodeCircuit,
     child_proof: ProofWithVK,
@@ -117,6 +128,11 @@ impl PartialNodeCircuitInputs {
             child_proof,
         }
     }
+
+    /// Proof of the child aggregated by the partial node
+    pub(crate) fn child_proof(&self) -> &ProofWithVK {
+        &self.child_proof
+    }
 }
 
 impl From<PartialNodeCircuitInputs> for (PartialNodeCircuit, ProofWithVK) {
@@ -1,12 +1,21 @@
 mod api;
 pub mod block;
+pub mod rates;
 pub mod revelation;
+mod slots;
 mod state;
 mod storage;
+mod subrange;
+pub mod sum;
 #[cfg(test)]
 mod tests;
+pub mod transcript;
 
 pub use api::{
-    BlockCircuitInput, CircuitInput, PublicParameters, RevelationErcInput, StateCircuitInput,
-    StorageCircuitInput,
+    AggregationPlan, BlockCircuitInput, CircuitInput, MultiContractCircuitInput, PublicParameters,
+    RevelationErcInput, StateCircuitInput, StorageCircuitInput,
 };
+pub use revelation::result_as_uint256_array;
+pub use slots::{slot_at_block, slot_remaps_commitment, MAX_SLOT_REMAPS};
+pub use storage::{leaf_mode_id, LeafMode, RewardFormula};
+pub use subrange::{plan_subranges, validate_subranges};
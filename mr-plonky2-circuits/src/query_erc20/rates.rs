@@ -0,0 +1,308 @@
+//! Commitment to a schedule of rewards rates varying per block, allowing to
+//! prove which rate applied at each block. The schedule is committed with a
+//! Poseidon Merkle tree, whose i-th leaf is the rate of the i-th block of the
+//! schedule; the leaf check binds the block number of a proof to the rate found
+//! at the corresponding index of the committed schedule.
+
+use anyhow::{ensure, Result};
+use ethers::types::U256;
+use plonky2::{
+    hash::{
+        hash_types::{HashOut, HashOutTarget},
+        merkle_proofs::MerkleProofTarget,
+        merkle_tree::MerkleTree,
+        poseidon::PoseidonHash,
+    },
+    iop::{
+        target::{BoolTarget, Target},
+        witness::{PartialWitness, WitnessWrite},
+    },
+    plonk::circuit_builder::CircuitBuilder,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::{D, F},
+    poseidon::hash_maybe_swap,
+    utils::ToFields,
+};
+use mrp2_utils::{
+    serialization::{deserialize, serialize},
+    u256::UInt256Target,
+};
+
+/// Depth of the tree committing to the schedule of rewards rates employed by
+/// the state circuit, i.e. a schedule covers `2^RATES_SCHEDULE_DEPTH` blocks
+pub const RATES_SCHEDULE_DEPTH: usize = 16;
+
+/// Build the Merkle tree over the rates of the schedule; the number of rates
+/// must be `2^DEPTH`.
+fn rates_tree<const DEPTH: usize>(rates: &[U256]) -> MerkleTree<F, PoseidonHash> {
+    assert_eq!(
+        rates.len(),
+        1 << DEPTH,
+        "the rates schedule must have 2^{DEPTH} entries"
+    );
+    let leaves = rates.iter().map(|rate| rate.to_fields()).collect();
+    MerkleTree::new(leaves, 0)
+}
+
+/// Compute the commitment to the schedule of rates, i.e. the Poseidon Merkle
+/// root over the rates, where `rates[i]` is the rate of the i-th block of the
+/// schedule. The number of rates must be `2^DEPTH`.
+pub fn rates_commitment<const DEPTH: usize>(rates: &[U256]) -> HashOut<F> {
+    rates_tree::<DEPTH>(rates).cap.0[0]
+}
+
+/// Wires of the rate leaf check
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RateLeafWires<const DEPTH: usize> {
+    /// Opening of the rate in the schedule tree
+    #[serde(serialize_with = "serialize", deserialize_with = "deserialize")]
+    siblings: MerkleProofTarget,
+}
+
+/// Circuit checking that a rate is the one committed for a given block number
+/// in a schedule of `2^DEPTH` rates starting at a given block number
+#[derive(Clone, Debug)]
+pub struct RateLeafCircuit<const DEPTH: usize> {
+    siblings: Vec<HashOut<F>>,
+}
+
+impl<const DEPTH: usize> RateLeafCircuit<DEPTH> {
+    /// Open the rate of the block `block_number` in the schedule `rates`, whose
+    /// first entry is the rate of block `first_block`. It fails if the block
+    /// number isn't covered by the schedule.
+    pub fn new(rates: &[U256], first_block: u32, block_number: u32) -> Result<Self> {
+        ensure!(
+            rates.len() == 1 << DEPTH,
+            "the rates schedule must have 2^{DEPTH} entries, found {}",
+            rates.len()
+        );
+        ensure!(
+            block_number >= first_block,
+            "block number {block_number} before the beginning of the rates schedule {first_block}"
+        );
+        let index = (block_number - first_block) as usize;
+        ensure!(
+            index < rates.len(),
+            "block number {block_number} after the end of the rates schedule"
+        );
+
+        Ok(Self {
+            siblings: rates_tree::<DEPTH>(rates).prove(index).siblings,
+        })
+    }
+
+    /// Opening employed when the rate isn't bound to any schedule, which is
+    /// ignored by the check built with `build_if_committed`
+    pub(crate) fn uncommitted() -> Self {
+        Self {
+            siblings: vec![HashOut::default(); DEPTH],
+        }
+    }
+
+    /// Build the check that `rate` is the one committed in `rates_commitment`
+    /// for block `block_number`, where the schedule starts at block `first_block`.
+    /// The block number must be in the range covered by the schedule.
+    pub fn build(
+        b: &mut CircuitBuilder<F, D>,
+        block_number: Target,
+        first_block: Target,
+        rate: &UInt256Target,
+        rates_commitment: HashOutTarget,
+    ) -> RateLeafWires<DEPTH> {
+        let enabled = b._true();
+        Self::build_with_flag(
+            b,
+            enabled,
+            block_number,
+            first_block,
+            rate,
+            rates_commitment,
+        )
+    }
+
+    /// Same as `build`, but the check is enforced only if `rates_commitment`
+    /// isn't the zero hash, which stands for a rate not bound to any schedule.
+    pub fn build_if_committed(
+        b: &mut CircuitBuilder<F, D>,
+        block_number: Target,
+        first_block: Target,
+        rate: &UInt256Target,
+        rates_commitment: HashOutTarget,
+    ) -> RateLeafWires<DEPTH> {
+        let zero = b.zero();
+        let mut is_uncommitted = b._true();
+        for e in rates_commitment.elements {
+            let is_zero = b.is_equal(e, zero);
+            is_uncommitted = b.and(is_uncommitted, is_zero);
+        }
+        let enabled = b.not(is_uncommitted);
+        Self::build_with_flag(
+            b,
+            enabled,
+            block_number,
+            first_block,
+            rate,
+            rates_commitment,
+        )
+    }
+
+    fn build_with_flag(
+        b: &mut CircuitBuilder<F, D>,
+        enabled: BoolTarget,
+        block_number: Target,
+        first_block: Target,
+        rate: &UInt256Target,
+        rates_commitment: HashOutTarget,
+    ) -> RateLeafWires<DEPTH> {
+        let siblings = MerkleProofTarget {
+            siblings: b.add_virtual_hashes(DEPTH),
+        };
+
+        // The index must fit in DEPTH bits, which ensures the block number is
+        // within the schedule; it is zeroed when the check is disabled, so that
+        // any block number is accepted
+        let index = b.sub(block_number, first_block);
+        let index = b.mul(index, enabled.target);
+        let index_bits = b.split_le(index, DEPTH);
+
+        // Recompute the root of the schedule tree as `verify_merkle_proof` does
+        let leaf: Vec<Target> = rate.into();
+        let mut root = b.hash_n_to_hash_no_pad::<PoseidonHash>(leaf);
+        for (sibling, bit) in siblings.siblings.iter().zip(index_bits) {
+            root = hash_maybe_swap(b, &[root.elements, sibling.elements], bit);
+        }
+        // enabled => root == rates_commitment
+        root.elements
+            .into_iter()
+            .zip(rates_commitment.elements)
+            .for_each(|(r, c)| {
+                let diff = b.sub(r, c);
+                let diff = b.mul(diff, enabled.target);
+                b.assert_zero(diff);
+            });
+
+        RateLeafWires { siblings }
+    }
+
+    /// Assign the wires.
+    pub fn assign(&self, pw: &mut PartialWitness<F>, wires: &RateLeafWires<DEPTH>) {
+        wires
+            .siblings
+            .siblings
+            .iter()
+            .zip(&self.siblings)
+            .for_each(|(t, h)| pw.set_hash_target(*t, *h));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::C;
+    use mrp2_test_utils::circuit::{run_circuit, UserCircuit};
+    use mrp2_utils::u256::{CircuitBuilderU256, WitnessWriteU256};
+    use plonky2::field::types::Field;
+
+    const DEPTH: usize = 2;
+    const FIRST_BLOCK: u32 = 1000;
+
+    #[derive(Clone, Debug)]
+    struct TestRateLeafCircuit {
+        block_number: u32,
+        rate: U256,
+        rates_commitment: HashOut<F>,
+        c: RateLeafCircuit<DEPTH>,
+    }
+
+    impl UserCircuit<F, D> for TestRateLeafCircuit {
+        type Wires = (Target, UInt256Target, HashOutTarget, RateLeafWires<DEPTH>);
+
+        fn build(b: &mut CircuitBuilder<F, D>) -> Self::Wires {
+            let block_number = b.add_virtual_target();
+            let first_block = b.constant(F::from_canonical_u32(FIRST_BLOCK));
+            let rate = b.add_virtual_u256();
+            let rates_commitment = b.add_virtual_hash();
+            let wires = RateLeafCircuit::<DEPTH>::build(
+                b,
+                block_number,
+                first_block,
+                &rate,
+                rates_commitment,
+            );
+            b.register_public_input(block_number);
+            b.register_public_input_u256(&rate);
+            b.register_public_inputs(&rates_commitment.elements);
+
+            (block_number, rate, rates_commitment, wires)
+        }
+
+        fn prove(&self, pw: &mut PartialWitness<F>, wires: &Self::Wires) {
+            pw.set_target(wires.0, F::from_canonical_u32(self.block_number));
+            pw.set_u256_target(&wires.1, self.rate);
+            pw.set_hash_target(wires.2, self.rates_commitment);
+            self.c.assign(pw, &wires.3);
+        }
+    }
+
+    #[test]
+    fn test_rate_leaf_circuit() {
+        let rates = [10, 20, 30, 40].map(U256::from);
+        let rates_commitment = rates_commitment::<DEPTH>(&rates);
+        let block_number = FIRST_BLOCK + 2;
+
+        // the rate used at block 2 of the schedule is proven
+        let proof = run_circuit::<F, D, C, _>(TestRateLeafCircuit {
+            block_number,
+            rate: rates[2],
+            rates_commitment,
+            c: RateLeafCircuit::new(&rates, FIRST_BLOCK, block_number).unwrap(),
+        });
+        assert_eq!(proof.public_inputs[0], F::from_canonical_u32(block_number));
+        assert_eq!(&proof.public_inputs[1..9], rates[2].to_fields().as_slice());
+        assert_eq!(
+            &proof.public_inputs[9..],
+            rates_commitment.elements.as_slice()
+        );
+
+        // the rate of another block of the schedule can't be used at block 2
+        let res = std::panic::catch_unwind(|| {
+            run_circuit::<F, D, C, _>(TestRateLeafCircuit {
+                block_number,
+                rate: rates[1],
+                rates_commitment,
+                c: RateLeafCircuit::new(&rates, FIRST_BLOCK, block_number).unwrap(),
+            })
+        });
+        assert!(res.is_err(), "wrong rate accepted");
+
+        // a valid opening can't be used for another block
+        let res = std::panic::catch_unwind(|| {
+            run_circuit::<F, D, C, _>(TestRateLeafCircuit {
+                block_number: block_number + 1,
+                rate: rates[2],
+                rates_commitment,
+                c: RateLeafCircuit::new(&rates, FIRST_BLOCK, block_number).unwrap(),
+            })
+        });
+        assert!(res.is_err(), "opening accepted for another block");
+
+        // a block out of the schedule is rejected
+        let res = std::panic::catch_unwind(|| {
+            run_circuit::<F, D, C, _>(TestRateLeafCircuit {
+                block_number: FIRST_BLOCK + 4,
+                rate: rates[0],
+                rates_commitment,
+                c: RateLeafCircuit::new(&rates, FIRST_BLOCK, FIRST_BLOCK).unwrap(),
+            })
+        });
+        assert!(res.is_err(), "block out of the schedule accepted");
+
+        // openings are only computed for blocks covered by the schedule
+        assert!(RateLeafCircuit::<DEPTH>::new(&rates, FIRST_BLOCK, FIRST_BLOCK - 1).is_err());
+        assert!(RateLeafCircuit::<DEPTH>::new(&rates, FIRST_BLOCK, FIRST_BLOCK + 4).is_err());
+        assert!(RateLeafCircuit::<DEPTH>::new(&rates[..3], FIRST_BLOCK, FIRST_BLOCK).is_err());
+    }
+}
@@ -1,6 +1,8 @@
+use ethers::types::{Address, U256};
 use itertools::Itertools;
 use mrp2_utils::{
     serialization::{deserialize, serialize},
+    u256::{CircuitBuilderU256, UInt256Target},
     utils::keccak256,
 };
 use plonky2::{
@@ -28,23 +30,141 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     api::{default_config, deserialize_proof, ProofWithVK, C, D, F},
+    array::{Array, Vector, VectorWire},
     block::{
         empty_merkle_root, public_inputs::PublicInputs as BlockDBPublicInputs,
         Parameters as BlockDbParameters,
     },
+    group_hashing::CircuitBuilderGroupHashing,
+    keccak::{compute_size_with_padding, InputData, KeccakCircuit, KeccakWires},
     query_erc20::{
         block::BlockPublicInputs as BlockQueryPublicInputs,
-        revelation::{BLOCK_DB_NUM_IO, QUERY_ERC_BLOCK_NUM_IO},
+        revelation::{identifier_for, BLOCK_DB_NUM_IO, MAX_DECIMALS, QUERY_ERC_BLOCK_NUM_IO},
     },
+    types::{PackedAddressTarget, ADDRESS_LEN},
     utils::less_than,
 };
 
 use super::{num_io, RevelationErcInput, RevelationPublicInputs};
 
+/// Length in bytes of the preimage of the query commitment:
+/// contract address (20) || user address (20) || min block (4) || max block (4) || leaf mode (1)
+/// || rewards rate (32) || threshold (32) || max rewards rate (32)
+pub(crate) const QUERY_COMMITMENT_INPUT_LEN: usize = 2 * ADDRESS_LEN + 2 * 4 + 1 + 3 * 32;
+const QUERY_COMMITMENT_PADDED_LEN: usize = compute_size_with_padding(QUERY_COMMITMENT_INPUT_LEN);
+
+/// Compute the commitment to the query parameters exposed by the revelation
+/// proof, i.e. `keccak(contract_address || user_address || min_block || max_block || leaf_mode
+/// || rewards_rate || threshold || max_rewards_rate)`, where the leaf mode is the
+/// identifier returned by `leaf_mode_id`, so that the commitment identifies the
+/// computation the proof attests to. The block numbers are encoded as big-endian
+/// uint32, the leaf mode as a single byte and the rewards rate, threshold and max
+/// rewards rate as big-endian uint256, to match `abi.encodePacked` in the verifier
+/// contract.
+pub fn query_commitment(
+    contract_address: &Address,
+    user_address: &Address,
+    min_block_number: u32,
+    max_block_number: u32,
+    leaf_mode: u8,
+    rewards_rate: U256,
+    threshold: U256,
+    max_rewards_rate: U256,
+) -> Vec<u8> {
+    let [rewards_rate_bytes, threshold_bytes, max_rewards_rate_bytes] =
+        [rewards_rate, threshold, max_rewards_rate].map(|v| {
+            let mut bytes = [0u8; 32];
+            v.to_big_endian(&mut bytes);
+            bytes
+        });
+    let preimage = contract_address
+        .as_bytes()
+        .iter()
+        .chain(user_address.as_bytes())
+        .chain(&min_block_number.to_be_bytes())
+        .chain(&max_block_number.to_be_bytes())
+        .chain(&[leaf_mode])
+        .chain(&rewards_rate_bytes)
+        .chain(&threshold_bytes)
+        .chain(&max_rewards_rate_bytes)
+        .copied()
+        .collect_vec();
+    assert_eq!(preimage.len(), QUERY_COMMITMENT_INPUT_LEN);
+
+    keccak256(&preimage)
+}
+
+/// Compute in circuit the commitment to the query parameters, see `query_commitment`.
+fn build_query_commitment(
+    b: &mut CircuitBuilder<GoldilocksField, 2>,
+    contract_address: &PackedAddressTarget,
+    user_address: &PackedAddressTarget,
+    min_block_number: Target,
+    max_block_number: Target,
+    leaf_mode: Target,
+    rewards_rate: &UInt256Target,
+    threshold: &UInt256Target,
+    max_rewards_rate: &UInt256Target,
+) -> KeccakWires<QUERY_COMMITMENT_PADDED_LEN> {
+    // The packed u32 are the little-endian packing of the original bytes,
+    // while the numbers are encoded in big-endian.
+    let mut bytes = vec![];
+    for limb in contract_address.arr.iter().chain(user_address.arr.iter()) {
+        bytes.extend(b.split_le_base::<256>(limb.0, 4));
+    }
+    for t in [min_block_number, max_block_number] {
+        bytes.extend(b.split_le_base::<256>(t, 4).into_iter().rev());
+    }
+    // The leaf mode fits in a single byte
+    bytes.extend(b.split_le_base::<256>(leaf_mode, 1));
+    for t in [rewards_rate, threshold, max_rewards_rate]
+        .into_iter()
+        .flat_map(UInt256Target::to_big_endian_targets)
+    {
+        bytes.extend(b.split_le_base::<256>(t, 4).into_iter().rev());
+    }
+    assert_eq!(bytes.len(), QUERY_COMMITMENT_INPUT_LEN);
+
+    let zero = b.zero();
+    bytes.resize(QUERY_COMMITMENT_PADDED_LEN, zero);
+    let preimage = VectorWire::<Target, QUERY_COMMITMENT_PADDED_LEN> {
+        real_len: b.constant(GoldilocksField::from_canonical_usize(
+            QUERY_COMMITMENT_INPUT_LEN,
+        )),
+        arr: Array {
+            arr: bytes.try_into().unwrap(),
+        },
+    };
+
+    KeccakCircuit::hash_vector(b, &preimage)
+}
+
+/// Compute in circuit `10^decimals`, enforcing that `decimals <= MAX_DECIMALS`
+fn build_power_of_ten(
+    b: &mut CircuitBuilder<GoldilocksField, 2>,
+    decimals: Target,
+) -> UInt256Target {
+    let _true = b._true();
+    let max_decimals = b.constant(GoldilocksField::from_canonical_u8(MAX_DECIMALS + 1));
+    let is_valid = less_than(b, decimals, max_decimals, 7);
+    b.connect(is_valid.target, _true.target);
+
+    let mut power = b.constant_u256(U256::one());
+    for i in 1..=MAX_DECIMALS {
+        let exponent = b.constant(GoldilocksField::from_canonical_u8(i));
+        let is_exponent = b.is_equal(decimals, exponent);
+        let candidate = b.constant_u256(U256::exp10(i as usize));
+        power = b.select_u256(is_exponent, &candidate, &power);
+    }
+    power
+}
+
 #[derive(Serialize, Deserialize)]
 pub(crate) struct RevelationWires {
     pub min_block_number: Target,
     pub max_block_number: Target,
+    pub decimals: Target,
+    query_commitment: KeccakWires<QUERY_COMMITMENT_PADDED_LEN>,
 }
 
 #[derive(Clone, Debug)]
@@ -52,6 +172,7 @@ pub struct RevelationCircuit<const L: usize> {
     // parameters of the query
     pub(crate) query_min_block_number: usize,
     pub(crate) query_max_block_number: usize,
+    pub(crate) decimals: u8,
 }
 impl<const L: usize> RevelationCircuit<L> {
     pub fn build<const MAX_DEPTH: usize>(
@@ -70,13 +191,15 @@ impl<const L: usize> RevelationCircuit<L> {
 
         let query_min_block_number = b.add_virtual_target();
         let query_max_block_number = b.add_virtual_target();
+        let decimals = b.add_virtual_target();
 
         // Assert the roots of the query and the block db are the same
         b.connect_hashes(root_proof.root(), db_proof.root());
         b.connect_hashes(db_proof.init_root(), empty_root);
 
         let one = b.one();
-        let computed_min_block = b.sub(root_proof.block_number(), root_proof.range());
+        let (block_number, range) = root_proof.block_number_and_range(b);
+        let computed_min_block = b.sub(block_number, range);
         let computed_min_block = b.add(computed_min_block, one);
         let min_block_in_db = db_proof.first_block_number();
         let max_block_in_db = db_proof.block_number();
@@ -94,12 +217,61 @@ impl<const L: usize> RevelationCircuit<L> {
         // where B_i is the latest block inserted in our db and B_MAX is the block parameter of the query
         let too_large_max = less_than(b, max_block_in_db.0, query_max_block_number, 32);
         let right_side = b.select(too_large_max, max_block_in_db.0, query_max_block_number);
-        b.connect(root_proof.block_number(), right_side);
+        b.connect(block_number, right_side);
+
+        // Bind the proof to the query parameters it has been generated for
+        let query_commitment = build_query_commitment(
+            b,
+            &root_proof.smart_contract_address(),
+            &root_proof.user_address(),
+            query_min_block_number,
+            query_max_block_number,
+            root_proof.leaf_mode(),
+            &root_proof.rewards_rate(),
+            &root_proof.threshold(),
+            &root_proof.max_rewards_rate(),
+        );
+
+        // Scale the result by the decimals of the token; the power of ten is never
+        // zero, so the division by zero flag can be ignored
+        let power_of_ten = build_power_of_ten(b, decimals);
+        let (scaled_result, scaled_remainder, _) =
+            b.div_u256(&root_proof.query_results(), &power_of_ten);
+
+        // Expose the digest of the contract, to link proofs about the same contract
+        let contract_digest =
+            b.map_to_curve_point(&root_proof.smart_contract_address().arr.map(|limb| limb.0));
+        // Commit to the ordered storage slots read by the query, together with
+        // the migrations of the mapping the slot of each block is resolved from,
+        // if the mapping migrated
+        let slots = vec![root_proof.mapping_slot(), root_proof.mapping_slot_length()];
+        let slot_remaps_commitment = root_proof.slot_remaps_commitment();
+        let zero = b.zero();
+        let mut never_migrated = b._true();
+        for e in slot_remaps_commitment.elements {
+            let is_zero = b.is_equal(e, zero);
+            never_migrated = b.and(never_migrated, is_zero);
+        }
+        let original_slots_commitment = b.hash_n_to_hash_no_pad::<PoseidonHash>(slots.clone());
+        let remapped_slots_commitment = b.hash_n_to_hash_no_pad::<PoseidonHash>(
+            slots
+                .into_iter()
+                .chain(slot_remaps_commitment.elements)
+                .collect(),
+        );
+        let slots_commitment = HashOutTarget::from_vec(
+            original_slots_commitment
+                .elements
+                .into_iter()
+                .zip(remapped_slots_commitment.elements)
+                .map(|(original, remapped)| b.select(never_migrated, original, remapped))
+                .collect(),
+        );
 
         RevelationPublicInputs::<Target, L>::register(
             b,
-            root_proof.block_number(),
-            root_proof.range(),
+            block_number,
+            range,
             query_min_block_number,
             query_max_block_number,
             &root_proof.smart_contract_address(),
@@ -109,11 +281,23 @@ impl<const L: usize> RevelationCircuit<L> {
             db_proof.original_block_header(),
             root_proof.query_results(),
             root_proof.rewards_rate(),
+            &query_commitment.output_array,
+            decimals,
+            &scaled_result,
+            &scaled_remainder,
+            contract_digest,
+            &slots_commitment,
+            root_proof.aggregation_mode(),
+            &root_proof.max_rewards_rate(),
+            &root_proof.threshold(),
+            root_proof.leaf_mode(),
         );
 
         RevelationWires {
             min_block_number: query_min_block_number,
             max_block_number: query_max_block_number,
+            decimals,
+            query_commitment,
         }
     }
 
@@ -126,6 +310,19 @@ impl<const L: usize> RevelationCircuit<L> {
             wires.max_block_number,
             GoldilocksField::from_canonical_usize(self.query_max_block_number),
         );
+        pw.set_target(
+            wires.decimals,
+            GoldilocksField::from_canonical_u8(self.decimals),
+        );
+        // The preimage is computed in circuit, only its length is needed here
+        KeccakCircuit::<QUERY_COMMITMENT_PADDED_LEN>::assign(
+            pw,
+            &wires.query_commitment,
+            &InputData::Assigned(&Vector {
+                arr: [0u8; QUERY_COMMITMENT_PADDED_LEN],
+                real_len: QUERY_COMMITMENT_INPUT_LEN,
+            }),
+        );
     }
 }
 
@@ -229,8 +426,7 @@ where
             RevelationCircuit::<L>::build::<BLOCK_DB_DEPTH>(builder, block_db_pi, query_block_pi);
 
         // register additional public input to identify the query circuits
-        let identifier =
-            builder.constant(F::from_canonical_u8(keccak256("QueryERC20".as_bytes())[0]));
+        let identifier = builder.constant(F::from_canonical_u8(identifier_for("QueryERC20")));
         builder.register_public_input(identifier);
 
         RevelationRecursiveWires {
@@ -1,4 +1,4 @@
-use anyhow::Result;
+use anyhow::{ensure, Result};
 use circuit::{revelation_num_io, BuilderParams, RevelationRecursiveWires};
 use recursion_framework::{
     circuit_builder::{CircuitWithUniversalVerifier, CircuitWithUniversalVerifierBuilder},
@@ -6,7 +6,9 @@ use recursion_framework::{
 };
 use serde::{Deserialize, Serialize};
 
+use ethers::types::U256;
 use plonky2::{
+    field::types::PrimeField64,
     hash::poseidon::PoseidonHash,
     iop::target::Target,
     plonk::{
@@ -17,16 +19,27 @@ use plonky2::{
 };
 
 use crate::{
-    api::{default_config, deserialize_proof, ProofWithVK, C, D, F, QUERY_CIRCUIT_SET_SIZE},
+    api::{
+        default_config, deserialize_proof, query_type_of, ProofWithVK, QueryType, C, D, F,
+        QUERY_CIRCUIT_SET_SIZE,
+    },
     block::NUM_IVC_PUBLIC_INPUTS,
+    query2::revelation::nft_ids_at_runtime,
     query_erc20::block,
 };
 
-pub use self::circuit::{RevelationCircuit, RevelationRecursiveInput};
+pub use self::circuit::{query_commitment, RevelationCircuit, RevelationRecursiveInput};
+pub use crate::query2::revelation::{identifier_for, identifier_hash};
 
 pub mod circuit;
 mod public_inputs;
-pub use self::public_inputs::RevelationPublicInputs;
+pub mod top_k;
+use self::public_inputs::query_results_at_runtime;
+pub use self::public_inputs::{
+    contract_digest, remapped_slots_commitment, revelation_io_len, slots_commitment,
+    OwnedRevelationPublicInputs, RevelationPublicInputs, RevelationPublicInputsBuilder,
+};
+pub use self::top_k::{TopKCircuit, TopKWires};
 /// Wires containing the main logic wires of the RevelationCircuit,
 /// the verifier wires to check a crate::block proof (block db) and
 /// the verifier wires to check a proof from query/block circuit set.
@@ -54,15 +67,23 @@ pub struct RevelationErcInput<const L: usize> {
 }
 
 impl<const L: usize> RevelationErcInput<L> {
+    /// `decimals` is the number of decimals the query result is scaled by in
+    /// the public inputs, in addition to the raw result
     pub fn new(
         query_min_block: usize,
         query_max_block: usize,
+        decimals: u8,
         query_block_proof: Vec<u8>,
         block_db_proof: Vec<u8>,
     ) -> Result<RevelationErcInput<L>> {
+        ensure!(
+            decimals <= MAX_DECIMALS,
+            "decimals can't be greater than {MAX_DECIMALS}"
+        );
         let main_inputs = RevelationCircuit {
             query_min_block_number: query_min_block,
             query_max_block_number: query_max_block,
+            decimals,
         };
         Ok(RevelationErcInput {
             logic_inputs: main_inputs,
@@ -70,8 +91,43 @@ impl<const L: usize> RevelationErcInput<L> {
             block_db_proof: deserialize_proof(&block_db_proof)?,
         })
     }
+
+    /// Proof of the root of the query block tree
+    pub(crate) fn query_block_proof(&self) -> &ProofWithVK {
+        &self.query_block_proof
+    }
+}
+
+/// Extract the result of the query proven by `proof`, a proof returned by
+/// `QueryParameters::generate_proof` for revelation inputs, as the `uint256[]`
+/// returned by the verifier contract: the `L` NFT IDs for NFT queries, and the
+/// query result for ERC20 queries.
+pub fn result_as_uint256_array(proof: &[u8]) -> Result<Vec<U256>> {
+    let query_type = query_type_of(proof)?;
+    let pis = deserialize_proof::<F, C, D>(proof)?.public_inputs;
+    // the proof exposes the revelation public inputs followed by the query identifier,
+    // so the number `L` of revealed items is inferred from the number of public inputs
+    let min_num_io = revelation_io_len(0, 1) + 1;
+    ensure!(
+        pis.len() >= min_num_io,
+        "query proof has {} public inputs, expected at least {min_num_io}",
+        pis.len()
+    );
+    let l = pis.len() - min_num_io;
+
+    Ok(match query_type {
+        QueryType::Nft => nft_ids_at_runtime(&pis, l)
+            .iter()
+            .map(|id| U256::from(id.to_canonical_u64()))
+            .collect(),
+        QueryType::Erc20 => vec![query_results_at_runtime(&pis, l)],
+    })
 }
 
+/// Maximum number of decimals the query result can be scaled by, since
+/// `10^77` is the greatest power of ten fitting in a uint256
+pub const MAX_DECIMALS: u8 = 77;
+
 const QUERY_ERC_BLOCK_NUM_IO: usize = block::BlockPublicInputs::<Target>::total_len();
 const BLOCK_DB_NUM_IO: usize = NUM_IVC_PUBLIC_INPUTS;
 pub const fn num_io<const L: usize>() -> usize {
@@ -131,6 +187,17 @@ where
         let proof = deserialize_proof(&proof)?;
         self.revelation_circuit.circuit_data().verify(proof)
     }
+    /// Verify the provided proof and return its decoded public inputs, only if
+    /// the verification succeeds.
+    pub fn verify_and_decode(&self, proof: &[u8]) -> Result<OwnedRevelationPublicInputs<L>> {
+        let proof = deserialize_proof::<F, C, D>(proof)?;
+        self.revelation_circuit
+            .circuit_data()
+            .verify(proof.clone())?;
+        Ok(OwnedRevelationPublicInputs(
+            proof.public_inputs[..RevelationPublicInputs::<F, L>::total_len()].to_vec(),
+        ))
+    }
 }
 
 #[cfg(test)]
@@ -138,14 +205,15 @@ mod test {
     use std::iter::once;
 
     use crate::{
-        api::{serialize_proof, ProofWithVK},
+        api::{serialize_proof, tests::dummy_proof_with_vk, ProofWithVK},
         block::empty_merkle_root,
         keccak::PACKED_HASH_LEN,
+        query2::revelation::RevelationPublicInputs as Query2PublicInputs,
         query_erc20::revelation::{RevelationRecursiveInput, QUERY_ERC_BLOCK_NUM_IO},
         utils::{Packer, ToFields},
     };
     use anyhow::Result;
-    use ethers::types::Address;
+    use ethers::types::{Address, U256};
     use itertools::Itertools;
     use mrp2_utils::types::PACKED_U256_LEN;
     use plonky2::{
@@ -165,7 +233,7 @@ mod test {
 
     use crate::{
         api::{C, D, F},
-        query_erc20::block::BlockPublicInputs,
+        query_erc20::block::{aggregation::AggregationKind, BlockPublicInputs},
     };
 
     #[test]
@@ -237,11 +305,15 @@ mod test {
         let user_address = Address::random();
         let mapping_slot = F::rand();
         let length_slot = F::rand();
-        let rewards_rate = [F::ZERO; PACKED_U256_LEN];
-        let query_results = [F::ZERO; PACKED_U256_LEN];
+        let rewards_rate_value = U256::from(thread_rng().gen::<u64>());
+        let rewards_rate: [F; PACKED_U256_LEN] = rewards_rate_value.to_fields().try_into().unwrap();
+        let query_results_value = U256::from(thread_rng().gen::<u128>());
+        let query_results: [F; PACKED_U256_LEN] =
+            query_results_value.to_fields().try_into().unwrap();
         let pis = BlockPublicInputs::from_parts(
             query_max_number,
             query_range,
+            query_range,
             query_root,
             &smc_address
                 .as_fixed_bytes()
@@ -259,6 +331,15 @@ mod test {
             length_slot,
             &query_results,
             &rewards_rate,
+            &U256::MAX.to_fields().try_into().unwrap(),
+            F::from_canonical_u8(3),
+            // results aggregated with any mode can be revealed
+            AggregationKind::Max,
+            HashOut::ZERO,
+            F::ZERO,
+            HashOut::ZERO,
+            &[F::ZERO; PACKED_U256_LEN],
+            F::ZERO,
         );
         let query_block_proof = query_testing_framework
             .generate_input_proofs([pis])
@@ -277,6 +358,7 @@ mod test {
                 // are the same, as they should be since we have to look at all the blocks
                 query_min_number.to_canonical_u64() as usize,
                 query_max_number.to_canonical_u64() as usize,
+                18,
                 q_proof_buff,
                 block_db_buff,
             )?,
@@ -284,7 +366,207 @@ mod test {
         )?;
         println!("generating revelation proof");
         let proof = params.generate_proof(queries_circuit_set, revelation_inputs)?;
-        params.verify_proof(proof)?;
+        // the identifier registered by the circuit is the one derived from the query name
+        let identifier = *deserialize_proof::<F, C, D>(&proof)?
+            .public_inputs
+            .last()
+            .unwrap();
+        assert_eq!(
+            identifier,
+            F::from_canonical_u8(identifier_for("QueryERC20"))
+        );
+        assert_eq!(
+            identifier_for("QueryERC20"),
+            identifier_hash("QueryERC20")[0]
+        );
+        let decoded_pis = params.verify_and_decode(&proof)?;
+        let pis = decoded_pis.view();
+        // a proof with tampered public inputs is rejected before being decoded
+        let mut tampered_proof = deserialize_proof::<F, C, D>(&proof)?;
+        tampered_proof.public_inputs[0] += F::ONE;
+        assert!(params
+            .verify_and_decode(&serialize_proof(&tampered_proof)?)
+            .is_err());
+        assert_eq!(pis.min_block_number(), query_min_number);
+        assert_eq!(pis.max_block_number(), query_max_number);
+        // the result is exposed both raw and scaled by 18 decimals
+        assert_eq!(pis.query_results(), query_results_value);
+        assert_eq!(pis.decimals(), F::from_canonical_u8(18));
+        assert_eq!(
+            pis.scaled_query_results(),
+            query_results_value / U256::exp10(18)
+        );
+        assert_eq!(
+            pis.scaled_query_remainder(),
+            query_results_value % U256::exp10(18)
+        );
+        assert_eq!(pis.aggregation_mode()?, AggregationKind::Max);
+        assert_eq!(pis.threshold(), U256::zero());
+        assert_eq!(pis.leaf_mode(), 0);
+
+        // Check the commitment to the query parameters, and that it changes
+        // whenever any of the query parameters is altered.
+        let min_block = query_min_number.to_canonical_u64() as u32;
+        let max_block = query_max_number.to_canonical_u64() as u32;
+        let expected_commitment = query_commitment(
+            &smc_address,
+            &user_address,
+            min_block,
+            max_block,
+            0,
+            rewards_rate_value,
+            U256::zero(),
+            U256::MAX,
+        );
+        assert_eq!(pis.query_commitment(), expected_commitment);
+        let other_address = Address::random();
+        [
+            query_commitment(
+                &other_address,
+                &user_address,
+                min_block,
+                max_block,
+                0,
+                rewards_rate_value,
+                U256::zero(),
+                U256::MAX,
+            ),
+            query_commitment(
+                &smc_address,
+                &other_address,
+                min_block,
+                max_block,
+                0,
+                rewards_rate_value,
+                U256::zero(),
+                U256::MAX,
+            ),
+            query_commitment(
+                &smc_address,
+                &user_address,
+                min_block - 1,
+                max_block,
+                0,
+                rewards_rate_value,
+                U256::zero(),
+                U256::MAX,
+            ),
+            query_commitment(
+                &smc_address,
+                &user_address,
+                min_block,
+                max_block + 1,
+                0,
+                rewards_rate_value,
+                U256::zero(),
+                U256::MAX,
+            ),
+            query_commitment(
+                &smc_address,
+                &user_address,
+                min_block,
+                max_block,
+                0,
+                rewards_rate_value + 1,
+                U256::zero(),
+                U256::MAX,
+            ),
+            query_commitment(
+                &smc_address,
+                &user_address,
+                min_block,
+                max_block,
+                1,
+                rewards_rate_value,
+                U256::zero(),
+                U256::MAX,
+            ),
+            query_commitment(
+                &smc_address,
+                &user_address,
+                min_block,
+                max_block,
+                0,
+                rewards_rate_value,
+                U256::one(),
+                U256::MAX,
+            ),
+            query_commitment(
+                &smc_address,
+                &user_address,
+                min_block,
+                max_block,
+                0,
+                rewards_rate_value,
+                U256::zero(),
+                U256::MAX - 1,
+            ),
+        ]
+        .iter()
+        .for_each(|commitment| assert_ne!(pis.query_commitment(), *commitment));
         Ok(())
     }
+
+    #[test]
+    fn test_result_as_uint256_array() {
+        const L: usize = 5;
+        const NUM_IO: usize = num_io::<L>();
+        let mut rng = thread_rng();
+        let wrapped_proof = |revelation_pis: &[F], query_type: QueryType| {
+            let pis = revelation_pis
+                .iter()
+                .cloned()
+                .chain(once(F::from_canonical_u8(query_type.identifier())))
+                .collect_vec();
+            serialize_proof(&dummy_proof_with_vk::<NUM_IO>(pis.try_into().unwrap()).proof).unwrap()
+        };
+
+        // ERC20 query: the query result is returned
+        let query_results = U256::from(rng.gen::<u128>());
+        let erc20_pis = RevelationPublicInputsBuilder::<L>::new()
+            .block_number(F::from_canonical_u32(100))
+            .range(F::from_canonical_u32(10))
+            .min_block_number(F::from_canonical_u32(91))
+            .max_block_number(F::from_canonical_u32(100))
+            .smart_contract_address(Address::random())
+            .user_address(Address::random())
+            .mapping_slot(F::from_canonical_u8(3))
+            .mapping_slot_length(F::from_canonical_u8(4))
+            .block_header(rng.gen())
+            .rewards_rate(U256::from(rng.gen::<u64>()))
+            .query_results(query_results)
+            .query_commitment(rng.gen())
+            .decimals(18)
+            .build()
+            .unwrap();
+        let proof = wrapped_proof(&erc20_pis, QueryType::Erc20);
+        assert_eq!(
+            result_as_uint256_array(&proof).unwrap(),
+            vec![query_results]
+        );
+
+        // NFT query: all the L NFT IDs are returned
+        let nft_pis = (0..RevelationPublicInputs::<F, L>::total_len())
+            .map(|_| F::from_canonical_u32(rng.gen()))
+            .collect_vec();
+        let nft_ids = Query2PublicInputs::<F, L>::from(nft_pis.as_slice())
+            .nft_ids()
+            .iter()
+            .map(|id| U256::from(id.to_canonical_u64()))
+            .collect_vec();
+        let proof = wrapped_proof(&nft_pis, QueryType::Nft);
+        let result = result_as_uint256_array(&proof).unwrap();
+        assert_eq!(result.len(), L);
+        assert_eq!(result, nft_ids);
+
+        // a proof with an unknown identifier is rejected
+        let known_identifiers = [QueryType::Nft, QueryType::Erc20].map(|q| q.identifier());
+        let unknown_identifier = (0..=u8::MAX)
+            .find(|id| !known_identifiers.contains(id))
+            .unwrap();
+        let mut pis = [F::ZERO; NUM_IO];
+        pis[NUM_IO - 1] = F::from_canonical_u8(unknown_identifier);
+        let proof = serialize_proof(&dummy_proof_with_vk::<NUM_IO>(pis).proof).unwrap();
+        assert!(result_as_uint256_array(&proof).is_err());
+    }
 }
@@ -1,18 +1,43 @@
 use std::array::from_fn as create_array;
 
-use ethers::prelude::U256;
+use anyhow::{anyhow, ensure, Result};
+use ethers::prelude::{Address, U256};
 use mrp2_utils::{
     types::PACKED_U256_LEN,
     u256::{CircuitBuilderU256, UInt256Target},
-    utils::convert_u32_fields_to_u256,
+    utils::{convert_u32_fields_to_u256, convert_u8_slice_to_u32_fields},
 };
 use plonky2::{
-    field::goldilocks_field::GoldilocksField, iop::target::Target,
+    field::{
+        extension::{quintic::QuinticExtension, FieldExtension},
+        goldilocks_field::GoldilocksField,
+        types::{Field, PrimeField64},
+    },
+    hash::{
+        hash_types::{HashOut, HashOutTarget, NUM_HASH_OUT_ELTS},
+        hashing::hash_n_to_hash_no_pad,
+        poseidon::PoseidonPermutation,
+    },
+    iop::target::Target,
     plonk::circuit_builder::CircuitBuilder,
 };
 use plonky2_crypto::u32::arithmetic_u32::U32Target;
+use plonky2_ecgfp5::{
+    curve::curve::WeierstrassPoint,
+    gadgets::curve::{CircuitBuilderEcGFp5, CurveTarget},
+};
 
-use crate::{keccak::OutputHash, types::PackedAddressTarget, utils::convert_u32_fields_to_u8_vec};
+use super::MAX_DECIMALS;
+use crate::{
+    group_hashing::map_to_curve_point,
+    keccak::OutputHash,
+    query_erc20::block::aggregation::AggregationKind,
+    types::{address_to_packed_fields, HashOutput, PackedAddressTarget, CURVE_TARGET_LEN},
+    utils::{
+        convert_point_to_curve_target, convert_slice_to_curve_point, convert_u32_fields_to_u8_vec,
+        ToFields,
+    },
+};
 
 #[derive(Clone, Copy, Debug)]
 #[repr(u8)]
@@ -30,9 +55,29 @@ enum Inputs<const L: usize> {
     BlockHeader,
     RewardsRate,
     QueryResult,
+    QueryCommitment,
+    // Number of decimals the query result is scaled by
+    Decimals,
+    // Query result divided by 10^decimals
+    ScaledQueryResult,
+    // Remainder of the division of the query result by 10^decimals
+    ScaledQueryRemainder,
+    // Digest of the smart contract address, to link proofs about the same contract
+    ContractDigest,
+    // Poseidon hash of the ordered storage slots read by the query, followed by
+    // the commitment to the migrations of the mapping if it migrated
+    SlotsCommitment,
+    // Mode employed to aggregate the query result over the blocks
+    AggregationMode,
+    // Upper bound on the rewards rate enforced by the storage leaves
+    MaxRewardsRate,
+    // Threshold the balances are compared to, or minimum balance of the flat rewards
+    Threshold,
+    // Mode of the storage leaves, see `leaf_mode_id`
+    LeafMode,
 }
 impl<const L: usize> Inputs<L> {
-    const SIZES: [usize; 12] = [
+    const SIZES: [usize; 22] = [
         // Block number
         1,
         // Range
@@ -57,6 +102,26 @@ impl<const L: usize> Inputs<L> {
         PACKED_U256_LEN,
         // reward rate - uint256
         PACKED_U256_LEN,
+        // Query commitment - keccak hash
+        OutputHash::LEN,
+        // Decimals
+        1,
+        // Scaled result - uint256
+        PACKED_U256_LEN,
+        // Remainder of the scaled result - uint256
+        PACKED_U256_LEN,
+        // Contract digest - curve point
+        CURVE_TARGET_LEN,
+        // Slots commitment - Poseidon hash
+        NUM_HASH_OUT_ELTS,
+        // Aggregation mode
+        1,
+        // Max reward rate - uint256
+        PACKED_U256_LEN,
+        // Threshold - uint256
+        PACKED_U256_LEN,
+        // Leaf mode
+        1,
     ];
 
     const fn total_len() -> usize {
@@ -72,6 +137,16 @@ impl<const L: usize> Inputs<L> {
             + Self::SIZES[9]
             + Self::SIZES[10]
             + Self::SIZES[11]
+            + Self::SIZES[12]
+            + Self::SIZES[13]
+            + Self::SIZES[14]
+            + Self::SIZES[15]
+            + Self::SIZES[16]
+            + Self::SIZES[17]
+            + Self::SIZES[18]
+            + Self::SIZES[19]
+            + Self::SIZES[20]
+            + Self::SIZES[21]
     }
 
     fn range(&self) -> std::ops::Range<usize> {
@@ -85,6 +160,24 @@ impl<const L: usize> Inputs<L> {
     }
 }
 
+/// Compute at runtime the number of public inputs of the revelation proof for
+/// `l` revealed items and `cols` query result columns, each result being a
+/// uint256. It's the non-const counterpart of `RevelationPublicInputs::total_len`,
+/// which is always computed for a single result column.
+pub fn revelation_io_len(l: usize, cols: usize) -> usize {
+    assert!(cols > 0, "at least one query result column is expected");
+    // `Inputs::<0>` accounts for no revealed item and a single result column
+    Inputs::<0>::total_len() + l + (cols - 1) * PACKED_U256_LEN
+}
+
+/// Get the query result from the public inputs of a revelation proof for `l`
+/// revealed items, where `l` is known only at runtime
+pub(crate) fn query_results_at_runtime(inputs: &[GoldilocksField], l: usize) -> U256 {
+    // `Inputs::<0>` has no revealed items, which precede the query result
+    let range = Inputs::<0>::QueryResult.range();
+    convert_u32_fields_to_u256(&inputs[range.start + l..range.end + l])
+}
+
 #[derive(Clone)]
 pub struct RevelationPublicInputs<'input, T: Clone, const L: usize> {
     pub inputs: &'input [T],
@@ -97,6 +190,23 @@ impl<'a, T: Clone + Copy, const L: usize> From<&'a [T]> for RevelationPublicInpu
     }
 }
 
+/// Public inputs of a revelation proof owned by the caller, as returned by
+/// `Parameters::verify_and_decode`, which are accessed through the
+/// `RevelationPublicInputs` view borrowing them
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OwnedRevelationPublicInputs<const L: usize>(pub(crate) Vec<GoldilocksField>);
+
+impl<const L: usize> OwnedRevelationPublicInputs<L> {
+    /// Returns the `RevelationPublicInputs` view over the public inputs
+    pub fn view(&self) -> RevelationPublicInputs<'_, GoldilocksField, L> {
+        RevelationPublicInputs::from(self.0.as_slice())
+    }
+
+    pub fn as_slice(&self) -> &[GoldilocksField] {
+        &self.0
+    }
+}
+
 impl<'a, T: Clone + Copy, const L: usize> RevelationPublicInputs<'a, T, L> {
     fn block_number_raw(&self) -> &[T] {
         &self.inputs[Inputs::<L>::BlockNumber.range()]
@@ -131,6 +241,36 @@ impl<'a, T: Clone + Copy, const L: usize> RevelationPublicInputs<'a, T, L> {
     fn query_rewards_rate_raw(&self) -> &[T] {
         &self.inputs[Inputs::<L>::RewardsRate.range()]
     }
+    fn query_commitment_raw(&self) -> &[T] {
+        &self.inputs[Inputs::<L>::QueryCommitment.range()]
+    }
+    fn decimals_raw(&self) -> &[T] {
+        &self.inputs[Inputs::<L>::Decimals.range()]
+    }
+    fn scaled_query_results_raw(&self) -> &[T] {
+        &self.inputs[Inputs::<L>::ScaledQueryResult.range()]
+    }
+    fn scaled_query_remainder_raw(&self) -> &[T] {
+        &self.inputs[Inputs::<L>::ScaledQueryRemainder.range()]
+    }
+    fn contract_digest_raw(&self) -> ([T; 5], [T; 5], T) {
+        convert_slice_to_curve_point(&self.inputs[Inputs::<L>::ContractDigest.range()])
+    }
+    fn slots_commitment_raw(&self) -> &[T] {
+        &self.inputs[Inputs::<L>::SlotsCommitment.range()]
+    }
+    fn aggregation_mode_raw(&self) -> &[T] {
+        &self.inputs[Inputs::<L>::AggregationMode.range()]
+    }
+    fn max_rewards_rate_raw(&self) -> &[T] {
+        &self.inputs[Inputs::<L>::MaxRewardsRate.range()]
+    }
+    fn threshold_raw(&self) -> &[T] {
+        &self.inputs[Inputs::<L>::Threshold.range()]
+    }
+    fn leaf_mode_raw(&self) -> &[T] {
+        &self.inputs[Inputs::<L>::LeafMode.range()]
+    }
     pub const fn total_len() -> usize {
         Inputs::<L>::total_len()
     }
@@ -152,6 +292,24 @@ impl<'a, const L: usize> RevelationPublicInputs<'a, Target, L> {
         lpn_latest_block: OutputHash,
         query_result: UInt256Target,
         rewards_rate: UInt256Target,
+        // keccak commitment to the query parameters
+        query_commitment: &OutputHash,
+        // the query result scaled by 10^decimals, as quotient and remainder
+        decimals: Target,
+        scaled_query_result: &UInt256Target,
+        scaled_query_remainder: &UInt256Target,
+        // digest of the smart contract address, i.e. `map_to_curve_point(contract_address)`
+        contract_digest: CurveTarget,
+        // Poseidon hash of the ordered storage slots read by the query
+        slots_commitment: &HashOutTarget,
+        // mode employed to aggregate the query result, see `AggregationKind`
+        aggregation_mode: Target,
+        // upper bound on the rewards rate, the maximum uint256 if unbounded
+        max_rewards_rate: &UInt256Target,
+        // threshold or minimum balance of the storage leaves, zero for proportional rewards
+        threshold: &UInt256Target,
+        // mode of the storage leaves, see `leaf_mode_id`
+        leaf_mode: Target,
     ) {
         b.register_public_input(query_block_number);
         b.register_public_input(query_range);
@@ -167,6 +325,16 @@ impl<'a, const L: usize> RevelationPublicInputs<'a, Target, L> {
         b.register_public_inputs(&lpn_latest_block.to_targets().arr);
         b.register_public_input_u256(&rewards_rate);
         b.register_public_input_u256(&query_result);
+        query_commitment.register_as_public_input(b);
+        b.register_public_input(decimals);
+        b.register_public_input_u256(scaled_query_result);
+        b.register_public_input_u256(scaled_query_remainder);
+        b.register_curve_public_input(contract_digest);
+        b.register_public_inputs(&slots_commitment.elements);
+        b.register_public_input(aggregation_mode);
+        b.register_public_input_u256(max_rewards_rate);
+        b.register_public_input_u256(threshold);
+        b.register_public_input(leaf_mode);
     }
 
     fn block_number(&self) -> Target {
@@ -207,14 +375,26 @@ impl<'a, const L: usize> RevelationPublicInputs<'a, Target, L> {
         self.mapping_slot_length_raw()[0]
     }
 
-    fn query_results(&self) -> &[Target] {
+    pub(crate) fn query_results(&self) -> &[Target] {
         self.query_results_raw()
     }
+
+    pub(crate) fn query_commitment(&self) -> &[Target] {
+        self.query_commitment_raw()
+    }
+
+    pub(crate) fn aggregation_mode(&self) -> Target {
+        self.aggregation_mode_raw()[0]
+    }
     fn query_rewards_rate(&self) -> &[Target] {
         self.query_rewards_rate_raw()
     }
 
-    fn block_header(&self) -> OutputHash {
+    pub(crate) fn contract_digest(&self) -> CurveTarget {
+        convert_point_to_curve_target(self.contract_digest_raw())
+    }
+
+    pub(crate) fn block_header(&self) -> OutputHash {
         OutputHash::from_array(
             self.block_header_raw()
                 .iter()
@@ -270,13 +450,326 @@ impl<'a, const L: usize> RevelationPublicInputs<'a, GoldilocksField, L> {
     pub(crate) fn block_header(&self) -> &[GoldilocksField] {
         self.block_header_raw()
     }
+
+    pub(crate) fn query_commitment(&self) -> Vec<u8> {
+        convert_u32_fields_to_u8_vec(self.query_commitment_raw())
+    }
+
+    pub(crate) fn decimals(&self) -> GoldilocksField {
+        self.decimals_raw()[0]
+    }
+
+    pub(crate) fn scaled_query_results(&self) -> U256 {
+        convert_u32_fields_to_u256(self.scaled_query_results_raw())
+    }
+
+    pub(crate) fn scaled_query_remainder(&self) -> U256 {
+        convert_u32_fields_to_u256(self.scaled_query_remainder_raw())
+    }
+
+    /// Digest of the smart contract address, equal for all the proofs about the
+    /// same contract, so that they can be linked without the full address
+    pub fn contract_digest(&self) -> WeierstrassPoint {
+        let (x, y, is_inf) = self.contract_digest_raw();
+        WeierstrassPoint {
+            x: QuinticExtension::<GoldilocksField>::from_basefield_array(x),
+            y: QuinticExtension::<GoldilocksField>::from_basefield_array(y),
+            is_inf: is_inf.is_nonzero(),
+        }
+    }
+
+    /// Commitment to the ordered storage slots read by the query, i.e. the mapping
+    /// slot and the slot of the length variable
+    pub fn slots_commitment(&self) -> HashOut<GoldilocksField> {
+        HashOut::from_partial(self.slots_commitment_raw())
+    }
+
+    /// Mode employed to aggregate the query result over the blocks of the range
+    pub fn aggregation_mode(&self) -> Result<AggregationKind> {
+        AggregationKind::from_field(self.aggregation_mode_raw()[0])
+    }
+
+    /// Upper bound on the rewards rate enforced by the storage leaves, which is
+    /// the maximum uint256 if the rate is unbounded
+    pub fn max_rewards_rate(&self) -> U256 {
+        convert_u32_fields_to_u256(self.max_rewards_rate_raw())
+    }
+
+    /// Threshold the balances are compared to, or minimum balance of the flat
+    /// rewards, which is zero for proportional rewards
+    pub fn threshold(&self) -> U256 {
+        convert_u32_fields_to_u256(self.threshold_raw())
+    }
+
+    /// Identifier of the mode of the storage leaves computing the query result,
+    /// see `leaf_mode_id`
+    pub fn leaf_mode(&self) -> u8 {
+        self.leaf_mode_raw()[0].to_canonical_u64() as u8
+    }
+
+    /// Check that the proof answers a query over exactly the `[min, max]` range
+    /// of blocks requested by the verifier
+    pub fn assert_query_range(&self, min: u32, max: u32) -> Result<()> {
+        let (found_min, found_max) = (
+            self.min_block_number().to_canonical_u64(),
+            self.max_block_number().to_canonical_u64(),
+        );
+        ensure!(
+            (found_min, found_max) == (min as u64, max as u64),
+            "proof is for query range [{found_min}, {found_max}], expected [{min}, {max}]"
+        );
+        Ok(())
+    }
+}
+
+/// Compute natively the digest of `contract_address` exposed by the revelation
+/// proofs, i.e. `map_to_curve_point(contract_address)` over the packed address
+pub fn contract_digest(contract_address: &Address) -> WeierstrassPoint {
+    map_to_curve_point(&address_to_packed_fields::<GoldilocksField>(
+        contract_address,
+    ))
+    .to_weierstrass()
+}
+
+/// Compute natively the commitment to the ordered storage `slots` read by a query
+/// exposed by the revelation proofs, i.e. the Poseidon hash of the slots
+pub fn slots_commitment(slots: &[u32]) -> HashOut<GoldilocksField> {
+    remapped_slots_commitment(slots, HashOut::ZERO)
+}
+
+/// Same as `slots_commitment`, for a mapping which migrated to other slots:
+/// the hashed slots are followed by `slot_remaps_commitment`, the commitment
+/// to the migrations computed by `slot_remaps_commitment`, unless it is the
+/// zero hash, i.e. the mapping never migrated.
+pub fn remapped_slots_commitment(
+    slots: &[u32],
+    slot_remaps_commitment: HashOut<GoldilocksField>,
+) -> HashOut<GoldilocksField> {
+    let remaps = if slot_remaps_commitment == HashOut::ZERO {
+        &[][..]
+    } else {
+        &slot_remaps_commitment.elements[..]
+    };
+    hash_n_to_hash_no_pad::<GoldilocksField, PoseidonPermutation<_>>(
+        &slots
+            .iter()
+            .map(|slot| GoldilocksField::from_canonical_u32(*slot))
+            .chain(remaps.iter().copied())
+            .collect::<Vec<_>>(),
+    )
+}
+
+/// Fluent builder of the public inputs of a revelation proof, employed to
+/// construct test vectors. All the fields must be set before calling `build`,
+/// apart from the aggregation mode which defaults to a sum, the bound on the
+/// rewards rate which defaults to the maximum uint256, and the leaf mode and
+/// threshold which default to proportional rewards.
+#[derive(Clone, Debug, Default)]
+pub struct RevelationPublicInputsBuilder<const L: usize> {
+    block_number: Option<GoldilocksField>,
+    range: Option<GoldilocksField>,
+    min_block_number: Option<GoldilocksField>,
+    max_block_number: Option<GoldilocksField>,
+    smart_contract_address: Option<Address>,
+    user_address: Option<Address>,
+    mapping_slot: Option<GoldilocksField>,
+    mapping_slot_length: Option<GoldilocksField>,
+    block_header: Option<HashOutput>,
+    rewards_rate: Option<U256>,
+    query_results: Option<U256>,
+    query_commitment: Option<HashOutput>,
+    decimals: Option<u8>,
+    aggregation_mode: Option<AggregationKind>,
+    max_rewards_rate: Option<U256>,
+    threshold: Option<U256>,
+    leaf_mode: Option<u8>,
+}
+
+impl<const L: usize> RevelationPublicInputsBuilder<L> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn block_number(mut self, block_number: GoldilocksField) -> Self {
+        self.block_number = Some(block_number);
+        self
+    }
+    pub fn range(mut self, range: GoldilocksField) -> Self {
+        self.range = Some(range);
+        self
+    }
+    pub fn min_block_number(mut self, min_block_number: GoldilocksField) -> Self {
+        self.min_block_number = Some(min_block_number);
+        self
+    }
+    pub fn max_block_number(mut self, max_block_number: GoldilocksField) -> Self {
+        self.max_block_number = Some(max_block_number);
+        self
+    }
+    pub fn smart_contract_address(mut self, address: Address) -> Self {
+        self.smart_contract_address = Some(address);
+        self
+    }
+    pub fn user_address(mut self, address: Address) -> Self {
+        self.user_address = Some(address);
+        self
+    }
+    pub fn mapping_slot(mut self, mapping_slot: GoldilocksField) -> Self {
+        self.mapping_slot = Some(mapping_slot);
+        self
+    }
+    pub fn mapping_slot_length(mut self, mapping_slot_length: GoldilocksField) -> Self {
+        self.mapping_slot_length = Some(mapping_slot_length);
+        self
+    }
+    pub fn block_header(mut self, block_header: HashOutput) -> Self {
+        self.block_header = Some(block_header);
+        self
+    }
+    pub fn rewards_rate(mut self, rewards_rate: U256) -> Self {
+        self.rewards_rate = Some(rewards_rate);
+        self
+    }
+    pub fn query_results(mut self, query_results: U256) -> Self {
+        self.query_results = Some(query_results);
+        self
+    }
+    pub fn query_commitment(mut self, query_commitment: HashOutput) -> Self {
+        self.query_commitment = Some(query_commitment);
+        self
+    }
+    pub fn decimals(mut self, decimals: u8) -> Self {
+        self.decimals = Some(decimals);
+        self
+    }
+    pub fn aggregation_mode(mut self, aggregation_mode: AggregationKind) -> Self {
+        self.aggregation_mode = Some(aggregation_mode);
+        self
+    }
+    pub fn max_rewards_rate(mut self, max_rewards_rate: U256) -> Self {
+        self.max_rewards_rate = Some(max_rewards_rate);
+        self
+    }
+    pub fn threshold(mut self, threshold: U256) -> Self {
+        self.threshold = Some(threshold);
+        self
+    }
+    pub fn leaf_mode(mut self, leaf_mode: u8) -> Self {
+        self.leaf_mode = Some(leaf_mode);
+        self
+    }
+
+    /// Build the public inputs, checking that all the fields are set and that the
+    /// queried range `[min_block_number, max_block_number]` ends at most at
+    /// `block_number`. The scaled query results are computed from the query
+    /// results and the decimals.
+    pub fn build(
+        &self,
+    ) -> Result<[GoldilocksField; RevelationPublicInputs::<'static, GoldilocksField, L>::total_len()]>
+    where
+        [(); RevelationPublicInputs::<'static, GoldilocksField, L>::total_len()]:,
+    {
+        fn field<T: Clone>(value: &Option<T>, name: &str) -> Result<T> {
+            value
+                .clone()
+                .ok_or(anyhow!("{name} not set in revelation public inputs"))
+        }
+        let block_number = field(&self.block_number, "block number")?;
+        let min_block_number = field(&self.min_block_number, "min block number")?;
+        let max_block_number = field(&self.max_block_number, "max block number")?;
+        ensure!(
+            min_block_number.to_canonical_u64() <= max_block_number.to_canonical_u64(),
+            "min block number must not be greater than max block number"
+        );
+        ensure!(
+            max_block_number.to_canonical_u64() <= block_number.to_canonical_u64(),
+            "max block number must not be greater than block number"
+        );
+
+        let mut inputs = vec![
+            block_number,
+            field(&self.range, "range")?,
+            min_block_number,
+            max_block_number,
+        ];
+        let smart_contract_address = field(&self.smart_contract_address, "smart contract address")?;
+        inputs.extend(address_to_packed_fields::<GoldilocksField>(
+            &smart_contract_address,
+        ));
+        inputs.extend(address_to_packed_fields::<GoldilocksField>(&field(
+            &self.user_address,
+            "user address",
+        )?));
+        let mapping_slot = field(&self.mapping_slot, "mapping slot")?;
+        let mapping_slot_length = field(&self.mapping_slot_length, "mapping slot length")?;
+        inputs.push(mapping_slot);
+        inputs.push(mapping_slot_length);
+        inputs.extend([GoldilocksField::ZERO; L]);
+        inputs.extend(convert_u8_slice_to_u32_fields::<GoldilocksField>(&field(
+            &self.block_header,
+            "block header",
+        )?));
+        inputs.extend(field(&self.rewards_rate, "rewards rate")?.to_fields());
+        let query_results = field(&self.query_results, "query results")?;
+        inputs.extend(query_results.to_fields());
+        inputs.extend(convert_u8_slice_to_u32_fields::<GoldilocksField>(&field(
+            &self.query_commitment,
+            "query commitment",
+        )?));
+        let decimals = field(&self.decimals, "decimals")?;
+        ensure!(
+            decimals <= MAX_DECIMALS,
+            "decimals can't be greater than {MAX_DECIMALS}"
+        );
+        let (scaled_query_results, scaled_query_remainder) =
+            query_results.div_mod(U256::exp10(decimals as usize));
+        inputs.push(GoldilocksField::from_canonical_u8(decimals));
+        inputs.extend(scaled_query_results.to_fields());
+        inputs.extend(scaled_query_remainder.to_fields());
+        let digest = contract_digest(&smart_contract_address);
+        inputs.extend(digest.x.0);
+        inputs.extend(digest.y.0);
+        inputs.push(GoldilocksField::from_bool(digest.is_inf));
+        inputs.extend(
+            slots_commitment(
+                &[mapping_slot, mapping_slot_length].map(|slot| slot.to_canonical_u64() as u32),
+            )
+            .elements,
+        );
+        inputs.push(self.aggregation_mode.unwrap_or_default().to_field());
+        let rewards_rate = field(&self.rewards_rate, "rewards rate")?;
+        let max_rewards_rate = self.max_rewards_rate.unwrap_or(U256::MAX);
+        let leaf_mode = self.leaf_mode.unwrap_or_default();
+        // the bound only applies to proportional rewards
+        ensure!(
+            leaf_mode != 0 || rewards_rate <= max_rewards_rate,
+            "rewards rate must not be greater than its bound"
+        );
+        inputs.extend(max_rewards_rate.to_fields());
+        inputs.extend(self.threshold.unwrap_or_default().to_fields());
+        inputs.push(GoldilocksField::from_canonical_u8(leaf_mode));
+
+        Ok(inputs.try_into().unwrap())
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::RevelationPublicInputs as QueryERC20PI;
-    use crate::query2::revelation::RevelationPublicInputs as Query2PI;
-    use plonky2::iop::target::Target;
+    use super::{
+        contract_digest, remapped_slots_commitment, revelation_io_len, slots_commitment,
+        AggregationKind, RevelationPublicInputs as QueryERC20PI, RevelationPublicInputsBuilder,
+        MAX_DECIMALS,
+    };
+    use crate::query_erc20::slot_remaps_commitment;
+    use crate::{query2::revelation::RevelationPublicInputs as Query2PI, types::HashOutput};
+    use ethers::prelude::{Address, U256};
+    use mrp2_utils::types::{address_to_packed_fields, PACKED_U256_LEN};
+    use plonky2::{
+        field::{goldilocks_field::GoldilocksField, types::Field},
+        iop::target::Target,
+    };
+    use rand::{thread_rng, Rng};
+
+    type F = GoldilocksField;
 
     #[test]
     fn test_same_pi_len_for_query2_and_query2_erc20() {
@@ -287,4 +780,172 @@ mod tests {
             QueryERC20PI::<Target, L>::total_len()
         );
     }
+
+    #[test]
+    fn test_revelation_io_len() {
+        const L: usize = 5;
+
+        assert_eq!(
+            revelation_io_len(L, 1),
+            QueryERC20PI::<Target, L>::total_len()
+        );
+        assert_eq!(
+            revelation_io_len(0, 1),
+            QueryERC20PI::<Target, 0>::total_len()
+        );
+        assert_eq!(
+            revelation_io_len(L, 3),
+            QueryERC20PI::<Target, L>::total_len() + 2 * PACKED_U256_LEN
+        );
+    }
+
+    #[test]
+    fn test_revelation_public_inputs_builder() {
+        const L: usize = 5;
+        let mut rng = thread_rng();
+        let contract = Address::random();
+        let user = Address::random();
+        let block_header = rng.gen::<HashOutput>();
+        let commitment = rng.gen::<HashOutput>();
+        let rewards_rate = U256::from(rng.gen::<u128>());
+        let results = U256::from(rng.gen::<u128>());
+        let builder = RevelationPublicInputsBuilder::<L>::new()
+            .block_number(F::from_canonical_u32(100))
+            .range(F::from_canonical_u32(10))
+            .min_block_number(F::from_canonical_u32(92))
+            .max_block_number(F::from_canonical_u32(98))
+            .smart_contract_address(contract)
+            .user_address(user)
+            .mapping_slot(F::from_canonical_u8(3))
+            .mapping_slot_length(F::from_canonical_u8(4))
+            .block_header(block_header)
+            .rewards_rate(rewards_rate)
+            .query_results(results)
+            .query_commitment(commitment)
+            .decimals(18);
+
+        let inputs = builder.build().unwrap();
+        let pi = QueryERC20PI::<_, L>::from(inputs.as_slice());
+        assert_eq!(pi.block_number(), F::from_canonical_u32(100));
+        assert_eq!(pi.range(), F::from_canonical_u32(10));
+        assert_eq!(pi.min_block_number(), F::from_canonical_u32(92));
+        assert_eq!(pi.max_block_number(), F::from_canonical_u32(98));
+        assert_eq!(
+            pi.smart_contract_address(),
+            address_to_packed_fields::<F>(&contract)
+        );
+        assert_eq!(pi.user_address(), address_to_packed_fields::<F>(&user));
+        assert_eq!(pi.mapping_slot(), F::from_canonical_u8(3));
+        assert_eq!(pi.mapping_slot_length(), F::from_canonical_u8(4));
+        assert_eq!(
+            mrp2_utils::utils::convert_u32_fields_to_u8_vec(pi.block_header()),
+            block_header
+        );
+        assert_eq!(pi.rewards_rate(), rewards_rate);
+        assert_eq!(pi.query_results(), results);
+        assert_eq!(pi.query_commitment(), commitment);
+        assert_eq!(pi.decimals(), F::from_canonical_u8(18));
+        assert_eq!(pi.scaled_query_results(), results / U256::exp10(18));
+        assert_eq!(pi.scaled_query_remainder(), results % U256::exp10(18));
+        assert_eq!(pi.contract_digest(), contract_digest(&contract));
+
+        // proofs about the same contract expose the same digest, regardless of
+        // the other query parameters, while different contracts don't
+        let same_contract = builder
+            .clone()
+            .user_address(Address::random())
+            .query_results(U256::from(rng.gen::<u128>()))
+            .build()
+            .unwrap();
+        let same_contract = QueryERC20PI::<_, L>::from(same_contract.as_slice());
+        assert_eq!(same_contract.contract_digest(), pi.contract_digest());
+        let other_contract = builder
+            .clone()
+            .smart_contract_address(Address::random())
+            .build()
+            .unwrap();
+        let other_contract = QueryERC20PI::<_, L>::from(other_contract.as_slice());
+        assert_ne!(other_contract.contract_digest(), pi.contract_digest());
+
+        // the slots commitment binds the ordered set of slots read by the query
+        assert_eq!(pi.slots_commitment(), slots_commitment(&[3, 4]));
+        assert_eq!(same_contract.slots_commitment(), pi.slots_commitment());
+        let other_slots = builder
+            .clone()
+            .mapping_slot(F::from_canonical_u8(5))
+            .build()
+            .unwrap();
+        let other_slots = QueryERC20PI::<_, L>::from(other_slots.as_slice());
+        assert_ne!(other_slots.slots_commitment(), pi.slots_commitment());
+        assert_ne!(slots_commitment(&[4, 3]), pi.slots_commitment());
+        // as well as the migrations of the mapping, if any
+        assert_eq!(
+            remapped_slots_commitment(&[3, 4], slot_remaps_commitment(&[]).unwrap()),
+            pi.slots_commitment()
+        );
+        assert_ne!(
+            remapped_slots_commitment(&[3, 4], slot_remaps_commitment(&[(10, 5)]).unwrap()),
+            pi.slots_commitment()
+        );
+
+        // the aggregation mode defaults to a sum
+        assert_eq!(pi.aggregation_mode().unwrap(), AggregationKind::Sum);
+        for mode in AggregationKind::ALL {
+            let inputs = builder.clone().aggregation_mode(mode).build().unwrap();
+            let pi = QueryERC20PI::<_, L>::from(inputs.as_slice());
+            assert_eq!(pi.aggregation_mode().unwrap(), mode);
+        }
+
+        // the rewards rate is unbounded by default
+        assert_eq!(pi.max_rewards_rate(), U256::MAX);
+        let inputs = builder
+            .clone()
+            .max_rewards_rate(rewards_rate)
+            .build()
+            .unwrap();
+        let bounded = QueryERC20PI::<_, L>::from(inputs.as_slice());
+        assert_eq!(bounded.max_rewards_rate(), rewards_rate);
+        assert!(builder
+            .clone()
+            .max_rewards_rate(rewards_rate - 1)
+            .build()
+            .is_err());
+
+        // the leaves compute proportional rewards by default
+        assert_eq!(pi.threshold(), U256::zero());
+        assert_eq!(pi.leaf_mode(), 0);
+        let threshold = U256::from(rng.gen::<u64>());
+        let inputs = builder
+            .clone()
+            .rewards_rate(U256::zero())
+            .threshold(threshold)
+            .leaf_mode(2)
+            .build()
+            .unwrap();
+        let threshold_pi = QueryERC20PI::<_, L>::from(inputs.as_slice());
+        assert_eq!(threshold_pi.threshold(), threshold);
+        assert_eq!(threshold_pi.leaf_mode(), 2);
+
+        // invalid block ranges and missing fields are rejected
+        assert!(builder
+            .clone()
+            .min_block_number(F::from_canonical_u32(99))
+            .build()
+            .is_err());
+        assert!(builder
+            .clone()
+            .max_block_number(F::from_canonical_u32(101))
+            .build()
+            .is_err());
+        assert!(builder.clone().decimals(MAX_DECIMALS + 1).build().is_err());
+
+        // the claimed query range must be exactly the requested one
+        assert!(pi.assert_query_range(92, 98).is_ok());
+        assert!(pi.assert_query_range(91, 98).is_err());
+        assert!(pi.assert_query_range(92, 99).is_err());
+        assert!(RevelationPublicInputsBuilder::<L>::new()
+            .block_number(F::ONE)
+            .build()
+            .is_err());
+    }
 }
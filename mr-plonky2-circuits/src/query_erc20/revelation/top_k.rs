@@ -0,0 +1,237 @@
+//! This circuit selects the top-K values among up to `L` candidates, proving
+//! that each returned value is greater or equal than any of the candidates
+//! which are not returned. The candidates not returned are accumulated in a
+//! digest, which is needed for completeness: a verifier can check that the
+//! returned values together with the discarded ones are exactly the matched set.
+
+use std::array;
+
+use ethers::types::U256;
+use itertools::Itertools;
+use mrp2_utils::u256::{CircuitBuilderU256, UInt256Target};
+use plonky2::{
+    field::types::Field,
+    iop::{
+        target::{BoolTarget, Target},
+        witness::{PartialWitness, WitnessWrite},
+    },
+    plonk::circuit_builder::CircuitBuilder,
+};
+use plonky2_ecgfp5::gadgets::curve::{CircuitBuilderEcGFp5, CurveTarget};
+
+use crate::{
+    api::{D, F},
+    group_hashing::CircuitBuilderGroupHashing,
+};
+
+/// Wires of the top-K circuit
+pub struct TopKWires<const K: usize> {
+    /// Index, among the candidates, of each returned value
+    indexes: [Target; K],
+    /// The K returned values, in the order of `indexes`
+    pub top: [UInt256Target; K],
+    /// The digest of the set of candidates not returned
+    pub discarded_digest: CurveTarget,
+}
+
+/// Circuit returning the K largest values among `L` candidates
+#[derive(Clone, Debug)]
+pub struct TopKCircuit<const K: usize, const L: usize> {
+    /// Index, among the candidates, of each returned value
+    indexes: [usize; K],
+}
+
+impl<const K: usize, const L: usize> TopKCircuit<K, L> {
+    /// Select the K largest values among the candidates, sorted in descending
+    /// order; ties are broken by returning first the candidate with lowest index.
+    pub fn new(candidates: &[U256; L]) -> Self {
+        assert!(K <= L, "cannot select {K} values out of {L} candidates");
+        let indexes = (0..L)
+            .sorted_by(|&i, &j| candidates[j].cmp(&candidates[i]).then(i.cmp(&j)))
+            .take(K)
+            .collect_vec();
+
+        Self {
+            indexes: indexes.try_into().unwrap(),
+        }
+    }
+
+    /// Build the circuit selecting the top-K values among `candidates`. The
+    /// returned values are the candidates found at the indexes provided as
+    /// witness, which must be distinct; each returned value is checked to be
+    /// greater or equal than all the candidates not returned.
+    pub fn build(b: &mut CircuitBuilder<F, D>, candidates: &[UInt256Target; L]) -> TopKWires<K> {
+        let indexes: [Target; K] = b.add_virtual_target_arr();
+        let t = b._true();
+        let zero_u256 = b.zero_u256();
+
+        // is_match[i][j] is true iff the i-th returned value is the j-th candidate
+        let is_match: [[BoolTarget; L]; K] = array::from_fn(|i| {
+            array::from_fn(|j| {
+                let j = b.constant(F::from_canonical_usize(j));
+                b.is_equal(indexes[i], j)
+            })
+        });
+
+        // Each index must match exactly one candidate, i.e. it must be smaller than L
+        let top = array::from_fn(|i| {
+            let num_matches = b.add_many(is_match[i].iter().map(|m| m.target));
+            b.connect(num_matches, t.target);
+            is_match[i]
+                .iter()
+                .zip(candidates)
+                .fold(zero_u256.clone(), |value, (m, candidate)| {
+                    b.select_u256(*m, candidate, &value)
+                })
+        });
+
+        // Each candidate can be returned at most once, which ensures the indexes
+        // are distinct
+        let is_selected: [BoolTarget; L] = array::from_fn(|j| {
+            let num_selections = b.add_many(is_match.iter().map(|m| m[j].target));
+            let is_selected = BoolTarget::new_unsafe(num_selections);
+            b.assert_bool(is_selected);
+            is_selected
+        });
+
+        // Each returned value must be greater or equal than the discarded candidates
+        for value in top.iter() {
+            for (candidate, selected) in candidates.iter().zip(is_selected) {
+                let is_ge = b.is_greater_or_equal_u256(value, candidate);
+                let is_valid = b.or(selected, is_ge);
+                b.connect(is_valid.target, t.target);
+            }
+        }
+
+        // Accumulate the discarded candidates in the digest
+        let zero_point = b.curve_zero();
+        let discarded = candidates
+            .iter()
+            .zip(is_selected)
+            .map(|(candidate, selected)| {
+                let limbs: Vec<Target> = candidate.into();
+                let point = b.map_to_curve_point(&limbs);
+                b.curve_select(selected, zero_point, point)
+            })
+            .collect_vec();
+        let discarded_digest = b.add_curve_point(&discarded);
+
+        TopKWires {
+            indexes,
+            top,
+            discarded_digest,
+        }
+    }
+
+    /// Assign the wires.
+    pub fn assign(&self, pw: &mut PartialWitness<F>, wires: &TopKWires<K>) {
+        wires
+            .indexes
+            .iter()
+            .zip(self.indexes)
+            .for_each(|(t, i)| pw.set_target(*t, F::from_canonical_usize(i)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        api::C, group_hashing::prove_multiset_equality, utils::convert_u32_fields_to_u256,
+    };
+    use mrp2_test_utils::circuit::{run_circuit, UserCircuit};
+    use mrp2_utils::{
+        u256::{WitnessWriteU256, NUM_LIMBS},
+        utils::ToFields,
+    };
+    use plonky2::field::extension::{quintic::QuinticExtension, FieldExtension};
+    use plonky2_ecgfp5::curve::curve::WeierstrassPoint;
+
+    const K: usize = 3;
+    const L: usize = 5;
+
+    #[derive(Clone, Debug)]
+    struct TestTopKCircuit {
+        candidates: [U256; L],
+        c: TopKCircuit<K, L>,
+    }
+
+    impl UserCircuit<F, D> for TestTopKCircuit {
+        type Wires = ([UInt256Target; L], TopKWires<K>);
+
+        fn build(b: &mut CircuitBuilder<F, D>) -> Self::Wires {
+            let candidates = array::from_fn(|_| b.add_virtual_u256());
+            let wires = TopKCircuit::<K, L>::build(b, &candidates);
+            wires
+                .top
+                .iter()
+                .for_each(|v| b.register_public_input_u256(v));
+            b.register_curve_public_input(wires.discarded_digest);
+
+            (candidates, wires)
+        }
+
+        fn prove(&self, pw: &mut PartialWitness<F>, wires: &Self::Wires) {
+            wires
+                .0
+                .iter()
+                .zip(self.candidates)
+                .for_each(|(t, v)| pw.set_u256_target(t, v));
+            self.c.assign(pw, &wires.1);
+        }
+    }
+
+    #[test]
+    fn test_top_k_circuit() {
+        let candidates = [10, 50, 20, 40, 30].map(U256::from);
+
+        let proof = run_circuit::<F, D, C, _>(TestTopKCircuit {
+            candidates,
+            c: TopKCircuit::new(&candidates),
+        });
+
+        // the 3 largest values are returned in descending order
+        let top = proof.public_inputs[..K * NUM_LIMBS]
+            .chunks(NUM_LIMBS)
+            .map(convert_u32_fields_to_u256)
+            .collect_vec();
+        assert_eq!(top, [50, 40, 30].map(U256::from));
+
+        // the digest accumulates the discarded values
+        let digest = &proof.public_inputs[K * NUM_LIMBS..];
+        let found_digest = WeierstrassPoint {
+            x: QuinticExtension::<F>::from_basefield_array(array::from_fn(|i| digest[i])),
+            y: QuinticExtension::<F>::from_basefield_array(array::from_fn(|i| digest[5 + i])),
+            is_inf: digest[10].is_nonzero(),
+        };
+        let discarded = [10, 20].map(|v| U256::from(v).to_fields::<F>());
+        assert!(prove_multiset_equality(&discarded, found_digest));
+
+        // returning a discarded value in place of a larger one fails
+        let res = std::panic::catch_unwind(|| {
+            run_circuit::<F, D, C, _>(TestTopKCircuit {
+                candidates,
+                c: TopKCircuit { indexes: [1, 3, 2] },
+            })
+        });
+        assert!(res.is_err(), "wrong top-K values not detected");
+
+        // returning the same candidate twice fails
+        let res = std::panic::catch_unwind(|| {
+            run_circuit::<F, D, C, _>(TestTopKCircuit {
+                candidates,
+                c: TopKCircuit { indexes: [1, 1, 3] },
+            })
+        });
+        assert!(res.is_err(), "duplicated top-K index not detected");
+
+        // returning a candidate out of range fails
+        let res = std::panic::catch_unwind(|| {
+            run_circuit::<F, D, C, _>(TestTopKCircuit {
+                candidates,
+                c: TopKCircuit { indexes: [1, 3, L] },
+            })
+        });
+        assert!(res.is_err(), "out of range top-K index not detected");
+    }
+}
@@ -0,0 +1,293 @@
+//! Resolution of the storage slot of the queried mapping at a given block, for
+//! contracts which migrated the mapping to a new slot during an upgrade. The
+//! migrations are committed with a Poseidon hash over a fixed number of
+//! `(block, slot)` pairs; the state circuit resolves the slot in use at the
+//! block of the proof from the committed migrations, so that blocks on either
+//! side of a migration expose the same original slot and commitment, and can
+//! then be aggregated together.
+
+use anyhow::{ensure, Result};
+use plonky2::{
+    field::types::Field,
+    hash::{
+        hash_types::{HashOut, HashOutTarget},
+        hashing::hash_n_to_hash_no_pad,
+        poseidon::{PoseidonHash, PoseidonPermutation},
+    },
+    iop::{
+        target::Target,
+        witness::{PartialWitness, WitnessWrite},
+    },
+    plonk::circuit_builder::CircuitBuilder,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::{D, F},
+    utils::less_than_or_equal_to,
+};
+
+/// Maximum number of migrations of the mapping to a new slot supported by the
+/// state circuit
+pub const MAX_SLOT_REMAPS: usize = 4;
+
+/// Number of bits of the block numbers compared to resolve the slot in use
+const BLOCK_NUMBER_BITS: usize = 32;
+
+/// Return the mapping slot in use at `block`, given the `remaps` of the mapping
+/// as `(block, slot)` pairs, each one specifying that the mapping is stored at
+/// `slot` starting from `block` included. It returns `None` if `block` precedes
+/// all the remaps, i.e. the original slot of the mapping is still in use.
+pub fn slot_at_block(block: u32, remaps: &[(u32, u32)]) -> Option<u32> {
+    remaps
+        .iter()
+        .filter(|(remap_block, _)| *remap_block <= block)
+        .max_by_key(|(remap_block, _)| *remap_block)
+        .map(|(_, slot)| *slot)
+}
+
+/// Sort the `remaps` by block and pad them to `MAX_SLOT_REMAPS` entries, as
+/// they are committed. The padding entries start at the last block, and keep
+/// the last slot, so that they never change the slot in use. It fails if there
+/// are too many remaps or several remaps at the same block.
+fn padded_remaps(remaps: &[(u32, u32)]) -> Result<Vec<(u32, u32)>> {
+    ensure!(
+        remaps.len() <= MAX_SLOT_REMAPS,
+        "at most {MAX_SLOT_REMAPS} migrations of the mapping slot are supported, found {}",
+        remaps.len()
+    );
+    let mut padded = remaps.to_vec();
+    padded.sort_by_key(|(block, _)| *block);
+    ensure!(
+        padded.windows(2).all(|w| w[0].0 != w[1].0),
+        "several migrations of the mapping slot at the same block"
+    );
+    let last_slot = padded.last().map(|(_, slot)| *slot).unwrap_or_default();
+    padded.resize(MAX_SLOT_REMAPS, (u32::MAX, last_slot));
+    Ok(padded)
+}
+
+/// Compute the commitment to the migrations of the mapping slot, i.e. the
+/// Poseidon hash of the `(block, slot)` pairs sorted by block and padded to
+/// `MAX_SLOT_REMAPS` entries, or the zero hash if the mapping never migrated.
+pub fn slot_remaps_commitment(remaps: &[(u32, u32)]) -> Result<HashOut<F>> {
+    if remaps.is_empty() {
+        return Ok(HashOut::ZERO);
+    }
+    let inputs = padded_remaps(remaps)?
+        .into_iter()
+        .flat_map(|(block, slot)| [block, slot].map(F::from_canonical_u32))
+        .collect::<Vec<_>>();
+    Ok(hash_n_to_hash_no_pad::<F, PoseidonPermutation<_>>(&inputs))
+}
+
+/// Wires of the resolution of the mapping slot
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SlotRemapsWires {
+    /// Committed `(block, slot)` pairs
+    remaps: [(Target, Target); MAX_SLOT_REMAPS],
+}
+
+/// Circuit resolving the mapping slot in use at a given block from the
+/// committed migrations of the mapping
+#[derive(Clone, Debug, Default)]
+pub struct SlotRemapsCircuit {
+    remaps: Vec<(u32, u32)>,
+}
+
+impl SlotRemapsCircuit {
+    /// Resolve the slot from the migrations `remaps`, which are committed with
+    /// `slot_remaps_commitment`. No migrations means the original slot is
+    /// always in use.
+    pub fn new(remaps: &[(u32, u32)]) -> Result<Self> {
+        let remaps = if remaps.is_empty() {
+            vec![]
+        } else {
+            padded_remaps(remaps)?
+        };
+        Ok(Self { remaps })
+    }
+
+    /// Build the resolution of the mapping slot in use at block `block_number`,
+    /// which is `mapping_slot` until the first migration committed in
+    /// `remaps_commitment`. If the commitment is the zero hash, the mapping
+    /// never migrated and `mapping_slot` is returned. The committed migrations
+    /// are sorted by block, so the last one preceding `block_number` is the one
+    /// in use.
+    pub fn build(
+        b: &mut CircuitBuilder<F, D>,
+        block_number: Target,
+        mapping_slot: Target,
+        remaps_commitment: HashOutTarget,
+    ) -> (SlotRemapsWires, Target) {
+        let remaps = [0; MAX_SLOT_REMAPS].map(|_| (b.add_virtual_target(), b.add_virtual_target()));
+
+        let zero = b.zero();
+        let mut is_uncommitted = b._true();
+        for e in remaps_commitment.elements {
+            let is_zero = b.is_equal(e, zero);
+            is_uncommitted = b.and(is_uncommitted, is_zero);
+        }
+        let enabled = b.not(is_uncommitted);
+
+        // enabled => Poseidon(remaps) == remaps_commitment
+        let inputs = remaps
+            .iter()
+            .flat_map(|(block, slot)| [*block, *slot])
+            .collect();
+        let commitment = b.hash_n_to_hash_no_pad::<PoseidonHash>(inputs);
+        commitment
+            .elements
+            .into_iter()
+            .zip(remaps_commitment.elements)
+            .for_each(|(h, c)| {
+                let diff = b.sub(h, c);
+                let diff = b.mul(diff, enabled.target);
+                b.assert_zero(diff);
+            });
+
+        let slot = remaps.iter().fold(mapping_slot, |slot, (block, new_slot)| {
+            let is_migrated = less_than_or_equal_to(b, *block, block_number, BLOCK_NUMBER_BITS);
+            let is_migrated = b.and(enabled, is_migrated);
+            b.select(is_migrated, *new_slot, slot)
+        });
+
+        (SlotRemapsWires { remaps }, slot)
+    }
+
+    /// Assign the wires.
+    pub fn assign(&self, pw: &mut PartialWitness<F>, wires: &SlotRemapsWires) {
+        // without migrations, the remaps are ignored by the circuit
+        let remaps = if self.remaps.is_empty() {
+            vec![(0, 0); MAX_SLOT_REMAPS]
+        } else {
+            self.remaps.clone()
+        };
+        wires
+            .remaps
+            .iter()
+            .zip(remaps)
+            .for_each(|((block_t, slot_t), (block, slot))| {
+                pw.set_target(*block_t, F::from_canonical_u32(block));
+                pw.set_target(*slot_t, F::from_canonical_u32(slot));
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::C;
+    use mrp2_test_utils::circuit::{run_circuit, UserCircuit};
+
+    const MAPPING_SLOT: u32 = 1;
+
+    #[derive(Clone, Debug)]
+    struct TestSlotRemapsCircuit {
+        block_number: u32,
+        remaps_commitment: HashOut<F>,
+        c: SlotRemapsCircuit,
+    }
+
+    impl UserCircuit<F, D> for TestSlotRemapsCircuit {
+        type Wires = (Target, HashOutTarget, SlotRemapsWires);
+
+        fn build(b: &mut CircuitBuilder<F, D>) -> Self::Wires {
+            let block_number = b.add_virtual_target();
+            let mapping_slot = b.constant(F::from_canonical_u32(MAPPING_SLOT));
+            let remaps_commitment = b.add_virtual_hash();
+            let (wires, slot) =
+                SlotRemapsCircuit::build(b, block_number, mapping_slot, remaps_commitment);
+            b.register_public_input(slot);
+
+            (block_number, remaps_commitment, wires)
+        }
+
+        fn prove(&self, pw: &mut PartialWitness<F>, wires: &Self::Wires) {
+            pw.set_target(wires.0, F::from_canonical_u32(self.block_number));
+            pw.set_hash_target(wires.1, self.remaps_commitment);
+            self.c.assign(pw, &wires.2);
+        }
+    }
+
+    fn prove_slot(block_number: u32, remaps: &[(u32, u32)]) -> F {
+        let proof = run_circuit::<F, D, C, _>(TestSlotRemapsCircuit {
+            block_number,
+            remaps_commitment: slot_remaps_commitment(remaps).unwrap(),
+            c: SlotRemapsCircuit::new(remaps).unwrap(),
+        });
+        proof.public_inputs[0]
+    }
+
+    #[test]
+    fn test_slot_at_block() {
+        let remaps = [(100, 3), (200, 7)];
+
+        assert_eq!(slot_at_block(0, &remaps), None);
+        assert_eq!(slot_at_block(99, &remaps), None);
+        assert_eq!(slot_at_block(100, &remaps), Some(3));
+        assert_eq!(slot_at_block(199, &remaps), Some(3));
+        assert_eq!(slot_at_block(200, &remaps), Some(7));
+        assert_eq!(slot_at_block(u32::MAX, &remaps), Some(7));
+        // the order of the remaps doesn't matter
+        assert_eq!(slot_at_block(150, &[(200, 7), (100, 3)]), Some(3));
+        // no remaps
+        assert_eq!(slot_at_block(150, &[]), None);
+    }
+
+    #[test]
+    fn test_slot_remaps_circuit() {
+        // the circuit resolves the same slot as `slot_at_block`
+        let remaps = [(200, 7), (100, 3)];
+        for block_number in [0, 99, 100, 199, 200, u32::MAX] {
+            let expected = slot_at_block(block_number, &remaps).unwrap_or(MAPPING_SLOT);
+            assert_eq!(
+                prove_slot(block_number, &remaps),
+                F::from_canonical_u32(expected),
+                "wrong slot resolved at block {block_number}"
+            );
+        }
+        // without migrations, the original slot is always in use
+        assert_eq!(prove_slot(150, &[]), F::from_canonical_u32(MAPPING_SLOT));
+
+        // the migrations must be the committed ones
+        let res = std::panic::catch_unwind(|| {
+            run_circuit::<F, D, C, _>(TestSlotRemapsCircuit {
+                block_number: 150,
+                remaps_commitment: slot_remaps_commitment(&remaps).unwrap(),
+                c: SlotRemapsCircuit::new(&[(100, 4), (200, 7)]).unwrap(),
+            })
+        });
+        assert!(res.is_err(), "uncommitted migrations accepted");
+        // committed migrations can't be ignored
+        let res = std::panic::catch_unwind(|| {
+            run_circuit::<F, D, C, _>(TestSlotRemapsCircuit {
+                block_number: 150,
+                remaps_commitment: slot_remaps_commitment(&remaps).unwrap(),
+                c: SlotRemapsCircuit::new(&[]).unwrap(),
+            })
+        });
+        assert!(res.is_err(), "committed migrations ignored");
+    }
+
+    #[test]
+    fn test_slot_remaps_commitment() {
+        assert_eq!(slot_remaps_commitment(&[]).unwrap(), HashOut::ZERO);
+        // the commitment doesn't depend on the order of the remaps
+        assert_eq!(
+            slot_remaps_commitment(&[(100, 3), (200, 7)]).unwrap(),
+            slot_remaps_commitment(&[(200, 7), (100, 3)]).unwrap(),
+        );
+        assert_ne!(
+            slot_remaps_commitment(&[(100, 3), (200, 7)]).unwrap(),
+            slot_remaps_commitment(&[(100, 7), (200, 3)]).unwrap(),
+        );
+        // too many remaps, or several remaps at the same block, are rejected
+        let too_many = (0..=MAX_SLOT_REMAPS as u32)
+            .map(|i| (i, i))
+            .collect::<Vec<_>>();
+        assert!(slot_remaps_commitment(&too_many).is_err());
+        assert!(SlotRemapsCircuit::new(&too_many).is_err());
+        assert!(slot_remaps_commitment(&[(100, 3), (100, 7)]).is_err());
+    }
+}
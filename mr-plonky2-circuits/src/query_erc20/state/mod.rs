@@ -1,14 +1,17 @@
 //! LPN State & Block DB provenance
 use std::iter;
 
-use ethers::types::Address;
+use ethers::types::{Address, U256};
 use itertools::Itertools;
 use mrp2_utils::serialization::{deserialize, serialize};
 use mrp2_utils::types::PackedSCAddress;
 use plonky2::{
-    field::{goldilocks_field::GoldilocksField, types::Field},
+    field::{
+        goldilocks_field::GoldilocksField,
+        types::{Field, PrimeField64},
+    },
     hash::{
-        hash_types::{HashOut, RichField},
+        hash_types::{HashOut, HashOutTarget, RichField},
         merkle_proofs::MerkleProofTarget,
         poseidon::PoseidonHash,
     },
@@ -33,12 +36,19 @@ use crate::{
     array::Array,
     keccak::{OutputHash, PACKED_HASH_LEN},
     merkle_tree::StateTreeWires,
-    query_erc20::storage::public_inputs::PublicInputs as StorageInputs,
+    query_erc20::{
+        rates::{rates_commitment, RateLeafCircuit, RateLeafWires, RATES_SCHEDULE_DEPTH},
+        slots::{slot_remaps_commitment, SlotRemapsCircuit, SlotRemapsWires},
+        storage::public_inputs::PublicInputs as StorageInputs,
+    },
     types::{HashOutput, PackedAddressTarget as PackedSCAddressTarget},
     utils::{Packer, ToFields},
 };
 
-use super::block::{BlockPublicInputs, BLOCK_CIRCUIT_SET_SIZE};
+use super::block::{
+    aggregation::{aggregation_mode_flags, AggregationKind},
+    BlockPublicInputs, BLOCK_CIRCUIT_SET_SIZE,
+};
 use anyhow::{bail, Result};
 
 #[cfg(test)]
@@ -49,7 +59,8 @@ pub(crate) mod tests;
 pub struct StateWires<const MAX_DEPTH: usize> {
     /// Smart contract address (unpacked)
     pub smart_contract_address: PackedSCAddressTarget,
-    /// Mapping of the storage slot
+    /// Mapping of the storage slot, which is the original one if the mapping
+    /// migrated to other slots
     pub mapping_slot: Target,
     /// Length of the storage slot
     pub length_slot: Target,
@@ -67,6 +78,22 @@ pub struct StateWires<const MAX_DEPTH: usize> {
     pub block_hash: OutputHash,
     /// The merkle root of the opening.
     pub state_tree: StateTreeWires<MAX_DEPTH>,
+    /// Aggregation mode of the query results along the block tree
+    pub aggregation_mode: Target,
+    #[serde(serialize_with = "serialize", deserialize_with = "deserialize")]
+    /// Commitment to the schedule of rewards rates, or the zero hash if the
+    /// rewards rate isn't bound to any schedule
+    pub rates_commitment: HashOutTarget,
+    /// First block number of the schedule of rewards rates
+    pub rates_first_block: Target,
+    /// Opening of the rewards rate of the block in the schedule
+    pub rates_opening: RateLeafWires<RATES_SCHEDULE_DEPTH>,
+    #[serde(serialize_with = "serialize", deserialize_with = "deserialize")]
+    /// Commitment to the migrations of the mapping to other slots, or the zero
+    /// hash if the mapping never migrated
+    pub slot_remaps_commitment: HashOutTarget,
+    /// Migrations of the mapping the slot of the block is resolved from
+    pub slot_remaps: SlotRemapsWires,
 }
 
 /// The provenance db circuit
@@ -89,6 +116,12 @@ pub struct StateWires<const MAX_DEPTH: usize> {
 /// - `T` Little-endian positions flags for the merkle opening path
 /// - `Y` Aggregated storage digest
 /// - `H` Block hash as stored in the leaf of the block db
+/// - `O` Aggregation mode of the query results
+/// - `K` Commitment to the schedule of rewards rates, zero if there is none
+/// - `K_B` First block number of the schedule of rewards rates
+/// - `Q` Opening of the rewards rate of `B` in the schedule
+/// - `K_M` Commitment to the migrations of the mapping slot, zero if there is none
+/// - `W` Migrations of the mapping slot committed in `K_M`
 ///
 /// # Public Inputs
 ///
@@ -103,13 +136,27 @@ pub struct StateWires<const MAX_DEPTH: usize> {
 /// - `S` Length of the slot
 /// - `V` Query result
 /// - `R` Rewards Rate
+/// - `R_MAX` Upper bound on the rewards rate
+/// - `D` Depth of the state tree opening
+/// - `O` Aggregation mode of the query results
+/// - `K` Commitment to the schedule of rewards rates
+/// - `K_B` First block number of the schedule of rewards rates
+/// - `K_M` Commitment to the migrations of the mapping slot
+/// - `T` Threshold or minimum balance of the storage leaves
+/// - `L` Mode of the storage leaves
 ///
 /// # Circuit
 ///
-/// 1. `state_leaf := Poseidon(A || M || S || C)`
+/// 1. `state_leaf := Poseidon(A || M_B || S || C)`, where `M_B` is the slot in
+///    use at block `B`, i.e. `M` if `K_M` is zero, otherwise the slot of the
+///    last migration of `W` preceding `B`, or `M` if there is none
 /// 2. Open the Merkle path `(P, T)` from `state_leaf` to `Z`
 /// 3. `C := Poseidon(B || H || Z)`
 /// 4. `R == 1`
+/// 5. `D` is the number of non-empty siblings in `P`, and `T` is `false` beyond `D`
+/// 6. `O` is a valid aggregation mode
+/// 7. If `K` isn't zero, `Q` opens `R` at index `B - K_B` of the schedule `K`
+/// 8. If `K_M` isn't zero, `K_M == Poseidon(W)`
 ///
 /// `MAX_DEPTH` is the maximum depth of the state tree in LPN database.
 #[derive(Debug, Clone)]
@@ -122,6 +169,12 @@ pub struct StateCircuit<const MAX_DEPTH: usize, F: RichField> {
     siblings: Vec<HashOut<F>>,
     positions: Vec<bool>,
     block_hash: Array<F, PACKED_HASH_LEN>,
+    aggregation: AggregationKind,
+    rates_commitment: HashOut<F>,
+    rates_first_block: F,
+    rates_opening: RateLeafCircuit<RATES_SCHEDULE_DEPTH>,
+    slot_remaps_commitment: HashOut<F>,
+    slot_remaps: SlotRemapsCircuit,
 }
 
 impl<const MAX_DEPTH: usize, F: RichField> StateCircuit<MAX_DEPTH, F> {
@@ -145,9 +198,22 @@ impl<const MAX_DEPTH: usize, F: RichField> StateCircuit<MAX_DEPTH, F> {
             siblings,
             positions,
             block_hash,
+            aggregation: AggregationKind::default(),
+            rates_commitment: HashOut::ZERO,
+            rates_first_block: F::ZERO,
+            rates_opening: RateLeafCircuit::uncommitted(),
+            slot_remaps_commitment: HashOut::ZERO,
+            slot_remaps: SlotRemapsCircuit::default(),
         }
     }
 
+    /// Sets the aggregation mode of the query results along the block tree,
+    /// which is `Sum` by default.
+    pub fn with_aggregation(mut self, aggregation: AggregationKind) -> Self {
+        self.aggregation = aggregation;
+        self
+    }
+
     /// Builds the circuit wires with virtual targets. It takes as argument
     /// the public inputs of the storage root proof.
     pub fn build(
@@ -159,6 +225,9 @@ impl<const MAX_DEPTH: usize, F: RichField> StateCircuit<MAX_DEPTH, F> {
         let c = storage_proof.root_hash();
         let v = storage_proof.query_results();
         let rewards = storage_proof.query_rewards_rate();
+        let max_rewards = storage_proof.max_rewards_rate();
+        let threshold = storage_proof.threshold();
+        let leaf_mode = storage_proof.leaf_mode();
 
         // contract address, mapping slot, length storage slot
         // block number, range
@@ -178,6 +247,11 @@ impl<const MAX_DEPTH: usize, F: RichField> StateCircuit<MAX_DEPTH, F> {
             .unzip();
         let siblings = MerkleProofTarget { siblings };
 
+        // the state leaf is the one of the slot in use at block B, resolved from
+        // the migrations of the mapping committed in K_M, if any
+        let slot_remaps_commitment = cb.add_virtual_hash();
+        let (slot_remaps, slot) = SlotRemapsCircuit::build(cb, b, m, slot_remaps_commitment);
+
         // FIXME the optimized version without the length slot is unimplemented
         // https://www.notion.so/lagrangelabs/Encoding-Specs-ccaa31d1598b4626860e26ac149705c4?pvs=4#fe2b40982352464ba39164cf4b41d301
         // Currently = H(pack_u32(address) || mapping_slot || length_slot || storageRoot)
@@ -185,12 +259,13 @@ impl<const MAX_DEPTH: usize, F: RichField> StateCircuit<MAX_DEPTH, F> {
             .to_targets()
             .arr
             .into_iter()
-            .chain(iter::once(m))
+            .chain(iter::once(slot))
             .chain(iter::once(s))
             .chain(c.elements.iter().copied())
             .collect();
 
         let state_tree = StateTreeWires::build(cb, state_leaf.as_slice(), &siblings, &positions);
+        state_tree.enforce_depth_consistency(cb, &siblings, &positions);
 
         // FIXME optimized version unimplemented
         // https://www.notion.so/lagrangelabs/Encoding-Specs-ccaa31d1598b4626860e26ac149705c4?pvs=4#5e8e6f06e2554b0caee4904258cbbca2
@@ -201,9 +276,45 @@ impl<const MAX_DEPTH: usize, F: RichField> StateCircuit<MAX_DEPTH, F> {
             .collect();
         let block_leaf_hash = cb.hash_n_to_hash_no_pad::<PoseidonHash>(block_leaf);
 
-        // we simply forward the results and rewards rate here
-        // range is 1 since it's only one block
-        BlockPublicInputs::register(cb, b, r, &block_leaf_hash, &a, &x, m, s, v, rewards);
+        // the aggregation mode is chosen here and enforced by every node up to the root
+        let aggregation_mode = cb.add_virtual_target();
+        aggregation_mode_flags(cb, aggregation_mode);
+
+        // the rewards rate is bound to the schedule committed in K, if any
+        let rates_commitment = cb.add_virtual_hash();
+        let rates_first_block = cb.add_virtual_target();
+        let rates_opening = RateLeafCircuit::<RATES_SCHEDULE_DEPTH>::build_if_committed(
+            cb,
+            b,
+            rates_first_block,
+            &rewards,
+            rates_commitment,
+        );
+
+        // we simply forward the results, rewards rate, its bound and the mode of the
+        // leaves here
+        // range and aggregated count are 1 since it's only one block
+        BlockPublicInputs::register(
+            cb,
+            b,
+            r,
+            r,
+            &block_leaf_hash,
+            &a,
+            &x,
+            m,
+            s,
+            v,
+            rewards,
+            max_rewards,
+            state_tree.depth,
+            aggregation_mode,
+            &rates_commitment,
+            rates_first_block,
+            &slot_remaps_commitment,
+            threshold,
+            leaf_mode,
+        );
 
         StateWires {
             smart_contract_address: a,
@@ -215,8 +326,37 @@ impl<const MAX_DEPTH: usize, F: RichField> StateCircuit<MAX_DEPTH, F> {
             positions,
             block_hash,
             state_tree,
+            aggregation_mode,
+            rates_commitment,
+            rates_first_block,
+            rates_opening,
+            slot_remaps_commitment,
+            slot_remaps,
         }
     }
+}
+
+impl<const MAX_DEPTH: usize> StateCircuit<MAX_DEPTH, F> {
+    /// Binds the rewards rate to the schedule `rates` of `2^RATES_SCHEDULE_DEPTH`
+    /// rates, whose first entry is the rate of block `first_block`. It fails if
+    /// the block isn't covered by the schedule.
+    pub fn with_rates_schedule(mut self, rates: &[U256], first_block: u32) -> Result<Self> {
+        let block_number = u32::try_from(self.block_number.to_canonical_u64())?;
+        self.rates_opening = RateLeafCircuit::new(rates, first_block, block_number)?;
+        self.rates_commitment = rates_commitment::<RATES_SCHEDULE_DEPTH>(rates);
+        self.rates_first_block = F::from_canonical_u32(first_block);
+        Ok(self)
+    }
+
+    /// Resolves the slot of the mapping at the block from the migrations
+    /// `remaps` of the mapping, as `(block, slot)` pairs, where the mapping
+    /// slot of the circuit is the original one. It fails if the migrations
+    /// can't be committed.
+    pub fn with_slot_remaps(mut self, remaps: &[(u32, u32)]) -> Result<Self> {
+        self.slot_remaps = SlotRemapsCircuit::new(remaps)?;
+        self.slot_remaps_commitment = slot_remaps_commitment(remaps)?;
+        Ok(self)
+    }
 
     /// Assigns the instance witness values to the provided wires.
     pub fn assign(&self, pw: &mut PartialWitness<F>, wires: &StateWires<MAX_DEPTH>) {
@@ -229,6 +369,15 @@ impl<const MAX_DEPTH: usize, F: RichField> StateCircuit<MAX_DEPTH, F> {
         pw.set_target(wires.mapping_slot, self.mapping_slot);
         pw.set_target(wires.length_slot, self.length_slot);
         pw.set_target(wires.block_number, self.block_number);
+        pw.set_target(
+            wires.aggregation_mode,
+            F::from_canonical_u8(self.aggregation as u8),
+        );
+        pw.set_hash_target(wires.rates_commitment, self.rates_commitment);
+        pw.set_target(wires.rates_first_block, self.rates_first_block);
+        self.rates_opening.assign(pw, &wires.rates_opening);
+        pw.set_hash_target(wires.slot_remaps_commitment, self.slot_remaps_commitment);
+        self.slot_remaps.assign(pw, &wires.slot_remaps);
 
         // make sure we always assign all the potential values
         // the depth is handled in the "self.depth" assignement above.
@@ -397,6 +546,78 @@ impl CircuitInput {
             storage_proof: ProofWithVK::deserialize(&storage_proof)?,
         })
     }
+
+    /// Proof of the storage tree employed by the state circuit
+    pub(crate) fn storage_proof(&self) -> &ProofWithVK {
+        &self.storage_proof
+    }
+
+    /// Sets the aggregation mode of the query results along the block tree,
+    /// which is `Sum` by default. The mode is exposed in the public inputs of
+    /// the state proof and must be the same for all the aggregated blocks.
+    pub fn with_aggregation(mut self, aggregation: AggregationKind) -> Self {
+        self.state_input = self.state_input.with_aggregation(aggregation);
+        self
+    }
+
+    /// Binds the rewards rate of the storage proof to the schedule `rates` of
+    /// `2^RATES_SCHEDULE_DEPTH` rates, whose first entry is the rate of block
+    /// `first_block`. The commitment to the schedule is exposed in the public
+    /// inputs of the state proof and must be the same for all the aggregated
+    /// blocks; without a schedule, the zero hash is exposed.
+    pub fn with_rates_schedule(mut self, rates: &[U256], first_block: u32) -> Result<Self> {
+        self.state_input = self.state_input.with_rates_schedule(rates, first_block)?;
+        Ok(self)
+    }
+
+    /// Resolves the slot of the mapping at the block from the migrations
+    /// `remaps` of the mapping to other slots, as `(block, slot)` pairs, where
+    /// the mapping slot of the input is the original one. The commitment to
+    /// the migrations is exposed in the public inputs of the state proof and
+    /// must be the same for all the aggregated blocks, allowing to aggregate
+    /// blocks on either side of a migration; without migrations, the zero
+    /// hash is exposed.
+    pub fn with_slot_remaps(mut self, remaps: &[(u32, u32)]) -> Result<Self> {
+        self.state_input = self.state_input.with_slot_remaps(remaps)?;
+        Ok(self)
+    }
+
+    /// Creates the inputs to prove membership in the state db of lagrange from
+    /// the root proof of the storage db (as returned by the storage circuits),
+    /// the `(mapping_slot, length_slot)` storage slots of the contract, and the
+    /// opening of the state tree, i.e. the `siblings` and `positions` of the
+    /// path from the state leaf of the contract to the state root of the block.
+    pub fn from_storage_proof(
+        storage_proof: Vec<u8>,
+        block_number: u32,
+        (mapping_slot, length_slot): (u32, u32),
+        sc_address: Address,
+        block_hash: HashOutput,
+        siblings: &[HashOutput],
+        positions: &[bool],
+    ) -> Result<Self> {
+        let num_storage_inputs = ProofWithVK::deserialize(&storage_proof)?
+            .proof()
+            .public_inputs
+            .len();
+        if num_storage_inputs < NUM_STORAGE_INPUTS {
+            bail!(
+                "storage proof expected to have at least {NUM_STORAGE_INPUTS} public inputs (currently {num_storage_inputs})"
+            );
+        }
+
+        Self::new(
+            sc_address,
+            mapping_slot,
+            length_slot,
+            block_number,
+            siblings.len() as u32,
+            siblings,
+            positions,
+            block_hash,
+            storage_proof,
+        )
+    }
 }
 
 impl Parameters {
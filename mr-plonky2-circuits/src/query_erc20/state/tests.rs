@@ -2,18 +2,25 @@ use super::StateWires;
 use crate::api::lpn_state::{state_leaf_hash, state_node_hash};
 use crate::block::block_leaf_hash;
 use crate::utils::{Packer, ToFields};
+use crate::{
+    api::ProofWithVK,
+    query_erc20::state::CircuitInputsInternal,
+    types::{HashOutput, MAPPING_KEY_LEN},
+};
 use crate::{
     array::Array,
     query_erc20::{
-        block::BlockPublicInputs, storage::public_inputs::PublicInputs as StorageInputs,
+        block::{aggregation::AggregationKind, BlockPublicInputs},
+        rates::{rates_commitment, RATES_SCHEDULE_DEPTH},
+        slots::slot_remaps_commitment,
+        storage::public_inputs::PublicInputs as StorageInputs,
     },
 };
-use crate::{query_erc20::state::CircuitInputsInternal, types::MAPPING_KEY_LEN};
 use ethers::abi::Hash;
 use ethers::types::{Address, U256};
 use mrp2_test_utils::circuit::{run_circuit, UserCircuit};
 use mrp2_utils::eth::left_pad32;
-use mrp2_utils::types::{PackedSCAddress, PACKED_ADDRESS_LEN};
+use mrp2_utils::types::{address_to_packed_fields, PackedSCAddress, PACKED_ADDRESS_LEN};
 use mrp2_utils::utils::{convert_u32_fields_to_u8_vec, convert_u8_to_u32_slice};
 use plonky2::field::types::{PrimeField64, Sample};
 use plonky2::plonk::config::GenericHashOut;
@@ -55,6 +62,160 @@ fn test_query_erc20_state_circuit() {
     );
 }
 
+#[test]
+fn test_query_erc20_state_circuit_mismatched_depth() {
+    let mut rng = thread_rng();
+    let root = create_array(|_| GoldilocksField::rand());
+    let user_address_fields = address_to_packed_fields(&Address::random());
+    let inputs = StorageInputs::from_parts(
+        &root,
+        &user_address_fields,
+        U256::from(rng.gen::<u64>()),
+        U256::from(rng.gen::<u64>()),
+    );
+    let circuit = TestStateCircuit::<MAX_DEPTH>::new(
+        rng.gen(),
+        rng.gen(),
+        rng.gen(),
+        Address::random(),
+        &StorageInputs::from_slice(&inputs),
+        REAL_DEPTH,
+    );
+
+    // a depth not matching the number of siblings is rejected
+    let mut shallower = circuit.clone();
+    shallower.c.depth = GoldilocksField::from_canonical_usize(REAL_DEPTH - 1);
+    let res =
+        std::panic::catch_unwind(|| run_circuit::<_, _, PoseidonGoldilocksConfig, _>(shallower));
+    assert!(res.is_err(), "depth smaller than the opening path accepted");
+
+    // a position beyond the depth is rejected
+    let mut positioned = circuit;
+    positioned.c.positions[REAL_DEPTH] = true;
+    let res =
+        std::panic::catch_unwind(|| run_circuit::<_, _, PoseidonGoldilocksConfig, _>(positioned));
+    assert!(res.is_err(), "position beyond the depth accepted");
+}
+
+#[test]
+fn test_query_erc20_state_circuit_rates_schedule() {
+    let mut rng = thread_rng();
+    let root = create_array(|_| GoldilocksField::rand());
+    let rewards_rate = U256::from(rng.gen::<u64>());
+    let inputs = StorageInputs::from_parts(
+        &root,
+        &address_to_packed_fields(&Address::random()),
+        U256::from(rng.gen::<u64>()),
+        rewards_rate,
+    );
+    let first_block = rng.gen_range(0..1000);
+    let index = rng.gen_range(0..1 << RATES_SCHEDULE_DEPTH);
+    let block_number = first_block + index as u32;
+    let circuit = TestStateCircuit::<MAX_DEPTH>::new(
+        block_number,
+        rng.gen(),
+        rng.gen(),
+        Address::random(),
+        &StorageInputs::from_slice(&inputs),
+        REAL_DEPTH,
+    );
+    let mut rates = (0..1 << RATES_SCHEDULE_DEPTH)
+        .map(|_| U256::from(rng.gen::<u64>()))
+        .collect::<Vec<_>>();
+    rates[index] = rewards_rate;
+
+    // the rate of the storage proof is the one of the block in the schedule
+    let mut scheduled = circuit.clone();
+    scheduled.c = scheduled
+        .c
+        .with_rates_schedule(&rates, first_block)
+        .unwrap();
+    let proof = run_circuit::<_, _, PoseidonGoldilocksConfig, _>(scheduled);
+    let pi = BlockPublicInputs::<'_, GoldilocksField>::from(proof.public_inputs.as_slice());
+    assert_eq!(
+        pi.rates_commitment(),
+        rates_commitment::<RATES_SCHEDULE_DEPTH>(&rates)
+    );
+    assert_eq!(
+        pi.rates_first_block(),
+        GoldilocksField::from_canonical_u32(first_block)
+    );
+
+    // a rate different from the one of the block in the schedule is rejected
+    rates[index] += U256::one();
+    let mut mismatched = circuit.clone();
+    mismatched.c = mismatched
+        .c
+        .with_rates_schedule(&rates, first_block)
+        .unwrap();
+    let res =
+        std::panic::catch_unwind(|| run_circuit::<_, _, PoseidonGoldilocksConfig, _>(mismatched));
+    assert!(res.is_err(), "rate not matching the schedule accepted");
+
+    // a block not covered by the schedule is rejected
+    assert!(circuit
+        .c
+        .with_rates_schedule(&rates, block_number + 1)
+        .is_err());
+}
+
+#[test]
+fn test_query_erc20_state_circuit_slot_remaps() {
+    let mut rng = thread_rng();
+    let root = create_array(|_| GoldilocksField::rand());
+    let inputs = StorageInputs::from_parts(
+        &root,
+        &address_to_packed_fields(&Address::random()),
+        U256::from(rng.gen::<u64>()),
+        U256::from(rng.gen::<u64>()),
+    );
+    let block_number = rng.gen_range(1000..10_000);
+    let original_slot = rng.gen::<u8>();
+    let new_slot = original_slot.wrapping_add(1);
+    // the state tree of the block stores the mapping at the new slot
+    let circuit = TestStateCircuit::<MAX_DEPTH>::new(
+        block_number,
+        rng.gen(),
+        new_slot,
+        Address::random(),
+        &StorageInputs::from_slice(&inputs),
+        REAL_DEPTH,
+    );
+    let remapped = |remaps: &[(u32, u32)]| {
+        let mut remapped = circuit.clone();
+        remapped.c.mapping_slot = GoldilocksField::from_canonical_u8(original_slot);
+        remapped.c = remapped.c.with_slot_remaps(remaps).unwrap();
+        let proof = run_circuit::<_, _, PoseidonGoldilocksConfig, _>(remapped);
+        proof.public_inputs
+    };
+
+    // after the migration, the state leaf is the one of the new slot, while
+    // the original slot and the commitment to the migrations are exposed
+    let remaps = [(block_number - 10, new_slot as u32), (block_number + 10, 0)];
+    let public_inputs = remapped(&remaps);
+    let pi = BlockPublicInputs::<'_, GoldilocksField>::from(public_inputs.as_slice());
+    assert_eq!(pi.root(), circuit.root);
+    assert_eq!(
+        pi.mapping_slot(),
+        GoldilocksField::from_canonical_u8(original_slot)
+    );
+    assert_eq!(
+        pi.slot_remaps_commitment(),
+        slot_remaps_commitment(&remaps).unwrap()
+    );
+
+    // before the migration, the state leaf is the one of the original slot
+    let public_inputs = remapped(&[(block_number + 1, new_slot as u32)]);
+    let pi = BlockPublicInputs::<'_, GoldilocksField>::from(public_inputs.as_slice());
+    assert_ne!(pi.root(), circuit.root);
+
+    // without migrations, the mapping slot is the one of the state leaf
+    let proof = run_circuit::<_, _, PoseidonGoldilocksConfig, _>(circuit.clone());
+    let pi = BlockPublicInputs::<'_, GoldilocksField>::from(proof.public_inputs.as_slice());
+    assert_eq!(pi.root(), circuit.root);
+    assert_eq!(pi.slot_remaps_commitment(), HashOut::ZERO);
+}
+
 #[test]
 #[serial]
 fn test_query_erc20_state_parameters() {
@@ -72,6 +233,104 @@ fn test_query_erc20_state_parameters() {
     params.verify_proof(&proof).unwrap();
 }
 
+#[test]
+#[serial]
+fn test_query_erc20_state_from_storage_proof() {
+    let mut rng = thread_rng();
+    let testing_framework = TestingRecursiveCircuits::<F, C, D, NUM_STORAGE_INPUTS>::default();
+    let params = StateParameters::build(testing_framework.get_recursive_circuit_set());
+
+    // Generate a storage root proof
+    let root = create_array(|_| GoldilocksField::rand());
+    let user_address = Address::random();
+    let user_address_fields = address_to_packed_fields(&user_address);
+    let value = U256::from(rng.gen::<u64>());
+    let rewards_rate = U256::from_dec_str("34").unwrap();
+    let storage_pi = StorageInputs::from_parts(&root, &user_address_fields, value, rewards_rate);
+    let storage_proof = ProofWithVK::from((
+        testing_framework
+            .generate_input_proofs([storage_pi])
+            .unwrap()[0]
+            .clone(),
+        testing_framework.verifier_data_for_input_proofs::<1>()[0].clone(),
+    ))
+    .serialize()
+    .unwrap();
+
+    let block_number = rng.gen::<u32>();
+    let (mapping_slot, length_slot) = (rng.gen::<u8>() as u32, rng.gen::<u8>() as u32);
+    let sc_address = Address::random();
+    let siblings = (0..REAL_DEPTH)
+        .map(|_| HashOut::<F>::rand().to_bytes().try_into().unwrap())
+        .collect::<Vec<HashOutput>>();
+    let positions = (0..REAL_DEPTH).map(|_| rng.gen()).collect::<Vec<bool>>();
+    let input = super::CircuitInput::from_storage_proof(
+        storage_proof,
+        block_number,
+        (mapping_slot, length_slot),
+        sc_address,
+        rng.gen(),
+        &siblings,
+        &positions,
+    )
+    .unwrap()
+    .with_aggregation(AggregationKind::Max);
+
+    let proof = params
+        .generate_proof(
+            testing_framework.get_recursive_circuit_set(),
+            CircuitInputsInternal::from_circuit_input(
+                input,
+                testing_framework.get_recursive_circuit_set(),
+            ),
+        )
+        .unwrap();
+    params.verify_proof(&proof).unwrap();
+
+    // Check the state proof exposes the values of the storage proof
+    let pi = ProofWithVK::deserialize(&proof)
+        .unwrap()
+        .proof
+        .public_inputs;
+    let pi = BlockPublicInputs::from(&pi[..BlockPublicInputs::<Target>::total_len()]);
+    assert_eq!(pi.block_number(), F::from_canonical_u32(block_number));
+    assert_eq!(pi.range(), F::ONE);
+    assert_eq!(
+        pi.smart_contract_address(),
+        address_to_packed_fields(&sc_address).as_slice()
+    );
+    assert_eq!(pi.user_address(), user_address_fields);
+    assert_eq!(pi.mapping_slot(), F::from_canonical_u32(mapping_slot));
+    assert_eq!(pi.mapping_slot_length(), F::from_canonical_u32(length_slot));
+    assert_eq!(pi.query_results(), value);
+    assert_eq!(pi.rewards_rate(), rewards_rate);
+    assert_eq!(pi.state_depth(), F::from_canonical_usize(REAL_DEPTH));
+    assert_eq!(pi.aggregation_mode().unwrap(), AggregationKind::Max);
+
+    // A storage proof with invalid public inputs is rejected
+    let mut invalid_proof = ProofWithVK::from((
+        testing_framework
+            .generate_input_proofs([storage_pi])
+            .unwrap()[0]
+            .clone(),
+        testing_framework.verifier_data_for_input_proofs::<1>()[0].clone(),
+    ));
+    invalid_proof
+        .proof
+        .public_inputs
+        .truncate(NUM_STORAGE_INPUTS - 1);
+    assert!(super::CircuitInput::from_storage_proof(
+        invalid_proof.serialize().unwrap(),
+        block_number,
+        (mapping_slot, length_slot),
+        sc_address,
+        rng.gen(),
+        &siblings,
+        &positions,
+    )
+    .is_err());
+}
+
 pub(crate) fn run_state_circuit_with_slot_and_addresses(
     block_number: u32,
     slot_length: u8,
@@ -82,12 +341,7 @@ pub(crate) fn run_state_circuit_with_slot_and_addresses(
     let root = create_array(|_| GoldilocksField::rand());
     let value = U256::from_dec_str("145648").unwrap();
     let rewards_rate = U256::from_dec_str("34").unwrap();
-    let user_address_fields: [GoldilocksField; PACKED_ADDRESS_LEN] = user_address
-        .as_fixed_bytes()
-        .pack()
-        .to_fields()
-        .try_into()
-        .unwrap();
+    let user_address_fields = address_to_packed_fields(&user_address);
     let inputs = StorageInputs::from_parts(&root, &user_address_fields, value, rewards_rate);
     let storage_pi = StorageInputs::from_slice(&inputs);
 
@@ -111,6 +365,12 @@ pub(crate) fn run_state_circuit_with_slot_and_addresses(
     assert_eq!(pi.user_address(), user_address_fields);
     assert_eq!(pi.mapping_slot(), circuit.mapping_slot);
     assert_eq!(pi.mapping_slot_length(), circuit.length_slot);
+    assert_eq!(
+        pi.state_depth(),
+        GoldilocksField::from_canonical_usize(REAL_DEPTH)
+    );
+    assert_eq!(pi.aggregation_mode().unwrap(), AggregationKind::Sum);
+    assert_eq!(pi.rates_commitment(), HashOut::ZERO);
 
     proof.public_inputs.to_owned()
 }
@@ -153,7 +413,9 @@ impl<const MAX_DEPTH: usize> TestStateCircuit<MAX_DEPTH> {
             })
             .collect();
 
-        let positions: Vec<_> = (0..MAX_DEPTH).map(|_| rng.next_u32() & 1 == 1).collect();
+        let positions: Vec<_> = (0..MAX_DEPTH)
+            .map(|i| i < depth && rng.next_u32() & 1 == 1)
+            .collect();
 
         let storage_root = HashOut::from_vec(storage.root_hash_raw().to_vec());
         let mut state_root = HashOut::from_bytes(&state_leaf_hash(
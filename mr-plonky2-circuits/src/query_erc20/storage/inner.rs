@@ -28,7 +28,7 @@ pub struct InnerNodeWires {
 /// This circuit prove the root of the subtree made of:
 ///   - a child whose hash has not changes on the side defined by unproved_is_left
 ///   - another child whose hash has been updated.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct InnerNodeCircuit {
     pub proved_is_right: bool,
     pub unproved_hash: HashOut<GoldilocksField>,
@@ -55,6 +55,11 @@ impl InnerNodeCircuit {
             &proved.query_user_address(),
             &proved.query_results(),
             &proved.query_rewards_rate(),
+            proved.is_present(),
+            proved.is_zero_balance(),
+            &proved.max_rewards_rate(),
+            &proved.threshold(),
+            proved.leaf_mode(),
         );
 
         InnerNodeWires {
@@ -1,5 +1,5 @@
 use mrp2_utils::array::Array;
-use mrp2_utils::types::{PackedMappingKeyTarget, MAPPING_KEY_LEN, VALUE_LEN};
+use mrp2_utils::types::{PackedMappingKeyTarget, MAPPING_KEY_LEN, PACKED_U256_LEN, VALUE_LEN};
 use mrp2_utils::u256::{CircuitBuilderU256, UInt256Target, WitnessWriteU256};
 
 use crate::{
@@ -17,6 +17,43 @@ use serde::{Deserialize, Serialize};
 
 pub(crate) const HASH_PREFIX: &[u8] = b"LEAF";
 
+/// Value exposed by the leaf circuit for the balance of the queried user
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LeafMode {
+    /// Expose the rewards of the user, i.e. `R * value / totalSupply`
+    #[default]
+    Rewards,
+    /// Expose only whether the balance of the user is at least a public threshold,
+    /// keeping the balance private. The threshold is exposed as public input, while
+    /// the rewards rate must be zero and the total supply is ignored.
+    Threshold,
+}
+
+/// Formula computing the rewards of the user in `LeafMode::Rewards`. The formula is
+/// fixed when building the circuit, so that it is bound to the verification key.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RewardFormula {
+    /// Rewards proportional to the balance of the user, i.e. `R * value / totalSupply`
+    #[default]
+    Proportional,
+    /// Flat reward for each holder whose balance exceeds a public minimum, where the
+    /// rewards rate is the reward granted to each holder. The minimum balance is
+    /// exposed as public input in place of the threshold, while the total supply is
+    /// ignored.
+    FlatPerHolder,
+}
+
+/// Identifier of the value exposed by a leaf built with `mode` and `formula`, which
+/// is exposed as public input and bound in the query commitment, so that a proof
+/// can't be presented as the result of another computation
+pub fn leaf_mode_id(mode: LeafMode, formula: RewardFormula) -> u8 {
+    match (mode, formula) {
+        (LeafMode::Rewards, RewardFormula::Proportional) => 0,
+        (LeafMode::Rewards, RewardFormula::FlatPerHolder) => 1,
+        (LeafMode::Threshold, _) => 2,
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct LeafWires {
     // Note this is a fix because we can't prove non membership yet in v0
@@ -25,18 +62,99 @@ pub struct LeafWires {
     value_bytes_be: Array<Target, VALUE_LEN>,
     total_supply: UInt256Target,
     rewards_rate: UInt256Target,
+    max_rewards_rate: UInt256Target,
+    threshold: UInt256Target,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct LeafCircuit {
     pub address: Address,
     pub query_address: Address,
     pub value: U256,
     pub total_supply: U256,
     pub rewards_rate: U256,
+    /// Upper bound on the rewards rate exposed as public input, the rate is
+    /// unbounded if `None`. The bound is only enforced on the proportional
+    /// rewards, since in the other modes the rate field holds a threshold or a
+    /// minimum balance.
+    pub max_rewards_rate: Option<U256>,
+    /// Threshold the balance is compared to in `LeafMode::Threshold`, or minimum
+    /// balance of `RewardFormula::FlatPerHolder`; it must be zero for proportional
+    /// rewards
+    pub threshold: U256,
+    /// Mode of the circuit the leaf is proven with
+    pub mode: LeafMode,
+    /// Reward formula of the circuit the leaf is proven with
+    pub formula: RewardFormula,
 }
 
 impl LeafCircuit {
+    /// Returns whether the inputs can't be proven by the circuit of their mode and
+    /// formula, which would make the circuit unsatisfiable. Only proportional rewards
+    /// can fail: if the rewards rate exceeds its bound, if `value * rewards_rate`
+    /// overflows or if `total_supply` is zero. It allows to validate the inputs
+    /// before proving instead of failing during proving.
+    pub fn would_overflow(&self) -> bool {
+        match (self.mode, self.formula) {
+            (LeafMode::Rewards, RewardFormula::Proportional) => {
+                self.rewards_rate > self.max_rewards_rate.unwrap_or(U256::MAX)
+                    || self.value.overflowing_mul(self.rewards_rate).1
+                    || self.total_supply.is_zero()
+            }
+            (LeafMode::Rewards, RewardFormula::FlatPerHolder) | (LeafMode::Threshold, _) => false,
+        }
+    }
+
+    /// Identifier of the mode of the leaf, see `leaf_mode_id`
+    pub fn mode_id(&self) -> u8 {
+        leaf_mode_id(self.mode, self.formula)
+    }
+
+    /// Inputs for a leaf circuit built in `LeafMode::Threshold`, proving whether the
+    /// balance `value` of the user is at least `threshold`.
+    pub fn new_threshold(
+        address: Address,
+        query_address: Address,
+        value: U256,
+        threshold: U256,
+    ) -> Self {
+        Self {
+            address,
+            query_address,
+            value,
+            threshold,
+            mode: LeafMode::Threshold,
+            ..Default::default()
+        }
+    }
+
+    /// Inputs for a leaf circuit built with `RewardFormula::FlatPerHolder`, granting
+    /// the flat `reward` to the user if its balance `value` exceeds `min_balance`.
+    pub fn new_flat_per_holder(
+        address: Address,
+        query_address: Address,
+        value: U256,
+        reward: U256,
+        min_balance: U256,
+    ) -> Self {
+        Self {
+            address,
+            query_address,
+            value,
+            rewards_rate: reward,
+            threshold: min_balance,
+            formula: RewardFormula::FlatPerHolder,
+            ..Default::default()
+        }
+    }
+
+    /// Bound the rewards rate by `max_rewards_rate`, so that a leaf with a larger
+    /// rate fails to be proven with proportional rewards.
+    pub fn with_max_rewards_rate(mut self, max_rewards_rate: U256) -> Self {
+        self.max_rewards_rate = Some(max_rewards_rate);
+        self
+    }
+
     pub fn assign(&self, pw: &mut PartialWitness<GoldilocksField>, wires: &LeafWires) {
         let address = self.address.0.pack().try_into().unwrap();
         wires.address.assign_from_data(pw, &address);
@@ -49,12 +167,53 @@ impl LeafCircuit {
         [
             (self.total_supply, &wires.total_supply),
             (self.rewards_rate, &wires.rewards_rate),
+            (
+                self.max_rewards_rate.unwrap_or(U256::MAX),
+                &wires.max_rewards_rate,
+            ),
+            (self.threshold, &wires.threshold),
         ]
         .iter()
         .for_each(|(v, w)| pw.set_u256_target(w, *v));
     }
 
     pub fn build(b: &mut CircuitBuilder<GoldilocksField, 2>) -> LeafWires {
+        Self::build_with_mode(b, LeafMode::Rewards)
+    }
+
+    pub fn build_with_mode(
+        b: &mut CircuitBuilder<GoldilocksField, 2>,
+        mode: LeafMode,
+    ) -> LeafWires {
+        Self::build_with_mode_and_formula(b, mode, RewardFormula::Proportional)
+    }
+
+    /// Build the circuit in `LeafMode::Rewards`, computing the rewards with `formula`
+    pub fn build_with_formula(
+        b: &mut CircuitBuilder<GoldilocksField, 2>,
+        formula: RewardFormula,
+    ) -> LeafWires {
+        Self::build_with_mode_and_formula(b, LeafMode::Rewards, formula)
+    }
+
+    pub(crate) fn build_with_mode_and_formula(
+        b: &mut CircuitBuilder<GoldilocksField, 2>,
+        mode: LeafMode,
+        formula: RewardFormula,
+    ) -> LeafWires {
+        // the u256 inputs are range-checked with lookups, which are cheaper than
+        // bit-decomposition already for the handful of inputs of the leaf
+        Self::build_with_u256_allocator(b, mode, formula, |b| b.add_virtual_u256_with_lookup())
+    }
+
+    /// Build the circuit allocating the u256 inputs with `add_u256`, which must
+    /// range-check the limbs of the allocated inputs
+    pub(crate) fn build_with_u256_allocator(
+        b: &mut CircuitBuilder<GoldilocksField, 2>,
+        mode: LeafMode,
+        formula: RewardFormula,
+        add_u256: fn(&mut CircuitBuilder<GoldilocksField, 2>) -> UInt256Target,
+    ) -> LeafWires {
         // address of the user stored at the leaf
         let address = PackedAddressTarget::new(b);
         // address of the query we expose as public input
@@ -67,7 +226,7 @@ impl LeafCircuit {
         let packed_le = value_big_endian.reverse().convert_u8_to_u32(b);
         // unwrap is safe because we exactly give 32 bytes  in packed format
         let value_u256 = UInt256Target::new_from_limbs(&packed_le.arr).unwrap();
-        let [total_supply, rewards_rate] = [0; 2].map(|_| b.add_virtual_u256());
+        let [total_supply, rewards_rate, max_rewards_rate, threshold] = [0; 4].map(|_| add_u256(b));
 
         // we left_pad the address to 8 (packed 32bytes ) as it is the
         // hashing structure expected: 32 byte for mapping key packed = 8 fields
@@ -82,21 +241,54 @@ impl LeafCircuit {
             .collect();
         let c = b.hash_n_to_hash_no_pad::<PoseidonHash>(inputs);
 
-        // V = R * value / totalSupply
-        // do multiplication first then division
         let zero_u256 = b.zero_u256();
-        let (op1, overflow) = b.mul_u256(&value_u256, &rewards_rate);
-        // ensure the prover is not trying to obtain invalid results by overflowing the mul
-        let _false = b._false();
-        b.connect(overflow.target, _false.target);
-        let (res, _, div_by_zero) = b.div_u256(&op1, &total_supply);
-        // ensure the prover is not trying to obtain invalid results by dividing by zero
-        b.connect(div_by_zero.target, _false.target);
+        let res = match (mode, formula) {
+            (LeafMode::Rewards, RewardFormula::Proportional) => {
+                // reject a rewards rate above the bound of the query, before it reaches
+                // the multiplication below
+                let is_rate_bounded = b.is_less_or_equal_u256(&rewards_rate, &max_rewards_rate);
+                b.assert_one(is_rate_bounded.target);
+                // V = R * value / totalSupply
+                // do multiplication first then division
+                let (op1, overflow) = b.mul_u256(&value_u256, &rewards_rate);
+                // ensure the prover is not trying to obtain invalid results by overflowing the mul
+                let _false = b._false();
+                b.connect(overflow.target, _false.target);
+                let (res, _, div_by_zero) = b.div_u256(&op1, &total_supply);
+                // ensure the prover is not trying to obtain invalid results by dividing by zero
+                b.connect(div_by_zero.target, _false.target);
+                // T is unused, it's zeroed so that the query is uniquely identified
+                b.enforce_equal_u256(&threshold, &zero_u256);
+                res
+            }
+            (LeafMode::Rewards, RewardFormula::FlatPerHolder) => {
+                // V = R if value > T, where R is the reward of each holder and T the
+                // minimum balance; no multiplication nor division is needed
+                let exceeds_minimum = b.is_less_than_u256(&threshold, &value_u256);
+                b.select_u256(exceeds_minimum, &rewards_rate, &zero_u256)
+            }
+            (LeafMode::Threshold, _) => {
+                // R is unused, it's zeroed so that the query is uniquely identified
+                b.enforce_equal_u256(&rewards_rate, &zero_u256);
+                // V = value >= T
+                let meets_threshold = b.is_greater_or_equal_u256(&value_u256, &threshold);
+                let mut limbs = [zero; PACKED_U256_LEN];
+                limbs[0] = meets_threshold.target;
+                UInt256Target::new_from_target_limbs(&limbs).unwrap()
+            }
+        };
         let are_addresses_equal = address.equals(b, &query_address);
         // only output real value if user address == query address.
         // That's a hack to allow to still have a proof when a user is not included in a block since non membership
         // proofs will be supported only in v1.
         let final_output = b.select_u256(are_addresses_equal, &res, &zero_u256);
+        // distinguish a present address holding no tokens from an absent address,
+        // since both expose a zero result
+        let is_zero_value = b.is_zero(&value_u256);
+        let is_zero_balance = b.and(are_addresses_equal, is_zero_value);
+        let mode_id = b.constant(GoldilocksField::from_canonical_u8(leaf_mode_id(
+            mode, formula,
+        )));
         PublicInputs::<GoldilocksField>::register(
             b,
             &c,
@@ -104,6 +296,11 @@ impl LeafCircuit {
             &query_address,
             &final_output,
             &rewards_rate,
+            are_addresses_equal,
+            is_zero_balance,
+            &max_rewards_rate,
+            &threshold,
+            mode_id,
         );
 
         LeafWires {
@@ -112,12 +309,15 @@ impl LeafCircuit {
             value_bytes_be: value_big_endian,
             total_supply,
             rewards_rate,
+            max_rewards_rate,
+            threshold,
         }
     }
 }
 
 impl CircuitLogicWires<GoldilocksField, 2, 0> for LeafWires {
-    type CircuitBuilderParams = ();
+    /// Mode and reward formula of the leaf
+    type CircuitBuilderParams = (LeafMode, RewardFormula);
     type Inputs = LeafCircuit;
 
     const NUM_PUBLIC_INPUTS: usize = PublicInputs::<GoldilocksField>::TOTAL_LEN;
@@ -125,9 +325,10 @@ impl CircuitLogicWires<GoldilocksField, 2, 0> for LeafWires {
     fn circuit_logic(
         builder: &mut CircuitBuilder<GoldilocksField, 2>,
         _verified_proofs: [&plonky2::plonk::proof::ProofWithPublicInputsTarget<2>; 0],
-        _builder_parameters: Self::CircuitBuilderParams,
+        builder_parameters: Self::CircuitBuilderParams,
     ) -> Self {
-        LeafCircuit::build(builder)
+        let (mode, formula) = builder_parameters;
+        LeafCircuit::build_with_mode_and_formula(builder, mode, formula)
     }
 
     fn assign_input(
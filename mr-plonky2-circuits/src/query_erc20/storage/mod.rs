@@ -1,11 +1,12 @@
 //! The module implementing the required mechanisms for Query ERC20
 //! https://www.notion.so/lagrangelabs/Cryptographic-Documentation-85adb821f18647b2a3dc65efbe144981?pvs=4#5776936f0833485ab9c7e27dcd277c91
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 use ethers::prelude::{Address, U256};
 use plonky2::{
-    field::goldilocks_field::GoldilocksField, hash::hash_types::HashOut,
-    plonk::config::GenericHashOut,
+    field::goldilocks_field::GoldilocksField,
+    hash::hash_types::HashOut,
+    plonk::{circuit_data::CircuitData, config::GenericHashOut},
 };
 use recursion_framework::{
     circuit_builder::{CircuitWithUniversalVerifier, CircuitWithUniversalVerifierBuilder},
@@ -13,7 +14,9 @@ use recursion_framework::{
 };
 use serde::{Deserialize, Serialize};
 
-use crate::api::{default_config, ProofWithVK, C, D, F};
+use crate::api::{
+    decode_circuit_input, default_config, encode_circuit_input, ProofWithVK, C, D, F,
+};
 
 use self::{
     inner::{InnerNodeCircuit, InnerNodeWires},
@@ -23,6 +26,8 @@ use self::{
 
 mod inner;
 mod leaf;
+
+pub use leaf::{leaf_mode_id, LeafMode, RewardFormula};
 pub mod public_inputs;
 #[cfg(test)]
 mod tests;
@@ -46,6 +51,68 @@ impl CircuitInput {
             value,
             total_supply,
             rewards_rate,
+            ..Default::default()
+        })
+    }
+
+    /// Leaf proving whether the balance `value` of the user is at least `threshold`,
+    /// see `LeafMode::Threshold`
+    pub fn new_threshold_leaf(
+        address: Address,
+        query_address: Address,
+        value: U256,
+        threshold: U256,
+    ) -> Self {
+        CircuitInput::Leaf(LeafCircuit::new_threshold(
+            address,
+            query_address,
+            value,
+            threshold,
+        ))
+    }
+
+    /// Leaf granting the flat `reward` to the user if its balance `value` exceeds
+    /// `min_balance`, see `RewardFormula::FlatPerHolder`
+    pub fn new_flat_per_holder_leaf(
+        address: Address,
+        query_address: Address,
+        value: U256,
+        reward: U256,
+        min_balance: U256,
+    ) -> Self {
+        CircuitInput::Leaf(LeafCircuit::new_flat_per_holder(
+            address,
+            query_address,
+            value,
+            reward,
+            min_balance,
+        ))
+    }
+
+    const LEAF_TAG: u8 = 0;
+    const INNER_TAG: u8 = 1;
+
+    /// Encode the input in a stable format, where each variant is identified by
+    /// an explicit tag, so that inputs can be durably queued before proving
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        match self {
+            CircuitInput::Leaf(leaf) => encode_circuit_input(Self::LEAF_TAG, leaf),
+            CircuitInput::Inner(inner, proof) => {
+                encode_circuit_input(Self::INNER_TAG, &(inner, proof))
+            }
+        }
+    }
+
+    /// Decode an input encoded with `to_bytes`
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let (tag, payload) = decode_circuit_input(bytes)?;
+        Ok(match tag {
+            Self::LEAF_TAG => CircuitInput::Leaf(bincode::deserialize(payload)?),
+            Self::INNER_TAG => {
+                let (inner, proof) = bincode::deserialize(payload)?;
+                CircuitInput::Inner(inner, proof)
+            }
+            _ => bail!("unknown tag {tag} for query ERC20 storage circuit input"),
         })
     }
 
@@ -64,12 +131,14 @@ impl CircuitInput {
     }
 }
 
-const STORAGE_CIRCUIT_SET_SIZE: usize = 2;
+const STORAGE_CIRCUIT_SET_SIZE: usize = 4;
 const NUM_IO: usize = PublicInputs::<GoldilocksField>::TOTAL_LEN;
 
 #[derive(Serialize, Deserialize)]
 pub struct Parameters {
     leaf_circuit: CircuitWithUniversalVerifier<F, C, D, 0, LeafWires>,
+    flat_leaf_circuit: CircuitWithUniversalVerifier<F, C, D, 0, LeafWires>,
+    threshold_leaf_circuit: CircuitWithUniversalVerifier<F, C, D, 0, LeafWires>,
     inner_node_circuit: CircuitWithUniversalVerifier<F, C, D, 1, InnerNodeWires>,
     set: RecursiveCircuits<F, C, D>,
 }
@@ -81,16 +150,25 @@ impl Parameters {
             config,
             STORAGE_CIRCUIT_SET_SIZE,
         );
-        let leaf_circuit = circuit_builder.build_circuit::<C, 0, LeafWires>(());
+        let leaf_circuit = circuit_builder
+            .build_circuit::<C, 0, LeafWires>((LeafMode::Rewards, RewardFormula::Proportional));
+        let flat_leaf_circuit = circuit_builder
+            .build_circuit::<C, 0, LeafWires>((LeafMode::Rewards, RewardFormula::FlatPerHolder));
+        let threshold_leaf_circuit = circuit_builder
+            .build_circuit::<C, 0, LeafWires>((LeafMode::Threshold, RewardFormula::Proportional));
         let inner_node_circuit = circuit_builder.build_circuit::<C, 1, InnerNodeWires>(());
 
         let circuit_set = vec![
             leaf_circuit.get_verifier_data().circuit_digest,
+            flat_leaf_circuit.get_verifier_data().circuit_digest,
+            threshold_leaf_circuit.get_verifier_data().circuit_digest,
             inner_node_circuit.get_verifier_data().circuit_digest,
         ];
 
         Self {
             leaf_circuit,
+            flat_leaf_circuit,
+            threshold_leaf_circuit,
             inner_node_circuit,
             set: RecursiveCircuits::new_from_circuit_digests(circuit_set),
         }
@@ -99,10 +177,15 @@ impl Parameters {
     pub fn generate_proof(&self, input: CircuitInput) -> Result<Vec<u8>> {
         match input {
             CircuitInput::Leaf(leaf) => {
-                let proof = self.set.generate_proof(&self.leaf_circuit, [], [], leaf)?;
+                let circuit = match (leaf.mode, leaf.formula) {
+                    (LeafMode::Rewards, RewardFormula::Proportional) => &self.leaf_circuit,
+                    (LeafMode::Rewards, RewardFormula::FlatPerHolder) => &self.flat_leaf_circuit,
+                    (LeafMode::Threshold, _) => &self.threshold_leaf_circuit,
+                };
+                let proof = self.set.generate_proof(circuit, [], [], leaf)?;
                 ProofWithVK {
                     proof,
-                    vk: self.leaf_circuit.get_verifier_data().clone(),
+                    vk: circuit.get_verifier_data().clone(),
                 }
             }
             CircuitInput::Inner(inner, child) => {
@@ -125,4 +208,12 @@ impl Parameters {
     pub(crate) fn get_storage_circuit_set(&self) -> &RecursiveCircuits<F, C, D> {
         &self.set
     }
+
+    pub(crate) fn leaf_circuit_data(&self) -> &CircuitData<F, C, D> {
+        self.leaf_circuit.circuit_data()
+    }
+
+    pub(crate) fn inner_node_circuit_data(&self) -> &CircuitData<F, C, D> {
+        self.inner_node_circuit.circuit_data()
+    }
 }
@@ -4,9 +4,12 @@ use ethers::prelude::{Address, U256};
 use mrp2_utils::u256::{CircuitBuilderU256, UInt256Target};
 use mrp2_utils::utils::convert_u32_fields_to_u256;
 use plonky2::{
-    field::goldilocks_field::GoldilocksField,
+    field::{
+        goldilocks_field::GoldilocksField,
+        types::{Field, PrimeField64},
+    },
     hash::hash_types::{HashOut, HashOutTarget, NUM_HASH_OUT_ELTS},
-    iop::target::Target,
+    iop::target::{BoolTarget, Target},
     plonk::circuit_builder::CircuitBuilder,
 };
 use plonky2_crypto::u32::arithmetic_u32::U32Target;
@@ -17,6 +20,12 @@ use std::array::from_fn;
 ///   - X ([5]F): address of the query (H160)
 ///   - V ([8]F): balance / total supply (U256)
 ///   - R ([8]F): reward (U256)
+///   - P (F): whether the queried address is present in the storage tree
+///   - Z (F): whether the queried address is present with a zero balance
+///   - M ([8]F): upper bound on the rewards rate (U256), the maximum U256 if unbounded
+///   - T ([8]F): threshold the balance is compared to, or minimum balance of the flat
+///     rewards (U256), zero for proportional rewards
+///   - L (F): identifier of the mode of the leaf, see `leaf_mode_id`
 #[derive(Debug)]
 pub struct PublicInputs<'input, T: Clone> {
     pub inputs: &'input [T],
@@ -40,7 +49,18 @@ impl<'a, T: Clone + Copy> PublicInputs<'a, T> {
         Self::QUERY_RESULT_OFFSET + Self::QUERY_RESULT_LEN;
     pub(crate) const QUERY_REWARDS_RATE_LEN: usize = PACKED_U256_LEN;
 
-    pub const TOTAL_LEN: usize = Self::QUERY_REWARDS_RATE_OFFSET + Self::QUERY_REWARDS_RATE_LEN;
+    pub(crate) const IS_PRESENT_OFFSET: usize =
+        Self::QUERY_REWARDS_RATE_OFFSET + Self::QUERY_REWARDS_RATE_LEN;
+    pub(crate) const IS_ZERO_BALANCE_OFFSET: usize = Self::IS_PRESENT_OFFSET + 1;
+    pub(crate) const MAX_REWARDS_RATE_OFFSET: usize = Self::IS_ZERO_BALANCE_OFFSET + 1;
+    pub(crate) const MAX_REWARDS_RATE_LEN: usize = PACKED_U256_LEN;
+
+    pub(crate) const THRESHOLD_OFFSET: usize =
+        Self::MAX_REWARDS_RATE_OFFSET + Self::MAX_REWARDS_RATE_LEN;
+    pub(crate) const THRESHOLD_LEN: usize = PACKED_U256_LEN;
+    pub(crate) const LEAF_MODE_OFFSET: usize = Self::THRESHOLD_OFFSET + Self::THRESHOLD_LEN;
+
+    pub const TOTAL_LEN: usize = Self::LEAF_MODE_OFFSET + 1;
 
     /// Creates a representation of the public inputs from the provided slice.
     ///
@@ -63,11 +83,21 @@ impl<'a, T: Clone + Copy> PublicInputs<'a, T> {
         x: &PackedAddressTarget,
         value: &UInt256Target,
         reward_rate: &UInt256Target,
+        is_present: BoolTarget,
+        is_zero_balance: BoolTarget,
+        max_reward_rate: &UInt256Target,
+        threshold: &UInt256Target,
+        leaf_mode: Target,
     ) {
         b.register_public_inputs(&c.elements);
         x.register_as_public_input(b);
         b.register_public_input_u256(value);
         b.register_public_input_u256(reward_rate);
+        b.register_public_input(is_present.target);
+        b.register_public_input(is_zero_balance.target);
+        b.register_public_input_u256(max_reward_rate);
+        b.register_public_input_u256(threshold);
+        b.register_public_input(leaf_mode);
     }
 
     pub(crate) fn root_hash_raw(&self) -> &[T] {
@@ -84,6 +114,22 @@ impl<'a, T: Clone + Copy> PublicInputs<'a, T> {
         &self.inputs[Self::QUERY_REWARDS_RATE_OFFSET
             ..Self::QUERY_REWARDS_RATE_OFFSET + Self::QUERY_REWARDS_RATE_LEN]
     }
+    pub(crate) fn is_present_raw(&self) -> T {
+        self.inputs[Self::IS_PRESENT_OFFSET]
+    }
+    pub(crate) fn is_zero_balance_raw(&self) -> T {
+        self.inputs[Self::IS_ZERO_BALANCE_OFFSET]
+    }
+    pub(crate) fn max_rewards_rate_raw(&self) -> &[T] {
+        &self.inputs[Self::MAX_REWARDS_RATE_OFFSET
+            ..Self::MAX_REWARDS_RATE_OFFSET + Self::MAX_REWARDS_RATE_LEN]
+    }
+    pub(crate) fn threshold_raw(&self) -> &[T] {
+        &self.inputs[Self::THRESHOLD_OFFSET..Self::THRESHOLD_OFFSET + Self::THRESHOLD_LEN]
+    }
+    pub(crate) fn leaf_mode_raw(&self) -> T {
+        self.inputs[Self::LEAF_MODE_OFFSET]
+    }
 }
 
 impl<'a> PublicInputs<'a, Target> {
@@ -109,6 +155,23 @@ impl<'a> PublicInputs<'a, Target> {
         )
         .expect("invalid length of slice inputs")
     }
+    pub fn is_present(&self) -> BoolTarget {
+        BoolTarget::new_unsafe(self.is_present_raw())
+    }
+    pub fn is_zero_balance(&self) -> BoolTarget {
+        BoolTarget::new_unsafe(self.is_zero_balance_raw())
+    }
+    pub fn max_rewards_rate(&self) -> UInt256Target {
+        UInt256Target::new_from_target_limbs(self.max_rewards_rate_raw())
+            .expect("invalid length of slice inputs")
+    }
+    pub fn threshold(&self) -> UInt256Target {
+        UInt256Target::new_from_target_limbs(self.threshold_raw())
+            .expect("invalid length of slice inputs")
+    }
+    pub fn leaf_mode(&self) -> Target {
+        self.leaf_mode_raw()
+    }
 }
 
 impl<'a> PublicInputs<'a, GoldilocksField> {
@@ -124,21 +187,54 @@ impl<'a> PublicInputs<'a, GoldilocksField> {
     pub fn query_rewards_rate(&self) -> U256 {
         convert_u32_fields_to_u256(self.query_rewards_rate_raw())
     }
+    /// Whether the queried address is present in the storage tree
+    pub fn is_present(&self) -> bool {
+        self.is_present_raw() == GoldilocksField::ONE
+    }
+    /// Whether the queried address is present in the storage tree with a zero
+    /// balance, which is distinct from the address being absent
+    pub fn is_zero_balance(&self) -> bool {
+        self.is_zero_balance_raw() == GoldilocksField::ONE
+    }
+    /// Upper bound the rewards rate has been checked against
+    pub fn max_rewards_rate(&self) -> U256 {
+        convert_u32_fields_to_u256(self.max_rewards_rate_raw())
+    }
+    /// Threshold the balance is compared to, or minimum balance of the flat rewards
+    pub fn threshold(&self) -> U256 {
+        convert_u32_fields_to_u256(self.threshold_raw())
+    }
+    /// Identifier of the mode of the leaf, see `leaf_mode_id`
+    pub fn leaf_mode(&self) -> u8 {
+        self.leaf_mode_raw().to_canonical_u64() as u8
+    }
 }
 
 #[cfg(test)]
 mod test {
     use mrp2_utils::utils::convert_u8_slice_to_u32_fields;
-    use plonky2::field::types::Field;
 
     use super::*;
     impl<'a> PublicInputs<'a, GoldilocksField> {
-        /// Writes the parts of the public inputs into the provided target array.
+        /// Writes the parts of the public inputs into the provided target array,
+        /// for a queried address present in the tree with a non-zero balance.
         pub fn from_parts(
             root_hash: &[GoldilocksField; PublicInputs::<()>::C_LEN],
             owner: &[GoldilocksField; PublicInputs::<()>::QUERY_ADDRESS_LEN],
             value: U256,
             reward_rate: U256,
+        ) -> [GoldilocksField; Self::TOTAL_LEN] {
+            Self::from_parts_with_flags(root_hash, owner, value, reward_rate, true, false)
+        }
+
+        /// Writes the parts of the public inputs into the provided target array.
+        pub fn from_parts_with_flags(
+            root_hash: &[GoldilocksField; PublicInputs::<()>::C_LEN],
+            owner: &[GoldilocksField; PublicInputs::<()>::QUERY_ADDRESS_LEN],
+            value: U256,
+            reward_rate: U256,
+            is_present: bool,
+            is_zero_balance: bool,
         ) -> [GoldilocksField; Self::TOTAL_LEN] {
             let mut values = [GoldilocksField::ZERO; Self::TOTAL_LEN];
             values[Self::C_OFFSET..Self::C_OFFSET + Self::C_LEN].copy_from_slice(root_hash);
@@ -155,6 +251,12 @@ mod test {
             values[Self::QUERY_REWARDS_RATE_OFFSET
                 ..Self::QUERY_REWARDS_RATE_OFFSET + Self::QUERY_REWARDS_RATE_LEN]
                 .copy_from_slice(&u256_to_fields(reward_rate));
+            values[Self::IS_PRESENT_OFFSET] = GoldilocksField::from_bool(is_present);
+            values[Self::IS_ZERO_BALANCE_OFFSET] = GoldilocksField::from_bool(is_zero_balance);
+            values[Self::MAX_REWARDS_RATE_OFFSET
+                ..Self::MAX_REWARDS_RATE_OFFSET + Self::MAX_REWARDS_RATE_LEN]
+                .copy_from_slice(&u256_to_fields(U256::MAX));
+            // the threshold and the mode are zero for proportional rewards
             values
         }
     }
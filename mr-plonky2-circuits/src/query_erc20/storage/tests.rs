@@ -2,13 +2,13 @@ use std::array;
 
 use super::{
     inner::{InnerNodeCircuit, InnerNodeWires},
-    leaf::{LeafCircuit, LeafWires, HASH_PREFIX},
+    leaf::{LeafCircuit, LeafMode, LeafWires, RewardFormula, HASH_PREFIX},
     public_inputs::PublicInputs,
     CircuitInput, Parameters,
 };
 use crate::{api::lpn_storage::intermediate_node_hash, storage::lpn::leaf_hash_for_mapping};
 use crate::{
-    api::ProofWithVK,
+    api::{default_config, tests::dummy_proof_with_vk, ProofWithVK},
     utils::{convert_u8_slice_to_u32_fields, ToFields},
 };
 use ethers::prelude::{Address, U256};
@@ -17,6 +17,7 @@ use mrp2_test_utils::circuit::{run_circuit, UserCircuit};
 use mrp2_utils::{
     eth::left_pad32,
     types::{MAPPING_KEY_LEN, PACKED_MAPPING_KEY_LEN, PACKED_VALUE_LEN},
+    u256::{CircuitBuilderU256, UInt256Target},
     utils::convert_u8_to_u32_slice,
 };
 use plonky2::field::types::Sample;
@@ -60,6 +61,43 @@ impl UserCircuit<GoldilocksField, 2> for TestLeafCircuit {
     }
 }
 
+#[derive(Clone, Debug)]
+struct TestThresholdLeafCircuit {
+    c: LeafCircuit,
+}
+
+impl UserCircuit<GoldilocksField, 2> for TestThresholdLeafCircuit {
+    type Wires = LeafWires;
+
+    fn build(b: &mut CircuitBuilder<GoldilocksField, 2>) -> Self::Wires {
+        LeafCircuit::build_with_mode(b, LeafMode::Threshold)
+    }
+
+    fn prove(&self, pw: &mut PartialWitness<GoldilocksField>, wires: &Self::Wires) {
+        self.c.assign(pw, wires);
+    }
+}
+
+/// Flat reward granted to each holder in the tests of `TestFlatLeafCircuit`
+const FLAT_REWARD: u64 = 1000;
+
+#[derive(Clone, Debug)]
+struct TestFlatLeafCircuit {
+    c: LeafCircuit,
+}
+
+impl UserCircuit<GoldilocksField, 2> for TestFlatLeafCircuit {
+    type Wires = LeafWires;
+
+    fn build(b: &mut CircuitBuilder<GoldilocksField, 2>) -> Self::Wires {
+        LeafCircuit::build_with_formula(b, RewardFormula::FlatPerHolder)
+    }
+
+    fn prove(&self, pw: &mut PartialWitness<GoldilocksField>, wires: &Self::Wires) {
+        self.c.assign(pw, wires);
+    }
+}
+
 #[derive(Clone, Debug)]
 struct TestInnerNodeCircuit<'a> {
     c: InnerNodeCircuit,
@@ -109,6 +147,8 @@ fn test_query_erc20_storage_leaf_circuit() {
             value,
             total_supply,
             rewards_rate,
+            max_rewards_rate: None,
+            ..Default::default()
         },
     };
 
@@ -126,6 +166,8 @@ fn test_query_erc20_storage_leaf_circuit() {
     assert_eq!(pi.query_user_address(), address);
     assert_eq!(pi.query_results(), exp_query_results);
     assert_eq!(pi.query_rewards_rate(), rewards_rate);
+    assert_eq!(pi.threshold(), U256::zero());
+    assert_eq!(pi.leaf_mode(), 0);
 
     // check that the circuit fails if there is an overflow
     let value = U256::max_value();
@@ -137,6 +179,8 @@ fn test_query_erc20_storage_leaf_circuit() {
             value,
             total_supply,
             rewards_rate,
+            max_rewards_rate: None,
+            ..Default::default()
         },
     };
 
@@ -156,6 +200,8 @@ fn test_query_erc20_storage_leaf_circuit() {
             value,
             total_supply,
             rewards_rate,
+            max_rewards_rate: None,
+            ..Default::default()
         },
     };
 
@@ -165,6 +211,336 @@ fn test_query_erc20_storage_leaf_circuit() {
     );
 }
 
+#[test]
+fn test_query_erc20_storage_leaf_threshold() {
+    let mut rng = thread_rng();
+    let address = Address::random();
+    let threshold = U256::from(rng.gen::<u128>()) + 1;
+
+    for (value, meets_threshold) in [
+        (threshold + rng.gen::<u64>(), true),
+        (threshold, true),
+        (threshold - 1, false),
+        (U256::zero(), false),
+    ] {
+        let test_circuit = TestThresholdLeafCircuit {
+            c: LeafCircuit::new_threshold(address, address, value, threshold),
+        };
+        let proof = run_circuit::<_, D, C, _>(test_circuit);
+        let pi = PublicInputs::<GoldilocksField>::from_slice(&proof.public_inputs);
+
+        // V is the boolean result and T the threshold, so the balance is exposed
+        // only in the hash
+        assert_eq!(proof.public_inputs.len(), PublicInputs::<Target>::TOTAL_LEN);
+        let mut bytes = [0; 32];
+        value.to_big_endian(&mut bytes);
+        let hash_bytes = leaf_hash_for_mapping(&address.to_fixed_bytes(), &bytes);
+        assert_eq!(pi.root_hash(), HashOut::from_bytes(&hash_bytes));
+        assert_eq!(pi.query_user_address(), address);
+        assert_eq!(pi.query_results(), U256::from(meets_threshold as u8));
+        assert_eq!(pi.query_rewards_rate(), U256::zero());
+        assert_eq!(pi.threshold(), threshold);
+        assert_eq!(pi.leaf_mode(), 2);
+    }
+
+    // a leaf of another user never meets the threshold
+    let test_circuit = TestThresholdLeafCircuit {
+        c: LeafCircuit::new_threshold(Address::random(), address, threshold, threshold),
+    };
+    let proof = run_circuit::<_, D, C, _>(test_circuit);
+    let pi = PublicInputs::<GoldilocksField>::from_slice(&proof.public_inputs);
+    assert_eq!(pi.query_results(), U256::zero());
+}
+
+#[test]
+fn test_query_erc20_storage_leaf_flat_per_holder() {
+    let mut rng = thread_rng();
+    let address = Address::random();
+    let min_balance = U256::from(rng.gen::<u128>()) + 1;
+
+    for (value, reward) in [
+        // a holder whose balance exceeds the minimum gets the flat reward, regardless
+        // of its balance
+        (min_balance + 1, FLAT_REWARD),
+        (U256::MAX, FLAT_REWARD),
+        // a holder not exceeding the minimum gets nothing
+        (min_balance, 0),
+        (min_balance - 1, 0),
+    ] {
+        let test_circuit = TestFlatLeafCircuit {
+            c: LeafCircuit::new_flat_per_holder(
+                address,
+                address,
+                value,
+                FLAT_REWARD.into(),
+                min_balance,
+            ),
+        };
+        let proof = run_circuit::<_, D, C, _>(test_circuit);
+        let pi = PublicInputs::<GoldilocksField>::from_slice(&proof.public_inputs);
+        assert_eq!(pi.query_user_address(), address);
+        assert_eq!(pi.query_results(), U256::from(reward));
+        assert_eq!(pi.query_rewards_rate(), U256::from(FLAT_REWARD));
+        assert_eq!(pi.threshold(), min_balance);
+        assert_eq!(pi.leaf_mode(), 1);
+    }
+
+    // a leaf of another user gets nothing
+    let test_circuit = TestFlatLeafCircuit {
+        c: LeafCircuit::new_flat_per_holder(
+            Address::random(),
+            address,
+            U256::MAX,
+            FLAT_REWARD.into(),
+            min_balance,
+        ),
+    };
+    let proof = run_circuit::<_, D, C, _>(test_circuit);
+    let pi = PublicInputs::<GoldilocksField>::from_slice(&proof.public_inputs);
+    assert_eq!(pi.query_results(), U256::zero());
+}
+
+#[test]
+fn test_query_erc20_storage_leaf_zero_balance() {
+    let queried = Address::random();
+    let other = Address::random();
+    let leaf = |address, value| TestLeafCircuit {
+        c: LeafCircuit {
+            query_address: queried,
+            address,
+            value,
+            total_supply: U256::from(1000),
+            rewards_rate: U256::from(3),
+            max_rewards_rate: None,
+            ..Default::default()
+        },
+    };
+
+    // the queried address holds 0 tokens
+    let proof = run_circuit::<_, D, C, _>(leaf(queried, U256::zero()));
+    let present_pi = PublicInputs::<GoldilocksField>::from_slice(&proof.public_inputs);
+    assert!(present_pi.is_present());
+    assert!(present_pi.is_zero_balance());
+    assert_eq!(present_pi.query_results(), U256::zero());
+
+    // the queried address isn't in the leaf: the result is the same, but the flags differ
+    let proof = run_circuit::<_, D, C, _>(leaf(other, U256::zero()));
+    let absent_pi = PublicInputs::<GoldilocksField>::from_slice(&proof.public_inputs);
+    assert!(!absent_pi.is_present());
+    assert!(!absent_pi.is_zero_balance());
+    assert_eq!(absent_pi.query_results(), present_pi.query_results());
+
+    // the queried address holds some tokens
+    let proof = run_circuit::<_, D, C, _>(leaf(queried, U256::from(100)));
+    let pi = PublicInputs::<GoldilocksField>::from_slice(&proof.public_inputs);
+    assert!(pi.is_present());
+    assert!(!pi.is_zero_balance());
+}
+
+/// The leaf packs the same big-endian value bytes in two different ways: big-endian
+/// u32 limbs for the hash, and little-endian limbs, after reversing the bytes, for
+/// the uint256 arithmetic. Check that both always refer to the same integer, so
+/// that swapping the packing order in either path is caught.
+#[test]
+fn u256_boundary_consistency() {
+    let mut rng = thread_rng();
+    let address = Address::random();
+    let boundaries = [
+        U256::zero(),
+        U256::one(),
+        U256::from(u32::MAX),
+        U256::from(u32::MAX) + 1,
+        U256::from(u64::MAX),
+        U256::one() << 128,
+        U256::one() << 255,
+        U256::MAX,
+    ];
+    let random_values = (0..4).map(|_| U256::from_big_endian(&rng.gen::<[u8; 32]>()));
+
+    for value in boundaries.into_iter().chain(random_values) {
+        let mut bytes = [0; 32];
+        value.to_big_endian(&mut bytes);
+        let mut reversed_bytes = bytes;
+        reversed_bytes.reverse();
+
+        // native packing: the uint256 limbs are the little-endian packing of the
+        // reversed bytes, while the hash input is the packing of the bytes as they are
+        let limbs: Vec<F> = value.to_fields();
+        assert_eq!(
+            limbs,
+            convert_u8_to_u32_slice(&reversed_bytes)
+                .into_iter()
+                .map(F::from_canonical_u32)
+                .collect_vec()
+        );
+
+        // with R = totalSupply = 1, the result exposed by the leaf is the value itself
+        let test_circuit = TestLeafCircuit {
+            c: LeafCircuit {
+                query_address: address,
+                address,
+                value,
+                total_supply: U256::one(),
+                rewards_rate: U256::one(),
+                max_rewards_rate: None,
+                ..Default::default()
+            },
+        };
+        let proof = run_circuit::<_, D, C, _>(test_circuit);
+        let pi = PublicInputs::<GoldilocksField>::from_slice(&proof.public_inputs);
+
+        assert_eq!(
+            pi.query_results(),
+            value,
+            "uint256 packing of the leaf value is inconsistent for {value}"
+        );
+        let hash_bytes = leaf_hash_for_mapping(&address.to_fixed_bytes(), &bytes);
+        assert_eq!(
+            pi.root_hash(),
+            HashOut::from_bytes(&hash_bytes),
+            "hash packing of the leaf value is inconsistent for {value}"
+        );
+        // the hash must depend on the byte order, otherwise a swapped packing
+        // would go unnoticed
+        if bytes != reversed_bytes {
+            let swapped_hash_bytes =
+                leaf_hash_for_mapping(&address.to_fixed_bytes(), &reversed_bytes);
+            assert_ne!(pi.root_hash(), HashOut::from_bytes(&swapped_hash_bytes));
+        }
+    }
+}
+
+#[test]
+fn test_query_erc20_storage_leaf_would_overflow() {
+    let address = Address::random();
+    let leaf = |value, total_supply, rewards_rate| LeafCircuit {
+        query_address: address,
+        address,
+        value,
+        total_supply,
+        rewards_rate,
+        max_rewards_rate: None,
+        ..Default::default()
+    };
+
+    assert!(!leaf(U256::from(42), U256::from(100), U256::from(3)).would_overflow());
+    // the product fits in 256 bits
+    assert!(!leaf(U256::MAX >> 16, U256::MAX, U256::from(u16::MAX)).would_overflow());
+    // the product overflows
+    assert!(leaf(U256::MAX, U256::from(100), U256::from(2)).would_overflow());
+    assert!(leaf(U256::MAX >> 16, U256::MAX, U256::from(u32::MAX)).would_overflow());
+    // the total supply is zero
+    assert!(leaf(U256::one(), U256::zero(), U256::one()).would_overflow());
+    assert!(leaf(U256::zero(), U256::zero(), U256::zero()).would_overflow());
+    // the rewards rate is above its bound
+    assert!(leaf(U256::from(42), U256::from(100), U256::from(3))
+        .with_max_rewards_rate(U256::from(2))
+        .would_overflow());
+    // the threshold and flat modes can always be proven
+    for (mode, formula) in [
+        (LeafMode::Threshold, RewardFormula::Proportional),
+        (LeafMode::Rewards, RewardFormula::FlatPerHolder),
+    ] {
+        let c = LeafCircuit {
+            mode,
+            formula,
+            ..leaf(U256::MAX, U256::zero(), U256::MAX)
+        }
+        .with_max_rewards_rate(U256::one());
+        assert!(!c.would_overflow());
+    }
+}
+
+#[test]
+fn test_query_erc20_storage_leaf_max_rewards_rate() {
+    let address = Address::random();
+    let leaf = LeafCircuit {
+        query_address: address,
+        address,
+        value: U256::from(42),
+        total_supply: U256::from(100),
+        rewards_rate: U256::from(3),
+        max_rewards_rate: None,
+        ..Default::default()
+    };
+
+    // an unbounded rate exposes the maximum U256 as bound
+    let proof = run_circuit::<_, D, C, _>(TestLeafCircuit { c: leaf.clone() });
+    let pi = PublicInputs::<GoldilocksField>::from_slice(&proof.public_inputs);
+    assert_eq!(pi.max_rewards_rate(), U256::MAX);
+
+    // a rate equal to the bound is accepted
+    let proof = run_circuit::<_, D, C, _>(TestLeafCircuit {
+        c: leaf.clone().with_max_rewards_rate(U256::from(3)),
+    });
+    let pi = PublicInputs::<GoldilocksField>::from_slice(&proof.public_inputs);
+    assert_eq!(pi.max_rewards_rate(), U256::from(3));
+    assert_eq!(pi.query_results(), U256::from(1));
+
+    // a rate above the bound fails the constraint, also for large values, as
+    // long as the multiplication doesn't overflow, and it's detected before proving
+    for value in [U256::from(42), U256::MAX >> 32] {
+        let c = LeafCircuit {
+            value,
+            rewards_rate: U256::from(u32::MAX),
+            ..leaf.clone()
+        }
+        .with_max_rewards_rate(U256::from(u16::MAX));
+        assert!(!c.value.overflowing_mul(c.rewards_rate).1);
+        assert!(c.would_overflow());
+        let res = std::panic::catch_unwind(|| run_circuit::<_, D, C, _>(TestLeafCircuit { c }));
+        assert!(res.is_err(), "rewards rate above the bound not detected");
+    }
+
+    // the bound only applies to proportional rewards: a threshold or a minimum
+    // balance above it is accepted
+    let threshold = U256::from(u32::MAX);
+    let threshold_leaf = LeafCircuit::new_threshold(address, address, threshold, threshold)
+        .with_max_rewards_rate(U256::from(u16::MAX));
+    assert!(!threshold_leaf.would_overflow());
+    let proof = run_circuit::<_, D, C, _>(TestThresholdLeafCircuit { c: threshold_leaf });
+    let pi = PublicInputs::<GoldilocksField>::from_slice(&proof.public_inputs);
+    assert_eq!(pi.query_results(), U256::one());
+    let flat_leaf =
+        LeafCircuit::new_flat_per_holder(address, address, U256::MAX, threshold, threshold)
+            .with_max_rewards_rate(U256::from(u16::MAX));
+    assert!(!flat_leaf.would_overflow());
+    let proof = run_circuit::<_, D, C, _>(TestFlatLeafCircuit { c: flat_leaf });
+    let pi = PublicInputs::<GoldilocksField>::from_slice(&proof.public_inputs);
+    assert_eq!(pi.query_results(), threshold);
+}
+
+/// Number of gates of the leaf circuit built with `mode` and `formula`, allocating
+/// the u256 inputs with `add_u256`
+fn leaf_gate_count(
+    mode: LeafMode,
+    formula: RewardFormula,
+    add_u256: fn(&mut CircuitBuilder<GoldilocksField, 2>) -> UInt256Target,
+) -> usize {
+    let mut b = CircuitBuilder::new(default_config());
+    LeafCircuit::build_with_u256_allocator(&mut b, mode, formula, add_u256);
+    // the lookup and lookup table gates are otherwise only placed when building
+    // the circuit
+    b.add_all_lookups();
+    b.num_gates()
+}
+
+#[test]
+fn test_query_erc20_storage_leaf_gate_count() {
+    for (mode, formula) in [
+        (LeafMode::Rewards, RewardFormula::Proportional),
+        (LeafMode::Rewards, RewardFormula::FlatPerHolder),
+        (LeafMode::Threshold, RewardFormula::Proportional),
+    ] {
+        let bits_gates = leaf_gate_count(mode, formula, |b| b.add_virtual_u256());
+        let lookup_gates = leaf_gate_count(mode, formula, |b| b.add_virtual_u256_with_lookup());
+        assert!(
+            lookup_gates < bits_gates,
+            "leaf {mode:?}/{formula:?} with lookups has {lookup_gates} gates, {bits_gates} without"
+        );
+    }
+}
+
 #[test]
 fn test_query_erc20_storage_inner_node_circuit() {
     let mut rng = thread_rng();
@@ -191,6 +567,11 @@ fn test_query_erc20_storage_inner_node_circuit() {
     assert_eq!(pi.query_user_address(), child_pi.query_user_address());
     assert_eq!(pi.query_results(), child_pi.query_results());
     assert_eq!(pi.query_rewards_rate(), child_pi.query_rewards_rate());
+    assert_eq!(pi.is_present_raw(), child_pi.is_present_raw());
+    assert_eq!(pi.is_zero_balance_raw(), child_pi.is_zero_balance_raw());
+    assert_eq!(pi.max_rewards_rate(), child_pi.max_rewards_rate());
+    assert_eq!(pi.threshold(), child_pi.threshold());
+    assert_eq!(pi.leaf_mode(), child_pi.leaf_mode());
 
     let test_circuit = TestInnerNodeCircuit {
         c: InnerNodeCircuit {
@@ -211,6 +592,11 @@ fn test_query_erc20_storage_inner_node_circuit() {
     assert_eq!(pi.query_user_address(), child_pi.query_user_address());
     assert_eq!(pi.query_results(), child_pi.query_results());
     assert_eq!(pi.query_rewards_rate(), child_pi.query_rewards_rate());
+    assert_eq!(pi.is_present_raw(), child_pi.is_present_raw());
+    assert_eq!(pi.is_zero_balance_raw(), child_pi.is_zero_balance_raw());
+    assert_eq!(pi.max_rewards_rate(), child_pi.max_rewards_rate());
+    assert_eq!(pi.threshold(), child_pi.threshold());
+    assert_eq!(pi.leaf_mode(), child_pi.leaf_mode());
 }
 
 #[test]
@@ -236,6 +622,40 @@ fn test_query_erc20_storage_api() {
         .verify(ProofWithVK::deserialize(&leaf).unwrap().proof)
         .unwrap();
 
+    // the threshold and flat leaves are proven with the circuit of their mode
+    let threshold_leaf = params
+        .generate_proof(CircuitInput::new_threshold_leaf(
+            address,
+            address,
+            value,
+            total_supply,
+        ))
+        .unwrap();
+    params
+        .threshold_leaf_circuit
+        .circuit_data()
+        .verify(ProofWithVK::deserialize(&threshold_leaf).unwrap().proof)
+        .unwrap();
+    let flat_leaf = params
+        .generate_proof(CircuitInput::new_flat_per_holder_leaf(
+            address,
+            address,
+            value,
+            rewards_rate,
+            total_supply,
+        ))
+        .unwrap();
+    params
+        .flat_leaf_circuit
+        .circuit_data()
+        .verify(ProofWithVK::deserialize(&flat_leaf).unwrap().proof)
+        .unwrap();
+    for (proof, mode) in [(threshold_leaf, 2), (flat_leaf, 1)] {
+        let proof = ProofWithVK::deserialize(&proof).unwrap();
+        let pi = PublicInputs::<F>::from_slice(&proof.proof().public_inputs);
+        assert_eq!(pi.leaf_mode(), mode);
+    }
+
     let unproved_hash = hash_n_to_hash_no_pad::<F, PoseidonPermutation<_>>(
         &rng.gen::<[u8; 16]>().map(F::from_canonical_u8),
     )
@@ -248,4 +668,66 @@ fn test_query_erc20_storage_api() {
         .circuit_data()
         .verify(ProofWithVK::deserialize(&inner).unwrap().proof)
         .unwrap();
+
+    // leaf and inner node proofs expose the same public inputs, followed by the
+    // digest of the storage circuit set
+    let [leaf, inner] = [&leaf, &inner].map(|proof| ProofWithVK::deserialize(proof).unwrap());
+    assert_eq!(leaf.num_public_inputs(), inner.num_public_inputs());
+    assert!(leaf.num_public_inputs() >= PublicInputs::<F>::TOTAL_LEN);
+    assert_eq!(leaf.num_public_inputs(), leaf.proof().public_inputs.len());
+}
+
+#[test]
+fn test_query_erc20_storage_circuit_input_encoding() {
+    let rng = &mut thread_rng();
+    let address = Address::random();
+    let leaf = CircuitInput::new_leaf(
+        address,
+        address,
+        U256::from(rng.gen::<u64>()),
+        U256::from(rng.gen::<u64>()),
+        U256::from(rng.gen::<u16>()),
+    );
+    let threshold_leaf = CircuitInput::new_threshold_leaf(
+        address,
+        address,
+        U256::from(rng.gen::<u64>()),
+        U256::from(rng.gen::<u64>()),
+    );
+    let inner = CircuitInput::Inner(
+        InnerNodeCircuit {
+            proved_is_right: rng.gen(),
+            unproved_hash: HashOut::rand(),
+        },
+        dummy_proof_with_vk([F::rand(); 4]),
+    );
+
+    for input in [leaf, threshold_leaf, inner] {
+        let bytes = input.to_bytes().unwrap();
+        let decoded = CircuitInput::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.to_bytes().unwrap(), bytes);
+        match (input, decoded) {
+            (CircuitInput::Leaf(expected), CircuitInput::Leaf(found)) => {
+                assert_eq!(found.address, expected.address);
+                assert_eq!(found.query_address, expected.query_address);
+                assert_eq!(found.value, expected.value);
+                assert_eq!(found.total_supply, expected.total_supply);
+                assert_eq!(found.rewards_rate, expected.rewards_rate);
+                assert_eq!(found.threshold, expected.threshold);
+                assert_eq!(found.mode, expected.mode);
+                assert_eq!(found.formula, expected.formula);
+            }
+            (CircuitInput::Inner(expected, expected_proof), CircuitInput::Inner(found, proof)) => {
+                assert_eq!(found.proved_is_right, expected.proved_is_right);
+                assert_eq!(found.unproved_hash, expected.unproved_hash);
+                assert_eq!(proof, expected_proof);
+            }
+            _ => panic!("wrong variant decoded"),
+        }
+
+        // an unknown tag is rejected
+        let mut unknown_tag = bytes;
+        unknown_tag[1] = u8::MAX;
+        assert!(CircuitInput::from_bytes(&unknown_tag).is_err());
+    }
 }
@@ -0,0 +1,119 @@
+//! Split a query block range into sub-ranges which could be proven in
+//! parallel and merged afterwards.
+
+use anyhow::{ensure, Result};
+
+/// Split the block range `[min, max]` into sub-ranges aligned on multiples of
+/// `chunk`: every sub-range boundary, except `min` and `max` themselves, is a
+/// multiple of `chunk`. Each returned pair is an inclusive `(min, max)` range,
+/// and the sub-ranges are sorted in ascending order.
+pub fn plan_subranges(min: u32, max: u32, chunk: usize) -> Vec<(u32, u32)> {
+    assert!(chunk > 0, "chunk size must be greater than 0");
+    assert!(
+        min <= max,
+        "min block number must not exceed max block number"
+    );
+
+    let chunk = chunk as u64;
+    let max = max as u64;
+    let mut ranges = vec![];
+    let mut start = min as u64;
+    while start <= max {
+        // End of the aligned chunk containing `start`.
+        let end = ((start / chunk + 1) * chunk - 1).min(max);
+        ranges.push((start as u32, end as u32));
+        start = end + 1;
+    }
+
+    ranges
+}
+
+/// Check that the sub-ranges tile the block range `[min, max]` exactly, i.e.
+/// they are sorted, contiguous, non-overlapping, and cover every block of the
+/// range.
+pub fn validate_subranges(min: u32, max: u32, ranges: &[(u32, u32)]) -> Result<()> {
+    ensure!(!ranges.is_empty(), "no sub-ranges provided");
+    ensure!(
+        ranges[0].0 == min,
+        "first sub-range starts at {} instead of {min}",
+        ranges[0].0
+    );
+    ensure!(
+        ranges[ranges.len() - 1].1 == max,
+        "last sub-range ends at {} instead of {max}",
+        ranges[ranges.len() - 1].1
+    );
+    for &(start, end) in ranges {
+        ensure!(start <= end, "invalid sub-range ({start}, {end})");
+    }
+    for pair in ranges.windows(2) {
+        let (prev, next) = (pair[0], pair[1]);
+        ensure!(
+            prev.1 as u64 + 1 == next.0 as u64,
+            "sub-ranges {prev:?} and {next:?} are not contiguous"
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Check that each block of `[min, max]` is covered by exactly one sub-range.
+    fn check_full_coverage(min: u32, max: u32, ranges: &[(u32, u32)]) {
+        for block in min..=max {
+            let count = ranges
+                .iter()
+                .filter(|(start, end)| (*start..=*end).contains(&block))
+                .count();
+            assert_eq!(count, 1, "block {block} covered {count} times");
+        }
+    }
+
+    #[test]
+    fn test_plan_subranges_even_division() {
+        let (min, max) = (100, 399);
+        let ranges = plan_subranges(min, max, 100);
+
+        assert_eq!(ranges, vec![(100, 199), (200, 299), (300, 399)]);
+        validate_subranges(min, max, &ranges).unwrap();
+        check_full_coverage(min, max, &ranges);
+    }
+
+    #[test]
+    fn test_plan_subranges_uneven_division() {
+        let (min, max) = (5594942, 5595951);
+        let ranges = plan_subranges(min, max, 256);
+
+        // Inner boundaries are aligned on the chunk size.
+        ranges[1..]
+            .iter()
+            .for_each(|(start, _)| assert_eq!(start % 256, 0));
+        ranges
+            .iter()
+            .for_each(|(start, end)| assert!(end - start < 256));
+        validate_subranges(min, max, &ranges).unwrap();
+        check_full_coverage(min, max, &ranges);
+
+        // A single block range
+        assert_eq!(plan_subranges(7, 7, 4), vec![(7, 7)]);
+        // The range ends on the last possible block.
+        let ranges = plan_subranges(u32::MAX - 10, u32::MAX, 4);
+        validate_subranges(u32::MAX - 10, u32::MAX, &ranges).unwrap();
+    }
+
+    #[test]
+    fn test_validate_subranges_failures() {
+        // Gap between sub-ranges
+        assert!(validate_subranges(0, 9, &[(0, 3), (5, 9)]).is_err());
+        // Overlapping sub-ranges
+        assert!(validate_subranges(0, 9, &[(0, 5), (5, 9)]).is_err());
+        // Not covering the full range
+        assert!(validate_subranges(0, 9, &[(0, 4), (5, 8)]).is_err());
+        assert!(validate_subranges(0, 9, &[(1, 4), (5, 9)]).is_err());
+        // Empty sub-ranges
+        assert!(validate_subranges(0, 9, &[]).is_err());
+    }
+}
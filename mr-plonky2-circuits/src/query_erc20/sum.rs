@@ -0,0 +1,372 @@
+//! This circuit proves that the results of two independent query ERC20
+//! revelation proofs sum up to a claimed total, e.g., to check that the rewards
+//! computed for two campaigns match the overall amount paid. Both revelation
+//! proofs must have been computed over the same block DB, i.e., they must
+//! expose the same latest block header, and their results must have been
+//! summed over the blocks, since adding minimum or maximum results is meaningless.
+//! The public inputs of the proof are the block header `H`, the claimed total
+//! `T`, as a packed uint256, and the query commitments `C1` and `C2` of the two
+//! revelation proofs, which identify the queries being summed.
+
+use anyhow::Result;
+use ethers::types::U256;
+use mrp2_utils::{
+    serialization::{deserialize, serialize},
+    u256::{CircuitBuilderU256, UInt256Target, WitnessWriteU256},
+};
+use plonky2::{
+    iop::{
+        target::Target,
+        witness::{PartialWitness, WitnessWrite},
+    },
+    plonk::{
+        circuit_builder::CircuitBuilder,
+        circuit_data::{CircuitData, VerifierCircuitData},
+        proof::{ProofWithPublicInputs, ProofWithPublicInputsTarget},
+    },
+};
+use serde::{Deserialize, Serialize};
+
+use crate::api::{default_config, serialize_proof, verify_proof_fixed_circuit, C, D, F};
+
+use super::{block::aggregation::AggregationKind, revelation::RevelationPublicInputs};
+
+/// Wires of the sum circuit
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SumWires {
+    /// Claimed total of the query results
+    total: UInt256Target,
+}
+
+/// Circuit proving the query results of two revelation proofs sum up to `total`
+#[derive(Clone, Debug)]
+pub struct SumCircuit {
+    /// Claimed total of the query results
+    pub total: U256,
+}
+
+impl SumCircuit {
+    /// Build the circuit over the public inputs of the two revelation proofs,
+    /// which are expected to be exactly `RevelationPublicInputs::<Target, L>::total_len()`
+    pub fn build<const L: usize>(
+        b: &mut CircuitBuilder<F, D>,
+        first_pi: &[Target],
+        second_pi: &[Target],
+    ) -> SumWires {
+        let zero = b.zero();
+        let first_pi = RevelationPublicInputs::<Target, L>::from(first_pi);
+        let second_pi = RevelationPublicInputs::<Target, L>::from(second_pi);
+
+        // Both results must be computed over the same block DB
+        let block_header = first_pi.block_header();
+        block_header.enforce_equal(b, &second_pi.block_header());
+
+        // Both results must be sums over the blocks
+        let sum_mode = b.constant(AggregationKind::Sum.to_field());
+        b.connect(first_pi.aggregation_mode(), sum_mode);
+        b.connect(second_pi.aggregation_mode(), sum_mode);
+
+        // The sum of the results must be equal to the claimed total, without overflow
+        let first_result = UInt256Target::new_from_target_limbs(first_pi.query_results())
+            .expect("invalid length of query results");
+        let second_result = UInt256Target::new_from_target_limbs(second_pi.query_results())
+            .expect("invalid length of query results");
+        let (sum, carry) = b.add_u256(&first_result, &second_result);
+        b.connect(carry.0, zero);
+        let total = b.add_virtual_u256();
+        b.enforce_equal_u256(&sum, &total);
+
+        block_header.register_as_public_input(b);
+        b.register_public_input_u256(&total);
+        b.register_public_inputs(first_pi.query_commitment());
+        b.register_public_inputs(second_pi.query_commitment());
+
+        SumWires { total }
+    }
+
+    pub fn assign(&self, pw: &mut PartialWitness<F>, wires: &SumWires) {
+        pw.set_u256_target(&wires.total, self.total);
+    }
+}
+
+/// Parameters of the sum circuit, verifying two proofs generated by the
+/// revelation circuit for `L` revealed items
+#[derive(Serialize, Deserialize)]
+pub struct Parameters<const L: usize> {
+    sum_wires: SumWires,
+    #[serde(serialize_with = "serialize", deserialize_with = "deserialize")]
+    data: CircuitData<F, C, D>,
+    #[serde(serialize_with = "serialize", deserialize_with = "deserialize")]
+    first_proof: ProofWithPublicInputsTarget<D>,
+    #[serde(serialize_with = "serialize", deserialize_with = "deserialize")]
+    second_proof: ProofWithPublicInputsTarget<D>,
+}
+
+impl<const L: usize> Parameters<L> {
+    /// Build the circuit parameters, given the verifier data of the revelation circuit
+    pub fn build(revelation_vd: &VerifierCircuitData<F, C, D>) -> Self {
+        let mut cb = CircuitBuilder::<F, D>::new(default_config());
+        let first_proof = verify_proof_fixed_circuit(&mut cb, revelation_vd);
+        let second_proof = verify_proof_fixed_circuit(&mut cb, revelation_vd);
+        // the revelation proofs expose an additional query identifier, which is ignored here
+        let num_io = RevelationPublicInputs::<Target, L>::total_len();
+        let sum_wires = SumCircuit::build::<L>(
+            &mut cb,
+            &first_proof.public_inputs[..num_io],
+            &second_proof.public_inputs[..num_io],
+        );
+        let data = cb.build::<C>();
+        Self {
+            sum_wires,
+            data,
+            first_proof,
+            second_proof,
+        }
+    }
+
+    /// Generate a proof that the query results of the two revelation proofs sum up to `total`
+    pub fn generate_proof(
+        &self,
+        first_proof: &ProofWithPublicInputs<F, C, D>,
+        second_proof: &ProofWithPublicInputs<F, C, D>,
+        total: U256,
+    ) -> Result<Vec<u8>> {
+        let mut pw = PartialWitness::<F>::new();
+        pw.set_proof_with_pis_target(&self.first_proof, first_proof);
+        pw.set_proof_with_pis_target(&self.second_proof, second_proof);
+        SumCircuit { total }.assign(&mut pw, &self.sum_wires);
+        let proof = self.data.prove(pw)?;
+        serialize_proof(&proof)
+    }
+
+    pub fn circuit_data(&self) -> &CircuitData<F, C, D> {
+        &self.data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::panic;
+
+    use super::*;
+    use crate::{
+        api::tests::TestDummyCircuit,
+        keccak::PACKED_HASH_LEN,
+        query_erc20::revelation::{num_io, RevelationPublicInputsBuilder},
+        types::HashOutput,
+    };
+    use ethers::types::Address;
+    use mrp2_test_utils::circuit::{run_circuit, UserCircuit};
+    use mrp2_utils::{
+        types::PACKED_U256_LEN,
+        utils::{convert_u32_fields_to_u256, convert_u32_fields_to_u8_vec},
+    };
+    use plonky2::{
+        field::types::Field,
+        iop::witness::{PartialWitness, WitnessWrite},
+    };
+    use rand::{thread_rng, Rng};
+
+    const L: usize = 2;
+
+    fn revelation_pi(
+        block_header: HashOutput,
+        results: U256,
+        query_commitment: HashOutput,
+    ) -> Vec<F> {
+        revelation_pi_with_mode(
+            block_header,
+            results,
+            query_commitment,
+            AggregationKind::Sum,
+        )
+    }
+
+    fn revelation_pi_with_mode(
+        block_header: HashOutput,
+        results: U256,
+        query_commitment: HashOutput,
+        aggregation: AggregationKind,
+    ) -> Vec<F> {
+        RevelationPublicInputsBuilder::<L>::new()
+            .block_number(F::from_canonical_u32(100))
+            .range(F::from_canonical_u32(10))
+            .min_block_number(F::from_canonical_u32(90))
+            .max_block_number(F::from_canonical_u32(99))
+            .smart_contract_address(Address::random())
+            .user_address(Address::random())
+            .mapping_slot(F::from_canonical_u8(3))
+            .mapping_slot_length(F::from_canonical_u8(4))
+            .block_header(block_header)
+            .rewards_rate(U256::from(thread_rng().gen::<u64>()))
+            .query_results(results)
+            .query_commitment(query_commitment)
+            .aggregation_mode(aggregation)
+            .decimals(18)
+            .build()
+            .unwrap()
+            .to_vec()
+    }
+
+    #[derive(Clone, Debug)]
+    struct TestSumCircuit {
+        c: SumCircuit,
+        first_pi: Vec<F>,
+        second_pi: Vec<F>,
+    }
+
+    impl UserCircuit<F, D> for TestSumCircuit {
+        type Wires = (Vec<Target>, Vec<Target>, SumWires);
+
+        fn build(b: &mut CircuitBuilder<F, D>) -> Self::Wires {
+            let first_pi = b.add_virtual_targets(RevelationPublicInputs::<Target, L>::total_len());
+            let second_pi = b.add_virtual_targets(RevelationPublicInputs::<Target, L>::total_len());
+            let wires = SumCircuit::build::<L>(b, &first_pi, &second_pi);
+
+            (first_pi, second_pi, wires)
+        }
+
+        fn prove(&self, pw: &mut PartialWitness<F>, wires: &Self::Wires) {
+            pw.set_target_arr(&wires.0, &self.first_pi);
+            pw.set_target_arr(&wires.1, &self.second_pi);
+            self.c.assign(pw, &wires.2);
+        }
+    }
+
+    #[test]
+    fn test_query_erc20_sum_circuit() {
+        let mut rng = thread_rng();
+        let block_header = rng.gen::<HashOutput>();
+        let first = U256::from(rng.gen::<u128>());
+        let second = U256::from(rng.gen::<u128>());
+        let [first_commitment, second_commitment] = [0; 2].map(|_| rng.gen::<HashOutput>());
+        let first_pi = revelation_pi(block_header, first, first_commitment);
+        let second_pi = revelation_pi(block_header, second, second_commitment);
+
+        let test_circuit = TestSumCircuit {
+            c: SumCircuit {
+                total: first + second,
+            },
+            first_pi: first_pi.clone(),
+            second_pi: second_pi.clone(),
+        };
+        let proof = run_circuit::<F, D, C, _>(test_circuit);
+        let (header_pi, pi) = proof.public_inputs.split_at(PACKED_HASH_LEN);
+        let (total_pi, pi) = pi.split_at(PACKED_U256_LEN);
+        let (first_commitment_pi, second_commitment_pi) = pi.split_at(PACKED_HASH_LEN);
+        assert_eq!(convert_u32_fields_to_u8_vec(header_pi), block_header);
+        assert_eq!(convert_u32_fields_to_u256(total_pi), first + second);
+        assert_eq!(
+            convert_u32_fields_to_u8_vec(first_commitment_pi),
+            first_commitment
+        );
+        assert_eq!(
+            convert_u32_fields_to_u8_vec(second_commitment_pi),
+            second_commitment
+        );
+
+        // a total different from the sum of the results should fail
+        let test_circuit = TestSumCircuit {
+            c: SumCircuit {
+                total: first + second + 1,
+            },
+            first_pi: first_pi.clone(),
+            second_pi: second_pi.clone(),
+        };
+        let result = panic::catch_unwind(|| run_circuit::<F, D, C, _>(test_circuit));
+        assert!(result.is_err(), "a wrong total should fail");
+
+        // results computed over different block DBs should fail
+        let test_circuit = TestSumCircuit {
+            c: SumCircuit {
+                total: first + second,
+            },
+            first_pi,
+            second_pi: revelation_pi(rng.gen::<HashOutput>(), second, second_commitment),
+        };
+        let result = panic::catch_unwind(|| run_circuit::<F, D, C, _>(test_circuit));
+        assert!(result.is_err(), "different block headers should fail");
+
+        // results not summed over the blocks can't be added
+        for (first_mode, second_mode) in [
+            (AggregationKind::Min, AggregationKind::Sum),
+            (AggregationKind::Sum, AggregationKind::Max),
+            (AggregationKind::Max, AggregationKind::Max),
+        ] {
+            let test_circuit = TestSumCircuit {
+                c: SumCircuit {
+                    total: first + second,
+                },
+                first_pi: revelation_pi_with_mode(
+                    block_header,
+                    first,
+                    first_commitment,
+                    first_mode,
+                ),
+                second_pi: revelation_pi_with_mode(
+                    block_header,
+                    second,
+                    second_commitment,
+                    second_mode,
+                ),
+            };
+            let result = panic::catch_unwind(|| run_circuit::<F, D, C, _>(test_circuit));
+            assert!(
+                result.is_err(),
+                "{first_mode:?} and {second_mode:?} results should fail"
+            );
+        }
+
+        // the sum of the results can't overflow, even if the total is the
+        // wrapped sum
+        let (wrapped_total, overflow) = U256::MAX.overflowing_add(second + 1);
+        assert!(overflow);
+        let test_circuit = TestSumCircuit {
+            c: SumCircuit {
+                total: wrapped_total,
+            },
+            first_pi: revelation_pi(block_header, U256::MAX, first_commitment),
+            second_pi: revelation_pi(block_header, second + 1, second_commitment),
+        };
+        let result = panic::catch_unwind(|| run_circuit::<F, D, C, _>(test_circuit));
+        assert!(result.is_err(), "an overflowing sum should fail");
+    }
+
+    #[test]
+    fn test_query_erc20_sum_parameters() {
+        let revelation_circuit = TestDummyCircuit::<{ num_io::<L>() }>::build();
+        let params = Parameters::<L>::build(&revelation_circuit.circuit_data().verifier_data());
+
+        let mut rng = thread_rng();
+        let block_header = rng.gen::<HashOutput>();
+        let first = U256::from(rng.gen::<u128>());
+        let second = U256::from(rng.gen::<u128>());
+        let gen_revelation_proof = |results| {
+            let mut pi = revelation_pi(block_header, results, thread_rng().gen());
+            // query identifier
+            pi.push(F::ONE);
+            revelation_circuit
+                .generate_proof(pi.try_into().unwrap())
+                .unwrap()
+        };
+        let first_proof = gen_revelation_proof(first);
+        let second_proof = gen_revelation_proof(second);
+
+        let proof = params
+            .generate_proof(&first_proof, &second_proof, first + second)
+            .unwrap();
+        params
+            .circuit_data()
+            .verify(bincode::deserialize(&proof).unwrap())
+            .unwrap();
+
+        // proving a wrong total should fail
+        let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            params.generate_proof(&first_proof, &second_proof, first + second - 1)
+        }));
+        assert!(
+            !matches!(result, Ok(Ok(_))),
+            "proving a wrong total should fail"
+        );
+    }
+}
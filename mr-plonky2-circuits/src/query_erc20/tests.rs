@@ -1,39 +1,51 @@
 use super::{
     block::{
+        aggregation::AggregationKind,
         full_node::{FullNodeCircuit, FullNodeWires},
         partial_node::{PartialNodeCircuit, PartialNodeWires},
         BlockPublicInputs as BlockQueryPublicInputs,
     },
     revelation::{
         circuit::{RevelationCircuit, RevelationWires},
-        RevelationPublicInputs,
+        contract_digest, slots_commitment, RevelationPublicInputs,
     },
     state::tests::run_state_circuit_with_slot_and_addresses,
+    transcript::{proof_digest, ProofTranscript, TranscriptCircuit},
+    AggregationPlan, CircuitInput, PublicParameters, StorageCircuitInput,
 };
 use crate::{
-    block::{empty_merkle_root, public_inputs::PublicInputs as BlockDBPublicInputs},
+    api::BlockDBCircuitInfo,
+    block::{
+        empty_merkle_root, public_inputs::PublicInputs as BlockDBPublicInputs,
+        NUM_IVC_PUBLIC_INPUTS,
+    },
     keccak::PACKED_HASH_LEN,
-    types::MAPPING_KEY_LEN,
-    utils::convert_u8_to_u32_slice,
+    types::{address_to_packed_fields, MAPPING_KEY_LEN, PACKED_U256_LEN},
+    utils::{convert_u32_fields_to_u8_vec, convert_u8_to_u32_slice, ToFields},
 };
-use ethers::types::Address;
+use ethers::types::{Address, U256};
 use itertools::Itertools;
 use mrp2_test_utils::circuit::{run_circuit, UserCircuit};
 use plonky2::{
     field::{
         goldilocks_field::GoldilocksField,
-        types::{Field, PrimeField64},
+        types::{Field, PrimeField64, Sample},
     },
-    hash::{hashing::hash_n_to_hash_no_pad, poseidon::PoseidonPermutation},
+    hash::{hash_types::HashOut, hashing::hash_n_to_hash_no_pad, poseidon::PoseidonPermutation},
     iop::{
         target::Target,
         witness::{PartialWitness, WitnessWrite},
     },
     plonk::{
         circuit_builder::CircuitBuilder,
-        config::{GenericConfig, PoseidonGoldilocksConfig},
+        config::{GenericConfig, GenericHashOut, PoseidonGoldilocksConfig},
     },
 };
+use rand::{thread_rng, Rng};
+use recursion_framework::{
+    framework::RecursiveCircuits, framework_testing::TestingRecursiveCircuits,
+};
+use serial_test::serial;
 
 const D: usize = 2;
 type C = PoseidonGoldilocksConfig;
@@ -158,7 +170,7 @@ fn test_query_erc20_main_api() {
     let right_leaf_pi = BlockQueryPublicInputs::<'_, F>::from(right_leaf_proof_io.as_slice());
 
     let middle_proof = run_circuit::<F, D, C, _>(FullNodeCircuitValidator {
-        validated: FullNodeCircuit {},
+        validated: FullNodeCircuit::default(),
         children: &[left_leaf_pi.clone(), right_leaf_pi.clone()],
     });
 
@@ -204,6 +216,7 @@ fn test_query_erc20_main_api() {
     let revelation_circuit = RevelationCircuit {
         query_min_block_number: query_min_block_number.to_canonical_u64() as usize,
         query_max_block_number: query_max_block_number.to_canonical_u64() as usize,
+        decimals: 18,
     };
 
     let final_proof = run_circuit::<F, D, C, _>(RevelationCircuitValidator::<MAX_DEPTH, L> {
@@ -234,4 +247,348 @@ fn test_query_erc20_main_api() {
         pi.query_results(),
         left_leaf_pi.query_results() + right_leaf_pi.query_results()
     );
+    // Check the result scaled by 18 decimals.
+    assert_eq!(pi.decimals(), F::from_canonical_u8(18));
+    let (quotient, remainder) = pi.query_results().div_mod(U256::exp10(18));
+    assert_eq!(pi.scaled_query_results(), quotient);
+    assert_eq!(pi.scaled_query_remainder(), remainder);
+    // Check the exposed digest of the contract.
+    assert_eq!(
+        pi.contract_digest(),
+        contract_digest(&Address::from_slice(&convert_u32_fields_to_u8_vec(
+            root_proof.smart_contract_address()
+        )))
+    );
+    // Check the exposed commitment to the slots read by the query.
+    assert_eq!(
+        pi.slots_commitment(),
+        slots_commitment(&[MAPPING_SLOT as u32, SLOT_LENGTH as u32])
+    );
+}
+
+/// Aggregates, with a full node and then a partial node, 2 subtrees where the
+/// left one spans over missing blocks: the range of the root covers the whole
+/// span, while the aggregated count only accounts for the blocks in the tree.
+#[test]
+fn test_query_erc20_aggregated_count_gapped_tree() {
+    const BLOCK_NUMBER: u32 = 123456;
+    let smart_contract_address = address_to_packed_fields(&Address::random());
+    let user_address = address_to_packed_fields(&Address::random());
+    let rewards_rate = U256::from(42).to_fields().try_into().unwrap();
+    let subtree_pi = |block_number: u32, range: u32, count: u32| {
+        BlockQueryPublicInputs::from_parts(
+            F::from_canonical_u32(block_number),
+            F::from_canonical_u32(range),
+            F::from_canonical_u32(count),
+            HashOut::rand(),
+            &smart_contract_address,
+            &user_address,
+            F::from_canonical_u8(24),
+            F::from_canonical_u8(42),
+            &U256::from(count).to_fields().try_into().unwrap(),
+            &rewards_rate,
+            &U256::MAX.to_fields().try_into().unwrap(),
+            F::from_canonical_u8(3),
+            AggregationKind::Sum,
+            HashOut::ZERO,
+            F::ZERO,
+            HashOut::ZERO,
+            &[F::ZERO; PACKED_U256_LEN],
+            F::ZERO,
+        )
+    };
+    // the left subtree spans 4 blocks, only 2 of which are in the tree
+    let left = subtree_pi(BLOCK_NUMBER, 4, 2);
+    let right = subtree_pi(BLOCK_NUMBER + 2, 2, 2);
+
+    let middle_proof = run_circuit::<F, D, C, _>(FullNodeCircuitValidator {
+        validated: FullNodeCircuit::default(),
+        children: &[
+            BlockQueryPublicInputs::from(left.as_slice()),
+            BlockQueryPublicInputs::from(right.as_slice()),
+        ],
+    });
+    let top_proof = run_circuit::<F, D, C, _>(PartialNodeCircuitValidator {
+        validated: PartialNodeCircuit::new(HashOut::rand(), true),
+        child_proof: BlockQueryPublicInputs::<F>::from(middle_proof.public_inputs.as_slice()),
+    });
+
+    let root_pi = BlockQueryPublicInputs::<F>::from(top_proof.public_inputs.as_slice());
+    assert_eq!(root_pi.range(), F::from_canonical_u32(6));
+    assert_eq!(root_pi.aggregated_count(), F::from_canonical_u32(4));
+    assert_ne!(root_pi.range(), root_pi.aggregated_count());
+}
+
+/// Aggregates with a full node 2 subtrees whose ranges sum up to more than
+/// 32 bits: the proof must fail instead of wrapping the range.
+#[test]
+fn test_query_erc20_full_node_range_overflow() {
+    let smart_contract_address = address_to_packed_fields(&Address::random());
+    let user_address = address_to_packed_fields(&Address::random());
+    let rewards_rate = U256::from(42).to_fields().try_into().unwrap();
+    let subtree_pi = |block_number: u64, range: u64| {
+        BlockQueryPublicInputs::from_parts(
+            F::from_canonical_u64(block_number),
+            F::from_canonical_u64(range),
+            F::ONE,
+            HashOut::rand(),
+            &smart_contract_address,
+            &user_address,
+            F::from_canonical_u8(24),
+            F::from_canonical_u8(42),
+            &U256::one().to_fields().try_into().unwrap(),
+            &rewards_rate,
+            &U256::MAX.to_fields().try_into().unwrap(),
+            F::from_canonical_u8(3),
+            AggregationKind::Sum,
+            HashOut::ZERO,
+            F::ZERO,
+            HashOut::ZERO,
+            &[F::ZERO; PACKED_U256_LEN],
+            F::ZERO,
+        )
+    };
+    // the right subtree starts where the left one ends; the ranges of the
+    // children aren't bound to their block numbers, which must fit in 32 bits
+    let full_node = |left_range: u64, right_range: u64| {
+        let left = subtree_pi(1, left_range);
+        let right = subtree_pi(1 + right_range, right_range);
+        std::panic::catch_unwind(|| {
+            run_circuit::<F, D, C, _>(FullNodeCircuitValidator {
+                validated: FullNodeCircuit::default(),
+                children: &[
+                    BlockQueryPublicInputs::from(left.as_slice()),
+                    BlockQueryPublicInputs::from(right.as_slice()),
+                ],
+            })
+        })
+    };
+
+    // the greatest range fitting in 32 bits is accepted
+    let proof = full_node(1 << 31, (1 << 31) - 1).expect("range fitting in 32 bits failed");
+    let pi = BlockQueryPublicInputs::<F>::from(proof.public_inputs.as_slice());
+    assert_eq!(pi.range(), F::from_canonical_u32(u32::MAX));
+
+    assert!(
+        full_node(1 << 31, 1 << 31).is_err(),
+        "range of 2^32 blocks didn't fail"
+    );
+    assert!(
+        full_node(u32::MAX as u64, u32::MAX as u64 - 2).is_err(),
+        "range overflowing 32 bits didn't fail"
+    );
+}
+
+/// Aggregates 3 leaves with full nodes for each aggregation mode: the root
+/// exposes the sum, the minimum or the maximum of the leaf results, together
+/// with the aggregation mode employed. Padding children are ignored.
+#[test]
+fn test_query_erc20_full_node_aggregation_modes() {
+    const BLOCK_NUMBER: u32 = 123456;
+    let rng = &mut thread_rng();
+    let smart_contract_address = address_to_packed_fields(&Address::random());
+    let user_address = address_to_packed_fields(&Address::random());
+    let rewards_rate = U256::from(42).to_fields().try_into().unwrap();
+    let results = [0; 3].map(|_| U256::from(rng.gen::<u128>()));
+    let leaf_pi_with_count = |i: usize, count: u32, aggregation: AggregationKind| {
+        BlockQueryPublicInputs::from_parts(
+            F::from_canonical_u32(BLOCK_NUMBER + i as u32),
+            F::ONE,
+            F::from_canonical_u32(count),
+            HashOut::rand(),
+            &smart_contract_address,
+            &user_address,
+            F::from_canonical_u8(24),
+            F::from_canonical_u8(42),
+            &results[i].to_fields().try_into().unwrap(),
+            &rewards_rate,
+            &U256::MAX.to_fields().try_into().unwrap(),
+            F::from_canonical_u8(3),
+            aggregation,
+            HashOut::ZERO,
+            F::ZERO,
+            HashOut::ZERO,
+            &[F::ZERO; PACKED_U256_LEN],
+            F::ZERO,
+        )
+    };
+    let leaf_pi = |i: usize, aggregation: AggregationKind| leaf_pi_with_count(i, 1, aggregation);
+    let full_node = |aggregation: AggregationKind, left: &[F], right: &[F]| {
+        run_circuit::<F, D, C, _>(FullNodeCircuitValidator {
+            validated: FullNodeCircuit::new(aggregation),
+            children: &[
+                BlockQueryPublicInputs::from(left),
+                BlockQueryPublicInputs::from(right),
+            ],
+        })
+    };
+
+    for (aggregation, expected) in [
+        (AggregationKind::Sum, results[0] + results[1] + results[2]),
+        (AggregationKind::Min, *results.iter().min().unwrap()),
+        (AggregationKind::Max, *results.iter().max().unwrap()),
+    ] {
+        let leaves = [0, 1, 2].map(|i| leaf_pi(i, aggregation));
+        let middle_proof = full_node(aggregation, &leaves[0], &leaves[1]);
+        let root_proof = full_node(aggregation, &middle_proof.public_inputs, &leaves[2]);
+
+        let root_pi = BlockQueryPublicInputs::<F>::from(root_proof.public_inputs.as_slice());
+        assert_eq!(root_pi.query_results(), expected, "{aggregation:?}");
+        assert_eq!(root_pi.aggregation_mode().unwrap(), aggregation);
+        assert_eq!(
+            root_pi.block_number(),
+            F::from_canonical_u32(BLOCK_NUMBER + 2)
+        );
+        assert_eq!(root_pi.range(), F::from_canonical_u8(3));
+
+        // a child aggregating no blocks is a padding child, which doesn't
+        // affect the aggregated result whatever its exposed result
+        let padding = leaf_pi_with_count(1, 0, aggregation);
+        let proof = full_node(aggregation, &leaves[0], &padding);
+        let pi = BlockQueryPublicInputs::<F>::from(proof.public_inputs.as_slice());
+        assert_eq!(pi.query_results(), results[0], "{aggregation:?}");
+        assert_eq!(pi.aggregated_count(), F::ONE);
+    }
+
+    // a full node can't aggregate with a mode different from the leaves one
+    let [left, right] = [0, 1].map(|i| leaf_pi(i, AggregationKind::Min));
+    let res = std::panic::catch_unwind(|| full_node(AggregationKind::Max, &left, &right));
+    assert!(res.is_err(), "aggregation mode of the leaves changed");
+    // nor aggregate leaves with different modes
+    let right = leaf_pi(1, AggregationKind::Max);
+    let res = std::panic::catch_unwind(|| full_node(AggregationKind::Min, &left, &right));
+    assert!(
+        res.is_err(),
+        "leaves with different aggregation modes aggregated"
+    );
+}
+
+#[test]
+fn test_query_erc20_max_block_range() {
+    const L: usize = 5;
+    const BLOCK_DB_DEPTH: usize = 2;
+    assert_eq!(
+        PublicParameters::<BLOCK_DB_DEPTH, L>::max_block_range(),
+        2usize.pow(BLOCK_DB_DEPTH as u32)
+    );
+}
+
+#[test]
+#[serial]
+fn test_query_erc20_estimate_peak_memory() {
+    const L: usize = 5;
+    const BLOCK_DB_DEPTH: usize = 2;
+    let block_db_circuits = TestingRecursiveCircuits::<F, C, D, NUM_IVC_PUBLIC_INPUTS>::default();
+    let block_db_info = BlockDBCircuitInfo::<BLOCK_DB_DEPTH>::new(
+        block_db_circuits.get_recursive_circuit_set().clone(),
+        block_db_circuits.verifier_data_for_input_proofs::<1>()[0].clone(),
+    );
+    let params =
+        PublicParameters::<BLOCK_DB_DEPTH, L>::build(&block_db_info.serialize().unwrap()).unwrap();
+
+    let leaf = || Box::new(AggregationPlan::Leaf { storage_depth: 3 });
+    let shallow = AggregationPlan::Full(leaf(), leaf());
+    let deeper = AggregationPlan::Partial(Box::new(AggregationPlan::Full(
+        Box::new(AggregationPlan::Full(leaf(), leaf())),
+        Box::new(AggregationPlan::Full(leaf(), leaf())),
+    )));
+
+    let shallow_memory = params.estimate_peak_memory(&shallow);
+    assert!(shallow_memory > 0);
+    assert!(params.estimate_peak_memory(&deeper) > shallow_memory);
+    // deeper storage trees require more memory as well
+    assert!(
+        params.estimate_peak_memory(&AggregationPlan::Leaf { storage_depth: 4 })
+            > params.estimate_peak_memory(&AggregationPlan::Leaf { storage_depth: 3 })
+    );
+}
+
+#[test]
+#[serial]
+fn test_query_erc20_proof_transcript() {
+    const L: usize = 5;
+    const BLOCK_DB_DEPTH: usize = 2;
+    let block_db_circuits = TestingRecursiveCircuits::<F, C, D, NUM_IVC_PUBLIC_INPUTS>::default();
+    let block_db_info = BlockDBCircuitInfo::<BLOCK_DB_DEPTH>::new(
+        block_db_circuits.get_recursive_circuit_set().clone(),
+        block_db_circuits.verifier_data_for_input_proofs::<1>()[0].clone(),
+    );
+    let params =
+        PublicParameters::<BLOCK_DB_DEPTH, L>::build(&block_db_info.serialize().unwrap()).unwrap();
+    let query_circuit_set = RecursiveCircuits::<F, C, D>::new_from_circuit_digests(vec![
+        params
+            .final_proof_circuit_data()
+            .verifier_only
+            .circuit_digest,
+    ]);
+
+    // prove a storage leaf and 2 inner nodes on top of it, recording the steps
+    let mut transcript = ProofTranscript::new();
+    let user_address = Address::random();
+    let (leaf_proof, _) = params
+        .generate_proof_with_transcript(
+            CircuitInput::Storage(StorageCircuitInput::new_leaf(
+                user_address,
+                user_address,
+                U256::from(42),
+                U256::from(1000),
+                U256::from(7),
+            )),
+            &query_circuit_set,
+            &mut transcript,
+        )
+        .unwrap();
+    let mut proofs = vec![leaf_proof];
+    for proved_is_right in [false, true] {
+        let sibling = HashOut::<F>::rand().to_bytes();
+        let proof = proofs.last().unwrap();
+        let (left, right) = if proved_is_right {
+            (sibling.as_slice(), proof.as_slice())
+        } else {
+            (proof.as_slice(), sibling.as_slice())
+        };
+        let (proof, _) = params
+            .generate_proof_with_transcript(
+                CircuitInput::Storage(StorageCircuitInput::new_inner_node(
+                    left,
+                    right,
+                    proved_is_right,
+                )),
+                &query_circuit_set,
+                &mut transcript,
+            )
+            .unwrap();
+        proofs.push(proof);
+    }
+    let final_proof = proofs.last().unwrap();
+
+    // the transcript reconstructed from the serialized proof matches the calls
+    let bytes = transcript.serialize_with_proof(final_proof).unwrap();
+    let (decoded_proof, decoded_transcript) =
+        ProofTranscript::deserialize_with_proof(&bytes).unwrap();
+    assert_eq!(&decoded_proof, final_proof);
+    assert_eq!(decoded_transcript, transcript);
+    let steps = decoded_transcript.steps();
+    assert_eq!(
+        steps.iter().map(|step| step.circuit).collect_vec(),
+        vec![
+            TranscriptCircuit::StorageLeaf,
+            TranscriptCircuit::StorageInner,
+            TranscriptCircuit::StorageInner
+        ]
+    );
+    assert!(steps[0].inputs.is_empty());
+    for (i, step) in steps.iter().enumerate() {
+        assert_eq!(step.output, proof_digest(&proofs[i]));
+        if i > 0 {
+            assert_eq!(step.inputs, vec![proof_digest(&proofs[i - 1])]);
+        }
+    }
+    decoded_transcript.replay(&decoded_proof).unwrap();
+
+    // replay fails for another final proof or an incomplete transcript
+    assert!(transcript.replay(&proofs[1]).is_err());
+    let mut partial_transcript = ProofTranscript::new();
+    partial_transcript.record(steps[2].circuit, steps[2].inputs.clone(), final_proof);
+    assert!(partial_transcript.replay(final_proof).is_err());
 }
@@ -0,0 +1,172 @@
+//! Transcript of the proofs generated to prove a query, recording for each
+//! recursion step the circuit employed, the digests of the proofs it aggregates
+//! and the digest of the generated proof. The transcript can be shipped together
+//! with the final proof, allowing an auditor to replay the aggregation structure.
+
+use anyhow::{ensure, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{api::ProofWithVK, types::HashOutput, utils::keccak256};
+
+use super::api::CircuitInput;
+
+/// Circuit employed in a step of a `ProofTranscript`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TranscriptCircuit {
+    StorageLeaf,
+    StorageInner,
+    State,
+    BlockFull,
+    BlockPartial,
+    BlockSingleLeaf,
+    MultiContract,
+    Revelation,
+}
+
+impl TranscriptCircuit {
+    /// Number of proofs generated by query circuits aggregated by the circuit
+    fn num_inputs(&self) -> usize {
+        match self {
+            TranscriptCircuit::StorageLeaf => 0,
+            TranscriptCircuit::BlockFull | TranscriptCircuit::MultiContract => 2,
+            TranscriptCircuit::StorageInner
+            | TranscriptCircuit::State
+            | TranscriptCircuit::BlockPartial
+            | TranscriptCircuit::BlockSingleLeaf
+            | TranscriptCircuit::Revelation => 1,
+        }
+    }
+}
+
+/// A proof generation step recorded in a `ProofTranscript`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TranscriptStep {
+    /// Circuit employed to generate the proof
+    pub circuit: TranscriptCircuit,
+    /// Digests of the input proofs generated by query circuits; the block DB proof
+    /// employed by the revelation circuit isn't included
+    pub inputs: Vec<HashOutput>,
+    /// Digest of the generated proof
+    pub output: HashOutput,
+}
+
+/// Ordered log of the proof generation steps performed to prove a query
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProofTranscript {
+    steps: Vec<TranscriptStep>,
+}
+
+/// Digest of a serialized proof employed in transcripts
+pub fn proof_digest(proof: &[u8]) -> HashOutput {
+    keccak256(proof).try_into().unwrap()
+}
+
+impl ProofTranscript {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Steps recorded so far, in the order they were performed
+    pub fn steps(&self) -> &[TranscriptStep] {
+        &self.steps
+    }
+
+    /// Compute the circuit employed to prove `input` and the digests of the input
+    /// proofs, to be recorded with `record` once the proof is generated
+    pub(crate) fn step_inputs<const L: usize>(
+        input: &CircuitInput<L>,
+    ) -> Result<(TranscriptCircuit, Vec<HashOutput>)> {
+        let (circuit, inputs) = Self::input_proofs(input);
+        let inputs = inputs
+            .into_iter()
+            .map(|proof| Ok(proof_digest(&proof.serialize()?)))
+            .collect::<Result<Vec<_>>>()?;
+        Ok((circuit, inputs))
+    }
+
+    /// Record the generation of `output` by `circuit` from the proofs with digests `inputs`
+    pub(crate) fn record(
+        &mut self,
+        circuit: TranscriptCircuit,
+        inputs: Vec<HashOutput>,
+        output: &[u8],
+    ) {
+        self.steps.push(TranscriptStep {
+            circuit,
+            inputs,
+            output: proof_digest(output),
+        });
+    }
+
+    fn input_proofs<const L: usize>(
+        input: &CircuitInput<L>,
+    ) -> (TranscriptCircuit, Vec<&ProofWithVK>) {
+        use super::{block::CircuitInput as Block, storage::CircuitInput as Storage};
+        match input {
+            CircuitInput::Storage(Storage::Leaf(_)) => (TranscriptCircuit::StorageLeaf, vec![]),
+            CircuitInput::Storage(Storage::Inner(_, proof)) => {
+                (TranscriptCircuit::StorageInner, vec![proof])
+            }
+            CircuitInput::State(input) => (TranscriptCircuit::State, vec![input.storage_proof()]),
+            CircuitInput::Block(Block::FullNode((left, right))) => {
+                (TranscriptCircuit::BlockFull, vec![left, right])
+            }
+            CircuitInput::Block(Block::PartialNode(input)) => {
+                (TranscriptCircuit::BlockPartial, vec![input.child_proof()])
+            }
+            CircuitInput::Block(Block::SingleLeaf(proof)) => {
+                (TranscriptCircuit::BlockSingleLeaf, vec![proof])
+            }
+            CircuitInput::MultiContract(input) => (
+                TranscriptCircuit::MultiContract,
+                input.block_proofs().iter().collect(),
+            ),
+            CircuitInput::Revelation(input) => (
+                TranscriptCircuit::Revelation,
+                vec![input.query_block_proof()],
+            ),
+        }
+    }
+
+    /// Replay the transcript, checking that it describes a valid aggregation
+    /// structure ending in `final_proof`: each step aggregates the expected
+    /// number of proofs, each input proof is generated by an earlier step and
+    /// aggregated only once, and every generated proof but the final one is
+    /// aggregated by a later step
+    pub fn replay(&self, final_proof: &[u8]) -> Result<()> {
+        let mut pending: Vec<HashOutput> = vec![];
+        for (i, step) in self.steps.iter().enumerate() {
+            ensure!(
+                step.inputs.len() == step.circuit.num_inputs(),
+                "step {i}: {:?} circuit aggregates {} proofs, found {}",
+                step.circuit,
+                step.circuit.num_inputs(),
+                step.inputs.len()
+            );
+            for input in &step.inputs {
+                let pos = pending.iter().position(|output| output == input);
+                ensure!(
+                    pos.is_some(),
+                    "step {i}: input proof not generated by a previous step"
+                );
+                pending.swap_remove(pos.unwrap());
+            }
+            pending.push(step.output);
+        }
+        ensure!(
+            pending == [proof_digest(final_proof)],
+            "transcript doesn't aggregate all the generated proofs into the final proof"
+        );
+        Ok(())
+    }
+
+    /// Serialize `proof` together with the transcript
+    pub fn serialize_with_proof(&self, proof: &[u8]) -> Result<Vec<u8>> {
+        Ok(bincode::serialize(&(proof, self))?)
+    }
+
+    /// Deserialize a proof and its transcript serialized with `serialize_with_proof`
+    pub fn deserialize_with_proof(bytes: &[u8]) -> Result<(Vec<u8>, Self)> {
+        Ok(bincode::deserialize(bytes)?)
+    }
+}
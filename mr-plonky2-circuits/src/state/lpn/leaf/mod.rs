@@ -14,12 +14,14 @@ use plonky2::{
         proof::{ProofWithPublicInputs, ProofWithPublicInputsTarget},
     },
 };
+use plonky2_crypto::u32::arithmetic_u32::U32Target;
 use recursion_framework::circuit_builder::CircuitLogicWires;
 use serde::{Deserialize, Serialize};
 
 use crate::{
     api::verify_proof_fixed_circuit,
     state::{lpn::StateInputs, BlockLinkingInputs},
+    types::PackedAddressTarget,
 };
 
 #[cfg(test)]
@@ -71,6 +73,27 @@ impl LeafCircuit {
     }
 }
 
+/// Enforces that the leaf built from `block_linking` binds the `expected` smart contract
+/// address, so that composite queries can pin the contract the storage values belong to.
+pub fn assert_leaf_contract<F, const D: usize>(
+    b: &mut CircuitBuilder<F, D>,
+    block_linking: &BlockLinkingInputs<Target>,
+    expected: &PackedAddressTarget,
+) where
+    F: RichField + Extendable<D>,
+{
+    let address = PackedAddressTarget::try_from(
+        block_linking
+            .packed_address()
+            .iter()
+            .map(|&t| U32Target(t))
+            .collect::<Vec<_>>(),
+    )
+    .unwrap();
+
+    address.enforce_equal(b, expected);
+}
+
 type F = crate::api::F;
 type C = crate::api::C;
 const D: usize = crate::api::D;
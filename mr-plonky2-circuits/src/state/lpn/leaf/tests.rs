@@ -23,7 +23,9 @@ use rand::{rngs::StdRng, RngCore, SeedableRng};
 
 use crate::{
     state::lpn::state_leaf_hash,
-    state::{BlockLinkingInputs, StateInputs},
+    state::{assert_leaf_contract, BlockLinkingInputs, StateInputs},
+    types::{PackedAddressTarget, PACKED_ADDRESS_LEN},
+    utils::{Packer, ToFields},
 };
 
 use super::LeafCircuit;
@@ -160,3 +162,68 @@ impl UserCircuit<GoldilocksField, 2> for TestLeafCircuit {
             .for_each(|(&v, &t)| pw.set_target(t, v));
     }
 }
+
+#[derive(Clone)]
+struct TestLeafContractWires {
+    block_linking: Vec<Target>,
+    expected: PackedAddressTarget,
+}
+
+#[derive(Clone, Debug)]
+struct TestLeafContractCircuit {
+    block_linking_values: Vec<GoldilocksField>,
+    expected: [GoldilocksField; PACKED_ADDRESS_LEN],
+}
+
+impl UserCircuit<GoldilocksField, 2> for TestLeafContractCircuit {
+    type Wires = TestLeafContractWires;
+
+    fn build(b: &mut CircuitBuilder<GoldilocksField, 2>) -> Self::Wires {
+        let targets = b.add_virtual_targets(BlockLinkingInputs::<()>::TOTAL_LEN);
+        let block_linking = BlockLinkingInputs::from_slice(&targets);
+        let expected = PackedAddressTarget::new(b);
+        LeafCircuit::build(b, &block_linking);
+        assert_leaf_contract(b, &block_linking, &expected);
+
+        TestLeafContractWires {
+            block_linking: targets.clone(),
+            expected,
+        }
+    }
+
+    fn prove(&self, pw: &mut PartialWitness<GoldilocksField>, wires: &Self::Wires) {
+        pw.set_target_arr(&wires.block_linking, &self.block_linking_values);
+        wires.expected.assign(pw, &self.expected);
+    }
+}
+
+#[test]
+fn test_leaf_contract_address() {
+    let block_linking_values =
+        BlockLinkingInputs::<GoldilocksField>::values_from_seed(TestLeafCircuit::PI_SEED);
+    let block_linking = BlockLinkingInputs::from_slice(&block_linking_values);
+
+    let circuit = TestLeafContractCircuit {
+        block_linking_values: block_linking_values.to_vec(),
+        expected: block_linking.packed_address().try_into().unwrap(),
+    };
+    run_circuit::<_, _, PoseidonGoldilocksConfig, _>(circuit);
+}
+
+#[test]
+#[should_panic]
+fn test_leaf_contract_address_mismatch() {
+    let block_linking_values =
+        BlockLinkingInputs::<GoldilocksField>::values_from_seed(TestLeafCircuit::PI_SEED);
+
+    let circuit = TestLeafContractCircuit {
+        block_linking_values: block_linking_values.to_vec(),
+        expected: Address::random()
+            .as_fixed_bytes()
+            .pack()
+            .to_fields()
+            .try_into()
+            .unwrap(),
+    };
+    run_circuit::<_, _, PoseidonGoldilocksConfig, _>(circuit);
+}
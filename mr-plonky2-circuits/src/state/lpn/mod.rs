@@ -6,6 +6,7 @@ mod node;
 mod public_inputs;
 
 use ethers::types::Address;
+pub use leaf::assert_leaf_contract;
 use plonky2::{
     field::{goldilocks_field::GoldilocksField, types::Field},
     hash::{hash_types::HashOut, poseidon::PoseidonHash},
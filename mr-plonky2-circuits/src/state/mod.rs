@@ -7,4 +7,4 @@ pub mod block_linking;
 pub mod lpn;
 
 pub use block_linking::BlockLinkingInputs;
-pub use lpn::StateInputs;
+pub use lpn::{assert_leaf_contract, StateInputs};
@@ -0,0 +1,203 @@
+use plonky2::{
+    field::goldilocks_field::GoldilocksField,
+    iop::{target::Target, witness::PartialWitness},
+    plonk::circuit_builder::CircuitBuilder,
+};
+use plonky2_crypto::u32::arithmetic_u32::U32Target;
+use recursion_framework::circuit_builder::CircuitLogicWires;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    array::{Array, Vector, VectorWire},
+    keccak::{InputData, KeccakCircuit, KeccakWires, PACKED_HASH_LEN},
+    mpt_sequential::{Circuit as MPTCircuit, PAD_LEN},
+    rlp::{decode_fixed_list, MAX_ITEMS_IN_LIST},
+    utils::convert_u8_targets_to_u32,
+};
+
+use super::public_inputs::PublicInputs;
+
+/// Circuit proving a branch node of the state trie on the path to an
+/// account. Contrary to the storage trie, only a single path is proven, so
+/// the branch node always has exactly one child proof.
+#[derive(Clone, Debug)]
+pub struct BranchCircuit<const NODE_LEN: usize> {
+    pub(super) node: Vec<u8>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct BranchWires<const NODE_LEN: usize>
+where
+    [(); PAD_LEN(NODE_LEN)]:,
+{
+    node: VectorWire<Target, { PAD_LEN(NODE_LEN) }>,
+    keccak: KeccakWires<{ PAD_LEN(NODE_LEN) }>,
+}
+
+impl<const NODE_LEN: usize> BranchCircuit<NODE_LEN>
+where
+    [(); PAD_LEN(NODE_LEN)]:,
+{
+    /// Creates the circuit for the branch `node`.
+    pub fn new(node: Vec<u8>) -> Self {
+        Self { node }
+    }
+
+    pub fn build(
+        b: &mut CircuitBuilder<GoldilocksField, 2>,
+        child_proof: &PublicInputs<Target>,
+    ) -> BranchWires<NODE_LEN> {
+        let node = VectorWire::<Target, { PAD_LEN(NODE_LEN) }>::new(b);
+        // always ensure the node is bytes at the beginning
+        node.assert_bytes(b);
+
+        let zero = b.zero();
+        let tru = b._true();
+        // First expose the keccak root of this subtree starting at this node
+        let root = KeccakCircuit::<{ PAD_LEN(NODE_LEN) }>::hash_vector(b, &node);
+
+        // we already decode the rlp headers here since we need it to verify
+        // the validity of the hash exposed by the proof
+        let headers = decode_fixed_list::<_, _, MAX_ITEMS_IN_LIST>(b, &node.arr.arr, zero);
+        // look at the key from the child proof and move its pointer according to this node
+        let child_key = child_proof.mpt_key();
+        let (new_key, hash, is_valid, _) =
+            MPTCircuit::<1, NODE_LEN>::advance_key_branch(b, &node.arr, &child_key, &headers);
+        // we always enforce it's a branch node, i.e. that it has 17 entries
+        b.connect(is_valid.target, tru.target);
+        // we check the hash is the one exposed by the proof
+        let packed_hash = Array::<U32Target, PACKED_HASH_LEN> {
+            arr: convert_u8_targets_to_u32(b, &hash.arr).try_into().unwrap(),
+        };
+        packed_hash.enforce_equal(b, &child_proof.root_hash());
+
+        PublicInputs::register(
+            b,
+            &child_proof.contract_address(),
+            &new_key,
+            &root.output_array,
+            &child_proof.storage_root(),
+        );
+        BranchWires { node, keccak: root }
+    }
+
+    pub fn assign(&self, pw: &mut PartialWitness<GoldilocksField>, wires: &BranchWires<NODE_LEN>) {
+        let vec = Vector::<u8, { PAD_LEN(NODE_LEN) }>::from_vec(&self.node).unwrap();
+        wires.node.assign(pw, &vec);
+        KeccakCircuit::<{ PAD_LEN(NODE_LEN) }>::assign(
+            pw,
+            &wires.keccak,
+            &InputData::Assigned(&vec),
+        );
+    }
+}
+
+/// D = 2,
+/// Num of children = 1
+impl<const NODE_LEN: usize> CircuitLogicWires<GoldilocksField, 2, 1> for BranchWires<NODE_LEN>
+where
+    [(); PAD_LEN(NODE_LEN)]:,
+{
+    type CircuitBuilderParams = ();
+
+    type Inputs = BranchCircuit<NODE_LEN>;
+
+    const NUM_PUBLIC_INPUTS: usize = PublicInputs::<GoldilocksField>::TOTAL_LEN;
+
+    fn circuit_logic(
+        builder: &mut CircuitBuilder<GoldilocksField, 2>,
+        verified_proofs: [&plonky2::plonk::proof::ProofWithPublicInputsTarget<2>; 1],
+        _: Self::CircuitBuilderParams,
+    ) -> Self {
+        let inputs = PublicInputs::from(&verified_proofs[0].public_inputs);
+        BranchCircuit::build(builder, &inputs)
+    }
+
+    fn assign_input(
+        &self,
+        inputs: Self::Inputs,
+        pw: &mut PartialWitness<GoldilocksField>,
+    ) -> anyhow::Result<()> {
+        inputs.assign(pw, self);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use mrp2_test_utils::circuit::{run_circuit, UserCircuit};
+    use plonky2::{
+        field::types::Field,
+        iop::witness::WitnessWrite,
+        plonk::config::{GenericConfig, PoseidonGoldilocksConfig},
+    };
+
+    use super::*;
+    use crate::{
+        storage::account::{
+            leaf::LeafCircuit, test::generate_account_trie, MAX_ACCOUNT_LEAF_NODE_LEN,
+        },
+        types::address_to_packed_fields,
+        utils::{convert_u8_to_u32_slice, keccak256},
+    };
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    #[derive(Clone, Debug)]
+    struct TestBranchCircuit<'a, const NODE_LEN: usize> {
+        c: BranchCircuit<NODE_LEN>,
+        child_pi: &'a [F],
+    }
+
+    impl<'a, const NODE_LEN: usize> UserCircuit<F, D> for TestBranchCircuit<'a, NODE_LEN>
+    where
+        [(); PAD_LEN(NODE_LEN)]:,
+    {
+        type Wires = (BranchWires<NODE_LEN>, Vec<Target>);
+
+        fn build(b: &mut CircuitBuilder<F, D>) -> Self::Wires {
+            let child_pi = b.add_virtual_targets(PublicInputs::<Target>::TOTAL_LEN);
+            let wires = BranchCircuit::build(b, &PublicInputs::from(&child_pi));
+            (wires, child_pi)
+        }
+
+        fn prove(&self, pw: &mut PartialWitness<F>, wires: &Self::Wires) {
+            pw.set_target_arr(&wires.1, self.child_pi);
+            self.c.assign(pw, &wires.0);
+        }
+    }
+
+    #[test]
+    fn test_account_branch_circuit() {
+        const NODE_LEN: usize = 100;
+        let (mut trie, accounts) = generate_account_trie();
+        let (address, storage_root) = accounts[0];
+        let mpt_key = keccak256(&address.0);
+        let proof = trie.get_proof(&mpt_key).unwrap();
+        // the root of the trie is the branch node and the account is stored right below it
+        assert_eq!(proof.len(), 2);
+        let (branch, leaf) = (proof[0].clone(), proof[1].clone());
+
+        let leaf_proof =
+            run_circuit::<F, D, C, _>(LeafCircuit::<MAX_ACCOUNT_LEAF_NODE_LEN>::new(leaf, address));
+        let circuit = TestBranchCircuit::<NODE_LEN> {
+            c: BranchCircuit::new(branch),
+            child_pi: &leaf_proof.public_inputs,
+        };
+        let proof = run_circuit::<F, D, C, _>(circuit);
+        let pi = PublicInputs::<F>::from(&proof.public_inputs);
+        // the proof exposes the state root and the storage root of the contract
+        let state_root = trie.root_hash().unwrap();
+        assert_eq!(pi.root_hash(), convert_u8_to_u32_slice(&state_root.0));
+        assert_eq!(pi.storage_root(), convert_u8_to_u32_slice(&storage_root.0));
+        assert_eq!(
+            pi.contract_address_info(),
+            address_to_packed_fields::<F>(&address)
+        );
+        // the whole key has been processed
+        let (_, ptr) = pi.mpt_key_info();
+        assert_eq!(ptr, F::NEG_ONE);
+    }
+}
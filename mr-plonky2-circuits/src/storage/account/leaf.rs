@@ -0,0 +1,284 @@
+//! Module handling the proving of the account leaf node of the state trie,
+//! extracting the storage root of the contract.
+
+use ethers::types::H160;
+use plonky2::{
+    field::{goldilocks_field::GoldilocksField, types::Field},
+    iop::{
+        target::Target,
+        witness::{PartialWitness, WitnessWrite},
+    },
+    plonk::circuit_builder::CircuitBuilder,
+};
+use recursion_framework::circuit_builder::CircuitLogicWires;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    array::{Array, Vector, VectorWire},
+    keccak::{InputData, KeccakCircuit, KeccakWires, OutputByteHash, HASH_LEN},
+    mpt_sequential::{Circuit as MPTCircuit, MPTKeyWire, MAX_LEAF_VALUE_LEN, PAD_LEN},
+    rlp::decode_fixed_list,
+    types::{AddressTarget, ADDRESS_LEN},
+};
+
+use super::public_inputs::PublicInputs;
+
+/// Keccak input padded length for address
+const INPUT_PADDED_ADDRESS_LEN: usize = PAD_LEN(ADDRESS_LEN);
+/// Number of items in the RLP encoded account:
+/// [nonce, balance, storage_root, code_hash]
+const ACCOUNT_NUM_ITEMS: usize = 4;
+/// Index of the storage root in the RLP encoded account
+const STORAGE_ROOT_IDX: usize = 2;
+
+/// Wires opening the leaf node of the state trie storing the account of a
+/// contract: the MPT key of the account is derived from the contract address,
+/// and the storage root is extracted from the RLP encoded account stored in
+/// the leaf. They are shared by the account leaf circuits of the storage and
+/// state modules.
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub(crate) struct AccountLeafWires<const NODE_LEN: usize>
+where
+    [(); PAD_LEN(NODE_LEN)]:,
+{
+    node: VectorWire<Target, { PAD_LEN(NODE_LEN) }>,
+    pub(crate) root: KeccakWires<{ PAD_LEN(NODE_LEN) }>,
+    pub(crate) address: AddressTarget,
+    keccak_address: KeccakWires<INPUT_PADDED_ADDRESS_LEN>,
+}
+
+impl<const NODE_LEN: usize> AccountLeafWires<NODE_LEN>
+where
+    [(); PAD_LEN(NODE_LEN)]:,
+{
+    /// Builds the wires, returning them together with the MPT key of the
+    /// account advanced up to the leaf and the storage root of the contract.
+    pub(crate) fn build(
+        b: &mut CircuitBuilder<GoldilocksField, 2>,
+    ) -> (Self, MPTKeyWire, OutputByteHash) {
+        let zero = b.zero();
+        let tru = b._true();
+        let node = VectorWire::<Target, { PAD_LEN(NODE_LEN) }>::new(b);
+        // always ensure the node is bytes at the beginning
+        node.assert_bytes(b);
+        let root = KeccakCircuit::<{ PAD_LEN(NODE_LEN) }>::hash_vector(b, &node);
+
+        // The MPT key of an account is the keccak of the contract address.
+        let address = Array::new(b);
+        address.assert_bytes(b);
+        let mut arr = [zero; INPUT_PADDED_ADDRESS_LEN];
+        arr[..ADDRESS_LEN].copy_from_slice(&address.arr);
+        let bytes_to_keccak = &VectorWire::<Target, INPUT_PADDED_ADDRESS_LEN> {
+            real_len: b.constant(GoldilocksField::from_canonical_usize(ADDRESS_LEN)),
+            arr: Array { arr },
+        };
+        let keccak_address = KeccakCircuit::hash_vector(b, bytes_to_keccak);
+        let mpt_key = MPTKeyWire::init_from_u32_targets(b, &keccak_address.output_array);
+
+        // small optimization here as we only need to decode two items for a leaf, since we know it's a leaf
+        let headers = decode_fixed_list::<_, _, 2>(b, &node.arr.arr, zero);
+        let (new_key, _, is_leaf) = MPTCircuit::<1, NODE_LEN>::advance_key_leaf_or_extension::<
+            _,
+            _,
+            _,
+            MAX_LEAF_VALUE_LEN,
+        >(b, &node.arr, &mpt_key, &headers);
+        b.connect(tru.target, is_leaf.target);
+
+        // The value of the leaf is the RLP encoded account, a list of 4 items
+        // starting right after the header of the value string.
+        let account =
+            decode_fixed_list::<_, _, ACCOUNT_NUM_ITEMS>(b, &node.arr.arr, headers.offset[1]);
+        let num_items = b.constant(GoldilocksField::from_canonical_usize(ACCOUNT_NUM_ITEMS));
+        b.connect(account.num_fields, num_items);
+        let hash_len = b.constant(GoldilocksField::from_canonical_usize(HASH_LEN));
+        b.connect(account.len[STORAGE_ROOT_IDX], hash_len);
+        let storage_root: OutputByteHash =
+            node.arr.extract_array(b, account.offset[STORAGE_ROOT_IDX]);
+
+        (
+            Self {
+                node,
+                root,
+                address,
+                keccak_address,
+            },
+            new_key,
+            storage_root,
+        )
+    }
+
+    pub(crate) fn assign(
+        &self,
+        pw: &mut PartialWitness<GoldilocksField>,
+        node: &[u8],
+        address: &H160,
+    ) {
+        let pad_node =
+            Vector::<u8, { PAD_LEN(NODE_LEN) }>::from_vec(node).expect("invalid node given");
+        self.node.assign(pw, &pad_node);
+        KeccakCircuit::<{ PAD_LEN(NODE_LEN) }>::assign(
+            pw,
+            &self.root,
+            &InputData::Assigned(&pad_node),
+        );
+        self.address
+            .assign(pw, &address.0.map(GoldilocksField::from_canonical_u8));
+        KeccakCircuit::<INPUT_PADDED_ADDRESS_LEN>::assign(
+            pw,
+            &self.keccak_address,
+            &InputData::Assigned(
+                &Vector::from_vec(&address.0)
+                    .expect("Cannot create vector input for keccak contract address"),
+            ),
+        );
+    }
+}
+
+/// Circuit proving the MPT key of an account is derived from the contract
+/// address, and extracting the storage root from the account stored in the
+/// leaf node.
+#[derive(Clone, Debug)]
+pub struct LeafCircuit<const NODE_LEN: usize> {
+    pub(super) node: Vec<u8>,
+    pub(super) address: H160,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct LeafWires<const NODE_LEN: usize>
+where
+    [(); PAD_LEN(NODE_LEN)]:,
+{
+    leaf: AccountLeafWires<NODE_LEN>,
+}
+
+impl<const NODE_LEN: usize> LeafCircuit<NODE_LEN>
+where
+    [(); PAD_LEN(NODE_LEN)]:,
+{
+    /// Creates the circuit for the account leaf `node` of the contract at `address`.
+    pub fn new(node: Vec<u8>, address: H160) -> Self {
+        Self { node, address }
+    }
+
+    pub fn build(b: &mut CircuitBuilder<GoldilocksField, 2>) -> LeafWires<NODE_LEN> {
+        let (leaf, new_key, storage_root) = AccountLeafWires::build(b);
+
+        let packed_address = leaf.address.convert_u8_to_u32(b);
+        let packed_storage_root = storage_root.convert_u8_to_u32(b);
+        PublicInputs::register(
+            b,
+            &packed_address,
+            &new_key,
+            &leaf.root.output_array,
+            &packed_storage_root,
+        );
+
+        LeafWires { leaf }
+    }
+
+    pub fn assign(&self, pw: &mut PartialWitness<GoldilocksField>, wires: &LeafWires<NODE_LEN>) {
+        wires.leaf.assign(pw, &self.node, &self.address);
+    }
+}
+
+/// D = 2,
+/// Num of children = 0
+impl<const NODE_LEN: usize> CircuitLogicWires<GoldilocksField, 2, 0> for LeafWires<NODE_LEN>
+where
+    [(); PAD_LEN(NODE_LEN)]:,
+{
+    type CircuitBuilderParams = ();
+
+    type Inputs = LeafCircuit<NODE_LEN>;
+
+    const NUM_PUBLIC_INPUTS: usize = PublicInputs::<GoldilocksField>::TOTAL_LEN;
+
+    fn circuit_logic(
+        builder: &mut CircuitBuilder<GoldilocksField, 2>,
+        _verified_proofs: [&plonky2::plonk::proof::ProofWithPublicInputsTarget<2>; 0],
+        _builder_parameters: Self::CircuitBuilderParams,
+    ) -> Self {
+        LeafCircuit::build(builder)
+    }
+
+    fn assign_input(
+        &self,
+        inputs: Self::Inputs,
+        pw: &mut PartialWitness<GoldilocksField>,
+    ) -> anyhow::Result<()> {
+        inputs.assign(pw, self);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use eth_trie::{Nibbles, Trie};
+    use mrp2_test_utils::circuit::{run_circuit, UserCircuit};
+    use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+
+    use super::*;
+    use crate::{
+        mpt_sequential::utils::bytes_to_nibbles,
+        rlp::MAX_KEY_NIBBLE_LEN,
+        storage::account::{test::generate_account_trie, MAX_ACCOUNT_LEAF_NODE_LEN},
+        utils::{convert_u8_to_u32_slice, keccak256},
+    };
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    impl<const NODE_LEN: usize> UserCircuit<F, D> for LeafCircuit<NODE_LEN>
+    where
+        [(); PAD_LEN(NODE_LEN)]:,
+    {
+        type Wires = LeafWires<NODE_LEN>;
+
+        fn build(b: &mut CircuitBuilder<F, D>) -> Self::Wires {
+            LeafCircuit::build(b)
+        }
+
+        fn prove(&self, pw: &mut PartialWitness<F>, wires: &Self::Wires) {
+            self.assign(pw, wires);
+        }
+    }
+
+    #[test]
+    fn test_account_leaf_circuit() {
+        let (mut trie, accounts) = generate_account_trie();
+        let (address, storage_root) = accounts[0];
+        let mpt_key = keccak256(&address.0);
+        let proof = trie.get_proof(&mpt_key).unwrap();
+        let node = proof.last().unwrap().clone();
+
+        let circuit = LeafCircuit::<MAX_ACCOUNT_LEAF_NODE_LEN>::new(node.clone(), address);
+        let proof = run_circuit::<F, D, C, _>(circuit);
+        let pi = PublicInputs::<F>::from(&proof.public_inputs);
+        assert_eq!(pi.storage_root(), convert_u8_to_u32_slice(&storage_root.0));
+        assert_eq!(pi.root_hash(), convert_u8_to_u32_slice(&keccak256(&node)));
+        {
+            let (key, ptr) = pi.mpt_key_info();
+            let exp_key = bytes_to_nibbles(&mpt_key)
+                .into_iter()
+                .map(F::from_canonical_u8)
+                .collect::<Vec<_>>();
+            assert_eq!(key, exp_key);
+            let leaf_key: Vec<Vec<u8>> = rlp::decode_list(&node);
+            let nib = Nibbles::from_compact(&leaf_key[0]);
+            let exp_ptr = F::from_canonical_usize(MAX_KEY_NIBBLE_LEN - 1 - nib.nibbles().len());
+            assert_eq!(exp_ptr, ptr);
+        }
+
+        // the leaf node doesn't store the account of another address
+        let (other_address, _) = accounts[1];
+        let res = std::panic::catch_unwind(|| {
+            run_circuit::<F, D, C, _>(LeafCircuit::<MAX_ACCOUNT_LEAF_NODE_LEN>::new(
+                node.clone(),
+                other_address,
+            ))
+        });
+        assert!(res.is_err(), "wrong contract address accepted");
+    }
+}
@@ -0,0 +1,58 @@
+//! Circuits proving the storage root of a contract from the state trie, i.e.
+//! the inclusion of the account of the contract in the state MPT.
+mod branch;
+mod leaf;
+mod public_inputs;
+
+pub use branch::{BranchCircuit, BranchWires};
+pub(crate) use leaf::AccountLeafWires;
+pub use leaf::{LeafCircuit, LeafWires};
+pub use public_inputs::PublicInputs;
+
+/// rlp( rlp(max key 32b) + rlp( rlp(nonce 8b) + rlp(balance 32b) + rlp(storage root 32b) + rlp(code hash 32b) ) )
+/// where the account is itself encoded as a string inside the leaf node.
+pub(crate) const MAX_ACCOUNT_LEAF_NODE_LEN: usize = 148;
+
+#[cfg(test)]
+pub(crate) mod test {
+    use std::sync::Arc;
+
+    use eth_trie::{EthTrie, MemoryDB, Trie};
+    use ethers::types::{H160, H256};
+    use rand::{thread_rng, Rng};
+    use rlp::RlpStream;
+
+    use crate::utils::keccak256;
+
+    /// Builds a state trie with two accounts whose MPT keys differ on the
+    /// first nibble, so the trie is made of a branch root and two leaves.
+    /// It returns the trie and the (address, storage root) of each account.
+    pub(crate) fn generate_account_trie() -> (EthTrie<MemoryDB>, Vec<(H160, H256)>) {
+        let memdb = Arc::new(MemoryDB::new(true));
+        let mut trie = EthTrie::new(Arc::clone(&memdb));
+        let mut rng = thread_rng();
+        let first_address = H160::random();
+        let first_nibble = |address: &H160| keccak256(&address.0)[0] >> 4;
+        let second_address = loop {
+            let address = H160::random();
+            if first_nibble(&address) != first_nibble(&first_address) {
+                break address;
+            }
+        };
+        let accounts = [first_address, second_address]
+            .into_iter()
+            .map(|address| {
+                let storage_root = H256::random();
+                let mut account = RlpStream::new_list(4);
+                account.append(&rng.gen::<u64>());
+                account.append(&rng.gen::<u64>());
+                account.append(&storage_root.as_bytes().to_vec());
+                account.append(&H256::random().as_bytes().to_vec());
+                trie.insert(&keccak256(&address.0), &account.out()).unwrap();
+                (address, storage_root)
+            })
+            .collect::<Vec<_>>();
+        trie.root_hash().unwrap();
+        (trie, accounts)
+    }
+}
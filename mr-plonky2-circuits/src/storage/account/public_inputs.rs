@@ -0,0 +1,112 @@
+use std::array::from_fn as create_array;
+
+use plonky2::{
+    field::goldilocks_field::GoldilocksField, iop::target::Target,
+    plonk::circuit_builder::CircuitBuilder,
+};
+use plonky2_crypto::u32::arithmetic_u32::U32Target;
+
+use crate::{
+    array::Array,
+    keccak::{OutputHash, PACKED_HASH_LEN},
+    mpt_sequential::MPTKeyWire,
+    rlp::MAX_KEY_NIBBLE_LEN,
+    types::{PackedAddressTarget, PACKED_ADDRESS_LEN},
+};
+
+// This is a wrapper around an array of targets set as public inputs
+// of any proof generated in this module. They all share the same
+// structure.
+// `A` Contract address
+// `K` Full key of the account leaf, i.e. keccak(address), in nibbles
+// `T` Index of the part “processed” on the full key
+// `C` MPT root (of the current node) of the state trie
+// `S` Storage MPT root of the contract
+#[derive(Clone, Debug)]
+pub struct PublicInputs<'a, T: Clone> {
+    pub(crate) proof_inputs: &'a [T],
+}
+
+impl<'a> PublicInputs<'a, Target> {
+    /// Registers the public inputs of an account proof, in the order
+    /// expected by this wrapper.
+    pub fn register(
+        b: &mut CircuitBuilder<GoldilocksField, 2>,
+        addr: &PackedAddressTarget,
+        key: &MPTKeyWire,
+        c: &OutputHash,
+        storage_root: &OutputHash,
+    ) {
+        addr.register_as_public_input(b);
+        key.register_as_input(b);
+        c.register_as_public_input(b);
+        storage_root.register_as_public_input(b);
+    }
+    /// Returns the contract address defined over the public inputs.
+    pub fn contract_address(&self) -> PackedAddressTarget {
+        let addr = self.contract_address_info();
+        PackedAddressTarget::from_array(create_array(|i| U32Target(addr[i])))
+    }
+    /// Returns the MPT key defined over the public inputs
+    pub fn mpt_key(&self) -> MPTKeyWire {
+        let (key, ptr) = self.mpt_key_info();
+        MPTKeyWire {
+            key: Array {
+                arr: create_array(|i| key[i]),
+            },
+            pointer: ptr,
+        }
+    }
+    /// Returns the merkle hash C of the subtree this proof has processed.
+    pub fn root_hash(&self) -> OutputHash {
+        let hash = self.root_hash_info();
+        Array::<U32Target, PACKED_HASH_LEN>::from_array(create_array(|i| U32Target(hash[i])))
+    }
+    /// Returns the storage MPT root extracted from the account node.
+    pub fn storage_root(&self) -> OutputHash {
+        let hash = self.storage_root_info();
+        Array::<U32Target, PACKED_HASH_LEN>::from_array(create_array(|i| U32Target(hash[i])))
+    }
+}
+
+impl<'a> PublicInputs<'a, GoldilocksField> {
+    /// Returns the merkle hash C in packed representation
+    pub fn root_hash(&self) -> Vec<u32> {
+        let hash = self.root_hash_info();
+        hash.iter().map(|t| t.0 as u32).collect()
+    }
+    /// Returns the storage MPT root in packed representation
+    pub fn storage_root(&self) -> Vec<u32> {
+        let hash = self.storage_root_info();
+        hash.iter().map(|t| t.0 as u32).collect()
+    }
+}
+
+impl<'a, T: Copy> PublicInputs<'a, T> {
+    pub(crate) const A_IDX: usize = 0; // packed address
+    pub(crate) const KEY_IDX: usize = Self::A_IDX + PACKED_ADDRESS_LEN; // 64 nibbles
+    pub(crate) const T_IDX: usize = Self::KEY_IDX + MAX_KEY_NIBBLE_LEN; // 1 index
+    pub(crate) const C_IDX: usize = Self::T_IDX + 1; // packed hash = 8 U32-F elements
+    pub(crate) const S_IDX: usize = Self::C_IDX + PACKED_HASH_LEN; // packed hash = 8 U32-F elements
+    pub(crate) const TOTAL_LEN: usize = Self::S_IDX + PACKED_HASH_LEN;
+    /// Wraps the given slice of public inputs
+    pub fn from(arr: &'a [T]) -> Self {
+        Self { proof_inputs: arr }
+    }
+
+    pub(crate) fn contract_address_info(&self) -> &[T] {
+        &self.proof_inputs[Self::A_IDX..Self::A_IDX + PACKED_ADDRESS_LEN]
+    }
+    pub(crate) fn mpt_key_info(&self) -> (&[T], T) {
+        let key_range = Self::KEY_IDX..Self::KEY_IDX + MAX_KEY_NIBBLE_LEN;
+        let key = &self.proof_inputs[key_range];
+        let ptr = self.proof_inputs[Self::T_IDX];
+        (key, ptr)
+    }
+    pub(crate) fn root_hash_info(&self) -> &[T] {
+        &self.proof_inputs[Self::C_IDX..Self::C_IDX + PACKED_HASH_LEN]
+    }
+    pub(crate) fn storage_root_info(&self) -> &[T] {
+        &self.proof_inputs[Self::S_IDX..Self::S_IDX + PACKED_HASH_LEN]
+    }
+}
@@ -468,7 +468,7 @@ mod tests {
         // this is read in the same order
         let found_address = Address::from_slice(&decoded_address.into_iter().collect::<Vec<u8>>());
         assert_eq!(found_address, mapping_value);
-        let slot = MappingSlot::new(mapping_slot as u8, mapping_key.to_vec());
+        let slot = MappingSlot::new(mapping_slot as u8, mapping_key.to_vec(), 0);
 
         let leaf_node = res.storage_proof[0].proof.last().unwrap();
         let mpt_circuit = storage::mapping::leaf::LeafCircuit::<80> {
@@ -7,7 +7,7 @@ use crate::{
     keccak::{ByteKeccakWires, InputData, KeccakCircuit, KeccakWires, HASH_LEN},
     mpt_sequential::{MPTKeyWire, PAD_LEN},
     types::{MAPPING_KEY_LEN, MAPPING_LEAF_VALUE_LEN},
-    utils::keccak256,
+    utils::{keccak256, less_than},
 };
 use plonky2::{
     field::extension::Extendable,
@@ -198,22 +198,33 @@ impl SimpleSlot {
 
 /// Circuit gadget that proves the correct derivation of a MPT key from a given mapping slot and storage slot.
 /// Deriving a MPT key from mapping slot is done like:
-/// 1. location = keccak(left_pad32(key), left_pad32(slot))
+/// 1. location = keccak(left_pad32(key), left_pad32(base_slot_offset + slot))
 /// 2. mpt_key = keccak(location)
+/// The base slot offset allows to target mappings declared inside a struct stored at a
+/// non-zero base slot, like in some proxy or diamond contracts.
 /// WARNING: Currently takes the assumption that the storage slot number fits inside a single byte.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct MappingSlot {
     mapping_slot: u8,
     mapping_key: Vec<u8>,
+    base_slot_offset: u8,
 }
 
 impl MappingSlot {
-    pub fn new(slot: u8, key: Vec<u8>) -> Self {
+    pub fn new(slot: u8, key: Vec<u8>, base_slot_offset: u8) -> Self {
         Self {
             mapping_slot: slot,
             mapping_key: key,
+            base_slot_offset,
         }
     }
+
+    /// Storage slot of the mapping, i.e. `base_slot_offset + mapping_slot`
+    pub fn slot(&self) -> u8 {
+        self.base_slot_offset
+            .checked_add(self.mapping_slot)
+            .expect("mapping slot must fit inside a single byte")
+    }
 }
 
 /// Contains the wires associated with the storage slot's mpt key
@@ -225,6 +236,11 @@ pub struct MappingSlotWires {
     pub(crate) mapping_key: Array<Target, MAPPING_KEY_LEN>,
     /// "input" mapping slot which is assumed to fit in a single byte
     pub(crate) mapping_slot: Target,
+    /// "input" offset of the base slot the mapping slot is declared at
+    pub(crate) base_slot_offset: Target,
+    /// storage slot of the mapping, i.e. `base_slot_offset + mapping_slot`,
+    /// range checked to fit in a single byte
+    pub(crate) slot: Target,
     /// Wires associated with the MPT key
     pub(crate) keccak_mpt: KeccakMPTWires,
 }
@@ -236,7 +252,7 @@ const MAPPING_INPUT_PADDED_LEN: usize = PAD_LEN(MAPPING_INPUT_TOTAL_LEN);
 impl MappingSlot {
     /// Derives the mpt_key in circuit according to which type of storage slot
     /// Remember the rules to get the mpt key is as follow:
-    /// * location = keccak256(pad32(mapping_key), pad32(mapping_slot))
+    /// * location = keccak256(pad32(mapping_key), pad32(base_slot_offset + mapping_slot))
     /// * mpt_key = keccak256(location)
     /// Note the mapping slot wire is NOT range checked, because it is expected to
     /// be given by the verifier. If that assumption is not true, then the caller
@@ -248,11 +264,15 @@ impl MappingSlot {
         // always ensure whatever goes into hash function, it's bytes
         mapping_key.assert_bytes(b);
         let mapping_slot = b.add_virtual_target();
+        let base_slot_offset = b.add_virtual_target();
+        // the slot is hashed as a single byte, so the addition must not overflow it
+        let slot = b.add(base_slot_offset, mapping_slot);
+        b.range_check(slot, 8);
         let mut input = [b.zero(); MAPPING_INPUT_PADDED_LEN];
         input[0..MAPPING_KEY_LEN].copy_from_slice(&mapping_key.arr);
-        input[2 * MAPPING_KEY_LEN - 1] = mapping_slot;
+        input[2 * MAPPING_KEY_LEN - 1] = slot;
 
-        // keccak(left_pad32(mapping_key), left_pad32(mapping_slot))
+        // keccak(left_pad32(mapping_key), left_pad32(slot))
         let inputs = VectorWire::<Target, MAPPING_INPUT_PADDED_LEN> {
             real_len: b.constant(F::from_canonical_usize(MAPPING_INPUT_TOTAL_LEN)),
             arr: Array { arr: input },
@@ -263,15 +283,21 @@ impl MappingSlot {
         MappingSlotWires {
             mapping_key,
             mapping_slot,
+            base_slot_offset,
+            slot,
             keccak_mpt,
         }
     }
     pub fn assign<F: RichField>(&self, pw: &mut PartialWitness<F>, wires: &MappingSlotWires) {
         // first assign the "inputs"
         let padded_mkey = left_pad32(&self.mapping_key);
-        let padded_slot = left_pad32(&[self.mapping_slot]);
+        let padded_slot = left_pad32(&[self.slot()]);
         // the "padding" is done in circuit for slot
         pw.set_target(wires.mapping_slot, F::from_canonical_u8(self.mapping_slot));
+        pw.set_target(
+            wires.base_slot_offset,
+            F::from_canonical_u8(self.base_slot_offset),
+        );
         // already give 32 bytes for the mapping key
         wires.mapping_key.assign_bytes(pw, &padded_mkey);
         // Then compute the entire expected array to derive the mpt key
@@ -281,14 +307,200 @@ impl MappingSlot {
             .chain(padded_slot)
             .collect::<Vec<_>>();
         // then compute the expected resulting hash for mpt key derivation.
-        let location = keccak256(&inputs);
+        let location = self.location();
         KeccakMPT::assign(pw, &wires.keccak_mpt, inputs, location);
     }
+
+    /// Storage location of the mapping entry, i.e.
+    /// `keccak256(pad32(mapping_key), pad32(base_slot_offset + mapping_slot))`
+    pub(crate) fn location(&self) -> Vec<u8> {
+        let inputs = left_pad32(&self.mapping_key)
+            .into_iter()
+            .chain(left_pad32(&[self.slot()]))
+            .collect::<Vec<_>>();
+        keccak256(&inputs)
+    }
+}
+
+/// Adds `offset` to the storage `location`, both expressed as 32 big-endian bytes,
+/// modulo 2^256 as the EVM does. The location bytes are expected to be range checked.
+pub(crate) fn add_location_offset<F: RichField + Extendable<D>, const D: usize>(
+    b: &mut CircuitBuilder<F, D>,
+    location: &[Target; HASH_LEN],
+    offset: &[Target; HASH_LEN],
+) -> [Target; HASH_LEN] {
+    let mut sum_bytes = [b.zero(); HASH_LEN];
+    let mut carry = b.zero();
+    for i in (0..HASH_LEN).rev() {
+        let sum = b.add_many([location[i], offset[i], carry]);
+        let (byte, next_carry) = b.split_low_high(sum, 8, 9);
+        sum_bytes[i] = byte;
+        carry = next_carry;
+    }
+    sum_bytes
+}
+
+/// Computes the MPT key `keccak(location)` of the given storage location, returning
+/// the keccak wires along with the key in nibbles.
+pub(crate) fn location_mpt_key<F: RichField + Extendable<D>, const D: usize>(
+    b: &mut CircuitBuilder<F, D>,
+    location: &[Target; HASH_LEN],
+) -> (KeccakWires<{ PAD_LEN(HASH_LEN) }>, MPTKeyWire) {
+    let mut padded_location = [b.zero(); PAD_LEN(HASH_LEN)];
+    padded_location[0..HASH_LEN].copy_from_slice(location);
+    let hash_len = b.constant(F::from_canonical_usize(HASH_LEN));
+    let keccak_mpt_key = KeccakCircuit::<{ PAD_LEN(HASH_LEN) }>::hash_vector(
+        b,
+        &VectorWire {
+            real_len: hash_len,
+            arr: Array {
+                arr: padded_location,
+            },
+        },
+    );
+    // MPT KEY is expressed in nibbles
+    let mpt_key = MPTKeyWire::init_from_u32_targets(b, &keccak_mpt_key.output_array);
+
+    (keccak_mpt_key, mpt_key)
+}
+
+/// Circuit gadget that proves the correct derivation of a MPT key from an element of
+/// an array stored at a given storage slot.
+/// Deriving a MPT key from an array element is done like:
+/// 1. location = keccak(left_pad32(slot)) + index
+/// 2. mpt_key = keccak(location)
+/// The index is enforced to be smaller than the length of the array.
+/// WARNING: Currently takes the assumption that the storage slot number fits inside a single byte.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ArraySlot {
+    slot: u8,
+    index: u32,
+    length: u32,
+}
+
+impl ArraySlot {
+    pub fn new(slot: u8, index: u32, length: u32) -> Self {
+        Self {
+            slot,
+            index,
+            length,
+        }
+    }
+}
+
+/// Contains the wires associated with the MPT key derivation logic of an array element.
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct ArraySlotWires {
+    /// "input" storage slot of the array, range checked to fit in a single byte
+    pub(crate) slot: Target,
+    /// "input" index of the element in the array
+    pub(crate) index: Target,
+    /// "input" length of the array the index is checked against
+    pub(crate) length: Target,
+    /// index of the element as 32 big-endian bytes, i.e. `left_pad32(index)`.
+    /// It doesn't need to be assigned.
+    pub(crate) index_bytes: Array<Target, MAPPING_KEY_LEN>,
+    /// Actual keccak wires created for the computation of the base location
+    /// of the array, i.e. `keccak(left_pad32(slot))`
+    pub(crate) keccak_base: ByteKeccakWires<INPUT_PADDED_LEN>,
+    /// Actual keccak wires created for the computation of the final MPT key
+    /// from the location of the element.
+    pub(crate) keccak_mpt_key: KeccakWires<{ PAD_LEN(HASH_LEN) }>,
+    /// The MPT key derived in circuit from the array element, in NIBBLES
+    pub(crate) mpt_key: MPTKeyWire,
+}
+
+/// Number of bytes the index of an array element is decomposed into
+const ARRAY_INDEX_LEN: usize = 4;
+
+impl ArraySlot {
+    /// Derives the mpt_key in circuit for the element of the array.
+    /// Remember the rules to get the mpt key is as follow:
+    /// * location = keccak256(pad32(slot)) + index
+    /// * mpt_key = keccak256(location)
+    /// Note the array length wire is NOT bound to the contract, because it is
+    /// expected to be given by the verifier.
+    pub fn mpt_key<F: RichField + Extendable<D>, const D: usize>(
+        b: &mut CircuitBuilder<F, D>,
+    ) -> ArraySlotWires {
+        let zero = b.zero();
+        let tru = b._true();
+        let slot = b.add_virtual_target();
+        b.range_check(slot, 8);
+        let index = b.add_virtual_target();
+        let length = b.add_virtual_target();
+        b.range_check(length, 32);
+
+        // the index must be within the array
+        let is_in_range = less_than(b, index, length, 32);
+        b.connect(is_in_range.target, tru.target);
+
+        // decompose the index into big-endian bytes, which also range checks it
+        // over 32 bits
+        let mut index_bytes = [zero; MAPPING_KEY_LEN];
+        let mut rest = index;
+        for i in 0..ARRAY_INDEX_LEN - 1 {
+            let (low, high) = b.split_low_high(rest, 8, 8 * (ARRAY_INDEX_LEN - i));
+            index_bytes[MAPPING_KEY_LEN - 1 - i] = low;
+            rest = high;
+        }
+        index_bytes[MAPPING_KEY_LEN - ARRAY_INDEX_LEN] = rest;
+
+        // keccak(left_pad32(slot))
+        let mut arr = [zero; INPUT_PADDED_LEN];
+        arr[INPUT_ELEMENT_LEN - 1] = slot;
+        let inputs = VectorWire::<Target, INPUT_PADDED_LEN> {
+            real_len: b.constant(F::from_canonical_usize(INPUT_ELEMENT_LEN)),
+            arr: Array { arr },
+        };
+        let keccak_base = KeccakCircuit::<INPUT_PADDED_LEN>::hash_to_bytes(b, &inputs);
+        keccak_base.output.assert_bytes(b);
+
+        // location = base + index
+        let location = add_location_offset(b, &keccak_base.output.arr, &index_bytes);
+        let (keccak_mpt_key, mpt_key) = location_mpt_key(b, &location);
+
+        ArraySlotWires {
+            slot,
+            index,
+            length,
+            index_bytes: Array { arr: index_bytes },
+            keccak_base,
+            keccak_mpt_key,
+            mpt_key,
+        }
+    }
+
+    pub fn assign<F: RichField>(&self, pw: &mut PartialWitness<F>, wires: &ArraySlotWires) {
+        pw.set_target(wires.slot, F::from_canonical_u8(self.slot));
+        pw.set_target(wires.index, F::from_canonical_u32(self.index));
+        pw.set_target(wires.length, F::from_canonical_u32(self.length));
+        KeccakCircuit::<{ INPUT_PADDED_LEN }>::assign_byte_keccak(
+            pw,
+            &wires.keccak_base,
+            &InputData::Assigned(
+                &Vector::from_vec(&left_pad32(&[self.slot]))
+                    .expect("Can't create vector input for keccak_base"),
+            ),
+        );
+        let location = StorageSlot::ArrayElement(self.slot as usize, self.index as usize)
+            .location()
+            .as_fixed_bytes()
+            .to_vec();
+        KeccakCircuit::<{ PAD_LEN(HASH_LEN) }>::assign(
+            pw,
+            &wires.keccak_mpt_key,
+            &InputData::Assigned(
+                &Vector::from_vec(&location).expect("Can't create vector input for keccak_mpt"),
+            ),
+        );
+    }
 }
 
 #[cfg(test)]
 mod test {
 
+    use ethers::types::H256;
     use mrp2_test_utils::circuit::{run_circuit, UserCircuit};
     use plonky2::{
         field::extension::Extendable,
@@ -303,14 +515,16 @@ mod test {
 
     use crate::{
         array::Array,
-        eth::StorageSlot,
+        eth::{left_pad32, StorageSlot},
         keccak::{HASH_LEN, PACKED_HASH_LEN},
         mpt_sequential::utils::bytes_to_nibbles,
         rlp::MAX_KEY_NIBBLE_LEN,
         utils::{convert_u8_slice_to_u32_fields, keccak256},
     };
 
-    use super::{MappingSlot, MappingSlotWires, SimpleSlot, SimpleSlotWires};
+    use super::{
+        ArraySlot, ArraySlotWires, MappingSlot, MappingSlotWires, SimpleSlot, SimpleSlotWires,
+    };
 
     #[derive(Clone, Debug)]
     struct TestMappingSlot {
@@ -396,16 +610,32 @@ mod test {
         let slot = StorageSlot::Mapping(mapping_key.clone(), mapping_slot);
         let mpt_key = slot.mpt_key_vec();
         let circuit = TestMappingSlot {
-            m: MappingSlot {
-                mapping_key,
-                mapping_slot: mapping_slot as u8,
-            },
+            m: MappingSlot::new(mapping_slot as u8, mapping_key, 0),
             exp_mpt_key_nibbles: bytes_to_nibbles(&mpt_key),
             exp_keccak_location: slot.location().as_bytes().to_vec(),
         };
         run_circuit::<F, D, C, _>(circuit);
     }
 
+    #[test]
+    fn test_mapping_slot_key_derivation_with_base_offset() {
+        let mapping_key = hex::decode("1234").unwrap();
+        let base_slot_offset = 5u8;
+        let mapping_slot = 2u8;
+        // location computed by ethers over the storage slot `base + declared_index`
+        let storage_slot = H256::from_low_u64_be((base_slot_offset + mapping_slot) as u64);
+        let location = ethers::utils::keccak256(
+            [left_pad32(&mapping_key).as_slice(), storage_slot.as_bytes()].concat(),
+        );
+        let mpt_key = ethers::utils::keccak256(location);
+        let circuit = TestMappingSlot {
+            m: MappingSlot::new(mapping_slot, mapping_key, base_slot_offset),
+            exp_mpt_key_nibbles: bytes_to_nibbles(&mpt_key),
+            exp_keccak_location: location.to_vec(),
+        };
+        run_circuit::<F, D, C, _>(circuit);
+    }
+
     #[derive(Clone, Debug)]
     struct TestSimpleSlot {
         slot: u8,
@@ -434,4 +664,54 @@ mod test {
         let circuit = TestSimpleSlot { slot: 8 };
         run_circuit::<F, D, C, _>(circuit);
     }
+
+    #[derive(Clone, Debug)]
+    struct TestArraySlot {
+        slot: u8,
+        index: u32,
+        length: u32,
+    }
+
+    impl UserCircuit<F, D> for TestArraySlot {
+        type Wires = (ArraySlotWires, Array<Target, MAX_KEY_NIBBLE_LEN>);
+
+        fn build(c: &mut CircuitBuilder<F, D>) -> Self::Wires {
+            let wires = ArraySlot::mpt_key(c);
+            let exp_key = Array::new(c);
+            wires.mpt_key.key.enforce_equal(c, &exp_key);
+            (wires, exp_key)
+        }
+
+        fn prove(&self, pw: &mut PartialWitness<F>, wires: &Self::Wires) {
+            let eth_slot = StorageSlot::ArrayElement(self.slot as usize, self.index as usize);
+            let circuit = ArraySlot::new(self.slot, self.index, self.length);
+            circuit.assign(pw, &wires.0);
+            wires.1.assign_bytes(pw, &eth_slot.mpt_nibbles());
+        }
+    }
+
+    #[test]
+    fn test_array_slot() {
+        run_circuit::<F, D, C, _>(TestArraySlot {
+            slot: 3,
+            index: 0,
+            length: 1,
+        });
+        // index spanning several bytes, so that the addition carries over
+        run_circuit::<F, D, C, _>(TestArraySlot {
+            slot: 3,
+            index: 0x01_02_03_04,
+            length: u32::MAX,
+        });
+
+        // index out of the array
+        let res = std::panic::catch_unwind(|| {
+            run_circuit::<F, D, C, _>(TestArraySlot {
+                slot: 3,
+                index: 5,
+                length: 5,
+            })
+        });
+        assert!(res.is_err(), "index out of the array not detected");
+    }
 }
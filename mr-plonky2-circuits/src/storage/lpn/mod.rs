@@ -26,6 +26,9 @@ use crate::{
 const LEAF_SIZE: usize = 32;
 /// Size of the input value we insert in a leaf node. Note the key is compacted in u32 slice before hashing.
 const KEY_SIZE: usize = 32;
+/// Domain-separation tag prepended to the inputs of the digest of an array element, so that
+/// the digest of the element at index `i` differs from the one of a mapping entry with key `i`
+pub const ARRAY_LEAF_DIGEST_TAG: u32 = u32::from_be_bytes(*b"ARRY");
 
 /// Returns the hash of the leaf node in the storage database for a mapping variable
 /// given a mapping key and its associated value. The key and value must be both
@@ -75,6 +78,21 @@ pub fn leaf_digest_for_mapping(key: &[u8], value: &[u8]) -> Digest {
     leaf_digest(&key.into_iter().chain(value).collect::<Vec<_>>())
 }
 
+/// Computes the digest for an array element with its index and value, domain separated
+/// from the digest of a mapping entry by `ARRAY_LEAF_DIGEST_TAG`
+pub fn leaf_digest_for_array(index: u32, value: &[u8]) -> Digest {
+    assert!(value.len() <= LEAF_SIZE);
+    let key = left_pad32(&index.to_be_bytes());
+    let value = left_pad32(value);
+    let u32_slice = convert_u8_to_u32_slice(&key.into_iter().chain(value).collect::<Vec<_>>());
+    map_to_curve_point(
+        &std::iter::once(ARRAY_LEAF_DIGEST_TAG)
+            .chain(u32_slice)
+            .map(GoldilocksField::from_canonical_u32)
+            .collect::<Vec<_>>(),
+    )
+}
+
 /// Computes the digest for a leaf node in the storage database
 pub fn leaf_digest(value: &[u8]) -> Digest {
     assert!(value.len() % 4 == 0, "value must be a multiple of 4 bytes");
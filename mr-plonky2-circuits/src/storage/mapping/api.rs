@@ -1,12 +1,14 @@
+use super::array_leaf::ArrayLeafCircuit;
+use super::array_leaf::ArrayLeafWires;
 use super::extension::ExtensionNodeCircuit;
 use super::extension::ExtensionWires;
 use super::leaf::LeafCircuit;
 use super::leaf::LeafWires;
-use super::leaf::StorageLeafWire;
 use super::PublicInputs;
 use crate::api::default_config;
 use crate::api::ProofWithVK;
 use crate::mpt_sequential::PAD_LEN;
+use crate::storage::key::ArraySlot;
 use crate::storage::key::MappingSlot;
 use crate::storage::mapping::branch::BranchCircuit;
 use crate::storage::mapping::branch::BranchWires;
@@ -38,9 +40,11 @@ type F = <C as GenericConfig<D>>::F;
 
 #[derive(Serialize, Deserialize)]
 /// CircuitType is a wrapper around the different specialized circuits that can be used to prove a MPT node recursively
-/// NOTE: Right now these circuits are specialized to prove inclusion of a single mapping slot.
+/// NOTE: Right now these circuits are specialized to prove inclusion of a single mapping slot,
+/// or of the elements of an array.
 pub enum CircuitInput {
     Leaf(LeafCircuit<MAX_LEAF_NODE_LEN>),
+    ArrayLeaf(ArrayLeafCircuit<MAX_LEAF_NODE_LEN>),
     Extension(ExtensionInput),
     Branch(BranchInput),
 }
@@ -50,7 +54,15 @@ impl CircuitInput {
     pub fn new_leaf(node: Vec<u8>, slot: usize, mapping_key: Vec<u8>) -> Self {
         CircuitInput::Leaf(LeafCircuit {
             node,
-            slot: MappingSlot::new(slot as u8, mapping_key),
+            slot: MappingSlot::new(slot as u8, mapping_key, 0),
+        })
+    }
+    /// Returns a circuit input for proving a leaf MPT node storing the element `index` of
+    /// the array of `length` elements at `slot`. The circuit enforces `index < length`.
+    pub fn new_array_leaf(node: Vec<u8>, slot: usize, index: usize, length: usize) -> Self {
+        CircuitInput::ArrayLeaf(ArrayLeafCircuit {
+            node,
+            slot: ArraySlot::new(slot as u8, index as u32, length as u32),
         })
     }
     /// Returns a circuit input for proving an extension MPT node
@@ -73,29 +85,48 @@ impl CircuitInput {
 /// Main struct holding the different circuit parameters for each of the MPT circuits defined here.
 /// Most notably, it holds them in a way to use the recursion framework allowing us to specialize
 /// circuits according to the situation.
-pub struct PublicParameters {
-    leaf_circuit: CircuitWithUniversalVerifier<F, C, D, 0, StorageLeafWire>,
+/// The maximum length of the leaf and branch nodes that can be proven is given by `LEAF_NODE_LEN`
+/// and `BRANCH_NODE_LEN`, allowing to build parameters for tries with larger nodes than the
+/// default ones.
+pub struct PublicParameters<
+    const LEAF_NODE_LEN: usize = MAX_LEAF_NODE_LEN,
+    const BRANCH_NODE_LEN: usize = MAX_BRANCH_NODE_LEN,
+> where
+    [(); PAD_LEN(LEAF_NODE_LEN)]:,
+    [(); PAD_LEN(BRANCH_NODE_LEN)]:,
+{
+    leaf_circuit: CircuitWithUniversalVerifier<F, C, D, 0, LeafWires<LEAF_NODE_LEN>>,
+    array_leaf_circuit: CircuitWithUniversalVerifier<F, C, D, 0, ArrayLeafWires<LEAF_NODE_LEN>>,
     ext_circuit: CircuitWithUniversalVerifier<F, C, D, 1, ExtensionWires>,
     #[cfg(not(test))]
-    branchs: BranchCircuits,
+    branchs: BranchCircuitsGenericNodeLen<BRANCH_NODE_LEN>,
     #[cfg(test)]
-    branchs: TestBranchCircuits,
+    branchs: TestBranchCircuitsGenericNodeLen<BRANCH_NODE_LEN>,
     #[cfg(not(test))]
     set: RecursiveCircuits<F, C, D>,
     #[cfg(test)]
     set: TestingRecursiveCircuits<F, C, D, NUM_IO>,
 }
 /// Public API employed to build the MPT circuits, which are returned in serialized form
-pub fn build_circuits_params() -> PublicParameters {
+pub fn build_circuits_params<const LEAF_NODE_LEN: usize, const BRANCH_NODE_LEN: usize>(
+) -> PublicParameters<LEAF_NODE_LEN, BRANCH_NODE_LEN>
+where
+    [(); PAD_LEN(LEAF_NODE_LEN)]:,
+    [(); PAD_LEN(BRANCH_NODE_LEN)]:,
+{
     PublicParameters::build()
 }
 
 /// Public API employed to generate a proof for the circuit specified by `CircuitType`,
 /// employing the `circuit_params` generated with the `build_circuits_params` API
-pub fn generate_proof(
-    circuit_params: &PublicParameters,
+pub fn generate_proof<const LEAF_NODE_LEN: usize, const BRANCH_NODE_LEN: usize>(
+    circuit_params: &PublicParameters<LEAF_NODE_LEN, BRANCH_NODE_LEN>,
     circuit_type: CircuitInput,
-) -> Result<Vec<u8>> {
+) -> Result<Vec<u8>>
+where
+    [(); PAD_LEN(LEAF_NODE_LEN)]:,
+    [(); PAD_LEN(BRANCH_NODE_LEN)]:,
+{
     circuit_params.generate_proof(circuit_type)?.serialize()
 }
 #[derive(Serialize, Deserialize)]
@@ -147,12 +178,15 @@ macro_rules! impl_branch_circuits {
         in combination with the node input length."]
         pub type $struct_name =  [< $struct_name GenericNodeLen>]<MAX_BRANCH_NODE_LEN>;
 
-        impl $struct_name {
+        impl<const NODE_LEN: usize> [< $struct_name GenericNodeLen>]<NODE_LEN>
+        where
+            [(); PAD_LEN(NODE_LEN)]:,
+        {
             fn new(builder: &CircuitWithUniversalVerifierBuilder<F, D, NUM_IO>) -> Self {
-                $struct_name {
+                Self {
                     $(
                         // generate one circuit with full node len
-                        [< b $i >]:  builder.build_circuit::<C, $i, BranchWires<MAX_BRANCH_NODE_LEN>>(()),
+                        [< b $i >]:  builder.build_circuit::<C, $i, BranchWires<NODE_LEN>>(()),
                     )+
                 }
             }
@@ -198,7 +232,7 @@ macro_rules! impl_branch_circuits {
                 if child_proofs.is_empty() || child_proofs.len() > 16 {
                     bail!("No child proofs or too many child proofs");
                 }
-                if branch_node.node.len() > MAX_BRANCH_NODE_LEN {
+                if branch_node.node.len() > NODE_LEN {
                     bail!("Branch node too long");
                 }
 
@@ -220,6 +254,8 @@ macro_rules! impl_branch_circuits {
                         (proof.clone(), vk)
                     })
                     .unzip();
+                 // the arities are given in increasing order to the macro, so the first
+                 // matching arm selects the tightest circuit for the number of children
                  match child_proofs.len() {
                      $(_ if $i == child_proofs.len() => {
                          set.generate_proof(
@@ -268,17 +304,23 @@ type C = crate::api::C;
     }
 }
 
-impl_branch_circuits!(BranchCircuits, 2, 9, 16);
+// arities must be listed in increasing order
+impl_branch_circuits!(BranchCircuits, 2, 4, 8, 9, 16);
 #[cfg(test)]
-impl_branch_circuits!(TestBranchCircuits, 1, 4, 9);
+impl_branch_circuits!(TestBranchCircuits, 1, 4, 8, 9);
 
 /// number of circuits in the set
 #[cfg(not(test))]
-const MAPPING_CIRCUIT_SET_SIZE: usize = 3 + 2; // 3 branch circuits + 1 ext + 1 leaf
+const MAPPING_CIRCUIT_SET_SIZE: usize = 5 + 3; // 5 branch circuits + 1 ext + 2 leaves
 #[cfg(test)]
-const MAPPING_CIRCUIT_SET_SIZE: usize = 3 + 2; // 3 branch + 1 ext + 1 leaf
-
-impl PublicParameters {
+const MAPPING_CIRCUIT_SET_SIZE: usize = 4 + 3; // 4 branch + 1 ext + 2 leaves
+
+impl<const LEAF_NODE_LEN: usize, const BRANCH_NODE_LEN: usize>
+    PublicParameters<LEAF_NODE_LEN, BRANCH_NODE_LEN>
+where
+    [(); PAD_LEN(LEAF_NODE_LEN)]:,
+    [(); PAD_LEN(BRANCH_NODE_LEN)]:,
+{
     /// Generates the circuit parameters for the MPT circuits.
     fn build() -> Self {
         let config = default_config();
@@ -294,18 +336,23 @@ impl PublicParameters {
         );
 
         debug!("Building leaf circuit");
-        let leaf_circuit = circuit_builder.build_circuit::<C, 0, LeafWires<MAX_LEAF_NODE_LEN>>(());
+        let leaf_circuit = circuit_builder.build_circuit::<C, 0, LeafWires<LEAF_NODE_LEN>>(());
+
+        debug!("Building array leaf circuit");
+        let array_leaf_circuit =
+            circuit_builder.build_circuit::<C, 0, ArrayLeafWires<LEAF_NODE_LEN>>(());
 
         debug!("Building extension circuit");
         let ext_circuit = circuit_builder.build_circuit::<C, 1, ExtensionWires>(());
 
         debug!("Building branch circuits");
         #[cfg(not(test))]
-        let branch_circuits = BranchCircuits::new(&circuit_builder);
+        let branch_circuits = BranchCircuitsGenericNodeLen::new(&circuit_builder);
         #[cfg(test)]
-        let branch_circuits = TestBranchCircuits::new(&circuit_builder);
+        let branch_circuits = TestBranchCircuitsGenericNodeLen::new(&circuit_builder);
         let mut circuits_set = vec![
             leaf_circuit.get_verifier_data().circuit_digest,
+            array_leaf_circuit.get_verifier_data().circuit_digest,
             ext_circuit.get_verifier_data().circuit_digest,
         ];
         circuits_set.extend(branch_circuits.circuit_set());
@@ -313,6 +360,7 @@ impl PublicParameters {
 
         PublicParameters {
             leaf_circuit,
+            array_leaf_circuit,
             ext_circuit,
             branchs: branch_circuits,
             #[cfg(not(test))]
@@ -328,9 +376,28 @@ impl PublicParameters {
         #[cfg(test)]
         let set = &self.set.get_recursive_circuit_set();
         match circuit_type {
-            CircuitInput::Leaf(leaf) => set
-                .generate_proof(&self.leaf_circuit, [], [], leaf)
-                .map(|p| (p, self.leaf_circuit.get_verifier_data().clone()).into()),
+            CircuitInput::Leaf(leaf) => {
+                if leaf.node.len() > LEAF_NODE_LEN {
+                    bail!("Leaf node too long");
+                }
+                let leaf = LeafCircuit::<LEAF_NODE_LEN> {
+                    node: leaf.node,
+                    slot: leaf.slot,
+                };
+                set.generate_proof(&self.leaf_circuit, [], [], leaf)
+                    .map(|p| (p, self.leaf_circuit.get_verifier_data().clone()).into())
+            }
+            CircuitInput::ArrayLeaf(leaf) => {
+                if leaf.node.len() > LEAF_NODE_LEN {
+                    bail!("Leaf node too long");
+                }
+                let leaf = ArrayLeafCircuit::<LEAF_NODE_LEN> {
+                    node: leaf.node,
+                    slot: leaf.slot,
+                };
+                set.generate_proof(&self.array_leaf_circuit, [], [], leaf)
+                    .map(|p| (p, self.array_leaf_circuit.get_verifier_data().clone()).into())
+            }
             CircuitInput::Extension(ext) => {
                 let mut child_proofs = ext.get_child_proofs()?;
                 let (child_proof, child_vk) = child_proofs
@@ -369,16 +436,21 @@ impl PublicParameters {
 #[cfg(test)]
 mod test {
     use eth_trie::{EthTrie, MemoryDB, Trie};
-    use mrp2_test_utils::{mpt_sequential::generate_random_storage_mpt, utils::random_vector};
+    use mrp2_test_utils::{
+        mpt_sequential::{build_mapping_trie, generate_random_storage_mpt},
+        utils::random_vector,
+    };
     use plonky2::field::{goldilocks_field::GoldilocksField, types::Field};
     use plonky2_ecgfp5::curve::curve::Point;
     use serial_test::serial;
-    use std::sync::Arc;
 
     use super::*;
     use crate::{
-        api::lpn_storage::leaf_digest_for_mapping, eth::StorageSlot,
-        mpt_sequential::utils::bytes_to_nibbles, storage::key::MappingSlot, types::ADDRESS_LEN,
+        api::lpn_storage::{leaf_digest_for_array, leaf_digest_for_mapping},
+        eth::StorageSlot,
+        mpt_sequential::utils::bytes_to_nibbles,
+        storage::key::MappingSlot,
+        types::ADDRESS_LEN,
     };
 
     struct TestData {
@@ -430,7 +502,7 @@ mod test {
     #[test]
     #[serial]
     fn test_serialization() {
-        let params = PublicParameters::build();
+        let params: PublicParameters = PublicParameters::build();
 
         let encoded = bincode::serialize(&params).unwrap();
         let decoded_params: PublicParameters = bincode::deserialize(&encoded).unwrap();
@@ -442,7 +514,7 @@ mod test {
         let p1 = test_data.trie.get_proof(&test_data.mpt_keys[0]).unwrap();
         let l1 = CircuitInput::Leaf(LeafCircuit {
             node: p1.last().unwrap().to_vec(),
-            slot: MappingSlot::new(slot as u8, test_data.key.clone()),
+            slot: MappingSlot::new(slot as u8, test_data.key.clone(), 0),
         });
 
         let encoded = bincode::serialize(&l1).unwrap();
@@ -481,9 +553,9 @@ mod test {
     #[test]
     #[serial]
     fn test_branch_logic() {
-        let params = PublicParameters::build();
+        let params: PublicParameters = PublicParameters::build();
         let slot = 0;
-        let num_children = 6;
+        let num_children = 9;
         let mut test_data = generate_storage_trie_and_keys(slot, num_children);
         let trie = &mut test_data.trie;
         let key = &test_data.key;
@@ -583,6 +655,30 @@ mod test {
             assert_eq!(pi1.mapping_slot(), branch_pub.mapping_slot());
         };
         check_public_input(2, &branch2);
+        // a branch with 5 children must be proven by the tightest circuit, i.e. the
+        // 8-arity one rather than the 9-arity one
+        let serialized_child_proofs = std::iter::once(bincode::serialize(&leaf1_proof).unwrap())
+            .chain((1..5).map(|i| {
+                bincode::serialize(&gen_fake_proof(test_data.mpt_keys[i].as_slice())).unwrap()
+            }))
+            .collect();
+        println!("[+] Generating branch proof 5...");
+        let branch_inputs = CircuitInput::Branch(BranchInput {
+            input: InputNode {
+                node: branch_node.clone(),
+            },
+            serialized_child_proofs,
+        });
+        let branch5 = params.generate_proof(branch_inputs).unwrap();
+        assert_eq!(
+            branch5.verifier_data(),
+            params.branchs.b8.get_verifier_data()
+        );
+        assert_ne!(
+            branch5.verifier_data(),
+            params.branchs.b9.get_verifier_data()
+        );
+        check_public_input(5, &branch5);
         // generate num_children-2 fake proofs to tesr branch circuit with num_children proofs
         let mut serialized_child_proofs = vec![
             bincode::serialize(&leaf1_proof).unwrap(),
@@ -608,34 +704,28 @@ mod test {
 
     #[test]
     fn test_mapping_api() {
-        let memdb = Arc::new(MemoryDB::new(true));
-        let mut trie = EthTrie::new(memdb.clone());
-
         let key1 = [1u8; 4];
         let val1 = [2u8; ADDRESS_LEN];
-        let slot1 = StorageSlot::Mapping(key1.to_vec(), 0);
-        let mpt_key1 = slot1.mpt_key();
 
         let key2 = [3u8; 4];
         let val2 = [4u8; ADDRESS_LEN];
-        let slot2 = StorageSlot::Mapping(key2.to_vec(), 0);
-        let mpt_key2 = slot2.mpt_key();
 
-        trie.insert(&mpt_key1, &rlp::encode(&val1.as_slice()))
-            .unwrap();
-        trie.insert(&mpt_key2, &rlp::encode(&val2.as_slice()))
-            .unwrap();
-        trie.root_hash().unwrap();
-
-        let proof1 = trie.get_proof(&mpt_key1).unwrap();
-        let proof2 = trie.get_proof(&mpt_key2).unwrap();
+        let (_, proofs) = build_mapping_trie(
+            &[
+                (key1.to_vec(), val1.to_vec()),
+                (key2.to_vec(), val2.to_vec()),
+            ],
+            0,
+        )
+        .unwrap();
+        let [proof1, proof2] = proofs.try_into().unwrap();
 
         assert_eq!(proof1[0], proof2[0]);
         // only need to make sure node above is really a branch node
         assert!(rlp::decode_list::<Vec<u8>>(&proof1[0]).len() == 17);
         use crate::storage::mapping::{self};
         println!("Generating params...");
-        let params = mapping::api::build_circuits_params();
+        let params: mapping::PublicParameters = mapping::api::build_circuits_params();
         println!("Proving leaf 1...");
 
         let leaf_input1 = mapping::CircuitInput::new_leaf(proof1[1].clone(), 0, key1.to_vec());
@@ -662,4 +752,97 @@ mod test {
 
         mapping::api::generate_proof(&params, branch_input).unwrap();
     }
+
+    #[test]
+    #[serial]
+    fn test_array_api() {
+        let array_slot = 2;
+        let array_len = 2;
+        let mut trie = EthTrie::new(std::sync::Arc::new(MemoryDB::new(true)));
+        let values = (0..array_len)
+            .map(|_| random_vector(32))
+            .collect::<Vec<_>>();
+        let mpt_keys = (0..array_len)
+            .map(|i| StorageSlot::ArrayElement(array_slot, i).mpt_key_vec())
+            .collect::<Vec<_>>();
+        for (key, value) in mpt_keys.iter().zip(&values) {
+            trie.insert(key, &rlp::encode(value)).unwrap();
+        }
+        trie.root_hash().unwrap();
+        let proofs = mpt_keys
+            .iter()
+            .map(|key| trie.get_proof(key).unwrap())
+            .collect::<Vec<_>>();
+        // both elements are stored in leaves below the same branch node
+        assert_eq!(proofs[0].len(), proofs[1].len());
+        let branch_node = proofs[0][proofs[0].len() - 2].clone();
+        assert_eq!(branch_node, proofs[1][proofs[1].len() - 2]);
+
+        let params: PublicParameters = PublicParameters::build();
+        let leaf_proofs = proofs
+            .iter()
+            .enumerate()
+            .map(|(i, proof)| {
+                println!("[+] Generating array leaf proof {}...", i);
+                let leaf_input = CircuitInput::new_array_leaf(
+                    proof.last().unwrap().to_vec(),
+                    array_slot,
+                    i,
+                    array_len,
+                );
+                let leaf_proof = generate_proof(&params, leaf_input).unwrap();
+                let lp = ProofWithVK::deserialize(&leaf_proof).unwrap();
+                assert_eq!(
+                    lp.verifier_data(),
+                    params.array_leaf_circuit.get_verifier_data()
+                );
+                let pi = PublicInputs::from(&lp.proof.public_inputs[..NUM_IO]);
+                assert_eq!(pi.mapping_slot(), F::from_canonical_usize(array_slot));
+                let exp_digest = leaf_digest_for_array(i as u32, &values[i]);
+                assert_eq!(pi.accumulator(), exp_digest.to_weierstrass());
+                leaf_proof
+            })
+            .collect::<Vec<_>>();
+
+        println!("[+] Generating branch proof...");
+        let branch_input = CircuitInput::new_branch(branch_node, leaf_proofs);
+        let branch_proof =
+            ProofWithVK::deserialize(&generate_proof(&params, branch_input).unwrap()).unwrap();
+        let pi = PublicInputs::from(&branch_proof.proof.public_inputs[..NUM_IO]);
+        assert_eq!(pi.n(), F::from_canonical_usize(array_len));
+        assert_eq!(pi.mapping_slot(), F::from_canonical_usize(array_slot));
+    }
+
+    #[test]
+    #[serial]
+    fn test_large_leaf_node() {
+        const LEAF_NODE_LEN: usize = MAX_LEAF_NODE_LEN + 11;
+        // a trie with a single entry is made of a leaf storing the whole key, which
+        // exceeds the default leaf length for a 32-byte value
+        let mut trie = EthTrie::new(std::sync::Arc::new(MemoryDB::new(true)));
+        let slot = 1;
+        let key = random_vector(20);
+        let mpt_key = StorageSlot::Mapping(key.clone(), slot).mpt_key_vec();
+        let mut value = random_vector(32);
+        value[0] = 0xff;
+        trie.insert(&mpt_key, &rlp::encode(&value)).unwrap();
+        trie.root_hash().unwrap();
+        let proof = trie.get_proof(&mpt_key).unwrap();
+        assert_eq!(proof.len(), 1);
+        let node = proof[0].clone();
+        assert!(node.len() > MAX_LEAF_NODE_LEN && node.len() <= LEAF_NODE_LEN);
+
+        let params = build_circuits_params::<LEAF_NODE_LEN, MAX_BRANCH_NODE_LEN>();
+        let leaf_proof = generate_proof(&params, CircuitInput::new_leaf(node, slot, key.clone()))
+            .and_then(|p| ProofWithVK::deserialize(&p))
+            .unwrap();
+        let pi = PublicInputs::from(&leaf_proof.proof.public_inputs[..NUM_IO]);
+        let (_, ptr) = pi.mpt_key_info();
+        // the whole key has been processed
+        assert_eq!(ptr, F::NEG_ONE);
+        assert_eq!(
+            pi.accumulator(),
+            leaf_digest_for_mapping(&key, &value).to_weierstrass()
+        );
+    }
 }
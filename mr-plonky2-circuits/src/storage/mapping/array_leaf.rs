@@ -0,0 +1,239 @@
+//! Module handling the proving of a leaf node storing an element of an array
+//! inside a storage trie.
+
+use crate::storage::key::{ArraySlot, ArraySlotWires};
+use crate::storage::lpn::ARRAY_LEAF_DIGEST_TAG;
+use crate::types::MAPPING_LEAF_VALUE_LEN;
+use crate::{
+    array::{Array, Vector, VectorWire},
+    keccak::{InputData, KeccakCircuit, KeccakWires},
+    mpt_sequential::PAD_LEN,
+};
+use plonky2::{
+    field::{goldilocks_field::GoldilocksField, types::Field},
+    iop::{target::Target, witness::PartialWitness},
+    plonk::circuit_builder::CircuitBuilder,
+};
+use recursion_framework::circuit_builder::CircuitLogicWires;
+use serde::{Deserialize, Serialize};
+
+use super::leaf::build_leaf_value;
+use crate::storage::mapping::public_inputs::PublicInputs;
+
+/// Circuit proving the correct derivation of the MPT key from an array slot and
+/// the index of an element, and extracting the element stored in the leaf node.
+/// The initial accumulator is computed from the pair (`left_pad32(index)`, value),
+/// the same way a leaf of a mapping is, so that the proofs can be aggregated by the
+/// extension and branch circuits, but prefixed by `ARRAY_LEAF_DIGEST_TAG`, so that
+/// array elements can't be mixed with mapping entries in the same accumulator.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct ArrayLeafCircuit<const NODE_LEN: usize> {
+    pub(crate) node: Vec<u8>,
+    pub(crate) slot: ArraySlot,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub(crate) struct ArrayLeafWires<const NODE_LEN: usize>
+where
+    [(); PAD_LEN(NODE_LEN)]:,
+{
+    node: VectorWire<Target, { PAD_LEN(NODE_LEN) }>,
+    root: KeccakWires<{ PAD_LEN(NODE_LEN) }>,
+    array_slot: ArraySlotWires,
+    value: Array<Target, MAPPING_LEAF_VALUE_LEN>,
+}
+
+impl<const NODE_LEN: usize> ArrayLeafCircuit<NODE_LEN>
+where
+    [(); PAD_LEN(NODE_LEN)]:,
+{
+    pub fn build(b: &mut CircuitBuilder<GoldilocksField, 2>) -> ArrayLeafWires<NODE_LEN> {
+        let node = VectorWire::<Target, { PAD_LEN(NODE_LEN) }>::new(b);
+        node.assert_bytes(b);
+
+        let root = KeccakCircuit::<{ PAD_LEN(NODE_LEN) }>::hash_vector(b, &node);
+
+        // derives the MPT key from the (slot, index) pair
+        let array_slot = ArraySlot::mpt_key(b);
+
+        let tag = b.constant(GoldilocksField::from_canonical_u32(ARRAY_LEAF_DIGEST_TAG));
+        let value = build_leaf_value::<NODE_LEN>(
+            b,
+            &node,
+            &root,
+            &array_slot.mpt_key,
+            &array_slot.index_bytes,
+            array_slot.slot,
+            Some(tag),
+        );
+        ArrayLeafWires {
+            node,
+            root,
+            array_slot,
+            value,
+        }
+    }
+
+    pub fn assign(
+        &self,
+        pw: &mut PartialWitness<GoldilocksField>,
+        wires: &ArrayLeafWires<NODE_LEN>,
+    ) {
+        let pad_node =
+            Vector::<u8, { PAD_LEN(NODE_LEN) }>::from_vec(&self.node).expect("invalid node given");
+        wires.node.assign(pw, &pad_node);
+        KeccakCircuit::<{ PAD_LEN(NODE_LEN) }>::assign(
+            pw,
+            &wires.root,
+            &InputData::Assigned(&pad_node),
+        );
+        self.slot.assign(pw, &wires.array_slot);
+    }
+}
+
+/// D = 2,
+/// Num of children = 0
+impl<const NODE_LEN: usize> CircuitLogicWires<GoldilocksField, 2, 0> for ArrayLeafWires<NODE_LEN>
+where
+    [(); PAD_LEN(NODE_LEN)]:,
+{
+    type CircuitBuilderParams = ();
+
+    type Inputs = ArrayLeafCircuit<NODE_LEN>;
+
+    const NUM_PUBLIC_INPUTS: usize = PublicInputs::<GoldilocksField>::TOTAL_LEN;
+
+    fn circuit_logic(
+        builder: &mut CircuitBuilder<GoldilocksField, 2>,
+        _verified_proofs: [&plonky2::plonk::proof::ProofWithPublicInputsTarget<2>; 0],
+        _builder_parameters: Self::CircuitBuilderParams,
+    ) -> Self {
+        ArrayLeafCircuit::build(builder)
+    }
+
+    fn assign_input(
+        &self,
+        inputs: Self::Inputs,
+        pw: &mut PartialWitness<GoldilocksField>,
+    ) -> anyhow::Result<()> {
+        inputs.assign(pw, self);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use eth_trie::{Nibbles, Trie};
+    use mrp2_test_utils::{
+        circuit::{run_circuit, UserCircuit},
+        mpt_sequential::generate_random_storage_mpt,
+        utils::random_vector,
+    };
+    use plonky2::{
+        field::types::Field,
+        plonk::config::{GenericConfig, PoseidonGoldilocksConfig},
+    };
+
+    use super::*;
+    use crate::{
+        eth::{left_pad32, StorageSlot},
+        mpt_sequential::utils::bytes_to_nibbles,
+        rlp::MAX_KEY_NIBBLE_LEN,
+        storage::lpn::{leaf_digest_for_array, leaf_digest_for_mapping},
+    };
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    #[derive(Clone, Debug)]
+    struct TestArrayLeafCircuit<const NODE_LEN: usize> {
+        c: ArrayLeafCircuit<NODE_LEN>,
+        exp_value: Vec<u8>,
+    }
+
+    impl<const NODE_LEN: usize> UserCircuit<F, D> for TestArrayLeafCircuit<NODE_LEN>
+    where
+        [(); PAD_LEN(NODE_LEN)]:,
+    {
+        // normal wires + expected extracted value
+        type Wires = (
+            ArrayLeafWires<NODE_LEN>,
+            Array<Target, MAPPING_LEAF_VALUE_LEN>,
+        );
+
+        fn build(b: &mut CircuitBuilder<F, D>) -> Self::Wires {
+            let exp_value = Array::<Target, MAPPING_LEAF_VALUE_LEN>::new(b);
+            let leaf_wires = ArrayLeafCircuit::<NODE_LEN>::build(b);
+            leaf_wires.value.enforce_equal(b, &exp_value);
+            (leaf_wires, exp_value)
+        }
+
+        fn prove(&self, pw: &mut PartialWitness<F>, wires: &Self::Wires) {
+            self.c.assign(pw, &wires.0);
+            wires
+                .1
+                .assign_bytes(pw, &left_pad32(&self.exp_value).try_into().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_array_leaf_circuit() {
+        const ARRAY_LEN: usize = 3;
+        let array_slot = 4;
+        let (mut trie, _) = generate_random_storage_mpt::<3, MAPPING_LEAF_VALUE_LEN>();
+        // insert a small array of fake uint256 values
+        let values = (0..ARRAY_LEN)
+            .map(|_| random_vector(MAPPING_LEAF_VALUE_LEN))
+            .collect::<Vec<_>>();
+        for (i, value) in values.iter().enumerate() {
+            let slot = StorageSlot::ArrayElement(array_slot, i);
+            let encoded_value: Vec<u8> = rlp::encode(value).to_vec();
+            trie.insert(&slot.mpt_key(), &encoded_value).unwrap();
+        }
+        trie.root_hash().unwrap();
+
+        let index = 1;
+        let mpt_key = StorageSlot::ArrayElement(array_slot, index).mpt_key_vec();
+        let proof = trie.get_proof(&mpt_key).unwrap();
+        let node = proof.last().unwrap().clone();
+        let circuit = |index: usize| TestArrayLeafCircuit {
+            c: ArrayLeafCircuit::<80> {
+                node: node.clone(),
+                slot: ArraySlot::new(array_slot as u8, index as u32, ARRAY_LEN as u32),
+            },
+            exp_value: values[1].clone(),
+        };
+
+        let proof = run_circuit::<F, D, C, _>(circuit(index));
+        let pi = PublicInputs::<F>::from(&proof.public_inputs);
+        {
+            // expected accumulator Acc((tag, index, value))
+            let exp_digest = leaf_digest_for_array(index as u32, &values[1]).to_weierstrass();
+            assert_eq!(exp_digest, pi.accumulator());
+            // which differs from the one of a mapping entry with the index as key
+            let mapping_digest =
+                leaf_digest_for_mapping(&(index as u32).to_be_bytes(), &values[1]).to_weierstrass();
+            assert_ne!(mapping_digest, pi.accumulator());
+        }
+        {
+            // expected mpt key wire
+            let (key, ptr) = pi.mpt_key_info();
+            let exp_key = bytes_to_nibbles(&mpt_key)
+                .into_iter()
+                .map(F::from_canonical_u8)
+                .collect::<Vec<_>>();
+            assert_eq!(key, exp_key);
+            let leaf_key: Vec<Vec<u8>> = rlp::decode_list(&node);
+            let nib = Nibbles::from_compact(&leaf_key[0].clone());
+            let exp_ptr = F::from_canonical_usize(MAX_KEY_NIBBLE_LEN - 1 - nib.nibbles().len());
+            assert_eq!(exp_ptr, ptr);
+        }
+        assert_eq!(pi.mapping_slot(), F::from_canonical_usize(array_slot));
+        assert_eq!(pi.n(), F::ONE);
+
+        // the leaf node doesn't store another element of the array
+        let res = std::panic::catch_unwind(|| run_circuit::<F, D, C, _>(circuit(2)));
+        assert!(res.is_err(), "wrong array element accepted");
+    }
+}
@@ -82,6 +82,8 @@ where
         let mut accumulator = b.curve_zero();
         // n being the total number of entries recursively verified
         let mut n = b.zero();
+        // a key is absent from this subtree if any processed child proves its absence
+        let mut is_absent = b._false();
         // we already decode the rlp headers here since we need it to verify
         // the validity of the hash exposed by the proofs
         let headers = decode_fixed_list::<_, _, MAX_ITEMS_IN_LIST>(b, &node.arr.arr, zero);
@@ -98,6 +100,9 @@ where
             // add the number of leaves this proof has processed
             let maybe_n = b.select(should_process, proof_inputs.n(), zero);
             n = b.add(n, maybe_n);
+            let child_is_absent = proof_inputs.absence_flag();
+            let maybe_is_absent = b.and(should_process, child_is_absent);
+            is_absent = b.or(is_absent, maybe_is_absent);
             let child_key = proof_inputs.mpt_key();
             let (_, hash, is_valid, nibble) =
                 MPTCircuit::<1, NODE_LEN>::advance_key_branch(b, &node.arr, &child_key, &headers);
@@ -139,7 +144,7 @@ where
 
         // we now extract the public input to register for this proofs
         let c = root.output_array.clone();
-        PublicInputs::register(b, &new_prefix, mapping_slot, n, &c, &accumulator);
+        PublicInputs::register(b, &new_prefix, mapping_slot, n, &c, &accumulator, is_absent);
         BranchWires {
             node,
             common_prefix,
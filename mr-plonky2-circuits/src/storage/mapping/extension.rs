@@ -75,6 +75,8 @@ impl ExtensionNodeCircuit {
             &root.output_array,
             // we pass the same accumulator since we didn't look at any value in this node
             &child_proof.accumulator(),
+            // the key is absent from this subtree if it's absent from the child one
+            child_proof.absence_flag(),
         );
         ExtensionWires { node, keccak: root }
     }
@@ -6,14 +6,13 @@ use std::array::from_fn as create_array;
 use crate::mpt_sequential::MAX_LEAF_VALUE_LEN;
 use crate::rlp::short_string_len;
 use crate::storage::key::{MappingSlotWires, MAPPING_INPUT_TOTAL_LEN};
-use crate::storage::MAX_LEAF_NODE_LEN;
 use crate::types::{MAPPING_KEY_LEN, MAPPING_LEAF_VALUE_LEN};
 use crate::utils::convert_u8_targets_to_u32;
 use crate::{
     array::{Array, Vector, VectorWire},
     group_hashing::CircuitBuilderGroupHashing,
     keccak::{InputData, KeccakCircuit, KeccakWires},
-    mpt_sequential::{Circuit as MPTCircuit, PAD_LEN},
+    mpt_sequential::{Circuit as MPTCircuit, MPTKeyWire, PAD_LEN},
     rlp::decode_fixed_list,
 };
 use mrp2_utils::utils::{less_than, less_than_or_equal_to};
@@ -56,8 +55,9 @@ where
         self.mapping_slot.mapping_key.clone()
     }
 
+    /// Storage slot of the mapping, including the base slot offset
     pub fn mapping_slot(&self) -> Target {
-        self.mapping_slot.mapping_slot
+        self.mapping_slot.slot
     }
 }
 
@@ -66,8 +66,6 @@ where
     [(); PAD_LEN(NODE_LEN)]:,
 {
     pub fn build(b: &mut CircuitBuilder<GoldilocksField, 2>) -> LeafWires<NODE_LEN> {
-        let zero = b.zero();
-        let tru = b._true();
         let node = VectorWire::<Target, { PAD_LEN(NODE_LEN) }>::new(b);
         // always ensure theThanks all node is bytes at the beginning
         node.assert_bytes(b);
@@ -78,59 +76,22 @@ where
         // Then derives the correct MPT key from this (mappingkey,mappingslot) pair
         let mapping_slot_wires = MappingSlot::mpt_key(b);
 
-        // Then advance the key and extract the value
-        // only decode two headers in the case of leaf
-        let rlp_headers = decode_fixed_list::<_, _, 2>(b, &node.arr.arr, zero);
-        let (new_key, encoded_value, is_valid) =
-            MPTCircuit::<1, NODE_LEN>::advance_key_leaf_or_extension::<_, _, _, MAX_LEAF_VALUE_LEN>(
-                b,
-                &node.arr,
-                &mapping_slot_wires.keccak_mpt.mpt_key,
-                &rlp_headers,
-            );
-        b.connect(tru.target, is_valid.target);
-        // Read the length of the relevant data (RLP header - 0x80)
-        let one = b.one();
-
-        let prefix = encoded_value[0];
-        let byte_80 = b.constant(GoldilocksField::from_canonical_usize(128));
-        let is_single_byte = less_than(b, prefix, byte_80, 8);
-        let value_len_80 = b.sub(encoded_value[0], byte_80);
-        let value_len = b.select(is_single_byte, one, value_len_80);
-        let offset = b.select(is_single_byte, zero, one);
-        let big_endian_left_padded = encoded_value
-            .extract_array::<GoldilocksField, _, MAPPING_LEAF_VALUE_LEN>(b, offset)
-            .into_vec(value_len)
-            .normalize_left::<_, _, MAPPING_LEAF_VALUE_LEN>(b);
-
-        // Then creates the initial accumulator from the (mapping_key, value)
-        let mut inputs = [b.zero(); MAPPING_INPUT_TOTAL_LEN];
-        inputs[0..MAPPING_KEY_LEN].copy_from_slice(&mapping_slot_wires.mapping_key.arr);
-        inputs[MAPPING_KEY_LEN..MAPPING_KEY_LEN + MAPPING_LEAF_VALUE_LEN]
-            .copy_from_slice(&big_endian_left_padded.arr);
-        // couldn't make it work with array API because of const generic issue...
-        //let packed = Array { arr: inputs }.convert_u8_to_u32(b);
-        let packed = convert_u8_targets_to_u32(b, &inputs)
-            .into_iter()
-            .map(|x| x.0)
-            .collect::<Vec<_>>();
-        let leaf_accumulator = b.map_to_curve_point(&packed);
-
-        // and register the public inputs
-        let n = b.one(); // only one leaf seen in that leaf !
-        PublicInputs::register(
+        let value = build_leaf_value::<NODE_LEN>(
             b,
-            &new_key,
-            mapping_slot_wires.mapping_slot,
-            n,
-            &root.output_array,
-            &leaf_accumulator,
+            &node,
+            &root,
+            &mapping_slot_wires.keccak_mpt.mpt_key,
+            &mapping_slot_wires.mapping_key,
+            // expose the actual storage slot, so that the base slot offset is bound
+            // to the proof
+            mapping_slot_wires.slot,
+            None,
         );
         LeafWires {
             node,
             root,
             mapping_slot: mapping_slot_wires,
-            value: big_endian_left_padded,
+            value,
         }
     }
 
@@ -147,13 +108,106 @@ where
     }
 }
 
-pub(super) type StorageLeafWire = LeafWires<MAX_LEAF_NODE_LEN>;
+/// Advances the MPT key over the leaf `node`, extracts the value stored in it and
+/// registers the public inputs, with the initial accumulator computed from the
+/// (`key`, value) pair, prefixed by the domain-separation `tag`, if any. It returns
+/// the extracted value, left padded to 32 bytes.
+pub(super) fn build_leaf_value<const NODE_LEN: usize>(
+    b: &mut CircuitBuilder<GoldilocksField, 2>,
+    node: &VectorWire<Target, { PAD_LEN(NODE_LEN) }>,
+    root: &KeccakWires<{ PAD_LEN(NODE_LEN) }>,
+    mpt_key: &MPTKeyWire,
+    key: &Array<Target, MAPPING_KEY_LEN>,
+    slot: Target,
+    tag: Option<Target>,
+) -> Array<Target, MAPPING_LEAF_VALUE_LEN>
+where
+    [(); PAD_LEN(NODE_LEN)]:,
+{
+    let (new_key, big_endian_left_padded) = extract_leaf_value::<NODE_LEN>(b, node, mpt_key);
+
+    // Then creates the initial accumulator from the (key, value)
+    let mut inputs = [b.zero(); MAPPING_INPUT_TOTAL_LEN];
+    inputs[0..MAPPING_KEY_LEN].copy_from_slice(&key.arr);
+    inputs[MAPPING_KEY_LEN..MAPPING_KEY_LEN + MAPPING_LEAF_VALUE_LEN]
+        .copy_from_slice(&big_endian_left_padded.arr);
+    // couldn't make it work with array API because of const generic issue...
+    //let packed = Array { arr: inputs }.convert_u8_to_u32(b);
+    let packed = tag
+        .into_iter()
+        .chain(
+            convert_u8_targets_to_u32(b, &inputs)
+                .into_iter()
+                .map(|x| x.0),
+        )
+        .collect::<Vec<_>>();
+    let leaf_accumulator = b.map_to_curve_point(&packed);
+
+    // and register the public inputs
+    let n = b.one(); // only one leaf seen in that leaf !
+    let is_absent = b._false(); // the leaf proves the key is present
+    PublicInputs::register(
+        b,
+        &new_key,
+        slot,
+        n,
+        &root.output_array,
+        &leaf_accumulator,
+        is_absent,
+    );
+
+    big_endian_left_padded
+}
+
+/// Advances the MPT key over the leaf `node` and extracts the value stored in it,
+/// left padded to 32 bytes. It returns the advanced key along with the value.
+pub(super) fn extract_leaf_value<const NODE_LEN: usize>(
+    b: &mut CircuitBuilder<GoldilocksField, 2>,
+    node: &VectorWire<Target, { PAD_LEN(NODE_LEN) }>,
+    mpt_key: &MPTKeyWire,
+) -> (MPTKeyWire, Array<Target, MAPPING_LEAF_VALUE_LEN>)
+where
+    [(); PAD_LEN(NODE_LEN)]:,
+{
+    let zero = b.zero();
+    let tru = b._true();
+    // Then advance the key and extract the value
+    // only decode two headers in the case of leaf
+    let rlp_headers = decode_fixed_list::<_, _, 2>(b, &node.arr.arr, zero);
+    let (new_key, encoded_value, is_valid) =
+        MPTCircuit::<1, NODE_LEN>::advance_key_leaf_or_extension::<_, _, _, MAX_LEAF_VALUE_LEN>(
+            b,
+            &node.arr,
+            mpt_key,
+            &rlp_headers,
+        );
+    b.connect(tru.target, is_valid.target);
+    // Read the length of the relevant data (RLP header - 0x80)
+    let one = b.one();
+
+    let prefix = encoded_value[0];
+    let byte_80 = b.constant(GoldilocksField::from_canonical_usize(128));
+    let is_single_byte = less_than(b, prefix, byte_80, 8);
+    let value_len_80 = b.sub(encoded_value[0], byte_80);
+    let value_len = b.select(is_single_byte, one, value_len_80);
+    let offset = b.select(is_single_byte, zero, one);
+    let big_endian_left_padded = encoded_value
+        .extract_array::<GoldilocksField, _, MAPPING_LEAF_VALUE_LEN>(b, offset)
+        .into_vec(value_len)
+        .normalize_left::<_, _, MAPPING_LEAF_VALUE_LEN>(b);
+
+    (new_key, big_endian_left_padded)
+}
+
 /// D = 2,
 /// Num of children = 0
-impl CircuitLogicWires<GoldilocksField, 2, 0> for StorageLeafWire {
+impl<const NODE_LEN: usize> CircuitLogicWires<GoldilocksField, 2, 0> for LeafWires<NODE_LEN>
+where
+    [(); PAD_LEN(NODE_LEN)]:,
+{
     type CircuitBuilderParams = ();
 
-    type Inputs = LeafCircuit<MAX_LEAF_NODE_LEN>;
+    type Inputs = LeafCircuit<NODE_LEN>;
 
     const NUM_PUBLIC_INPUTS: usize = PublicInputs::<GoldilocksField>::TOTAL_LEN;
 
@@ -270,7 +324,11 @@ mod test {
         let circuit = TestLeafCircuit {
             c: LeafCircuit::<80> {
                 node: leaf_node.to_vec(),
-                slot: MappingSlot::new(mapping_slot as u8, user_address.to_fixed_bytes().to_vec()),
+                slot: MappingSlot::new(
+                    mapping_slot as u8,
+                    user_address.to_fixed_bytes().to_vec(),
+                    0,
+                ),
             },
             exp_value: value_buff.to_vec(),
         };
@@ -293,7 +351,7 @@ mod test {
         let proof = trie.get_proof(&slot.mpt_key_vec()).unwrap();
         let node = proof.last().unwrap().clone(); // proof from RPC gives leaf as last
         let mpt_key = slot.mpt_key_vec();
-        let slot = MappingSlot::new(mapping_slot as u8, mapping_key.clone());
+        let slot = MappingSlot::new(mapping_slot as u8, mapping_key.clone(), 0);
         let circuit = LeafCircuit::<80> {
             node: node.clone(),
             slot,
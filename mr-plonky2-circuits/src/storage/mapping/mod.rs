@@ -1,9 +1,14 @@
 pub mod api;
+mod array_leaf;
 mod branch;
 mod extension;
 pub(crate) mod leaf;
+mod multi_slot_leaf;
+mod non_membership;
 mod public_inputs;
 
 pub use api::{build_circuits_params, generate_proof, CircuitInput, PublicParameters};
 pub(crate) use extension::{ExtensionNodeCircuit, ExtensionWires};
+pub use multi_slot_leaf::{MultiSlotLeafCircuit, MultiSlotLeafWires};
+pub use non_membership::{NonMembershipCircuit, NonMembershipWires};
 pub use public_inputs::PublicInputs;
@@ -0,0 +1,346 @@
+//! Module handling the proving of a mapping entry whose value is a struct
+//! spanning several consecutive storage slots, each one stored in its own
+//! leaf of the storage trie.
+
+use crate::keccak::HASH_LEN;
+use crate::mpt_sequential::MPTKeyWire;
+use crate::rlp::MAX_KEY_NIBBLE_LEN;
+use crate::storage::key::{add_location_offset, location_mpt_key, MappingSlot, MappingSlotWires};
+use crate::types::{MAPPING_KEY_LEN, MAPPING_LEAF_VALUE_LEN};
+use crate::utils::convert_u8_targets_to_u32;
+use crate::{
+    array::{Vector, VectorWire},
+    group_hashing::CircuitBuilderGroupHashing,
+    keccak::{InputData, KeccakCircuit, KeccakWires, PACKED_HASH_LEN},
+    mpt_sequential::PAD_LEN,
+};
+use anyhow::{ensure, Result};
+use ethers::types::U256;
+use mrp2_utils::u256::{CircuitBuilderU256, UInt256Target, NUM_LIMBS};
+use plonky2::field::types::Field;
+use plonky2::{
+    field::goldilocks_field::GoldilocksField,
+    iop::{target::Target, witness::PartialWitness},
+    plonk::circuit_builder::CircuitBuilder,
+};
+use plonky2_ecgfp5::gadgets::curve::CircuitBuilderEcGFp5;
+use serde::{Deserialize, Serialize};
+
+use super::leaf::extract_leaf_value;
+
+/// Circuit proving the `K` consecutive storage slots of a struct stored in a
+/// mapping, i.e. the slots at `location + j` for `j` in `0..K` where `location`
+/// is derived from the (mapping key, mapping slot) pair. Each slot is stored
+/// in its own leaf of the storage trie.
+///
+/// The public inputs are:
+/// * the accumulator digest of (mapping_key, value_0, ..., value_{K-1})
+/// * for each slot, the MPT key advanced over the leaf and the hash of the leaf
+/// * the storage slot of the mapping
+/// * each value of the struct, as a `UInt256Target`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MultiSlotLeafCircuit<const NODE_LEN: usize, const K: usize> {
+    nodes: Vec<Vec<u8>>,
+    slot: MappingSlot,
+}
+
+/// Wires of the multi-slot leaf circuit
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MultiSlotLeafWires<const NODE_LEN: usize, const K: usize>
+where
+    [(); PAD_LEN(NODE_LEN)]:,
+{
+    nodes: Vec<VectorWire<Target, { PAD_LEN(NODE_LEN) }>>,
+    roots: Vec<KeccakWires<{ PAD_LEN(NODE_LEN) }>>,
+    mapping_slot: MappingSlotWires,
+    /// Keccak wires of the MPT keys of the slots following the first one
+    offset_mpt_keys: Vec<KeccakWires<{ PAD_LEN(HASH_LEN) }>>,
+    fields: Vec<UInt256Target>,
+}
+
+impl<const NODE_LEN: usize, const K: usize> MultiSlotLeafCircuit<NODE_LEN, K>
+where
+    [(); PAD_LEN(NODE_LEN)]:,
+{
+    /// Index of the accumulator digest in the public inputs
+    pub const D_IDX: usize = 0; // 5F for each coordinates + 1 bool flag
+    /// Index of the MPT key and leaf hash of the first slot in the public inputs
+    pub const LEAVES_IDX: usize = 11;
+    /// Length of the MPT key, pointer and leaf hash exposed for each slot
+    pub const LEAF_LEN: usize = MAX_KEY_NIBBLE_LEN + 1 + PACKED_HASH_LEN;
+    /// Index of the storage slot of the mapping in the public inputs
+    pub const S_IDX: usize = Self::LEAVES_IDX + K * Self::LEAF_LEN;
+    /// Index of the first value of the struct in the public inputs
+    pub const FIELDS_IDX: usize = Self::S_IDX + 1;
+    /// Total number of public inputs
+    pub const TOTAL_LEN: usize = Self::FIELDS_IDX + K * NUM_LIMBS;
+
+    /// Build a new circuit proving the `K` slots of the struct stored at `slot`,
+    /// where `nodes[j]` is the leaf node storing the slot `j` of the struct.
+    pub fn new(nodes: Vec<Vec<u8>>, slot: MappingSlot) -> Result<Self> {
+        ensure!(
+            nodes.len() == K,
+            "expected {} leaf nodes, got {}",
+            K,
+            nodes.len()
+        );
+        Ok(Self { nodes, slot })
+    }
+
+    /// Build the multi-slot leaf circuit
+    pub fn build(b: &mut CircuitBuilder<GoldilocksField, 2>) -> MultiSlotLeafWires<NODE_LEN, K> {
+        assert!(K > 0 && K <= u8::MAX as usize, "invalid number of slots");
+        let zero = b.zero();
+
+        // derive the location of the first slot from the (mapping key, mapping slot) pair
+        let mapping_slot = MappingSlot::mpt_key(b);
+        let location = mapping_slot.keccak_mpt.keccak_location.output.clone();
+        // the location is added to the offset of each slot, so it must be bytes
+        location.assert_bytes(b);
+
+        // derive the MPT keys of the following slots from location + j
+        let (offset_mpt_keys, next_mpt_keys): (Vec<_>, Vec<_>) = (1..K)
+            .map(|j| {
+                let mut offset = [zero; HASH_LEN];
+                offset[HASH_LEN - 1] = b.constant(GoldilocksField::from_canonical_usize(j));
+                let slot_location = add_location_offset(b, &location.arr, &offset);
+                location_mpt_key(b, &slot_location)
+            })
+            .unzip();
+        let mpt_keys = std::iter::once(mapping_slot.keccak_mpt.mpt_key.clone())
+            .chain(next_mpt_keys)
+            .collect::<Vec<MPTKeyWire>>();
+
+        // extract the value of each slot from its leaf
+        let mut nodes = Vec::with_capacity(K);
+        let mut roots = Vec::with_capacity(K);
+        let mut leaves = Vec::with_capacity(K);
+        let mut values = Vec::with_capacity(K);
+        for mpt_key in &mpt_keys {
+            let node = VectorWire::<Target, { PAD_LEN(NODE_LEN) }>::new(b);
+            node.assert_bytes(b);
+            let root = KeccakCircuit::<{ PAD_LEN(NODE_LEN) }>::hash_vector(b, &node);
+            let (new_key, value) = extract_leaf_value::<NODE_LEN>(b, &node, mpt_key);
+            nodes.push(node);
+            roots.push(root);
+            leaves.push(new_key);
+            values.push(value);
+        }
+
+        // hash the mapping key and all the values together in the accumulator
+        let inputs = mapping_slot
+            .mapping_key
+            .arr
+            .iter()
+            .chain(values.iter().flat_map(|v| v.arr.iter()))
+            .cloned()
+            .collect::<Vec<_>>();
+        assert_eq!(inputs.len(), MAPPING_KEY_LEN + K * MAPPING_LEAF_VALUE_LEN);
+        let packed = convert_u8_targets_to_u32(b, &inputs)
+            .into_iter()
+            .map(|x| x.0)
+            .collect::<Vec<_>>();
+        let accumulator = b.map_to_curve_point(&packed);
+
+        // values are big-endian, while u256 limbs are little-endian
+        let fields = values
+            .iter()
+            .map(|v| {
+                let packed_le = v.reverse().convert_u8_to_u32(b);
+                // unwrap is safe because we exactly give 32 bytes in packed format
+                UInt256Target::new_from_limbs(&packed_le.arr).unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        // and register the public inputs
+        b.register_curve_public_input(accumulator);
+        for (key, root) in leaves.iter().zip(&roots) {
+            key.register_as_input(b);
+            root.output_array.register_as_public_input(b);
+        }
+        b.register_public_input(mapping_slot.slot);
+        fields.iter().for_each(|f| b.register_public_input_u256(f));
+
+        MultiSlotLeafWires {
+            nodes,
+            roots,
+            mapping_slot,
+            offset_mpt_keys,
+            fields,
+        }
+    }
+
+    /// Assign the witness values of the multi-slot leaf circuit
+    pub fn assign(
+        &self,
+        pw: &mut PartialWitness<GoldilocksField>,
+        wires: &MultiSlotLeafWires<NODE_LEN, K>,
+    ) {
+        for ((node, node_wires), root_wires) in
+            self.nodes.iter().zip(&wires.nodes).zip(&wires.roots)
+        {
+            let pad_node =
+                Vector::<u8, { PAD_LEN(NODE_LEN) }>::from_vec(node).expect("invalid node given");
+            node_wires.assign(pw, &pad_node);
+            KeccakCircuit::<{ PAD_LEN(NODE_LEN) }>::assign(
+                pw,
+                root_wires,
+                &InputData::Assigned(&pad_node),
+            );
+        }
+        self.slot.assign(pw, &wires.mapping_slot);
+
+        let location = U256::from_big_endian(&self.slot.location());
+        for (j, keccak_wires) in wires.offset_mpt_keys.iter().enumerate() {
+            let mut slot_location = [0u8; HASH_LEN];
+            location
+                .overflowing_add(U256::from(j + 1))
+                .0
+                .to_big_endian(&mut slot_location);
+            KeccakCircuit::<{ PAD_LEN(HASH_LEN) }>::assign(
+                pw,
+                keccak_wires,
+                &InputData::Assigned(
+                    &Vector::from_vec(&slot_location)
+                        .expect("Can't create vector input for keccak_mpt"),
+                ),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use eth_trie::{Nibbles, Trie};
+    use ethers::types::H256;
+    use mrp2_test_utils::{
+        circuit::{run_circuit, UserCircuit},
+        mpt_sequential::generate_random_storage_mpt,
+        utils::random_vector,
+    };
+    use mrp2_utils::utils::{convert_u8_to_u32_slice, ToFields};
+    use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+
+    use super::*;
+    use crate::{
+        eth::{left_pad32, StorageSlot},
+        mpt_sequential::utils::bytes_to_nibbles,
+        storage::{lpn::leaf_digest, MAX_LEAF_NODE_LEN},
+        utils::keccak256,
+    };
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    const NUM_SLOTS: usize = 3;
+
+    impl<const NODE_LEN: usize, const K: usize> UserCircuit<F, D> for MultiSlotLeafCircuit<NODE_LEN, K>
+    where
+        [(); PAD_LEN(NODE_LEN)]:,
+    {
+        type Wires = MultiSlotLeafWires<NODE_LEN, K>;
+
+        fn build(b: &mut CircuitBuilder<F, D>) -> Self::Wires {
+            MultiSlotLeafCircuit::build(b)
+        }
+
+        fn prove(&self, pw: &mut PartialWitness<F>, wires: &Self::Wires) {
+            self.assign(pw, wires);
+        }
+    }
+
+    #[test]
+    fn test_multi_slot_leaf_circuit() {
+        let mapping_key = random_vector(20);
+        let mapping_slot = 5;
+        let location = StorageSlot::Mapping(mapping_key.clone(), mapping_slot).location();
+        let location = U256::from_big_endian(location.as_bytes());
+        // the MPT keys of the consecutive slots of the struct
+        let mpt_keys = (0..NUM_SLOTS)
+            .map(|j| keccak256(H256::from_uint(&(location + j)).as_bytes()))
+            .collect::<Vec<_>>();
+
+        let (mut trie, _) = generate_random_storage_mpt::<3, MAPPING_LEAF_VALUE_LEN>();
+        let values = (0..NUM_SLOTS)
+            .map(|_| random_vector(MAPPING_LEAF_VALUE_LEN))
+            .collect::<Vec<_>>();
+        for (key, value) in mpt_keys.iter().zip(&values) {
+            trie.insert(key, &rlp::encode(value)).unwrap();
+        }
+        trie.root_hash().unwrap();
+        let nodes = mpt_keys
+            .iter()
+            .map(|key| trie.get_proof(key).unwrap().last().unwrap().clone())
+            .collect::<Vec<_>>();
+
+        let slot = MappingSlot::new(mapping_slot as u8, mapping_key.clone(), 0);
+        let circuit =
+            MultiSlotLeafCircuit::<MAX_LEAF_NODE_LEN, NUM_SLOTS>::new(nodes.clone(), slot.clone())
+                .unwrap();
+        let proof = run_circuit::<F, D, C, _>(circuit);
+        let pi = &proof.public_inputs;
+        type Circuit = MultiSlotLeafCircuit<MAX_LEAF_NODE_LEN, NUM_SLOTS>;
+        assert_eq!(pi.len(), Circuit::TOTAL_LEN);
+        {
+            // expected accumulator Acc((mapping_key, value_0, ..., value_{K-1}))
+            let inputs = left_pad32(&mapping_key)
+                .into_iter()
+                .chain(values.iter().flatten().cloned())
+                .collect::<Vec<_>>();
+            let exp_digest = leaf_digest(&inputs).to_weierstrass();
+            let exp_digest = exp_digest
+                .x
+                .0
+                .iter()
+                .chain(&exp_digest.y.0)
+                .cloned()
+                .chain(std::iter::once(F::from_bool(exp_digest.is_inf)))
+                .collect::<Vec<_>>();
+            assert_eq!(pi[Circuit::D_IDX..Circuit::LEAVES_IDX], exp_digest);
+        }
+        for (j, node) in nodes.iter().enumerate() {
+            // expected MPT key, pointer and hash of each leaf
+            let leaf = &pi[Circuit::LEAVES_IDX + j * Circuit::LEAF_LEN..];
+            let exp_key = bytes_to_nibbles(&mpt_keys[j])
+                .into_iter()
+                .map(F::from_canonical_u8)
+                .collect::<Vec<_>>();
+            assert_eq!(leaf[..MAX_KEY_NIBBLE_LEN], exp_key);
+            let leaf_key: Vec<Vec<u8>> = rlp::decode_list(node);
+            let nib = Nibbles::from_compact(&leaf_key[0]);
+            let exp_ptr = F::from_canonical_usize(MAX_KEY_NIBBLE_LEN - 1 - nib.nibbles().len());
+            assert_eq!(leaf[MAX_KEY_NIBBLE_LEN], exp_ptr);
+            let exp_hash = convert_u8_to_u32_slice(&keccak256(node))
+                .into_iter()
+                .map(F::from_canonical_u32)
+                .collect::<Vec<_>>();
+            assert_eq!(leaf[MAX_KEY_NIBBLE_LEN + 1..Circuit::LEAF_LEN], exp_hash);
+        }
+        assert_eq!(pi[Circuit::S_IDX], F::from_canonical_usize(mapping_slot));
+        for (j, value) in values.iter().enumerate() {
+            // expected field of the struct
+            let field_idx = Circuit::FIELDS_IDX + j * NUM_LIMBS;
+            let exp_field = U256::from_big_endian(value).to_fields::<F>();
+            assert_eq!(pi[field_idx..field_idx + NUM_LIMBS], exp_field);
+        }
+
+        // the leaves must be given in the order of the slots
+        let mut swapped_nodes = nodes.clone();
+        swapped_nodes.swap(0, 1);
+        let res = std::panic::catch_unwind(|| {
+            run_circuit::<F, D, C, _>(
+                MultiSlotLeafCircuit::<MAX_LEAF_NODE_LEN, NUM_SLOTS>::new(swapped_nodes, slot)
+                    .unwrap(),
+            )
+        });
+        assert!(res.is_err(), "slots given out of order not detected");
+
+        // the number of leaves must match the number of slots
+        assert!(MultiSlotLeafCircuit::<MAX_LEAF_NODE_LEN, NUM_SLOTS>::new(
+            nodes[..NUM_SLOTS - 1].to_vec(),
+            MappingSlot::new(mapping_slot as u8, mapping_key, 0)
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,388 @@
+//! Module proving that a mapping entry is absent from a storage trie, i.e.
+//! that the MPT path of its key ends before reaching a leaf for that key.
+
+use crate::rlp::{decode_compact_encoding, RlpHeader, MAX_ITEMS_IN_LIST};
+use crate::storage::key::{MappingSlot, MappingSlotWires};
+use crate::{
+    array::{Vector, VectorWire},
+    keccak::{InputData, KeccakCircuit, KeccakWires},
+    mpt_sequential::{MPTKeyWire, PAD_LEN},
+    rlp::decode_fixed_list,
+};
+use mrp2_utils::utils::less_than;
+use plonky2::field::types::Field;
+use plonky2::{
+    field::goldilocks_field::GoldilocksField,
+    iop::{
+        target::Target,
+        witness::{PartialWitness, WitnessWrite},
+    },
+    plonk::circuit_builder::CircuitBuilder,
+};
+use plonky2_ecgfp5::gadgets::curve::CircuitBuilderEcGFp5;
+use serde::{Deserialize, Serialize};
+
+use crate::storage::mapping::public_inputs::PublicInputs;
+
+/// Circuit proving that the key derived from a (mapping key, mapping slot)
+/// pair is not in the MPT. The node given is the last one found along the
+/// path of the key, and the key is proven absent if either:
+/// * the node is a leaf or an extension whose encoded path differs from the
+///   nibbles of the key covered by the node
+/// * the node is a branch whose child at the nibble of the key is empty
+///
+/// The public inputs are the same as the ones of the mapping leaf circuit,
+/// with no leaf seen, an empty accumulator and the absence flag set. The flag
+/// is propagated by the extension and branch circuits, which allows to tell
+/// non-membership proofs apart from membership ones up to the MPT root.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NonMembershipCircuit<const NODE_LEN: usize> {
+    node: Vec<u8>,
+    slot: MappingSlot,
+    pointer: usize,
+}
+
+/// Wires of the non-membership circuit
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct NonMembershipWires<const NODE_LEN: usize>
+where
+    [(); PAD_LEN(NODE_LEN)]:,
+{
+    node: VectorWire<Target, { PAD_LEN(NODE_LEN) }>,
+    root: KeccakWires<{ PAD_LEN(NODE_LEN) }>,
+    mapping_slot: MappingSlotWires,
+    pointer: Target,
+}
+
+impl<const NODE_LEN: usize> NonMembershipCircuit<NODE_LEN>
+where
+    [(); PAD_LEN(NODE_LEN)]:,
+{
+    /// Build a new circuit proving the absence of the key of `slot`, where
+    /// `node` is the last node along the path of the key in the MPT, and
+    /// `pointer` is the index of the last nibble of the key covered by `node`,
+    /// i.e. its depth for a branch node, or its depth plus the length of its
+    /// path minus one for a leaf or extension node.
+    pub fn new(node: Vec<u8>, slot: MappingSlot, pointer: usize) -> Self {
+        Self {
+            node,
+            slot,
+            pointer,
+        }
+    }
+
+    /// Build the non-membership circuit
+    pub fn build(b: &mut CircuitBuilder<GoldilocksField, 2>) -> NonMembershipWires<NODE_LEN> {
+        let zero = b.zero();
+        let one = b.one();
+        let tru = b._true();
+        let node = VectorWire::<Target, { PAD_LEN(NODE_LEN) }>::new(b);
+        node.assert_bytes(b);
+
+        // First expose the keccak root of this subtree starting at this node
+        let root = KeccakCircuit::<{ PAD_LEN(NODE_LEN) }>::hash_vector(b, &node);
+
+        // Then derive the MPT key from the (mapping key, mapping slot) pair,
+        // pointing to the last nibble covered by the node
+        let mapping_slot = MappingSlot::mpt_key(b);
+        let pointer = b.add_virtual_target();
+        let key = MPTKeyWire {
+            key: mapping_slot.keccak_mpt.mpt_key.key.clone(),
+            pointer,
+        };
+
+        // decode as many headers as in a branch node, since we don't know
+        // the type of the node in advance
+        let rlp_headers = decode_fixed_list::<_, _, MAX_ITEMS_IN_LIST>(b, &node.arr.arr, zero);
+
+        // Leaf or extension node: the key is absent if the path of the node
+        // differs from key[pointer + 1 - path_len..=pointer]
+        let two = b.two();
+        let is_tuple = b.is_equal(rlp_headers.num_fields, two);
+        let path_header = RlpHeader {
+            data_type: rlp_headers.data_type[0],
+            offset: rlp_headers.offset[0],
+            len: rlp_headers.len[0],
+        };
+        let (path, is_valid_path) = decode_compact_encoding(b, &node.arr, &path_header);
+        let is_tuple = b.and(is_tuple, is_valid_path);
+        // the decoded path is meaningless for a branch node, so its length is
+        // zeroed to keep the comparisons below in range
+        let path_len = b.select(is_tuple, path.real_len, zero);
+        let pointer_plus_one = b.add(pointer, one);
+        let path_start = b.sub(pointer_plus_one, path_len);
+        let mut is_same_path = b._true();
+        for (i, path_nibble) in path.arr.arr.iter().enumerate() {
+            let it = b.constant(GoldilocksField::from_canonical_usize(i));
+            let is_in_path = less_than(b, it, path_len, 7);
+            let key_idx = b.add(path_start, it);
+            // indexes outside of the path may be out of the key range, so we
+            // can't use random access here
+            let key_nibble = key.key.value_at_failover(b, key_idx);
+            let is_same_nibble = b.is_equal(*path_nibble, key_nibble);
+            let is_out_of_path = b.not(is_in_path);
+            let is_nibble_ok = b.or(is_same_nibble, is_out_of_path);
+            is_same_path = b.and(is_same_path, is_nibble_ok);
+        }
+        let is_different_path = b.not(is_same_path);
+        let is_absent_from_tuple = b.and(is_tuple, is_different_path);
+
+        // Branch node: the key is absent if the child at the nibble of the key
+        // is empty, i.e. encoded as an empty string
+        let seventeen = b.constant(GoldilocksField::from_canonical_usize(MAX_ITEMS_IN_LIST));
+        let is_branch = b.is_equal(rlp_headers.num_fields, seventeen);
+        let nibble = key.current_nibble(b);
+        let child_header = rlp_headers.select(b, nibble);
+        let is_empty_child = b.is_equal(child_header.len, zero);
+        let is_absent_from_branch = b.and(is_branch, is_empty_child);
+
+        let is_absent = b.or(is_absent_from_tuple, is_absent_from_branch);
+        b.connect(is_absent.target, tru.target);
+
+        // the node covers either its whole path or a single nibble
+        let covered_len = b.select(is_tuple, path_len, one);
+        let new_key = key.advance_by(b, covered_len);
+
+        // and register the public inputs
+        let n = zero; // no leaf seen, since the key is absent
+        let accumulator = b.curve_zero();
+        PublicInputs::register(
+            b,
+            &new_key,
+            mapping_slot.slot,
+            n,
+            &root.output_array,
+            &accumulator,
+            is_absent,
+        );
+
+        NonMembershipWires {
+            node,
+            root,
+            mapping_slot,
+            pointer,
+        }
+    }
+
+    /// Assign the witness values of the non-membership circuit
+    pub fn assign(
+        &self,
+        pw: &mut PartialWitness<GoldilocksField>,
+        wires: &NonMembershipWires<NODE_LEN>,
+    ) {
+        let pad_node =
+            Vector::<u8, { PAD_LEN(NODE_LEN) }>::from_vec(&self.node).expect("invalid node given");
+        wires.node.assign(pw, &pad_node);
+        KeccakCircuit::<{ PAD_LEN(NODE_LEN) }>::assign(
+            pw,
+            &wires.root,
+            &InputData::Assigned(&pad_node),
+        );
+        self.slot.assign(pw, &wires.mapping_slot);
+        pw.set_target(
+            wires.pointer,
+            GoldilocksField::from_canonical_usize(self.pointer),
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use eth_trie::{Nibbles, Trie};
+    use mrp2_test_utils::{
+        circuit::{run_circuit, UserCircuit},
+        mpt_sequential::generate_random_storage_mpt,
+        utils::random_vector,
+    };
+    use plonky2::field::types::Field;
+    use plonky2::iop::target::Target;
+    use plonky2::iop::witness::{PartialWitness, WitnessWrite};
+    use plonky2::plonk::circuit_builder::CircuitBuilder;
+    use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
+    use recursion_framework::circuit_builder::CircuitLogicWires;
+
+    use super::super::branch::{BranchCircuit, BranchWires};
+    use super::{NonMembershipCircuit, NonMembershipWires, PublicInputs};
+    use crate::eth::StorageSlot;
+    use crate::mpt_sequential::PAD_LEN;
+    use crate::rlp::{MAX_ITEMS_IN_LIST, MAX_KEY_NIBBLE_LEN};
+    use crate::storage::key::MappingSlot;
+    use crate::storage::MAX_BRANCH_NODE_LEN;
+    use crate::types::MAPPING_LEAF_VALUE_LEN;
+    use crate::utils::{convert_u8_to_u32_slice, keccak256};
+
+    const D: usize = 2;
+    type C = PoseidonGoldilocksConfig;
+    type F = <C as GenericConfig<D>>::F;
+
+    /// Returns the depth of the last node of the MPT proof, together with the
+    /// pointer to the last nibble of the key covered by this node, as expected
+    /// by `NonMembershipCircuit`
+    fn last_node_position(proof: &[Vec<u8>]) -> (usize, usize) {
+        let covered_len = |node: &[u8]| {
+            let items: Vec<Vec<u8>> = rlp::decode_list(node);
+            match items.len() {
+                MAX_ITEMS_IN_LIST => 1,
+                2 => Nibbles::from_compact(&items[0]).nibbles().len(),
+                _ => panic!("invalid MPT node"),
+            }
+        };
+        let (last, path) = proof.split_last().expect("empty MPT proof");
+        let depth: usize = path.iter().map(|node| covered_len(node)).sum();
+        let pointer = depth + covered_len(last) - 1;
+        assert!(pointer < MAX_KEY_NIBBLE_LEN);
+        (depth, pointer)
+    }
+
+    impl<const NODE_LEN: usize> UserCircuit<F, D> for NonMembershipCircuit<NODE_LEN>
+    where
+        [(); PAD_LEN(NODE_LEN)]:,
+    {
+        type Wires = NonMembershipWires<NODE_LEN>;
+        fn build(b: &mut CircuitBuilder<F, D>) -> Self::Wires {
+            NonMembershipCircuit::build(b)
+        }
+        fn prove(&self, pw: &mut PartialWitness<F>, wires: &Self::Wires) {
+            self.assign(pw, wires);
+        }
+    }
+
+    /// Branch circuit over a single child proof, whose public inputs are
+    /// provided as witness
+    #[derive(Clone, Debug)]
+    struct TestBranchCircuit {
+        c: BranchCircuit<MAX_BRANCH_NODE_LEN, 1>,
+        child_pi: Vec<F>,
+    }
+
+    impl UserCircuit<F, D> for TestBranchCircuit {
+        type Wires = (BranchWires<MAX_BRANCH_NODE_LEN>, Vec<Target>);
+        fn build(b: &mut CircuitBuilder<F, D>) -> Self::Wires {
+            let child_pi = b.add_virtual_targets(PublicInputs::<Target>::TOTAL_LEN);
+            let wires =
+                BranchCircuit::<MAX_BRANCH_NODE_LEN, 1>::build(b, &[PublicInputs::from(&child_pi)]);
+            (wires, child_pi)
+        }
+        fn prove(&self, pw: &mut PartialWitness<F>, wires: &Self::Wires) {
+            pw.set_target_arr(&wires.1, &self.child_pi);
+            <BranchWires<MAX_BRANCH_NODE_LEN> as CircuitLogicWires<F, D, 1>>::assign_input(
+                &wires.0,
+                self.c.clone(),
+                pw,
+            )
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_non_membership_circuit() {
+        let mapping_slot = 2;
+        let (mut trie, _) = generate_random_storage_mpt::<3, MAPPING_LEAF_VALUE_LEN>();
+        // insert a mapping entry, to check that a present key can't be proven absent
+        let present_key = random_vector(20);
+        let present_slot = StorageSlot::Mapping(present_key.clone(), mapping_slot);
+        let encoded_value: Vec<u8> = rlp::encode(&random_vector(MAPPING_LEAF_VALUE_LEN)).to_vec();
+        trie.insert(&present_slot.mpt_key(), &encoded_value)
+            .unwrap();
+        trie.root_hash().unwrap();
+
+        // the MPT path of an absent key ends either in a leaf or extension
+        // for a different key, or in a branch with an empty child
+        let absent_key = random_vector(20);
+        let absent_slot = StorageSlot::Mapping(absent_key.clone(), mapping_slot);
+        let proof = trie.get_proof(&absent_slot.mpt_key_vec()).unwrap();
+        let node = proof.last().unwrap().clone();
+        let (depth, pointer) = last_node_position(&proof);
+        let circuit = NonMembershipCircuit::<MAX_BRANCH_NODE_LEN>::new(
+            node.clone(),
+            MappingSlot::new(mapping_slot as u8, absent_key, 0),
+            pointer,
+        );
+        let proof_with_pi = run_circuit::<F, D, C, _>(circuit);
+        let pi = PublicInputs::<F>::from(&proof_with_pi.public_inputs);
+        {
+            // the key is absent
+            assert_eq!(pi.is_absent(), F::ONE);
+        }
+        {
+            // expected MPT hash
+            let exp_packed = convert_u8_to_u32_slice(&keccak256(&node));
+            assert_eq!(exp_packed, pi.root_hash());
+        }
+        {
+            // the pointer moves to the nibble of the parent node, i.e. -1 at the root
+            let (_, ptr) = pi.mpt_key_info();
+            assert_eq!(ptr, F::from_canonical_usize(depth) - F::ONE);
+        }
+        {
+            // no leaf seen, for the expected mapping slot
+            assert_eq!(pi.n(), F::ZERO);
+            assert_eq!(pi.mapping_slot(), F::from_canonical_usize(mapping_slot));
+        }
+
+        // a present key makes the circuit fail
+        let proof = trie.get_proof(&present_slot.mpt_key_vec()).unwrap();
+        let circuit = NonMembershipCircuit::<MAX_BRANCH_NODE_LEN>::new(
+            proof.last().unwrap().clone(),
+            MappingSlot::new(mapping_slot as u8, present_key, 0),
+            last_node_position(&proof).1,
+        );
+        let res = std::panic::catch_unwind(|| run_circuit::<F, D, C, _>(circuit));
+        assert!(res.is_err(), "present key proven absent");
+    }
+
+    #[test]
+    fn test_non_membership_through_branch() {
+        let mapping_slot = 3;
+        let (mut trie, _) = generate_random_storage_mpt::<3, MAPPING_LEAF_VALUE_LEN>();
+        let present_key = random_vector(20);
+        let present_slot = StorageSlot::Mapping(present_key, mapping_slot);
+        let encoded_value: Vec<u8> = rlp::encode(&random_vector(MAPPING_LEAF_VALUE_LEN)).to_vec();
+        trie.insert(&present_slot.mpt_key(), &encoded_value)
+            .unwrap();
+        trie.root_hash().unwrap();
+
+        // look for an absent key whose MPT path ends below a branch node
+        let (absent_key, proof) = loop {
+            let absent_key = random_vector(20);
+            let absent_slot = StorageSlot::Mapping(absent_key.clone(), mapping_slot);
+            let proof = trie.get_proof(&absent_slot.mpt_key_vec()).unwrap();
+            if proof.len() >= 2
+                && rlp::decode_list::<Vec<u8>>(&proof[proof.len() - 2]).len() == MAX_ITEMS_IN_LIST
+            {
+                break (absent_key, proof);
+            }
+        };
+        let (depth, pointer) = last_node_position(&proof);
+        let circuit = NonMembershipCircuit::<MAX_BRANCH_NODE_LEN>::new(
+            proof.last().unwrap().clone(),
+            MappingSlot::new(mapping_slot as u8, absent_key.clone(), 0),
+            pointer,
+        );
+        let child_proof = run_circuit::<F, D, C, _>(circuit);
+        let child_pi = PublicInputs::<F>::from(&child_proof.public_inputs);
+        let (child_key, child_ptr) = child_pi.mpt_key_info();
+        assert_eq!(child_ptr, F::from_canonical_usize(depth) - F::ONE);
+
+        // the parent branch propagates the absence flag
+        let node = proof[proof.len() - 2].clone();
+        let branch = BranchCircuit::<MAX_BRANCH_NODE_LEN, 1> {
+            node: node.clone(),
+            common_prefix: child_key.iter().map(|nibble| nibble.0 as u8).collect(),
+            expected_pointer: depth - 1,
+            mapping_slot,
+            nb_proofs: 1,
+        };
+        let branch_proof = run_circuit::<F, D, C, _>(TestBranchCircuit {
+            c: branch,
+            child_pi: child_proof.public_inputs.clone(),
+        });
+        let pi = PublicInputs::<F>::from(&branch_proof.public_inputs);
+        assert_eq!(pi.is_absent(), F::ONE);
+        assert_eq!(pi.n(), F::ZERO);
+        assert_eq!(pi.mapping_slot(), F::from_canonical_usize(mapping_slot));
+        assert_eq!(pi.root_hash(), convert_u8_to_u32_slice(&keccak256(&node)));
+        let (_, ptr) = pi.mpt_key_info();
+        assert_eq!(ptr, F::from_canonical_usize(depth) - F::TWO);
+    }
+}
@@ -1,12 +1,13 @@
 use std::array::from_fn as create_array;
 
+use anyhow::{ensure, Result};
 use plonky2::{
     field::{
         extension::{quintic::QuinticExtension, FieldExtension},
         goldilocks_field::GoldilocksField,
         types::Field,
     },
-    iop::target::Target,
+    iop::target::{BoolTarget, Target},
     plonk::circuit_builder::CircuitBuilder,
 };
 use plonky2_crypto::u32::arithmetic_u32::U32Target;
@@ -16,6 +17,7 @@ use plonky2_ecgfp5::{
 };
 
 use crate::{
+    api::ProofWithVK,
     array::Array,
     keccak::{OutputHash, PACKED_HASH_LEN},
     mpt_sequential::MPTKeyWire,
@@ -31,6 +33,7 @@ use crate::{
 // `n` number of items seen so far up to this node
 // `C` MPT root (of the current node)
 // `D` Accumulator digest of the values
+// `A` Flag, true if the subtree proves that a key is absent from the MPT
 #[derive(Clone, Debug)]
 pub struct PublicInputs<'a, T: Clone> {
     pub(crate) proof_inputs: &'a [T],
@@ -44,12 +47,14 @@ impl<'a> PublicInputs<'a, Target> {
         n: Target,
         c: &OutputHash,
         d: &CurveTarget,
+        a: BoolTarget,
     ) {
         b.register_curve_public_input(*d);
         key.register_as_input(b);
         b.register_public_input(slot);
         b.register_public_input(n);
         c.register_as_public_input(b);
+        b.register_public_input(a.target);
     }
     /// Returns the MPT key defined over the public inputs
     pub fn mpt_key(&self) -> MPTKeyWire {
@@ -72,6 +77,12 @@ impl<'a> PublicInputs<'a, Target> {
         let hash = self.root_hash_info();
         Array::<U32Target, PACKED_HASH_LEN>::from_array(create_array(|i| U32Target(hash[i])))
     }
+
+    /// Returns the flag telling whether the subtree proves that a key is absent
+    pub fn absence_flag(&self) -> BoolTarget {
+        // the flag is registered as a boolean by all the circuits of the module
+        BoolTarget::new_unsafe(self.is_absent())
+    }
 }
 impl<'a> PublicInputs<'a, GoldilocksField> {
     /// Returns the accumulator digest defined over the public inputs
@@ -91,6 +102,26 @@ impl<'a> PublicInputs<'a, GoldilocksField> {
         let hash = self.root_hash_info();
         hash.iter().map(|t| t.0 as u32).collect()
     }
+    /// Checks that the serialized storage proof has been generated for the
+    /// `expected` mapping slot, returning an error otherwise
+    pub fn assert_mapping_slot(serialized: &[u8], expected: usize) -> Result<()> {
+        let proof = ProofWithVK::deserialize(serialized)?;
+        let public_inputs = &proof.proof().public_inputs;
+        ensure!(
+            public_inputs.len() >= Self::TOTAL_LEN,
+            "storage proof has {} public inputs, expected at least {}",
+            public_inputs.len(),
+            Self::TOTAL_LEN
+        );
+        let found = PublicInputs::from(public_inputs).mapping_slot();
+        ensure!(
+            found == GoldilocksField::from_canonical_usize(expected),
+            "storage proof is for mapping slot {}, expected slot {}",
+            found,
+            expected
+        );
+        Ok(())
+    }
     #[cfg(test)]
     pub fn create_public_inputs_arr(
         key: &[u8],
@@ -120,6 +151,8 @@ impl<'a> PublicInputs<'a, GoldilocksField> {
                 .map(|x| GoldilocksField::from_canonical_u32(*x))
                 .collect::<Vec<_>>(),
         );
+        // a membership proof, as the test proofs are built from existing leaves
+        arr.push(GoldilocksField::ZERO);
         arr
     }
 }
@@ -130,8 +163,9 @@ impl<'a, T: Copy> PublicInputs<'a, T> {
     pub(crate) const S_IDX: usize = 76; // 1 index
     pub(crate) const N_IDX: usize = 77; // 1 index
     pub(crate) const C_IDX: usize = 78; // packed hash = 8 U32-F elements
+    pub(crate) const A_IDX: usize = Self::C_IDX + PACKED_HASH_LEN; // 1 bool flag
     pub(crate) const EXTENSION: usize = 5;
-    pub(crate) const TOTAL_LEN: usize = Self::C_IDX + 8;
+    pub(crate) const TOTAL_LEN: usize = Self::A_IDX + 1;
     pub fn from(arr: &'a [T]) -> Self {
         Self { proof_inputs: arr }
     }
@@ -162,6 +196,11 @@ impl<'a, T: Copy> PublicInputs<'a, T> {
     pub fn n(&self) -> T {
         self.proof_inputs[Self::N_IDX]
     }
+    /// Returns the flag which is true if the subtree proves that a key is
+    /// absent from the MPT, i.e. for a non-membership proof
+    pub fn is_absent(&self) -> T {
+        self.proof_inputs[Self::A_IDX]
+    }
 }
 
 #[cfg(test)]
@@ -173,7 +212,7 @@ mod test {
     use plonky2::field::types::Field;
     use plonky2::{
         iop::{
-            target::Target,
+            target::{BoolTarget, Target},
             witness::{PartialWitness, WitnessWrite},
         },
         plonk::{
@@ -186,13 +225,14 @@ mod test {
     use std::array::from_fn as create_array;
 
     use crate::{
+        api::tests::dummy_proof_with_vk,
         array::Array,
         group_hashing::{map_to_curve_point, CircuitBuilderGroupHashing},
         keccak::PACKED_HASH_LEN,
         mpt_sequential::MPTKeyWire,
     };
 
-    use super::PublicInputs;
+    use super::{super::api::NUM_IO, PublicInputs};
     const D: usize = 2;
     type C = PoseidonGoldilocksConfig;
     type F = <C as GenericConfig<D>>::F;
@@ -204,6 +244,7 @@ mod test {
         slot: usize,
         n: usize,
         c: Vec<u32>,
+        is_absent: bool,
     }
 
     impl UserCircuit<F, D> for TestPublicInputs {
@@ -213,6 +254,7 @@ mod test {
             Target,
             Array<U32Target, PACKED_HASH_LEN>,
             CurveTarget,
+            BoolTarget,
         );
         fn build(b: &mut CircuitBuilder<F, D>) -> Self::Wires {
             let key = MPTKeyWire::new(b);
@@ -221,8 +263,9 @@ mod test {
             let c = Array::<U32Target, PACKED_HASH_LEN>::new(b);
             let one = b.one();
             let accumulator = b.map_to_curve_point(&[one]);
-            PublicInputs::register(b, &key, slot, n, &c, &accumulator);
-            (key, slot, n, c, accumulator)
+            let is_absent = b.add_virtual_bool_target_safe();
+            PublicInputs::register(b, &key, slot, n, &c, &accumulator, is_absent);
+            (key, slot, n, c, accumulator, is_absent)
         }
 
         fn prove(&self, pw: &mut PartialWitness<F>, wires: &Self::Wires) {
@@ -235,6 +278,7 @@ mod test {
                 pw,
                 &create_array(|i| F::from_canonical_u64(self.c[i] as u64)),
             );
+            pw.set_bool_target(wires.5, self.is_absent);
         }
     }
     #[test]
@@ -251,6 +295,7 @@ mod test {
             slot,
             n,
             c: c.clone(),
+            is_absent: true,
         };
         let proof = run_circuit::<F, D, C, _>(test);
         let pi = PublicInputs::<F>::from(&proof.public_inputs);
@@ -281,5 +326,29 @@ mod test {
             let found_p = pi.accumulator();
             assert_eq!(found_p, p);
         }
+        {
+            assert_eq!(pi.is_absent(), F::ONE);
+            assert_eq!(proof.public_inputs.len(), PublicInputs::<F>::TOTAL_LEN);
+        }
+    }
+
+    #[test]
+    fn test_assert_mapping_slot() {
+        let slot = 3;
+        let p = map_to_curve_point(&[F::ONE]).to_weierstrass();
+        let arr = PublicInputs::<F>::create_public_inputs_arr(
+            &random_vector::<u8>(64),
+            2,
+            slot,
+            4,
+            &random_vector::<u32>(8),
+            &p,
+        );
+        let proof = dummy_proof_with_vk::<NUM_IO>(arr.try_into().unwrap())
+            .serialize()
+            .unwrap();
+
+        assert!(PublicInputs::<F>::assert_mapping_slot(&proof, slot).is_ok());
+        assert!(PublicInputs::<F>::assert_mapping_slot(&proof, slot + 1).is_err());
     }
 }
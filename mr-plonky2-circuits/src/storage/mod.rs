@@ -1,3 +1,4 @@
+pub mod account;
 pub mod digest_equal;
 pub mod key;
 pub mod length_extract;
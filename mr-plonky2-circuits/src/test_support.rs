@@ -0,0 +1,151 @@
+//! Utilities to generate fake proofs, allowing integrators to test the final
+//! steps of the query pipeline (revelation, wrapping, Groth16) without proving
+//! the whole storage, state and block trees.
+
+use anyhow::Result;
+use ethers::types::{Address, U256};
+use plonky2::{field::types::Field, hash::hash_types::HashOut};
+use recursion_framework::{
+    framework::RecursiveCircuits, framework_testing::TestingRecursiveCircuits,
+};
+
+use crate::{
+    api::{ProofWithVK, C, D, F},
+    query_erc20::block::{aggregation::AggregationKind, BlockPublicInputs, NUM_IO},
+    types::address_to_packed_fields,
+    utils::ToFields,
+};
+
+/// Values exposed by a fake proof of the ERC20 query block circuits
+#[derive(Clone, Debug)]
+pub struct FakeQueryBlockParams {
+    /// Last block number of the aggregated range, below `u32::MAX`
+    pub max_block_number: u32,
+    /// Number of blocks in the aggregated range
+    pub range: u32,
+    /// Root of the block tree, which must match the one of the block DB proof
+    pub root: HashOut<F>,
+    /// Address of the ERC20 contract
+    pub contract_address: Address,
+    /// Address of the user whose balance is queried
+    pub user_address: Address,
+    /// Slot of the balances mapping in the contract storage
+    pub mapping_slot: u32,
+    /// Slot of the length variable in the contract storage
+    pub length_slot: u32,
+    /// Result of the query over the aggregated range
+    pub query_results: U256,
+    /// Rewards rate of the query
+    pub rewards_rate: U256,
+    /// Upper bound on the rewards rate, the maximum U256 if unbounded
+    pub max_rewards_rate: U256,
+    /// Threshold or minimum balance of the storage leaves, zero for
+    /// proportional rewards
+    pub threshold: U256,
+    /// Mode of the storage leaves, see `leaf_mode_id`
+    pub leaf_mode: u8,
+    /// Maximum depth of the state tree openings of the aggregated blocks
+    pub state_depth: u32,
+}
+
+/// Generate a proof of a circuit in a fake query block circuit set exposing the
+/// public inputs of an ERC20 query block proof built from `params`. It returns the
+/// proof together with the circuit set, which is the one to be provided to the
+/// revelation circuit in place of the set of the query block circuits.
+pub fn fake_query_block_proof(
+    params: &FakeQueryBlockParams,
+) -> Result<(ProofWithVK, RecursiveCircuits<F, C, D>)> {
+    let circuits = TestingRecursiveCircuits::<F, C, D, NUM_IO>::default();
+    let pi = BlockPublicInputs::from_parts(
+        F::from_canonical_u32(params.max_block_number),
+        F::from_canonical_u32(params.range),
+        F::from_canonical_u32(params.range),
+        params.root,
+        &address_to_packed_fields(&params.contract_address),
+        &address_to_packed_fields(&params.user_address),
+        F::from_canonical_u32(params.mapping_slot),
+        F::from_canonical_u32(params.length_slot),
+        &params.query_results.to_fields().try_into().unwrap(),
+        &params.rewards_rate.to_fields().try_into().unwrap(),
+        &params.max_rewards_rate.to_fields().try_into().unwrap(),
+        F::from_canonical_u32(params.state_depth),
+        AggregationKind::Sum,
+        HashOut::ZERO,
+        F::ZERO,
+        HashOut::ZERO,
+        &params.threshold.to_fields().try_into().unwrap(),
+        F::from_canonical_u8(params.leaf_mode),
+    );
+    let [proof] = circuits.generate_input_proofs([pi])?;
+    let [vk] = circuits.verifier_data_for_input_proofs::<1>();
+
+    Ok((
+        ProofWithVK::from((proof, vk.clone())),
+        circuits.get_recursive_circuit_set().clone(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::default_config;
+    use plonky2::{
+        field::types::Sample, iop::witness::PartialWitness, plonk::circuit_builder::CircuitBuilder,
+    };
+    use rand::{thread_rng, Rng};
+    use recursion_framework::framework::RecursiveCircuitsVerifierGagdet;
+
+    #[test]
+    fn test_fake_query_block_proof() {
+        let rng = &mut thread_rng();
+        let params = FakeQueryBlockParams {
+            max_block_number: rng.gen_range(1000..10_000),
+            range: rng.gen_range(1..1000),
+            root: HashOut::rand(),
+            contract_address: Address::random(),
+            user_address: Address::random(),
+            mapping_slot: rng.gen::<u8>() as u32,
+            length_slot: rng.gen::<u8>() as u32,
+            query_results: U256::from(rng.gen::<u128>()),
+            rewards_rate: U256::from(rng.gen::<u64>()),
+            max_rewards_rate: U256::MAX,
+            threshold: U256::from(rng.gen::<u64>()),
+            leaf_mode: 1,
+            state_depth: rng.gen_range(1..=5),
+        };
+        let (proof, circuit_set) = fake_query_block_proof(&params).unwrap();
+
+        // the fake proof is recursively verified as a proof of the circuit set
+        let mut b = CircuitBuilder::new(default_config());
+        let verifier_gadget =
+            RecursiveCircuitsVerifierGagdet::<F, C, D, NUM_IO>::new(default_config(), &circuit_set);
+        let verifier_wires = verifier_gadget.verify_proof_in_circuit_set(&mut b);
+        b.register_public_inputs(verifier_wires.get_public_input_targets::<F, NUM_IO>());
+        let data = b.build::<C>();
+        let mut pw = PartialWitness::new();
+        verifier_wires
+            .set_target(&mut pw, &circuit_set, proof.proof(), proof.verifier_data())
+            .unwrap();
+        let verified = data.prove(pw).unwrap();
+        data.verify(verified.clone()).unwrap();
+
+        // the public inputs decode to the given parameters
+        let pi = BlockPublicInputs::from(&verified.public_inputs[..NUM_IO]);
+        assert_eq!(
+            pi.block_number(),
+            F::from_canonical_u32(params.max_block_number)
+        );
+        assert_eq!(pi.range(), F::from_canonical_u32(params.range));
+        assert_eq!(pi.root(), params.root);
+        assert_eq!(
+            pi.user_address(),
+            address_to_packed_fields(&params.user_address).as_slice()
+        );
+        assert_eq!(pi.query_results(), params.query_results);
+        assert_eq!(pi.rewards_rate(), params.rewards_rate);
+        assert_eq!(pi.max_rewards_rate(), params.max_rewards_rate);
+        assert_eq!(pi.threshold(), params.threshold);
+        assert_eq!(pi.leaf_mode(), params.leaf_mode);
+        assert_eq!(pi.state_depth(), F::from_canonical_u32(params.state_depth));
+    }
+}
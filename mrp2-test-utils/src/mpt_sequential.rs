@@ -1,10 +1,15 @@
+use anyhow::Result;
 use eth_trie::{EthTrie, MemoryDB, Trie};
 use rand::{thread_rng, Rng};
+use sha3::{Digest, Keccak256};
 use std::sync::Arc;
 
 /// Simply the maximum number of nibbles a key can have.
 const MAX_KEY_NIBBLE_LEN: usize = 64;
 
+/// MPT proof of a key, i.e. the list of nodes from the root to the leaf
+pub type MptProof = Vec<Vec<u8>>;
+
 /// generate a random storage trie and a key. The MPT proof corresponding to
 /// that key is guaranteed to be of DEPTH length. Each leaves in the trie
 /// is of NODE_LEN length.
@@ -39,3 +44,84 @@ pub fn generate_random_storage_mpt<const DEPTH: usize, const VALUE_LEN: usize>(
     }
     (trie, keys[right_key_idx].to_vec())
 }
+
+/// Build a storage trie containing the given `(mapping_key, value)` entries of
+/// a mapping located at `slot`. The values are RLP encoded before insertion,
+/// as in the storage trie of a contract. It returns the trie along with the
+/// MPT proofs of each entry, in the same order as `entries`. It fails if the
+/// slot doesn't fit in a single byte.
+pub fn build_mapping_trie(
+    entries: &[(Vec<u8>, Vec<u8>)],
+    slot: usize,
+) -> Result<(EthTrie<MemoryDB>, Vec<MptProof>)> {
+    let memdb = Arc::new(MemoryDB::new(true));
+    let mut trie = EthTrie::new(Arc::clone(&memdb));
+    let mpt_keys = entries
+        .iter()
+        .map(|(mapping_key, value)| {
+            let mpt_key = mapping_mpt_key(mapping_key, slot)?;
+            trie.insert(&mpt_key, &rlp::encode(&value.as_slice()))?;
+            Ok(mpt_key)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    trie.root_hash()?;
+    let proofs = mpt_keys
+        .iter()
+        .map(|k| trie.get_proof(k))
+        .collect::<Result<_, _>>()?;
+    Ok((trie, proofs))
+}
+
+/// Compute the MPT key of a mapping entry:
+/// keccak(keccak(pad32(mapping_key), pad32(mapping_slot)))
+fn mapping_mpt_key(mapping_key: &[u8], slot: usize) -> Result<Vec<u8>> {
+    let slot = u8::try_from(slot)?;
+    let location = keccak256(
+        &left_pad32(mapping_key)
+            .into_iter()
+            .chain(left_pad32(&[slot]))
+            .collect::<Vec<_>>(),
+    );
+    Ok(keccak256(&location))
+}
+
+fn keccak256(data: &[u8]) -> Vec<u8> {
+    Keccak256::digest(data).to_vec()
+}
+
+fn left_pad32(slice: &[u8]) -> [u8; 32] {
+    assert!(slice.len() <= 32, "slice too long to be padded to 32 bytes");
+    let mut padded = [0u8; 32];
+    padded[32 - slice.len()..].copy_from_slice(slice);
+    padded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::random_vector;
+
+    #[test]
+    fn test_build_mapping_trie() {
+        const SLOT: usize = 2;
+        let entries = (0..4)
+            .map(|_| (random_vector(20), random_vector(20)))
+            .collect::<Vec<_>>();
+
+        let (mut trie, proofs) = build_mapping_trie(&entries, SLOT).unwrap();
+        assert_eq!(proofs.len(), entries.len());
+
+        let root = trie.root_hash().unwrap();
+        entries
+            .iter()
+            .zip(proofs)
+            .for_each(|((mapping_key, value), proof)| {
+                let mpt_key = mapping_mpt_key(mapping_key, SLOT).unwrap();
+                let proven = trie
+                    .verify_proof(root, &mpt_key, proof)
+                    .unwrap()
+                    .expect("entry not found in the trie");
+                assert_eq!(proven, rlp::encode(&value.as_slice()).to_vec());
+            });
+    }
+}
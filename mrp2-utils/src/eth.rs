@@ -6,7 +6,7 @@ use ethers::{
     providers::{Http, Middleware, Provider},
     types::{
         Address, Block, BlockId, Bytes, EIP1186ProofResponse, Transaction, TransactionReceipt,
-        H256, U64,
+        H256, U256, U64,
     },
 };
 use rlp::{Encodable, Rlp, RlpStream};
@@ -218,6 +218,39 @@ pub fn extract_child_hashes(rlp_data: &[u8]) -> Vec<Vec<u8>> {
     hashes
 }
 
+/// Type of a node of a Merkle Patricia Trie
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MptNodeKind {
+    /// Node with 16 children and a value
+    Branch,
+    /// Node with a shared path of nibbles and a single child
+    Extension,
+    /// Node with the remaining path of nibbles and a value
+    Leaf,
+}
+
+/// Classify a RLP encoded MPT node, e.g. one of the nodes returned by `eth_getProof`,
+/// to pick the circuit employed to prove it. Branch nodes are lists of 17 items,
+/// while leaf and extension nodes are lists of 2 items, distinguished by the flag
+/// in the first nibble of the hex-prefix encoded path: 0 or 1 for an extension,
+/// 2 or 3 for a leaf.
+/// It panics if the node is not a valid RLP encoded MPT node.
+pub fn classify_mpt_node(node: &[u8]) -> MptNodeKind {
+    let rlp = Rlp::new(node);
+    match rlp.item_count().expect("MPT node must be a RLP list") {
+        17 => MptNodeKind::Branch,
+        2 => {
+            let path = rlp.at(0).and_then(|item| item.data()).unwrap();
+            match path.first().expect("MPT node path must not be empty") >> 4 {
+                0 | 1 => MptNodeKind::Extension,
+                2 | 3 => MptNodeKind::Leaf,
+                flag => panic!("invalid hex-prefix flag {flag} in MPT node path"),
+            }
+        }
+        n => panic!("invalid number of items in MPT node: {n}"),
+    }
+}
+
 pub fn left_pad32(slice: &[u8]) -> [u8; 32] {
     left_pad::<32>(slice)
 }
@@ -254,6 +287,10 @@ pub enum StorageSlot {
     /// Second argument is the slot location inthe contract
     /// (mapping_key, mapping_slot)
     Mapping(Vec<u8>, usize),
+    /// Element of a dynamic array - Solidity stores the element `i` of the array
+    /// at location `keccak(pad32(slot)) + i`
+    /// (array_slot, index)
+    ArrayElement(usize, usize),
 }
 impl StorageSlot {
     pub fn location(&self) -> H256 {
@@ -269,6 +306,16 @@ impl StorageSlot {
                     .collect::<Vec<_>>();
                 H256::from_slice(&keccak256(&concat))
             }
+            StorageSlot::ArrayElement(slot, index) => {
+                // H(pad32(slot)) + index
+                let padded_slot = H256::from_low_u64_be(*slot as u64);
+                let base = U256::from_big_endian(&keccak256(padded_slot.as_bytes()));
+                let mut location = [0u8; 32];
+                base.overflowing_add(U256::from(*index))
+                    .0
+                    .to_big_endian(&mut location);
+                H256::from(location)
+            }
         }
     }
     pub fn mpt_key_vec(&self) -> Vec<u8> {
@@ -295,6 +342,12 @@ impl ProofQuery {
             slot: StorageSlot::Mapping(mapping_key, slot),
         }
     }
+    pub fn new_array_element_slot(address: Address, slot: usize, index: usize) -> Self {
+        Self {
+            contract: address,
+            slot: StorageSlot::ArrayElement(slot, index),
+        }
+    }
     pub async fn query_mpt_proof<P: Middleware + 'static>(
         &self,
         provider: &P,
@@ -399,6 +452,67 @@ mod test {
     }
 
     use super::*;
+    #[test]
+    fn test_array_element_slot() {
+        // the elements of an array at slot 0 start at the well-known keccak(pad32(0))
+        let slot = StorageSlot::ArrayElement(0, 1);
+        assert_eq!(
+            slot.location(),
+            H256::from_str("0x290decd9548b62a8d60345a988386fc84ba6bc95484008f6362f93160ef3e564")
+                .unwrap()
+        );
+
+        let (array_slot, index) = (7, 42);
+        let slot = StorageSlot::ArrayElement(array_slot, index);
+        let base = ethers::utils::keccak256(ethers::abi::encode(&[ethers::abi::Token::Uint(
+            array_slot.into(),
+        )]));
+        let mut expected_location = [0u8; 32];
+        (U256::from_big_endian(&base) + U256::from(index)).to_big_endian(&mut expected_location);
+        assert_eq!(slot.location(), H256::from(expected_location));
+        assert_eq!(slot.mpt_key(), ethers::utils::keccak256(expected_location));
+    }
+
+    #[test]
+    fn test_classify_mpt_node() {
+        // 2 keys sharing the first byte, so that the root is an extension node
+        // followed by a branch node, whose children are the 2 leaves
+        let mut trie = EthTrie::new(Arc::new(MemoryDB::new(true)));
+        let mut keys = [[0u8; 32]; 2];
+        thread_rng().fill(&mut keys[0]);
+        keys[1] = keys[0];
+        keys[0][1] = 0x40 | (keys[0][1] & 0x0f);
+        keys[1][1] = 0x50 | (keys[1][1] & 0x0f);
+        for key in &keys {
+            trie.insert(key, &thread_rng().gen::<[u8; 32]>()).unwrap();
+        }
+        trie.root_hash().unwrap();
+
+        for key in &keys {
+            let proof = trie.get_proof(key).unwrap();
+            let kinds = proof
+                .iter()
+                .map(|node| classify_mpt_node(node))
+                .collect::<Vec<_>>();
+            assert_eq!(
+                kinds,
+                [
+                    MptNodeKind::Extension,
+                    MptNodeKind::Branch,
+                    MptNodeKind::Leaf
+                ]
+            );
+        }
+
+        // a single key is stored in a leaf as the root
+        let mut trie = EthTrie::new(Arc::new(MemoryDB::new(true)));
+        trie.insert(&keys[0], &[42; 32]).unwrap();
+        trie.root_hash().unwrap();
+        let proof = trie.get_proof(&keys[0]).unwrap();
+        assert_eq!(proof.len(), 1);
+        assert_eq!(classify_mpt_node(&proof[0]), MptNodeKind::Leaf);
+    }
+
     #[tokio::test]
     async fn test_sepolia_slot() -> Result<()> {
         #[cfg(feature = "ci")]
@@ -524,6 +638,11 @@ mod test {
         let raw_address = ProofQuery::verify_storage_proof(&res)?;
         // the value is actually RLP encoded !
         let decoded_address: Vec<u8> = rlp::decode(&raw_address).unwrap();
+        let proof = &res.storage_proof[0].proof;
+        assert_eq!(classify_mpt_node(proof.last().unwrap()), MptNodeKind::Leaf);
+        assert!(proof[..proof.len() - 1]
+            .iter()
+            .all(|node| classify_mpt_node(node) != MptNodeKind::Leaf));
         let leaf_node: Vec<Vec<u8>> = rlp::decode_list(&res.storage_proof[0].proof.last().unwrap());
         println!("leaf_node[1].len() = {}", leaf_node[1].len());
         // this is read in the same order
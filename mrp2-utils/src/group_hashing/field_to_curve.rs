@@ -12,7 +12,7 @@ use plonky2::{
     plonk::circuit_builder::CircuitBuilder,
 };
 use plonky2_ecgfp5::{
-    curve::curve::Point,
+    curve::curve::{Point, WeierstrassPoint},
     gadgets::{
         base_field::{CircuitBuilderGFp5, QuinticExtensionTarget},
         curve::{CircuitBuilderEcGFp5, CurveTarget},
@@ -49,6 +49,38 @@ where
     QuinticExtension::from_basefield_array(hash).map_to_curve_point()
 }
 
+/// Convert the field values to a curve point in the domain identified by
+/// `domain_tag`, which is prepended to the values before mapping them. Values
+/// mapped under different tags yield unrelated points, so that digests of
+/// different kinds of values (e.g., NFT IDs and addresses) can't collide.
+pub fn map_to_curve_point_tagged<F>(values: &[F], domain_tag: F) -> Point
+where
+    F: RichField + Extendable<N>,
+    QuinticExtension<F>: ToCurvePoint,
+{
+    let tagged_values: Vec<_> = std::iter::once(domain_tag)
+        .chain(values.iter().copied())
+        .collect();
+
+    map_to_curve_point(&tagged_values)
+}
+
+/// Check that the multiset of `claimed` keys is the one accumulated in `digest`, i.e., that
+/// `digest` is the sum of the curve points the keys are mapped to. The order of the keys
+/// doesn't matter, while the multiplicity of each key does.
+pub fn prove_multiset_equality<F>(claimed: &[Vec<F>], digest: WeierstrassPoint) -> bool
+where
+    F: RichField + Extendable<N>,
+    QuinticExtension<F>: ToCurvePoint,
+{
+    let accumulated = claimed
+        .iter()
+        .map(|key| map_to_curve_point(key))
+        .fold(Point::NEUTRAL, |acc, p| acc + p);
+
+    accumulated.to_weierstrass() == digest
+}
+
 /// Convert the field targets to a curve target.
 pub(crate) fn map_to_curve_target<F, const D: usize>(
     b: &mut CircuitBuilder<F, D>,
@@ -71,7 +103,7 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::group_hashing::CircuitBuilderGroupHashing;
+    use crate::group_hashing::{add_curve_point, CircuitBuilderGroupHashing};
     use anyhow::Result;
     use plonky2::{
         field::{extension::quintic::QuinticExtension, types::Field},
@@ -82,7 +114,6 @@ mod tests {
             config::{GenericConfig, PoseidonGoldilocksConfig},
         },
     };
-    use plonky2_ecgfp5::curve::curve::WeierstrassPoint;
     use rand::Rng;
 
     const ARITY: usize = 1;
@@ -141,4 +172,88 @@ mod tests {
         // Verify the proof.
         data.verify(proof)
     }
+
+    /// Test field to curve point conversion under a domain tag.
+    #[test]
+    fn test_field_to_curve_point_tagged() -> Result<()> {
+        let config = CircuitConfig::standard_recursion_config();
+        let mut b = CircuitBuilder::<F, D>::new(config);
+
+        let input_values = rand::thread_rng()
+            .gen::<[u64; ARITY]>()
+            .map(F::from_canonical_u64);
+        let [tag, other_tag] = [1, 2].map(F::from_canonical_u64);
+
+        // the same input under two tags, or without tag, yields different points
+        let tagged_point = map_to_curve_point_tagged(&input_values, tag).to_weierstrass();
+        assert_ne!(
+            tagged_point,
+            map_to_curve_point_tagged(&input_values, other_tag).to_weierstrass()
+        );
+        assert_ne!(
+            tagged_point,
+            map_to_curve_point(&input_values).to_weierstrass()
+        );
+
+        // the gadget computes the same point
+        let input_targets = [0; ARITY].map(|_| b.add_virtual_target());
+        let tag_target = b.constant(tag);
+        let output_target = b.map_to_curve_point_tagged(&input_targets, tag_target);
+        b.register_curve_public_input(output_target);
+
+        let mut pw = PartialWitness::new();
+        input_targets
+            .into_iter()
+            .zip(input_values)
+            .for_each(|(it, iv)| pw.set_target(it, iv));
+
+        let data = b.build::<C>();
+        let proof = data.prove(pw)?;
+
+        let real_point = WeierstrassPoint {
+            x: QuinticExtension(proof.public_inputs[..N].try_into().unwrap()),
+            y: QuinticExtension(proof.public_inputs[N..N + N].try_into().unwrap()),
+            is_inf: proof.public_inputs[N + N].is_nonzero(),
+        };
+        assert_eq!(real_point, tagged_point);
+
+        data.verify(proof)
+    }
+
+    /// Test the multiset equality between a list of keys and a digest.
+    #[test]
+    fn test_prove_multiset_equality() {
+        let rng = &mut rand::thread_rng();
+        let keys = (0..5)
+            .map(|_| rng.gen::<[u64; 4]>().map(F::from_canonical_u64).to_vec())
+            .collect::<Vec<_>>();
+        let digest = add_curve_point(
+            &keys
+                .iter()
+                .map(|key| map_to_curve_point(key))
+                .collect::<Vec<_>>(),
+        )
+        .to_weierstrass();
+
+        // the order of the keys doesn't matter
+        let mut shuffled_keys = keys.clone();
+        shuffled_keys.reverse();
+        assert!(prove_multiset_equality(&keys, digest));
+        assert!(prove_multiset_equality(&shuffled_keys, digest));
+
+        // a missing key, an additional key or a different key are detected
+        assert!(!prove_multiset_equality(&keys[1..], digest));
+        let mut more_keys = keys.clone();
+        more_keys.push(keys[0].clone());
+        assert!(!prove_multiset_equality(&more_keys, digest));
+        let mut wrong_keys = keys.clone();
+        wrong_keys[2][0] += F::ONE;
+        assert!(!prove_multiset_equality(&wrong_keys, digest));
+
+        // an empty list of keys corresponds to the neutral point
+        assert!(prove_multiset_equality::<F>(
+            &[],
+            Point::NEUTRAL.to_weierstrass()
+        ));
+    }
 }
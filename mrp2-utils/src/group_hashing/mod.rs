@@ -20,7 +20,7 @@ pub const EXTENSION_DEGREE: usize = 5;
 
 pub use curve_add::add_curve_point;
 /// Field-to-curve and curve point addition functions
-pub use field_to_curve::map_to_curve_point;
+pub use field_to_curve::{map_to_curve_point, map_to_curve_point_tagged, prove_multiset_equality};
 
 /// Trait for adding field-to-curve and curve point addition functions to
 /// circuit builder
@@ -34,6 +34,10 @@ pub trait CircuitBuilderGroupHashing {
     /// Convert the field targets to a curve target.
     fn map_to_curve_point(&mut self, targets: &[Target]) -> CurveTarget;
 
+    /// Convert the field targets to a curve target in the domain identified by
+    /// `domain_tag`, see [map_to_curve_point_tagged].
+    fn map_to_curve_point_tagged(&mut self, targets: &[Target], domain_tag: Target) -> CurveTarget;
+
     /// Require that two points must be equal, and none is infinity.
     /// Unlike the [curve_eq](https://github.com/Lagrange-Labs/plonky2-ecgfp5/blob/d5a6a0b7dfee4ab69d8c1c315f9f4407502f07eb/src/gadgets/curve.rs#L83)
     /// function, this constrains none of the points is infinity.
@@ -57,6 +61,13 @@ where
         field_to_curve::map_to_curve_target(self, targets)
     }
 
+    fn map_to_curve_point_tagged(&mut self, targets: &[Target], domain_tag: Target) -> CurveTarget {
+        let tagged_targets: Vec<_> = std::iter::once(domain_tag)
+            .chain(targets.iter().copied())
+            .collect();
+        self.map_to_curve_point(&tagged_targets)
+    }
+
     fn connect_curve_points(&mut self, a: CurveTarget, b: CurveTarget) {
         let CurveTarget(([ax, ay], a_is_inf)) = a;
         let CurveTarget(([bx, by], b_is_inf)) = b;
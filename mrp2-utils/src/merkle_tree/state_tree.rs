@@ -116,6 +116,38 @@ impl<const MAX_DEPTH: usize> StateTreeWires<MAX_DEPTH> {
         }
     }
 
+    /// Enforces that the proven depth is consistent with the opening path: the siblings must be
+    /// non-empty exactly up to the depth, and the positions beyond the depth must be `false`.
+    ///
+    /// - `siblings` and `positions` must be the same employed to build the wires.
+    pub fn enforce_depth_consistency<F, const D: usize>(
+        &self,
+        cb: &mut CircuitBuilder<F, D>,
+        siblings: &MerkleProofTarget,
+        positions: &[BoolTarget],
+    ) where
+        F: RichField + Extendable<D>,
+    {
+        let zero = cb.zero();
+        let _false = cb._false();
+        for d in 0..MAX_DEPTH {
+            let is_empty = siblings.siblings[d]
+                .elements
+                .iter()
+                .fold(cb._true(), |acc, &e| {
+                    let is_zero = cb.is_equal(e, zero);
+                    cb.and(acc, is_zero)
+                });
+            // v₍ᵢ₎ == (sibling₍ᵢ₎ != 0)
+            let is_non_empty = cb.not(is_empty);
+            cb.connect(self.is_value[d].target, is_non_empty.target);
+            // (1 - v₍ᵢ₎) · position₍ᵢ₎ == 0
+            let is_padding = cb.not(self.is_value[d]);
+            let padding_position = cb.and(is_padding, positions[d]);
+            cb.connect(padding_position.target, _false.target);
+        }
+    }
+
     /// Assigns the provided data as witness.
     ///
     /// - `depth` will be the variable depth to be proven. Will not be assigned to a target.
@@ -166,6 +198,33 @@ mod tests {
         assert_eq!(root, proof.public_inputs);
     }
 
+    #[test]
+    fn mismatched_depth_fails() {
+        let seed = 0xdead;
+        let depth = 3;
+
+        // a position set beyond the depth
+        let mut circuit = TestVariableDepthCircuit::from_seed_with_depth(seed, depth);
+        circuit.positions[depth as usize] = GoldilocksField::ONE;
+        let res =
+            std::panic::catch_unwind(|| run_circuit::<_, _, PoseidonGoldilocksConfig, _>(circuit));
+        assert!(res.is_err(), "position beyond the depth accepted");
+
+        // a depth smaller than the number of non-empty siblings
+        let mut circuit = TestVariableDepthCircuit::from_seed_with_depth(seed, depth);
+        circuit.depth = GoldilocksField::from_canonical_u32(depth - 1);
+        let res =
+            std::panic::catch_unwind(|| run_circuit::<_, _, PoseidonGoldilocksConfig, _>(circuit));
+        assert!(res.is_err(), "depth smaller than the opening path accepted");
+
+        // a depth greater than the number of non-empty siblings
+        let mut circuit = TestVariableDepthCircuit::from_seed_with_depth(seed, depth);
+        circuit.depth = GoldilocksField::from_canonical_u32(depth + 1);
+        let res =
+            std::panic::catch_unwind(|| run_circuit::<_, _, PoseidonGoldilocksConfig, _>(circuit));
+        assert!(res.is_err(), "depth greater than the opening path accepted");
+    }
+
     #[derive(Clone, Debug)]
     struct TestVariableDepthWires {
         pub wires: StateTreeWires<TEST_MAX_DEPTH>,
@@ -254,6 +313,7 @@ mod tests {
                 .map(|_| b.add_virtual_bool_target_safe())
                 .collect();
             let wires = StateTreeWires::build(b, &leaf_data, &siblings, &positions);
+            wires.enforce_depth_consistency(b, &siblings, &positions);
 
             b.register_public_inputs(&wires.root.elements);
 
@@ -51,12 +51,35 @@ where
     }
 }
 
+/// Return the Poseidon hash of `inputs` if `flag` is true, and `passthrough` otherwise.
+/// It allows to forward the root of an empty subtree in place of the hash of an absent
+/// node when aggregating sparse trees.
+pub fn hash_or_passthrough<F, const D: usize>(
+    b: &mut CircuitBuilder<F, D>,
+    flag: BoolTarget,
+    inputs: &[Target],
+    passthrough: HashOutTarget,
+) -> HashOutTarget
+where
+    F: RichField + Extendable<D>,
+{
+    let hash = b.hash_n_to_hash_no_pad::<H>(inputs.to_vec());
+
+    HashOutTarget {
+        elements: std::array::from_fn(|i| {
+            b.select(flag, hash.elements[i], passthrough.elements[i])
+        }),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use mrp2_test_utils::circuit::{run_circuit, UserCircuit};
     use plonky2::{
-        field::types::Field,
-        hash::{hashing::hash_n_to_hash_no_pad, poseidon::PoseidonPermutation},
+        field::types::{Field, Sample},
+        hash::{
+            hash_types::HashOut, hashing::hash_n_to_hash_no_pad, poseidon::PoseidonPermutation,
+        },
         iop::witness::{PartialWitness, WitnessWrite},
         plonk::config::PoseidonGoldilocksConfig,
     };
@@ -143,4 +166,61 @@ mod tests {
             }
         }
     }
+
+    const NUM_INPUTS: usize = 6;
+
+    #[derive(Debug, Clone)]
+    struct TestHashOrPassthroughCircuit {
+        inputs: [GoldilocksField; NUM_INPUTS],
+        passthrough: HashOut<GoldilocksField>,
+        flag: bool,
+    }
+
+    impl UserCircuit<GoldilocksField, 2> for TestHashOrPassthroughCircuit {
+        type Wires = ([Target; NUM_INPUTS], HashOutTarget, BoolTarget);
+
+        fn build(cb: &mut CircuitBuilder<GoldilocksField, 2>) -> Self::Wires {
+            let inputs = cb.add_virtual_target_arr();
+            let passthrough = cb.add_virtual_hash();
+            let flag = cb.add_virtual_bool_target_safe();
+            let h = hash_or_passthrough(cb, flag, &inputs, passthrough);
+
+            cb.register_public_inputs(&h.elements);
+
+            (inputs, passthrough, flag)
+        }
+
+        fn prove(&self, pw: &mut PartialWitness<GoldilocksField>, wires: &Self::Wires) {
+            pw.set_target_arr(&wires.0, &self.inputs);
+            pw.set_hash_target(wires.1, self.passthrough);
+            pw.set_bool_target(wires.2, self.flag);
+        }
+    }
+
+    #[test]
+    fn hash_or_passthrough_both_branches() {
+        let inputs = GoldilocksField::rand_array();
+        let passthrough = HashOut::rand();
+        let h = hash_n_to_hash_no_pad::<GoldilocksField, PoseidonPermutation<GoldilocksField>>(
+            inputs.as_slice(),
+        );
+
+        // the inputs are hashed if the flag is set
+        let circuit = TestHashOrPassthroughCircuit {
+            inputs,
+            passthrough,
+            flag: true,
+        };
+        let proof = run_circuit::<_, _, PoseidonGoldilocksConfig, _>(circuit);
+        assert_eq!(&h.elements[..], proof.public_inputs.as_slice());
+
+        // the passthrough hash is forwarded otherwise
+        let circuit = TestHashOrPassthroughCircuit {
+            inputs,
+            passthrough,
+            flag: false,
+        };
+        let proof = run_circuit::<_, _, PoseidonGoldilocksConfig, _>(circuit);
+        assert_eq!(&passthrough.elements[..], proof.public_inputs.as_slice());
+    }
 }
@@ -65,7 +65,7 @@ use plonky2_ecgfp5::{
     gadgets::base_field::{QuinticQuotientGenerator, QuinticSqrtGenerator},
 };
 
-use crate::u256::UInt256DivGenerator;
+use crate::u256::{LimbSplitGenerator, UInt256DivGenerator, UInt512ModGenerator};
 
 use super::{FromBytes, SerializationError, ToBytes};
 
@@ -224,7 +224,9 @@ where
         QuinticQuotientGenerator,
         QuinticSqrtGenerator,
         // uint256 generators added
-        UInt256DivGenerator
+        UInt256DivGenerator,
+        UInt512ModGenerator,
+        LimbSplitGenerator
     }
 }
 
@@ -1,9 +1,19 @@
 //! Custom types
 
-use crate::{array::Array, u256::NUM_LIMBS, D};
+use crate::{
+    array::Array,
+    u256::NUM_LIMBS,
+    utils::{less_than, less_than_or_equal_to, Packer, ToFields},
+    D,
+};
+use anyhow::{ensure, Result};
+use ethers::types::Address;
 use plonky2::{
-    field::{extension::quintic::QuinticExtension, goldilocks_field::GoldilocksField},
-    iop::target::Target,
+    field::{
+        extension::quintic::QuinticExtension, goldilocks_field::GoldilocksField, types::Field,
+    },
+    hash::hash_types::RichField,
+    iop::target::{BoolTarget, Target},
     plonk::circuit_builder::CircuitBuilder,
 };
 use plonky2_crypto::u32::arithmetic_u32::U32Target;
@@ -37,6 +47,21 @@ pub type AddressTarget = Array<Target, ADDRESS_LEN>;
 /// U32 representation of an address
 pub type PackedAddressTarget = Array<U32Target, PACKED_ADDRESS_LEN>;
 
+impl PackedAddressTarget {
+    /// Build a packed address from `targets`, each being a u32 limb of the address;
+    /// it fails if there are not exactly `PACKED_ADDRESS_LEN` targets
+    pub fn from_targets_checked(targets: &[Target]) -> Result<Self> {
+        ensure!(
+            targets.len() == PACKED_ADDRESS_LEN,
+            "a packed address is made of {PACKED_ADDRESS_LEN} targets, found {}",
+            targets.len()
+        );
+        Ok(Self {
+            arr: std::array::from_fn(|i| U32Target(targets[i])),
+        })
+    }
+}
+
 pub const PACKED_U256_LEN: usize = NUM_LIMBS;
 
 /// The length of a mapping key in bytes
@@ -68,3 +93,176 @@ pub const MAX_BLOCK_LEN: usize = 650;
 pub const MAPPING_LEAF_VALUE_LEN: usize = 32;
 
 pub type PackedSCAddress<F> = Array<F, PACKED_ADDRESS_LEN>;
+
+/// Pack an address in u32 limbs, converted to field elements, which is the
+/// representation of addresses in public inputs
+pub fn address_to_packed_fields<F: RichField>(addr: &Address) -> [F; PACKED_ADDRESS_LEN] {
+    addr.as_fixed_bytes()
+        .pack()
+        .to_fields()
+        .try_into()
+        .expect("an address is packed in PACKED_ADDRESS_LEN limbs")
+}
+
+/// Pack each of the addresses with [address_to_packed_fields]
+pub fn addresses_to_packed_fields<F: RichField>(addrs: &[Address]) -> Vec<[F; PACKED_ADDRESS_LEN]> {
+    addrs.iter().map(address_to_packed_fields).collect()
+}
+
+/// Enforce that the first `count` addresses in `addrs` are sorted in strictly
+/// increasing order, and so that they are unique. Addresses are compared
+/// lexicographically over their packed limbs, starting from the first limb, with
+/// each limb compared as a 32-bit integer; the addresses beyond `count` are
+/// ignored, and `count` must not be greater than the number of addresses.
+pub fn assert_sorted_unique_addresses(
+    b: &mut CBuilder,
+    addrs: &[PackedAddressTarget],
+    count: Target,
+) {
+    let _true = b._true();
+    let num_addrs = b.constant(GoldilocksField::from_canonical_usize(addrs.len()));
+    let valid_count = less_than_or_equal_to(b, count, num_addrs, 32);
+    b.connect(valid_count.target, _true.target);
+
+    for i in 1..addrs.len() {
+        let it = b.constant(GoldilocksField::from_canonical_usize(i));
+        let should_be_ordered = less_than(b, it, count, 32);
+        let ordered = is_less_than_address(b, &addrs[i - 1], &addrs[i]);
+        let is_ordered = b.select(should_be_ordered, ordered.target, _true.target);
+        b.connect(is_ordered, _true.target);
+    }
+}
+
+/// Returns true if `left < right` in the lexicographic order over the packed limbs
+fn is_less_than_address(
+    b: &mut CBuilder,
+    left: &PackedAddressTarget,
+    right: &PackedAddressTarget,
+) -> BoolTarget {
+    let mut is_less = b._false();
+    let mut is_equal_so_far = b._true();
+    for (l, r) in left.arr.iter().zip(right.arr.iter()) {
+        let is_limb_less = less_than(b, l.0, r.0, 32);
+        let is_limb_equal = b.is_equal(l.0, r.0);
+        let is_less_at_limb = b.and(is_equal_so_far, is_limb_less);
+        is_less = b.or(is_less, is_less_at_limb);
+        is_equal_so_far = b.and(is_equal_so_far, is_limb_equal);
+    }
+    is_less
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mrp2_test_utils::circuit::{run_circuit, UserCircuit};
+    use plonky2::{
+        iop::witness::{PartialWitness, WitnessWrite},
+        plonk::{circuit_data::CircuitConfig, config::PoseidonGoldilocksConfig},
+    };
+
+    const NUM_ADDRESSES: usize = 4;
+
+    #[derive(Clone, Debug)]
+    struct TestSortedAddressesCircuit {
+        addrs: [[GFp; PACKED_ADDRESS_LEN]; NUM_ADDRESSES],
+        count: usize,
+    }
+
+    impl UserCircuit<GFp, D> for TestSortedAddressesCircuit {
+        type Wires = ([PackedAddressTarget; NUM_ADDRESSES], Target);
+
+        fn build(b: &mut CBuilder) -> Self::Wires {
+            let addrs = [0; NUM_ADDRESSES].map(|_| PackedAddressTarget::new(b));
+            let count = b.add_virtual_target();
+            assert_sorted_unique_addresses(b, &addrs, count);
+            (addrs, count)
+        }
+
+        fn prove(&self, pw: &mut PartialWitness<GFp>, wires: &Self::Wires) {
+            wires
+                .0
+                .iter()
+                .zip(self.addrs.iter())
+                .for_each(|(t, v)| t.assign(pw, v));
+            pw.set_target(wires.1, GFp::from_canonical_usize(self.count));
+        }
+    }
+
+    fn check_addresses(addrs: [[GFp; PACKED_ADDRESS_LEN]; NUM_ADDRESSES], count: usize) -> bool {
+        std::panic::catch_unwind(|| {
+            run_circuit::<GFp, D, PoseidonGoldilocksConfig, _>(TestSortedAddressesCircuit {
+                addrs,
+                count,
+            })
+        })
+        .is_ok()
+    }
+
+    #[test]
+    fn test_address_to_packed_fields() {
+        let addr = Address::from(std::array::from_fn::<u8, ADDRESS_LEN, _>(|i| i as u8 + 1));
+        let exp_limbs = [0x04030201, 0x08070605, 0x0c0b0a09, 0x100f0e0d, 0x14131211]
+            .map(GFp::from_canonical_u32);
+        assert_eq!(address_to_packed_fields::<GFp>(&addr), exp_limbs);
+
+        let other_addr = Address::random();
+        assert_eq!(
+            addresses_to_packed_fields::<GFp>(&[addr, other_addr]),
+            vec![exp_limbs, address_to_packed_fields(&other_addr)]
+        );
+    }
+
+    #[test]
+    fn test_packed_address_target_from_targets_checked() {
+        let mut b = CBuilder::new(CircuitConfig::standard_recursion_config());
+        let targets = b.add_virtual_targets(PACKED_ADDRESS_LEN + 1);
+
+        let addr =
+            PackedAddressTarget::from_targets_checked(&targets[..PACKED_ADDRESS_LEN]).unwrap();
+        assert_eq!(
+            addr.arr.map(|limb| limb.0).as_slice(),
+            &targets[..PACKED_ADDRESS_LEN]
+        );
+        assert!(PackedAddressTarget::from_targets_checked(&targets).is_err());
+        assert!(
+            PackedAddressTarget::from_targets_checked(&targets[..PACKED_ADDRESS_LEN - 1]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_assert_sorted_unique_addresses() {
+        let mut addrs =
+            [0; NUM_ADDRESSES].map(|_| address_to_packed_fields::<GFp>(&Address::random()));
+        addrs.sort_by_key(|addr| addr.map(|f| f.0));
+        // addresses sharing the first limbs are ordered by the following ones
+        let mut prefix_addr = addrs[NUM_ADDRESSES - 1];
+        prefix_addr[PACKED_ADDRESS_LEN - 1] = GFp::from_canonical_u32(u32::MAX);
+        let mut sorted_addrs = addrs;
+        sorted_addrs[NUM_ADDRESSES - 1] = prefix_addr;
+        sorted_addrs[NUM_ADDRESSES - 2] = addrs[NUM_ADDRESSES - 1];
+        sorted_addrs[NUM_ADDRESSES - 2][PACKED_ADDRESS_LEN - 1] = GFp::from_canonical_u32(0);
+
+        // sorted and unique addresses
+        assert!(check_addresses(sorted_addrs, NUM_ADDRESSES));
+        assert!(check_addresses(sorted_addrs, 0));
+
+        // duplicated addresses
+        let mut duplicated = sorted_addrs;
+        duplicated[2] = duplicated[1];
+        assert!(!check_addresses(duplicated, NUM_ADDRESSES));
+        // the addresses beyond `count` are ignored
+        assert!(check_addresses(duplicated, 2));
+
+        // out-of-order addresses
+        let mut out_of_order = sorted_addrs;
+        out_of_order.swap(0, 1);
+        assert!(!check_addresses(out_of_order, NUM_ADDRESSES));
+        let mut out_of_order = sorted_addrs;
+        out_of_order.swap(2, 3);
+        assert!(!check_addresses(out_of_order, NUM_ADDRESSES));
+        assert!(check_addresses(out_of_order, 3));
+
+        // `count` greater than the number of addresses
+        assert!(!check_addresses(sorted_addrs, NUM_ADDRESSES + 1));
+    }
+}
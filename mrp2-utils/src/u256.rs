@@ -3,6 +3,7 @@
 
 use std::{
     array::{self, from_fn as create_array},
+    sync::{Arc, OnceLock},
     usize,
 };
 
@@ -13,14 +14,15 @@ use crate::{
     utils::{convert_u8_to_u32_slice, ToFields},
 };
 use anyhow::{ensure, Result};
-use ethers::types::U256;
+use ethers::types::{I256, U256, U512};
 use itertools::Itertools;
 use plonky2::{
+    gates::lookup_table::LookupTable,
     hash::hash_types::RichField,
     iop::{
         generator::{GeneratedValues, SimpleGenerator},
         target::{BoolTarget, Target},
-        witness::{PartitionWitness, WitnessWrite},
+        witness::{PartitionWitness, Witness, WitnessWrite},
     },
     plonk::{circuit_builder::CircuitBuilder, circuit_data::CommonCircuitData},
     util::serialization::{Buffer, IoResult, Read, Write},
@@ -34,10 +36,26 @@ use serde::{Deserialize, Serialize};
 /// Number of limbs employed to represent a 256-bit unsigned integer
 pub const NUM_LIMBS: usize = 8;
 
+/// Number of bits of a 256-bit unsigned integer
+pub const NUM_BITS: usize = 256;
+
+/// Number of bits range-checked by a single lookup in the range-check table. Small enough
+/// for the table to be cheaper than bit-decomposition even in circuits with a few range-checked
+/// `UInt256Target`s
+const LOOKUP_BITS: usize = 8;
+
+/// Number of chunks of `LOOKUP_BITS` bits a 32-bit limb is split into to be range-checked
+const NUM_LOOKUP_CHUNKS: usize = 32 / LOOKUP_BITS;
+
 /// Circuit representation of u256
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct UInt256Target([U32Target; NUM_LIMBS]);
 
+/// Circuit representation of i256, employing the same limbs as `UInt256Target` to store
+/// the two's complement representation of the integer
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct I256Target(UInt256Target);
+
 pub trait CircuitBuilderU256<F: SerializableRichField<D>, const D: usize> {
     /// Add a UInt256Target without any range-check on the limbs
     fn add_virtual_u256_unsafe(&mut self) -> UInt256Target;
@@ -45,12 +63,22 @@ pub trait CircuitBuilderU256<F: SerializableRichField<D>, const D: usize> {
     /// Add a UInt256Target while enforcing that all the limbs are range-checked
     fn add_virtual_u256(&mut self) -> UInt256Target;
 
+    /// Add a UInt256Target while enforcing that all the limbs are range-checked with
+    /// lookups in an 8-bit range-check table. Cheaper than `add_virtual_u256` as soon as
+    /// a few UInt256Target are allocated, since the small table adds only a handful of
+    /// gates to the circuit; note that the table also changes the common data of the
+    /// circuit, which must be accounted for in recursive circuit sets
+    fn add_virtual_u256_with_lookup(&mut self) -> UInt256Target;
+
     /// Register a UInt256Target as public input
     fn register_public_input_u256(&mut self, target: &UInt256Target);
 
     /// Return the constant target representing 0_u256
     fn zero_u256(&mut self) -> UInt256Target;
 
+    /// Return the constant target representing `value`
+    fn constant_u256(&mut self, value: U256) -> UInt256Target;
+
     /// Add 2 UInt256Target, returning the addition modulo 2^256 and the carry
     fn add_u256(
         &mut self,
@@ -58,6 +86,10 @@ pub trait CircuitBuilderU256<F: SerializableRichField<D>, const D: usize> {
         right: &UInt256Target,
     ) -> (UInt256Target, U32Target);
 
+    /// Add all the `terms`, returning the sum modulo 2^256 and a flag specifying whether any
+    /// of the additions overflowed
+    fn add_many_u256(&mut self, terms: &[UInt256Target]) -> (UInt256Target, BoolTarget);
+
     /// Subtract 2 UInt256Target, returning the difference modulo 2^256 and the borrow, if any
     fn sub_u256(
         &mut self,
@@ -65,6 +97,13 @@ pub trait CircuitBuilderU256<F: SerializableRichField<D>, const D: usize> {
         right: &UInt256Target,
     ) -> (UInt256Target, U32Target);
 
+    /// Subtract 2 UInt256Target, returning the difference if `left >= right`, and zero otherwise
+    fn saturating_sub_u256(&mut self, left: &UInt256Target, right: &UInt256Target)
+        -> UInt256Target;
+
+    /// Compute the absolute difference between 2 UInt256Target, i.e., `max(left, right) - min(left, right)`
+    fn abs_diff_u256(&mut self, left: &UInt256Target, right: &UInt256Target) -> UInt256Target;
+
     /// Multiply 2 UInt256Target, returning the product and a flag specifying whether
     /// overflow has occurred or not
     fn mul_u256(
@@ -73,6 +112,10 @@ pub trait CircuitBuilderU256<F: SerializableRichField<D>, const D: usize> {
         right: &UInt256Target,
     ) -> (UInt256Target, BoolTarget);
 
+    /// Raise `base` to the constant `exponent`, returning the power and a flag specifying
+    /// whether overflow has occurred or not
+    fn exp_u256(&mut self, base: &UInt256Target, exponent: u32) -> (UInt256Target, BoolTarget);
+
     /// Divide 2 UInt256Target, returning the quotient and the remainder; it also returns a flag specifying
     /// whether a division by zero error has occurred
     fn div_u256(
@@ -81,9 +124,86 @@ pub trait CircuitBuilderU256<F: SerializableRichField<D>, const D: usize> {
         right: &UInt256Target,
     ) -> (UInt256Target, UInt256Target, BoolTarget);
 
+    /// Divide 2 UInt256Target, returning the quotient rounded to the nearest integer, with ties
+    /// rounded up, i.e., `(left + right/2) / right` without overflowing; it also returns a flag
+    /// specifying whether a division by zero error has occurred
+    fn div_round_u256(
+        &mut self,
+        left: &UInt256Target,
+        right: &UInt256Target,
+    ) -> (UInt256Target, BoolTarget);
+
+    /// Compute `(left + right) % modulus` without overflowing 256 bits before the reduction;
+    /// it also returns a flag specifying whether `modulus` is zero, in which case the result
+    /// is zero
+    fn add_mod(
+        &mut self,
+        left: &UInt256Target,
+        right: &UInt256Target,
+        modulus: &UInt256Target,
+    ) -> (UInt256Target, BoolTarget);
+
+    /// Compute `(left * right) % modulus` over the full 512-bit product; it also returns a
+    /// flag specifying whether `modulus` is zero, in which case the result is zero
+    fn mul_mod(
+        &mut self,
+        left: &UInt256Target,
+        right: &UInt256Target,
+        modulus: &UInt256Target,
+    ) -> (UInt256Target, BoolTarget);
+
+    /// Sum all the UInt256Target in `values`, returning the sum modulo 2^256 and a flag
+    /// specifying whether overflow has occurred or not
+    fn sum_u256(&mut self, values: &[UInt256Target]) -> (UInt256Target, BoolTarget);
+
+    /// Compute the mean `sum(values)/count`, where `count` is a 32-bit integer and the entries
+    /// of `values` not accounted in `count` must be zero; it also returns a flag specifying
+    /// whether the mean is defined, i.e., `count` is not zero and the sum doesn't overflow
+    fn mean_u256(&mut self, values: &[UInt256Target], count: Target)
+        -> (UInt256Target, BoolTarget);
+
+    /// Decompose `x` in its bits, in little-endian order
+    fn to_bits_u256(&mut self, x: &UInt256Target) -> [BoolTarget; NUM_BITS];
+
+    /// Recompose a UInt256Target from its bits, provided in little-endian order
+    fn from_bits_u256(&mut self, bits: &[BoolTarget; NUM_BITS]) -> UInt256Target;
+
+    /// Compute the bitwise AND of 2 UInt256Target
+    fn and_u256(&mut self, left: &UInt256Target, right: &UInt256Target) -> UInt256Target;
+
+    /// Compute the bitwise OR of 2 UInt256Target
+    fn or_u256(&mut self, left: &UInt256Target, right: &UInt256Target) -> UInt256Target;
+
+    /// Compute the bitwise XOR of 2 UInt256Target
+    fn xor_u256(&mut self, left: &UInt256Target, right: &UInt256Target) -> UInt256Target;
+
+    /// Shift `x` to the left by the constant number of bits `shift`, discarding the bits
+    /// beyond 256; shifts of 256 bits or more yield zero
+    fn shl_u256(&mut self, x: &UInt256Target, shift: usize) -> UInt256Target;
+
+    /// Shift `x` to the right by the constant number of bits `shift`; shifts of 256 bits
+    /// or more yield zero
+    fn shr_u256(&mut self, x: &UInt256Target, shift: usize) -> UInt256Target;
+
     /// Compute a `BoolTarget` being true if and only `left < right`
     fn is_less_than_u256(&mut self, left: &UInt256Target, right: &UInt256Target) -> BoolTarget;
 
+    /// Compute a `BoolTarget` being true if and only `left >= right`
+    fn is_greater_or_equal_u256(
+        &mut self,
+        left: &UInt256Target,
+        right: &UInt256Target,
+    ) -> BoolTarget;
+
+    /// Compute a `BoolTarget` being true if and only `left <= right`
+    fn is_less_or_equal_u256(&mut self, left: &UInt256Target, right: &UInt256Target) -> BoolTarget;
+
+    /// Return the minimum between `left` and `right`; `left` is returned if they are equal
+    fn min_u256(&mut self, left: &UInt256Target, right: &UInt256Target) -> UInt256Target;
+
+    /// Return the maximum between `left` and `right`; `left` is returned if they are equal
+    fn max_u256(&mut self, left: &UInt256Target, right: &UInt256Target) -> UInt256Target;
+
     /// Compute a `BoolTarget` being true if and only the 2 input UInt256Target are equal
     fn is_equal_u256(&mut self, left: &UInt256Target, right: &UInt256Target) -> BoolTarget;
 
@@ -99,6 +219,9 @@ pub trait CircuitBuilderU256<F: SerializableRichField<D>, const D: usize> {
         left: &UInt256Target,
         right: &UInt256Target,
     ) -> UInt256Target;
+
+    /// Return `values[index]`, enforcing that `index` is smaller than the number of `values`
+    fn select_array_u256(&mut self, index: Target, values: &[UInt256Target]) -> UInt256Target;
 }
 
 pub trait WitnessWriteU256<F: RichField> {
@@ -106,7 +229,45 @@ pub trait WitnessWriteU256<F: RichField> {
 }
 
 pub trait WitnessReadU256<F: RichField> {
-    fn get_u256_target(&self, target: &UInt256Target) -> U256;
+    /// Read the value of `target`, panicking if any of its limbs doesn't fit in 32 bits
+    fn get_u256_target(&self, target: &UInt256Target) -> U256 {
+        self.try_get_u256_target(target).unwrap()
+    }
+
+    /// Read the value of `target`, returning an error specifying the first limb not fitting
+    /// in 32 bits, if any
+    fn try_get_u256_target(&self, target: &UInt256Target) -> Result<U256>;
+}
+
+pub trait CircuitBuilderI256<F: SerializableRichField<D>, const D: usize> {
+    /// Add an I256Target while enforcing that all the limbs are range-checked
+    fn add_virtual_i256(&mut self) -> I256Target;
+
+    /// Register an I256Target as public input
+    fn register_public_input_i256(&mut self, target: &I256Target);
+
+    /// Add 2 I256Target, returning the addition modulo 2^256 and a flag specifying
+    /// whether the signed addition overflowed
+    fn add_i256(&mut self, left: &I256Target, right: &I256Target) -> (I256Target, BoolTarget);
+
+    /// Subtract 2 I256Target, returning the difference modulo 2^256 and a flag specifying
+    /// whether the signed subtraction overflowed
+    fn sub_i256(&mut self, left: &I256Target, right: &I256Target) -> (I256Target, BoolTarget);
+
+    /// Compute a `BoolTarget` being true if and only if the input I256Target is negative
+    fn is_negative(&mut self, target: &I256Target) -> BoolTarget;
+
+    /// Negate an I256Target, returning the negated value modulo 2^256 and a flag specifying
+    /// whether the negation overflowed, which happens only for `I256::MIN`
+    fn neg_i256(&mut self, target: &I256Target) -> (I256Target, BoolTarget);
+}
+
+pub trait WitnessWriteI256<F: RichField> {
+    fn set_i256_target(&mut self, target: &I256Target, value: I256);
+}
+
+pub trait WitnessReadI256<F: RichField> {
+    fn get_i256_target(&self, target: &I256Target) -> I256;
 }
 
 impl<F: SerializableRichField<D>, const D: usize> CircuitBuilderU256<F, D>
@@ -117,7 +278,6 @@ impl<F: SerializableRichField<D>, const D: usize> CircuitBuilderU256<F, D>
     }
 
     fn add_virtual_u256(&mut self) -> UInt256Target {
-        //ToDo: make it more efficient by employing lookup-gates
         let target = self.add_virtual_u256_unsafe();
         // add range checks for each limb
         target.0.iter().for_each(|t| {
@@ -126,6 +286,15 @@ impl<F: SerializableRichField<D>, const D: usize> CircuitBuilderU256<F, D>
         target
     }
 
+    fn add_virtual_u256_with_lookup(&mut self) -> UInt256Target {
+        let target = self.add_virtual_u256_unsafe();
+        // add range checks for each limb
+        target.0.iter().for_each(|t| {
+            range_check_u32_with_lookup(self, t.0);
+        });
+        target
+    }
+
     fn register_public_input_u256(&mut self, target: &UInt256Target) {
         target
             .0
@@ -160,11 +329,22 @@ impl<F: SerializableRichField<D>, const D: usize> CircuitBuilderU256<F, D>
         )
     }
 
+    fn add_many_u256(&mut self, terms: &[UInt256Target]) -> (UInt256Target, BoolTarget) {
+        self.sum_u256(terms)
+    }
+
     fn zero_u256(&mut self) -> UInt256Target {
         let zero = self.zero_u32();
         UInt256Target([zero; NUM_LIMBS])
     }
 
+    fn constant_u256(&mut self, value: U256) -> UInt256Target {
+        // each u64 word of `value` is split in 2 limbs, in little-endian order
+        UInt256Target(create_array(|i| {
+            self.constant_u32((value.0[i / 2] >> (32 * (i % 2))) as u32)
+        }))
+    }
+
     fn mul_u256(
         &mut self,
         left: &UInt256Target,
@@ -247,6 +427,29 @@ impl<F: SerializableRichField<D>, const D: usize> CircuitBuilderU256<F, D>
         (UInt256Target(res), overflow)
     }
 
+    fn exp_u256(&mut self, base: &UInt256Target, exponent: u32) -> (UInt256Target, BoolTarget) {
+        let _false = self._false();
+        if exponent == 0 {
+            return (self.constant_u256(U256::one()), _false);
+        }
+        // left-to-right square-and-multiply: each intermediate result is `base^k` for a
+        // prefix `k` of the bits of `exponent`, so it overflows only if the final power
+        // overflows too
+        let num_bits = u32::BITS - exponent.leading_zeros();
+        (0..num_bits - 1)
+            .rev()
+            .fold((base.clone(), _false), |(res, overflow), i| {
+                let (res, square_overflow) = self.mul_u256(&res, &res);
+                let overflow = self.or(overflow, square_overflow);
+                if (exponent >> i) & 1 == 1 {
+                    let (res, mul_overflow) = self.mul_u256(&res, base);
+                    (res, self.or(overflow, mul_overflow))
+                } else {
+                    (res, overflow)
+                }
+            })
+    }
+
     fn sub_u256(
         &mut self,
         left: &UInt256Target,
@@ -269,6 +472,24 @@ impl<F: SerializableRichField<D>, const D: usize> CircuitBuilderU256<F, D>
         (UInt256Target(res), borrow)
     }
 
+    fn saturating_sub_u256(
+        &mut self,
+        left: &UInt256Target,
+        right: &UInt256Target,
+    ) -> UInt256Target {
+        let (diff, borrow) = self.sub_u256(left, right);
+        // the borrow of the most significant limb is 1 iff `left < right`
+        let zero = self.zero_u256();
+        self.select_u256(BoolTarget::new_unsafe(borrow.0), &zero, &diff)
+    }
+
+    fn abs_diff_u256(&mut self, left: &UInt256Target, right: &UInt256Target) -> UInt256Target {
+        let is_less_than = self.is_less_than_u256(left, right);
+        let (left_minus_right, _) = self.sub_u256(left, right);
+        let (right_minus_left, _) = self.sub_u256(right, left);
+        self.select_u256(is_less_than, &right_minus_left, &left_minus_right)
+    }
+
     fn div_u256(
         &mut self,
         left: &UInt256Target,
@@ -304,6 +525,145 @@ impl<F: SerializableRichField<D>, const D: usize> CircuitBuilderU256<F, D>
         (quotient, remainder, is_zero)
     }
 
+    fn div_round_u256(
+        &mut self,
+        left: &UInt256Target,
+        right: &UInt256Target,
+    ) -> (UInt256Target, BoolTarget) {
+        let (quotient, remainder, is_zero) = self.div_u256(left, right);
+        // the quotient is rounded up iff remainder >= right/2, that is iff 2*remainder >= right;
+        // 2*remainder might overflow, in which case it is necessarily greater than right
+        let (double_remainder, carry) = self.add_u256(&remainder, &remainder);
+        let is_greater_or_equal = self.is_greater_or_equal_u256(&double_remainder, right);
+        let round_up = self.or(BoolTarget::new_unsafe(carry.0), is_greater_or_equal);
+        // no rounding in case of division by zero
+        let is_not_zero = self.not(is_zero);
+        let round_up = self.and(round_up, is_not_zero);
+        let mut rounding = self.zero_u256();
+        rounding.0[0] = U32Target(round_up.target);
+        // the quotient can't be the maximum value if rounded up, so the addition never overflows
+        let (rounded_quotient, _) = self.add_u256(&quotient, &rounding);
+
+        (rounded_quotient, is_zero)
+    }
+
+    fn add_mod(
+        &mut self,
+        left: &UInt256Target,
+        right: &UInt256Target,
+        modulus: &UInt256Target,
+    ) -> (UInt256Target, BoolTarget) {
+        // reduce `left` first, so that the quotient of `left + right` by `modulus` always
+        // fits in 256 bits
+        let (_, reduced_left, _) = self.div_u256(left, modulus);
+        // the sum is a 257-bit integer, whose most significant bit is the carry
+        let (low, carry) = self.add_u256(&reduced_left, right);
+        let mut high = self.zero_u256();
+        high.0[0] = carry;
+        mod_u512(self, &low, &high, modulus)
+    }
+
+    fn mul_mod(
+        &mut self,
+        left: &UInt256Target,
+        right: &UInt256Target,
+        modulus: &UInt256Target,
+    ) -> (UInt256Target, BoolTarget) {
+        // reduce `left` first, so that the quotient of `left * right` by `modulus` always
+        // fits in 256 bits
+        let (_, reduced_left, _) = self.div_u256(left, modulus);
+        let (low, high) = mul_u256_wide(self, &reduced_left, right);
+        mod_u512(self, &low, &high, modulus)
+    }
+
+    fn sum_u256(&mut self, values: &[UInt256Target]) -> (UInt256Target, BoolTarget) {
+        let zero = self.zero_u256();
+        let _false = self._false();
+        values
+            .iter()
+            .fold((zero, _false), |(sum, overflow), value| {
+                let (sum, carry) = self.add_u256(&sum, value);
+                // the carry of the addition of 2 operands is either 0 or 1
+                let overflow = self.or(overflow, BoolTarget::new_unsafe(carry.0));
+                (sum, overflow)
+            })
+    }
+
+    fn mean_u256(
+        &mut self,
+        values: &[UInt256Target],
+        count: Target,
+    ) -> (UInt256Target, BoolTarget) {
+        let (sum, overflow) = self.sum_u256(values);
+        self.range_check(count, 32);
+        let mut divisor = self.zero_u256();
+        divisor.0[0] = U32Target(count);
+        // the division by zero is not failing, so an empty set of values yields an undefined mean
+        let (mean, _, is_zero) = self.div_u256(&sum, &divisor);
+        let is_undefined = self.or(is_zero, overflow);
+        let is_defined = self.not(is_undefined);
+
+        (mean, is_defined)
+    }
+
+    fn to_bits_u256(&mut self, x: &UInt256Target) -> [BoolTarget; NUM_BITS] {
+        x.0.iter()
+            .flat_map(|limb| self.split_le(limb.0, 32))
+            .collect_vec()
+            .try_into()
+            .unwrap()
+    }
+
+    fn from_bits_u256(&mut self, bits: &[BoolTarget; NUM_BITS]) -> UInt256Target {
+        let limbs = bits
+            .chunks(32)
+            .map(|limb_bits| U32Target(self.le_sum(limb_bits.iter())))
+            .collect_vec();
+        UInt256Target(limbs.try_into().unwrap())
+    }
+
+    fn and_u256(&mut self, left: &UInt256Target, right: &UInt256Target) -> UInt256Target {
+        bitwise_u256(self, left, right, |b, l, r| b.and(l, r))
+    }
+
+    fn or_u256(&mut self, left: &UInt256Target, right: &UInt256Target) -> UInt256Target {
+        bitwise_u256(self, left, right, |b, l, r| b.or(l, r))
+    }
+
+    fn xor_u256(&mut self, left: &UInt256Target, right: &UInt256Target) -> UInt256Target {
+        bitwise_u256(self, left, right, xor_bool)
+    }
+
+    fn shl_u256(&mut self, x: &UInt256Target, shift: usize) -> UInt256Target {
+        let zero = U32Target(self.zero());
+        // shifts by whole limbs only move the limbs, without any decomposition
+        if shift % 32 == 0 {
+            let limb_shift = shift / 32;
+            return UInt256Target(create_array(|i| {
+                i.checked_sub(limb_shift).map_or(zero, |j| x.0[j])
+            }));
+        }
+        let bits = self.to_bits_u256(x);
+        let _false = self._false();
+        let shifted = create_array(|i| i.checked_sub(shift).map_or(_false, |j| bits[j]));
+        self.from_bits_u256(&shifted)
+    }
+
+    fn shr_u256(&mut self, x: &UInt256Target, shift: usize) -> UInt256Target {
+        let zero = U32Target(self.zero());
+        // shifts by whole limbs only move the limbs, without any decomposition
+        if shift % 32 == 0 {
+            let limb_shift = shift / 32;
+            return UInt256Target(create_array(|i| {
+                x.0.get(i + limb_shift).copied().unwrap_or(zero)
+            }));
+        }
+        let bits = self.to_bits_u256(x);
+        let _false = self._false();
+        let shifted = create_array(|i| bits.get(i + shift).copied().unwrap_or(_false));
+        self.from_bits_u256(&shifted)
+    }
+
     fn enforce_equal_u256(&mut self, left: &UInt256Target, right: &UInt256Target) {
         left.0
             .iter()
@@ -314,13 +674,13 @@ impl<F: SerializableRichField<D>, const D: usize> CircuitBuilderU256<F, D>
     }
 
     fn is_equal_u256(&mut self, left: &UInt256Target, right: &UInt256Target) -> BoolTarget {
-        let _false = self._false();
+        let _true = self._true();
         left.0
             .iter()
             .zip(right.0.iter())
-            .fold(_false, |is_eq, (left_limb, right_limb)| {
+            .fold(_true, |is_eq, (left_limb, right_limb)| {
                 let is_limb_equal = self.is_equal(left_limb.0, right_limb.0);
-                self.or(is_eq, is_limb_equal)
+                self.and(is_eq, is_limb_equal)
             })
     }
 
@@ -341,6 +701,30 @@ impl<F: SerializableRichField<D>, const D: usize> CircuitBuilderU256<F, D>
         let (_, borrow) = self.sub_u256(left, right);
         BoolTarget::new_unsafe(borrow.0)
     }
+
+    fn is_greater_or_equal_u256(
+        &mut self,
+        left: &UInt256Target,
+        right: &UInt256Target,
+    ) -> BoolTarget {
+        let is_less_than = self.is_less_than_u256(left, right);
+        self.not(is_less_than)
+    }
+
+    fn is_less_or_equal_u256(&mut self, left: &UInt256Target, right: &UInt256Target) -> BoolTarget {
+        self.is_greater_or_equal_u256(right, left)
+    }
+
+    fn min_u256(&mut self, left: &UInt256Target, right: &UInt256Target) -> UInt256Target {
+        let is_right_smaller = self.is_less_than_u256(right, left);
+        self.select_u256(is_right_smaller, right, left)
+    }
+
+    fn max_u256(&mut self, left: &UInt256Target, right: &UInt256Target) -> UInt256Target {
+        let is_right_greater = self.is_less_than_u256(left, right);
+        self.select_u256(is_right_greater, right, left)
+    }
+
     fn select_u256(
         &mut self,
         cond: BoolTarget,
@@ -350,6 +734,236 @@ impl<F: SerializableRichField<D>, const D: usize> CircuitBuilderU256<F, D>
         let limbs = create_array(|i| U32Target(self.select(cond, left.0[i].0, right.0[i].0)));
         UInt256Target(limbs)
     }
+
+    fn select_array_u256(&mut self, index: Target, values: &[UInt256Target]) -> UInt256Target {
+        // one-hot decomposition of `index`: exactly one flag must be set, which fails if
+        // `index` is out of range
+        let flags = (0..values.len())
+            .map(|i| {
+                let i = self.constant(F::from_canonical_usize(i));
+                self.is_equal(index, i)
+            })
+            .collect_vec();
+        let num_set_flags = self.add_many(flags.iter().map(|flag| flag.target));
+        let one = self.one();
+        self.connect(num_set_flags, one);
+        // sum the values masked by the flags, which yields only the selected one
+        let zero = self.zero();
+        let limbs = create_array(|i| {
+            let limb = flags.iter().zip(values).fold(zero, |acc, (flag, value)| {
+                self.mul_add(flag.target, value.0[i].0, acc)
+            });
+            U32Target(limb)
+        });
+        UInt256Target(limbs)
+    }
+}
+
+impl<F: SerializableRichField<D>, const D: usize> CircuitBuilderI256<F, D>
+    for CircuitBuilder<F, D>
+{
+    fn add_virtual_i256(&mut self) -> I256Target {
+        I256Target(self.add_virtual_u256())
+    }
+
+    fn register_public_input_i256(&mut self, target: &I256Target) {
+        self.register_public_input_u256(&target.0)
+    }
+
+    fn add_i256(&mut self, left: &I256Target, right: &I256Target) -> (I256Target, BoolTarget) {
+        // two's complement addition is the same as the unsigned one
+        let (res, _) = self.add_u256(&left.0, &right.0);
+        let res = I256Target(res);
+        // the addition overflows iff the operands have the same sign, and the sign of the
+        // result is different from such sign
+        let left_sign = self.is_negative(left);
+        let right_sign = self.is_negative(right);
+        let res_sign = self.is_negative(&res);
+        let different_operand_signs = xor_bool(self, left_sign, right_sign);
+        let same_operand_signs = self.not(different_operand_signs);
+        let sign_flipped = xor_bool(self, left_sign, res_sign);
+        let overflow = self.and(same_operand_signs, sign_flipped);
+
+        (res, overflow)
+    }
+
+    fn sub_i256(&mut self, left: &I256Target, right: &I256Target) -> (I256Target, BoolTarget) {
+        // two's complement subtraction is the same as the unsigned one
+        let (res, _) = self.sub_u256(&left.0, &right.0);
+        let res = I256Target(res);
+        // the subtraction overflows iff the operands have different signs, and the sign of
+        // the result is different from the sign of `left`
+        let left_sign = self.is_negative(left);
+        let right_sign = self.is_negative(right);
+        let res_sign = self.is_negative(&res);
+        let different_operand_signs = xor_bool(self, left_sign, right_sign);
+        let sign_flipped = xor_bool(self, left_sign, res_sign);
+        let overflow = self.and(different_operand_signs, sign_flipped);
+
+        (res, overflow)
+    }
+
+    fn is_negative(&mut self, target: &I256Target) -> BoolTarget {
+        // the sign is the most significant bit of the most significant limb; splitting the
+        // limb at 31 bits range-checks the high part to a single bit
+        let (_, sign) = self.split_low_high(target.0 .0[NUM_LIMBS - 1].0, 31, 32);
+        BoolTarget::new_unsafe(sign)
+    }
+
+    fn neg_i256(&mut self, target: &I256Target) -> (I256Target, BoolTarget) {
+        let zero = I256Target(self.zero_u256());
+        let (res, _) = self.sub_i256(&zero, target);
+        // -x has the same sign as a negative x only if x is `I256::MIN`, which is the only
+        // value whose negation overflows
+        let is_negative = self.is_negative(target);
+        let is_res_negative = self.is_negative(&res);
+        let overflow = self.and(is_negative, is_res_negative);
+
+        (res, overflow)
+    }
+}
+
+/// Compute the XOR of 2 `BoolTarget`s as `l + r - 2*l*r`
+fn xor_bool<F: SerializableRichField<D>, const D: usize>(
+    b: &mut CircuitBuilder<F, D>,
+    left: BoolTarget,
+    right: BoolTarget,
+) -> BoolTarget {
+    let sum = b.add(left.target, right.target);
+    BoolTarget::new_unsafe(b.arithmetic(-F::TWO, F::ONE, left.target, right.target, sum))
+}
+
+/// Lookup table containing all the `LOOKUP_BITS`-bit values, employed to range-check limbs. The table
+/// is built only once and shared among all the circuits, so that looking up whether it is
+/// already stored in a circuit builder is cheap
+fn range_check_table() -> LookupTable {
+    static TABLE: OnceLock<LookupTable> = OnceLock::new();
+    TABLE
+        .get_or_init(|| Arc::new((0..1 << LOOKUP_BITS).map(|v| (v, v)).collect()))
+        .clone()
+}
+
+/// Enforce that `limb` fits in 32 bits, by splitting it in `NUM_LOOKUP_CHUNKS` chunks of
+/// `LOOKUP_BITS` bits each, which are range-checked with lookups in the range-check table
+fn range_check_u32_with_lookup<F: SerializableRichField<D>, const D: usize>(
+    b: &mut CircuitBuilder<F, D>,
+    limb: Target,
+) {
+    let chunks = [0; NUM_LOOKUP_CHUNKS].map(|_| b.add_virtual_target());
+    b.add_simple_generator(LimbSplitGenerator { limb, chunks });
+    let table_index = b.add_lookup_table_from_pairs(range_check_table());
+    chunks.iter().for_each(|chunk| {
+        b.add_lookup_from_index(*chunk, table_index);
+    });
+    // recombine the chunks starting from the most significant one; since all the chunks are
+    // range-checked, the recombination can't overflow the field
+    let shift = F::from_canonical_u64(1 << LOOKUP_BITS);
+    let recombined = chunks
+        .iter()
+        .rev()
+        .skip(1)
+        .fold(chunks[NUM_LOOKUP_CHUNKS - 1], |acc, chunk| {
+            b.mul_const_add(shift, acc, *chunk)
+        });
+    b.connect(recombined, limb);
+}
+
+/// Apply the bitwise operator `op` to each pair of bits of `left` and `right`
+fn bitwise_u256<F: SerializableRichField<D>, const D: usize>(
+    b: &mut CircuitBuilder<F, D>,
+    left: &UInt256Target,
+    right: &UInt256Target,
+    op: impl Fn(&mut CircuitBuilder<F, D>, BoolTarget, BoolTarget) -> BoolTarget,
+) -> UInt256Target {
+    let left_bits = b.to_bits_u256(left);
+    let right_bits = b.to_bits_u256(right);
+    let bits = create_array(|i| op(b, left_bits[i], right_bits[i]));
+    b.from_bits_u256(&bits)
+}
+
+/// Multiply 2 UInt256Target without overflow, returning the least significant 256 bits and
+/// the most significant 256 bits of the 512-bit product
+fn mul_u256_wide<F: SerializableRichField<D>, const D: usize>(
+    b: &mut CircuitBuilder<F, D>,
+    left: &UInt256Target,
+    right: &UInt256Target,
+) -> (UInt256Target, UInt256Target) {
+    // we implement schoolbook multiplication over 32-bit limbs, as in `mul_u256`, but
+    // keeping all the `2*NUM_LIMBS` limbs of the product
+
+    // this vector stores the intermediate results to be added together for each limb
+    let mut tmp_res = vec![vec![]; 2 * NUM_LIMBS];
+    for i in 0..NUM_LIMBS {
+        let mut carry = b.zero_u32();
+        for j in 0..NUM_LIMBS {
+            let (res, next_carry) = b.mul_add_u32(left.0[j], right.0[i], carry);
+            tmp_res[i + j].push(res);
+            carry = next_carry;
+        }
+        tmp_res[i + NUM_LIMBS].push(carry);
+    }
+    // sum up the intermediate results for each limb, moving the carry to the next limb; the
+    // carry of the most significant limb is always zero, as the product fits in 512 bits
+    let mut limbs = Vec::with_capacity(2 * NUM_LIMBS);
+    for i in 0..2 * NUM_LIMBS {
+        let (res, carry) = b.add_many_u32(&tmp_res[i]);
+        if i + 1 < 2 * NUM_LIMBS {
+            tmp_res[i + 1].push(carry);
+        }
+        limbs.push(res);
+    }
+    let (low, high) = limbs.split_at(NUM_LIMBS);
+
+    (
+        UInt256Target(low.try_into().unwrap()),
+        UInt256Target(high.try_into().unwrap()),
+    )
+}
+
+/// Reduce the 512-bit integer `high*2^256 + low` modulo `modulus`, provided that the quotient
+/// fits in 256 bits; it returns the remainder and a flag specifying whether `modulus` is zero,
+/// in which case the remainder is zero
+fn mod_u512<F: SerializableRichField<D>, const D: usize>(
+    b: &mut CircuitBuilder<F, D>,
+    low: &UInt256Target,
+    high: &UInt256Target,
+    modulus: &UInt256Target,
+) -> (UInt256Target, BoolTarget) {
+    let _true = b._true();
+    let zero = b.zero();
+
+    let is_zero = b.is_zero(modulus);
+    let quotient = b.add_virtual_u256();
+    let remainder = b.add_virtual_u256();
+    b.add_simple_generator(UInt512ModGenerator {
+        low: low.clone(),
+        high: high.clone(),
+        modulus: modulus.clone(),
+        quotient: quotient.clone(),
+        remainder: remainder.clone(),
+    });
+    // enforce that remainder < modulus, if modulus != 0
+    let is_less_than = b.is_less_than_u256(&remainder, modulus);
+    let is_not_zero = b.not(is_zero);
+    b.connect(is_less_than.target, is_not_zero.target);
+    // compute quotient*modulus + remainder over 512 bits, ensuring no overflow occurs
+    let (prod_low, prod_high) = mul_u256_wide(b, &quotient, modulus);
+    let (computed_low, carry) = b.add_u256(&prod_low, &remainder);
+    let mut carry_u256 = b.zero_u256();
+    carry_u256.0[0] = carry;
+    let (computed_high, carry) = b.add_u256(&prod_high, &carry_u256);
+    b.connect(carry.0, zero);
+    // enforce that high*2^256 + low == quotient*modulus + remainder, if modulus != 0
+    let is_low_equal = b.is_equal_u256(low, &computed_low);
+    let is_high_equal = b.is_equal_u256(high, &computed_high);
+    let is_equal = b.and(is_low_equal, is_high_equal);
+    let is_valid = b.or(is_equal, is_zero);
+    b.connect(is_valid.target, _true.target);
+    // the remainder is unconstrained if modulus == 0, so we return zero in this case
+    let zero_u256 = b.zero_u256();
+    let remainder = b.select_u256(is_zero, &zero_u256, &remainder);
+
+    (remainder, is_zero)
 }
 
 impl<T: WitnessWrite<F>, F: RichField> WitnessWriteU256<F> for T {
@@ -367,17 +981,43 @@ impl<T: WitnessWrite<F>, F: RichField> WitnessWriteU256<F> for T {
 }
 
 impl<T: WitnessU32<F>, F: RichField> WitnessReadU256<F> for T {
-    fn get_u256_target(&self, target: &UInt256Target) -> U256 {
-        let bytes = target
-            .0
-            .iter()
-            .flat_map(|t| {
-                let (low, high) = self.get_u32_target(*t);
-                assert_eq!(high, 0); // check it is a 32-bit limb
-                low.to_le_bytes().to_vec()
-            })
-            .collect_vec();
-        U256::from_little_endian(&bytes)
+    fn try_get_u256_target(&self, target: &UInt256Target) -> Result<U256> {
+        let mut bytes = Vec::with_capacity(NUM_LIMBS * 4);
+        for (i, t) in target.0.iter().enumerate() {
+            let (low, high) = self.get_u32_target(*t);
+            // check it is a 32-bit limb
+            ensure!(
+                high == 0,
+                "limb {i} of u256 target is not a 32-bit value: high bits are {high}"
+            );
+            bytes.extend_from_slice(&low.to_le_bytes());
+        }
+        Ok(U256::from_little_endian(&bytes))
+    }
+}
+
+impl<T: WitnessWrite<F>, F: RichField> WitnessWriteI256<F> for T {
+    fn set_i256_target(&mut self, target: &I256Target, value: I256) {
+        self.set_u256_target(&target.0, value.into_raw())
+    }
+}
+
+impl<T: WitnessU32<F>, F: RichField> WitnessReadI256<F> for T {
+    fn get_i256_target(&self, target: &I256Target) -> I256 {
+        I256::from_raw(self.get_u256_target(&target.0))
+    }
+}
+
+impl I256Target {
+    /// Interpret the limbs of a `UInt256Target` as the two's complement representation of
+    /// a signed integer
+    pub fn from_unsigned(target: UInt256Target) -> Self {
+        I256Target(target)
+    }
+
+    /// Return the `UInt256Target` storing the two's complement representation of `self`
+    pub fn as_unsigned(&self) -> &UInt256Target {
+        &self.0
     }
 }
 
@@ -513,49 +1153,197 @@ impl<F: SerializableRichField<D>, const D: usize> SimpleGenerator<F, D> for UInt
     }
 }
 
-#[cfg(test)]
-mod tests {
+/// Generator employed to fill witness values needed for the reduction of a 512-bit integer
+/// modulo a UInt256Target
+#[derive(Clone, Debug, Default)]
+pub struct UInt512ModGenerator {
+    low: UInt256Target,
+    high: UInt256Target,
+    modulus: UInt256Target,
+    quotient: UInt256Target,
+    remainder: UInt256Target,
+}
 
-    use ethers::types::U256;
-    use mrp2_test_utils::circuit::{run_circuit, UserCircuit};
-    use plonky2::{
-        field::types::Field,
-        iop::witness::PartialWitness,
-        plonk::{
-            circuit_builder::CircuitBuilder,
-            circuit_data::{CircuitConfig, CircuitData},
-            config::PoseidonGoldilocksConfig,
-            proof::ProofWithPublicInputs,
-        },
-    };
-    use rand::{thread_rng, Rng};
-    use serde::{Deserialize, Serialize};
+impl<F: SerializableRichField<D>, const D: usize> SimpleGenerator<F, D> for UInt512ModGenerator {
+    fn id(&self) -> String {
+        "UInt512ModGenerator".to_string()
+    }
 
-    use crate::{
-        serialization::{deserialize, serialize},
-        types::GFp,
-        u256::NUM_LIMBS,
-        utils::convert_u32_fields_to_u256,
-    };
+    fn dependencies(&self) -> Vec<Target> {
+        [&self.low, &self.high, &self.modulus]
+            .into_iter()
+            .flat_map::<Vec<Target>, _>(|u256_t| u256_t.into())
+            .collect_vec()
+    }
 
-    use super::{CircuitBuilderU256, UInt256Target, WitnessWriteU256};
+    fn run_once(&self, witness: &PartitionWitness<F>, out_buffer: &mut GeneratedValues<F>) {
+        let low = witness.get_u256_target(&self.low);
+        let high = witness.get_u256_target(&self.high);
+        let modulus = witness.get_u256_target(&self.modulus);
 
-    const D: usize = 2;
-    type F = GFp;
-    type C = PoseidonGoldilocksConfig;
+        let (quotient, remainder) = if modulus.is_zero() {
+            (U256::zero(), U256::zero())
+        } else {
+            let value = (U512::from(high) << 256) + U512::from(low);
+            let (quotient, remainder) = value.div_mod(U512::from(modulus));
+            (
+                U256::try_from(quotient).expect("quotient doesn't fit in 256 bits"),
+                U256::try_from(remainder).unwrap(),
+            )
+        };
 
-    #[derive(Clone, Debug)]
-    struct TestOperationsCircuit {
-        left: U256,
-        right: U256,
+        out_buffer.set_u256_target(&self.quotient, quotient);
+        out_buffer.set_u256_target(&self.remainder, remainder);
     }
 
-    impl UserCircuit<F, D> for TestOperationsCircuit {
-        type Wires = (UInt256Target, UInt256Target);
+    fn serialize(&self, dst: &mut Vec<u8>, _common_data: &CommonCircuitData<F, D>) -> IoResult<()> {
+        self.low.write_to_bytes(dst);
+        self.high.write_to_bytes(dst);
+        self.modulus.write_to_bytes(dst);
+        self.quotient.write_to_bytes(dst);
+        self.remainder.write_to_bytes(dst);
 
-        fn build(c: &mut CircuitBuilder<F, D>) -> Self::Wires {
-            let left = c.add_virtual_u256_unsafe();
-            let right = c.add_virtual_u256_unsafe();
+        Ok(())
+    }
+
+    fn deserialize(src: &mut Buffer, _common_data: &CommonCircuitData<F, D>) -> IoResult<Self>
+    where
+        Self: Sized,
+    {
+        let low = UInt256Target::read_from_buffer(src)?;
+        let high = UInt256Target::read_from_buffer(src)?;
+        let modulus = UInt256Target::read_from_buffer(src)?;
+        let quotient = UInt256Target::read_from_buffer(src)?;
+        let remainder = UInt256Target::read_from_buffer(src)?;
+
+        Ok(Self {
+            low,
+            high,
+            modulus,
+            quotient,
+            remainder,
+        })
+    }
+}
+
+/// Generator employed to split a 32-bit limb in its `NUM_LOOKUP_CHUNKS` chunks of
+/// `LOOKUP_BITS` bits, from the least significant one
+#[derive(Clone, Debug, Default)]
+pub struct LimbSplitGenerator {
+    limb: Target,
+    chunks: [Target; NUM_LOOKUP_CHUNKS],
+}
+
+impl<F: SerializableRichField<D>, const D: usize> SimpleGenerator<F, D> for LimbSplitGenerator {
+    fn id(&self) -> String {
+        "LimbSplitGenerator".to_string()
+    }
+
+    fn dependencies(&self) -> Vec<Target> {
+        vec![self.limb]
+    }
+
+    fn run_once(&self, witness: &PartitionWitness<F>, out_buffer: &mut GeneratedValues<F>) {
+        let limb = witness.get_target(self.limb).to_canonical_u64();
+        let mask = (1 << LOOKUP_BITS) - 1;
+        self.chunks.iter().enumerate().for_each(|(i, chunk)| {
+            out_buffer.set_target(
+                *chunk,
+                F::from_canonical_u64((limb >> (i * LOOKUP_BITS)) & mask),
+            )
+        });
+    }
+
+    fn serialize(&self, dst: &mut Vec<u8>, _common_data: &CommonCircuitData<F, D>) -> IoResult<()> {
+        dst.write_target(self.limb)?;
+        self.chunks
+            .iter()
+            .try_for_each(|chunk| dst.write_target(*chunk))
+    }
+
+    fn deserialize(src: &mut Buffer, _common_data: &CommonCircuitData<F, D>) -> IoResult<Self>
+    where
+        Self: Sized,
+    {
+        let limb = src.read_target()?;
+        let mut chunks = [Target::default(); NUM_LOOKUP_CHUNKS];
+        for chunk in chunks.iter_mut() {
+            *chunk = src.read_target()?;
+        }
+
+        Ok(Self { limb, chunks })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use ethers::types::{I256, U256, U512};
+    use itertools::Itertools;
+    use mrp2_test_utils::circuit::{run_circuit, UserCircuit};
+    use plonky2::{
+        field::types::Field,
+        iop::{
+            target::Target,
+            witness::{PartialWitness, WitnessWrite},
+        },
+        plonk::{
+            circuit_builder::CircuitBuilder,
+            circuit_data::{CircuitConfig, CircuitData},
+            config::PoseidonGoldilocksConfig,
+            proof::ProofWithPublicInputs,
+        },
+    };
+    use rand::{thread_rng, Rng};
+    use serde::{Deserialize, Serialize};
+
+    use crate::{
+        serialization::{deserialize, serialize},
+        types::GFp,
+        u256::{NUM_BITS, NUM_LIMBS},
+        utils::{convert_u32_fields_to_u256, ToFields},
+    };
+
+    use super::{
+        CircuitBuilderI256, CircuitBuilderU256, I256Target, UInt256Target, WitnessReadU256,
+        WitnessWriteI256, WitnessWriteU256,
+    };
+
+    const D: usize = 2;
+    type F = GFp;
+    type C = PoseidonGoldilocksConfig;
+
+    /// Constant embedded in the circuit by `TestConstantCircuit`
+    fn test_constant() -> U256 {
+        U256::exp10(18) + U256::max_value() / 3
+    }
+
+    #[derive(Clone, Debug)]
+    struct TestConstantCircuit;
+
+    impl UserCircuit<F, D> for TestConstantCircuit {
+        type Wires = ();
+
+        fn build(c: &mut CircuitBuilder<F, D>) -> Self::Wires {
+            let constant = c.constant_u256(test_constant());
+            c.register_public_input_u256(&constant);
+        }
+
+        fn prove(&self, _pw: &mut PartialWitness<F>, _wires: &Self::Wires) {}
+    }
+
+    #[derive(Clone, Debug)]
+    struct TestOperationsCircuit {
+        left: U256,
+        right: U256,
+    }
+
+    impl UserCircuit<F, D> for TestOperationsCircuit {
+        type Wires = (UInt256Target, UInt256Target);
+
+        fn build(c: &mut CircuitBuilder<F, D>) -> Self::Wires {
+            let left = c.add_virtual_u256_unsafe();
+            let right = c.add_virtual_u256_unsafe();
             (left, right)
         }
 
@@ -584,6 +1372,31 @@ mod tests {
         }
     }
 
+    /// Number of terms summed by `TestAddManyCircuit`
+    const NUM_TERMS: usize = 16;
+
+    #[derive(Clone, Debug)]
+    struct TestAddManyCircuit([U256; NUM_TERMS]);
+
+    impl UserCircuit<F, D> for TestAddManyCircuit {
+        type Wires = [UInt256Target; NUM_TERMS];
+
+        fn build(c: &mut CircuitBuilder<F, D>) -> Self::Wires {
+            let terms = [0; NUM_TERMS].map(|_| c.add_virtual_u256_unsafe());
+            let (res, overflow) = c.add_many_u256(&terms);
+            c.register_public_input_u256(&res);
+            c.register_public_input(overflow.target);
+            terms
+        }
+
+        fn prove(&self, pw: &mut PartialWitness<F>, wires: &Self::Wires) {
+            wires
+                .iter()
+                .zip(self.0)
+                .for_each(|(t, v)| pw.set_u256_target(t, v))
+        }
+    }
+
     #[derive(Clone, Debug)]
     struct TestSubCircuit(TestOperationsCircuit);
 
@@ -603,6 +1416,42 @@ mod tests {
         }
     }
 
+    #[derive(Clone, Debug)]
+    struct TestSaturatingSubCircuit(TestOperationsCircuit);
+
+    impl UserCircuit<F, D> for TestSaturatingSubCircuit {
+        type Wires = <TestOperationsCircuit as UserCircuit<F, D>>::Wires;
+
+        fn build(c: &mut CircuitBuilder<F, D>) -> Self::Wires {
+            let (left, right) = TestOperationsCircuit::build(c);
+            let res = c.saturating_sub_u256(&left, &right);
+            c.register_public_input_u256(&res);
+            (left, right)
+        }
+
+        fn prove(&self, pw: &mut PartialWitness<F>, wires: &Self::Wires) {
+            self.0.prove(pw, wires)
+        }
+    }
+
+    #[derive(Clone, Debug)]
+    struct TestAbsDiffCircuit(TestOperationsCircuit);
+
+    impl UserCircuit<F, D> for TestAbsDiffCircuit {
+        type Wires = <TestOperationsCircuit as UserCircuit<F, D>>::Wires;
+
+        fn build(c: &mut CircuitBuilder<F, D>) -> Self::Wires {
+            let (left, right) = TestOperationsCircuit::build(c);
+            let res = c.abs_diff_u256(&left, &right);
+            c.register_public_input_u256(&res);
+            (left, right)
+        }
+
+        fn prove(&self, pw: &mut PartialWitness<F>, wires: &Self::Wires) {
+            self.0.prove(pw, wires)
+        }
+    }
+
     #[derive(Clone, Debug)]
     struct TestMulCircuit(TestOperationsCircuit);
 
@@ -622,6 +1471,125 @@ mod tests {
         }
     }
 
+    #[derive(Clone, Debug)]
+    struct TestExpCircuit<const EXP: u32>(U256);
+
+    impl<const EXP: u32> UserCircuit<F, D> for TestExpCircuit<EXP> {
+        type Wires = UInt256Target;
+
+        fn build(c: &mut CircuitBuilder<F, D>) -> Self::Wires {
+            let base = c.add_virtual_u256_unsafe();
+            let (res, overflow) = c.exp_u256(&base, EXP);
+            c.register_public_input_u256(&res);
+            c.register_public_input(overflow.target);
+            base
+        }
+
+        fn prove(&self, pw: &mut PartialWitness<F>, wires: &Self::Wires) {
+            pw.set_u256_target(wires, self.0)
+        }
+    }
+
+    #[derive(Clone, Debug)]
+    struct TestBitsCircuit(U256);
+
+    impl UserCircuit<F, D> for TestBitsCircuit {
+        type Wires = UInt256Target;
+
+        fn build(c: &mut CircuitBuilder<F, D>) -> Self::Wires {
+            let x = c.add_virtual_u256();
+            let bits = c.to_bits_u256(&x);
+            let recomposed = c.from_bits_u256(&bits);
+            c.enforce_equal_u256(&x, &recomposed);
+            bits.iter()
+                .for_each(|bit| c.register_public_input(bit.target));
+            x
+        }
+
+        fn prove(&self, pw: &mut PartialWitness<F>, wires: &Self::Wires) {
+            pw.set_u256_target(wires, self.0)
+        }
+    }
+
+    #[derive(Clone, Debug)]
+    struct TestBitwiseCircuit(TestOperationsCircuit);
+
+    impl UserCircuit<F, D> for TestBitwiseCircuit {
+        type Wires = <TestOperationsCircuit as UserCircuit<F, D>>::Wires;
+
+        fn build(c: &mut CircuitBuilder<F, D>) -> Self::Wires {
+            let (left, right) = TestOperationsCircuit::build(c);
+            let and = c.and_u256(&left, &right);
+            let or = c.or_u256(&left, &right);
+            let xor = c.xor_u256(&left, &right);
+            c.register_public_input_u256(&and);
+            c.register_public_input_u256(&or);
+            c.register_public_input_u256(&xor);
+            (left, right)
+        }
+
+        fn prove(&self, pw: &mut PartialWitness<F>, wires: &Self::Wires) {
+            self.0.prove(pw, wires)
+        }
+    }
+
+    /// Maximum shift checked by `TestShiftCircuit`
+    const MAX_SHIFT: usize = 260;
+
+    #[derive(Clone, Debug)]
+    struct TestShiftCircuit(U256);
+
+    impl UserCircuit<F, D> for TestShiftCircuit {
+        type Wires = UInt256Target;
+
+        fn build(c: &mut CircuitBuilder<F, D>) -> Self::Wires {
+            let x = c.add_virtual_u256();
+            for shift in 0..=MAX_SHIFT {
+                let shl = c.shl_u256(&x, shift);
+                let shr = c.shr_u256(&x, shift);
+                c.register_public_input_u256(&shl);
+                c.register_public_input_u256(&shr);
+            }
+            x
+        }
+
+        fn prove(&self, pw: &mut PartialWitness<F>, wires: &Self::Wires) {
+            pw.set_u256_target(wires, self.0)
+        }
+    }
+
+    #[derive(Clone, Debug)]
+    struct TestI256Circuit {
+        left: I256,
+        right: I256,
+    }
+
+    impl UserCircuit<F, D> for TestI256Circuit {
+        type Wires = (I256Target, I256Target);
+
+        fn build(c: &mut CircuitBuilder<F, D>) -> Self::Wires {
+            let left = c.add_virtual_i256();
+            let right = c.add_virtual_i256();
+            let (sum, add_overflow) = c.add_i256(&left, &right);
+            let (diff, sub_overflow) = c.sub_i256(&left, &right);
+            let (neg, neg_overflow) = c.neg_i256(&left);
+            let is_negative = c.is_negative(&left);
+            c.register_public_input_i256(&sum);
+            c.register_public_input(add_overflow.target);
+            c.register_public_input_i256(&diff);
+            c.register_public_input(sub_overflow.target);
+            c.register_public_input_i256(&neg);
+            c.register_public_input(neg_overflow.target);
+            c.register_public_input(is_negative.target);
+            (left, right)
+        }
+
+        fn prove(&self, pw: &mut PartialWitness<F>, wires: &Self::Wires) {
+            pw.set_i256_target(&wires.0, self.left);
+            pw.set_i256_target(&wires.1, self.right);
+        }
+    }
+
     #[derive(Clone, Debug)]
     struct TestDivCircuit(TestOperationsCircuit);
 
@@ -642,6 +1610,118 @@ mod tests {
         }
     }
 
+    #[derive(Clone, Debug)]
+    struct TestDivRoundCircuit(TestOperationsCircuit);
+
+    impl UserCircuit<F, D> for TestDivRoundCircuit {
+        type Wires = <TestOperationsCircuit as UserCircuit<F, D>>::Wires;
+
+        fn build(c: &mut CircuitBuilder<F, D>) -> Self::Wires {
+            let (left, right) = TestOperationsCircuit::build(c);
+            let (quotient, div_zero) = c.div_round_u256(&left, &right);
+            c.register_public_input_u256(&quotient);
+            c.register_public_input(div_zero.target);
+            (left, right)
+        }
+
+        fn prove(&self, pw: &mut PartialWitness<F>, wires: &Self::Wires) {
+            self.0.prove(pw, wires)
+        }
+    }
+
+    #[derive(Clone, Debug)]
+    struct TestModOperationsCircuit {
+        left: U256,
+        right: U256,
+        modulus: U256,
+    }
+
+    impl UserCircuit<F, D> for TestModOperationsCircuit {
+        type Wires = (UInt256Target, UInt256Target, UInt256Target);
+
+        fn build(c: &mut CircuitBuilder<F, D>) -> Self::Wires {
+            let left = c.add_virtual_u256_unsafe();
+            let right = c.add_virtual_u256_unsafe();
+            let modulus = c.add_virtual_u256_unsafe();
+            (left, right, modulus)
+        }
+
+        fn prove(&self, pw: &mut PartialWitness<F>, wires: &Self::Wires) {
+            pw.set_u256_target(&wires.0, self.left);
+            pw.set_u256_target(&wires.1, self.right);
+            pw.set_u256_target(&wires.2, self.modulus);
+        }
+    }
+
+    #[derive(Clone, Debug)]
+    struct TestAddModCircuit(TestModOperationsCircuit);
+
+    impl UserCircuit<F, D> for TestAddModCircuit {
+        type Wires = <TestModOperationsCircuit as UserCircuit<F, D>>::Wires;
+
+        fn build(c: &mut CircuitBuilder<F, D>) -> Self::Wires {
+            let (left, right, modulus) = TestModOperationsCircuit::build(c);
+            let (res, mod_zero) = c.add_mod(&left, &right, &modulus);
+            c.register_public_input_u256(&res);
+            c.register_public_input(mod_zero.target);
+            (left, right, modulus)
+        }
+
+        fn prove(&self, pw: &mut PartialWitness<F>, wires: &Self::Wires) {
+            self.0.prove(pw, wires)
+        }
+    }
+
+    #[derive(Clone, Debug)]
+    struct TestMulModCircuit(TestModOperationsCircuit);
+
+    impl UserCircuit<F, D> for TestMulModCircuit {
+        type Wires = <TestModOperationsCircuit as UserCircuit<F, D>>::Wires;
+
+        fn build(c: &mut CircuitBuilder<F, D>) -> Self::Wires {
+            let (left, right, modulus) = TestModOperationsCircuit::build(c);
+            let (res, mod_zero) = c.mul_mod(&left, &right, &modulus);
+            c.register_public_input_u256(&res);
+            c.register_public_input(mod_zero.target);
+            (left, right, modulus)
+        }
+
+        fn prove(&self, pw: &mut PartialWitness<F>, wires: &Self::Wires) {
+            self.0.prove(pw, wires)
+        }
+    }
+
+    #[derive(Clone, Debug)]
+    struct TestMeanCircuit<const N: usize> {
+        values: [U256; N],
+        count: u32,
+    }
+
+    impl<const N: usize> UserCircuit<F, D> for TestMeanCircuit<N> {
+        type Wires = ([UInt256Target; N], Target);
+
+        fn build(c: &mut CircuitBuilder<F, D>) -> Self::Wires {
+            let values = [0; N].map(|_| c.add_virtual_u256_unsafe());
+            let count = c.add_virtual_target();
+            let (sum, overflow) = c.sum_u256(&values);
+            c.register_public_input_u256(&sum);
+            c.register_public_input(overflow.target);
+            let (mean, is_defined) = c.mean_u256(&values, count);
+            c.register_public_input_u256(&mean);
+            c.register_public_input(is_defined.target);
+            (values, count)
+        }
+
+        fn prove(&self, pw: &mut PartialWitness<F>, wires: &Self::Wires) {
+            wires
+                .0
+                .iter()
+                .zip(self.values.iter())
+                .for_each(|(target, value)| pw.set_u256_target(target, *value));
+            pw.set_target(wires.1, F::from_canonical_u32(self.count));
+        }
+    }
+
     #[derive(Clone, Debug)]
     struct TestEqCircuit(TestOperationsCircuit);
 
@@ -670,6 +1750,30 @@ mod tests {
             let (left, right) = TestOperationsCircuit::build(c);
             let is_eq = c.is_less_than_u256(&left, &right);
             c.register_public_input(is_eq.target);
+            let is_ge = c.is_greater_or_equal_u256(&left, &right);
+            c.register_public_input(is_ge.target);
+            let is_le = c.is_less_or_equal_u256(&left, &right);
+            c.register_public_input(is_le.target);
+            (left, right)
+        }
+
+        fn prove(&self, pw: &mut PartialWitness<F>, wires: &Self::Wires) {
+            self.0.prove(pw, wires)
+        }
+    }
+
+    #[derive(Clone, Debug)]
+    struct TestMinMaxCircuit(TestOperationsCircuit);
+
+    impl UserCircuit<F, D> for TestMinMaxCircuit {
+        type Wires = <TestOperationsCircuit as UserCircuit<F, D>>::Wires;
+
+        fn build(c: &mut CircuitBuilder<F, D>) -> Self::Wires {
+            let (left, right) = TestOperationsCircuit::build(c);
+            let min = c.min_u256(&left, &right);
+            let max = c.max_u256(&left, &right);
+            c.register_public_input_u256(&min);
+            c.register_public_input_u256(&max);
             (left, right)
         }
 
@@ -678,6 +1782,36 @@ mod tests {
         }
     }
 
+    /// Number of values among which `TestSelectArrayCircuit` selects
+    const NUM_SELECTABLE: usize = 5;
+
+    #[derive(Clone, Debug)]
+    struct TestSelectArrayCircuit {
+        values: [U256; NUM_SELECTABLE],
+        index: usize,
+    }
+
+    impl UserCircuit<F, D> for TestSelectArrayCircuit {
+        type Wires = ([UInt256Target; NUM_SELECTABLE], Target);
+
+        fn build(c: &mut CircuitBuilder<F, D>) -> Self::Wires {
+            let values = [0; NUM_SELECTABLE].map(|_| c.add_virtual_u256());
+            let index = c.add_virtual_target();
+            let selected = c.select_array_u256(index, &values);
+            c.register_public_input_u256(&selected);
+            (values, index)
+        }
+
+        fn prove(&self, pw: &mut PartialWitness<F>, wires: &Self::Wires) {
+            wires
+                .0
+                .iter()
+                .zip(self.values)
+                .for_each(|(t, v)| pw.set_u256_target(t, v));
+            pw.set_target(wires.1, F::from_canonical_usize(self.index));
+        }
+    }
+
     #[derive(Clone, Debug)]
     struct TestIsZeroCircuit(U256);
 
@@ -732,6 +1866,17 @@ mod tests {
         U256::from_little_endian(bytes.as_slice())
     }
 
+    #[test]
+    fn test_u256_constant() {
+        let proof = run_circuit::<F, D, C, _>(TestConstantCircuit);
+        assert_eq!(
+            convert_u32_fields_to_u256(&proof.public_inputs),
+            test_constant()
+        );
+        // the limbs are the little-endian 32-bit words of the constant
+        assert_eq!(proof.public_inputs, test_constant().to_fields::<F>());
+    }
+
     #[test]
     fn test_u256_add() {
         let rng = &mut thread_rng();
@@ -760,6 +1905,33 @@ mod tests {
         check_result(res, carry, &proof, "double");
     }
 
+    #[test]
+    fn test_u256_add_many() {
+        let rng = &mut thread_rng();
+        // random terms most likely overflow, while terms of 248 bits can't
+        let random_terms = [0; NUM_TERMS].map(|_| gen_random_u256(rng));
+        let small_terms = random_terms.map(|v| v >> 8);
+        // only the last addition overflows
+        let mut last_overflow_terms = [U256::zero(); NUM_TERMS];
+        last_overflow_terms[0] = U256::MAX;
+        last_overflow_terms[NUM_TERMS - 1] = U256::one();
+        for (terms, test_case) in [
+            (random_terms, "random terms"),
+            (small_terms, "small terms"),
+            (last_overflow_terms, "last addition overflow"),
+        ] {
+            let proof = run_circuit::<F, D, C, _>(TestAddManyCircuit(terms));
+            let (res, overflow) =
+                terms
+                    .iter()
+                    .fold((U256::zero(), false), |(sum, overflow), term| {
+                        let (res, carry) = sum.overflowing_add(*term);
+                        (res, overflow || carry)
+                    });
+            check_result(res, overflow, &proof, test_case);
+        }
+    }
+
     #[test]
     fn test_u256_sub() {
         let rng = &mut thread_rng();
@@ -798,6 +1970,104 @@ mod tests {
         check_result(res, true, &proof, "negation");
     }
 
+    #[test]
+    fn test_u256_saturating_sub() {
+        let check_saturating_sub_result =
+            |result: U256, proof: &ProofWithPublicInputs<F, C, D>, test_case: &str| {
+                let proven_res = convert_u32_fields_to_u256(&proof.public_inputs[..NUM_LIMBS]);
+                assert_eq!(
+                    result, proven_res,
+                    "result not correct for test: {}",
+                    test_case
+                );
+            };
+
+        let rng = &mut thread_rng();
+        // generate left and right operand for sub, with left >= right
+        let left = gen_random_u256(rng);
+        let right = gen_random_u256(rng);
+        let (left, right) = if left >= right {
+            (left, right)
+        } else {
+            (right, left)
+        };
+        let circuit = TestSaturatingSubCircuit(TestOperationsCircuit { left, right });
+        let proof = run_circuit::<F, D, C, _>(circuit);
+        check_saturating_sub_result(left - right, &proof, "saturating sub");
+
+        // test underflow is floored at zero
+        let circuit = TestSaturatingSubCircuit(TestOperationsCircuit {
+            left: right,
+            right: left,
+        });
+        let proof = run_circuit::<F, D, C, _>(circuit);
+        check_saturating_sub_result(U256::zero(), &proof, "saturating sub underflow");
+
+        // test underflow by one
+        let circuit = TestSaturatingSubCircuit(TestOperationsCircuit {
+            left: U256::zero(),
+            right: U256::one(),
+        });
+        let proof = run_circuit::<F, D, C, _>(circuit);
+        check_saturating_sub_result(U256::zero(), &proof, "saturating sub underflow by one");
+
+        // test subtraction by itself
+        let circuit = TestSaturatingSubCircuit(TestOperationsCircuit { left, right: left });
+        let proof = run_circuit::<F, D, C, _>(circuit);
+        check_saturating_sub_result(U256::zero(), &proof, "saturating sub by itself");
+    }
+
+    #[test]
+    fn test_u256_abs_diff() {
+        let check_abs_diff_result =
+            |result: U256, proof: &ProofWithPublicInputs<F, C, D>, test_case: &str| {
+                let proven_res = convert_u32_fields_to_u256(&proof.public_inputs[..NUM_LIMBS]);
+                assert_eq!(
+                    result, proven_res,
+                    "result not correct for test: {}",
+                    test_case
+                );
+            };
+
+        let rng = &mut thread_rng();
+        let left = gen_random_u256(rng);
+        let right = gen_random_u256(rng);
+        let (greater, smaller) = if left >= right {
+            (left, right)
+        } else {
+            (right, left)
+        };
+
+        // test left > right
+        let circuit = TestAbsDiffCircuit(TestOperationsCircuit {
+            left: greater,
+            right: smaller,
+        });
+        let proof = run_circuit::<F, D, C, _>(circuit);
+        check_abs_diff_result(greater - smaller, &proof, "abs diff left > right");
+
+        // test left < right
+        let circuit = TestAbsDiffCircuit(TestOperationsCircuit {
+            left: smaller,
+            right: greater,
+        });
+        let proof = run_circuit::<F, D, C, _>(circuit);
+        check_abs_diff_result(greater - smaller, &proof, "abs diff left < right");
+
+        // test left == right
+        let circuit = TestAbsDiffCircuit(TestOperationsCircuit { left, right: left });
+        let proof = run_circuit::<F, D, C, _>(circuit);
+        check_abs_diff_result(U256::zero(), &proof, "abs diff left == right");
+
+        // test the whole range of uint256
+        let circuit = TestAbsDiffCircuit(TestOperationsCircuit {
+            left: U256::zero(),
+            right: U256::max_value(),
+        });
+        let proof = run_circuit::<F, D, C, _>(circuit);
+        check_abs_diff_result(U256::max_value(), &proof, "abs diff full range");
+    }
+
     #[test]
     fn test_u256_mul() {
         let rng = &mut thread_rng();
@@ -841,6 +2111,130 @@ mod tests {
         check_result(res, overflow, &proof, "mul no overflow");
     }
 
+    #[test]
+    fn test_u256_exp() {
+        let rng = &mut thread_rng();
+        let base = gen_random_u256(rng);
+
+        // exponent 0 always yields 1, without overflow
+        let proof = run_circuit::<F, D, C, _>(TestExpCircuit::<0>(base));
+        check_result(U256::one(), false, &proof, "exp 0");
+        // exponent 1 yields the base unchanged
+        let proof = run_circuit::<F, D, C, _>(TestExpCircuit::<1>(base));
+        check_result(base, false, &proof, "exp 1");
+
+        // no overflow: a 24-bit base raised to 10 fits in 240 bits
+        let small_base = U256::from(rng.gen::<u32>() >> 8);
+        let proof = run_circuit::<F, D, C, _>(TestExpCircuit::<10>(small_base));
+        check_result(small_base.pow(U256::from(10)), false, &proof, "exp 10");
+
+        // overflow mid-way: 2^64 raised to 5 overflows while squaring 2^128
+        let large_base = U256::one() << 64;
+        let proof = run_circuit::<F, D, C, _>(TestExpCircuit::<5>(large_base));
+        let (res, overflow) = large_base.overflowing_pow(U256::from(5));
+        assert!(overflow);
+        check_result(res, overflow, &proof, "exp 5 overflow");
+
+        // a random base raised to 13 most likely overflows
+        let proof = run_circuit::<F, D, C, _>(TestExpCircuit::<13>(base));
+        let (res, overflow) = base.overflowing_pow(U256::from(13));
+        check_result(res, overflow, &proof, "exp 13");
+    }
+
+    #[test]
+    fn test_u256_bits() {
+        let rng = &mut thread_rng();
+        for x in [
+            gen_random_u256(rng),
+            gen_random_u256(rng),
+            U256::zero(),
+            U256::MAX,
+        ] {
+            let proof = run_circuit::<F, D, C, _>(TestBitsCircuit(x));
+            let expected_bits = (0..NUM_BITS).map(|i| F::from_bool(x.bit(i))).collect_vec();
+            assert_eq!(proof.public_inputs, expected_bits, "wrong bits of {x}");
+        }
+    }
+
+    #[test]
+    fn test_u256_bitwise() {
+        let rng = &mut thread_rng();
+        let random = gen_random_u256(rng);
+        for (left, right) in [
+            (random, gen_random_u256(rng)),
+            (random, U256::zero()),
+            (random, U256::MAX),
+            (U256::zero(), U256::zero()),
+            (U256::MAX, U256::MAX),
+        ] {
+            let circuit = TestBitwiseCircuit(TestOperationsCircuit { left, right });
+            let proof = run_circuit::<F, D, C, _>(circuit);
+            let results = proof
+                .public_inputs
+                .chunks(NUM_LIMBS)
+                .map(convert_u32_fields_to_u256)
+                .collect_vec();
+            assert_eq!(results, vec![left & right, left | right, left ^ right]);
+        }
+    }
+
+    #[test]
+    fn test_u256_shift() {
+        let rng = &mut thread_rng();
+        for x in [gen_random_u256(rng), U256::MAX] {
+            let proof = run_circuit::<F, D, C, _>(TestShiftCircuit(x));
+            let results = proof
+                .public_inputs
+                .chunks(NUM_LIMBS)
+                .map(convert_u32_fields_to_u256)
+                .collect_vec();
+            for shift in 0..=MAX_SHIFT {
+                // `U256` shifts by 256 bits or more already yield zero
+                assert_eq!(results[2 * shift], x << shift, "wrong shl by {shift}");
+                assert_eq!(results[2 * shift + 1], x >> shift, "wrong shr by {shift}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_i256_operations() {
+        let rng = &mut thread_rng();
+        let random = I256::from_raw(gen_random_u256(rng));
+        for (left, right) in [
+            (random, I256::from_raw(gen_random_u256(rng))),
+            (random, I256::zero()),
+            (random.overflowing_neg().0, random),
+            // sign overflow in both directions
+            (I256::MAX, I256::one()),
+            (I256::MIN, I256::one()),
+            (I256::MIN, I256::minus_one()),
+            // the negation of `I256::MIN` overflows
+            (I256::MIN, I256::zero()),
+            (I256::zero(), I256::MIN),
+        ] {
+            let proof = run_circuit::<F, D, C, _>(TestI256Circuit { left, right });
+            let results = proof
+                .public_inputs
+                .chunks(NUM_LIMBS + 1)
+                .take(3)
+                .map(|chunk| {
+                    let res = I256::from_raw(convert_u32_fields_to_u256(&chunk[..NUM_LIMBS]));
+                    (res, chunk[NUM_LIMBS] == F::ONE)
+                })
+                .collect_vec();
+            let test_case = format!("left = {left}, right = {right}");
+            assert_eq!(results[0], left.overflowing_add(right), "add: {test_case}");
+            assert_eq!(results[1], left.overflowing_sub(right), "sub: {test_case}");
+            assert_eq!(results[2], left.overflowing_neg(), "neg: {test_case}");
+            // the last chunk only contains the sign flag
+            assert_eq!(
+                proof.public_inputs.last().unwrap(),
+                &F::from_bool(left.is_negative()),
+                "is_negative: {test_case}"
+            );
+        }
+    }
+
     #[test]
     fn test_u256_div() {
         // function to check the correctness of division results
@@ -919,6 +2313,169 @@ mod tests {
         check_div_result(left, U256::zero(), false, &proof, "div after mul");
     }
 
+    #[test]
+    fn test_u256_div_round() {
+        // reference computed over 512 bits, so that `left + right/2` can't overflow
+        let div_round = |left: U256, right: U256| {
+            let (left, right) = (U512::from(left), U512::from(right));
+            U256::try_from((left + right / 2) / right).unwrap()
+        };
+        let check_div_round = |left: U256, right: U256, test_case: &str| {
+            let circuit = TestDivRoundCircuit(TestOperationsCircuit { left, right });
+            let proof = run_circuit::<F, D, C, _>(circuit);
+            let expected = if right.is_zero() {
+                U256::zero()
+            } else {
+                div_round(left, right)
+            };
+            check_result(expected, right.is_zero(), &proof, test_case);
+        };
+
+        let rng = &mut thread_rng();
+        let left = gen_random_u256(rng);
+        let right = U256::from(rng.gen::<u128>());
+        check_div_round(left, right, "div round");
+
+        // exact division
+        let quotient = U256::from(rng.gen::<u128>());
+        let divisor = U256::from(rng.gen::<u64>()) * 2 + 1;
+        check_div_round(quotient * divisor, divisor, "exact");
+        // remainder right below half of an odd divisor is rounded down
+        let half = divisor / 2;
+        check_div_round(quotient * divisor + half, divisor, "round down");
+        // remainder right above half of an odd divisor is rounded up
+        check_div_round(quotient * divisor + half + 1, divisor, "round up");
+        // remainder exactly half of an even divisor is rounded up
+        let divisor = divisor + 1;
+        check_div_round(quotient * divisor + divisor / 2, divisor, "round half up");
+        check_div_round(
+            quotient * divisor + divisor / 2 - 1,
+            divisor,
+            "round below half down",
+        );
+        // `left + right/2` would overflow 256 bits
+        check_div_round(U256::max_value(), U256::from(2), "overflow safe");
+        // `2*remainder` would overflow 256 bits
+        check_div_round(
+            U256::max_value() - 1,
+            U256::max_value(),
+            "overflow safe remainder",
+        );
+        // division by 0
+        check_div_round(left, U256::zero(), "div round by 0");
+    }
+
+    #[test]
+    fn test_u256_add_mod_and_mul_mod() {
+        let rng = &mut thread_rng();
+        // compute the expected result over 512 bits, being zero if modulus is zero
+        let expected = |value: U512, modulus: U256| {
+            value
+                .checked_rem(U512::from(modulus))
+                .map(|res| U256::try_from(res).unwrap())
+                .unwrap_or_default()
+        };
+        let left = gen_random_u256(rng);
+        let right = gen_random_u256(rng);
+        for modulus in [
+            gen_random_u256(rng),
+            U256::from(rng.gen::<u64>()),
+            U256::max_value(),
+            U256::one(),
+            U256::zero(),
+        ] {
+            let inputs = TestModOperationsCircuit {
+                left,
+                right,
+                modulus,
+            };
+            // the sum and the product of random operands most likely exceed 2^256
+            let proof = run_circuit::<F, D, C, _>(TestAddModCircuit(inputs.clone()));
+            let sum = U512::from(left) + U512::from(right);
+            check_result(expected(sum, modulus), modulus.is_zero(), &proof, "add mod");
+
+            let proof = run_circuit::<F, D, C, _>(TestMulModCircuit(inputs));
+            let prod = U512::from(left) * U512::from(right);
+            check_result(
+                expected(prod, modulus),
+                modulus.is_zero(),
+                &proof,
+                "mul mod",
+            );
+        }
+
+        // operands smaller than the modulus, whose product doesn't exceed 2^256
+        let left = U256::from(rng.gen::<u64>());
+        let right = U256::from(rng.gen::<u64>());
+        let modulus = U256::from(rng.gen::<u128>());
+        let inputs = TestModOperationsCircuit {
+            left,
+            right,
+            modulus,
+        };
+        let proof = run_circuit::<F, D, C, _>(TestMulModCircuit(inputs));
+        check_result(
+            (left * right).checked_rem(modulus).unwrap(),
+            false,
+            &proof,
+            "mul mod no overflow",
+        );
+    }
+
+    #[test]
+    fn test_u256_mean() {
+        fn check_mean<const N: usize>(
+            values: [U256; N],
+            count: u32,
+            expected: Option<U256>,
+            test_case: &str,
+        ) {
+            let proof = run_circuit::<F, D, C, _>(TestMeanCircuit { values, count });
+            let (sum, overflow) =
+                values
+                    .iter()
+                    .fold((U256::zero(), false), |(sum, overflow), value| {
+                        let (sum, carry) = sum.overflowing_add(*value);
+                        (sum, overflow || carry)
+                    });
+            check_result(sum, overflow, &proof, test_case);
+            let proven_mean =
+                convert_u32_fields_to_u256(&proof.public_inputs[NUM_LIMBS + 1..2 * NUM_LIMBS + 1]);
+            assert_eq!(
+                F::from_bool(expected.is_some()),
+                proof.public_inputs[2 * NUM_LIMBS + 1],
+                "defined flag not correct for test: {}",
+                test_case
+            );
+            if let Some(mean) = expected {
+                assert_eq!(
+                    mean, proven_mean,
+                    "mean not correct for test: {}",
+                    test_case
+                );
+            }
+        }
+
+        let rng = &mut thread_rng();
+        let values = [0; 4].map(|_| U256::from(rng.gen::<u128>()));
+        let sum = values.iter().fold(U256::zero(), |sum, value| sum + value);
+        check_mean(values, 4, Some(sum / 4), "mean");
+        // trailing values not accounted in count are zero
+        let padded_values = [values[0], values[1], U256::zero(), U256::zero()];
+        check_mean(
+            padded_values,
+            2,
+            Some((values[0] + values[1]) / 2),
+            "padded mean",
+        );
+        check_mean([values[0]], 1, Some(values[0]), "single value");
+        // the mean of an empty set of values is undefined
+        check_mean([], 0, None, "empty");
+        check_mean([U256::zero(); 4], 0, None, "zero count");
+        // the mean is undefined if the sum overflows
+        check_mean([U256::max_value(), U256::one()], 2, None, "overflow");
+    }
+
     #[test]
     fn test_u256_eq() {
         let rng = &mut thread_rng();
@@ -939,6 +2496,16 @@ mod tests {
         assert_eq!(F::ONE, proof.public_inputs[0]);
     }
 
+    #[test]
+    fn test_u256_eq_differing_high_limb() {
+        // the values share all the limbs except the most significant one
+        let left = U256::one();
+        let right = (U256::one() << 255) + U256::one();
+        let circuit = TestEqCircuit(TestOperationsCircuit { left, right });
+        let proof = run_circuit::<F, D, C, _>(circuit);
+        assert_eq!(F::ZERO, proof.public_inputs[0]);
+    }
+
     #[test]
     fn test_u256_is_less_than() {
         let rng = &mut thread_rng();
@@ -952,11 +2519,17 @@ mod tests {
         } else {
             assert_eq!(F::ZERO, proof.public_inputs[0]);
         }
+        // greater or equal is the negation of less than
+        assert_eq!(F::ONE - proof.public_inputs[0], proof.public_inputs[1]);
+        // less or equal is true iff left <= right
+        assert_eq!(F::from_bool(left <= right), proof.public_inputs[2]);
 
         // test left == right
         let circuit = TestLessThanCircuit(TestOperationsCircuit { left, right: left });
         let proof = run_circuit::<F, D, C, _>(circuit);
         assert_eq!(F::ZERO, proof.public_inputs[0]);
+        assert_eq!(F::ONE, proof.public_inputs[1]);
+        assert_eq!(F::ONE, proof.public_inputs[2]);
 
         // test zero is always less than any other non-zero item
         let circuit = TestLessThanCircuit(TestOperationsCircuit {
@@ -979,6 +2552,51 @@ mod tests {
         assert_eq!(F::ZERO, proof.public_inputs[0]);
     }
 
+    #[test]
+    fn test_u256_min_max() {
+        let rng = &mut thread_rng();
+        let random = gen_random_u256(rng);
+        for (left, right) in [
+            (random, gen_random_u256(rng)),
+            (gen_random_u256(rng), random),
+            (random, random),
+            (U256::zero(), U256::MAX),
+            (U256::MAX, U256::zero()),
+        ] {
+            let circuit = TestMinMaxCircuit(TestOperationsCircuit { left, right });
+            let proof = run_circuit::<F, D, C, _>(circuit);
+            let results = proof
+                .public_inputs
+                .chunks(NUM_LIMBS)
+                .map(convert_u32_fields_to_u256)
+                .collect_vec();
+            assert_eq!(results, vec![left.min(right), left.max(right)]);
+        }
+    }
+
+    #[test]
+    fn test_u256_select_array() {
+        let rng = &mut thread_rng();
+        let values = [0; NUM_SELECTABLE].map(|_| gen_random_u256(rng));
+        for index in 0..NUM_SELECTABLE {
+            let proof = run_circuit::<F, D, C, _>(TestSelectArrayCircuit { values, index });
+            assert_eq!(
+                convert_u32_fields_to_u256(&proof.public_inputs),
+                values[index],
+                "wrong value selected for index {index}"
+            );
+        }
+
+        // an out-of-range index is rejected
+        let res = std::panic::catch_unwind(|| {
+            run_circuit::<F, D, C, _>(TestSelectArrayCircuit {
+                values,
+                index: NUM_SELECTABLE,
+            })
+        });
+        assert!(res.is_err(), "out-of-range index not detected");
+    }
+
     #[test]
     fn test_u256_is_zero() {
         let rng = &mut thread_rng();
@@ -999,6 +2617,113 @@ mod tests {
         assert_eq!(F::ONE, proof.public_inputs[0]);
     }
 
+    #[derive(Clone, Debug)]
+    struct TestRangeCheckCircuit<const WITH_LOOKUP: bool>([u64; NUM_LIMBS]);
+
+    impl<const WITH_LOOKUP: bool> UserCircuit<F, D> for TestRangeCheckCircuit<WITH_LOOKUP> {
+        type Wires = UInt256Target;
+
+        fn build(c: &mut CircuitBuilder<F, D>) -> Self::Wires {
+            let target = if WITH_LOOKUP {
+                c.add_virtual_u256_with_lookup()
+            } else {
+                c.add_virtual_u256()
+            };
+            c.register_public_input_u256(&target);
+            target
+        }
+
+        fn prove(&self, pw: &mut PartialWitness<F>, wires: &Self::Wires) {
+            // limbs are assigned directly, to be able to provide out-of-range values
+            wires
+                .0
+                .iter()
+                .zip(self.0)
+                .for_each(|(t, v)| pw.set_target(t.0, F::from_canonical_u64(v)));
+        }
+    }
+
+    fn check_u256_range_check<const WITH_LOOKUP: bool>() {
+        let rng = &mut thread_rng();
+        let limbs = [0; NUM_LIMBS].map(|_| rng.gen::<u32>() as u64);
+        let proof = run_circuit::<F, D, C, _>(TestRangeCheckCircuit::<WITH_LOOKUP>(limbs));
+        assert_eq!(
+            proof.public_inputs,
+            limbs.map(F::from_canonical_u64).to_vec()
+        );
+        // boundary values are accepted
+        run_circuit::<F, D, C, _>(TestRangeCheckCircuit::<WITH_LOOKUP>(
+            [u32::MAX as u64; NUM_LIMBS],
+        ));
+        run_circuit::<F, D, C, _>(TestRangeCheckCircuit::<WITH_LOOKUP>([0; NUM_LIMBS]));
+
+        // a limb not fitting in 32 bits is rejected
+        let mut limbs = limbs;
+        limbs[NUM_LIMBS - 1] = 1 << 32;
+        let res = std::panic::catch_unwind(|| {
+            run_circuit::<F, D, C, _>(TestRangeCheckCircuit::<WITH_LOOKUP>(limbs))
+        });
+        assert!(res.is_err(), "out-of-range limb not detected");
+    }
+
+    #[test]
+    fn test_u256_range_check() {
+        check_u256_range_check::<false>();
+        check_u256_range_check::<true>();
+    }
+
+    /// Number of `UInt256Target`s allocated to compare the cost of range-checks
+    const NUM_RANGE_CHECKED_U256: usize = 1024;
+
+    #[test]
+    fn test_add_virtual_u256_gate_count() {
+        let config = CircuitConfig::standard_recursion_config();
+        // baseline: range-check each limb by decomposing it in bits, as in `add_virtual_u256`
+        let mut b = CircuitBuilder::<F, D>::new(config.clone());
+        for _ in 0..NUM_RANGE_CHECKED_U256 {
+            b.add_virtual_u256();
+        }
+        let bits_degree = b.build::<C>().common.degree_bits();
+
+        // lookup-based range-checks, as employed in `add_virtual_u256_with_lookup`
+        let mut b = CircuitBuilder::<F, D>::new(config);
+        for _ in 0..NUM_RANGE_CHECKED_U256 {
+            b.add_virtual_u256_with_lookup();
+        }
+        let lookup_degree = b.build::<C>().common.degree_bits();
+
+        // one bit-decomposition gate per limb yields 2^14 rows, while lookups need less than
+        // 2^12 rows, including the one-time cost of the 2^8-entry range-check table
+        assert!(
+            lookup_degree + 2 <= bits_degree,
+            "lookup range-checks degree 2^{lookup_degree} not smaller than bit-decomposition degree 2^{bits_degree}"
+        );
+    }
+
+    #[test]
+    fn test_try_get_u256_target() {
+        let mut b = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
+        let target = b.add_virtual_u256_unsafe();
+        let rng = &mut thread_rng();
+        let value = gen_random_u256(rng);
+        let mut pw = PartialWitness::<F>::new();
+        pw.set_u256_target(&target, value);
+        assert_eq!(pw.try_get_u256_target(&target).unwrap(), value);
+        assert_eq!(pw.get_u256_target(&target), value);
+
+        // overwrite a limb with a value not fitting in 32 bits
+        let mut pw = PartialWitness::<F>::new();
+        target.0.iter().enumerate().for_each(|(i, t)| {
+            let limb = if i == 5 { 1 << 32 } else { i as u64 };
+            pw.set_target(t.0, F::from_canonical_u64(limb))
+        });
+        let err = pw.try_get_u256_target(&target).unwrap_err();
+        assert!(
+            err.to_string().contains("limb 5"),
+            "error doesn't name the out-of-range limb: {err}"
+        );
+    }
+
     #[test]
     fn test_serialization_with_u256_div() {
         let mut b = CircuitBuilder::<F, D>::new(CircuitConfig::standard_recursion_config());
@@ -20,6 +20,7 @@ use sha3::Keccak256;
 
 use crate::u256::NUM_LIMBS;
 use crate::{
+    array::Array,
     group_hashing::{map_to_curve_point, CircuitBuilderGroupHashing, EXTENSION_DEGREE},
     types::{GFp, HashOutput},
     ProofTuple,
@@ -231,6 +232,43 @@ pub fn greater_than_or_equal_to<F: RichField + Extendable<D>, const D: usize>(
     less_than(builder, b, a_plus_1, n)
 }
 
+/// Enforce that the packed value is a canonical left-padded 32-byte encoding
+/// of a big-endian value of `byte_len` bytes, i.e., `left_pad32(big_endian(value))`.
+/// Each `u32` limb of `packed` is expected to pack 4 bytes in little-endian order,
+/// as done by `convert_u8_targets_to_u32`; this gadget constrains the first
+/// `32 - byte_len` bytes of the encoding to be zero. `byte_len` must be at most 32.
+pub fn assert_left_padded32<F: RichField + Extendable<D>, const D: usize>(
+    b: &mut CircuitBuilder<F, D>,
+    packed: &Array<Target, 8>,
+    byte_len: Target,
+) {
+    let zero = b.zero();
+    let tru = b._true();
+    let thirty_two = b.constant(F::from_canonical_u8(32));
+    // 6 bits are enough to represent any length in [0, 32]
+    let valid_len = less_than_or_equal_to(b, byte_len, thirty_two, 6);
+    b.connect(valid_len.target, tru.target);
+    let pad_len = b.sub(thirty_two, byte_len);
+
+    let bytes = packed
+        .arr
+        .iter()
+        .flat_map(|limb| {
+            let bits = b.split_le(*limb, 32);
+            bits.chunks(8)
+                .map(|byte_bits| bits_to_num(b, byte_bits))
+                .collect_vec()
+        })
+        .collect_vec();
+    for (i, byte) in bytes.into_iter().enumerate() {
+        let i = b.constant(F::from_canonical_usize(i));
+        // byte at position i must be zero if it belongs to the padding
+        let is_pad = less_than(b, i, pad_len, 6);
+        let padded_byte = b.mul(is_pad.target, byte);
+        b.connect(padded_byte, zero);
+    }
+}
+
 /// Resize the input vector if needed
 pub fn convert_u8_to_u32_slice(data: &[u8]) -> Vec<u32> {
     let mut d = data.to_vec();
@@ -394,13 +432,16 @@ impl<const N: usize> Packer for [u8; N] {
 
 #[cfg(test)]
 mod test {
-    use super::{bits_to_num, Packer, ToFields};
+    use super::{assert_left_padded32, bits_to_num, Packer, ToFields};
+    use crate::array::Array;
+    use crate::eth::left_pad32;
     use crate::utils::{
         convert_u8_to_u32_slice, greater_than, greater_than_or_equal_to, less_than,
         less_than_or_equal_to, num_to_bits,
     };
     use anyhow::Result;
     use ethers::types::Address;
+    use mrp2_test_utils::circuit::{run_circuit, UserCircuit};
     use plonky2::field::extension::Extendable;
     use plonky2::field::goldilocks_field::GoldilocksField;
     use plonky2::field::types::Field;
@@ -411,6 +452,7 @@ mod test {
     use plonky2::plonk::circuit_data::CircuitConfig;
     use plonky2::plonk::config::{GenericConfig, PoseidonGoldilocksConfig};
     use rand::{thread_rng, Rng, RngCore};
+    use std::panic;
 
     #[test]
     fn test_pack() {
@@ -433,6 +475,65 @@ mod test {
         // Check if the length of the u32 slice is correct
         assert_eq!(u32_slice.len(), (SIZE + (4 - (SIZE % 4))) / 4);
     }
+    #[test]
+    fn test_assert_left_padded32() {
+        const D: usize = 2;
+        type C = PoseidonGoldilocksConfig;
+        type F = <C as GenericConfig<D>>::F;
+
+        #[derive(Clone, Debug)]
+        struct TestLeftPadded32 {
+            encoding: [u8; 32],
+            byte_len: usize,
+        }
+
+        impl UserCircuit<F, D> for TestLeftPadded32 {
+            type Wires = (Array<Target, 8>, Target);
+
+            fn build(b: &mut CircuitBuilder<F, D>) -> Self::Wires {
+                let packed = Array::<Target, 8>::new(b);
+                let byte_len = b.add_virtual_target();
+                assert_left_padded32(b, &packed, byte_len);
+
+                (packed, byte_len)
+            }
+
+            fn prove(&self, pw: &mut PartialWitness<F>, wires: &Self::Wires) {
+                let packed: Vec<F> = self.encoding.pack().to_fields();
+                wires.0.assign(pw, &packed.try_into().unwrap());
+                pw.set_target(wires.1, F::from_canonical_usize(self.byte_len));
+            }
+        }
+
+        // 20-byte address left-padded to 32 bytes
+        let address = Address::random();
+        let encoding = left_pad32(address.as_bytes());
+        run_circuit::<F, D, C, _>(TestLeftPadded32 {
+            encoding,
+            byte_len: 20,
+        });
+
+        // a non-zero byte in the padding should fail
+        let mut dirty = encoding;
+        dirty[thread_rng().gen_range(0..12)] = thread_rng().gen_range(1..=u8::MAX);
+        let res = panic::catch_unwind(|| {
+            run_circuit::<F, D, C, _>(TestLeftPadded32 {
+                encoding: dirty,
+                byte_len: 20,
+            })
+        });
+        assert!(res.is_err(), "non-zero padding byte should fail");
+
+        // a byte length greater than 32 should fail
+        let res = panic::catch_unwind(|| {
+            run_circuit::<F, D, C, _>(TestLeftPadded32 {
+                encoding,
+                byte_len: 33,
+            })
+        });
+        assert!(res.is_err(), "byte length greater than 32 should fail");
+    }
+
     #[test]
     fn test_bits_to_num() -> Result<()> {
         const D: usize = 2;